@@ -48,6 +48,7 @@ fn main() {
             direction: -Vector3::z(),
             rayleigh_range: f64::INFINITY,
             ellipticity: 0.0,
+            focus_offset: 0.0,
         })
         .with(CoolingLight::for_transition::<Strontium88_461>(
             detuning,
@@ -63,6 +64,7 @@ fn main() {
             direction: Vector3::z(),
             rayleigh_range: f64::INFINITY,
             ellipticity: 0.0,
+            focus_offset: 0.0,
         })
         .with(CoolingLight::for_transition::<Strontium88_461>(
             detuning,
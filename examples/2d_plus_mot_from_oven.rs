@@ -58,6 +58,7 @@ fn main() {
             direction: Vector3::z(),
             rayleigh_range: f64::INFINITY,
             ellipticity: 0.0,
+            focus_offset: 0.0,
         })
         .with(CoolingLight::for_transition::<Strontium88_461>(
             push_beam_detuning,
@@ -78,6 +79,7 @@ fn main() {
             direction: Vector3::new(1.0, 1.0, 0.0).normalize(),
             rayleigh_range: f64::INFINITY,
             ellipticity: 0.0,
+            focus_offset: 0.0,
         })
         .with(CoolingLight::for_transition::<Strontium88_461>(
             detuning,
@@ -93,6 +95,7 @@ fn main() {
             direction: Vector3::new(1.0, -1.0, 0.0).normalize(),
             rayleigh_range: f64::INFINITY,
             ellipticity: 0.0,
+            focus_offset: 0.0,
         })
         .with(CoolingLight::for_transition::<Strontium88_461>(
             detuning,
@@ -108,6 +111,7 @@ fn main() {
             direction: Vector3::new(-1.0, 1.0, 0.0).normalize(),
             rayleigh_range: f64::INFINITY,
             ellipticity: 0.0,
+            focus_offset: 0.0,
         })
         .with(CoolingLight::for_transition::<Strontium88_461>(
             detuning,
@@ -123,6 +127,7 @@ fn main() {
             direction: Vector3::new(-1.0, -1.0, 0.0).normalize(),
             rayleigh_range: f64::INFINITY,
             ellipticity: 0.0,
+            focus_offset: 0.0,
         })
         .with(CoolingLight::for_transition::<Strontium88_461>(
             detuning,
@@ -91,6 +91,7 @@ fn main() {
             direction: Vector3::new(0.0, 0.0, 1.0),
             rayleigh_range: f64::INFINITY,
             ellipticity: 0.0,
+            focus_offset: 0.0,
         })
         .with(CoolingLight::for_transition::<Rubidium87_780D2>(
             detuning,
@@ -106,6 +107,7 @@ fn main() {
             direction: Vector3::new(0.0, 0.0, -1.0),
             rayleigh_range: f64::INFINITY,
             ellipticity: 0.0,
+            focus_offset: 0.0,
         })
         .with(CoolingLight::for_transition::<Rubidium87_780D2>(
             detuning,
@@ -121,6 +123,7 @@ fn main() {
             direction: Vector3::new(-1.0, 0.0, 0.0),
             rayleigh_range: f64::INFINITY,
             ellipticity: 0.0,
+            focus_offset: 0.0,
         })
         .with(CoolingLight::for_transition::<Rubidium87_780D2>(
             detuning,
@@ -136,6 +139,7 @@ fn main() {
             direction: Vector3::new(1.0, 0.0, 0.0),
             rayleigh_range: f64::INFINITY,
             ellipticity: 0.0,
+            focus_offset: 0.0,
         })
         .with(CoolingLight::for_transition::<Rubidium87_780D2>(
             detuning,
@@ -151,6 +155,7 @@ fn main() {
             direction: Vector3::new(0.0, 1.0, 0.0),
             rayleigh_range: f64::INFINITY,
             ellipticity: 0.0,
+            focus_offset: 0.0,
         })
         .with(CoolingLight::for_transition::<Rubidium87_780D2>(
             detuning,
@@ -166,6 +171,7 @@ fn main() {
             direction: Vector3::new(0.0, -1.0, 0.0),
             rayleigh_range: f64::INFINITY,
             ellipticity: 0.0,
+            focus_offset: 0.0,
         })
         .with(CoolingLight::for_transition::<Rubidium87_780D2>(
             detuning,
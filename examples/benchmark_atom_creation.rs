@@ -0,0 +1,62 @@
+//! Benchmark of atom creation, comparing the individual and batched creation of 100k atoms in one step.
+
+extern crate atomecs as lib;
+extern crate nalgebra;
+use lib::atom::{Atom, Force, InitialVelocity, Mass, Position, Velocity};
+use lib::atom_sources::batch::AtomBatch;
+use lib::initiate::NewlyCreated;
+use lib::species::{Rubidium87, Rubidium87_780D2};
+use nalgebra::Vector3;
+use specs::prelude::*;
+use std::time::Instant;
+
+const N_ATOMS: usize = 100_000;
+
+fn create_world() -> World {
+    let mut world = World::new();
+    world.register::<Position>();
+    world.register::<Velocity>();
+    world.register::<InitialVelocity>();
+    world.register::<Force>();
+    world.register::<Mass>();
+    world.register::<Atom>();
+    world.register::<NewlyCreated>();
+    world.register::<Rubidium87_780D2>();
+    world
+}
+
+fn create_atoms(batched: bool) -> u128 {
+    let mut world = create_world();
+    let mut batch = AtomBatch::new();
+    for i in 0..N_ATOMS {
+        batch.push(
+            Vector3::new(i as f64 * 1.0e-9, 0.0, 0.0),
+            Vector3::new(0.0, 0.0, 1.0),
+            87.0,
+        );
+    }
+
+    let now = Instant::now();
+    batch.create::<Rubidium87>(
+        &world.entities(),
+        &world.read_resource::<LazyUpdate>(),
+        batched,
+    );
+    world.maintain();
+    let elapsed = now.elapsed().as_millis();
+    assert_eq!(world.read_storage::<Atom>().count(), N_ATOMS);
+    elapsed
+}
+
+fn main() {
+    println!(
+        "Individual: created {} atoms in {} ms",
+        N_ATOMS,
+        create_atoms(false)
+    );
+    println!(
+        "Batched: created {} atoms in {} ms",
+        N_ATOMS,
+        create_atoms(true)
+    );
+}
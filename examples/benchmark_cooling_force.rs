@@ -0,0 +1,150 @@
+//! Benchmark of the absorption force calculation, comparing the cached wavevectors of the cooling
+//! beams against recalculating the wavevector of each beam for every atom.
+
+extern crate atomecs as lib;
+extern crate nalgebra;
+use lib::atom::Force;
+use lib::integrator::Timestep;
+use lib::laser::gaussian::{calculate_rayleigh_range, GaussianBeam};
+use lib::laser::index::LaserIndex;
+use lib::laser::DEFAULT_BEAM_LIMIT;
+use lib::laser_cooling::force::CalculateAbsorptionForcesSystem;
+use lib::laser_cooling::photons_scattered::{ActualPhotonsScattered, ActualPhotonsScatteredVector};
+use lib::laser_cooling::repump::Dark;
+use lib::laser_cooling::scattering::force_per_beam;
+use lib::laser_cooling::transition::AtomicTransition;
+use lib::laser_cooling::wavevector::CacheCoolingWavevectorsSystem;
+use lib::laser_cooling::CoolingLight;
+use lib::species::Rubidium87_780D2;
+use nalgebra::Vector3;
+use specs::prelude::*;
+use std::time::Instant;
+
+const N_ATOMS: usize = 100_000;
+const N_STEPS: usize = 100;
+
+type Scattered = ActualPhotonsScatteredVector<Rubidium87_780D2, { DEFAULT_BEAM_LIMIT }>;
+
+/// Calculates the absorption forces, recomputing the wavevector of each beam for every atom.
+struct UncachedAbsorptionForcesSystem;
+impl<'a> System<'a> for UncachedAbsorptionForcesSystem {
+    type SystemData = (
+        ReadStorage<'a, LaserIndex>,
+        ReadStorage<'a, CoolingLight>,
+        ReadStorage<'a, GaussianBeam>,
+        ReadStorage<'a, Scattered>,
+        WriteStorage<'a, Force>,
+        ReadExpect<'a, Timestep>,
+    );
+
+    fn run(
+        &mut self,
+        (indices, cooling, gaussian, scattered, mut forces, timestep): Self::SystemData,
+    ) {
+        use rayon::prelude::*;
+
+        let lasers: Vec<(CoolingLight, LaserIndex, GaussianBeam)> = (&cooling, &indices, &gaussian)
+            .join()
+            .map(|(cooling, index, gaussian)| (*cooling, *index, *gaussian))
+            .collect();
+        (&scattered, &mut forces)
+            .par_join()
+            .for_each(|(scattered, force)| {
+                for (cooling, index, gaussian) in lasers.iter() {
+                    let new_force = force_per_beam(
+                        scattered.contents[index.index].scattered / timestep.delta,
+                        gaussian.direction.normalize() * cooling.wavenumber(),
+                    );
+                    force.force += new_force.cast();
+                }
+            });
+    }
+}
+
+fn create_world() -> World {
+    let mut world = World::new();
+    world.register::<LaserIndex>();
+    world.register::<CoolingLight>();
+    world.register::<GaussianBeam>();
+    world.register::<Scattered>();
+    world.register::<Force>();
+    world.register::<Dark>();
+    world.insert(Timestep { delta: 1.0e-6 });
+
+    let wavelength = Rubidium87_780D2::wavelength();
+    let directions = [
+        Vector3::x(),
+        -Vector3::x(),
+        Vector3::y(),
+        -Vector3::y(),
+        Vector3::z(),
+        -Vector3::z(),
+    ];
+    for (i, direction) in directions.iter().enumerate() {
+        world
+            .create_entity()
+            .with(CoolingLight {
+                polarization: 1,
+                wavelength,
+            })
+            .with(LaserIndex {
+                index: i,
+                initiated: true,
+            })
+            .with(GaussianBeam {
+                direction: *direction,
+                intersection: Vector3::new(0.0, 0.0, 0.0),
+                e_radius: 0.01,
+                power: 0.01,
+                rayleigh_range: calculate_rayleigh_range(&wavelength, &0.01),
+                ellipticity: 0.0,
+                focus_offset: 0.0,
+            })
+            .build();
+    }
+
+    let mut aps = ActualPhotonsScattered::<Rubidium87_780D2>::default();
+    aps.scattered = 1.0;
+    for _ in 0..N_ATOMS {
+        world
+            .create_entity()
+            .with(ActualPhotonsScatteredVector {
+                contents: [aps; DEFAULT_BEAM_LIMIT],
+            })
+            .with(Force::new())
+            .build();
+    }
+    world
+}
+
+fn main() {
+    let world = create_world();
+    let mut uncached = UncachedAbsorptionForcesSystem;
+    let now = Instant::now();
+    for _ in 0..N_STEPS {
+        uncached.run_now(&world);
+    }
+    println!(
+        "Uncached: {} atoms, {} steps in {} ms",
+        N_ATOMS,
+        N_STEPS,
+        now.elapsed().as_millis()
+    );
+
+    let mut world = create_world();
+    let mut cache = CacheCoolingWavevectorsSystem;
+    System::setup(&mut cache, &mut world);
+    let mut cached =
+        CalculateAbsorptionForcesSystem::<Rubidium87_780D2, { DEFAULT_BEAM_LIMIT }>::default();
+    let now = Instant::now();
+    for _ in 0..N_STEPS {
+        cache.run_now(&world);
+        cached.run_now(&world);
+    }
+    println!(
+        "Cached: {} atoms, {} steps in {} ms",
+        N_ATOMS,
+        N_STEPS,
+        now.elapsed().as_millis()
+    );
+}
@@ -46,6 +46,7 @@ fn main() {
             direction: Vector3::x(),
             rayleigh_range: f64::INFINITY,
             ellipticity: 0.0,
+            focus_offset: 0.0,
         })
         .with(CoolingLight::for_transition::<Rubidium87_780D2>(
             detuning,
@@ -40,6 +40,7 @@ fn main() {
         direction: Vector3::x(),
         rayleigh_range: crate::laser::gaussian::calculate_rayleigh_range(&wavelength, &e_radius),
         ellipticity: 0.0,
+        focus_offset: 0.0,
     };
     sim.world
         .create_entity()
@@ -58,6 +59,7 @@ fn main() {
         direction: Vector3::y(),
         rayleigh_range: crate::laser::gaussian::calculate_rayleigh_range(&wavelength, &e_radius),
         ellipticity: 0.0,
+        focus_offset: 0.0,
     };
     sim.world
         .create_entity()
@@ -53,6 +53,7 @@ fn main() {
             direction: -Vector3::z(),
             rayleigh_range: f64::INFINITY,
             ellipticity: 0.0,
+            focus_offset: 0.0,
         })
         .with(CoolingLight::for_transition::<Rubidium87_780D2>(
             -6.0,
@@ -68,6 +69,7 @@ fn main() {
             direction: Vector3::z(),
             rayleigh_range: f64::INFINITY,
             ellipticity: 0.0,
+            focus_offset: 0.0,
         })
         .with(CoolingLight::for_transition::<Rubidium87_780D2>(
             -6.0,
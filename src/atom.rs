@@ -4,6 +4,7 @@ use crate::initiate::DeflagNewAtomsSystem;
 use crate::integrator::AddOldForceToNewAtomsSystem;
 use crate::output::file::BinaryConversion;
 use crate::output::file::XYZPosition;
+use crate::parallel::{ForceSerial, MaybeParJoin};
 use crate::ramp::Lerp;
 use crate::simulation::Plugin;
 use nalgebra::Vector3;
@@ -13,13 +14,36 @@ use serde::{Deserialize, Serialize};
 use specs::{Component, NullStorage, System, VecStorage, World, WriteStorage};
 use std::fmt;
 
+/// The floating point type used by the core kinematic components: [Position], [Velocity] and [Force].
+///
+/// This is `f64` by default. Enabling the `f32` feature builds these components in single precision,
+/// which halves their memory footprint from 72 to 36 bytes per atom, and the memory bandwidth
+/// required to integrate them. This can give a higher throughput for runs with tens of millions of atoms,
+/// which are typically memory-bound. Intermediate physics, such as laser intensities and magnetic fields,
+/// is still calculated in `f64` and converted when added to the force.
+#[cfg(not(feature = "f32"))]
+pub type Scalar = f64;
+/// The floating point type used by the core kinematic components: [Position], [Velocity] and [Force].
+///
+/// The `f32` feature is enabled, so these components are built in single precision.
+#[cfg(feature = "f32")]
+pub type Scalar = f32;
+
 /// Position of an entity in space, with respect to cartesian x,y,z axes.
 ///
 /// SI units (metres)
-#[derive(Deserialize, Serialize, Clone, Lerp)]
+#[derive(Deserialize, Serialize, Clone)]
 pub struct Position {
     /// position in 3D in units of m
-    pub pos: Vector3<f64>,
+    pub pos: Vector3<Scalar>,
+}
+
+impl Lerp<Position> for Position {
+    fn lerp(&self, b: &Position, amount: f64) -> Self {
+        Position {
+            pos: self.pos - (self.pos - b.pos) * amount as Scalar,
+        }
+    }
 }
 
 impl Default for Position {
@@ -47,12 +71,15 @@ impl fmt::Display for Position {
 }
 impl BinaryConversion for Position {
     fn data(&self) -> Vec<f64> {
-        vec![self.pos[0], self.pos[1], self.pos[2]]
+        self.pos.cast::<f64>().iter().copied().collect()
+    }
+    fn field_names() -> Vec<&'static str> {
+        vec!["x", "y", "z"]
     }
 }
 impl XYZPosition for Position {
     fn pos(&self) -> Vector3<f64> {
-        self.pos
+        self.pos.cast()
     }
 }
 
@@ -62,7 +89,7 @@ impl XYZPosition for Position {
 #[derive(Clone, Copy, Serialize)]
 pub struct Velocity {
     /// velocity vector in 3D in units of m/s
-    pub vel: Vector3<f64>,
+    pub vel: Vector3<Scalar>,
 }
 impl fmt::Display for Velocity {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -71,7 +98,10 @@ impl fmt::Display for Velocity {
 }
 impl BinaryConversion for Velocity {
     fn data(&self) -> Vec<f64> {
-        vec![self.vel[0], self.vel[1], self.vel[2]]
+        self.vel.cast::<f64>().iter().copied().collect()
+    }
+    fn field_names() -> Vec<&'static str> {
+        vec!["vx", "vy", "vz"]
     }
 }
 
@@ -96,7 +126,7 @@ impl Component for InitialVelocity {
 #[derive(Copy, Clone, Serialize)]
 pub struct Force {
     /// force vector in 3D in units of N
-    pub force: Vector3<f64>,
+    pub force: Vector3<Scalar>,
 }
 impl Component for Force {
     type Storage = VecStorage<Self>;
@@ -117,7 +147,8 @@ impl Force {
 
 /// Inertial and Gravitational mass of an entity
 ///
-/// Mass is specified in atom mass units (amu).
+/// Mass is specified in atom mass units (amu). The gravitational mass can be set separately with a
+/// [GravitationalMass].
 #[derive(Deserialize, Serialize, Clone)]
 pub struct Mass {
     /// mass value in atom mass units
@@ -128,6 +159,21 @@ impl Component for Mass {
     type Storage = VecStorage<Self>;
 }
 
+/// Gravitational mass of an entity, when it differs from the inertial [Mass].
+///
+/// Used to test the equivalence principle, for example in simulations of atom interferometers. The force of gravity
+/// on an entity with a [GravitationalMass] uses this mass, while its acceleration under every force uses the
+/// inertial [Mass]. Mass is specified in atom mass units (amu).
+#[derive(Deserialize, Serialize, Clone, Copy)]
+pub struct GravitationalMass {
+    /// mass value in atom mass units
+    pub value: f64,
+}
+
+impl Component for GravitationalMass {
+    type Storage = VecStorage<Self>;
+}
+
 /// Component that marks an entity as an [atom](struct.Atom.html).
 /// This provides a simple way for systems to get only [atom](struct.Atom.html)s, even though non-atom entities may also share components, eg [position](struct.Position.html).
 #[derive(Default)]
@@ -137,15 +183,86 @@ impl Component for Atom {
     type Storage = NullStorage<Self>;
 }
 
+/// The statistical weight of an atom, used when atoms are created by importance sampling.
+///
+/// Each simulated atom represents `value` real atoms, so that diagnostics computed as weighted averages over the
+/// simulated atoms remain unbiased. Atoms without this component have a weight of 1.
+/// See [crate::atom_sources::vapor::VelocityImportanceSampling].
+#[derive(Deserialize, Serialize, Clone, Copy, Debug)]
+pub struct StatisticalWeight {
+    pub value: f64,
+}
+
+impl Component for StatisticalWeight {
+    type Storage = VecStorage<Self>;
+}
+
+impl Default for StatisticalWeight {
+    fn default() -> Self {
+        StatisticalWeight { value: 1.0 }
+    }
+}
+
+/// The number of real atoms represented by a coarse-grained simulated 'super-atom'.
+///
+/// Forces and the motion of the super-atom are those of a single real atom, but densities, collision rates and
+/// statistics count the super-atom `n_real` times, so that a few thousand super-atoms can model a cloud of millions
+/// of atoms. Atoms without this component represent a single real atom. See [crate::collisions].
+#[derive(Deserialize, Serialize, Clone, Copy, Debug)]
+pub struct SuperAtomWeight {
+    pub n_real: f64,
+}
+
+impl Component for SuperAtomWeight {
+    type Storage = VecStorage<Self>;
+}
+
+impl Default for SuperAtomWeight {
+    fn default() -> Self {
+        SuperAtomWeight { n_real: 1.0 }
+    }
+}
+
+/// The total weight of an atom in statistics, the product of its [StatisticalWeight] and [SuperAtomWeight].
+pub fn total_weight(
+    statistical: Option<&StatisticalWeight>,
+    super_atom: Option<&SuperAtomWeight>,
+) -> f64 {
+    statistical.map_or(1.0, |weight| weight.value) * super_atom.map_or(1.0, |weight| weight.n_real)
+}
+
+/// The number of real atoms represented by the simulated atoms, accounting for their [StatisticalWeight] and
+/// [SuperAtomWeight].
+pub fn weighted_atom_number(world: &World) -> f64 {
+    let atoms = world.read_storage::<Atom>();
+    let weights = world.read_storage::<StatisticalWeight>();
+    let super_atoms = world.read_storage::<SuperAtomWeight>();
+    (&atoms, weights.maybe(), super_atoms.maybe())
+        .join()
+        .map(|(_, weight, super_atom)| total_weight(weight, super_atom))
+        .sum()
+}
+
+/// A user-assigned identifier of an atom.
+///
+/// Unlike the [Entity](specs::Entity), the identifier does not depend on the order in which atoms are created
+/// and destroyed, so it can be used to match atoms between runs, see [crate::replay].
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct AtomId {
+    pub id: u64,
+}
+
+impl Component for AtomId {
+    type Storage = VecStorage<Self>;
+}
+
 /// A system that sets force to zero at the start of each simulation step.
 pub struct ClearForceSystem;
 
 impl<'a> System<'a> for ClearForceSystem {
-    type SystemData = WriteStorage<'a, Force>;
-    fn run(&mut self, mut force: Self::SystemData) {
-        use rayon::prelude::*;
-
-        (&mut force).par_join().for_each(|force| {
+    type SystemData = (WriteStorage<'a, Force>, Option<Read<'a, ForceSerial>>);
+    fn run(&mut self, (mut force, force_serial): Self::SystemData) {
+        (&mut force).maybe_par_for_each(force_serial.is_some(), |force| {
             force.force = Vector3::new(0.0, 0.0, 0.0);
         });
     }
@@ -169,8 +286,12 @@ impl Plugin for AtomPlugin {
 fn register_components(world: &mut World) {
     world.register::<Position>();
     world.register::<Mass>();
+    world.register::<GravitationalMass>();
     world.register::<Force>();
     world.register::<Atom>();
+    world.register::<AtomId>();
+    world.register::<StatisticalWeight>();
+    world.register::<SuperAtomWeight>();
     world.register::<InitialVelocity>();
     world.register::<Velocity>();
 }
@@ -0,0 +1,224 @@
+//! Batched creation of atoms.
+//!
+//! Atom sources collect the atoms they emit each step into an [AtomBatch]. By default, the components of each
+//! new atom are inserted one at a time using the `LazyUpdate`. When the [BatchAtomCreationOption] resource is
+//! present, a block of entities is instead reserved at once and each component type is inserted for the whole
+//! block in a single update, which greatly reduces the overhead when creating thousands of atoms per step.
+//!
+//! In both cases the atoms are created when the world is next maintained, and are marked as [NewlyCreated].
+
+use super::species::AtomCreator;
+use crate::atom::*;
+use crate::initiate::NewlyCreated;
+use nalgebra::Vector3;
+use specs::prelude::*;
+use specs::world::EntitiesRes;
+
+/// A resource that indicates that atom sources should create their atoms in batches.
+///
+/// See [crate::atom_sources::batch].
+#[derive(Clone, Copy, Default)]
+pub struct BatchAtomCreationOption;
+
+/// The properties of an atom to be created by an [AtomBatch].
+struct AtomToCreate {
+    position: Vector3<f64>,
+    velocity: Vector3<f64>,
+    mass: f64,
+}
+
+/// A collection of atoms to be created.
+#[derive(Default)]
+pub struct AtomBatch {
+    atoms: Vec<AtomToCreate>,
+}
+impl AtomBatch {
+    pub fn new() -> Self {
+        AtomBatch::default()
+    }
+
+    /// Adds an atom to the batch.
+    ///
+    /// # Arguments
+    ///
+    /// `position`: position of the atom, in SI units of m.
+    ///
+    /// `velocity`: velocity of the atom, in SI units of m/s.
+    ///
+    /// `mass`: mass of the atom, in atomic mass units.
+    pub fn push(&mut self, position: Vector3<f64>, velocity: Vector3<f64>, mass: f64) {
+        self.atoms.push(AtomToCreate {
+            position,
+            velocity,
+            mass,
+        });
+    }
+
+    /// Number of atoms in the batch.
+    pub fn len(&self) -> usize {
+        self.atoms.len()
+    }
+
+    /// Returns true if the batch contains no atoms.
+    pub fn is_empty(&self) -> bool {
+        self.atoms.is_empty()
+    }
+
+    /// Creates the atoms of species `T`, which are added to the world when it is next maintained.
+    ///
+    /// Returns the entities of the new atoms.
+    ///
+    /// # Arguments
+    ///
+    /// `batched`: if true, the components are inserted in bulk. See [crate::atom_sources::batch].
+    pub fn create<T>(
+        self,
+        entities: &EntitiesRes,
+        updater: &LazyUpdate,
+        batched: bool,
+    ) -> Vec<Entity>
+    where
+        T: AtomCreator + 'static,
+    {
+        if batched {
+            self.create_batched::<T>(entities, updater)
+        } else {
+            self.create_individually::<T>(entities, updater)
+        }
+    }
+
+    fn create_individually<T>(self, entities: &EntitiesRes, updater: &LazyUpdate) -> Vec<Entity>
+    where
+        T: AtomCreator + 'static,
+    {
+        let mut new_atoms = Vec::with_capacity(self.atoms.len());
+        for atom in self.atoms {
+            let new_atom = entities.create();
+            updater.insert(
+                new_atom,
+                Position {
+                    pos: atom.position.cast(),
+                },
+            );
+            updater.insert(
+                new_atom,
+                Velocity {
+                    vel: atom.velocity.cast(),
+                },
+            );
+            updater.insert(new_atom, Force::new());
+            updater.insert(new_atom, Mass { value: atom.mass });
+            updater.insert(new_atom, Atom);
+            updater.insert(new_atom, InitialVelocity { vel: atom.velocity });
+            updater.insert(new_atom, NewlyCreated);
+            T::mutate(updater, new_atom);
+            new_atoms.push(new_atom);
+        }
+        new_atoms
+    }
+
+    fn create_batched<T>(self, entities: &EntitiesRes, updater: &LazyUpdate) -> Vec<Entity>
+    where
+        T: AtomCreator + 'static,
+    {
+        let new_atoms: Vec<Entity> = entities.create_iter().take(self.atoms.len()).collect();
+
+        let mut positions = Vec::with_capacity(self.atoms.len());
+        let mut velocities = Vec::with_capacity(self.atoms.len());
+        let mut masses = Vec::with_capacity(self.atoms.len());
+        let mut initial_velocities = Vec::with_capacity(self.atoms.len());
+        for (atom, entity) in self.atoms.into_iter().zip(new_atoms.iter()) {
+            positions.push((
+                *entity,
+                Position {
+                    pos: atom.position.cast(),
+                },
+            ));
+            velocities.push((
+                *entity,
+                Velocity {
+                    vel: atom.velocity.cast(),
+                },
+            ));
+            masses.push((*entity, Mass { value: atom.mass }));
+            initial_velocities.push((*entity, InitialVelocity { vel: atom.velocity }));
+        }
+
+        updater.insert_all(positions);
+        updater.insert_all(velocities);
+        updater.insert_all(masses);
+        updater.insert_all(initial_velocities);
+        updater.insert_all(new_atoms.clone().into_iter().map(|e| (e, Force::new())));
+        updater.insert_all(new_atoms.clone().into_iter().map(|e| (e, Atom)));
+        updater.insert_all(new_atoms.clone().into_iter().map(|e| (e, NewlyCreated)));
+        T::mutate_all(updater, &new_atoms);
+        new_atoms
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use crate::laser::intensity::{LaserIntensitySamplers, TotalIntensity};
+    use crate::laser::intensity_gradient::LaserIntensityGradientSamplers;
+    use crate::laser::sampler::CoolingLaserSamplerMasks;
+    use crate::laser::{AttachLaserComponentsToNewlyCreatedAtomsSystem, DEFAULT_BEAM_LIMIT};
+    use crate::species::{Rubidium87, Rubidium87_780D2};
+
+    #[test]
+    fn test_batched_atoms_have_all_components() {
+        let mut test_world = World::new();
+        test_world.register::<Position>();
+        test_world.register::<Velocity>();
+        test_world.register::<InitialVelocity>();
+        test_world.register::<Force>();
+        test_world.register::<Mass>();
+        test_world.register::<Atom>();
+        test_world.register::<NewlyCreated>();
+        test_world.register::<Rubidium87_780D2>();
+        test_world.register::<CoolingLaserSamplerMasks<{ DEFAULT_BEAM_LIMIT }>>();
+        test_world.register::<LaserIntensitySamplers<{ DEFAULT_BEAM_LIMIT }>>();
+        test_world.register::<TotalIntensity>();
+        test_world.register::<LaserIntensityGradientSamplers<{ DEFAULT_BEAM_LIMIT }>>();
+
+        let number = 1000;
+        let mut batch = AtomBatch::new();
+        for i in 0..number {
+            batch.push(
+                Vector3::new(i as f64, 0.0, 0.0),
+                Vector3::new(0.0, i as f64, 0.0),
+                87.0,
+            );
+        }
+        let new_atoms = batch.create::<Rubidium87>(
+            &test_world.entities(),
+            &test_world.read_resource::<LazyUpdate>(),
+            true,
+        );
+        test_world.maintain();
+
+        AttachLaserComponentsToNewlyCreatedAtomsSystem::<{ DEFAULT_BEAM_LIMIT }>
+            .run_now(&test_world);
+        test_world.maintain();
+
+        assert_eq!(new_atoms.len(), number);
+        let positions = test_world.read_storage::<Position>();
+        let velocities = test_world.read_storage::<Velocity>();
+        let masks = test_world.read_storage::<CoolingLaserSamplerMasks<{ DEFAULT_BEAM_LIMIT }>>();
+        for (i, atom) in new_atoms.iter().enumerate() {
+            assert!(test_world.is_alive(*atom));
+            assert_eq!(positions.get(*atom).unwrap().pos[0], i as Scalar);
+            assert_eq!(velocities.get(*atom).unwrap().vel[1], i as Scalar);
+            assert!(test_world.read_storage::<Force>().contains(*atom));
+            assert!(test_world.read_storage::<Mass>().contains(*atom));
+            assert!(test_world.read_storage::<Atom>().contains(*atom));
+            assert!(test_world.read_storage::<InitialVelocity>().contains(*atom));
+            assert!(test_world.read_storage::<NewlyCreated>().contains(*atom));
+            assert!(test_world
+                .read_storage::<Rubidium87_780D2>()
+                .contains(*atom));
+            assert!(masks.contains(*atom));
+        }
+    }
+}
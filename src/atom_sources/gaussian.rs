@@ -2,11 +2,11 @@
 
 use std::marker::PhantomData;
 
+use super::batch::{AtomBatch, BatchAtomCreationOption};
 use super::{WeightedProbabilityDistribution, species::AtomCreator};
 use crate::atom::*;
 use crate::atom_sources::emit::AtomNumberToEmit;
 use crate::constant::EXP;
-use crate::initiate::*;
 use nalgebra::Vector3;
 
 use rand;
@@ -125,14 +125,16 @@ impl<'a, T> System<'a> for GaussianCreateAtomsSystem<T> where T : AtomCreator +
         ReadStorage<'a, AtomNumberToEmit>,
         ReadStorage<'a, Position>,
         ReadStorage<'a, Mass>,
+        Option<Read<'a, BatchAtomCreationOption>>,
         Read<'a, LazyUpdate>,
     );
 
     fn run(
         &mut self,
-        (entities, sources, numbers_to_emits, positions, masses, updater): Self::SystemData,
+        (entities, sources, numbers_to_emits, positions, masses, batching, updater): Self::SystemData,
     ) {
         let mut rng = rand::thread_rng();
+        let mut batch = AtomBatch::new();
         for (source, number_to_emit, source_position, mass) in (
             &sources,
             &numbers_to_emits,
@@ -142,22 +144,10 @@ impl<'a, T> System<'a> for GaussianCreateAtomsSystem<T> where T : AtomCreator +
             .join()
         {
             for _i in 0..number_to_emit.number {
-                let new_atom = entities.create();
                 let new_vel = source.get_random_velocity(&mut rng);
-                updater.insert(
-                    new_atom,
-                    Velocity {
-                        vel: new_vel,
-                    },
-                );
-                updater.insert(new_atom, source_position.clone());
-                updater.insert(new_atom, Force::new());
-                updater.insert(new_atom, mass.clone());
-                updater.insert(new_atom, Atom);
-                updater.insert(new_atom, InitialVelocity { vel: new_vel });
-                updater.insert(new_atom, NewlyCreated);
-                T::mutate(&updater, new_atom);
+                batch.push(source_position.pos.cast(), new_vel, mass.value);
             }
         }
+        batch.create::<T>(&entities, &updater, batching.is_some());
     }
 }
@@ -1,5 +1,6 @@
 //! Creation of atoms in a controlled manner and realease into the simulation
 
+pub mod batch;
 pub mod emit;
 pub mod gaussian;
 pub mod mass;
@@ -7,6 +8,8 @@ pub mod oven;
 pub mod precalc;
 pub mod surface;
 pub mod species;
+pub mod thermal;
+pub mod vapor;
 
 use specs::prelude::*;
 
@@ -16,6 +19,7 @@ use rand::distributions::WeightedIndex;
 use rand::Rng;
 use std::marker::PhantomData;
 
+use crate::dispatch::SystemDispatcherBuilder;
 use crate::simulation::Plugin;
 
 use self::species::AtomCreator;
@@ -52,7 +56,7 @@ impl<T> Plugin for AtomSourcePlugin<T> where T : AtomCreator + 'static {
 ///
 /// `deps`: any dependencies that must be completed before the atom_sources systems run.
 fn add_systems_to_dispatch<T>(
-    builder: &mut DispatcherBuilder<'static, 'static>,
+    builder: &mut impl SystemDispatcherBuilder,
     deps: &[&str],
 ) where T : AtomCreator + 'static {
     builder.add(
@@ -99,6 +103,11 @@ fn add_systems_to_dispatch<T>(
         "gaussian_create_atoms",
         &["emit_number_per_frame", "precalculate_gaussian"],
     );
+    builder.add(
+        vapor::VaporCreateAtomsSystem::<T>::default(),
+        "vapor_create_atoms",
+        deps,
+    );
     builder.add(
         emit::EmitOnceSystem,
         "emit_once_system",
@@ -121,6 +130,7 @@ fn register_components<T>(world: &mut World) where T : AtomCreator + 'static {
     world.register::<surface::SurfaceSource<T>>();
     world.register::<gaussian::GaussianVelocityDistributionSource<T>>();
     world.register::<gaussian::GaussianVelocityDistributionSourceDefinition<T>>();
+    world.register::<vapor::VaporBackgroundSource<T>>();
 }
 
 /// A simple probability distribution which uses weighted indices to retrieve values.
@@ -6,10 +6,10 @@ use std::marker::PhantomData;
 
 use super::emit::AtomNumberToEmit;
 use super::precalc::{MaxwellBoltzmannSource, PrecalculatedSpeciesInformation};
+use super::batch::{AtomBatch, BatchAtomCreationOption};
 use super::species::{AtomCreator};
 use crate::constant;
 use crate::constant::PI;
-use crate::initiate::*;
 
 use super::VelocityCap;
 use super::WeightedProbabilityDistribution;
@@ -187,12 +187,13 @@ impl<'a, T> System<'a> for OvenCreateAtomsSystem<T> where T : AtomCreator + 'sta
         ReadStorage<'a, Position>,
         ReadStorage<'a, PrecalculatedSpeciesInformation>,
         Option<Read<'a, VelocityCap>>,
+        Option<Read<'a, BatchAtomCreationOption>>,
         Read<'a, LazyUpdate>,
     );
 
     fn run(
         &mut self,
-        (entities, oven, numbers_to_emit, pos, precalcs, velocity_cap, updater): Self::SystemData,
+        (entities, oven, numbers_to_emit, pos, precalcs, velocity_cap, batching, updater): Self::SystemData,
     ) {
         let max_vel = match velocity_cap {
             Some(cap) => cap.value,
@@ -200,6 +201,7 @@ impl<'a, T> System<'a> for OvenCreateAtomsSystem<T> where T : AtomCreator + 'sta
         };
 
         let mut rng = rand::thread_rng();
+        let mut batch = AtomBatch::new();
         for (oven, number_to_emit, oven_position, precalcs) in
             (&oven, &numbers_to_emit, &pos, &precalcs).join()
         {
@@ -215,28 +217,12 @@ impl<'a, T> System<'a> for OvenCreateAtomsSystem<T> where T : AtomCreator + 'sta
                 if theta > oven.max_theta {
                     continue;
                 }
-                let new_atom = entities.create();
-                let start_position = oven_position.pos + oven.get_random_spawn_position();
-                updater.insert(
-                    new_atom,
-                    Position {
-                        pos: start_position,
-                    },
-                );
-                updater.insert(
-                    new_atom,
-                    Velocity {
-                        vel: new_vel,
-                    },
-                );
-                updater.insert(new_atom, Force::new());
-                updater.insert(new_atom, Mass { value: mass });
-                updater.insert(new_atom, Atom);
-                updater.insert(new_atom, InitialVelocity { vel: new_vel });
-                updater.insert(new_atom, NewlyCreated);
-                T::mutate(&updater, new_atom);
+                let start_position =
+                    oven_position.pos.cast::<f64>() + oven.get_random_spawn_position();
+                batch.push(start_position, new_vel, mass);
             }
         }
+        batch.create::<T>(&entities, &updater, batching.is_some());
     }
 }
 
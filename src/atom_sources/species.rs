@@ -6,6 +6,13 @@ use specs::prelude::*;
 pub trait AtomCreationModifier {
     /// Modifies the created atom
     fn mutate(updater: &LazyUpdate, new_atom: Entity);
+
+    /// Modifies a batch of created atoms, see [crate::atom_sources::batch].
+    fn mutate_all(updater: &LazyUpdate, new_atoms: &[Entity]) {
+        for new_atom in new_atoms {
+            Self::mutate(updater, *new_atom);
+        }
+    }
 }
 pub trait AtomCreator : AtomCreationModifier + Copy + Send + Sync + Default {}
 impl<T> AtomCreator for T where T : AtomCreationModifier + Copy + Send + Sync + Default {}
@@ -32,6 +39,14 @@ macro_rules! species {
             fn mutate(updater: &specs::LazyUpdate, new_atom: specs::Entity) {
                 updater.insert(new_atom, $transition::default());
             }
+            fn mutate_all(updater: &specs::LazyUpdate, new_atoms: &[specs::Entity]) {
+                updater.insert_all(
+                    new_atoms
+                        .to_vec()
+                        .into_iter()
+                        .map(|atom| (atom, $transition::default())),
+                );
+            }
         }
     };
 }
@@ -7,13 +7,13 @@ use nalgebra::Vector3;
 
 use super::emit::AtomNumberToEmit;
 use super::VelocityCap;
+use super::batch::{AtomBatch, BatchAtomCreationOption};
 use super::species::AtomCreator;
 use rand;
 use rand::Rng;
 
 use super::precalc::{MaxwellBoltzmannSource, PrecalculatedSpeciesInformation};
 use crate::atom::*;
-use crate::initiate::NewlyCreated;
 use crate::shapes::{Cylinder, Surface};
 
 extern crate specs;
@@ -50,6 +50,7 @@ impl<'a, T> System<'a> for CreateAtomsOnSurfaceSystem<T> where T : AtomCreator +
         ReadStorage<'a, Position>,
         ReadStorage<'a, PrecalculatedSpeciesInformation>,
         Option<Read<'a, VelocityCap>>,
+        Option<Read<'a, BatchAtomCreationOption>>,
         Read<'a, LazyUpdate>,
     );
 
@@ -63,6 +64,7 @@ impl<'a, T> System<'a> for CreateAtomsOnSurfaceSystem<T> where T : AtomCreator +
             source_positions,
             species,
             velocity_cap,
+            batching,
             updater,
         ): Self::SystemData,
     ) {
@@ -73,6 +75,7 @@ impl<'a, T> System<'a> for CreateAtomsOnSurfaceSystem<T> where T : AtomCreator +
         };
 
         let mut rng = rand::thread_rng();
+        let mut batch = AtomBatch::new();
         for (_, shape, number_to_emit, source_position, species) in (
             &surfaces,
             &shapes,
@@ -90,7 +93,8 @@ impl<'a, T> System<'a> for CreateAtomsOnSurfaceSystem<T> where T : AtomCreator +
                 }
 
                 // generate a random position on the surface.
-                let (position, normal) = shape.get_random_point_on_surface(&source_position.pos);
+                let (position, normal) =
+                    shape.get_random_point_on_surface(&source_position.pos.cast());
 
                 // lambert cosine emission
                 let direction = -normal.normalize();
@@ -116,17 +120,9 @@ impl<'a, T> System<'a> for CreateAtomsOnSurfaceSystem<T> where T : AtomCreator +
                     + theta.sin() * (perp_a * phi.cos() + perp_b * phi.sin());
 
                 let velocity = speed * emission_direction;
-
-                let new_atom = entities.create();
-                updater.insert(new_atom, Position { pos: position });
-                updater.insert(new_atom, Velocity { vel: velocity });
-                updater.insert(new_atom, Force::new());
-                updater.insert(new_atom, Mass { value: mass });
-                updater.insert(new_atom, Atom);
-                updater.insert(new_atom, InitialVelocity { vel: velocity });
-                updater.insert(new_atom, NewlyCreated);
-                T::mutate(&updater, new_atom);
+                batch.push(position, velocity, mass);
             }
         }
+        batch.create::<T>(&entities, &updater, batching.is_some());
     }
 }
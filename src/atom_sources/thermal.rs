@@ -0,0 +1,108 @@
+//! Sampling of thermal velocities, for sources that create atoms from a gas at a given temperature.
+
+use nalgebra::Vector3;
+use rand::Rng;
+use rand_distr::{Distribution, Normal};
+
+use crate::constant::{AMU, BOLTZCONST};
+
+/// Draws a velocity from the Maxwell-Boltzmann distribution, in SI units of m/s.
+///
+/// Each component of the velocity is drawn from a Gaussian distribution of zero mean and standard deviation
+/// `sqrt(k_B T / m)`. Pass a seeded `rng` for reproducible samples.
+///
+/// # Arguments
+///
+/// `rng`: the random number generator.
+///
+/// `temperature`: temperature of the distribution, in SI units of K. A temperature of zero gives a zero velocity.
+///
+/// `mass`: mass of the atom, in atomic mass units.
+///
+/// Panics if the temperature is negative.
+pub fn maxwell_boltzmann_velocity<R: Rng + ?Sized>(
+    rng: &mut R,
+    temperature: f64,
+    mass: f64,
+) -> Vector3<f64> {
+    assert!(temperature >= 0.0, "Temperature must not be negative.");
+    if temperature == 0.0 {
+        return Vector3::new(0.0, 0.0, 0.0);
+    }
+    let width = (BOLTZCONST * temperature / (mass * AMU)).sqrt();
+    let distribution = Normal::new(0.0, width).expect("Could not create velocity distribution.");
+    Vector3::new(
+        distribution.sample(rng),
+        distribution.sample(rng),
+        distribution.sample(rng),
+    )
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use crate::constant::PI;
+    use assert_approx_eq::assert_approx_eq;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_zero_temperature_gives_zero_velocity() {
+        let mut rng = StdRng::seed_from_u64(1);
+        assert_eq!(
+            maxwell_boltzmann_velocity(&mut rng, 0.0, 87.0),
+            Vector3::new(0.0, 0.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn test_maxwell_boltzmann_velocity_distribution() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let temperature = 100.0e-6;
+        let mass = 87.0;
+        let width = (BOLTZCONST * temperature / (mass * AMU)).sqrt();
+        let n = 100_000;
+        let velocities: Vec<Vector3<f64>> = (0..n)
+            .map(|_| maxwell_boltzmann_velocity(&mut rng, temperature, mass))
+            .collect();
+
+        // The variance of each component is k_B T / m.
+        for axis in 0..3 {
+            let mean = velocities.iter().map(|v| v[axis]).sum::<f64>() / n as f64;
+            let variance = velocities
+                .iter()
+                .map(|v| (v[axis] - mean).powi(2))
+                .sum::<f64>()
+                / n as f64;
+            assert_approx_eq!(mean, 0.0, 0.02 * width);
+            assert_approx_eq!(variance, width.powi(2), 0.02 * width.powi(2));
+        }
+
+        // Chi-squared test of the speed distribution, f(x) = sqrt(2 / pi) x^2 exp(-x^2 / 2) for x = v / width.
+        let bins = 20;
+        let x_max = 4.0;
+        let dx = x_max / bins as f64;
+        let mut observed = vec![0.0; bins + 1];
+        for v in velocities.iter() {
+            let bin = ((v.norm() / width / dx) as usize).min(bins);
+            observed[bin] += 1.0;
+        }
+        let pdf = |x: f64| (2.0 / PI).sqrt() * x.powi(2) * (-x.powi(2) / 2.0).exp();
+        let mut expected: Vec<f64> = (0..bins)
+            .map(|bin| {
+                // Simpson's rule over each bin.
+                let (a, b) = (bin as f64 * dx, (bin + 1) as f64 * dx);
+                (b - a) / 6.0 * (pdf(a) + 4.0 * pdf(0.5 * (a + b)) + pdf(b)) * n as f64
+            })
+            .collect();
+        expected.push(n as f64 - expected.iter().sum::<f64>());
+        let chi_squared: f64 = observed
+            .iter()
+            .zip(expected.iter())
+            .map(|(o, e)| (o - e).powi(2) / e)
+            .sum();
+        // The 99.9th percentile of the chi-squared distribution with 20 degrees of freedom is 45.3.
+        assert!(chi_squared < 45.3, "chi squared = {}", chi_squared);
+    }
+}
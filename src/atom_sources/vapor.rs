@@ -0,0 +1,328 @@
+//! Background vapor sources, used to model the loading of a vapor-cell MOT.
+
+use std::marker::PhantomData;
+
+use super::batch::{AtomBatch, BatchAtomCreationOption};
+use super::species::AtomCreator;
+use super::thermal::maxwell_boltzmann_velocity;
+use crate::atom::*;
+use crate::constant::{AMU, BOLTZCONST, PI};
+use crate::integrator::Timestep;
+use crate::shapes::Cuboid;
+use nalgebra::Vector3;
+
+use rand;
+use rand::Rng;
+use rand_distr::{Distribution, Poisson};
+
+use specs::{
+    Component, Entities, HashMapStorage, Join, LazyUpdate, Read, ReadExpect, ReadStorage, System,
+};
+
+/// A source that creates atoms from a thermal background vapor, within a capture region.
+///
+/// Atoms are created uniformly within the capture region, which is centred on the [Position] of the source,
+/// with velocities drawn from a Maxwell-Boltzmann distribution at the vapor temperature.
+/// The mass of the atoms is given by the [Mass] of the source.
+///
+/// Atoms are created at the rate at which vapor atoms enter the capture region, `n v A / 4`,
+/// where `n = P / (k_B T)` is the number density of the vapor, `v` is the mean speed of the vapor atoms,
+/// and `A` is the surface area of the capture region.
+///
+/// To reduce the number of atoms needed for good statistics, the velocities may instead be drawn preferentially
+/// towards the centre of the capture region, see [VelocityImportanceSampling].
+pub struct VaporBackgroundSource<T>
+where
+    T: AtomCreator,
+{
+    /// Temperature of the vapor, in Kelvin.
+    pub temperature: f64,
+    /// Partial pressure of the vapor, in SI units of Pa.
+    pub pressure: f64,
+    /// The region within which atoms are created.
+    pub capture_region: Cuboid,
+    /// If set, velocities are importance sampled. Otherwise, each created atom has a [StatisticalWeight] of 1.
+    pub importance_sampling: Option<VelocityImportanceSampling>,
+    phantom: PhantomData<T>,
+}
+impl<T> Component for VaporBackgroundSource<T>
+where
+    T: AtomCreator + 'static,
+{
+    type Storage = HashMapStorage<Self>;
+}
+impl<T> VaporBackgroundSource<T>
+where
+    T: AtomCreator,
+{
+    pub fn new(temperature: f64, pressure: f64, capture_region: Cuboid) -> Self {
+        VaporBackgroundSource {
+            temperature,
+            pressure,
+            capture_region,
+            importance_sampling: None,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Draws the velocities of created atoms using the given [VelocityImportanceSampling].
+    pub fn with_importance_sampling(
+        mut self,
+        importance_sampling: VelocityImportanceSampling,
+    ) -> Self {
+        self.importance_sampling = Some(importance_sampling);
+        self
+    }
+
+    /// The rate at which atoms are created, in atoms per second.
+    ///
+    /// # Arguments
+    ///
+    /// `mass`: mass of the vapor atoms, in atomic mass units.
+    pub fn emission_rate(&self, mass: f64) -> f64 {
+        let density = self.pressure / (BOLTZCONST * self.temperature);
+        let mean_speed = (8.0 * BOLTZCONST * self.temperature / (PI * mass * AMU)).sqrt();
+        let w = self.capture_region.half_width;
+        let area = 8.0 * (w[0] * w[1] + w[1] * w[2] + w[0] * w[2]);
+        density * mean_speed * area / 4.0
+    }
+}
+
+/// Importance sampling of the velocities of atoms created by a [VaporBackgroundSource].
+///
+/// Instead of the thermal distribution `p(v)`, the velocity of each atom is drawn from a gaussian distribution `q(v)`
+/// with a mean of `inward_speed` directed towards the centre of the capture region, and a standard deviation
+/// `width_factor` times the thermal width. Slow atoms moving into the trap, which are the atoms that can be
+/// captured, are therefore created more often. Each atom is given a [StatisticalWeight] of `p(v) / q(v)`, so that
+/// weighted statistics of the created atoms match the thermal vapor.
+///
+/// The variance of the weights is finite only for `width_factor > 1 / sqrt(2)`.
+#[derive(Clone, Copy, Debug)]
+pub struct VelocityImportanceSampling {
+    /// Mean speed of the created atoms towards the centre of the capture region, in SI units of m/s.
+    pub inward_speed: f64,
+    /// Width of the sampled velocity distribution, as a fraction of the thermal width.
+    pub width_factor: f64,
+}
+impl VelocityImportanceSampling {
+    /// The statistical weight `p(v) / q(v)` of an atom created with the given velocity.
+    ///
+    /// # Arguments
+    ///
+    /// `velocity`: velocity of the atom, in SI units of m/s.
+    ///
+    /// `mean`: mean velocity of the sampled distribution, in SI units of m/s.
+    ///
+    /// `thermal_width`: standard deviation of each component of the thermal velocity, in SI units of m/s.
+    pub fn weight(&self, velocity: &Vector3<f64>, mean: &Vector3<f64>, thermal_width: f64) -> f64 {
+        let width = self.width_factor * thermal_width;
+        self.width_factor.powi(3)
+            * (-velocity.norm_squared() / (2.0 * thermal_width.powi(2))
+                + (velocity - mean).norm_squared() / (2.0 * width.powi(2)))
+            .exp()
+    }
+}
+
+/// This system creates atoms from [VaporBackgroundSource]s each step.
+#[derive(Default)]
+pub struct VaporCreateAtomsSystem<T>(PhantomData<T>);
+impl<'a, T> System<'a> for VaporCreateAtomsSystem<T>
+where
+    T: AtomCreator + 'static,
+{
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, VaporBackgroundSource<T>>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Mass>,
+        ReadExpect<'a, Timestep>,
+        Option<Read<'a, BatchAtomCreationOption>>,
+        Read<'a, LazyUpdate>,
+    );
+
+    fn run(
+        &mut self,
+        (entities, sources, positions, masses, timestep, batching, updater): Self::SystemData,
+    ) {
+        let mut rng = rand::thread_rng();
+        let mut batch = AtomBatch::new();
+        let mut weights = Vec::new();
+        for (source, source_position, mass) in (&sources, &positions, &masses).join() {
+            let mean_number = source.emission_rate(mass.value) * timestep.delta;
+            let number = match Poisson::new(mean_number) {
+                Ok(poisson) => poisson.sample(&mut rng) as u64,
+                Err(_) => 0,
+            };
+            let thermal_width = (BOLTZCONST * source.temperature / (mass.value * AMU)).sqrt();
+            // Broadening the distribution by `width_factor` is equivalent to raising its temperature.
+            let sampling_temperature = match source.importance_sampling {
+                Some(sampling) => sampling.width_factor.powi(2) * source.temperature,
+                None => source.temperature,
+            };
+            let w = source.capture_region.half_width;
+
+            for _i in 0..number {
+                let offset = Vector3::new(
+                    rng.gen_range(-w[0]..w[0]),
+                    rng.gen_range(-w[1]..w[1]),
+                    rng.gen_range(-w[2]..w[2]),
+                );
+                let position = source_position.pos.cast::<f64>() + offset;
+                let mut velocity =
+                    maxwell_boltzmann_velocity(&mut rng, sampling_temperature, mass.value);
+                let weight = match source.importance_sampling {
+                    Some(sampling) => {
+                        let mean = -sampling.inward_speed
+                            * offset.try_normalize(0.0).unwrap_or_else(Vector3::zeros);
+                        velocity += mean;
+                        sampling.weight(&velocity, &mean, thermal_width)
+                    }
+                    None => 1.0,
+                };
+                batch.push(position, velocity, mass.value);
+                weights.push(weight);
+            }
+        }
+        let new_atoms = batch.create::<T>(&entities, &updater, batching.is_some());
+        updater.insert_all(
+            new_atoms
+                .into_iter()
+                .zip(weights)
+                .map(|(atom, value)| (atom, StatisticalWeight { value })),
+        );
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use crate::equilibrium::measure_weighted_temperature;
+    use crate::initiate::NewlyCreated;
+    use crate::species::{Rubidium87, Rubidium87_780D2};
+    use specs::prelude::*;
+
+    /// Runs a vapor source for a number of steps, and returns the velocities of the created atoms.
+    fn run_vapor_source(pressure: f64, temperature: f64, steps: usize) -> Vec<Vector3<f64>> {
+        run_vapor_source_with(pressure, temperature, steps, None)
+            .into_iter()
+            .map(|(vel, _)| vel)
+            .collect()
+    }
+
+    /// Runs a vapor source for a number of steps, and returns the velocities and statistical weights of the created atoms.
+    fn run_vapor_source_with(
+        pressure: f64,
+        temperature: f64,
+        steps: usize,
+        importance_sampling: Option<VelocityImportanceSampling>,
+    ) -> Vec<(Vector3<f64>, f64)> {
+        let mut test_world = World::new();
+        test_world.register::<VaporBackgroundSource<Rubidium87>>();
+        test_world.register::<Position>();
+        test_world.register::<Velocity>();
+        test_world.register::<InitialVelocity>();
+        test_world.register::<Force>();
+        test_world.register::<Mass>();
+        test_world.register::<Atom>();
+        test_world.register::<StatisticalWeight>();
+        test_world.register::<NewlyCreated>();
+        test_world.register::<Rubidium87_780D2>();
+        test_world.insert(Timestep { delta: 1.0e-6 });
+
+        let mut source = VaporBackgroundSource::<Rubidium87>::new(
+            temperature,
+            pressure,
+            Cuboid {
+                half_width: Vector3::new(5.0e-3, 5.0e-3, 5.0e-3),
+            },
+        );
+        source.importance_sampling = importance_sampling;
+        test_world
+            .create_entity()
+            .with(source)
+            .with(Position::new())
+            .with(Mass { value: 87.0 })
+            .build();
+
+        let mut system = VaporCreateAtomsSystem::<Rubidium87>::default();
+        for _ in 0..steps {
+            system.run_now(&test_world);
+            test_world.maintain();
+        }
+
+        let velocities = test_world.read_storage::<Velocity>();
+        let weights = test_world.read_storage::<StatisticalWeight>();
+        let atoms = test_world.read_storage::<Atom>();
+        (&velocities, &weights, &atoms)
+            .join()
+            .map(|(vel, weight, _)| (vel.vel.cast(), weight.value))
+            .collect()
+    }
+
+    #[test]
+    fn test_emission_rate_scales_with_pressure() {
+        let source = VaporBackgroundSource::<Rubidium87>::new(
+            300.0,
+            1.0e-12,
+            Cuboid {
+                half_width: Vector3::new(5.0e-3, 5.0e-3, 5.0e-3),
+            },
+        );
+        let rate = source.emission_rate(87.0);
+        let doubled =
+            VaporBackgroundSource::<Rubidium87>::new(300.0, 2.0e-12, source.capture_region);
+        assert_eq!(doubled.emission_rate(87.0), 2.0 * rate);
+
+        // Number of atoms created by the system.
+        let steps = 100;
+        let expected = rate * 1.0e-6 * steps as f64;
+        let low = run_vapor_source(1.0e-12, 300.0, steps).len() as f64;
+        let high = run_vapor_source(2.0e-12, 300.0, steps).len() as f64;
+        assert!((low - expected).abs() < 5.0 * expected.sqrt());
+        assert!((high - 2.0 * expected).abs() < 5.0 * (2.0 * expected).sqrt());
+    }
+
+    #[test]
+    fn test_velocity_distribution_matches_vapor_temperature() {
+        let temperature = 300.0;
+        let velocities = run_vapor_source(1.0e-10, temperature, 20);
+        assert!(velocities.len() > 10_000);
+
+        // Equipartition: m <v^2> = 3 k_B T.
+        let mean_square =
+            velocities.iter().map(|v| v.norm_squared()).sum::<f64>() / velocities.len() as f64;
+        let measured = 87.0 * AMU * mean_square / (3.0 * BOLTZCONST);
+        assert!((measured / temperature - 1.0).abs() < 0.05);
+    }
+
+    #[test]
+    fn test_importance_sampled_temperature_matches_vapor_temperature() {
+        let temperature = 300.0;
+        let thermal_width = (BOLTZCONST * temperature / (87.0 * AMU)).sqrt();
+        let sampling = VelocityImportanceSampling {
+            inward_speed: 0.5 * thermal_width,
+            width_factor: 0.8,
+        };
+        let biased = run_vapor_source_with(1.0e-10, temperature, 100, Some(sampling));
+        assert!(biased.len() > 10_000);
+
+        let unweighted: Vec<(Vector3<f64>, f64, f64)> =
+            biased.iter().map(|(vel, _)| (*vel, 87.0, 1.0)).collect();
+        let weighted: Vec<(Vector3<f64>, f64, f64)> = biased
+            .iter()
+            .map(|(vel, weight)| (*vel, 87.0, *weight))
+            .collect();
+        // Without the weights, the biased sample is colder than the vapor.
+        assert!(measure_weighted_temperature(&unweighted) / temperature < 0.9);
+        assert!((measure_weighted_temperature(&weighted) / temperature - 1.0).abs() < 0.05);
+
+        // The mean weight is one, so the weighted number of atoms matches the number created.
+        let total_weight: f64 = biased.iter().map(|(_, weight)| weight).sum();
+        assert!((total_weight / biased.len() as f64 - 1.0).abs() < 0.05);
+
+        // Without importance sampling, every atom has unit weight.
+        let unbiased = run_vapor_source_with(1.0e-10, temperature, 1, None);
+        assert!(unbiased.iter().all(|(_, weight)| *weight == 1.0));
+    }
+}
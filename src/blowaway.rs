@@ -0,0 +1,214 @@
+//! Resonant blow-away beams, which clear atoms from a region.
+//!
+//! Before imaging, unwanted atoms are often removed with a short pulse of resonant light. Atoms in the beam
+//! scatter photons at a high rate, and are either pushed out of the trap by the radiation pressure or optically
+//! pumped into an untrapped state. A [ResonantBlowawayRegion] models the beam as a region of space, defined by a
+//! [Sphere], [Cuboid] or [Cylinder] component on the same entity, in which atoms scatter photons at a fixed rate.
+//! Atoms outside the region are unaffected.
+
+use std::marker::PhantomData;
+
+use crate::atom::{Atom, Force, Position};
+use crate::constant::HBAR;
+use crate::integrator::{Timestep, INTEGRATE_POSITION_SYSTEM_NAME};
+use crate::shapes::{Cuboid, Cylinder, Sphere, Volume};
+use crate::simulation::Plugin;
+use nalgebra::Vector3;
+use rand::Rng;
+use specs::prelude::*;
+
+/// The effect of a [ResonantBlowawayRegion] on the atoms inside it.
+#[derive(Clone, Copy, Debug)]
+pub enum BlowawayEffect {
+    /// Atoms are pushed by the mean radiation pressure of the beam, `hbar k` per scattered photon.
+    Push {
+        /// Wavevector of the blow-away beam, in SI units of rad/m.
+        wavevector: Vector3<f64>,
+    },
+    /// Atoms are removed from the simulation after scattering a photon, for example by optical pumping into an
+    /// untrapped state. The chance that an atom is removed each step is `1 - exp(-rate dt)`.
+    Remove,
+}
+
+/// A component that marks a region in which atoms are blown away by resonant light.
+///
+/// The entity must also have a [Position] and a volume component ([Sphere], [Cuboid] or [Cylinder]) that
+/// defines the region. See [crate::blowaway].
+#[derive(Clone, Copy, Debug)]
+pub struct ResonantBlowawayRegion {
+    /// Rate at which atoms in the region scatter photons, in SI units of Hz.
+    pub scattering_rate: f64,
+    /// What happens to atoms in the region.
+    pub effect: BlowawayEffect,
+}
+impl Component for ResonantBlowawayRegion {
+    type Storage = HashMapStorage<Self>;
+}
+
+/// Applies the [ResonantBlowawayRegion]s with volume type `V` to the atoms inside them.
+pub struct ApplyBlowawaySystem<V: Volume> {
+    marker: PhantomData<V>,
+}
+impl<V: Volume> Default for ApplyBlowawaySystem<V> {
+    fn default() -> Self {
+        ApplyBlowawaySystem {
+            marker: PhantomData,
+        }
+    }
+}
+impl<'a, V> System<'a> for ApplyBlowawaySystem<V>
+where
+    V: Volume + Component,
+{
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, V>,
+        ReadStorage<'a, ResonantBlowawayRegion>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Atom>,
+        WriteStorage<'a, Force>,
+        ReadExpect<'a, Timestep>,
+    );
+
+    fn run(
+        &mut self,
+        (entities, volumes, regions, positions, atoms, mut forces, timestep): Self::SystemData,
+    ) {
+        let mut rng = rand::thread_rng();
+        for (volume, region, region_pos) in (&volumes, &regions, &positions).join() {
+            let region_pos = region_pos.pos.cast::<f64>();
+            for (entity, pos, force, _) in (&entities, &positions, &mut forces, &atoms).join() {
+                if !volume.contains(&region_pos, &pos.pos.cast()) {
+                    continue;
+                }
+                match region.effect {
+                    BlowawayEffect::Push { wavevector } => {
+                        force.force += (HBAR * region.scattering_rate * wavevector).cast();
+                    }
+                    BlowawayEffect::Remove => {
+                        let chance = 1.0 - (-region.scattering_rate * timestep.delta).exp();
+                        if rng.gen_range(0.0..1.0) < chance {
+                            entities.delete(entity).expect("Could not delete entity");
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// This plugin applies [ResonantBlowawayRegion]s to the atoms.
+///
+/// See also [crate::blowaway].
+pub struct BlowawayPlugin;
+impl Plugin for BlowawayPlugin {
+    fn build(&self, builder: &mut crate::simulation::SimulationBuilder) {
+        builder.dispatcher_builder.add(
+            ApplyBlowawaySystem::<Sphere>::default(),
+            "blowaway_sphere",
+            &["clear", INTEGRATE_POSITION_SYSTEM_NAME],
+        );
+        builder.dispatcher_builder.add(
+            ApplyBlowawaySystem::<Cuboid>::default(),
+            "blowaway_cuboid",
+            &["blowaway_sphere"],
+        );
+        builder.dispatcher_builder.add(
+            ApplyBlowawaySystem::<Cylinder>::default(),
+            "blowaway_cylinder",
+            &["blowaway_cuboid"],
+        );
+    }
+    fn deps(&self) -> Vec<Box<dyn Plugin>> {
+        Vec::new()
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use crate::atom::{Mass, Velocity};
+    use crate::constant::PI;
+    use crate::initiate::NewlyCreated;
+    use crate::simulation::{Simulation, SimulationBuilder};
+
+    fn add_atom(sim: &mut Simulation, pos: Vector3<f64>) -> Entity {
+        sim.world
+            .create_entity()
+            .with(Position { pos: pos.cast() })
+            .with(Velocity {
+                vel: Vector3::new(0.0, 0.0, 0.0).cast(),
+            })
+            .with(Force::new())
+            .with(Mass { value: 87.0 })
+            .with(Atom)
+            .with(NewlyCreated)
+            .build()
+    }
+
+    fn add_region(sim: &mut Simulation, radius: f64, effect: BlowawayEffect) {
+        sim.world
+            .create_entity()
+            .with(Position::new())
+            .with(Sphere { radius })
+            .with(ResonantBlowawayRegion {
+                scattering_rate: 1.0e8,
+                effect,
+            })
+            .build();
+    }
+
+    #[test]
+    fn test_blowaway_removes_atoms_inside_region() {
+        let mut sim_builder = SimulationBuilder::default();
+        sim_builder.add_plugin(BlowawayPlugin);
+        let mut sim = sim_builder.build();
+        sim.world.insert(Timestep { delta: 1.0e-6 });
+        let radius = 1.0e-3;
+        add_region(&mut sim, radius, BlowawayEffect::Remove);
+
+        let inside: Vec<Entity> = [0.0, 0.5, 0.99]
+            .iter()
+            .map(|r| add_atom(&mut sim, Vector3::new(r * radius, 0.0, 0.0)))
+            .collect();
+        let outside: Vec<Entity> = [1.01, 2.0]
+            .iter()
+            .map(|r| add_atom(&mut sim, Vector3::new(0.0, r * radius, 0.0)))
+            .collect();
+        for _ in 0..3 {
+            sim.step();
+        }
+
+        for atom in inside.iter() {
+            assert!(!sim.world.is_alive(*atom));
+        }
+        for atom in outside.iter() {
+            assert!(sim.world.is_alive(*atom));
+        }
+    }
+
+    #[test]
+    fn test_blowaway_pushes_atoms_inside_region() {
+        let mut sim_builder = SimulationBuilder::default();
+        sim_builder.add_plugin(BlowawayPlugin);
+        let mut sim = sim_builder.build();
+        sim.world.insert(Timestep { delta: 1.0e-6 });
+        let radius = 1.0e-3;
+        let wavevector = Vector3::new(0.0, 0.0, 2.0 * PI / 780.0e-9);
+        add_region(&mut sim, radius, BlowawayEffect::Push { wavevector });
+
+        let inside = add_atom(&mut sim, Vector3::new(0.9 * radius, 0.0, 0.0));
+        let outside = add_atom(&mut sim, Vector3::new(1.1 * radius, 0.0, 0.0));
+        sim.step();
+        sim.step();
+
+        let forces = sim.world.read_storage::<Force>();
+        let expected = HBAR * 1.0e8 * wavevector;
+        assert_eq!(forces.get(inside).unwrap().force.cast::<f64>(), expected);
+        assert_eq!(
+            forces.get(outside).unwrap().force.cast::<f64>(),
+            Vector3::new(0.0, 0.0, 0.0)
+        );
+    }
+}
@@ -0,0 +1,259 @@
+//! Callbacks that run custom logic each simulation step.
+//!
+//! Closures registered in the [StepCallbacks] resource are invoked once per step by the [RunCallbacksSystem].
+//! This allows custom diagnostics or feedback control, such as adjusting the power of a beam based on the
+//! measured temperature, without writing new systems.
+//!
+//! The [CallbackPoint] of the [StepCallbacksPlugin] selects where in the dispatch the callbacks run: after all
+//! other systems, or between the systems added before the plugin and those added after it.
+//!
+//! # Accessing the world
+//!
+//! Callbacks receive a shared reference to the `World`. The [RunCallbacksSystem] always runs alone: either as a
+//! thread-local system after all other systems in the dispatch, or between two barriers. No other system holds
+//! a borrow of any storage while the callbacks run. Callbacks may therefore fetch any storage or resource,
+//! mutably or otherwise, using `world.read_storage`, `world.write_storage`, `world.read_resource` and
+//! `world.write_resource`.
+//!
+//! Borrows are checked at runtime, and fetching a storage mutably while another borrow of it is alive
+//! panics. Callbacks should fetch what they need in a local scope, and must not hold borrows between calls.
+//! The [StepCallbacks] are removed from the world while they run, so a callback may register further callbacks.
+
+use crate::integrator::{SimulationTime, Step, Timestep};
+use crate::simulation::Plugin;
+use specs::prelude::*;
+use specs::shred::{AccessorCow, BatchAccessor, BatchUncheckedWorld};
+
+/// A callback invoked once per simulation step, see [crate::callbacks].
+pub type StepCallback = Box<dyn Fn(&World, &SimulationTime) + Send + Sync>;
+
+/// A resource holding the callbacks to invoke each simulation step.
+#[derive(Default)]
+pub struct StepCallbacks {
+    callbacks: Vec<StepCallback>,
+}
+impl StepCallbacks {
+    /// Registers a callback to be invoked each step.
+    pub fn add<F>(&mut self, callback: F)
+    where
+        F: Fn(&World, &SimulationTime) + Send + Sync + 'static,
+    {
+        self.callbacks.push(Box::new(callback));
+    }
+
+    /// Number of registered callbacks.
+    pub fn len(&self) -> usize {
+        self.callbacks.len()
+    }
+
+    /// Returns true if no callbacks are registered.
+    pub fn is_empty(&self) -> bool {
+        self.callbacks.is_empty()
+    }
+}
+
+/// Invokes each of the [StepCallbacks], in the order they were registered.
+///
+/// This system requires direct access to the `World`. It declares no resource accesses, so it must either be
+/// added to the dispatcher as a thread-local system, or between two barriers, so that it runs alone.
+///
+/// The elapsed [SimulationTime] is accumulated from the [Timestep] of each step, so it stays correct when a
+/// callback changes the [Timestep].
+pub struct RunCallbacksSystem {
+    accessor: BatchAccessor,
+    /// The time at which the callbacks were last invoked.
+    last: Option<SimulationTime>,
+}
+impl RunCallbacksSystem {
+    pub fn new() -> Self {
+        RunCallbacksSystem {
+            accessor: BatchAccessor::new(Vec::new(), Vec::new()),
+            last: None,
+        }
+    }
+}
+impl Default for RunCallbacksSystem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl<'a> System<'a> for RunCallbacksSystem {
+    type SystemData = BatchUncheckedWorld<'a>;
+
+    fn run(&mut self, BatchUncheckedWorld(world): Self::SystemData) {
+        let step = world.read_resource::<Step>().n;
+        let delta = world.read_resource::<Timestep>().delta;
+        // The steps since the last invocation were run with the current timestep.
+        let time = match self.last {
+            Some(last) => last.time + step.saturating_sub(last.step) as f64 * delta,
+            None => step as f64 * delta,
+        };
+        let time = SimulationTime { step, time };
+        self.last = Some(time);
+
+        let callbacks = std::mem::take(&mut world.write_resource::<StepCallbacks>().callbacks);
+        for callback in callbacks.iter() {
+            callback(world, &time);
+        }
+
+        // Restore the callbacks, retaining any that were registered during this step.
+        let mut resource = world.write_resource::<StepCallbacks>();
+        let added = std::mem::replace(&mut resource.callbacks, callbacks);
+        resource.callbacks.extend(added);
+    }
+
+    fn accessor<'b>(&'b self) -> AccessorCow<'a, 'b, Self> {
+        AccessorCow::Ref(&self.accessor)
+    }
+
+    fn setup(&mut self, world: &mut World) {
+        world
+            .entry::<StepCallbacks>()
+            .or_insert_with(StepCallbacks::default);
+    }
+}
+
+/// The point in the dispatch at which the [StepCallbacks] are invoked.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CallbackPoint {
+    /// After all other systems, at the end of each step.
+    EndOfStep,
+    /// After all systems added before the [StepCallbacksPlugin], and before all systems added after it.
+    ///
+    /// The callbacks are separated from the other systems by barriers, which prevents those systems from running
+    /// in parallel across this point.
+    AfterPreviousSystems,
+}
+
+/// This plugin invokes the [StepCallbacks] each simulation step, at the given [CallbackPoint].
+///
+/// See also [crate::callbacks].
+pub struct StepCallbacksPlugin {
+    pub point: CallbackPoint,
+}
+impl StepCallbacksPlugin {
+    pub fn new(point: CallbackPoint) -> Self {
+        StepCallbacksPlugin { point }
+    }
+}
+impl Default for StepCallbacksPlugin {
+    fn default() -> Self {
+        Self::new(CallbackPoint::EndOfStep)
+    }
+}
+impl Plugin for StepCallbacksPlugin {
+    fn build(&self, builder: &mut crate::simulation::SimulationBuilder) {
+        match self.point {
+            CallbackPoint::EndOfStep => builder
+                .dispatcher_builder
+                .add_thread_local(RunCallbacksSystem::new()),
+            CallbackPoint::AfterPreviousSystems => {
+                builder.dispatcher_builder.add_barrier();
+                builder
+                    .dispatcher_builder
+                    .add(RunCallbacksSystem::new(), "run_callbacks", &[]);
+                builder.dispatcher_builder.add_barrier();
+            }
+        }
+    }
+    fn deps(&self) -> Vec<Box<dyn Plugin>> {
+        Vec::new()
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use crate::simulation::SimulationBuilder;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_callback_fires_every_step() {
+        let mut sim_builder = SimulationBuilder::default();
+        sim_builder.add_plugin(StepCallbacksPlugin::default());
+        let mut sim = sim_builder.build();
+        sim.world.insert(Timestep { delta: 1.0e-3 });
+
+        let recorded = Arc::new(Mutex::new(Vec::new()));
+        let record = recorded.clone();
+        sim.world
+            .write_resource::<StepCallbacks>()
+            .add(move |world, time| {
+                assert_eq!(world.read_resource::<Step>().n, time.step);
+                record.lock().unwrap().push(time.step);
+            });
+
+        for _ in 0..10 {
+            sim.step();
+        }
+        let recorded = recorded.lock().unwrap();
+        assert_eq!(*recorded, (1..=10).collect::<Vec<u64>>());
+    }
+
+    #[test]
+    fn test_callback_can_modify_world() {
+        let mut sim_builder = SimulationBuilder::default();
+        sim_builder.add_plugin(StepCallbacksPlugin::default());
+        let mut sim = sim_builder.build();
+        sim.world.insert(Timestep { delta: 1.0e-3 });
+
+        let times = Arc::new(Mutex::new(Vec::new()));
+        let record = times.clone();
+        sim.world
+            .write_resource::<StepCallbacks>()
+            .add(move |world, time| {
+                record.lock().unwrap().push(time.time);
+                world.write_resource::<Timestep>().delta = 2.0e-3;
+                if time.step == 1 {
+                    world.write_resource::<StepCallbacks>().add(|_, _| {});
+                }
+            });
+
+        sim.step();
+        sim.step();
+        assert_eq!(sim.world.read_resource::<Timestep>().delta, 2.0e-3);
+        assert_eq!(sim.world.read_resource::<StepCallbacks>().len(), 2);
+
+        // The first step ran with the initial timestep, and the second with the timestep set by the callback.
+        let times = times.lock().unwrap();
+        assert_eq!(times.len(), 2);
+        assert!((times[0] - 1.0e-3).abs() < 1e-15);
+        assert!((times[1] - 3.0e-3).abs() < 1e-15);
+    }
+
+    /// Counts the invocations of a callback.
+    #[derive(Default)]
+    struct CallbackCount(u64);
+
+    /// Checks that the callback has already run on the current step.
+    struct CheckCallbackCountSystem;
+    impl<'a> System<'a> for CheckCallbackCountSystem {
+        type SystemData = (Read<'a, CallbackCount>, ReadExpect<'a, Step>);
+        fn run(&mut self, (count, step): Self::SystemData) {
+            assert_eq!(count.0, step.n);
+        }
+    }
+
+    #[test]
+    fn test_callbacks_run_before_systems_added_after_plugin() {
+        let mut sim_builder = SimulationBuilder::default();
+        sim_builder.add_plugin(StepCallbacksPlugin::new(CallbackPoint::AfterPreviousSystems));
+        sim_builder
+            .dispatcher_builder
+            .add(CheckCallbackCountSystem, "check_callback_count", &[]);
+        let mut sim = sim_builder.build();
+        sim.world.insert(Timestep { delta: 1.0e-3 });
+
+        sim.world
+            .write_resource::<StepCallbacks>()
+            .add(|world, _| {
+                world.write_resource::<CallbackCount>().0 += 1;
+            });
+
+        for _ in 0..5 {
+            sim.step();
+        }
+        assert_eq!(sim.world.read_resource::<CallbackCount>().0, 5);
+    }
+}
@@ -9,20 +9,43 @@
 //! For cases where this approximation is poor, the collision rate may be wrong.
 //! We assume a single species of atom, with a constant (not velocity dependent) collisional cross-section.
 //!
+//! Each simulated particle represents [CollisionParameters::macroparticle] real atoms, multiplied by its
+//! [SuperAtomWeight] if it has one. The density of a cell counts the real atoms, but collision partners are
+//! chosen uniformly, so the super-atom weights should be similar within each cell.
 //!
+//! If a [FeshbachResonance] resource is present, the cross-section of each cell is calculated from the mean
+//! magnitude of the magnetic field of the atoms in the cell, replacing [CollisionParameters::sigma]. Only atoms with
+//! a [MagneticFieldSampler] contribute to the mean, and cells without such atoms keep [CollisionParameters::sigma].
+//!
+//! When [PeriodicBounds](crate::periodic::PeriodicBounds) are active, the collision cells tile the periodic box instead
+//! of the grid of [CollisionParameters::box_number] cells. The box must be a whole number of cells wide along each
+//! axis, and more than two cells wide. An atom is binned in the same cell as all of its periodic images, so the cells
+//! on opposite faces of the box are adjacent and no atom falls outside the grid.
+//!
+//! Inelastic collisions, which release enough energy to eject both atoms from the trap, can be modelled by inserting
+//! a [TwoBodyLoss] resource. The density of real atoms is calculated in the same collision cells, and each atom is
+//! lost with a chance `1 - exp(-coefficient n dt)` each step, so that the density follows `dn/dt = -coefficient n^2`.
+//! Atoms outside the grid of collision cells are not lost.
+//!
+//! Collisions and two-body losses are drawn from the [DeterministicRng] resource if it is present, with an independent
+//! generator for each collision cell, see [crate::rng].
 //!
 
 extern crate multimap;
-use crate::atom::{Position, Velocity};
+use crate::atom::{Atom, Position, SuperAtomWeight, Velocity};
 use crate::constant::{PI, SQRT2};
+use crate::magnetic::MagneticFieldSampler;
 use crate::integrator::{Timestep, INTEGRATE_VELOCITY_SYSTEM_NAME};
+use crate::parallel::{ForceSerial, MaybeParJoin};
+use crate::periodic::{PeriodicBounds, WRAP_PERIODIC_BOUNDS_SYSTEM_NAME};
+use crate::rng::{DeterministicRng, RngStreams};
 use crate::simulation::{Plugin, SimulationBuilder};
 use hashbrown::HashMap;
 use nalgebra::Vector3;
 use rand::Rng;
 use specs::{
-    Component, Entities, Join, LazyUpdate, Read, ReadExpect, ReadStorage, System, VecStorage,
-    WriteExpect, WriteStorage,
+    Component, Entities, Entity, Join, LazyUpdate, Read, ReadExpect, ReadStorage, System,
+    VecStorage, Write, WriteExpect, WriteStorage,
 };
 
 /// A resource that indicates that the simulation should apply scattering
@@ -40,6 +63,11 @@ impl Component for BoxID {
 /// A patition of space within which collisions can occur
 pub struct CollisionBox<'a> {
     pub velocities: Vec<&'a mut Velocity>,
+    /// Number of real atoms represented by each particle, excluding the [CollisionParameters::macroparticle].
+    pub weights: Vec<f64>,
+    /// Magnitude of the magnetic field at each particle with a [MagneticFieldSampler], in SI units of T. Only used with
+    /// a [FeshbachResonance].
+    pub fields: Vec<f64>,
     pub expected_collision_number: f64,
     pub collision_number: i32,
     pub density: f64,
@@ -52,6 +80,8 @@ impl Default for CollisionBox<'_> {
     fn default() -> Self {
         CollisionBox {
             velocities: Vec::new(),
+            weights: Vec::new(),
+            fields: Vec::new(),
             expected_collision_number: 0.0,
             density: 0.0,
             volume: 0.0,
@@ -63,11 +93,22 @@ impl Default for CollisionBox<'_> {
 }
 
 impl CollisionBox<'_> {
-    /// Perform collisions within a box.
-    fn do_collisions(&mut self, params: CollisionParameters, dt: f64) {
-        let mut rng = rand::thread_rng();
+    /// The mean magnitude of the magnetic field sampled by the particles in the box, or `None` if no particle samples
+    /// the field.
+    fn mean_field(&self) -> Option<f64> {
+        if self.fields.is_empty() {
+            None
+        } else {
+            Some(self.fields.iter().sum::<f64>() / self.fields.len() as f64)
+        }
+    }
+
+    /// Perform collisions within a box, drawing from `rng`.
+    fn do_collisions<R: Rng>(&mut self, params: CollisionParameters, dt: f64, rng: &mut R) {
         self.particle_number = self.velocities.len() as i32;
-        self.atom_number = self.particle_number as f64 * params.macroparticle;
+        self.atom_number = self.weights.iter().sum::<f64>() * params.macroparticle;
+        self.volume = params.box_width.powi(3);
+        self.density = self.atom_number / self.volume;
 
         // Only one atom or less in box - no collisions.
         if self.particle_number <= 1 {
@@ -78,7 +119,7 @@ impl CollisionBox<'_> {
         // vbar is the average _speed_, not the average _velocity_.
         let mut vsum = 0.0;
         for i in 0..self.velocities.len() {
-            vsum += self.velocities[i].vel.norm();
+            vsum += self.velocities[i].vel.cast::<f64>().norm();
         }
         let vbar = vsum / self.velocities.len() as f64;
 
@@ -86,9 +127,8 @@ impl CollisionBox<'_> {
         // probability of one particle colliding is n*sigma*vrel*dt where n is the atom density, sigma cross section and vrel the average relative velocity
         // vrel = SQRT(2)*vbar, and since we assume these are identical particles we must divide by two since otherwise we count each collision twice
         // so total number of collisions is N_particles * probability = N_p*n*sigma*vbar*dt/SQRT(2)
-        let density = self.atom_number / params.box_width.powi(3);
         self.expected_collision_number =
-            self.particle_number as f64 * density * params.sigma * vbar * dt * (1.0 / SQRT2);
+            self.particle_number as f64 * self.density * params.sigma * vbar * dt * (1.0 / SQRT2);
 
         let mut num_collisions_left: f64 = self.expected_collision_number;
 
@@ -110,11 +150,11 @@ impl CollisionBox<'_> {
                     idx2 = rng.gen_range(0..self.velocities.len())
                 }
 
-                let v1 = self.velocities[idx1].vel;
-                let v2 = self.velocities[idx2].vel;
-                let (v1new, v2new) = do_collision(v1, v2);
-                self.velocities[idx1].vel = v1new;
-                self.velocities[idx2].vel = v2new;
+                let v1 = self.velocities[idx1].vel.cast();
+                let v2 = self.velocities[idx2].vel.cast();
+                let (v1new, v2new) = do_collision(v1, v2, rng);
+                self.velocities[idx1].vel = v1new.cast();
+                self.velocities[idx2].vel = v2new.cast();
                 self.collision_number += 1;
             }
 
@@ -139,6 +179,36 @@ pub struct CollisionParameters {
     pub collision_limit: f64,
 }
 
+/// A resource describing a magnetic Feshbach resonance, which tunes the collisional cross-section.
+///
+/// Close to the resonance, the s-wave scattering length is `a(B) = a_bg (1 - width / (B - position))`, and the
+/// cross-section of identical bosons is `8 pi a(B)^2`. The cross-section diverges at the resonance, so the
+/// [CollisionParameters::collision_limit] may be exceeded for atoms close to the resonance. Far from the resonance
+/// the cross-section is the background value `8 pi a_bg^2`.
+///
+/// The scattering length passes through zero at `B = position + width`, where the atoms do not collide at all. The
+/// cross-section therefore only rises monotonically towards the resonance between `position` and this zero crossing.
+#[derive(Copy, Clone)]
+pub struct FeshbachResonance {
+    /// Background scattering length, in SI units of m.
+    pub background_scattering_length: f64,
+    /// Magnitude of the magnetic field at the resonance, in SI units of T.
+    pub position: f64,
+    /// Width of the resonance, in SI units of T.
+    pub width: f64,
+}
+impl FeshbachResonance {
+    /// The s-wave scattering length at a magnetic field of magnitude `field`, in SI units of m.
+    pub fn scattering_length(&self, field: f64) -> f64 {
+        self.background_scattering_length * (1.0 - self.width / (field - self.position))
+    }
+
+    /// The collisional cross-section at a magnetic field of magnitude `field`, in SI units of m^2.
+    pub fn cross_section(&self, field: f64) -> f64 {
+        8.0 * PI * self.scattering_length(field).powi(2)
+    }
+}
+
 /// store stats about collisions
 #[derive(Clone)]
 pub struct CollisionsTracker {
@@ -157,6 +227,9 @@ impl<'a> System<'a> for ApplyCollisionsSystem {
         ReadStorage<'a, Position>,
         ReadStorage<'a, crate::atom::Atom>,
         WriteStorage<'a, Velocity>,
+        ReadStorage<'a, SuperAtomWeight>,
+        ReadStorage<'a, MagneticFieldSampler>,
+        Option<Read<'a, FeshbachResonance>>,
         Option<Read<'a, ApplyCollisionsOption>>,
         ReadExpect<'a, Timestep>,
         Entities<'a>,
@@ -164,6 +237,9 @@ impl<'a> System<'a> for ApplyCollisionsSystem {
         Read<'a, LazyUpdate>,
         ReadExpect<'a, CollisionParameters>,
         WriteExpect<'a, CollisionsTracker>,
+        Option<Read<'a, PeriodicBounds>>,
+        Option<Write<'a, DeterministicRng>>,
+        Option<Read<'a, ForceSerial>>,
     );
 
     fn run(
@@ -172,6 +248,9 @@ impl<'a> System<'a> for ApplyCollisionsSystem {
             positions,
             atoms,
             mut velocities,
+            super_atoms,
+            field_samplers,
+            feshbach,
             collisions_option,
             t,
             entities,
@@ -179,10 +258,12 @@ impl<'a> System<'a> for ApplyCollisionsSystem {
             updater,
             params,
             mut tracker,
+            periodic_bounds,
+            mut deterministic,
+            force_serial,
         ): Self::SystemData,
     ) {
         use rayon::prelude::*;
-        use specs::ParJoin;
 
         match collisions_option {
             None => (),
@@ -195,29 +276,58 @@ impl<'a> System<'a> for ApplyCollisionsSystem {
                     updater.insert(entity, BoxID { id: 0 });
                 }
 
-                // build list of ids for each atom
+                // build list of ids for each atom, with cells that tile the box if bounds are periodic
+                let periodic_cells = periodic_cell_number(periodic_bounds.as_deref(), params.box_width);
                 (&positions, &mut boxids)
-                    .par_join()
-                    .for_each(|(position, mut boxid)| {
-                        boxid.id = pos_to_id(position.pos, n, params.box_width);
+                    .maybe_par_for_each(force_serial.is_some(), |(position, mut boxid)| {
+                        boxid.id = match periodic_cells {
+                            Some(cells) => periodic_pos_to_id(position.pos.cast(), cells, params.box_width),
+                            None => pos_to_id(position.pos.cast(), n, params.box_width),
+                        };
                     });
 
                 //insert atom velocity into hash
                 let mut map: HashMap<i64, CollisionBox> = HashMap::new();
-                for (velocity, boxid) in (&mut velocities, &boxids).join() {
+                for (velocity, boxid, super_atom, sampler) in (
+                    &mut velocities,
+                    &boxids,
+                    super_atoms.maybe(),
+                    field_samplers.maybe(),
+                )
+                    .join()
+                {
                     if boxid.id == i64::MAX {
                         continue;
                     } else {
-                        map.entry(boxid.id).or_default().velocities.push(velocity);
+                        let collision_box = map.entry(boxid.id).or_default();
+                        collision_box.velocities.push(velocity);
+                        collision_box
+                            .weights
+                            .push(super_atom.map_or(1.0, |weight| weight.n_real));
+                        if let Some(sampler) = sampler {
+                            collision_box.fields.push(sampler.magnitude);
+                        }
                     }
                 }
 
                 // get immutable list of boxes and iterate in parallel
                 // (Note that using hashmap parallel values mut does not work in parallel, tested.)
-                let boxes: Vec<&mut CollisionBox> = map.values_mut().collect();
-                boxes.into_par_iter().for_each(|collision_box| {
-                    collision_box.do_collisions(*params, t.delta);
-                });
+                let boxes: Vec<(&i64, &mut CollisionBox)> = map.iter_mut().collect();
+                let feshbach = feshbach.as_deref().copied();
+                let streams = RngStreams::new(deterministic.as_deref_mut());
+                let collide = |(id, collision_box): (&i64, &mut CollisionBox)| {
+                    let mut params = *params;
+                    if let (Some(resonance), Some(field)) = (feshbach, collision_box.mean_field()) {
+                        params.sigma = resonance.cross_section(field);
+                    }
+                    let mut rng = streams.stream(*id as u64);
+                    collision_box.do_collisions(params, t.delta, &mut rng);
+                };
+                if force_serial.is_some() {
+                    boxes.into_iter().for_each(collide);
+                } else {
+                    boxes.into_par_iter().for_each(collide);
+                }
 
                 tracker.num_atoms = map
                     .values()
@@ -236,8 +346,86 @@ impl<'a> System<'a> for ApplyCollisionsSystem {
     }
 }
 
-fn do_collision(mut v1: Vector3<f64>, mut v2: Vector3<f64>) -> (Vector3<f64>, Vector3<f64>) {
-    let mut rng = rand::thread_rng();
+/// A resource that enables the loss of atoms through inelastic two-body collisions, see [crate::collisions].
+#[derive(Clone, Copy, Debug)]
+pub struct TwoBodyLoss {
+    /// Two-body loss coefficient `beta` in `dn/dt = -beta n^2`, in SI units of m^3/s.
+    pub coefficient: f64,
+}
+
+/// A system that randomly deletes atoms at the rate `beta n` given by the [TwoBodyLoss] resource and the local
+/// density `n` of real atoms in each collision cell.
+///
+/// The losses are drawn from the [DeterministicRng] resource if it is present.
+pub struct ApplyTwoBodyLossSystem;
+impl<'a> System<'a> for ApplyTwoBodyLossSystem {
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Atom>,
+        ReadStorage<'a, SuperAtomWeight>,
+        Option<Read<'a, TwoBodyLoss>>,
+        ReadExpect<'a, Timestep>,
+        ReadExpect<'a, CollisionParameters>,
+        Option<Read<'a, PeriodicBounds>>,
+        Option<Write<'a, DeterministicRng>>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            positions,
+            atoms,
+            super_atoms,
+            loss,
+            t,
+            params,
+            periodic_bounds,
+            mut deterministic,
+        ): Self::SystemData,
+    ) {
+        let coefficient = match loss {
+            Some(loss) if loss.coefficient > 0.0 => loss.coefficient,
+            _ => return,
+        };
+        let periodic_cells = periodic_cell_number(periodic_bounds.as_deref(), params.box_width);
+
+        let mut cells: HashMap<i64, (f64, Vec<Entity>)> = HashMap::new();
+        for (entity, position, super_atom, _) in
+            (&entities, &positions, super_atoms.maybe(), &atoms).join()
+        {
+            let id = match periodic_cells {
+                Some(cells) => periodic_pos_to_id(position.pos.cast(), cells, params.box_width),
+                None => pos_to_id(position.pos.cast(), params.box_number, params.box_width),
+            };
+            if id == i64::MAX {
+                continue;
+            }
+            let cell = cells.entry(id).or_default();
+            cell.0 += super_atom.map_or(1.0, |weight| weight.n_real) * params.macroparticle;
+            cell.1.push(entity);
+        }
+
+        let volume = params.box_width.powi(3);
+        let streams = RngStreams::new(deterministic.as_deref_mut());
+        for (id, (atom_number, members)) in cells.iter() {
+            let chance = 1.0 - (-coefficient * atom_number / volume * t.delta).exp();
+            let mut rng = streams.stream(*id as u64);
+            for entity in members.iter() {
+                if rng.gen::<f64>() < chance {
+                    entities.delete(*entity).expect("Could not delete entity");
+                }
+            }
+        }
+    }
+}
+
+fn do_collision<R: Rng>(
+    mut v1: Vector3<f64>,
+    mut v2: Vector3<f64>,
+    rng: &mut R,
+) -> (Vector3<f64>, Vector3<f64>) {
 
     // Randomly modify velocities in CoM frame, conserving energy & momentum
     let vcm = 0.5 * (v1 + v2);
@@ -285,21 +473,43 @@ fn pos_to_id(pos: Vector3<f64>, n: i64, width: f64) -> i64 {
     id
 }
 
+/// Returns the number of collision cells along each axis of the periodic box, if [PeriodicBounds] are present.
+fn periodic_cell_number(bounds: Option<&PeriodicBounds>, width: f64) -> Option<Vector3<i64>> {
+    bounds.map(|bounds| {
+        bounds.check_interaction_range(width);
+        bounds.cell_number(width)
+    })
+}
+
+/// Returns the id of the collision cell containing `pos`, for `cells` of the given `width` that tile the periodic box.
+///
+/// The position need not be in the primary cell: all periodic images of a position are in the same collision cell.
+fn periodic_pos_to_id(pos: Vector3<f64>, cells: Vector3<i64>, width: f64) -> i64 {
+    let index = |axis: usize| {
+        ((pos[axis] / width + 0.5 * cells[axis] as f64).floor() as i64).rem_euclid(cells[axis])
+    };
+    index(0) + cells[0] * index(1) + cells[0] * cells[1] * index(2)
+}
+
 pub struct CollisionPlugin;
 impl Plugin for CollisionPlugin {
     fn build(&self, builder: &mut SimulationBuilder) {
         // Note that the collisions system must be applied after the velocity integrator or it will violate conservation of energy and cause heating
-        builder.dispatcher_builder.add(
-            ApplyCollisionsSystem,
-            "collisions",
-            &[INTEGRATE_VELOCITY_SYSTEM_NAME],
-        );
+        let mut deps = vec![INTEGRATE_VELOCITY_SYSTEM_NAME];
+        if builder.dispatcher_builder.has_system(WRAP_PERIODIC_BOUNDS_SYSTEM_NAME) {
+            deps.push(WRAP_PERIODIC_BOUNDS_SYSTEM_NAME);
+        }
+        builder.dispatcher_builder.add(ApplyCollisionsSystem, "collisions", &deps);
+        builder
+            .dispatcher_builder
+            .add(ApplyTwoBodyLossSystem, "two_body_loss", &["collisions"]);
     }
     fn deps(&self) -> Vec<Box<dyn Plugin>> {
         Vec::new()
     }
 }
 
+#[cfg(test)]
 pub mod tests {
     #[allow(unused_imports)]
     use super::*;
@@ -349,6 +559,32 @@ pub mod tests {
         assert_eq!(id7, 0);
     }
 
+    #[test]
+    fn test_periodic_pos_to_id() {
+        let bounds = PeriodicBounds {
+            box_size: Vector3::new(1.0, 1.0, 1.0),
+        };
+        let width = 0.25;
+        let cells = periodic_cell_number(Some(&bounds), width).unwrap();
+        assert_eq!(cells, Vector3::new(4, 4, 4));
+
+        // Cells tile the primary cell, without any atoms falling outside the grid.
+        assert_eq!(periodic_pos_to_id(Vector3::new(-0.49, -0.49, -0.49), cells, width), 0);
+        assert_eq!(periodic_pos_to_id(Vector3::new(0.49, 0.49, 0.49), cells, width), 63);
+
+        // An atom that has just left the box through one face is binned in the cell on the opposite face.
+        let inside = Vector3::new(-0.45, 0.1, 0.3);
+        let image = inside + Vector3::new(1.0, 0.0, -2.0);
+        assert_eq!(
+            periodic_pos_to_id(image, cells, width),
+            periodic_pos_to_id(inside, cells, width)
+        );
+        assert_ne!(
+            periodic_pos_to_id(Vector3::new(0.45, 0.1, 0.3), cells, width),
+            periodic_pos_to_id(inside, cells, width)
+        );
+    }
+
     #[test]
     fn test_do_collision() {
         // do this test muliple times since there is a random element involved in do_collision
@@ -359,7 +595,7 @@ pub mod tests {
             let ptoti = v1 + v2;
             let energyi = 0.5 * (v1.norm_squared() + v2.norm_squared());
 
-            let (v1new, v2new) = do_collision(v1, v2);
+            let (v1new, v2new) = do_collision(v1, v2, &mut rand::thread_rng());
 
             //energy and momentum after
             let ptotf = v1new + v2new;
@@ -377,11 +613,12 @@ pub mod tests {
     fn collision_rate() {
         use assert_approx_eq::assert_approx_eq;
 
-        let vel = Vector3::new(1.0, 0.0, 0.0);
+        let vel: Vector3<f64> = Vector3::new(1.0, 0.0, 0.0);
         const MACRO_ATOM_NUMBER: usize = 100;
-        let mut velocities: Vec<Velocity> = vec![Velocity { vel }; MACRO_ATOM_NUMBER];
+        let mut velocities: Vec<Velocity> = vec![Velocity { vel: vel.cast() }; MACRO_ATOM_NUMBER];
         let mut collision_box = CollisionBox {
             velocities: velocities.iter_mut().collect(),
+            weights: vec![1.0; MACRO_ATOM_NUMBER],
             ..Default::default()
         };
 
@@ -393,7 +630,7 @@ pub mod tests {
             collision_limit: 10_000.0,
         };
         let dt = 1e-3;
-        collision_box.do_collisions(params, dt);
+        collision_box.do_collisions(params, dt, &mut rand::thread_rng());
         assert_eq!(collision_box.particle_number, MACRO_ATOM_NUMBER as i32);
         let atom_number = params.macroparticle * MACRO_ATOM_NUMBER as f64;
         assert_eq!(collision_box.atom_number, atom_number);
@@ -407,6 +644,173 @@ pub mod tests {
         );
     }
 
+    /// Test that a box of super-atoms has the same density, and the same rate of real collisions, as the atoms
+    /// that they represent.
+    #[test]
+    fn super_atom_density() {
+        use assert_approx_eq::assert_approx_eq;
+
+        let vel: Vector3<f64> = Vector3::new(1.0, 0.0, 0.0);
+        let params = CollisionParameters {
+            macroparticle: 1.0,
+            box_number: 1,
+            box_width: 1e-3,
+            sigma: 1e-8,
+            collision_limit: 10_000.0,
+        };
+        let dt = 1e-3;
+
+        let collide = |particles: usize, n_real: f64| {
+            let mut velocities: Vec<Velocity> = vec![Velocity { vel: vel.cast() }; particles];
+            let mut collision_box = CollisionBox {
+                velocities: velocities.iter_mut().collect(),
+                weights: vec![n_real; particles],
+                ..Default::default()
+            };
+            collision_box.do_collisions(params, dt, &mut rand::thread_rng());
+            (collision_box.density, collision_box.expected_collision_number * n_real)
+        };
+        let (full_density, full_collisions) = collide(1000, 1.0);
+        let (super_density, super_collisions) = collide(50, 20.0);
+
+        assert_approx_eq!(full_density, 1000.0 / params.box_width.powi(3), 1e-6 * full_density);
+        assert_approx_eq!(super_density, full_density, 1e-6 * full_density);
+        assert_approx_eq!(super_collisions, full_collisions, 1e-6 * full_collisions);
+    }
+
+    #[test]
+    fn test_feshbach_cross_section() {
+        use assert_approx_eq::assert_approx_eq;
+
+        let resonance = FeshbachResonance {
+            background_scattering_length: 100.0 * 5.29e-11,
+            position: 0.1,
+            width: 1.0e-4,
+        };
+        let background = 8.0 * PI * resonance.background_scattering_length.powi(2);
+
+        // Far from the resonance, the cross-section is the background value.
+        assert_approx_eq!(resonance.cross_section(0.0), background, 1e-2 * background);
+        assert_approx_eq!(resonance.cross_section(1.0), background, 1e-2 * background);
+
+        // The cross-section diverges approaching the resonance from either side, within the width of the zero crossing.
+        let mut previous = (0.0, 0.0);
+        for detuning in [1.0e-5, 1.0e-6, 1.0e-7, 1.0e-8].iter() {
+            let above = resonance.cross_section(resonance.position + detuning);
+            let below = resonance.cross_section(resonance.position - detuning);
+            assert!(above > previous.0 && below > previous.1);
+            previous = (above, below);
+        }
+        assert!(previous.0 > 1.0e6 * background);
+        assert!(previous.1 > 1.0e6 * background);
+
+        // The scattering length crosses zero at position + width.
+        assert_approx_eq!(
+            resonance.scattering_length(resonance.position + resonance.width),
+            0.0,
+            1e-20
+        );
+    }
+
+    /// Tests that only particles which sample the magnetic field contribute to the mean field of a box.
+    #[test]
+    fn test_mean_field_of_sampled_particles() {
+        let mut collision_box = CollisionBox::default();
+        assert_eq!(collision_box.mean_field(), None);
+        collision_box.fields = vec![1.0e-3, 3.0e-3];
+        assert_eq!(collision_box.mean_field(), Some(2.0e-3));
+    }
+
+    fn two_body_loss_simulation(
+        atom_number: usize,
+        coefficient: f64,
+    ) -> crate::simulation::Simulation {
+        use rand::Rng;
+
+        let mut simulation_builder = SimulationBuilder::default();
+        simulation_builder.add_end_frame_systems();
+        simulation_builder.add_plugin(CollisionPlugin);
+        let mut sim = simulation_builder.build();
+        sim.world.insert(Timestep { delta: 1.0e-4 });
+        sim.world.insert(CollisionsTracker {
+            num_collisions: Vec::new(),
+            num_atoms: Vec::new(),
+            num_particles: Vec::new(),
+        });
+        sim.world.insert(CollisionParameters {
+            macroparticle: 1.0,
+            box_number: 1,
+            box_width: 1.0e-3,
+            sigma: 0.0,
+            collision_limit: 10_000.0,
+        });
+        sim.world.insert(TwoBodyLoss { coefficient });
+
+        // A uniform cloud of stationary atoms filling a single collision cell.
+        let mut rng = rand::thread_rng();
+        for _ in 0..atom_number {
+            let pos = Vector3::new(
+                rng.gen_range(-0.5e-3..0.5e-3),
+                rng.gen_range(-0.5e-3..0.5e-3),
+                rng.gen_range(-0.5e-3..0.5e-3),
+            );
+            sim.world
+                .create_entity()
+                .with(Velocity {
+                    vel: Vector3::new(0.0, 0.0, 0.0).cast(),
+                })
+                .with(Position { pos: pos.cast() })
+                .with(Atom)
+                .with(Force::new())
+                .with(Mass { value: 87.0 })
+                .with(NewlyCreated)
+                .build();
+        }
+        sim
+    }
+
+    /// Test that the density of a uniform cloud follows `n(t) = n0 / (1 + beta n0 t)`.
+    #[test]
+    fn test_two_body_loss_follows_rate_equation() {
+        let n0 = 10_000;
+        let volume = 1.0e-9;
+        // beta n0 = 100 Hz.
+        let coefficient = 100.0 * volume / n0 as f64;
+        let mut sim = two_body_loss_simulation(n0, coefficient);
+
+        let dt = 1.0e-4;
+        let mut steps = 0;
+        for &time in [0.01, 0.05].iter() {
+            while (steps as f64) * dt < time - 0.5 * dt {
+                sim.step();
+                steps += 1;
+            }
+            let number = sim.world.read_storage::<Atom>().join().count() as f64;
+            let expected = n0 as f64 / (1.0 + 100.0 * time);
+            assert!(
+                (number - expected).abs() < 5.0 * expected.sqrt() + 0.02 * expected,
+                "{} atoms at t = {} s, expected {}",
+                number,
+                time,
+                expected
+            );
+        }
+    }
+
+    /// Test that a dilute cloud experiences negligible two-body loss.
+    #[test]
+    fn test_two_body_loss_is_negligible_at_low_density() {
+        let volume = 1.0e-9;
+        // The same coefficient as a cloud of 10000 atoms, but with only 10 atoms, so beta n = 0.1 Hz.
+        let coefficient = 100.0 * volume / 10_000.0;
+        let mut sim = two_body_loss_simulation(10, coefficient);
+        for _ in 0..1000 {
+            sim.step();
+        }
+        // Over 0.1 s, each atom is lost with a chance of 1%.
+        assert!(sim.world.read_storage::<Atom>().join().count() >= 8);
+    }
+
     /// Test that the system runs and causes nearby atoms to collide. More of an integration test than a unit test.
     #[test]
     fn test_collisions() {
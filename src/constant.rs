@@ -24,5 +24,8 @@ pub const AMU: f64 = 1.6605e-27;
 /// Speed of light in SI units of m/s
 pub const C: f64 = 299792458.0;
 
+/// Vacuum permeability in SI units of T m/A
+pub const MU0: f64 = 1.25663706212e-6;
+
 /// Sqrt of 2
 pub const SQRT2: f64 = std::f64::consts::SQRT_2;
@@ -0,0 +1,139 @@
+//! Applies a user-defined force field, for prototyping new potentials without writing a new system.
+
+use crate::atom::{Atom, Force, Position, Velocity};
+use crate::integrator::INTEGRATE_POSITION_SYSTEM_NAME;
+use crate::parallel::{ForceSerial, MaybeParJoin};
+use crate::simulation::Plugin;
+use nalgebra::Vector3;
+use specs::prelude::*;
+
+/// A closure that calculates the force on an atom from its position and velocity, see [CustomForceField].
+pub type ForceFieldFn = Box<dyn Fn(Vector3<f64>, Vector3<f64>) -> Vector3<f64> + Send + Sync>;
+
+/// A resource holding a user-defined force field.
+///
+/// The closure is called with the position (in m) and velocity (in m/s) of each atom, and returns the force
+/// on the atom, in SI units of N. The closure is called for many atoms in parallel, so it must be `Send + Sync`.
+pub struct CustomForceField {
+    pub f: ForceFieldFn,
+}
+impl CustomForceField {
+    pub fn new<F>(f: F) -> Self
+    where
+        F: Fn(Vector3<f64>, Vector3<f64>) -> Vector3<f64> + Send + Sync + 'static,
+    {
+        CustomForceField { f: Box::new(f) }
+    }
+}
+
+/// This system adds the force of the [CustomForceField], if present, to each atom.
+pub struct ApplyCustomForceSystem;
+impl<'a> System<'a> for ApplyCustomForceSystem {
+    type SystemData = (
+        WriteStorage<'a, Force>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Velocity>,
+        ReadStorage<'a, Atom>,
+        Option<Read<'a, CustomForceField>>,
+        Option<Read<'a, ForceSerial>>,
+    );
+
+    fn run(
+        &mut self,
+        (mut forces, positions, velocities, atoms, field, force_serial): Self::SystemData,
+    ) {
+        let field = match field {
+            Some(field) => field,
+            None => return,
+        };
+        (&mut forces, &positions, &velocities, &atoms).maybe_par_for_each(
+            force_serial.is_some(),
+            |(force, pos, vel, _)| {
+                force.force += (field.f)(pos.pos.cast(), vel.vel.cast()).cast();
+            },
+        );
+    }
+}
+
+/// This plugin applies the [CustomForceField] resource, if present.
+///
+/// See also [crate::custom_force].
+pub struct CustomForcePlugin;
+impl Plugin for CustomForcePlugin {
+    fn build(&self, builder: &mut crate::simulation::SimulationBuilder) {
+        builder.dispatcher_builder.add(
+            ApplyCustomForceSystem,
+            "add_custom_force",
+            &["clear", INTEGRATE_POSITION_SYSTEM_NAME],
+        );
+    }
+    fn deps(&self) -> Vec<Box<dyn Plugin>> {
+        Vec::new()
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use crate::atom::Mass;
+    use crate::constant::{AMU, PI};
+    use crate::initiate::NewlyCreated;
+    use crate::integrator::Timestep;
+    use crate::simulation::SimulationBuilder;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_harmonic_custom_force_oscillates_at_trap_frequency() {
+        let mut sim_builder = SimulationBuilder::default();
+        sim_builder.add_plugin(CustomForcePlugin);
+        let mut sim = sim_builder.build();
+
+        let mass = 87.0;
+        let frequency = 100.0;
+        let spring_constant = mass * AMU * (2.0 * PI * frequency).powi(2);
+        sim.world.insert(CustomForceField::new(move |pos, _vel| {
+            -spring_constant * pos
+        }));
+        let dt = 1.0e-6;
+        sim.world.insert(Timestep { delta: dt });
+
+        let amplitude: f64 = 1.0e-3;
+        let atom = sim
+            .world
+            .create_entity()
+            .with(Position {
+                pos: Vector3::new(amplitude, 0.0, 0.0).cast(),
+            })
+            .with(Velocity {
+                vel: Vector3::new(0.0, 0.0, 0.0).cast(),
+            })
+            .with(Force::new())
+            .with(Mass { value: mass })
+            .with(Atom)
+            .with(NewlyCreated)
+            .build();
+
+        // Record the times at which the atom crosses the origin, which are half a period apart.
+        let mut crossings = Vec::new();
+        let mut last_x = amplitude;
+        for step in 1..=50_000 {
+            sim.step();
+            let x = sim
+                .world
+                .read_storage::<Position>()
+                .get(atom)
+                .unwrap()
+                .pos
+                .cast::<f64>()[0];
+            if x.signum() != last_x.signum() {
+                crossings.push(step as f64 * dt);
+            }
+            last_x = x;
+        }
+
+        assert_eq!(crossings.len(), 10);
+        let half_period = (crossings[9] - crossings[0]) / 9.0;
+        assert_approx_eq!(1.0 / (2.0 * half_period), frequency, 1e-3 * frequency);
+    }
+}
@@ -4,10 +4,16 @@
 //! world resource will be deleted by the [DestroyOutOfBoundAtomsSystem](struct.DestroyOutOfBoundAtomsSystem.html).
 //! Removing atoms that will not be of interest for further simulation (eg, those that escape the trapping region)
 //! ensures that CPU time will not be wasted simulating them.
+//!
+//! Collisions with the background gas, which limit the lifetime of atoms in a trap, can be modelled by inserting a
+//! [BackgroundLoss] resource.
 extern crate specs;
+use rand::{Rng, RngCore};
 use specs::prelude::*;
 
-use crate::{simulation::Plugin, integrator::{INTEGRATE_POSITION_SYSTEM_NAME}};
+use crate::atom::Atom;
+use crate::rng::DeterministicRng;
+use crate::{simulation::Plugin, integrator::{Timestep, INTEGRATE_POSITION_SYSTEM_NAME}};
 
 /// A system that deletes entities which have been marked for destruction using the [ToBeDestroyed](struct.ToBeDestroyed.html) component.
 pub struct DeleteToBeDestroyedEntitiesSystem;
@@ -21,7 +27,49 @@ impl<'a> System<'a> for DeleteToBeDestroyedEntitiesSystem {
     }
 }
 
-/// This plugin implements removal of atoms marked as `ToBeDestroyed`.
+/// A resource that enables the loss of atoms through collisions with the background gas.
+///
+/// Each atom is removed from the simulation with a chance `1 - exp(-rate dt)` each step, so that the number of
+/// atoms decays exponentially with lifetime `1 / rate`. A rate of zero disables the loss.
+#[derive(Clone, Copy, Debug)]
+pub struct BackgroundLoss {
+    /// Rate of loss from background gas collisions, in SI units of Hz.
+    pub rate: f64,
+}
+
+/// A system that randomly deletes atoms at the rate given by the [BackgroundLoss] resource.
+///
+/// The losses are drawn from the [DeterministicRng] resource if it is present.
+pub struct ApplyBackgroundLossSystem;
+impl<'a> System<'a> for ApplyBackgroundLossSystem {
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, Atom>,
+        Option<Read<'a, BackgroundLoss>>,
+        ReadExpect<'a, Timestep>,
+        Option<Write<'a, DeterministicRng>>,
+    );
+
+    fn run(&mut self, (ents, atoms, loss, timestep, mut deterministic): Self::SystemData) {
+        let rate = match loss {
+            Some(loss) if loss.rate > 0.0 => loss.rate,
+            _ => return,
+        };
+        let chance = 1.0 - (-rate * timestep.delta).exp();
+        let mut thread_rng = rand::thread_rng();
+        let rng: &mut dyn RngCore = match deterministic.as_mut() {
+            Some(deterministic) => &mut **deterministic,
+            None => &mut thread_rng,
+        };
+        for (entity, _) in (&ents, &atoms).join() {
+            if rng.gen_range(0.0..1.0) < chance {
+                ents.delete(entity).expect("Could not delete entity");
+            }
+        }
+    }
+}
+
+/// This plugin implements removal of atoms marked as `ToBeDestroyed`, and of atoms lost to the background gas.
 /// 
 /// See also [crate::destructor].
 pub struct DestroyAtomsPlugin;
@@ -32,6 +80,11 @@ impl Plugin for DestroyAtomsPlugin {
             "",
             &[INTEGRATE_POSITION_SYSTEM_NAME],
         );
+        builder.dispatcher_builder.add(
+            ApplyBackgroundLossSystem,
+            "background_loss",
+            &[INTEGRATE_POSITION_SYSTEM_NAME],
+        );
     }
     fn deps(&self) -> Vec::<Box<dyn Plugin>> {
         Vec::new()
@@ -48,6 +101,7 @@ impl Component for ToBeDestroyed {
     type Storage = NullStorage<Self>;
 }
 
+#[cfg(test)]
 pub mod tests {
     // These imports are actually needed! The compiler is getting confused and warning they are not.
     #[allow(unused_imports)]
@@ -81,4 +135,61 @@ pub mod tests {
         assert!(positions.get(test_entity1).is_some());
         assert!(positions.get(test_entity2).is_none());
     }
+
+    fn count_surviving_atoms(rate: f64, n_atoms: usize, steps: &[usize], dt: f64) -> Vec<usize> {
+        use crate::atom::{Force, Mass, Velocity};
+        use crate::simulation::SimulationBuilder;
+
+        let mut sim = SimulationBuilder::default().build();
+        sim.world.insert(Timestep { delta: dt });
+        sim.world.insert(BackgroundLoss { rate });
+        for _ in 0..n_atoms {
+            sim.world
+                .create_entity()
+                .with(Position::new())
+                .with(Velocity {
+                    vel: Vector3::new(0.0, 0.0, 0.0).cast(),
+                })
+                .with(Force::new())
+                .with(Mass { value: 87.0 })
+                .with(Atom)
+                .build();
+        }
+        let mut counts = Vec::new();
+        let mut n = 0;
+        for &step in steps.iter() {
+            while n < step {
+                sim.step();
+                n += 1;
+            }
+            counts.push(sim.world.read_storage::<Atom>().join().count());
+        }
+        counts
+    }
+
+    #[test]
+    fn test_background_loss_is_exponential() {
+        let (rate, n_atoms, dt) = (1.0e3, 4000, 1.0e-5);
+        let steps = [50, 100, 200];
+        let counts = count_surviving_atoms(rate, n_atoms, &steps, dt);
+        for (step, count) in steps.iter().zip(counts.iter()) {
+            // The number of surviving atoms is binomially distributed, with N p (1 - p) variance.
+            let p = (-rate * *step as f64 * dt).exp();
+            let expected = n_atoms as f64 * p;
+            let sigma = (n_atoms as f64 * p * (1.0 - p)).sqrt();
+            assert!(
+                (*count as f64 - expected).abs() < 5.0 * sigma,
+                "{} atoms after {} steps, expected {}",
+                count,
+                step,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_zero_background_loss() {
+        let counts = count_surviving_atoms(0.0, 100, &[100], 1.0e-5);
+        assert_eq!(counts, vec![100]);
+    }
 }
@@ -0,0 +1,104 @@
+//! Diagnostics that summarise the motion of the whole cloud.
+
+use crate::atom::{total_weight, Atom, Mass, Position, StatisticalWeight, SuperAtomWeight, Velocity};
+use crate::constant;
+use nalgebra::Vector3;
+use specs::prelude::*;
+
+/// The total angular momentum of the atoms about an axis through `center`, in SI units of kg m^2/s.
+///
+/// Sums `m (r - center) x v . axis` over all atoms with a [Position], [Velocity] and [Mass], where the [Mass] is
+/// converted from amu to kg. Each atom is counted with its [total_weight], so that the result is the angular
+/// momentum of the real atoms. Use this to track the rotation of a cloud that is stirred, or held in a rotating trap.
+///
+/// # Arguments
+///
+/// `axis`: direction of the axis of rotation, which need not be normalized.
+///
+/// `center`: a point on the axis, in SI units of m.
+///
+/// Panics if the axis is zero.
+pub fn angular_momentum(world: &World, axis: Vector3<f64>, center: Vector3<f64>) -> f64 {
+    let axis = axis
+        .try_normalize(0.0)
+        .expect("The axis of the angular momentum must be non-zero.");
+    let atoms = world.read_storage::<Atom>();
+    let positions = world.read_storage::<Position>();
+    let velocities = world.read_storage::<Velocity>();
+    let masses = world.read_storage::<Mass>();
+    let weights = world.read_storage::<StatisticalWeight>();
+    let super_atoms = world.read_storage::<SuperAtomWeight>();
+    (
+        &atoms,
+        &positions,
+        &velocities,
+        &masses,
+        weights.maybe(),
+        super_atoms.maybe(),
+    )
+        .join()
+        .map(|(_, pos, vel, mass, weight, super_atom)| {
+            let r = pos.pos.cast::<f64>() - center;
+            let l = mass.value * constant::AMU * r.cross(&vel.vel.cast::<f64>());
+            total_weight(weight, super_atom) * l.dot(&axis)
+        })
+        .sum()
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_angular_momentum_of_circular_flow() {
+        let mut world = World::new();
+        world.register::<Atom>();
+        world.register::<Position>();
+        world.register::<Velocity>();
+        world.register::<Mass>();
+        world.register::<StatisticalWeight>();
+        world.register::<SuperAtomWeight>();
+
+        // Atoms in rigid rotation with angular velocity omega about the z axis through the center.
+        let center = Vector3::new(1.0e-3, -2.0e-3, 0.5e-3);
+        let omega = 2.0 * constant::PI * 100.0;
+        let radius = 1.0e-4;
+        let mass = 87.0;
+        let number = 12;
+        for i in 0..number {
+            let angle = 2.0 * constant::PI * i as f64 / number as f64;
+            let r = radius * Vector3::new(angle.cos(), angle.sin(), 0.0);
+            let v = omega * Vector3::z().cross(&r);
+            world
+                .create_entity()
+                .with(Position {
+                    pos: (center + r).cast(),
+                })
+                .with(Velocity { vel: v.cast() })
+                .with(Mass { value: mass })
+                .with(Atom)
+                .build();
+        }
+
+        let expected = number as f64 * mass * constant::AMU * radius.powi(2) * omega;
+        assert_approx_eq!(
+            angular_momentum(&world, Vector3::z(), center),
+            expected,
+            1e-9 * expected
+        );
+        // The axis need not be normalized, and reversing it reverses the sign.
+        assert_approx_eq!(
+            angular_momentum(&world, -3.0 * Vector3::z(), center),
+            -expected,
+            1e-9 * expected
+        );
+        // There is no rotation about a perpendicular axis.
+        assert_approx_eq!(
+            angular_momentum(&world, Vector3::x(), center),
+            0.0,
+            1e-9 * expected
+        );
+    }
+}
@@ -0,0 +1,295 @@
+//! Standard figures of merit for atoms held in a harmonic trap, for example for evaporation modelling.
+//!
+//! The trap frequencies may be obtained from a [crate::dipole::parametric_scan], or calculated for a single focused
+//! beam with [waist_sweep].
+
+use crate::constant::{BOLTZCONST, PI};
+use crate::dipole::Polarizability;
+use crate::gravity::Gravity;
+use crate::laser_cooling::transition::AtomicTransition;
+
+/// The depth and trap frequencies of a single-beam dipole trap, see [waist_sweep].
+#[derive(Clone, Copy, Debug)]
+pub struct TrapSummary {
+    /// The `1/e^2` intensity radius of the beam at the focus, in SI units of m.
+    pub waist: f64,
+    /// Depth of the trap, in SI units of J.
+    pub depth: f64,
+    /// Trap frequency perpendicular to the beam, in SI units of Hz.
+    pub radial_frequency: f64,
+    /// Trap frequency along the beam, in SI units of Hz.
+    pub axial_frequency: f64,
+}
+impl TrapSummary {
+    /// The distance by which gravity pulls the trap minimum below the focus of a horizontal beam, `g / omega_r^2`,
+    /// in SI units of m.
+    ///
+    /// The sag is zero without gravity, see [Gravity::zero], even for a repulsive beam.
+    pub fn gravitational_sag(&self, gravity: &Gravity) -> f64 {
+        if gravity.is_zero() {
+            return 0.0;
+        }
+        gravity.acceleration.norm() / (2.0 * PI * self.radial_frequency).powi(2)
+    }
+}
+
+/// Calculates the depth and trap frequencies of a single focused beam.
+///
+/// Close to the focus, the potential is harmonic, with `U = -U0 (1 - 2 r^2 / w^2 - z^2 / z_R^2)`. The depth is
+/// `U0 = alpha I0`, where `I0 = 2 P / (pi w^2)` is the peak intensity and `z_R = pi w^2 / lambda` is the Rayleigh
+/// range.
+///
+/// # Arguments
+///
+/// `power`: power of the beam, in SI units of W.
+///
+/// `wavelength`: wavelength of the beam, in SI units of m.
+///
+/// `waist`: `1/e^2` intensity radius of the beam at the focus, in SI units of m.
+///
+/// `polarizability`: polarizability of the atom at the wavelength of the beam.
+///
+/// `mass`: mass of an atom, in SI units of kg.
+pub fn gaussian_trap_summary(
+    power: f64,
+    wavelength: f64,
+    waist: f64,
+    polarizability: &Polarizability,
+    mass: f64,
+) -> TrapSummary {
+    let depth = polarizability.prefactor * 2.0 * power / (PI * waist.powi(2));
+    let rayleigh_range = PI * waist.powi(2) / wavelength;
+    TrapSummary {
+        waist,
+        depth,
+        radial_frequency: (4.0 * depth / (mass * waist.powi(2))).sqrt() / (2.0 * PI),
+        axial_frequency: (2.0 * depth / (mass * rayleigh_range.powi(2))).sqrt() / (2.0 * PI),
+    }
+}
+
+/// Calculates the depth and trap frequencies of a single focused beam for each of a list of waists, at fixed power.
+///
+/// See [gaussian_trap_summary]. The depth scales as `1/w^2`, the radial frequency as `1/w^2` and the axial
+/// frequency as `1/w^3`. A beam blue-detuned from the transition repels the atoms, and has a negative depth and
+/// NaN frequencies.
+///
+/// # Arguments
+///
+/// `power`: power of the beam, in SI units of W.
+///
+/// `wavelength`: wavelength of the beam, in SI units of m.
+///
+/// `mass`: mass of an atom, in SI units of kg.
+///
+/// `waists`: the `1/e^2` intensity radii of the beam at the focus, in SI units of m.
+///
+/// # Generic Arguments
+///
+/// * `T`: The strong optical transition that determines the polarizability of the atom.
+pub fn waist_sweep<T>(power: f64, wavelength: f64, mass: f64, waists: &[f64]) -> Vec<TrapSummary>
+where
+    T: AtomicTransition,
+{
+    let polarizability = Polarizability::calculate_for(wavelength, T::wavelength(), T::linewidth());
+    waists
+        .iter()
+        .map(|&waist| gaussian_trap_summary(power, wavelength, waist, &polarizability, mass))
+        .collect()
+}
+
+/// Calculates the effective volume of a harmonic trap, `V_eff = N / n_0`, where `n_0` is the peak density.
+///
+/// For a thermal cloud, `V_eff = (2 pi k_B T / m)^(3/2) / (omega_x omega_y omega_z)`.
+///
+/// # Arguments
+///
+/// `frequencies`: trap frequencies along each axis, in SI units of Hz.
+///
+/// `temperature`: temperature of the atoms, in SI units of K.
+///
+/// `mass`: mass of an atom, in SI units of kg.
+///
+/// Returns the effective volume, in SI units of m^3.
+pub fn effective_volume(frequencies: [f64; 3], temperature: f64, mass: f64) -> f64 {
+    let angular_product: f64 = frequencies.iter().map(|f| 2.0 * PI * f).product();
+    (2.0 * PI * BOLTZCONST * temperature / mass).powf(1.5) / angular_product
+}
+
+/// Calculates the peak elastic collision rate of atoms in a harmonic trap, `n_0 sigma v_rel`.
+///
+/// The peak density is `n_0 = N / V_eff`, see [effective_volume], and `v_rel = sqrt(16 k_B T / (pi m))` is the
+/// mean relative speed of two atoms in a thermal cloud.
+///
+/// # Arguments
+///
+/// `frequencies`: trap frequencies along each axis, in SI units of Hz.
+///
+/// `temperature`: temperature of the atoms, in SI units of K.
+///
+/// `mass`: mass of an atom, in SI units of kg.
+///
+/// `number`: number of trapped atoms.
+///
+/// `cross_section`: elastic collision cross section, in SI units of m^2.
+///
+/// Returns the collision rate, in SI units of 1/s.
+pub fn peak_collision_rate(
+    frequencies: [f64; 3],
+    temperature: f64,
+    mass: f64,
+    number: f64,
+    cross_section: f64,
+) -> f64 {
+    let peak_density = number / effective_volume(frequencies, temperature, mass);
+    let relative_speed = (16.0 * BOLTZCONST * temperature / (PI * mass)).sqrt();
+    peak_density * cross_section * relative_speed
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use crate::atom::Position;
+    use crate::constant::{AMU, GC};
+    use crate::laser::gaussian::{get_gaussian_beam_intensity, GaussianBeam};
+    use crate::species::Rubidium87_780D2;
+    use assert_approx_eq::assert_approx_eq;
+    use nalgebra::Vector3;
+
+    #[test]
+    fn test_effective_volume_matches_integral_of_boltzmann_factor() {
+        let frequencies = [100.0, 150.0, 400.0];
+        let temperature = 1.0e-6;
+        let mass = 87.0 * AMU;
+
+        // V_eff is the integral of exp(-U / k_B T), which is separable for a harmonic trap.
+        let mut volume = 1.0;
+        for frequency in frequencies.iter() {
+            let omega = 2.0 * PI * frequency;
+            let width = (BOLTZCONST * temperature / (mass * omega.powi(2))).sqrt();
+            let n = 10_000;
+            let dx = 20.0 * width / n as f64;
+            let integral: f64 = (0..n)
+                .map(|i| {
+                    let x = -10.0 * width + (i as f64 + 0.5) * dx;
+                    (-0.5 * mass * omega.powi(2) * x.powi(2) / (BOLTZCONST * temperature)).exp()
+                })
+                .sum::<f64>()
+                * dx;
+            volume *= integral;
+        }
+
+        let v_eff = effective_volume(frequencies, temperature, mass);
+        assert_approx_eq!(v_eff, volume, v_eff * 1e-9);
+    }
+
+    #[test]
+    fn test_peak_collision_rate() {
+        let frequencies = [100.0, 100.0, 100.0];
+        let temperature = 1.0e-6;
+        let mass = 87.0 * AMU;
+        let number = 1.0e6;
+        let cross_section = 8.0 * PI * (100.0 * 5.29e-11_f64).powi(2);
+
+        let rate = peak_collision_rate(frequencies, temperature, mass, number, cross_section);
+        let density = number / effective_volume(frequencies, temperature, mass);
+        let mean_speed = (8.0 * BOLTZCONST * temperature / (PI * mass)).sqrt();
+        assert_approx_eq!(
+            rate,
+            2.0_f64.sqrt() * density * cross_section * mean_speed,
+            rate * 1e-12
+        );
+        assert_approx_eq!(
+            peak_collision_rate(frequencies, temperature, mass, 2.0 * number, cross_section),
+            2.0 * rate,
+            rate * 1e-12
+        );
+    }
+
+    #[test]
+    fn test_waist_sweep_scaling() {
+        let power = 1.0;
+        let wavelength = 1064.0e-9;
+        let mass = 87.0 * AMU;
+        let waists = [20.0e-6, 40.0e-6, 80.0e-6];
+        let summaries = waist_sweep::<Rubidium87_780D2>(power, wavelength, mass, &waists);
+        assert_eq!(summaries.len(), waists.len());
+
+        let reference = summaries[0];
+        assert!(reference.depth > 0.0);
+        for summary in summaries.iter() {
+            let ratio = summary.waist / reference.waist;
+            assert_approx_eq!(
+                summary.depth,
+                reference.depth / ratio.powi(2),
+                reference.depth * 1e-12
+            );
+            assert_approx_eq!(
+                summary.radial_frequency,
+                reference.radial_frequency / ratio.powi(2),
+                reference.radial_frequency * 1e-12
+            );
+            assert_approx_eq!(
+                summary.axial_frequency,
+                reference.axial_frequency / ratio.powi(3),
+                reference.axial_frequency * 1e-12
+            );
+        }
+    }
+
+    #[test]
+    fn test_gravitational_sag() {
+        let polarizability = Polarizability::calculate_for(1064.0e-9, 780.0e-9, 6.065e6);
+        let summary = gaussian_trap_summary(2.0, 1064.0e-9, 50.0e-6, &polarizability, 87.0 * AMU);
+        let omega = 2.0 * PI * summary.radial_frequency;
+        assert_approx_eq!(
+            summary.gravitational_sag(&Gravity::default()),
+            GC / omega.powi(2),
+            1e-12 * GC / omega.powi(2)
+        );
+        assert_eq!(summary.gravitational_sag(&Gravity::zero()), 0.0);
+
+        // A repulsive beam has no trap frequency, but there is still no sag without gravity.
+        let blue = Polarizability::calculate_for(532.0e-9, 780.0e-9, 6.065e6);
+        let repulsive = gaussian_trap_summary(2.0, 532.0e-9, 50.0e-6, &blue, 87.0 * AMU);
+        assert!(repulsive.radial_frequency.is_nan());
+        assert_eq!(repulsive.gravitational_sag(&Gravity::zero()), 0.0);
+    }
+
+    #[test]
+    fn test_trap_summary_matches_beam_potential() {
+        let (power, wavelength, waist) = (2.0, 1064.0e-9, 50.0e-6);
+        let mass = 87.0 * AMU;
+        let polarizability = Polarizability::calculate_for(wavelength, 780.0e-9, 6.065e6);
+        let summary = gaussian_trap_summary(power, wavelength, waist, &polarizability, mass);
+
+        let beam = GaussianBeam::from_power_with_ellipticity_and_rayleigh_range(
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::z(),
+            power,
+            waist / 2.0_f64.sqrt(),
+            wavelength,
+            0.0,
+        );
+        let potential = |pos: Vector3<f64>| {
+            -polarizability.prefactor
+                * get_gaussian_beam_intensity(&beam, &Position { pos: pos.cast() }, None, None)
+        };
+        let centre = Vector3::new(0.0, 0.0, 0.0);
+        assert_approx_eq!(-potential(centre), summary.depth, summary.depth * 1e-9);
+
+        // The trap frequencies follow from the curvature of the potential at the focus.
+        for &(axis, frequency, step) in [
+            (Vector3::x(), summary.radial_frequency, 1.0e-7_f64),
+            (Vector3::z(), summary.axial_frequency, 1.0e-5_f64),
+        ]
+        .iter()
+        {
+            let curvature = (potential(step * axis) + potential(-step * axis)
+                - 2.0 * potential(centre))
+                / step.powi(2);
+            let expected = (curvature / mass).sqrt() / (2.0 * PI);
+            assert_approx_eq!(frequency, expected, expected * 1e-3);
+        }
+    }
+}
@@ -4,13 +4,18 @@ use specs::{Join, ReadStorage, System, WriteStorage};
 extern crate nalgebra;
 use crate::atom::Force;
 use crate::dipole::DipoleLight;
+use crate::dipole::transitions::{dipole_prefactor, MultiTransitionPolarizability};
 use crate::dipole::Polarizability;
 use crate::laser::index::LaserIndex;
+use crate::parallel::{ForceSerial, MaybeParJoin};
 
 /// Calculates forces exerted onto the atoms by dipole laser beams.
 ///
 /// It uses the `LaserIntensityGradientSamplers` and the properties of the `DipoleLight`
 /// to add the respective amount of force to `Force`
+///
+/// The force acts on atoms with either a [Polarizability] or a [MultiTransitionPolarizability], see
+/// [dipole_prefactor].
 pub struct ApplyDipoleForceSystem<const N: usize>;
 
 impl<'a, const N: usize> System<'a> for ApplyDipoleForceSystem<N> {
@@ -18,22 +23,44 @@ impl<'a, const N: usize> System<'a> for ApplyDipoleForceSystem<N> {
         ReadStorage<'a, DipoleLight>,
         ReadStorage<'a, LaserIndex>,
         ReadStorage<'a, Polarizability>,
+        ReadStorage<'a, MultiTransitionPolarizability>,
         ReadStorage<'a, LaserIntensityGradientSamplers<N>>,
         WriteStorage<'a, Force>,
+        Option<Read<'a, ForceSerial>>,
     );
 
     fn run(
         &mut self,
-        (dipole_light, dipole_index, polarizability, gradient_sampler, mut force): Self::SystemData,
+        (
+            dipole_light,
+            dipole_index,
+            polarizability,
+            multi_transition,
+            gradient_sampler,
+            mut force,
+            force_serial,
+        ): Self::SystemData,
     ) {
-        (&mut force, &polarizability, &gradient_sampler)
-            .par_join()
-            .for_each(|(force, polarizability, sampler)| {
-                for (index, _dipole) in (&dipole_index, &dipole_light).join() {
-                    force.force +=
-                        polarizability.prefactor * sampler.contents[index.index].gradient;
-                }
-            });
+        (
+            &mut force,
+            polarizability.maybe(),
+            multi_transition.maybe(),
+            &gradient_sampler,
+        )
+            .maybe_par_for_each(
+                force_serial.is_some(),
+                |(force, polarizability, multi_transition, sampler)| {
+                    if polarizability.is_none() && multi_transition.is_none() {
+                        return;
+                    }
+                    for (index, dipole) in (&dipole_index, &dipole_light).join() {
+                        let prefactor =
+                            dipole_prefactor(polarizability, multi_transition, dipole.wavelength)
+                                .unwrap_or_default();
+                        force.force += (prefactor * sampler.contents[index.index].gradient).cast();
+                    }
+                },
+            );
     }
 }
 
@@ -60,6 +87,7 @@ pub mod tests {
         test_world.register::<Force>();
         test_world.register::<LaserIntensityGradientSamplers<{ DEFAULT_BEAM_LIMIT }>>();
         test_world.register::<Polarizability>();
+        test_world.register::<MultiTransitionPolarizability>();
 
         let transition_linewidth = 32e6;
         let transition_lambda = 461e-9;
@@ -92,7 +120,11 @@ pub mod tests {
         system.run_now(&test_world);
         test_world.maintain();
         let sampler_storage = test_world.read_storage::<Force>();
-        let sim_result_force = sampler_storage.get(atom1).expect("Entity not found!").force;
+        let sim_result_force = sampler_storage
+            .get(atom1)
+            .expect("Entity not found!")
+            .force
+            .cast::<f64>();
 
         let transition_f = constant::C / transition_lambda;
         let actual_force = 3. * constant::PI * constant::C.powf(2.0)
@@ -115,6 +147,7 @@ pub mod tests {
         test_world.register::<Force>();
         test_world.register::<LaserIntensityGradientSamplers<{ DEFAULT_BEAM_LIMIT }>>();
         test_world.register::<Polarizability>();
+        test_world.register::<MultiTransitionPolarizability>();
 
         test_world
             .create_entity()
@@ -144,7 +177,11 @@ pub mod tests {
         system.run_now(&test_world);
         test_world.maintain();
         let sampler_storage = test_world.read_storage::<Force>();
-        let sim_result_force = sampler_storage.get(atom1).expect("Entity not found!").force;
+        let sim_result_force = sampler_storage
+            .get(atom1)
+            .expect("Entity not found!")
+            .force
+            .cast::<f64>();
 
         assert_approx_eq!(-6.386888332902177e-29, sim_result_force[0], 3e-30_f64);
         assert_approx_eq!(-3.11151847e-23, sim_result_force[1], 2e-24_f64);
@@ -160,9 +197,12 @@ pub mod tests {
         test_world.register::<Force>();
         test_world.register::<LaserIntensityGradientSamplers<{ DEFAULT_BEAM_LIMIT }>>();
         test_world.register::<Polarizability>();
+        test_world.register::<MultiTransitionPolarizability>();
         test_world.register::<crate::atom::Position>();
         test_world.register::<crate::laser::gaussian::GaussianBeam>();
         test_world.register::<crate::laser::frame::Frame>();
+        test_world.register::<crate::laser::intensity_gradient::GradientMethod>();
+        test_world.register::<crate::laser::gaussian::NonParaxialCorrection>();
 
         let power = 10.0;
         let e_radius = 60.0e-6 / (2.0_f64.sqrt());
@@ -174,6 +214,7 @@ pub mod tests {
             direction: Vector3::x(),
             rayleigh_range: crate::laser::gaussian::calculate_rayleigh_range(&1064.0e-9, &e_radius),
             ellipticity: 0.0,
+            focus_offset: 0.0,
         };
         test_world
             .create_entity()
@@ -197,6 +238,7 @@ pub mod tests {
             direction: Vector3::y(),
             rayleigh_range: crate::laser::gaussian::calculate_rayleigh_range(&1064.0e-9, &e_radius),
             ellipticity: 0.0,
+            focus_offset: 0.0,
         };
         test_world
             .create_entity()
@@ -231,7 +273,7 @@ pub mod tests {
             .build();
         let mut grad_system = laser::intensity_gradient::SampleGaussianLaserIntensityGradientSystem::<
             { DEFAULT_BEAM_LIMIT },
-        >;
+        >::default();
         let mut force_system = ApplyDipoleForceSystem::<{ DEFAULT_BEAM_LIMIT }>;
         grad_system.run_now(&test_world);
         test_world.maintain();
@@ -240,7 +282,11 @@ pub mod tests {
         let sampler_storage = test_world.read_storage::<Force>();
         let grad_sampler_storage =
             test_world.read_storage::<LaserIntensityGradientSamplers<{ DEFAULT_BEAM_LIMIT }>>();
-        let sim_result_force = sampler_storage.get(atom1).expect("Entity not found!").force;
+        let sim_result_force = sampler_storage
+            .get(atom1)
+            .expect("Entity not found!")
+            .force
+            .cast::<f64>();
         let _sim_result_grad = grad_sampler_storage
             .get(atom1)
             .expect("Entity not found!")
@@ -1,6 +1,6 @@
 //! A module that implements systems and components for dipole trapping in AtomECS.
 
-use specs::DispatcherBuilder;
+use crate::dispatch::SystemDispatcherBuilder;
 
 use crate::laser::LaserPlugin;
 use crate::{constant, simulation::Plugin};
@@ -9,7 +9,21 @@ use crate::laser::index::LaserIndex;
 use serde::{Deserialize, Serialize};
 use specs::prelude::*;
 
+pub mod analysis;
 pub mod force;
+pub mod overlap;
+pub mod parametric;
+pub mod stark;
+pub mod tilt;
+pub mod transitions;
+pub mod trapped;
+pub mod vector;
+
+pub use overlap::optimize_overlap;
+pub use parametric::parametric_scan;
+pub use tilt::{compensating_tilt, tilt_beam};
+pub use transitions::{magic_wavelength, DipoleTransition, MultiTransitionPolarizability};
+pub use trapped::{trapped_fraction, TransferEfficiency};
 
 /// A component marking the entity as laser beam for dipole forces and
 /// holding properties of the light
@@ -108,7 +122,7 @@ impl<const N: usize> Plugin for DipolePlugin<N> {
 ///
 /// `deps`: any dependencies that must be completed before the systems run.
 fn add_systems_to_dispatch<const N: usize>(
-    builder: &mut DispatcherBuilder<'static, 'static>,
+    builder: &mut impl SystemDispatcherBuilder,
     deps: &[&str],
 ) {
     builder.add(
@@ -116,6 +130,16 @@ fn add_systems_to_dispatch<const N: usize>(
         "apply_dipole_force",
         &["sample_intensity_gradient"],
     );
+    builder.add(
+        stark::CalculateAcStarkShiftSystem::<N>,
+        "calculate_ac_stark_shift",
+        &["sample_laser_intensity"],
+    );
+    builder.add(
+        vector::ApplyVectorDipoleForceSystem::<N>,
+        "apply_vector_dipole_force",
+        &["apply_dipole_force"],
+    );
     builder.add(
         crate::dipole::AttachIndexToDipoleLightSystem,
         "attach_dipole_index",
@@ -125,4 +149,8 @@ fn add_systems_to_dispatch<const N: usize>(
 
 fn register_components(world: &mut World) {
     world.register::<DipoleLight>();
+    world.register::<stark::AcStarkShift>();
+    world.register::<vector::DipolePolarization>();
+    world.register::<vector::VectorPolarizability>();
+    world.register::<transitions::MultiTransitionPolarizability>();
 }
@@ -0,0 +1,160 @@
+//! Diagnosing the alignment of crossed dipole traps.
+//!
+//! A crossed dipole trap is deepest when the foci of both beams coincide. If the beam axes do not
+//! intersect, or cross away from the foci, the trap is shallower than expected.
+
+use nalgebra::Vector3;
+
+use crate::atom::Position;
+use crate::constant::PI;
+use crate::dipole::Polarizability;
+use crate::laser::gaussian::{get_gaussian_beam_intensity, GaussianBeam};
+
+/// The fraction of the ideal trap depth below which the beams are considered misaligned.
+pub const OVERLAP_WARNING_THRESHOLD: f64 = 0.9;
+
+/// Describes the overlap of two dipole beams, see [analyse_overlap].
+#[derive(Clone, Copy, Debug)]
+pub struct BeamOverlap {
+    /// The point midway between the beam axes at their closest approach, in SI units of m.
+    pub closest_approach: Vector3<f64>,
+    /// The distance between the beam axes at their closest approach, in SI units of m.
+    pub axis_separation: f64,
+    /// The trap depth at the closest approach, in SI units of J.
+    pub depth: f64,
+    /// The trap depth if the foci of both beams coincided, in SI units of J.
+    pub ideal_depth: f64,
+}
+impl BeamOverlap {
+    /// The trap depth as a fraction of the ideal trap depth.
+    pub fn depth_ratio(&self) -> f64 {
+        self.depth / self.ideal_depth
+    }
+
+    /// Returns a warning if the beams do not cross near their foci, so that the trap depth is
+    /// less than [OVERLAP_WARNING_THRESHOLD] of the ideal depth.
+    pub fn warning(&self) -> Option<String> {
+        if self.depth_ratio() < OVERLAP_WARNING_THRESHOLD {
+            Some(format!(
+                "Dipole beams do not cross near their foci: the axes are separated by {:.3e} m at their closest approach, and the trap depth is reduced to {:.1}% of the ideal depth.",
+                self.axis_separation,
+                100.0 * self.depth_ratio()
+            ))
+        } else {
+            None
+        }
+    }
+}
+
+/// Calculates the closest approach of the axes of two dipole beams, and the trap depth there.
+///
+/// If the beams are parallel, the closest approach is taken nearest the focus of `beam_a`.
+pub fn analyse_overlap(
+    beam_a: &GaussianBeam,
+    beam_b: &GaussianBeam,
+    polarizability: &Polarizability,
+) -> BeamOverlap {
+    let dir_a = beam_a.direction.normalize();
+    let dir_b = beam_b.direction.normalize();
+    let focus_a = beam_a.intersection + beam_a.focus_offset * beam_a.direction;
+    let focus_b = beam_b.intersection + beam_b.focus_offset * beam_b.direction;
+
+    // Closest points on the axes, `focus_a + t * dir_a` and `focus_b + s * dir_b`.
+    let w = focus_a - focus_b;
+    let b = dir_a.dot(&dir_b);
+    let d = dir_a.dot(&w);
+    let e = dir_b.dot(&w);
+    let denominator = 1.0 - b * b;
+    let (t, s) = if denominator < f64::EPSILON {
+        (0.0, e)
+    } else {
+        ((b * e - d) / denominator, (e - b * d) / denominator)
+    };
+    let point_a = focus_a + t * dir_a;
+    let point_b = focus_b + s * dir_b;
+    let closest_approach = (point_a + point_b) / 2.0;
+
+    let position = Position {
+        pos: closest_approach.cast(),
+    };
+    let intensity = get_gaussian_beam_intensity(beam_a, &position, None, None)
+        + get_gaussian_beam_intensity(beam_b, &position, None, None);
+    let peak_intensity = |beam: &GaussianBeam| beam.power / (PI * beam.e_radius.powi(2));
+
+    BeamOverlap {
+        closest_approach,
+        axis_separation: (point_a - point_b).norm(),
+        depth: polarizability.prefactor * intensity,
+        ideal_depth: polarizability.prefactor * (peak_intensity(beam_a) + peak_intensity(beam_b)),
+    }
+}
+
+/// Calculates the point of closest approach of the axes of two dipole beams, and the trap depth there.
+///
+/// Prints a warning if the beams do not cross near their foci, see [BeamOverlap::warning].
+///
+/// Returns the closest approach in SI units of m, and the trap depth in SI units of J.
+pub fn optimize_overlap(
+    beam_a: &GaussianBeam,
+    beam_b: &GaussianBeam,
+    polarizability: &Polarizability,
+) -> (Vector3<f64>, f64) {
+    let overlap = analyse_overlap(beam_a, beam_b, polarizability);
+    if let Some(warning) = overlap.warning() {
+        eprintln!("Warning: {}", warning);
+    }
+    (overlap.closest_approach, overlap.depth)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use crate::laser::gaussian::calculate_rayleigh_range;
+    use assert_approx_eq::assert_approx_eq;
+
+    fn beam(direction: Vector3<f64>, intersection: Vector3<f64>) -> GaussianBeam {
+        let e_radius = 50.0e-6;
+        GaussianBeam {
+            intersection,
+            direction,
+            e_radius,
+            power: 5.0,
+            rayleigh_range: calculate_rayleigh_range(&1064.0e-9, &e_radius),
+            ellipticity: 0.0,
+            focus_offset: 0.0,
+        }
+    }
+
+    #[test]
+    fn test_offset_beams_report_closest_approach_and_warning() {
+        let polarizability = Polarizability::calculate_for(1064e-9, 780e-9, 6.065e6);
+        let offset = 50.0e-6;
+        let beam_a = beam(Vector3::x(), Vector3::new(0.0, 0.0, 0.0));
+        let beam_b = beam(Vector3::y(), Vector3::new(1.0e-3, 0.0, offset));
+
+        let overlap = analyse_overlap(&beam_a, &beam_b, &polarizability);
+        assert_approx_eq!(overlap.closest_approach[0], 1.0e-3, 1e-12);
+        assert_approx_eq!(overlap.closest_approach[1], 0.0, 1e-12);
+        assert_approx_eq!(overlap.closest_approach[2], offset / 2.0, 1e-12);
+        assert_approx_eq!(overlap.axis_separation, offset, 1e-12);
+        assert!(overlap.depth < overlap.ideal_depth);
+        assert!(overlap.warning().is_some());
+
+        let (point, depth) = optimize_overlap(&beam_a, &beam_b, &polarizability);
+        assert_eq!(point, overlap.closest_approach);
+        assert_eq!(depth, overlap.depth);
+    }
+
+    #[test]
+    fn test_aligned_beams_have_ideal_depth() {
+        let polarizability = Polarizability::calculate_for(1064e-9, 780e-9, 6.065e6);
+        let beam_a = beam(Vector3::x(), Vector3::new(0.0, 0.0, 0.0));
+        let beam_b = beam(Vector3::y(), Vector3::new(0.0, 0.0, 0.0));
+
+        let overlap = analyse_overlap(&beam_a, &beam_b, &polarizability);
+        assert!(overlap.depth > 0.0);
+        assert_approx_eq!(overlap.depth_ratio(), 1.0, 1e-9);
+        assert!(overlap.warning().is_none());
+    }
+}
@@ -0,0 +1,231 @@
+//! Measurement of trap frequencies by parametric excitation.
+//!
+//! Modulating the depth of a dipole trap at twice the trap frequency resonantly heats the
+//! trapped atoms. Scanning the modulation frequency and recording the heating reproduces the
+//! way trap frequencies are measured experimentally.
+
+use nalgebra::Vector3;
+use specs::prelude::*;
+
+use crate::atom::{Force, Mass, Position, Velocity};
+use crate::constant;
+use crate::dipole::{DipoleLight, Polarizability};
+use crate::integrator::Timestep;
+use crate::laser::frame::Frame;
+use crate::laser::gaussian::{get_gaussian_beam_intensity, CircularMask, GaussianBeam};
+use crate::simulation::Simulation;
+
+/// Configures a parametric heating scan, see [parametric_scan].
+pub struct ParametricScanConfig {
+    /// The dipole beam entity whose power is modulated.
+    pub beam: Entity,
+    /// Fractional depth of the power modulation, so that `power = p0 * (1 + depth * sin(2pi f t))`.
+    pub modulation_depth: f64,
+    /// Duration of the modulation at each frequency, in SI units of seconds.
+    pub duration: f64,
+}
+
+/// Performs a parametric heating scan of a dipole trap.
+///
+/// For each modulation frequency (in Hz), the atoms are returned to their initial state and the power
+/// of the configured beam is modulated for the configured duration. The heating is the increase in
+/// the mean total energy per atom, in SI units of J, evaluated in the unmodulated trap. The heating
+/// peaks when the modulation frequency is twice a trap frequency.
+///
+/// The simulation is returned to its initial state once the scan is complete.
+///
+/// Returns a list of `(frequency, heating)` pairs.
+pub fn parametric_scan(
+    simulation: &mut Simulation,
+    config: &ParametricScanConfig,
+    frequencies: &[f64],
+) -> Vec<(f64, f64)> {
+    let dt = simulation.world.read_resource::<Timestep>().delta;
+    let base_power = simulation
+        .world
+        .read_storage::<GaussianBeam>()
+        .get(config.beam)
+        .expect("Modulated beam must have a GaussianBeam component.")
+        .power;
+    let initial_state = record_atom_state(&simulation.world);
+    let steps = (config.duration / dt).round() as u64;
+
+    let mut results = Vec::new();
+    for &frequency in frequencies {
+        restore_atom_state(&simulation.world, &initial_state);
+        let initial_energy = mean_energy(&simulation.world);
+        for i in 0..steps {
+            let t = i as f64 * dt;
+            set_beam_power(
+                &simulation.world,
+                config.beam,
+                base_power
+                    * (1.0 + config.modulation_depth * (2.0 * constant::PI * frequency * t).sin()),
+            );
+            simulation.step();
+        }
+        set_beam_power(&simulation.world, config.beam, base_power);
+        results.push((frequency, mean_energy(&simulation.world) - initial_energy));
+    }
+
+    restore_atom_state(&simulation.world, &initial_state);
+    results
+}
+
+type AtomState = (Entity, Vector3<f64>, Vector3<f64>, Vector3<f64>);
+
+fn record_atom_state(world: &World) -> Vec<AtomState> {
+    let entities = world.entities();
+    let positions = world.read_storage::<Position>();
+    let velocities = world.read_storage::<Velocity>();
+    let forces = world.read_storage::<Force>();
+    (&entities, &positions, &velocities, &forces)
+        .join()
+        .map(|(ent, pos, vel, force)| (ent, pos.pos.cast(), vel.vel.cast(), force.force.cast()))
+        .collect()
+}
+
+fn restore_atom_state(world: &World, state: &[AtomState]) {
+    let mut positions = world.write_storage::<Position>();
+    let mut velocities = world.write_storage::<Velocity>();
+    let mut forces = world.write_storage::<Force>();
+    for (ent, pos, vel, force) in state {
+        if let Some(p) = positions.get_mut(*ent) {
+            p.pos = pos.cast();
+        }
+        if let Some(v) = velocities.get_mut(*ent) {
+            v.vel = vel.cast();
+        }
+        if let Some(f) = forces.get_mut(*ent) {
+            f.force = force.cast();
+        }
+    }
+}
+
+fn set_beam_power(world: &World, beam: Entity, power: f64) {
+    if let Some(beam) = world.write_storage::<GaussianBeam>().get_mut(beam) {
+        beam.power = power;
+    }
+}
+
+/// Mean of the kinetic plus dipole potential energy of the atoms, in SI units of J.
+fn mean_energy(world: &World) -> f64 {
+    let positions = world.read_storage::<Position>();
+    let velocities = world.read_storage::<Velocity>();
+    let masses = world.read_storage::<Mass>();
+    let polarizabilities = world.read_storage::<Polarizability>();
+    let dipoles = world.read_storage::<DipoleLight>();
+    let beams = world.read_storage::<GaussianBeam>();
+    let masks = world.read_storage::<CircularMask>();
+    let frames = world.read_storage::<Frame>();
+
+    let mut total = 0.0;
+    let mut count = 0;
+    for (pos, vel, mass, polarizability) in
+        (&positions, &velocities, &masses, &polarizabilities).join()
+    {
+        let kinetic = 0.5 * mass.value * constant::AMU * vel.vel.cast::<f64>().norm_squared();
+        let potential: f64 = (&dipoles, &beams, masks.maybe(), frames.maybe())
+            .join()
+            .map(|(_, beam, mask, frame)| {
+                -polarizability.prefactor * get_gaussian_beam_intensity(beam, pos, mask, frame)
+            })
+            .sum();
+        total += kinetic + potential;
+        count += 1;
+    }
+    if count == 0 {
+        0.0
+    } else {
+        total / count as f64
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use crate::atom::Atom;
+    use crate::dipole::DipolePlugin;
+    use crate::initiate::NewlyCreated;
+    use crate::laser::gaussian::calculate_rayleigh_range;
+    use crate::laser::LaserPlugin;
+    use crate::simulation::SimulationBuilder;
+
+    #[test]
+    fn test_parametric_heating_peaks_at_twice_trap_frequency() {
+        let mut sim_builder = SimulationBuilder::default();
+        sim_builder.add_plugin(LaserPlugin::<1>);
+        sim_builder.add_plugin(DipolePlugin::<1>);
+        let mut sim = sim_builder.build();
+
+        let power = 1.0;
+        let e_radius = 50.0e-6;
+        let wavelength = 1064.0e-9;
+        let mass = 87.0;
+        let polarizability = Polarizability::calculate_for(wavelength, 461e-9, 32.0e6);
+
+        let beam = sim
+            .world
+            .create_entity()
+            .with(GaussianBeam {
+                intersection: Vector3::new(0.0, 0.0, 0.0),
+                e_radius,
+                power,
+                direction: Vector3::z(),
+                rayleigh_range: calculate_rayleigh_range(&wavelength, &e_radius),
+                ellipticity: 0.0,
+                focus_offset: 0.0,
+            })
+            .with(DipoleLight { wavelength })
+            .with(Frame {
+                x_vector: Vector3::x(),
+                y_vector: Vector3::y(),
+            })
+            .build();
+
+        // Harmonic approximation to the radial trapping potential.
+        let peak_intensity = power / (constant::PI * e_radius.powi(2));
+        let trap_frequency = (2.0 * polarizability.prefactor * peak_intensity
+            / (mass * constant::AMU * e_radius.powi(2)))
+        .sqrt()
+            / (2.0 * constant::PI);
+
+        sim.world.insert(Timestep {
+            delta: 1.0 / (200.0 * trap_frequency),
+        });
+        sim.world
+            .create_entity()
+            .with(Position {
+                pos: Vector3::new(1.0e-6, 0.0, 0.0),
+            })
+            .with(Velocity {
+                vel: Vector3::new(0.0, 0.0, 0.0),
+            })
+            .with(Force::new())
+            .with(Mass { value: mass })
+            .with(polarizability)
+            .with(Atom)
+            .with(NewlyCreated)
+            .build();
+        sim.step();
+
+        let config = ParametricScanConfig {
+            beam,
+            modulation_depth: 0.1,
+            duration: 20.0 / trap_frequency,
+        };
+        let frequencies: Vec<f64> = [1.0, 1.5, 2.0, 2.5, 3.0]
+            .iter()
+            .map(|x| x * trap_frequency)
+            .collect();
+        let scan = parametric_scan(&mut sim, &config, &frequencies);
+
+        let (peak_frequency, peak_heating) = scan
+            .iter()
+            .cloned()
+            .fold((0.0, f64::MIN), |a, b| if b.1 > a.1 { b } else { a });
+        assert!(peak_heating > 0.0);
+        assert_eq!(peak_frequency, frequencies[2]);
+    }
+}
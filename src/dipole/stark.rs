@@ -0,0 +1,130 @@
+//! The AC Stark shift of atoms in dipole beams.
+//!
+//! An atom with [Polarizability] `alpha` in a dipole beam of intensity `I` has potential energy `-alpha * I`, which
+//! shifts its ground state by `-alpha * I / h`. The [AcStarkShift] component holds the shift summed over all dipole
+//! beams, for example for spectroscopy or analysis. It is opt-in: add an [AcStarkShift] to the atoms of interest.
+
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+
+use crate::constant::{HBAR, PI};
+use crate::dipole::{DipoleLight, Polarizability};
+use crate::laser::index::LaserIndex;
+use crate::laser::intensity::LaserIntensitySamplers;
+use crate::parallel::{ForceSerial, MaybeParJoin};
+
+/// An atom component that holds the total AC Stark shift of the atom from all dipole beams.
+#[derive(Deserialize, Serialize, Clone, Copy)]
+pub struct AcStarkShift {
+    /// Shift of the ground state, in SI units of Hz.
+    pub hz: f64,
+}
+impl Default for AcStarkShift {
+    fn default() -> Self {
+        AcStarkShift { hz: f64::NAN }
+    }
+}
+impl Component for AcStarkShift {
+    type Storage = VecStorage<Self>;
+}
+
+/// Calculates the [AcStarkShift] of each atom from its `LaserIntensitySamplers` and [Polarizability].
+///
+/// Only the intensities of indexed `DipoleLight` beams are summed.
+pub struct CalculateAcStarkShiftSystem<const N: usize>;
+
+impl<'a, const N: usize> System<'a> for CalculateAcStarkShiftSystem<N> {
+    type SystemData = (
+        ReadStorage<'a, DipoleLight>,
+        ReadStorage<'a, LaserIndex>,
+        ReadStorage<'a, Polarizability>,
+        ReadStorage<'a, LaserIntensitySamplers<N>>,
+        WriteStorage<'a, AcStarkShift>,
+        Option<Read<'a, ForceSerial>>,
+    );
+
+    fn run(
+        &mut self,
+        (dipole_light, dipole_index, polarizability, samplers, mut shifts, force_serial): Self::SystemData,
+    ) {
+        let active: Vec<usize> = (&dipole_index, &dipole_light)
+            .join()
+            .map(|(index, _)| index.index)
+            .collect();
+
+        (&mut shifts, &polarizability, &samplers).maybe_par_for_each(
+            force_serial.is_some(),
+            |(shift, polarizability, samplers)| {
+                let intensity: f64 = active
+                    .iter()
+                    .map(|index| samplers.contents[*index].intensity)
+                    .sum();
+                shift.hz = -polarizability.prefactor * intensity / HBAR / (2.0 * PI);
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use crate::laser::intensity::LaserIntensitySampler;
+    use crate::laser::DEFAULT_BEAM_LIMIT;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_ac_stark_shift_sums_dipole_beams() {
+        let mut test_world = World::new();
+        test_world.register::<LaserIndex>();
+        test_world.register::<DipoleLight>();
+        test_world.register::<Polarizability>();
+        test_world.register::<LaserIntensitySamplers<{ DEFAULT_BEAM_LIMIT }>>();
+        test_world.register::<AcStarkShift>();
+
+        for index in [0, 2].iter() {
+            test_world
+                .create_entity()
+                .with(LaserIndex {
+                    index: *index,
+                    initiated: true,
+                })
+                .with(DipoleLight {
+                    wavelength: 1064.0e-9,
+                })
+                .build();
+        }
+        // An indexed beam that is not a dipole beam, for example a cooling beam, does not shift the atom.
+        test_world
+            .create_entity()
+            .with(LaserIndex {
+                index: 1,
+                initiated: true,
+            })
+            .build();
+
+        let polarizability = Polarizability::calculate_for(1064e-9, 780e-9, 6.065e6);
+        let mut samplers = [LaserIntensitySampler::default(); DEFAULT_BEAM_LIMIT];
+        samplers[0].intensity = 1.0e8;
+        samplers[1].intensity = 5.0e7;
+        samplers[2].intensity = 2.0e8;
+        let atom = test_world
+            .create_entity()
+            .with(polarizability)
+            .with(LaserIntensitySamplers { contents: samplers })
+            .with(AcStarkShift::default())
+            .build();
+
+        CalculateAcStarkShiftSystem::<{ DEFAULT_BEAM_LIMIT }>.run_now(&test_world);
+        test_world.maintain();
+
+        let shift = test_world
+            .read_storage::<AcStarkShift>()
+            .get(atom)
+            .expect("entity not found")
+            .hz;
+        let expected = -polarizability.prefactor * 3.0e8 / HBAR / (2.0 * PI);
+        assert!(expected < 0.0);
+        assert_approx_eq!(shift, expected, expected.abs() * 1e-12);
+    }
+}
@@ -0,0 +1,195 @@
+//! Tilting a single-beam dipole trap to compensate gravity along its weak axis.
+//!
+//! A single-beam dipole trap confines atoms weakly along the beam axis, over the Rayleigh range, and strongly
+//! across it, over the beam waist. If the beam is not perpendicular to gravity, the component of gravity along
+//! the axis pulls the trap minimum far from the focus, or out of the trap entirely. Tilting the beam until it is
+//! perpendicular to gravity centers the trap minimum on the focal plane. Gravity then only sags the minimum a
+//! small distance below the focus, across the strong axes.
+
+use nalgebra::{Rotation3, Unit, Vector3};
+
+use crate::constant::{EXP, PI};
+use crate::dipole::Polarizability;
+use crate::laser::frame::Frame;
+use crate::laser::gaussian::GaussianBeam;
+
+/// Calculates the angle through which a dipole beam must be tilted to compensate gravity along its axis.
+///
+/// The tilt rotates the beam towards the direction of gravity, in the plane containing both, so a beam
+/// pointing upwards has a positive tilt. Apply the tilt with [tilt_beam].
+///
+/// Prints a warning if the trap is too weak to hold the atoms against gravity even when tilted, because the
+/// maximum restoring force across the beam is smaller than the weight of an atom. Without gravity, see
+/// [Gravity::zero](crate::gravity::Gravity::zero), no tilt is needed and the angle is zero.
+///
+/// # Arguments
+///
+/// `beam`: the dipole beam.
+///
+/// `polarizability`: polarizability of the atoms in the dipole beam.
+///
+/// `mass`: mass of an atom, in SI units of kg.
+///
+/// `gravity`: acceleration due to gravity, in SI units of m/s^2.
+///
+/// Returns the tilt angle, in radians.
+pub fn compensating_tilt(
+    beam: &GaussianBeam,
+    polarizability: &Polarizability,
+    mass: f64,
+    gravity: Vector3<f64>,
+) -> f64 {
+    if gravity.norm() == 0.0 {
+        return 0.0;
+    }
+    let weight = mass * gravity.norm();
+    let peak_intensity = beam.power / (PI * beam.e_radius.powi(2));
+    // The radial intensity gradient at the focus is largest at a distance e_radius / sqrt(2) from the axis.
+    let max_radial_force = polarizability.prefactor.abs() * peak_intensity * 2.0_f64.sqrt()
+        / beam.e_radius
+        * EXP.powf(-0.5);
+    if max_radial_force < weight {
+        eprintln!(
+            "Warning: no tilt of the dipole beam can compensate gravity: the maximum restoring force of the trap ({:.3e} N) is less than the weight of an atom ({:.3e} N).",
+            max_radial_force, weight
+        );
+    }
+    -beam
+        .direction
+        .normalize()
+        .dot(&gravity.normalize())
+        .clamp(-1.0, 1.0)
+        .asin()
+}
+
+/// Tilts a dipole beam towards the direction of gravity, rotating it about its `intersection`.
+///
+/// The tilt is a rotation in the plane containing the beam and gravity, see [compensating_tilt]. If the beam is
+/// parallel to gravity, or there is no gravity, it is tilted in the plane containing the `x_vector` of its
+/// [Frame], if given. The [Frame] of the beam, if given, is rotated with it so that it remains orthogonal to the
+/// beam.
+///
+/// # Arguments
+///
+/// `beam`: the dipole beam to tilt.
+///
+/// `frame`: the reference frame of the beam, if the beam has one.
+///
+/// `gravity`: acceleration due to gravity, in SI units of m/s^2.
+///
+/// `angle`: the tilt angle, in radians.
+pub fn tilt_beam(
+    beam: &mut GaussianBeam,
+    frame: Option<&mut Frame>,
+    gravity: Vector3<f64>,
+    angle: f64,
+) {
+    let direction = beam.direction.normalize();
+    let mut axis = direction.cross(&gravity);
+    if axis.norm() <= 1e-12 * gravity.norm() {
+        let perpendicular = match frame {
+            Some(ref frame) => frame.x_vector,
+            None => direction.cross(&Vector3::x()) + direction.cross(&Vector3::y()),
+        };
+        axis = perpendicular.cross(&direction);
+    }
+    let rotation = Rotation3::from_axis_angle(&Unit::new_normalize(axis), angle);
+    beam.direction = rotation * direction;
+    if let Some(frame) = frame {
+        frame.x_vector = rotation * frame.x_vector;
+        frame.y_vector = rotation * frame.y_vector;
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use crate::atom::Position;
+    use crate::constant::{AMU, GC};
+    use crate::laser::gaussian::{calculate_rayleigh_range, get_gaussian_beam_intensity};
+    use assert_approx_eq::assert_approx_eq;
+
+    /// The total force on an atom from the dipole beam and gravity, using a numerical intensity gradient.
+    fn total_force(
+        beam: &GaussianBeam,
+        polarizability: &Polarizability,
+        mass: f64,
+        gravity: Vector3<f64>,
+        pos: Vector3<f64>,
+    ) -> Vector3<f64> {
+        let step = 1.0e-9;
+        let intensity = |pos: Vector3<f64>| {
+            get_gaussian_beam_intensity(beam, &Position { pos: pos.cast() }, None, None)
+        };
+        let mut gradient = Vector3::new(0.0, 0.0, 0.0);
+        for axis in 0..3 {
+            let mut offset = Vector3::new(0.0, 0.0, 0.0);
+            offset[axis] = step;
+            gradient[axis] = (intensity(pos + offset) - intensity(pos - offset)) / (2.0 * step);
+        }
+        polarizability.prefactor * gradient + mass * gravity
+    }
+
+    /// Finds the equilibrium point below the focus, where the vertical forces balance.
+    fn sagged_point(
+        beam: &GaussianBeam,
+        polarizability: &Polarizability,
+        mass: f64,
+        gravity: Vector3<f64>,
+    ) -> Vector3<f64> {
+        let (mut low, mut high) = (-beam.e_radius / 2.0_f64.sqrt(), 0.0);
+        for _ in 0..100 {
+            let mid = (low + high) / 2.0;
+            let pos = beam.intersection + Vector3::new(0.0, 0.0, mid);
+            if total_force(beam, polarizability, mass, gravity, pos)[2] > 0.0 {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+        beam.intersection + Vector3::new(0.0, 0.0, (low + high) / 2.0)
+    }
+
+    #[test]
+    fn test_tilted_trap_minimum_is_at_focus() {
+        let polarizability = Polarizability::calculate_for(1064e-9, 780e-9, 6.065e6);
+        let mass = 87.0 * AMU;
+        let gravity = Vector3::new(0.0, 0.0, -GC);
+        let e_radius = 50.0e-6;
+        let misalignment: f64 = 0.05;
+        let mut beam = GaussianBeam {
+            intersection: Vector3::new(0.0, 0.0, 0.0),
+            direction: Vector3::new(misalignment.cos(), 0.0, misalignment.sin()),
+            e_radius,
+            power: 1.0,
+            rayleigh_range: calculate_rayleigh_range(&1064.0e-9, &e_radius),
+            ellipticity: 0.0,
+            focus_offset: 0.0,
+        };
+        let mut frame = Frame::from_direction(beam.direction, Vector3::y());
+
+        // Before tilting, gravity pulls the atoms along the beam axis.
+        let pos = sagged_point(&beam, &polarizability, mass, gravity);
+        let axial_force =
+            total_force(&beam, &polarizability, mass, gravity, pos).dot(&beam.direction);
+        assert_approx_eq!(
+            axial_force,
+            -mass * GC * misalignment.sin(),
+            1e-3 * mass * GC
+        );
+
+        let tilt = compensating_tilt(&beam, &polarizability, mass, gravity);
+        assert_approx_eq!(tilt, misalignment, 1e-12);
+        tilt_beam(&mut beam, Some(&mut frame), gravity, tilt);
+        assert_approx_eq!(beam.direction[2], 0.0, 1e-12);
+        assert_approx_eq!(frame.x_vector.dot(&beam.direction), 0.0, 1e-12);
+        assert_approx_eq!(frame.y_vector.dot(&beam.direction), 0.0, 1e-12);
+
+        // After tilting, the trap minimum lies directly below the focus.
+        let pos = sagged_point(&beam, &polarizability, mass, gravity);
+        let force = total_force(&beam, &polarizability, mass, gravity, pos);
+        assert!(force.norm() < 1e-3 * mass * GC);
+        assert!(pos[2] < 0.0 && pos[2] > -0.5 * e_radius);
+    }
+}
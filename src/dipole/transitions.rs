@@ -0,0 +1,271 @@
+//! Polarizabilities summed over several optical transitions, for magic-wavelength and state-insensitive traps.
+//!
+//! A [Polarizability] is calculated from a single transition, at the wavelength of a single beam. This is a good
+//! approximation far from resonance for atoms in their ground state, but the differential light shift between two
+//! states, and the wavelength at which it vanishes, depend on all of the strong transitions from both states.
+//!
+//! A [MultiTransitionPolarizability] sums the contributions of several [DipoleTransition]s, and is evaluated at the
+//! wavelength of each `DipoleLight` beam, so that beams of different wavelengths can be combined in one trap. The
+//! [magic_wavelength] of two states is the wavelength at which their polarizabilities are equal, and the dipole
+//! force on them is the same.
+//!
+//! The [ApplyDipoleForceSystem](crate::dipole::force::ApplyDipoleForceSystem) and the dipole potential energy of the
+//! [EnergyComponents](crate::energy::EnergyComponents) use whichever of the two components an atom has, see
+//! [dipole_prefactor]. An atom must not have both. The AC Stark shift is still calculated from the [Polarizability]
+//! only.
+
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+
+use crate::dipole::Polarizability;
+
+/// An optical transition contributing to a [MultiTransitionPolarizability].
+#[derive(Deserialize, Serialize, Clone, Copy, Debug)]
+pub struct DipoleTransition {
+    /// Wavelength of the transition, in SI units of m.
+    pub wavelength: f64,
+    /// Linewidth of the transition, in SI units of Hz, as for [Polarizability::calculate_for].
+    pub linewidth: f64,
+    /// Relative strength of the transition. It is `1.0` for a transition to a higher state, and negative for a
+    /// transition to a lower state, which shifts the state in the opposite direction.
+    pub weight: f64,
+}
+impl DipoleTransition {
+    /// A transition to a higher state, with unit weight.
+    pub fn new(wavelength: f64, linewidth: f64) -> Self {
+        DipoleTransition {
+            wavelength,
+            linewidth,
+            weight: 1.0,
+        }
+    }
+}
+
+/// An atom component that represents the polarizability of the atom as a sum over optical transitions.
+///
+/// The force exerted on the atom by a `DipoleLight` beam is `force = prefactor(wavelength) * intensity_gradient`,
+/// where the prefactor is evaluated at the wavelength of the beam.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct MultiTransitionPolarizability {
+    /// The transitions from the state of the atom, whose contributions to the polarizability are summed.
+    pub transitions: Vec<DipoleTransition>,
+}
+impl Component for MultiTransitionPolarizability {
+    type Storage = VecStorage<Self>;
+}
+impl MultiTransitionPolarizability {
+    /// The polarizability of a single transition, which is the same as [Polarizability::calculate_for].
+    pub fn single(wavelength: f64, linewidth: f64) -> Self {
+        MultiTransitionPolarizability {
+            transitions: vec![DipoleTransition::new(wavelength, linewidth)],
+        }
+    }
+
+    /// The constant of proportionality that relates the intensity gradient (in W/m) of a beam of the given
+    /// wavelength, in SI units of m, to the force on the atom (in N).
+    pub fn prefactor(&self, dipole_beam_wavelength: f64) -> f64 {
+        self.transitions
+            .iter()
+            .map(|transition| {
+                transition.weight
+                    * Polarizability::calculate_for(
+                        dipole_beam_wavelength,
+                        transition.wavelength,
+                        transition.linewidth,
+                    )
+                    .prefactor
+            })
+            .sum()
+    }
+
+    /// The [Polarizability] of the atom in a beam of the given wavelength, in SI units of m.
+    pub fn at(&self, dipole_beam_wavelength: f64) -> Polarizability {
+        Polarizability {
+            prefactor: self.prefactor(dipole_beam_wavelength),
+        }
+    }
+}
+
+/// Finds a wavelength between `min` and `max`, in SI units of m, at which two states have the same polarizability.
+///
+/// The wavelength is found by bisection, so the difference between the polarizabilities must change sign between
+/// `min` and `max`. Returns `None` if it does not, or if a transition of either state lies within the interval,
+/// where the polarizability diverges rather than crosses.
+pub fn magic_wavelength(
+    first: &MultiTransitionPolarizability,
+    second: &MultiTransitionPolarizability,
+    min: f64,
+    max: f64,
+) -> Option<f64> {
+    let resonant = first
+        .transitions
+        .iter()
+        .chain(second.transitions.iter())
+        .any(|transition| transition.wavelength >= min && transition.wavelength <= max);
+    if resonant {
+        return None;
+    }
+    let difference = |wavelength: f64| first.prefactor(wavelength) - second.prefactor(wavelength);
+    let (mut low, mut high) = (min, max);
+    let low_sign = difference(low) > 0.0;
+    if low_sign == (difference(high) > 0.0) {
+        return None;
+    }
+    for _ in 0..200 {
+        let middle = 0.5 * (low + high);
+        if (difference(middle) > 0.0) == low_sign {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+    Some(0.5 * (low + high))
+}
+
+/// The constant of proportionality that relates the intensity gradient (in W/m) of a beam of the given wavelength,
+/// in SI units of m, to the force on an atom (in N), or `None` if the atom has neither polarizability component.
+///
+/// # Panics
+///
+/// If the atom has both a [Polarizability] and a [MultiTransitionPolarizability], which would count the dipole
+/// force twice.
+pub fn dipole_prefactor(
+    polarizability: Option<&Polarizability>,
+    multi_transition: Option<&MultiTransitionPolarizability>,
+    dipole_beam_wavelength: f64,
+) -> Option<f64> {
+    match (polarizability, multi_transition) {
+        (Some(polarizability), None) => Some(polarizability.prefactor),
+        (None, Some(multi_transition)) => Some(multi_transition.prefactor(dipole_beam_wavelength)),
+        (None, None) => None,
+        (Some(_), Some(_)) => panic!(
+            "An atom has both a Polarizability and a MultiTransitionPolarizability. Use only one of them."
+        ),
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use crate::atom::Force;
+    use crate::dipole::force::ApplyDipoleForceSystem;
+    use crate::dipole::DipoleLight;
+    use crate::laser::index::LaserIndex;
+    use crate::laser::intensity_gradient::{LaserIntensityGradientSampler, LaserIntensityGradientSamplers};
+    use crate::laser::DEFAULT_BEAM_LIMIT;
+    use assert_approx_eq::assert_approx_eq;
+    use nalgebra::Vector3;
+
+    fn world(wavelength: f64) -> World {
+        let mut world = World::new();
+        world.register::<LaserIndex>();
+        world.register::<DipoleLight>();
+        world.register::<Force>();
+        world.register::<LaserIntensityGradientSamplers<{ DEFAULT_BEAM_LIMIT }>>();
+        world.register::<Polarizability>();
+        world.register::<MultiTransitionPolarizability>();
+        world
+            .create_entity()
+            .with(LaserIndex {
+                index: 0,
+                initiated: true,
+            })
+            .with(DipoleLight { wavelength })
+            .build();
+        world
+    }
+
+    fn add_atom<T: Component + Send + Sync>(world: &mut World, polarizability: T) -> Entity {
+        world
+            .create_entity()
+            .with(Force::new())
+            .with(LaserIntensityGradientSamplers {
+                contents: [LaserIntensityGradientSampler {
+                    gradient: Vector3::new(1.0e12, -2.0e12, 0.5e12),
+                }; DEFAULT_BEAM_LIMIT],
+            })
+            .with(polarizability)
+            .build()
+    }
+
+    fn run(world: &mut World) {
+        ApplyDipoleForceSystem::<{ DEFAULT_BEAM_LIMIT }>.run_now(world);
+        world.maintain();
+    }
+
+    fn force(world: &World, atom: Entity) -> Vector3<f64> {
+        world
+            .read_storage::<Force>()
+            .get(atom)
+            .unwrap()
+            .force
+            .cast()
+    }
+
+    #[test]
+    fn test_single_transition_matches_polarizability() {
+        let mut world = world(1064.0e-9);
+        let single = add_atom(
+            &mut world,
+            MultiTransitionPolarizability::single(780.0e-9, 6.065e6),
+        );
+        let reference = add_atom(
+            &mut world,
+            Polarizability::calculate_for(1064.0e-9, 780.0e-9, 6.065e6),
+        );
+        run(&mut world);
+        let (single, reference) = (force(&world, single), force(&world, reference));
+        assert_approx_eq!((single - reference).norm(), 0.0, 1e-12 * reference.norm());
+    }
+
+    #[test]
+    fn test_equal_force_at_magic_wavelength() {
+        // A ground state with a single strong transition, and an excited state coupled to two higher states.
+        let ground = MultiTransitionPolarizability::single(461.0e-9, 32.0e6);
+        let excited = MultiTransitionPolarizability {
+            transitions: vec![
+                DipoleTransition::new(679.0e-9, 8.6e6),
+                DipoleTransition::new(2600.0e-9, 1.0e6),
+            ],
+        };
+        let magic = magic_wavelength(&ground, &excited, 700.0e-9, 2000.0e-9).unwrap();
+        assert!(magic > 780.0e-9 && magic < 810.0e-9);
+        assert!(magic_wavelength(&ground, &excited, 600.0e-9, 2000.0e-9).is_none());
+
+        let forces = |wavelength: f64| {
+            let mut world = world(wavelength);
+            let ground = add_atom(&mut world, ground.clone());
+            let excited = add_atom(&mut world, excited.clone());
+            run(&mut world);
+            (force(&world, ground), force(&world, excited))
+        };
+        let (ground_force, excited_force) = forces(magic);
+        assert!(ground_force.norm() > 0.0);
+        assert_approx_eq!(
+            (ground_force - excited_force).norm(),
+            0.0,
+            1e-9 * ground_force.norm()
+        );
+
+        // Away from the magic wavelength, the forces differ.
+        let (ground_force, excited_force) = forces(1064.0e-9);
+        assert!((ground_force - excited_force).norm() > 0.1 * ground_force.norm());
+    }
+
+    #[test]
+    #[should_panic(expected = "both a Polarizability and a MultiTransitionPolarizability")]
+    fn test_atom_with_both_polarizabilities_is_rejected() {
+        let mut world = world(1064.0e-9);
+        let atom = add_atom(
+            &mut world,
+            MultiTransitionPolarizability::single(780.0e-9, 6.065e6),
+        );
+        world
+            .write_storage::<Polarizability>()
+            .insert(atom, Polarizability::calculate_for(1064.0e-9, 780.0e-9, 6.065e6))
+            .unwrap();
+        world.insert(crate::parallel::ForceSerial);
+        run(&mut world);
+    }
+}
@@ -0,0 +1,422 @@
+//! Classification of atoms as trapped or transiting, to measure the capture efficiency of a trap.
+//!
+//! The [TransferEfficiency] compares the number of atoms held in two traps before and after a transfer between
+//! them, for example when loading a dipole trap from a MOT.
+//!
+//! The atoms are counted as the number of real atoms they represent, so that an atom with a [StatisticalWeight] or
+//! [SuperAtomWeight] counts as its total weight, and an unweighted atom counts as one.
+
+use nalgebra::Vector3;
+use specs::prelude::*;
+
+use crate::atom::{
+    total_weight, Atom, Mass, Position, StatisticalWeight, SuperAtomWeight, Velocity,
+};
+use crate::constant;
+use crate::dipole::{DipoleLight, Polarizability};
+use crate::laser::frame::Frame;
+use crate::laser::gaussian::{get_gaussian_beam_intensity, CircularMask, GaussianBeam};
+use crate::shapes::Volume;
+
+/// The criterion used by [trapped_fraction] to decide whether an atom is trapped.
+pub enum TrapCriterion<'a> {
+    /// An atom is trapped if its kinetic energy plus dipole potential energy is below `escape_energy`.
+    ///
+    /// The dipole potential is zero far from the beams. `escape_energy` is the potential energy at the lowest
+    /// point on the rim of the trap, in SI units of J, which is zero for a single beam. For example, for two
+    /// crossed beams it is the potential at the centre of the shallower beam, far from the other.
+    Energy {
+        /// Potential energy at which atoms escape the trap, in SI units of J.
+        escape_energy: f64,
+    },
+    /// An atom is trapped if it is inside a volume centred on `position`.
+    Region {
+        /// Position of the volume, in SI units of m.
+        position: Vector3<f64>,
+        /// The volume that contains trapped atoms.
+        volume: &'a dyn Volume,
+    },
+}
+
+/// Calculates the fraction of atoms that are trapped, according to a [TrapCriterion].
+///
+/// For the [TrapCriterion::Energy] criterion, the potential energy is summed over all [DipoleLight]
+/// [GaussianBeam]s, and atoms without a [Polarizability] have no potential energy. Atoms exactly at the escape
+/// energy, or on the boundary of the volume, are not trapped.
+///
+/// Atoms are weighted by the number of real atoms they represent, see [crate::dipole::trapped]. Returns zero if
+/// there are no atoms.
+pub fn trapped_fraction(world: &World, criterion: &TrapCriterion) -> f64 {
+    let (trapped, total) = count_trapped(world, criterion);
+    if total == 0.0 {
+        0.0
+    } else {
+        trapped / total
+    }
+}
+
+/// Counts the real atoms that are trapped according to a [TrapCriterion], see [trapped_fraction].
+pub fn trapped_number(world: &World, criterion: &TrapCriterion) -> f64 {
+    count_trapped(world, criterion).0
+}
+
+/// Returns the number of trapped real atoms and the total number of real atoms.
+fn count_trapped(world: &World, criterion: &TrapCriterion) -> (f64, f64) {
+    let atoms = world.read_storage::<Atom>();
+    let positions = world.read_storage::<Position>();
+    let velocities = world.read_storage::<Velocity>();
+    let masses = world.read_storage::<Mass>();
+    let polarizabilities = world.read_storage::<Polarizability>();
+    let dipoles = world.read_storage::<DipoleLight>();
+    let beams = world.read_storage::<GaussianBeam>();
+    let masks = world.read_storage::<CircularMask>();
+    let frames = world.read_storage::<Frame>();
+    let weights = world.read_storage::<StatisticalWeight>();
+    let super_atoms = world.read_storage::<SuperAtomWeight>();
+
+    let mut total = 0.0;
+    let mut trapped = 0.0;
+    for (_, pos, vel, mass, polarizability, weight, super_atom) in (
+        &atoms,
+        &positions,
+        &velocities,
+        &masses,
+        polarizabilities.maybe(),
+        weights.maybe(),
+        super_atoms.maybe(),
+    )
+        .join()
+    {
+        let weight = total_weight(weight, super_atom);
+        total += weight;
+        let is_trapped = match criterion {
+            TrapCriterion::Energy { escape_energy } => {
+                let kinetic =
+                    0.5 * mass.value * constant::AMU * vel.vel.cast::<f64>().norm_squared();
+                let potential: f64 = match polarizability {
+                    Some(polarizability) => dipole_potential_energy(
+                        polarizability,
+                        pos,
+                        &dipoles,
+                        &beams,
+                        &masks,
+                        &frames,
+                    ),
+                    None => 0.0,
+                };
+                kinetic + potential < *escape_energy
+            }
+            TrapCriterion::Region { position, volume } => {
+                volume.contains(position, &pos.pos.cast())
+            }
+        };
+        if is_trapped {
+            trapped += weight;
+        }
+    }
+    (trapped, total)
+}
+
+/// Calculates the dipole potential energy of an atom at `pos`, summed over all [DipoleLight] [GaussianBeam]s, in
+/// SI units of J.
+pub(crate) fn dipole_potential_energy(
+    polarizability: &Polarizability,
+    pos: &Position,
+    dipoles: &ReadStorage<DipoleLight>,
+    beams: &ReadStorage<GaussianBeam>,
+    masks: &ReadStorage<CircularMask>,
+    frames: &ReadStorage<Frame>,
+) -> f64 {
+    (dipoles, beams, masks.maybe(), frames.maybe())
+        .join()
+        .map(|(_, beam, mask, frame)| {
+            -polarizability.prefactor * get_gaussian_beam_intensity(beam, pos, mask, frame)
+        })
+        .sum()
+}
+
+/// Measures the fraction of atoms transferred from one trap to another, for example from a MOT into a dipole trap.
+///
+/// The atoms held in the initial trap are counted with [TransferEfficiency::start] before the transfer, and the
+/// atoms held in the final trap are counted with [TransferEfficiency::finish] afterwards. Atoms lost during the
+/// transfer reduce the efficiency.
+pub struct TransferEfficiency {
+    /// Number of real atoms in the initial trap before the transfer.
+    initial_number: f64,
+}
+impl TransferEfficiency {
+    /// Counts the atoms held in the initial trap, according to `criterion`.
+    pub fn start(world: &World, criterion: &TrapCriterion) -> Self {
+        TransferEfficiency {
+            initial_number: trapped_number(world, criterion),
+        }
+    }
+
+    /// Number of real atoms in the initial trap before the transfer.
+    pub fn initial_number(&self) -> f64 {
+        self.initial_number
+    }
+
+    /// The number of atoms held in the final trap according to `criterion`, as a fraction of the number of
+    /// atoms that were in the initial trap.
+    ///
+    /// Returns zero if the initial trap was empty.
+    pub fn finish(&self, world: &World, criterion: &TrapCriterion) -> f64 {
+        if self.initial_number == 0.0 {
+            return 0.0;
+        }
+        trapped_number(world, criterion) / self.initial_number
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use crate::atom::Force;
+    use crate::dipole::DipolePlugin;
+    use crate::initiate::NewlyCreated;
+    use crate::integrator::Timestep;
+    use crate::laser::LaserPlugin;
+    use crate::shapes::Sphere;
+    use crate::simulation::SimulationBuilder;
+
+    fn add_atom(world: &mut World, pos: Vector3<f64>, vel: Vector3<f64>, polarizability: f64) {
+        world
+            .create_entity()
+            .with(Position { pos: pos.cast() })
+            .with(Velocity { vel: vel.cast() })
+            .with(Mass { value: 87.0 })
+            .with(Polarizability {
+                prefactor: polarizability,
+            })
+            .with(Atom)
+            .build();
+    }
+
+    #[test]
+    fn test_trapped_fraction_of_bound_and_free_atoms() {
+        let mut world = World::new();
+        world.register::<Atom>();
+        world.register::<Position>();
+        world.register::<Velocity>();
+        world.register::<Mass>();
+        world.register::<Polarizability>();
+        world.register::<DipoleLight>();
+        world.register::<GaussianBeam>();
+        world.register::<CircularMask>();
+        world.register::<Frame>();
+        world.register::<StatisticalWeight>();
+        world.register::<SuperAtomWeight>();
+
+        let beam = GaussianBeam {
+            intersection: Vector3::new(0.0, 0.0, 0.0),
+            e_radius: 50.0e-6,
+            power: 1.0,
+            direction: Vector3::x(),
+            rayleigh_range: f64::INFINITY,
+            ellipticity: 0.0,
+            focus_offset: 0.0,
+        };
+        world
+            .create_entity()
+            .with(beam)
+            .with(DipoleLight {
+                wavelength: 1064.0e-9,
+            })
+            .build();
+
+        // The trap depth is the potential energy on the beam axis.
+        let polarizability = Polarizability::calculate_for(1064e-9, 780e-9, 6.0e6).prefactor;
+        let depth =
+            polarizability * get_gaussian_beam_intensity(&beam, &Position::new(), None, None);
+        let speed_at_energy = |energy: f64| (2.0 * energy / (87.0 * constant::AMU)).sqrt();
+
+        // Bound: at rest on the axis, or moving with half the escape energy.
+        let bound = [
+            (Vector3::new(0.0, 0.0, 0.0), 0.0),
+            (Vector3::new(0.0, 10.0e-6, 0.0), 0.0),
+            (
+                Vector3::new(1.0e-3, 0.0, -5.0e-6),
+                speed_at_energy(0.5 * depth),
+            ),
+        ];
+        // Free: far from the beam, or moving with twice the escape energy.
+        let free = [
+            (Vector3::new(0.0, 1.0e-3, 0.0), 0.1),
+            (Vector3::new(0.0, 0.0, 0.0), speed_at_energy(2.0 * depth)),
+        ];
+        for (pos, speed) in bound.iter().chain(free.iter()) {
+            add_atom(
+                &mut world,
+                *pos,
+                Vector3::new(0.0, 0.0, *speed),
+                polarizability,
+            );
+        }
+
+        let fraction = trapped_fraction(&world, &TrapCriterion::Energy { escape_energy: 0.0 });
+        assert_eq!(fraction, 3.0 / 5.0);
+
+        // An atom exactly at the escape energy is not trapped.
+        add_atom(
+            &mut world,
+            Vector3::new(0.0, 2.0e-3, 0.0),
+            Vector3::new(0.0, 0.0, 0.0),
+            polarizability,
+        );
+        let fraction = trapped_fraction(&world, &TrapCriterion::Energy { escape_energy: 0.0 });
+        assert_eq!(fraction, 3.0 / 6.0);
+
+        // The spatial criterion only depends on the positions. The atom at (0, 1mm, 0) lies on the boundary.
+        let sphere = Sphere { radius: 1.0e-3 };
+        let region = TrapCriterion::Region {
+            position: Vector3::new(0.0, 0.0, 0.0),
+            volume: &sphere,
+        };
+        assert_eq!(trapped_fraction(&world, &region), 3.0 / 6.0);
+    }
+
+    #[test]
+    fn test_trapped_fraction_without_atoms() {
+        let mut world = World::new();
+        world.register::<Atom>();
+        world.register::<Position>();
+        world.register::<Velocity>();
+        world.register::<Mass>();
+        world.register::<Polarizability>();
+        world.register::<DipoleLight>();
+        world.register::<GaussianBeam>();
+        world.register::<CircularMask>();
+        world.register::<Frame>();
+        world.register::<StatisticalWeight>();
+        world.register::<SuperAtomWeight>();
+        let criterion = TrapCriterion::Energy { escape_energy: 0.0 };
+        assert_eq!(trapped_fraction(&world, &criterion), 0.0);
+    }
+
+    #[test]
+    fn test_trapped_number_sums_weights() {
+        let mut world = World::new();
+        world.register::<Atom>();
+        world.register::<Position>();
+        world.register::<Velocity>();
+        world.register::<Mass>();
+        world.register::<Polarizability>();
+        world.register::<DipoleLight>();
+        world.register::<GaussianBeam>();
+        world.register::<CircularMask>();
+        world.register::<Frame>();
+        world.register::<StatisticalWeight>();
+        world.register::<SuperAtomWeight>();
+
+        // Two weighted atoms and one unweighted atom inside the region, and a weighted atom outside it.
+        let atoms = [
+            (0.0, Some(2.5), None),
+            (0.0, Some(0.5), Some(100.0)),
+            (0.0, None, None),
+            (1.0, None, Some(1000.0)),
+        ];
+        for &(x, weight, n_real) in atoms.iter() {
+            let mut builder = world
+                .create_entity()
+                .with(Position {
+                    pos: Vector3::new(x, 0.0, 0.0).cast(),
+                })
+                .with(Velocity {
+                    vel: Vector3::new(0.0, 0.0, 0.0).cast(),
+                })
+                .with(Mass { value: 87.0 })
+                .with(Atom);
+            if let Some(value) = weight {
+                builder = builder.with(StatisticalWeight { value });
+            }
+            if let Some(n_real) = n_real {
+                builder = builder.with(SuperAtomWeight { n_real });
+            }
+            builder.build();
+        }
+
+        let sphere = Sphere { radius: 0.1 };
+        let region = TrapCriterion::Region {
+            position: Vector3::new(0.0, 0.0, 0.0),
+            volume: &sphere,
+        };
+        assert_eq!(trapped_number(&world, &region), 2.5 + 50.0 + 1.0);
+        assert_eq!(trapped_fraction(&world, &region), 53.5 / 1053.5);
+    }
+
+    #[test]
+    fn test_transfer_efficiency_into_dipole_trap() {
+        let mut sim_builder = SimulationBuilder::default();
+        sim_builder.add_plugin(LaserPlugin::<1>);
+        sim_builder.add_plugin(DipolePlugin::<1>);
+        let mut sim = sim_builder.build();
+        sim.world.insert(Timestep { delta: 1.0e-6 });
+
+        let e_radius = 50.0e-6;
+        let beam = GaussianBeam {
+            intersection: Vector3::new(0.0, 0.0, 0.0),
+            e_radius,
+            power: 1.0,
+            direction: Vector3::z(),
+            rayleigh_range: f64::INFINITY,
+            ellipticity: 0.0,
+            focus_offset: 0.0,
+        };
+        sim.world
+            .create_entity()
+            .with(beam)
+            .with(DipoleLight {
+                wavelength: 1064.0e-9,
+            })
+            .with(Frame {
+                x_vector: Vector3::x(),
+                y_vector: Vector3::y(),
+            })
+            .build();
+
+        // The atoms move along the beam with a kinetic energy of depth / e, so that the capture volume of the
+        // dipole trap is a cylinder of radius e_radius, where the intensity is peak / e.
+        let polarizability = Polarizability::calculate_for(1064e-9, 780e-9, 6.0e6);
+        let depth = polarizability.prefactor
+            * get_gaussian_beam_intensity(&beam, &Position::new(), None, None);
+        let speed = (2.0 * depth / constant::EXP / (87.0 * constant::AMU)).sqrt();
+        let mot_radius = 5.0 * e_radius;
+        let radii = [0.0, 0.3, 0.6, 0.9, 1.2, 1.6, 2.5, 20.0, 30.0];
+        for (i, r) in radii.iter().enumerate() {
+            let angle = i as f64;
+            sim.world
+                .create_entity()
+                .with(Position {
+                    pos: (r * e_radius * Vector3::new(angle.cos(), angle.sin(), 0.0)).cast(),
+                })
+                .with(Velocity {
+                    vel: Vector3::new(0.0, 0.0, speed).cast(),
+                })
+                .with(Force::new())
+                .with(Mass { value: 87.0 })
+                .with(polarizability)
+                .with(Atom)
+                .with(NewlyCreated)
+                .build();
+        }
+        let in_mot = radii.iter().filter(|r| **r * e_radius < mot_radius).count();
+        let in_capture_volume = radii.iter().filter(|r| **r < 1.0).count();
+
+        let sphere = Sphere { radius: mot_radius };
+        let mot = TrapCriterion::Region {
+            position: Vector3::new(0.0, 0.0, 0.0),
+            volume: &sphere,
+        };
+        sim.step();
+        let transfer = TransferEfficiency::start(&sim.world, &mot);
+        assert_eq!(transfer.initial_number(), in_mot as f64);
+
+        for _ in 0..500 {
+            sim.step();
+        }
+        let efficiency = transfer.finish(&sim.world, &TrapCriterion::Energy { escape_energy: 0.0 });
+        assert_eq!(efficiency, in_capture_volume as f64 / in_mot as f64);
+    }
+}
@@ -0,0 +1,175 @@
+//! The vector light shift of atoms in circularly polarized dipole beams.
+//!
+//! Circularly polarized light shifts the magnetic sublevels of an atom in proportion to `m_F`, as if the atom
+//! were in an effective magnetic field directed along the beam. Close to the zero of the real magnetic field,
+//! this effective field defines the quantization axis, and the intensity gradient of the beam produces a
+//! state-dependent force on top of the scalar dipole force. The force is used, for example, to transport atoms
+//! in different spin states in opposite directions.
+//!
+//! The vector shift is opt-in: it is only applied to atoms with a [VectorPolarizability], in beams with a
+//! [DipolePolarization]. Linearly polarized beams, with zero ellipticity, produce no vector shift.
+
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+
+use crate::atom::Force;
+use crate::dipole::DipoleLight;
+use crate::laser::index::LaserIndex;
+use crate::laser::intensity_gradient::LaserIntensityGradientSamplers;
+use crate::parallel::{ForceSerial, MaybeParJoin};
+
+/// A component describing the polarization of a `DipoleLight` beam.
+#[derive(Deserialize, Serialize, Clone, Copy)]
+pub struct DipolePolarization {
+    /// Degree of circular polarization relative to the beam direction, from `1.0` for σ+ light, through `0.0`
+    /// for linearly polarized light, to `-1.0` for σ- light.
+    pub ellipticity: f64,
+}
+impl Component for DipolePolarization {
+    type Storage = HashMapStorage<Self>;
+}
+
+/// An atom component that represents the vector polarizability of the atom in a `DipoleLight` beam.
+///
+/// The force exerted on the atom by a beam with a [DipolePolarization] is:
+/// `force = prefactor * ellipticity * m_f * intensity_gradient`
+#[derive(Deserialize, Serialize, Clone, Copy)]
+pub struct VectorPolarizability {
+    /// Constant of proportionality that relates the intensity gradient (in W/m) of a σ+ beam to the force (in N)
+    /// on an atom in the sublevel `m_F = 1`.
+    pub prefactor: f64,
+    /// Zeeman sublevel of the atom.
+    pub m_f: f64,
+}
+impl Component for VectorPolarizability {
+    type Storage = VecStorage<Self>;
+}
+
+/// Adds the state-dependent force from the vector light shift of circularly polarized dipole beams.
+///
+/// Beams without a [DipolePolarization] are treated as linearly polarized, and add no force.
+pub struct ApplyVectorDipoleForceSystem<const N: usize>;
+
+impl<'a, const N: usize> System<'a> for ApplyVectorDipoleForceSystem<N> {
+    type SystemData = (
+        ReadStorage<'a, DipoleLight>,
+        ReadStorage<'a, DipolePolarization>,
+        ReadStorage<'a, LaserIndex>,
+        ReadStorage<'a, VectorPolarizability>,
+        ReadStorage<'a, LaserIntensityGradientSamplers<N>>,
+        WriteStorage<'a, Force>,
+        Option<Read<'a, ForceSerial>>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            dipole_light,
+            dipole_polarization,
+            dipole_index,
+            vector_polarizability,
+            gradient_sampler,
+            mut force,
+            force_serial,
+        ): Self::SystemData,
+    ) {
+        (&mut force, &vector_polarizability, &gradient_sampler).maybe_par_for_each(
+            force_serial.is_some(),
+            |(force, polarizability, sampler)| {
+                for (index, polarization, _dipole) in
+                    (&dipole_index, &dipole_polarization, &dipole_light).join()
+                {
+                    force.force += (polarizability.prefactor
+                        * polarization.ellipticity
+                        * polarizability.m_f
+                        * sampler.contents[index.index].gradient)
+                        .cast();
+                }
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use crate::laser::intensity_gradient::LaserIntensityGradientSampler;
+    use crate::laser::DEFAULT_BEAM_LIMIT;
+    use assert_approx_eq::assert_approx_eq;
+    use nalgebra::Vector3;
+
+    fn create_world(ellipticity: f64) -> World {
+        let mut test_world = World::new();
+        test_world.register::<LaserIndex>();
+        test_world.register::<DipoleLight>();
+        test_world.register::<DipolePolarization>();
+        test_world.register::<Force>();
+        test_world.register::<LaserIntensityGradientSamplers<{ DEFAULT_BEAM_LIMIT }>>();
+        test_world.register::<VectorPolarizability>();
+
+        test_world
+            .create_entity()
+            .with(LaserIndex {
+                index: 0,
+                initiated: true,
+            })
+            .with(DipoleLight {
+                wavelength: 1064.0e-9,
+            })
+            .with(DipolePolarization { ellipticity })
+            .build();
+        test_world
+    }
+
+    fn create_atom(test_world: &mut World, m_f: f64) -> Entity {
+        test_world
+            .create_entity()
+            .with(Force::new())
+            .with(LaserIntensityGradientSamplers {
+                contents: [LaserIntensityGradientSampler {
+                    gradient: Vector3::new(0.0, 1.0, -2.0),
+                }; DEFAULT_BEAM_LIMIT],
+            })
+            .with(VectorPolarizability {
+                prefactor: 1.0e-36,
+                m_f,
+            })
+            .build()
+    }
+
+    fn force_on(test_world: &World, atom: Entity) -> Vector3<f64> {
+        test_world
+            .read_storage::<Force>()
+            .get(atom)
+            .expect("Entity not found!")
+            .force
+            .cast::<f64>()
+    }
+
+    #[test]
+    fn test_sigma_plus_beam_pushes_opposite_sublevels_apart() {
+        let mut test_world = create_world(1.0);
+        let plus = create_atom(&mut test_world, 1.0);
+        let minus = create_atom(&mut test_world, -1.0);
+        ApplyVectorDipoleForceSystem::<{ DEFAULT_BEAM_LIMIT }>.run_now(&test_world);
+        test_world.maintain();
+
+        let plus_force = force_on(&test_world, plus);
+        let minus_force = force_on(&test_world, minus);
+        assert!(plus_force.norm() > 0.0);
+        for i in 0..3 {
+            assert_approx_eq!(plus_force[i], 1.0e-36 * [0.0, 1.0, -2.0][i], 1e-48);
+            assert_approx_eq!(plus_force[i], -minus_force[i], 1e-48);
+        }
+    }
+
+    #[test]
+    fn test_linear_polarization_has_no_vector_shift() {
+        let mut test_world = create_world(0.0);
+        let atom = create_atom(&mut test_world, 1.0);
+        ApplyVectorDipoleForceSystem::<{ DEFAULT_BEAM_LIMIT }>.run_now(&test_world);
+        test_world.maintain();
+        assert_eq!(force_on(&test_world, atom), Vector3::new(0.0, 0.0, 0.0));
+    }
+}
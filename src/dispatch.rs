@@ -0,0 +1,466 @@
+//! Records the order of the systems in a simulation, so that it can be validated before a run.
+//!
+//! specs runs the systems of a dispatcher in parallel. Systems run after their explicit dependencies, after any
+//! barrier added before them, and after any previously added system that accesses the same resources. A system
+//! added with the wrong dependencies still runs, but sees stale data: for example, a force written before the
+//! forces are cleared is lost, and a force written after the velocities are integrated has no effect until the
+//! next step.
+//!
+//! The [RecordingDispatcherBuilder] of a [SimulationBuilder](crate::simulation::SimulationBuilder) records the
+//! name, dependencies and resource accesses of each system, and [validate_dispatch_order] checks the invariants
+//! that the integrator relies on.
+//!
+//! Functions that add the systems of a module are generic over [SystemDispatcherBuilder], so they accept either a
+//! [RecordingDispatcherBuilder] or a plain [DispatcherBuilder].
+//!
+//! `SimulationBuilder::dispatcher_builder` used to be a plain [DispatcherBuilder]. A [RecordingDispatcherBuilder]
+//! dereferences to the [DispatcherBuilder] it wraps, so code written for the old type still compiles, but systems
+//! added through the [DispatcherBuilder] directly are not recorded, and are not checked by [validate_dispatch_order].
+
+use std::fmt;
+use std::sync::Arc;
+
+use rayon::ThreadPool;
+use specs::prelude::*;
+use specs::shred::{Accessor, ResourceId, RunNow};
+use specs::storage::MaskedStorage;
+
+use crate::atom::Force;
+use crate::integrator::{INTEGRATE_POSITION_SYSTEM_NAME, INTEGRATE_VELOCITY_SYSTEM_NAME};
+
+/// Name of the system that clears the forces at the start of each step.
+pub const CLEAR_FORCE_SYSTEM_NAME: &str = "clear";
+
+/// Systems whose names start with these prefixes must run after the positions are integrated.
+const SAMPLE_AFTER_INTEGRATION: [&str; 2] = ["sample_laser_intensity", "sample_intensity_gradient"];
+
+/// The name, dependencies and resource accesses of a system added to a [RecordingDispatcherBuilder].
+struct SystemRecord {
+    name: String,
+    deps: Vec<String>,
+    reads: Vec<ResourceId>,
+    writes: Vec<ResourceId>,
+    /// Number of barriers added before the system.
+    barrier: usize,
+    thread_local: bool,
+}
+impl SystemRecord {
+    /// True if the systems access the same resource, and at least one of them writes it.
+    fn conflicts_with(&self, other: &SystemRecord) -> bool {
+        self.writes
+            .iter()
+            .any(|id| other.reads.contains(id) || other.writes.contains(id))
+            || other.writes.iter().any(|id| self.reads.contains(id))
+    }
+
+    fn writes_force(&self) -> bool {
+        self.writes
+            .contains(&ResourceId::new::<MaskedStorage<Force>>())
+    }
+}
+
+/// A [DispatcherBuilder] that records the systems added to it, see [crate::dispatch].
+#[derive(Default)]
+pub struct RecordingDispatcherBuilder {
+    builder: DispatcherBuilder<'static, 'static>,
+    systems: Vec<SystemRecord>,
+    barriers: usize,
+}
+impl RecordingDispatcherBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a system, see [DispatcherBuilder::add].
+    pub fn add<T>(&mut self, system: T, name: &str, deps: &[&str])
+    where
+        T: for<'c> System<'c> + Send + 'static,
+    {
+        let (reads, writes) = {
+            let accessor = system.accessor();
+            (accessor.reads(), accessor.writes())
+        };
+        self.builder.add(system, name, deps);
+        self.systems.push(SystemRecord {
+            name: name.to_string(),
+            deps: deps.iter().map(|dep| dep.to_string()).collect(),
+            reads,
+            writes,
+            barrier: self.barriers,
+            thread_local: false,
+        });
+    }
+
+    /// Adds a system that runs on the main thread after all other systems, see
+    /// [DispatcherBuilder::add_thread_local].
+    pub fn add_thread_local<T>(&mut self, system: T)
+    where
+        T: for<'c> RunNow<'c> + 'static,
+    {
+        self.builder.add_thread_local(system);
+        self.systems.push(SystemRecord {
+            name: String::new(),
+            deps: Vec::new(),
+            reads: Vec::new(),
+            writes: Vec::new(),
+            barrier: self.barriers,
+            thread_local: true,
+        });
+    }
+
+    /// Adds a barrier, so that systems added afterwards run after all systems added before, see
+    /// [DispatcherBuilder::add_barrier].
+    pub fn add_barrier(&mut self) {
+        self.builder.add_barrier();
+        self.barriers += 1;
+    }
+
+    /// Sets the thread pool of the dispatcher, see [DispatcherBuilder::add_pool].
+    pub fn add_pool(&mut self, pool: Arc<ThreadPool>) {
+        self.builder.add_pool(pool);
+    }
+
+    /// True if a system with the given name has been added.
+    pub fn has_system(&self, name: &str) -> bool {
+        self.find(name).is_some()
+    }
+
+    /// Builds the dispatcher.
+    pub fn build(self) -> Dispatcher<'static, 'static> {
+        self.builder.build()
+    }
+
+    fn find(&self, name: &str) -> Option<usize> {
+        if name.is_empty() {
+            return None;
+        }
+        self.systems.iter().position(|system| system.name == name)
+    }
+
+    /// The systems that the system at `index` waits for directly: its dependencies, and the previously added
+    /// systems that conflict with it. Systems in earlier barrier regions are not included.
+    fn predecessors(&self, index: usize) -> impl Iterator<Item = usize> + '_ {
+        let system = &self.systems[index];
+        self.systems[..index]
+            .iter()
+            .enumerate()
+            .filter(move |(_, other)| {
+                other.barrier == system.barrier
+                    && !other.thread_local
+                    && ((!other.name.is_empty() && system.deps.contains(&other.name))
+                        || other.conflicts_with(system))
+            })
+            .map(|(i, _)| i)
+    }
+
+    /// True if the system at `first` always completes before the system at `second` starts.
+    fn runs_before(&self, first: usize, second: usize) -> bool {
+        let (a, b) = (&self.systems[first], &self.systems[second]);
+        if first == second {
+            return false;
+        }
+        if b.thread_local {
+            return !a.thread_local || first < second;
+        }
+        if a.thread_local {
+            return false;
+        }
+        if a.barrier != b.barrier {
+            return a.barrier < b.barrier;
+        }
+        if first > second {
+            return false;
+        }
+        let mut visited = vec![false; self.systems.len()];
+        let mut stack = vec![second];
+        while let Some(index) = stack.pop() {
+            for predecessor in self.predecessors(index) {
+                if predecessor == first {
+                    return true;
+                }
+                if predecessor > first && !visited[predecessor] {
+                    visited[predecessor] = true;
+                    stack.push(predecessor);
+                }
+            }
+        }
+        false
+    }
+
+    fn display_name(&self, index: usize) -> String {
+        match self.systems[index].name.as_str() {
+            "" => format!("<unnamed system #{}>", index),
+            name => name.to_string(),
+        }
+    }
+}
+
+/// A builder to which systems can be added, implemented by both [DispatcherBuilder] and
+/// [RecordingDispatcherBuilder].
+pub trait SystemDispatcherBuilder {
+    /// Adds a system, see [DispatcherBuilder::add].
+    fn add<T>(&mut self, system: T, name: &str, deps: &[&str])
+    where
+        T: for<'c> System<'c> + Send + 'static;
+
+    /// Adds a system that runs on the main thread after all other systems, see
+    /// [DispatcherBuilder::add_thread_local].
+    fn add_thread_local<T>(&mut self, system: T)
+    where
+        T: for<'c> RunNow<'c> + 'static;
+
+    /// Adds a barrier, see [DispatcherBuilder::add_barrier].
+    fn add_barrier(&mut self);
+
+    /// Calls `f` with a [RecordingDispatcherBuilder] that adds its systems to this builder.
+    fn with_recording(&mut self, f: &mut dyn FnMut(&mut RecordingDispatcherBuilder));
+}
+/// Gives access to the wrapped [DispatcherBuilder], for code written when
+/// `SimulationBuilder::dispatcher_builder` was a plain [DispatcherBuilder].
+///
+/// Systems added through it are not recorded: [RecordingDispatcherBuilder::has_system] does not find them, and
+/// [validate_dispatch_order] does not check them.
+impl std::ops::Deref for RecordingDispatcherBuilder {
+    type Target = DispatcherBuilder<'static, 'static>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.builder
+    }
+}
+impl std::ops::DerefMut for RecordingDispatcherBuilder {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.builder
+    }
+}
+
+impl SystemDispatcherBuilder for RecordingDispatcherBuilder {
+    fn add<T>(&mut self, system: T, name: &str, deps: &[&str])
+    where
+        T: for<'c> System<'c> + Send + 'static,
+    {
+        RecordingDispatcherBuilder::add(self, system, name, deps);
+    }
+
+    fn add_thread_local<T>(&mut self, system: T)
+    where
+        T: for<'c> RunNow<'c> + 'static,
+    {
+        RecordingDispatcherBuilder::add_thread_local(self, system);
+    }
+
+    fn add_barrier(&mut self) {
+        RecordingDispatcherBuilder::add_barrier(self);
+    }
+
+    fn with_recording(&mut self, f: &mut dyn FnMut(&mut RecordingDispatcherBuilder)) {
+        f(self);
+    }
+}
+impl SystemDispatcherBuilder for DispatcherBuilder<'static, 'static> {
+    fn add<T>(&mut self, system: T, name: &str, deps: &[&str])
+    where
+        T: for<'c> System<'c> + Send + 'static,
+    {
+        DispatcherBuilder::add(self, system, name, deps);
+    }
+
+    fn add_thread_local<T>(&mut self, system: T)
+    where
+        T: for<'c> RunNow<'c> + 'static,
+    {
+        DispatcherBuilder::add_thread_local(self, system);
+    }
+
+    fn add_barrier(&mut self) {
+        DispatcherBuilder::add_barrier(self);
+    }
+
+    fn with_recording(&mut self, f: &mut dyn FnMut(&mut RecordingDispatcherBuilder)) {
+        let mut recording = RecordingDispatcherBuilder {
+            builder: std::mem::take(self),
+            ..Default::default()
+        };
+        f(&mut recording);
+        *self = recording.builder;
+    }
+}
+
+/// A system that may run before a system it must follow, see [validate_dispatch_order].
+#[derive(Clone, Debug, PartialEq)]
+pub struct OrderingError {
+    /// Name of the system that runs too early.
+    pub system: String,
+    /// Name of the system that it must run after.
+    pub predecessor: String,
+    /// Why the order matters.
+    pub reason: &'static str,
+}
+impl fmt::Display for OrderingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "System `{}` may run before `{}`, but {}. Add `{}` to the dependencies of `{}`, or add it after a barrier.",
+            self.system, self.predecessor, self.reason, self.predecessor, self.system
+        )
+    }
+}
+
+/// Checks the order of the systems added to a [RecordingDispatcherBuilder].
+///
+/// The invariants are:
+///
+/// * Every system that writes the [Force] after the positions are integrated runs after the forces are cleared.
+/// * The laser intensities and gradients are sampled after the positions are integrated.
+/// * The velocities are integrated after every system that writes the [Force].
+///
+/// Invariants that involve a system which has not been added are not checked. Returns the first violation.
+pub fn validate_dispatch_order(builder: &RecordingDispatcherBuilder) -> Result<(), OrderingError> {
+    let position = builder.find(INTEGRATE_POSITION_SYSTEM_NAME);
+    let clear = builder.find(CLEAR_FORCE_SYSTEM_NAME);
+    let velocity = builder.find(INTEGRATE_VELOCITY_SYSTEM_NAME);
+    let must_follow = |system: usize, predecessor: usize, reason: &'static str| {
+        if builder.runs_before(predecessor, system) {
+            Ok(())
+        } else {
+            Err(OrderingError {
+                system: builder.display_name(system),
+                predecessor: builder.display_name(predecessor),
+                reason,
+            })
+        }
+    };
+
+    let force_writers: Vec<usize> = (0..builder.systems.len())
+        .filter(|&i| builder.systems[i].writes_force() && Some(i) != clear)
+        .collect();
+
+    if let Some(clear) = clear {
+        for &writer in force_writers.iter() {
+            if position.map_or(false, |position| builder.runs_before(writer, position)) {
+                continue;
+            }
+            must_follow(
+                writer,
+                clear,
+                "forces must be cleared before they are applied",
+            )?;
+        }
+    }
+
+    if let Some(position) = position {
+        for (i, system) in builder.systems.iter().enumerate() {
+            if SAMPLE_AFTER_INTEGRATION
+                .iter()
+                .any(|prefix| system.name.starts_with(prefix))
+            {
+                must_follow(
+                    i,
+                    position,
+                    "beams must be sampled at the integrated positions",
+                )?;
+            }
+        }
+    }
+
+    if let Some(velocity) = velocity {
+        for &writer in force_writers.iter() {
+            if position.map_or(false, |position| builder.runs_before(writer, position)) {
+                continue;
+            }
+            must_follow(
+                velocity,
+                writer,
+                "all forces must be applied before the velocities are integrated",
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use crate::atom::ClearForceSystem;
+    use crate::custom_force::CustomForcePlugin;
+    use crate::dipole::DipolePlugin;
+    use crate::gravity::ApplyGravitationalForceSystem;
+    use crate::initiate::{ValidateNewAtomsSystem, VALIDATE_NEW_ATOMS_SYSTEM_NAME};
+    use crate::integrator::{
+        VelocityVerletIntegratePositionSystem, VelocityVerletIntegrateVelocitySystem,
+    };
+    use crate::laser::LaserPlugin;
+    use crate::laser_cooling::LaserCoolingPlugin;
+    use crate::simulation::SimulationBuilder;
+    use crate::species::Rubidium87_780D2;
+
+    #[test]
+    fn test_default_configuration_is_valid() {
+        let mut sim_builder = SimulationBuilder::default();
+        sim_builder.add_plugin(LaserPlugin::<8>);
+        sim_builder.add_plugin(LaserCoolingPlugin::<Rubidium87_780D2, 8>::default());
+        sim_builder.add_plugin(DipolePlugin::<8>);
+        sim_builder.add_end_frame_systems();
+        assert_eq!(
+            validate_dispatch_order(&sim_builder.dispatcher_builder),
+            Ok(())
+        );
+        sim_builder.build();
+    }
+
+    #[test]
+    fn test_dispatcher_builder_accepts_plain_dispatcher_builder_functions() {
+        fn add_gravity(builder: &mut DispatcherBuilder<'static, 'static>) {
+            builder.add(ApplyGravitationalForceSystem, "add_gravity", &[]);
+        }
+
+        let mut sim_builder = SimulationBuilder::default();
+        add_gravity(&mut sim_builder.dispatcher_builder);
+        assert!(!sim_builder.dispatcher_builder.has_system("add_gravity"));
+        sim_builder.build();
+    }
+
+    #[test]
+    fn test_force_before_clear_is_detected() {
+        let mut builder = RecordingDispatcherBuilder::new();
+        builder.add(ValidateNewAtomsSystem, VALIDATE_NEW_ATOMS_SYSTEM_NAME, &[]);
+        builder.add(
+            VelocityVerletIntegratePositionSystem,
+            INTEGRATE_POSITION_SYSTEM_NAME,
+            &[VALIDATE_NEW_ATOMS_SYSTEM_NAME],
+        );
+        builder.add(
+            ApplyGravitationalForceSystem,
+            "add_gravity",
+            &[INTEGRATE_POSITION_SYSTEM_NAME],
+        );
+        builder.add(
+            ClearForceSystem,
+            CLEAR_FORCE_SYSTEM_NAME,
+            &[INTEGRATE_POSITION_SYSTEM_NAME],
+        );
+        builder.add_barrier();
+        builder.add(
+            VelocityVerletIntegrateVelocitySystem,
+            INTEGRATE_VELOCITY_SYSTEM_NAME,
+            &[],
+        );
+
+        let error = validate_dispatch_order(&builder).unwrap_err();
+        assert_eq!(error.system, "add_gravity");
+        assert_eq!(error.predecessor, CLEAR_FORCE_SYSTEM_NAME);
+        let message = error.to_string();
+        assert!(message.contains("add_gravity") && message.contains("cleared"));
+    }
+
+    #[test]
+    fn test_force_after_velocity_integration_is_detected() {
+        let mut sim_builder = SimulationBuilder::default();
+        sim_builder.add_end_frame_systems();
+        sim_builder.add_plugin(CustomForcePlugin);
+
+        let error = validate_dispatch_order(&sim_builder.dispatcher_builder).unwrap_err();
+        assert_eq!(error.system, INTEGRATE_VELOCITY_SYSTEM_NAME);
+    }
+}
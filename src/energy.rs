@@ -0,0 +1,375 @@
+//! Per-atom energy, split into its kinetic and potential components.
+//!
+//! The [EnergyComponents] of each atom are calculated from the samplers of the forces acting on it: the dipole
+//! energy from the [LaserIntensitySamplers] of the [DipoleLight] beams, the magnetic energy from the
+//! [MagneticFieldSampler], and the gravitational energy from the [Gravity] resource. A component is zero for an atom
+//! that the corresponding force does not act on.
+//!
+//! The [EnergyComponents] can be written to file by a [FileOutputPlugin](crate::output::file::FileOutputPlugin),
+//! to follow how the energy of the atoms is exchanged between the kinetic and potential terms, for example during
+//! evaporation or when a trap is ramped.
+
+use crate::atom::{GravitationalMass, Mass, Position, Velocity};
+use crate::constant;
+use crate::dipole::transitions::dipole_prefactor;
+use crate::dipole::{DipoleLight, MultiTransitionPolarizability, Polarizability};
+use crate::gravity::{ApplyGravityOption, Gravity};
+use crate::integrator::{SynchronizedVelocity, INTEGRATE_POSITION_SYSTEM_NAME};
+use crate::laser::index::LaserIndex;
+use crate::laser::intensity::LaserIntensitySamplers;
+use crate::magnetic::force::MagneticDipole;
+use crate::magnetic::MagneticFieldSampler;
+use crate::output::file::BinaryConversion;
+use crate::simulation::Plugin;
+use serde::Serialize;
+use specs::prelude::*;
+use std::fmt;
+
+/// Name of the system that calculates the [EnergyComponents], see [CalculateEnergyComponentsSystem].
+pub const CALCULATE_ENERGY_COMPONENTS_SYSTEM_NAME: &str = "calculate_energy_components";
+
+/// The energy of an atom, split into kinetic and potential components, in SI units of J.
+#[derive(Clone, Copy, Serialize, Default, Debug)]
+pub struct EnergyComponents {
+    /// Kinetic energy. The [SynchronizedVelocity] is used if present, and otherwise the [Velocity].
+    pub kinetic: f64,
+    /// Potential energy in the [DipoleLight] beams, `-prefactor * intensity` summed over the beams. The prefactor of a
+    /// [MultiTransitionPolarizability] is evaluated at the wavelength of each beam.
+    pub dipole: f64,
+    /// Gravitational potential energy, `-mass * gravity.acceleration . position`, which is zero at the origin.
+    pub gravity: f64,
+    /// Zeeman energy of the [MagneticDipole], `mFgF * BOHRMAG * |B|`.
+    pub magnetic: f64,
+}
+impl Component for EnergyComponents {
+    type Storage = VecStorage<Self>;
+}
+impl EnergyComponents {
+    /// The sum of the potential energies, in SI units of J.
+    pub fn potential(&self) -> f64 {
+        self.dipole + self.gravity + self.magnetic
+    }
+
+    /// The total energy, in SI units of J.
+    pub fn total(&self) -> f64 {
+        self.kinetic + self.potential()
+    }
+}
+impl fmt::Display for EnergyComponents {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "({:?},{:?},{:?},{:?})",
+            self.kinetic, self.dipole, self.gravity, self.magnetic
+        )
+    }
+}
+impl BinaryConversion for EnergyComponents {
+    fn data(&self) -> Vec<f64> {
+        vec![self.kinetic, self.dipole, self.gravity, self.magnetic]
+    }
+    fn field_names() -> Vec<&'static str> {
+        vec!["kinetic", "dipole", "gravity", "magnetic"]
+    }
+}
+
+/// Calculates the [EnergyComponents] of each atom with a [Mass].
+///
+/// The gravitational energy is only included if the [ApplyGravityOption] resource is present, and uses the
+/// [GravitationalMass] of an atom if it has one, as for the
+/// [ApplyGravitationalForceSystem](crate::gravity::ApplyGravitationalForceSystem).
+pub struct CalculateEnergyComponentsSystem<const N: usize>;
+impl<'a, const N: usize> System<'a> for CalculateEnergyComponentsSystem<N> {
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Velocity>,
+        ReadStorage<'a, SynchronizedVelocity>,
+        ReadStorage<'a, Mass>,
+        ReadStorage<'a, GravitationalMass>,
+        ReadStorage<'a, Polarizability>,
+        ReadStorage<'a, MultiTransitionPolarizability>,
+        ReadStorage<'a, LaserIntensitySamplers<N>>,
+        ReadStorage<'a, DipoleLight>,
+        ReadStorage<'a, LaserIndex>,
+        ReadStorage<'a, MagneticDipole>,
+        ReadStorage<'a, MagneticFieldSampler>,
+        Option<Read<'a, ApplyGravityOption>>,
+        Option<Read<'a, Gravity>>,
+        WriteStorage<'a, EnergyComponents>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            positions,
+            velocities,
+            synchronized,
+            masses,
+            gravitational_masses,
+            polarizabilities,
+            multi_transitions,
+            intensity_samplers,
+            dipole_lights,
+            indices,
+            dipoles,
+            field_samplers,
+            gravity_option,
+            gravity,
+            mut energies,
+        ): Self::SystemData,
+    ) {
+        let acceleration = gravity_option.map(|_| {
+            gravity
+                .map(|gravity| *gravity)
+                .unwrap_or_default()
+                .acceleration
+        });
+        let dipole_beams: Vec<(usize, f64)> = (&indices, &dipole_lights)
+            .join()
+            .map(|(index, light)| (index.index, light.wavelength))
+            .collect();
+
+        for (
+            entity,
+            pos,
+            vel,
+            synchronized,
+            mass,
+            gravitational_mass,
+            polarizability,
+            multi_transition,
+            intensities,
+            dipole,
+            field,
+        ) in (
+            &entities,
+            &positions,
+            &velocities,
+            synchronized.maybe(),
+            &masses,
+            gravitational_masses.maybe(),
+            polarizabilities.maybe(),
+            multi_transitions.maybe(),
+            intensity_samplers.maybe(),
+            dipoles.maybe(),
+            field_samplers.maybe(),
+        )
+            .join()
+        {
+            let vel = synchronized.map_or(vel.vel.cast::<f64>(), |synchronized| synchronized.vel);
+            let kinetic = 0.5 * mass.value * constant::AMU * vel.norm_squared();
+
+            let dipole_energy = match intensities {
+                Some(intensities) => dipole_beams
+                    .iter()
+                    .map(|&(index, wavelength)| {
+                        -dipole_prefactor(polarizability, multi_transition, wavelength)
+                            .unwrap_or_default()
+                            * intensities.contents[index].intensity
+                    })
+                    .sum(),
+                None => 0.0,
+            };
+
+            let gravity_energy = acceleration.map_or(0.0, |acceleration| {
+                let mass = gravitational_mass.map_or(mass.value, |mass| mass.value);
+                -mass * constant::AMU * acceleration.dot(&pos.pos.cast::<f64>())
+            });
+
+            let magnetic = match (dipole, field) {
+                (Some(dipole), Some(field)) => dipole.mFgF * constant::BOHRMAG * field.magnitude,
+                _ => 0.0,
+            };
+
+            energies
+                .insert(
+                    entity,
+                    EnergyComponents {
+                        kinetic,
+                        dipole: dipole_energy,
+                        gravity: gravity_energy,
+                        magnetic,
+                    },
+                )
+                .expect("Could not insert energy components.");
+        }
+    }
+}
+
+/// This plugin calculates the [EnergyComponents] of each atom, see [crate::energy].
+///
+/// Add this plugin after the plugins that sample the laser intensities and magnetic fields, such as the
+/// [LaserPlugin](crate::laser::LaserPlugin) and the [MagneticsPlugin](crate::magnetic::MagneticsPlugin), so that the
+/// energies are calculated from the samplers at the current positions. To use the [SynchronizedVelocity] for the
+/// kinetic energy, add the [SynchronizedVelocityPlugin](crate::integrator::SynchronizedVelocityPlugin) first.
+pub struct EnergyComponentsPlugin<const N: usize>;
+impl<const N: usize> Plugin for EnergyComponentsPlugin<N> {
+    fn build(&self, builder: &mut crate::simulation::SimulationBuilder) {
+        builder.world.register::<EnergyComponents>();
+        builder.dispatcher_builder.add(
+            CalculateEnergyComponentsSystem::<N>,
+            CALCULATE_ENERGY_COMPONENTS_SYSTEM_NAME,
+            &[INTEGRATE_POSITION_SYSTEM_NAME],
+        );
+    }
+    fn deps(&self) -> Vec<Box<dyn Plugin>> {
+        Vec::new()
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use crate::atom::{Atom, Force};
+    use crate::dipole::DipolePlugin;
+    use crate::initiate::NewlyCreated;
+    use crate::integrator::{Pinned, Timestep};
+    use crate::laser::gaussian::{get_gaussian_beam_intensity, GaussianBeam};
+    use crate::laser::LaserPlugin;
+    use crate::magnetic::uniform::UniformMagneticField;
+    use crate::simulation::SimulationBuilder;
+    use assert_approx_eq::assert_approx_eq;
+    use nalgebra::Vector3;
+
+    #[test]
+    fn test_energy_components_in_dipole_trap_under_gravity() {
+        let mut sim_builder = SimulationBuilder::default();
+        sim_builder.add_plugin(LaserPlugin::<1>);
+        sim_builder.add_plugin(DipolePlugin::<1>);
+        sim_builder.add_plugin(EnergyComponentsPlugin::<1>);
+        let mut sim = sim_builder.build();
+        sim.world.insert(Timestep { delta: 1.0e-6 });
+        sim.world.insert(ApplyGravityOption);
+
+        let beam = GaussianBeam::from_power_with_ellipticity_and_rayleigh_range(
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::x(),
+            5.0,
+            50.0e-6,
+            1064.0e-9,
+            0.0,
+        );
+        sim.world
+            .create_entity()
+            .with(beam)
+            .with(DipoleLight {
+                wavelength: 1064.0e-9,
+            })
+            .with(LaserIndex::default())
+            .build();
+        let field = Vector3::new(0.0, 0.0, 1.0e-4);
+        sim.world
+            .create_entity()
+            .with(UniformMagneticField::tesla(field))
+            .build();
+
+        // The atoms are pinned, so that they stay at the positions for which the energies are calculated.
+        let mass: f64 = 87.0;
+        let polarizability = Polarizability::calculate_for(1064.0e-9, 780.0e-9, 6.065e6);
+        let pos = Vector3::new(1.0e-4, 20.0e-6, -10.0e-6);
+        let vel: Vector3<f64> = Vector3::new(0.01, -0.02, 0.03);
+        let trapped = sim
+            .world
+            .create_entity()
+            .with(Position { pos: pos.cast() })
+            .with(Velocity { vel: vel.cast() })
+            .with(Mass { value: mass })
+            .with(Force::new())
+            .with(polarizability)
+            .with(MagneticDipole { mFgF: 0.5 })
+            .with(Atom)
+            .with(Pinned)
+            .with(NewlyCreated)
+            .build();
+        // An atom with no polarizability or magnetic dipole has only kinetic and gravitational energy.
+        let bare = sim
+            .world
+            .create_entity()
+            .with(Position { pos: pos.cast() })
+            .with(Velocity { vel: vel.cast() })
+            .with(Mass { value: mass })
+            .with(Force::new())
+            .with(Atom)
+            .with(Pinned)
+            .with(NewlyCreated)
+            .build();
+        // The same polarizability, expressed as a sum over transitions evaluated at the wavelength of the beam.
+        let multi_transition = sim
+            .world
+            .create_entity()
+            .with(Position { pos: pos.cast() })
+            .with(Velocity { vel: vel.cast() })
+            .with(Mass { value: mass })
+            .with(Force::new())
+            .with(MultiTransitionPolarizability::single(780.0e-9, 6.065e6))
+            .with(Atom)
+            .with(Pinned)
+            .with(NewlyCreated)
+            .build();
+
+        for _ in 0..3 {
+            sim.step();
+        }
+
+        let kinetic = 0.5 * mass * constant::AMU * vel.norm_squared();
+        let dipole = -polarizability.prefactor
+            * get_gaussian_beam_intensity(&beam, &Position { pos: pos.cast() }, None, None);
+        let gravity = mass * constant::AMU * constant::GC * pos[2];
+        let magnetic = 0.5 * constant::BOHRMAG * field.norm();
+        assert!(dipole < 0.0 && gravity < 0.0 && magnetic > 0.0);
+
+        let energies = sim.world.read_storage::<EnergyComponents>();
+        let energy = energies.get(trapped).expect("entity not found");
+        assert_approx_eq!(energy.kinetic, kinetic, kinetic * 1e-12);
+        assert_approx_eq!(energy.dipole, dipole, dipole.abs() * 1e-9);
+        assert_approx_eq!(energy.gravity, gravity, gravity.abs() * 1e-12);
+        assert_approx_eq!(energy.magnetic, magnetic, magnetic * 1e-12);
+        assert_approx_eq!(
+            energy.total(),
+            kinetic + dipole + gravity + magnetic,
+            dipole.abs() * 1e-9
+        );
+
+        let energy = energies.get(bare).expect("entity not found");
+        assert_approx_eq!(energy.kinetic, kinetic, kinetic * 1e-12);
+        assert_eq!(energy.dipole, 0.0);
+        assert_approx_eq!(energy.gravity, gravity, gravity.abs() * 1e-12);
+        assert_eq!(energy.magnetic, 0.0);
+
+        let energy = energies.get(multi_transition).expect("entity not found");
+        assert_approx_eq!(energy.dipole, dipole, dipole.abs() * 1e-9);
+    }
+
+    #[test]
+    fn test_gravity_energy_requires_gravity_option() {
+        let mut sim = {
+            let mut sim_builder = SimulationBuilder::default();
+            sim_builder.add_plugin(EnergyComponentsPlugin::<1>);
+            sim_builder.build()
+        };
+        sim.world.insert(Timestep { delta: 1.0e-6 });
+        let atom = sim
+            .world
+            .create_entity()
+            .with(Position {
+                pos: Vector3::new(0.0, 0.0, 1.0e-3).cast(),
+            })
+            .with(Velocity {
+                vel: Vector3::new(0.0, 0.0, 0.0).cast(),
+            })
+            .with(Mass { value: 87.0 })
+            .with(Force::new())
+            .with(Atom)
+            .with(Pinned)
+            .with(NewlyCreated)
+            .build();
+        sim.step();
+
+        let energies = sim.world.read_storage::<EnergyComponents>();
+        let energy = energies.get(atom).expect("entity not found");
+        assert_eq!(energy.total(), 0.0);
+    }
+}
@@ -0,0 +1,463 @@
+//! Detection of thermal equilibrium during a cooling run.
+//!
+//! The [EquilibriumDetector] resource records the temperature of the atom cloud each step, and flags
+//! equilibrium once the temperature has settled. User code, for example a step callback, can consult
+//! [EquilibriumDetector::is_equilibrium] to stop the simulation or move on to the next phase.
+//!
+//! The [AveragedTemperature] resource accumulates the time-averaged temperature, ignoring an initial settling
+//! period so that the loading transient does not bias the average.
+
+use std::collections::VecDeque;
+
+use crate::atom::{total_weight, Atom, Mass, StatisticalWeight, SuperAtomWeight, Velocity};
+use crate::constant::{AMU, BOLTZCONST};
+use crate::integrator::{SimulationTime, Step, Timestep, INTEGRATE_VELOCITY_SYSTEM_NAME};
+use crate::laser_cooling::transition::AtomicTransition;
+use crate::simulation::Plugin;
+use nalgebra::Vector3;
+use specs::prelude::*;
+
+/// A resource that tracks the temperature of the atoms to detect thermal equilibrium.
+///
+/// The temperature is considered settled when the relative change in temperature across a sliding `window`
+/// is below `threshold`. Equilibrium is flagged once the temperature has remained settled for `duration`.
+///
+/// A slow drift may change the temperature by less than `threshold` in each window, yet never reach
+/// equilibrium. To avoid flagging such drifts, the temperature must also remain within `threshold` of the
+/// temperature at which it first settled; otherwise, the settling period restarts. Once flagged, equilibrium
+/// persists until the relative change across the window exceeds `threshold`.
+pub struct EquilibriumDetector {
+    /// Duration of the sliding window, in SI units of s.
+    pub window: f64,
+    /// Maximum relative change in temperature across the window for the temperature to be settled.
+    pub threshold: f64,
+    /// Duration for which the temperature must remain settled before equilibrium is flagged, in SI units of s.
+    pub duration: f64,
+    /// The recorded `(time, temperature)` samples within the window.
+    samples: VecDeque<(f64, f64)>,
+    /// The `(time, temperature)` at which the temperature settled.
+    settled: Option<(f64, f64)>,
+    /// The time at which equilibrium was flagged.
+    equilibrium_time: Option<f64>,
+    /// The most recently recorded temperature.
+    temperature: Option<f64>,
+}
+impl EquilibriumDetector {
+    /// Creates a new `EquilibriumDetector`.
+    ///
+    /// # Arguments
+    ///
+    /// `window`: duration of the sliding window, in SI units of s.
+    ///
+    /// `threshold`: maximum relative change in temperature across the window, eg `0.01` for 1%.
+    ///
+    /// `duration`: time the temperature must remain settled before equilibrium is flagged, in SI units of s.
+    pub fn new(window: f64, threshold: f64, duration: f64) -> Self {
+        EquilibriumDetector {
+            window,
+            threshold,
+            duration,
+            samples: VecDeque::new(),
+            settled: None,
+            equilibrium_time: None,
+            temperature: None,
+        }
+    }
+
+    /// Records the temperature at the given time, and updates the equilibrium flag.
+    ///
+    /// # Arguments
+    ///
+    /// `time`: simulation time, in SI units of s.
+    ///
+    /// `temperature`: temperature of the atoms, in SI units of K.
+    pub fn record(&mut self, time: f64, temperature: f64) {
+        self.temperature = Some(temperature);
+        self.samples.push_back((time, temperature));
+        while self.samples.len() > 1 && self.samples[1].0 <= time - self.window {
+            self.samples.pop_front();
+        }
+
+        let (oldest_time, oldest_temperature) = self.samples[0];
+        let window_filled = oldest_time <= time - self.window;
+        let relative_change = |reference: f64| (temperature - reference).abs() / temperature;
+        if !window_filled || relative_change(oldest_temperature) >= self.threshold {
+            self.settled = None;
+            self.equilibrium_time = None;
+            return;
+        }
+
+        if self.equilibrium_time.is_some() {
+            return;
+        }
+        match self.settled {
+            Some((_, settled_temperature))
+                if relative_change(settled_temperature) < self.threshold => {}
+            _ => self.settled = Some((time, temperature)),
+        }
+        let (settled_time, _) = self.settled.unwrap();
+        if time - settled_time >= self.duration {
+            self.equilibrium_time = Some(time);
+        }
+    }
+
+    /// Returns true if the atoms are in thermal equilibrium.
+    pub fn is_equilibrium(&self) -> bool {
+        self.equilibrium_time.is_some()
+    }
+
+    /// The time at which equilibrium was flagged, in SI units of s, or `None` if not in equilibrium.
+    pub fn equilibrium_time(&self) -> Option<f64> {
+        self.equilibrium_time
+    }
+
+    /// The most recently recorded temperature, in SI units of K.
+    pub fn temperature(&self) -> Option<f64> {
+        self.temperature
+    }
+
+    /// The ratio of the most recently recorded temperature to the Doppler limit of the transition `T`.
+    ///
+    /// A ratio below one indicates that sub-Doppler cooling mechanisms are active.
+    pub fn doppler_ratio<T: AtomicTransition>(&self) -> Option<f64> {
+        self.temperature
+            .map(|temperature| temperature / T::doppler_temperature())
+    }
+
+    /// Clears the recorded temperatures, for example when starting a new phase of the simulation.
+    pub fn reset(&mut self) {
+        self.temperature = None;
+        self.samples.clear();
+        self.settled = None;
+        self.equilibrium_time = None;
+    }
+}
+
+/// A resource that accumulates the time-averaged temperature of the atoms.
+///
+/// The temperature recorded during the first `settling_steps` steps is discarded, so that the average
+/// describes the equilibrium state rather than the initial transient.
+pub struct AveragedTemperature {
+    /// Number of steps at the start of the run that are excluded from the average.
+    pub settling_steps: u64,
+    /// Sum of the recorded temperatures, in SI units of K.
+    sum: f64,
+    /// Number of recorded temperatures.
+    samples: u64,
+}
+impl AveragedTemperature {
+    /// Creates a new `AveragedTemperature` that ignores the first `settling_steps` steps.
+    pub fn new(settling_steps: u64) -> Self {
+        AveragedTemperature {
+            settling_steps,
+            sum: 0.0,
+            samples: 0,
+        }
+    }
+
+    /// Records the temperature of the atoms, in SI units of K, at step number `step`.
+    ///
+    /// Temperatures recorded before `settling_steps` are ignored.
+    pub fn record(&mut self, step: u64, temperature: f64) {
+        if step < self.settling_steps {
+            return;
+        }
+        self.sum += temperature;
+        self.samples += 1;
+    }
+
+    /// The time-averaged temperature, in SI units of K.
+    ///
+    /// Returns `None` if no temperature has been recorded since the settling period, for example if the run was
+    /// shorter than the settling period.
+    pub fn mean(&self) -> Option<f64> {
+        if self.samples == 0 {
+            None
+        } else {
+            Some(self.sum / self.samples as f64)
+        }
+    }
+
+    /// The number of temperatures included in the average.
+    pub fn samples(&self) -> u64 {
+        self.samples
+    }
+
+    /// Clears the recorded temperatures. The settling period is counted from the step number, so it is not
+    /// restarted.
+    pub fn reset(&mut self) {
+        self.sum = 0.0;
+        self.samples = 0;
+    }
+}
+
+/// Calculates the temperature of a collection of atoms from their velocities, in SI units of K.
+///
+/// The temperature is calculated from the kinetic energy in the centre-of-mass frame,
+/// `T = sum(m |v - <v>|^2) / (3 N k_B)`.
+///
+/// # Arguments
+///
+/// `atoms`: velocities (in m/s) and masses (in atomic mass units) of the atoms.
+pub fn measure_temperature(atoms: &[(Vector3<f64>, f64)]) -> f64 {
+    let weighted: Vec<(Vector3<f64>, f64, f64)> =
+        atoms.iter().map(|(vel, mass)| (*vel, *mass, 1.0)).collect();
+    measure_weighted_temperature(&weighted)
+}
+
+/// Calculates the temperature of a collection of atoms with [StatisticalWeight]s, in SI units of K.
+///
+/// The temperature is calculated as for [measure_temperature], with weighted sums and averages,
+/// `T = sum(w m |v - <v>_w|^2) / (3 k_B sum(w))`.
+///
+/// # Arguments
+///
+/// `atoms`: velocities (in m/s), masses (in atomic mass units) and statistical weights of the atoms.
+pub fn measure_weighted_temperature(atoms: &[(Vector3<f64>, f64, f64)]) -> f64 {
+    let total_weight: f64 = atoms.iter().map(|(_, _, weight)| weight).sum();
+    let mean_velocity = atoms
+        .iter()
+        .fold(Vector3::new(0.0, 0.0, 0.0), |sum, (vel, _, weight)| {
+            sum + vel * *weight
+        })
+        / total_weight;
+    let energy: f64 = atoms
+        .iter()
+        .map(|(vel, mass, weight)| weight * mass * AMU * (vel - mean_velocity).norm_squared())
+        .sum();
+    energy / (3.0 * total_weight * BOLTZCONST)
+}
+
+/// Measures the temperature of the atoms each step, and records it in the [EquilibriumDetector].
+///
+/// The temperature accounts for the [StatisticalWeight] and [SuperAtomWeight] of each atom.
+///
+/// Does nothing if the [EquilibriumDetector] resource is not present, or if there are no atoms.
+pub struct UpdateEquilibriumDetectorSystem;
+impl<'a> System<'a> for UpdateEquilibriumDetectorSystem {
+    type SystemData = (
+        ReadStorage<'a, Velocity>,
+        ReadStorage<'a, Mass>,
+        ReadStorage<'a, StatisticalWeight>,
+        ReadStorage<'a, SuperAtomWeight>,
+        ReadStorage<'a, Atom>,
+        ReadExpect<'a, Step>,
+        ReadExpect<'a, Timestep>,
+        Option<Write<'a, EquilibriumDetector>>,
+    );
+
+    fn run(
+        &mut self,
+        (velocities, masses, weights, super_atoms, atoms, step, timestep, detector): Self::SystemData,
+    ) {
+        let mut detector = match detector {
+            Some(detector) => detector,
+            None => return,
+        };
+        let samples: Vec<(Vector3<f64>, f64, f64)> =
+            (&velocities, &masses, weights.maybe(), super_atoms.maybe(), &atoms)
+                .join()
+                .map(|(vel, mass, weight, super_atom, _)| {
+                    (vel.vel.cast::<f64>(), mass.value, total_weight(weight, super_atom))
+                })
+                .collect();
+        if samples.is_empty() {
+            return;
+        }
+        let time = SimulationTime::new(&step, &timestep).time;
+        detector.record(time, measure_weighted_temperature(&samples));
+    }
+}
+
+/// Records the temperature of the atoms each step in the [AveragedTemperature].
+///
+/// The temperature accounts for the [StatisticalWeight] and [SuperAtomWeight] of each atom.
+///
+/// Does nothing if the [AveragedTemperature] resource is not present, or if there are no atoms.
+pub struct UpdateAveragedTemperatureSystem;
+impl<'a> System<'a> for UpdateAveragedTemperatureSystem {
+    type SystemData = (
+        ReadStorage<'a, Velocity>,
+        ReadStorage<'a, Mass>,
+        ReadStorage<'a, StatisticalWeight>,
+        ReadStorage<'a, SuperAtomWeight>,
+        ReadStorage<'a, Atom>,
+        ReadExpect<'a, Step>,
+        Option<Write<'a, AveragedTemperature>>,
+    );
+
+    fn run(&mut self, (velocities, masses, weights, super_atoms, atoms, step, average): Self::SystemData) {
+        let mut average = match average {
+            Some(average) => average,
+            None => return,
+        };
+        let samples: Vec<(Vector3<f64>, f64, f64)> =
+            (&velocities, &masses, weights.maybe(), super_atoms.maybe(), &atoms)
+                .join()
+                .map(|(vel, mass, weight, super_atom, _)| {
+                    (vel.vel.cast::<f64>(), mass.value, total_weight(weight, super_atom))
+                })
+                .collect();
+        if samples.is_empty() {
+            return;
+        }
+        average.record(step.n, measure_weighted_temperature(&samples));
+    }
+}
+
+/// This plugin updates the [EquilibriumDetector] and [AveragedTemperature] resources, if present, each step.
+///
+/// See also [crate::equilibrium].
+pub struct EquilibriumPlugin;
+impl Plugin for EquilibriumPlugin {
+    fn build(&self, builder: &mut crate::simulation::SimulationBuilder) {
+        builder.dispatcher_builder.add(
+            UpdateEquilibriumDetectorSystem,
+            "update_equilibrium_detector",
+            &[INTEGRATE_VELOCITY_SYSTEM_NAME],
+        );
+        builder.dispatcher_builder.add(
+            UpdateAveragedTemperatureSystem,
+            "update_averaged_temperature",
+            &[INTEGRATE_VELOCITY_SYSTEM_NAME],
+        );
+    }
+    fn deps(&self) -> Vec<Box<dyn Plugin>> {
+        Vec::new()
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use crate::species::Rubidium87_780D2;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_exponential_relaxation_reaches_equilibrium() {
+        let (initial, equilibrium, tau) = (1.0e-3, 100.0e-6, 1.0e-3);
+        let (window, threshold, duration) = (0.5e-3, 0.01, 0.5e-3);
+        let dt = 1.0e-6;
+        let mut detector = EquilibriumDetector::new(window, threshold, duration);
+
+        // The relative change across the window first falls below the threshold at time `settle`.
+        let growth = (window / tau).exp() - 1.0;
+        let settle = -tau
+            * (threshold * equilibrium / ((initial - equilibrium) * (growth - threshold))).ln();
+        let expected = settle + duration;
+
+        let mut flagged = None;
+        for step in 0..20_000 {
+            let time = step as f64 * dt;
+            let temperature = equilibrium + (initial - equilibrium) * (-time / tau).exp();
+            detector.record(time, temperature);
+            if detector.is_equilibrium() && flagged.is_none() {
+                flagged = Some(time);
+            }
+            if time < expected - 2.0 * dt {
+                assert!(!detector.is_equilibrium());
+            }
+        }
+        assert_approx_eq!(flagged.unwrap(), expected, 2.0 * dt);
+        assert_eq!(detector.equilibrium_time(), flagged);
+        assert_approx_eq!(
+            detector.doppler_ratio::<Rubidium87_780D2>().unwrap(),
+            detector.temperature().unwrap() / Rubidium87_780D2::doppler_temperature(),
+            1e-12
+        );
+
+        detector.reset();
+        assert!(!detector.is_equilibrium());
+        assert_eq!(detector.doppler_ratio::<Rubidium87_780D2>(), None);
+    }
+
+    #[test]
+    fn test_slow_drift_is_not_equilibrium() {
+        let (window, threshold, duration) = (0.5e-3, 0.01, 1.0e-3);
+        let dt = 1.0e-6;
+        let mut detector = EquilibriumDetector::new(window, threshold, duration);
+
+        // The temperature rises by 1.5% per ms, which is less than the threshold in each window.
+        for step in 0..20_000 {
+            let time = step as f64 * dt;
+            detector.record(time, 100.0e-6 * (1.0 + 15.0 * time));
+            assert!(!detector.is_equilibrium());
+        }
+    }
+
+    #[test]
+    fn test_averaged_temperature_excludes_settling_period() {
+        let mut average = AveragedTemperature::new(50);
+
+        // A hot loading transient, followed by a steady temperature.
+        for step in 0..50 {
+            average.record(step, 1.0e-3);
+        }
+        assert_eq!(average.mean(), None);
+        assert_eq!(average.samples(), 0);
+
+        for step in 50..150 {
+            let fluctuation = if step % 2 == 0 { 1.0e-6 } else { -1.0e-6 };
+            average.record(step, 100.0e-6 + fluctuation);
+        }
+        assert_eq!(average.samples(), 100);
+        assert_approx_eq!(average.mean().unwrap(), 100.0e-6, 1e-15);
+
+        average.reset();
+        assert_eq!(average.mean(), None);
+        average.record(150, 200.0e-6);
+        assert_eq!(average.mean(), Some(200.0e-6));
+    }
+
+    #[test]
+    fn test_averaged_temperature_system() {
+        let mut world = World::new();
+        world.register::<Velocity>();
+        world.register::<Mass>();
+        world.register::<StatisticalWeight>();
+        world.register::<SuperAtomWeight>();
+        world.register::<Atom>();
+        world.insert(AveragedTemperature::new(2));
+        let mass = 87.0;
+        let speed: f64 = 0.1;
+        for dir in [Vector3::x(), -Vector3::x()].iter() {
+            world
+                .create_entity()
+                .with(Velocity {
+                    vel: (speed * dir).cast(),
+                })
+                .with(Mass { value: mass })
+                .with(Atom)
+                .build();
+        }
+
+        // The run is shorter than the settling period, so no data is collected.
+        let mut system = UpdateAveragedTemperatureSystem;
+        for n in 0..2 {
+            world.insert(Step { n });
+            system.run_now(&world);
+        }
+        assert_eq!(world.read_resource::<AveragedTemperature>().mean(), None);
+
+        world.insert(Step { n: 2 });
+        system.run_now(&world);
+        let expected = mass * AMU * speed.powi(2) / (3.0 * BOLTZCONST);
+        let average = world.read_resource::<AveragedTemperature>();
+        assert_eq!(average.samples(), 1);
+        assert_approx_eq!(average.mean().unwrap(), expected, expected * 1e-12);
+    }
+
+    #[test]
+    fn test_measure_temperature() {
+        let mass = 87.0;
+        let speed: f64 = 0.1;
+        let drift = Vector3::new(1.0, 2.0, 3.0);
+        let atoms: Vec<(Vector3<f64>, f64)> = [Vector3::x(), -Vector3::x()]
+            .iter()
+            .map(|dir| (drift + speed * dir, mass))
+            .collect();
+        let expected = mass * AMU * speed.powi(2) / (3.0 * BOLTZCONST);
+        assert_approx_eq!(measure_temperature(&atoms), expected, expected * 1e-12);
+    }
+}
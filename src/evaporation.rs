@@ -0,0 +1,289 @@
+//! Forced evaporation by a truncation in energy.
+//!
+//! In RF or optical evaporation, the most energetic atoms are removed from the trap, and the remaining atoms
+//! rethermalize at a lower temperature. The [EvaporationCut] resource models the truncation directly: atoms whose
+//! kinetic plus potential energy exceeds a threshold are deleted. The threshold can be ramped down over time to
+//! force the evaporation.
+
+use crate::atom::{Atom, Mass, Position, Velocity};
+use crate::constant::{AMU, BOHRMAG};
+use crate::dipole::trapped::dipole_potential_energy;
+use crate::dipole::{DipoleLight, Polarizability};
+use crate::integrator::{SimulationTime, Step, Timestep, INTEGRATE_VELOCITY_SYSTEM_NAME};
+use crate::laser::frame::Frame;
+use crate::laser::gaussian::{CircularMask, GaussianBeam};
+use crate::magnetic::force::MagneticDipole;
+use crate::magnetic::{MagneticFieldSampler, MagneticsPlugin};
+use crate::simulation::Plugin;
+use specs::prelude::*;
+
+/// A linear ramp of the threshold of an [EvaporationCut].
+#[derive(Clone, Copy, Debug)]
+pub struct EvaporationRamp {
+    /// Threshold at the end of the ramp, in SI units of J.
+    pub final_threshold: f64,
+    /// Simulation time at which the ramp starts, in SI units of s.
+    pub start_time: f64,
+    /// Duration of the ramp, in SI units of s.
+    pub duration: f64,
+}
+
+/// A resource that removes atoms whose total energy exceeds a threshold.
+///
+/// The total energy of an atom is its kinetic energy, plus the Zeeman energy of atoms with a [MagneticDipole]
+/// and the dipole potential energy of atoms with a [Polarizability]. The dipole potential is summed over all
+/// [DipoleLight] [GaussianBeam]s.
+#[derive(Clone, Copy, Debug)]
+pub struct EvaporationCut {
+    /// Energy above which atoms are removed, in SI units of J. If `ramp` is set, this is the threshold before
+    /// the ramp starts.
+    pub energy_threshold: f64,
+    /// An optional ramp of the threshold, from `energy_threshold` to the final threshold.
+    pub ramp: Option<EvaporationRamp>,
+}
+impl EvaporationCut {
+    /// Creates an `EvaporationCut` with a constant threshold, in SI units of J.
+    pub fn new(energy_threshold: f64) -> Self {
+        EvaporationCut {
+            energy_threshold,
+            ramp: None,
+        }
+    }
+
+    /// The threshold at the given simulation time, in SI units of J.
+    pub fn threshold(&self, time: f64) -> f64 {
+        match self.ramp {
+            None => self.energy_threshold,
+            Some(ramp) => {
+                let amount = if ramp.duration > 0.0 {
+                    ((time - ramp.start_time) / ramp.duration).clamp(0.0, 1.0)
+                } else if time >= ramp.start_time {
+                    1.0
+                } else {
+                    0.0
+                };
+                self.energy_threshold + amount * (ramp.final_threshold - self.energy_threshold)
+            }
+        }
+    }
+}
+
+/// Deletes atoms whose total energy exceeds the threshold of the [EvaporationCut].
+///
+/// Does nothing if the [EvaporationCut] resource is not present.
+pub struct ApplyEvaporationCutSystem;
+impl<'a> System<'a> for ApplyEvaporationCutSystem {
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, Atom>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Velocity>,
+        ReadStorage<'a, Mass>,
+        ReadStorage<'a, MagneticDipole>,
+        ReadStorage<'a, MagneticFieldSampler>,
+        ReadStorage<'a, Polarizability>,
+        ReadStorage<'a, DipoleLight>,
+        ReadStorage<'a, GaussianBeam>,
+        ReadStorage<'a, CircularMask>,
+        ReadStorage<'a, Frame>,
+        Option<Read<'a, EvaporationCut>>,
+        ReadExpect<'a, Step>,
+        ReadExpect<'a, Timestep>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            atoms,
+            positions,
+            velocities,
+            masses,
+            magnetic_dipoles,
+            samplers,
+            polarizabilities,
+            dipoles,
+            beams,
+            masks,
+            frames,
+            cut,
+            step,
+            timestep,
+        ): Self::SystemData,
+    ) {
+        let cut = match cut {
+            Some(cut) => cut,
+            None => return,
+        };
+        let threshold = cut.threshold(SimulationTime::new(&step, &timestep).time);
+        for (entity, _, pos, vel, mass, magnetic_dipole, sampler, polarizability) in (
+            &entities,
+            &atoms,
+            &positions,
+            &velocities,
+            &masses,
+            magnetic_dipoles.maybe(),
+            samplers.maybe(),
+            polarizabilities.maybe(),
+        )
+            .join()
+        {
+            let mut energy = 0.5 * mass.value * AMU * vel.vel.cast::<f64>().norm_squared();
+            if let (Some(magnetic_dipole), Some(sampler)) = (magnetic_dipole, sampler) {
+                energy += magnetic_dipole.mFgF * BOHRMAG * sampler.magnitude;
+            }
+            if let Some(polarizability) = polarizability {
+                energy +=
+                    dipole_potential_energy(polarizability, pos, &dipoles, &beams, &masks, &frames);
+            }
+            if energy > threshold {
+                entities.delete(entity).expect("Could not delete entity");
+            }
+        }
+    }
+}
+
+/// This plugin removes atoms above the threshold of the [EvaporationCut] resource.
+///
+/// The cut is applied after the velocity integrator, so the end frame systems must be added to the
+/// [SimulationBuilder](crate::simulation::SimulationBuilder) before this plugin.
+///
+/// See also [crate::evaporation].
+pub struct EvaporationPlugin;
+impl Plugin for EvaporationPlugin {
+    fn build(&self, builder: &mut crate::simulation::SimulationBuilder) {
+        builder.dispatcher_builder.add(
+            ApplyEvaporationCutSystem,
+            "apply_evaporation_cut",
+            &[INTEGRATE_VELOCITY_SYSTEM_NAME],
+        );
+    }
+    fn deps(&self) -> Vec<Box<dyn Plugin>> {
+        vec![Box::new(MagneticsPlugin)]
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use crate::atom::Force;
+    use crate::atom_sources::thermal::maxwell_boltzmann_velocity;
+    use crate::constant::BOLTZCONST;
+    use crate::equilibrium::measure_temperature;
+    use crate::magnetic::quadrupole::QuadrupoleField3D;
+    use crate::simulation::{Simulation, SimulationBuilder};
+    use assert_approx_eq::assert_approx_eq;
+    use nalgebra::Vector3;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn build_simulation(cut: EvaporationCut) -> Simulation {
+        let mut sim_builder = SimulationBuilder::default();
+        sim_builder.add_end_frame_systems();
+        sim_builder.add_plugin(EvaporationPlugin);
+        let mut sim = sim_builder.build();
+        sim.world.insert(Timestep { delta: 1.0e-6 });
+        sim.world.insert(cut);
+        sim
+    }
+
+    fn add_atom(sim: &mut Simulation, pos: Vector3<f64>, vel: Vector3<f64>) -> Entity {
+        sim.world
+            .create_entity()
+            .with(Position { pos: pos.cast() })
+            .with(Velocity { vel: vel.cast() })
+            .with(Force::new())
+            .with(Mass { value: 87.0 })
+            .with(Atom)
+            .build()
+    }
+
+    fn cloud_temperature(sim: &Simulation) -> f64 {
+        let velocities = sim.world.read_storage::<Velocity>();
+        let atoms = sim.world.read_storage::<Atom>();
+        let samples: Vec<(Vector3<f64>, f64)> = (&velocities, &atoms)
+            .join()
+            .map(|(vel, _)| (vel.vel.cast::<f64>(), 87.0))
+            .collect();
+        measure_temperature(&samples)
+    }
+
+    #[test]
+    fn test_evaporation_cut_removes_hot_atoms() {
+        let temperature = 100.0e-6;
+        let threshold = 2.0 * BOLTZCONST * temperature;
+        let mut sim = build_simulation(EvaporationCut::new(threshold));
+        let mut rng = StdRng::seed_from_u64(3);
+        for _ in 0..2000 {
+            let vel = maxwell_boltzmann_velocity(&mut rng, temperature, 87.0);
+            add_atom(&mut sim, Vector3::new(0.0, 0.0, 0.0), vel);
+        }
+        let initial_temperature = cloud_temperature(&sim);
+        sim.step();
+
+        let remaining = sim.world.read_storage::<Atom>().join().count();
+        assert!(remaining > 0 && remaining < 2000);
+        {
+            let velocities = sim.world.read_storage::<Velocity>();
+            let atoms = sim.world.read_storage::<Atom>();
+            for (vel, _) in (&velocities, &atoms).join() {
+                let energy = 0.5 * 87.0 * AMU * vel.vel.cast::<f64>().norm_squared();
+                assert!(energy <= threshold);
+            }
+        }
+        let final_temperature = cloud_temperature(&sim);
+        assert!(
+            final_temperature < 0.8 * initial_temperature,
+            "temperature {} K after the cut, {} K before",
+            final_temperature,
+            initial_temperature
+        );
+    }
+
+    #[test]
+    fn test_evaporation_cut_includes_zeeman_energy() {
+        let mut sim = build_simulation(EvaporationCut::new(1.0e-28));
+        sim.world
+            .create_entity()
+            .with(QuadrupoleField3D::gauss_per_cm(100.0, Vector3::z()))
+            .with(Position::new())
+            .build();
+        let atoms: Vec<Entity> = [1.0e-6, 1.0e-3]
+            .iter()
+            .map(|x| {
+                let atom = add_atom(&mut sim, Vector3::new(*x, 0.0, 0.0), Vector3::zeros());
+                sim.world
+                    .write_storage::<MagneticDipole>()
+                    .insert(atom, MagneticDipole { mFgF: 0.5 })
+                    .expect("Could not add magnetic dipole");
+                sim.world
+                    .write_storage::<MagneticFieldSampler>()
+                    .insert(atom, MagneticFieldSampler::default())
+                    .expect("Could not add field sampler");
+                atom
+            })
+            .collect();
+        sim.step();
+
+        // The Zeeman energy is 0.5 * mu_B * 1 T/m * x, about 5e-30 J and 5e-27 J.
+        assert!(sim.world.is_alive(atoms[0]));
+        assert!(!sim.world.is_alive(atoms[1]));
+    }
+
+    #[test]
+    fn test_evaporation_ramp() {
+        let cut = EvaporationCut {
+            energy_threshold: 10.0,
+            ramp: Some(EvaporationRamp {
+                final_threshold: 2.0,
+                start_time: 1.0,
+                duration: 4.0,
+            }),
+        };
+        assert_approx_eq!(cut.threshold(0.0), 10.0);
+        assert_approx_eq!(cut.threshold(3.0), 6.0);
+        assert_approx_eq!(cut.threshold(10.0), 2.0);
+        assert_approx_eq!(EvaporationCut::new(4.0).threshold(100.0), 4.0);
+    }
+}
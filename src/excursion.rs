@@ -0,0 +1,179 @@
+//! Tracking of the maximum displacement of each atom from its starting position.
+//!
+//! The [MaxExcursion] of each atom records the farthest distance it has moved from its start. Summary statistics
+//! over all atoms, see [summarize_max_excursion], give the evolution of the cloud size cheaply, for example
+//! to measure the expansion of a released cloud or the size of a trap.
+
+use crate::atom::{Atom, Position};
+use crate::initiate::NewlyCreated;
+use crate::integrator::INTEGRATE_POSITION_SYSTEM_NAME;
+use crate::simulation::Plugin;
+use nalgebra::Vector3;
+use specs::prelude::*;
+
+/// A component that records the maximum distance of an atom from its starting position.
+#[derive(Clone, Copy, Debug)]
+pub struct MaxExcursion {
+    /// The maximum distance of the atom from `start`, in SI units of m.
+    pub value: f64,
+    /// The position of the atom when tracking started, in SI units of m.
+    pub start: Vector3<f64>,
+}
+impl Component for MaxExcursion {
+    type Storage = VecStorage<Self>;
+}
+impl MaxExcursion {
+    /// Starts tracking the excursion from the given position, in SI units of m.
+    pub fn new(start: Vector3<f64>) -> Self {
+        MaxExcursion { value: 0.0, start }
+    }
+
+    /// Updates the maximum excursion with the current position of the atom.
+    pub fn update(&mut self, pos: &Position) {
+        self.value = self.value.max((pos.pos.cast::<f64>() - self.start).norm());
+    }
+}
+
+/// Attaches a [MaxExcursion] to newly created atoms, starting from their current position.
+pub struct AttachMaxExcursionToNewlyCreatedAtomsSystem;
+impl<'a> System<'a> for AttachMaxExcursionToNewlyCreatedAtomsSystem {
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, NewlyCreated>,
+        ReadStorage<'a, Position>,
+        Read<'a, LazyUpdate>,
+    );
+
+    fn run(&mut self, (ent, newly_created, positions, updater): Self::SystemData) {
+        for (ent, _, pos) in (&ent, &newly_created, &positions).join() {
+            updater.insert(ent, MaxExcursion::new(pos.pos.cast()));
+        }
+    }
+}
+
+/// Updates the [MaxExcursion] of each atom with its current position.
+pub struct UpdateMaxExcursionSystem;
+impl<'a> System<'a> for UpdateMaxExcursionSystem {
+    type SystemData = (ReadStorage<'a, Position>, WriteStorage<'a, MaxExcursion>);
+
+    fn run(&mut self, (positions, mut excursions): Self::SystemData) {
+        for (pos, excursion) in (&positions, &mut excursions).join() {
+            excursion.update(pos);
+        }
+    }
+}
+
+/// Restarts the [MaxExcursion] of every atom from its current position, for example at a phase transition.
+pub fn reset_max_excursion(world: &World) {
+    let positions = world.read_storage::<Position>();
+    let mut excursions = world.write_storage::<MaxExcursion>();
+    for (pos, excursion) in (&positions, &mut excursions).join() {
+        *excursion = MaxExcursion::new(pos.pos.cast());
+    }
+}
+
+/// Summary statistics of the [MaxExcursion] of all atoms, see [summarize_max_excursion].
+#[derive(Clone, Copy, Debug)]
+pub struct ExcursionSummary {
+    /// Number of atoms.
+    pub count: usize,
+    /// Mean of the maximum excursions, in SI units of m.
+    pub mean: f64,
+    /// Largest maximum excursion of any atom, in SI units of m.
+    pub max: f64,
+}
+
+/// Calculates summary statistics of the [MaxExcursion] of all atoms, or `None` if no atoms are tracked.
+pub fn summarize_max_excursion(world: &World) -> Option<ExcursionSummary> {
+    let excursions = world.read_storage::<MaxExcursion>();
+    let atoms = world.read_storage::<Atom>();
+    let values: Vec<f64> = (&excursions, &atoms)
+        .join()
+        .map(|(excursion, _)| excursion.value)
+        .collect();
+    if values.is_empty() {
+        return None;
+    }
+    Some(ExcursionSummary {
+        count: values.len(),
+        mean: values.iter().sum::<f64>() / values.len() as f64,
+        max: values.iter().cloned().fold(0.0, f64::max),
+    })
+}
+
+/// This plugin tracks the [MaxExcursion] of each atom.
+///
+/// See also [crate::excursion].
+pub struct MaxExcursionPlugin;
+impl Plugin for MaxExcursionPlugin {
+    fn build(&self, builder: &mut crate::simulation::SimulationBuilder) {
+        builder.dispatcher_builder.add(
+            AttachMaxExcursionToNewlyCreatedAtomsSystem,
+            "attach_max_excursion",
+            &[],
+        );
+        builder.dispatcher_builder.add(
+            UpdateMaxExcursionSystem,
+            "update_max_excursion",
+            &[INTEGRATE_POSITION_SYSTEM_NAME],
+        );
+    }
+    fn deps(&self) -> Vec<Box<dyn Plugin>> {
+        Vec::new()
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_max_excursion_records_farthest_distance() {
+        let mut test_world = World::new();
+        test_world.register::<Position>();
+        test_world.register::<NewlyCreated>();
+        test_world.register::<Atom>();
+        test_world.register::<MaxExcursion>();
+
+        let start = Vector3::new(1.0, 1.0, 0.0);
+        let atom = test_world
+            .create_entity()
+            .with(Position { pos: start.cast() })
+            .with(Atom)
+            .with(NewlyCreated)
+            .build();
+        AttachMaxExcursionToNewlyCreatedAtomsSystem.run_now(&test_world);
+        test_world.maintain();
+
+        // The atom moves out to a distance of 5 and returns towards its start.
+        let path = [
+            Vector3::new(2.0, 1.0, 0.0),
+            Vector3::new(4.0, 5.0, 0.0),
+            Vector3::new(1.0, 3.0, 0.0),
+            Vector3::new(1.0, 1.0, 1.0),
+        ];
+        let mut system = UpdateMaxExcursionSystem;
+        for pos in path.iter() {
+            test_world
+                .write_storage::<Position>()
+                .get_mut(atom)
+                .unwrap()
+                .pos = pos.cast();
+            system.run_now(&test_world);
+        }
+
+        let excursion = *test_world.read_storage::<MaxExcursion>().get(atom).unwrap();
+        assert_eq!(excursion.start, start);
+        assert_approx_eq!(excursion.value, 5.0, 1e-6);
+        let summary = summarize_max_excursion(&test_world).unwrap();
+        assert_eq!(summary.count, 1);
+        assert_approx_eq!(summary.max, 5.0, 1e-6);
+
+        reset_max_excursion(&test_world);
+        let excursion = *test_world.read_storage::<MaxExcursion>().get(atom).unwrap();
+        assert_eq!(excursion.value, 0.0);
+        assert_eq!(excursion.start, path[3]);
+    }
+}
@@ -1,8 +1,9 @@
 //! Implements the force of gravity.
 
-use crate::atom::{Force, Mass};
+use crate::atom::{Force, GravitationalMass, Mass};
 use crate::constant;
 use crate::integrator::INTEGRATE_POSITION_SYSTEM_NAME;
+use crate::parallel::{ForceSerial, MaybeParJoin};
 use crate::simulation::Plugin;
 use nalgebra::Vector3;
 use specs::prelude::*;
@@ -10,25 +11,97 @@ use specs::prelude::*;
 /// A resource that indicates that the simulation should apply the force of gravity.
 pub struct ApplyGravityOption;
 
+/// A resource that sets the acceleration due to gravity, in SI units of m/s^2.
+///
+/// If the resource is not present, gravity acts along `-z` with magnitude [constant::GC].
+#[derive(Clone, Copy, Debug)]
+pub struct Gravity {
+    /// The acceleration due to gravity, in SI units of m/s^2.
+    pub acceleration: Vector3<f64>,
+}
+impl Default for Gravity {
+    fn default() -> Self {
+        Gravity {
+            acceleration: Vector3::new(0.0, 0.0, -constant::GC),
+        }
+    }
+}
+impl Gravity {
+    /// No gravity, for microgravity experiments such as drop towers or experiments in orbit.
+    ///
+    /// The analyses that depend on gravity, such as [crate::dipole::analysis::TrapSummary::gravitational_sag],
+    /// [crate::dipole::compensating_tilt] and [crate::magnetic::levitation::levitation_gradient_for], report no
+    /// effect of gravity rather than dividing by its magnitude.
+    pub fn zero() -> Self {
+        Gravity {
+            acceleration: Vector3::new(0.0, 0.0, 0.0),
+        }
+    }
+
+    /// True if the acceleration due to gravity is zero.
+    pub fn is_zero(&self) -> bool {
+        self.acceleration == Vector3::new(0.0, 0.0, 0.0)
+    }
+
+    /// Gravity tilted away from the `-z` axis, for example on an inclined optical table.
+    ///
+    /// # Arguments
+    ///
+    /// `magnitude`: magnitude of the acceleration, in SI units of m/s^2.
+    ///
+    /// `polar`: angle between the acceleration and the `-z` axis, in radians.
+    ///
+    /// `azimuth`: angle of the horizontal component of the acceleration from the `x` axis, towards the `y` axis,
+    /// in radians.
+    ///
+    /// Panics if the magnitude is negative, or if any argument is not finite.
+    pub fn tilted(magnitude: f64, polar: f64, azimuth: f64) -> Self {
+        assert!(
+            magnitude.is_finite() && magnitude >= 0.0,
+            "The magnitude of gravity must be finite and non-negative."
+        );
+        assert!(
+            polar.is_finite() && azimuth.is_finite(),
+            "The angles of gravity must be finite."
+        );
+        Gravity {
+            acceleration: magnitude
+                * Vector3::new(
+                    polar.sin() * azimuth.cos(),
+                    polar.sin() * azimuth.sin(),
+                    -polar.cos(),
+                ),
+        }
+    }
+}
+
 /// This system adds the gravitational force to all entities with [Mass](struct.Mass.html).
+///
+/// The direction and magnitude of the force are given by the [Gravity] resource. The force is proportional to the
+/// [GravitationalMass] of an entity, if it has one, and otherwise to its [Mass].
 pub struct ApplyGravitationalForceSystem;
 impl<'a> System<'a> for ApplyGravitationalForceSystem {
     type SystemData = (
         WriteStorage<'a, Force>,
         ReadStorage<'a, Mass>,
+        ReadStorage<'a, GravitationalMass>,
         Option<Read<'a, ApplyGravityOption>>,
+        Option<Read<'a, Gravity>>,
+        Option<Read<'a, ForceSerial>>,
     );
 
-    fn run(&mut self, (mut force, mass, gravity_option): Self::SystemData) {
-        use rayon::prelude::*;
-
+    fn run(
+        &mut self,
+        (mut force, mass, gravitational_mass, gravity_option, gravity, force_serial): Self::SystemData,
+    ) {
         match gravity_option {
             None => (),
             Some(_) => {
-                (&mut force, &mass)
-                    .par_join()
-                    .for_each(|(force, mass)| {
-                        force.force += mass.value * constant::AMU * constant::GC * Vector3::new(0., 0., -1.);
+                let acceleration = gravity.map(|gravity| *gravity).unwrap_or_default().acceleration;
+                (&mut force, &mass, gravitational_mass.maybe())
+                    .maybe_par_for_each(force_serial.is_some(), |(force, mass, gravitational_mass)| {
+                        let mass = gravitational_mass.map_or(mass.value, |mass| mass.value);
+                        force.force += (mass * constant::AMU * acceleration).cast();
                     });
             }
         }
@@ -41,6 +114,7 @@ impl<'a> System<'a> for ApplyGravitationalForceSystem {
 pub struct GravityPlugin;
 impl Plugin for GravityPlugin {
     fn build(&self, builder: &mut crate::simulation::SimulationBuilder) {
+        builder.world.register::<GravitationalMass>();
         builder.dispatcher_builder.add(
             ApplyGravitationalForceSystem,
             "add_gravity",
@@ -60,6 +134,10 @@ pub mod tests {
     extern crate specs;
     use assert_approx_eq::assert_approx_eq;
     use specs::{Builder, RunNow, World};
+    use crate::atom::{Atom, Position, Velocity};
+    use crate::initiate::NewlyCreated;
+    use crate::integrator::Timestep;
+    use crate::simulation::{Simulation, SimulationBuilder};
     extern crate nalgebra;
     use nalgebra::Vector3;
 
@@ -69,6 +147,7 @@ pub mod tests {
         let mut test_world = World::new();
 
         test_world.register::<Mass>();
+        test_world.register::<GravitationalMass>();
         test_world.register::<Force>();
         test_world.insert(ApplyGravityOption);
 
@@ -85,9 +164,192 @@ pub mod tests {
         let sampler_storage = test_world.read_storage::<Force>();
 
         assert_approx_eq!(
-            sampler_storage.get(atom1).expect("entity not found").force[2],
+            sampler_storage
+                .get(atom1)
+                .expect("entity not found")
+                .force
+                .cast::<f64>()[2],
             -1.0 * constant::AMU * constant::GC,
             1e-30_f64
         );
     }
+
+    #[test]
+    fn test_tilted_gravity_direction() {
+        let gravity = Gravity::tilted(constant::GC, 0.0, 1.3);
+        assert_approx_eq!(
+            (gravity.acceleration - Gravity::default().acceleration).norm(),
+            0.0,
+            1e-12
+        );
+
+        let polar = 0.2;
+        let azimuth = 3.0 * constant::PI / 4.0;
+        let gravity = Gravity::tilted(2.0, polar, azimuth);
+        assert_approx_eq!(gravity.acceleration.norm(), 2.0, 1e-12);
+        assert_approx_eq!(gravity.acceleration.dot(&-Vector3::z()), 2.0 * polar.cos(), 1e-12);
+        let horizontal = Vector3::new(gravity.acceleration[0], gravity.acceleration[1], 0.0);
+        assert_approx_eq!(horizontal[1].atan2(horizontal[0]), azimuth, 1e-12);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_tilted_gravity_rejects_negative_magnitude() {
+        Gravity::tilted(-constant::GC, 0.1, 0.0);
+    }
+
+    /// Tests that an atom under tilted gravity accelerates along the tilted direction.
+    #[test]
+    fn test_atom_accelerates_along_tilted_gravity() {
+        // The default simulation includes the GravityPlugin.
+        let mut sim = SimulationBuilder::default().build();
+        let dt = 1.0e-4;
+        sim.world.insert(Timestep { delta: dt });
+        sim.world.insert(ApplyGravityOption);
+        let gravity = Gravity::tilted(constant::GC, 0.3, -0.7);
+        sim.world.insert(gravity);
+
+        let atom = sim
+            .world
+            .create_entity()
+            .with(Position::new())
+            .with(Velocity {
+                vel: Vector3::new(0.0, 0.0, 0.0).cast(),
+            })
+            .with(Force::new())
+            .with(Mass { value: 87.0 })
+            .with(Atom)
+            .with(NewlyCreated)
+            .build();
+        let velocity = |sim: &Simulation| {
+            sim.world
+                .read_storage::<Velocity>()
+                .get(atom)
+                .expect("entity not found")
+                .vel
+                .cast::<f64>()
+        };
+        // Forces are first applied once the atom has been initialised.
+        for _ in 0..10 {
+            sim.step();
+        }
+        let initial = velocity(&sim);
+        let steps = 100;
+        for _ in 0..steps {
+            sim.step();
+        }
+
+        let expected = gravity.acceleration * steps as f64 * dt;
+        let velocity = velocity(&sim) - initial;
+        assert_approx_eq!((velocity - expected).norm(), 0.0, 1e-6 * expected.norm());
+    }
+
+    /// Tests that the analyses that depend on gravity report no effect of gravity, rather than NaN, without it.
+    #[test]
+    fn test_zero_gravity_has_no_effects() {
+        use crate::dipole::analysis::gaussian_trap_summary;
+        use crate::dipole::{compensating_tilt, tilt_beam, Polarizability};
+        use crate::laser::gaussian::GaussianBeam;
+        use crate::magnetic::levitation::levitation_gradient_for;
+
+        let gravity = Gravity::zero();
+        assert!(gravity.is_zero() && !Gravity::default().is_zero());
+
+        let polarizability = Polarizability::calculate_for(1064e-9, 780e-9, 6.065e6);
+        let mass = 87.0;
+        let summary = gaussian_trap_summary(
+            1.0,
+            1064e-9,
+            50e-6,
+            &polarizability,
+            mass * constant::AMU,
+        );
+        assert_eq!(summary.gravitational_sag(&gravity), 0.0);
+
+        let direction = Vector3::new(1.0, 0.0, 1.0).normalize();
+        let mut beam = GaussianBeam::from_power_with_ellipticity_and_rayleigh_range(
+            Vector3::new(0.0, 0.0, 0.0),
+            direction,
+            1.0,
+            50e-6,
+            1064e-9,
+            0.0,
+        );
+        let tilt = compensating_tilt(
+            &beam,
+            &polarizability,
+            mass * constant::AMU,
+            gravity.acceleration,
+        );
+        assert_eq!(tilt, 0.0);
+        tilt_beam(&mut beam, None, gravity.acceleration, tilt);
+        assert_approx_eq!((beam.direction - direction).norm(), 0.0, 1e-12);
+
+        assert_eq!(levitation_gradient_for(mass, 2.0, 0.5, &gravity), 0.0);
+        assert_eq!(levitation_gradient_for(mass, -1.0, 0.5, &gravity), 0.0);
+
+        // An atom at rest stays at rest with the force of gravity enabled.
+        let mut sim = SimulationBuilder::default().build();
+        sim.world.insert(Timestep { delta: 1.0e-4 });
+        sim.world.insert(ApplyGravityOption);
+        sim.world.insert(gravity);
+        let atom = sim
+            .world
+            .create_entity()
+            .with(Position::new())
+            .with(Velocity {
+                vel: Vector3::new(0.0, 0.0, 0.0).cast(),
+            })
+            .with(Force::new())
+            .with(Mass { value: mass })
+            .with(Atom)
+            .with(NewlyCreated)
+            .build();
+        for _ in 0..100 {
+            sim.step();
+        }
+        let velocities = sim.world.read_storage::<Velocity>();
+        assert_eq!(
+            velocities.get(atom).expect("entity not found").vel.norm(),
+            0.0
+        );
+    }
+
+    /// Tests that an atom with a gravitational mass falls with the acceleration scaled by the ratio of its masses.
+    #[test]
+    fn test_gravitational_mass_scales_acceleration() {
+        let mut sim = SimulationBuilder::default().build();
+        let dt = 1.0e-4;
+        sim.world.insert(Timestep { delta: dt });
+        sim.world.insert(ApplyGravityOption);
+
+        let eta = 1.0e-3;
+        let mut atoms = Vec::new();
+        for gravitational_mass in [None, Some(87.0 * (1.0 + eta))].iter() {
+            let mut builder = sim
+                .world
+                .create_entity()
+                .with(Position::new())
+                .with(Velocity {
+                    vel: Vector3::new(0.0, 0.0, 0.0).cast(),
+                })
+                .with(Force::new())
+                .with(Mass { value: 87.0 })
+                .with(Atom)
+                .with(NewlyCreated);
+            if let Some(value) = gravitational_mass {
+                builder = builder.with(GravitationalMass { value: *value });
+            }
+            atoms.push(builder.build());
+        }
+        for _ in 0..100 {
+            sim.step();
+        }
+
+        let velocities = sim.world.read_storage::<Velocity>();
+        let standard = velocities.get(atoms[0]).unwrap().vel.cast::<f64>()[2];
+        let scaled = velocities.get(atoms[1]).unwrap().vel.cast::<f64>()[2];
+        assert!(standard < 0.0);
+        assert_approx_eq!(scaled / standard, 1.0 + eta, 1e-9);
+    }
 }
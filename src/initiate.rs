@@ -9,9 +9,18 @@
 //! This module defines the [NewlyCreated](struct.NewlyCreated.html) component, and also the
 //! [DeflagNewAtomsSystem](struct.DeflagNewAtomsSystem.html) which is responsible for cleaning
 //! up these components each integration step.
+//!
+//! Newly created atoms are checked by the [ValidateNewAtomsSystem], so that a source or loader producing a
+//! non-finite [Position], [Velocity] or [Mass] cannot corrupt the rest of the simulation. What happens to invalid
+//! atoms is set by the [InvalidAtomPolicy] resource.
 
 use specs::prelude::*;
 
+use crate::atom::{Atom, Force, Mass, Position, Scalar, Velocity};
+
+/// Name of the system that validates newly created atoms, see [ValidateNewAtomsSystem].
+pub const VALIDATE_NEW_ATOMS_SYSTEM_NAME: &str = "validate_new_atoms";
+
 /// A marker component that indicates an entity has been `NewlyCreated`.
 /// The main use of this component is to allow different modules to identify when an atom has been created and to attach any appropriate components required.
 /// For instance, a NewlyCreated atom could have a field sampler added to it so that magnetic systems will be able to calculate fields at the atom's position.
@@ -46,6 +55,124 @@ impl<'a> System<'a> for DeflagNewAtomsSystem {
     }
 }
 
+/// A resource that determines how newly created atoms with a non-finite [Position], [Velocity] or [Mass] are handled.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum InvalidAtomPolicy {
+    /// The atom is removed from the simulation. A warning gives the number of atoms rejected in each step.
+    Reject,
+    /// `NaN` components of the position and velocity are replaced with zero, and infinite components are clamped to
+    /// the largest finite value. A warning gives the number of atoms clamped in each step. A mass cannot be clamped
+    /// meaningfully, so atoms with a non-finite mass are always rejected.
+    Clamp,
+}
+impl Default for InvalidAtomPolicy {
+    fn default() -> Self {
+        InvalidAtomPolicy::Reject
+    }
+}
+
+fn is_finite(vector: &nalgebra::Vector3<Scalar>) -> bool {
+    vector.iter().all(|x| x.is_finite())
+}
+
+fn clamp(vector: &mut nalgebra::Vector3<Scalar>) {
+    for x in vector.iter_mut() {
+        *x = if x.is_nan() {
+            0.0
+        } else {
+            x.clamp(Scalar::MIN, Scalar::MAX)
+        };
+    }
+}
+
+/// This system checks that the [Position], [Velocity] and [Mass] of `NewlyCreated` atoms are finite, and handles
+/// invalid atoms according to the [InvalidAtomPolicy].
+///
+/// Rejected atoms are deleted, and their position, velocity, mass and force are removed immediately, so that no
+/// other system sees them before the world is maintained at the end of the step.
+///
+/// ## When should this system run?
+///
+/// This system runs before the positions are integrated, see [VALIDATE_NEW_ATOMS_SYSTEM_NAME].
+pub struct ValidateNewAtomsSystem;
+
+impl<'a> System<'a> for ValidateNewAtomsSystem {
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, NewlyCreated>,
+        ReadStorage<'a, Atom>,
+        WriteStorage<'a, Position>,
+        WriteStorage<'a, Velocity>,
+        WriteStorage<'a, Mass>,
+        WriteStorage<'a, Force>,
+        Read<'a, InvalidAtomPolicy>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            newly_created,
+            atoms,
+            mut positions,
+            mut velocities,
+            mut masses,
+            mut forces,
+            policy,
+        ): Self::SystemData,
+    ) {
+        let mut rejected = Vec::new();
+        let mut clamped = 0;
+        for (ent, _, _, pos, vel, mass) in (
+            &entities,
+            &newly_created,
+            &atoms,
+            (&mut positions).maybe(),
+            (&mut velocities).maybe(),
+            masses.maybe(),
+        )
+            .join()
+        {
+            let pos_valid = pos.as_ref().map_or(true, |pos| is_finite(&pos.pos));
+            let vel_valid = vel.as_ref().map_or(true, |vel| is_finite(&vel.vel));
+            let mass_valid = mass.map_or(true, |mass| mass.value.is_finite());
+            if pos_valid && vel_valid && mass_valid {
+                continue;
+            }
+            if *policy == InvalidAtomPolicy::Clamp && mass_valid {
+                if let Some(pos) = pos {
+                    clamp(&mut pos.pos);
+                }
+                if let Some(vel) = vel {
+                    clamp(&mut vel.vel);
+                }
+                clamped += 1;
+                continue;
+            }
+            rejected.push(ent);
+        }
+        if clamped > 0 {
+            eprintln!(
+                "Warning: clamped the non-finite position or velocity of {} newly created atoms.",
+                clamped
+            );
+        }
+        if !rejected.is_empty() {
+            eprintln!(
+                "Warning: rejected {} newly created atoms with a non-finite position, velocity or mass.",
+                rejected.len()
+            );
+        }
+        for ent in rejected {
+            positions.remove(ent);
+            velocities.remove(ent);
+            masses.remove(ent);
+            forces.remove(ent);
+            entities.delete(ent).expect("Could not delete invalid atom.");
+        }
+    }
+}
+
 pub mod tests {
     #[allow(unused_imports)]
     use super::*;
@@ -71,4 +198,74 @@ pub mod tests {
         let created_flags = test_world.read_storage::<NewlyCreated>();
         assert!(!created_flags.contains(test_entity));
     }
+
+    #[test]
+    fn test_atom_with_nan_position_is_rejected() {
+        use crate::atom::{Atom, Mass, Position, Velocity};
+        use nalgebra::Vector3;
+
+        let mut test_world = World::new();
+        let mut dispatcher = DispatcherBuilder::new()
+            .with(ValidateNewAtomsSystem, "validate", &[])
+            .build();
+        dispatcher.setup(&mut test_world);
+
+        let create = |world: &mut World, pos: Vector3<f64>| {
+            world
+                .create_entity()
+                .with(Position { pos: pos.cast() })
+                .with(Velocity {
+                    vel: Vector3::new(1.0, 0.0, 0.0).cast(),
+                })
+                .with(Mass { value: 87.0 })
+                .with(Atom)
+                .with(NewlyCreated)
+                .build()
+        };
+        let valid = create(&mut test_world, Vector3::new(0.0, 0.0, 0.0));
+        let invalid = create(&mut test_world, Vector3::new(f64::NAN, 0.0, 0.0));
+
+        dispatcher.dispatch(&test_world);
+        test_world.maintain();
+
+        assert!(test_world.is_alive(valid));
+        assert!(!test_world.is_alive(invalid));
+        assert_eq!(test_world.read_storage::<Atom>().join().count(), 1);
+        assert_eq!(test_world.read_storage::<Position>().join().count(), 1);
+    }
+
+    #[test]
+    fn test_clamp_policy_clamps_infinite_velocity() {
+        use crate::atom::{Atom, Mass, Position, Velocity};
+        use nalgebra::Vector3;
+
+        let mut test_world = World::new();
+        let mut dispatcher = DispatcherBuilder::new()
+            .with(ValidateNewAtomsSystem, "validate", &[])
+            .build();
+        dispatcher.setup(&mut test_world);
+        test_world.insert(InvalidAtomPolicy::Clamp);
+
+        let atom = test_world
+            .create_entity()
+            .with(Position {
+                pos: Vector3::new(f64::NAN, 1.0, 0.0).cast(),
+            })
+            .with(Velocity {
+                vel: Vector3::new(f64::INFINITY, 0.0, 0.0).cast(),
+            })
+            .with(Mass { value: 87.0 })
+            .with(Atom)
+            .with(NewlyCreated)
+            .build();
+
+        dispatcher.dispatch(&test_world);
+        test_world.maintain();
+
+        assert!(test_world.is_alive(atom));
+        let pos = test_world.read_storage::<Position>().get(atom).unwrap().pos;
+        let vel = test_world.read_storage::<Velocity>().get(atom).unwrap().vel;
+        assert_eq!(pos, Vector3::new(0.0, 1.0, 0.0).cast());
+        assert_eq!(vel[0], Scalar::MAX);
+    }
 }
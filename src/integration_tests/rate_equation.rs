@@ -38,8 +38,55 @@ pub mod tests {
         test_single_beam_scattering_rate(5.0, 0.0);
     }
 
+    /// The rate coefficients keep the natural linewidth, and the two-level population broadens the line, so the
+    /// half width of the force against detuning is `gamma/2 sqrt(1 + s)`.
+    #[test]
+    fn single_beam_force_is_power_broadened() {
+        // The force relative to resonance, averaged over red and blue detuning to cancel the small Zeeman shift.
+        let relative_force = |i_over_i_sat: f64, delta_over_gamma: f64| {
+            let (_, on_resonance) = single_beam_scattering(i_over_i_sat, 0.0);
+            let (_, red) = single_beam_scattering(i_over_i_sat, -delta_over_gamma);
+            let (_, blue) = single_beam_scattering(i_over_i_sat, delta_over_gamma);
+            (red + blue) / (2.0 * on_resonance)
+        };
+        for i_over_i_sat in [0.1, 3.0, 15.0].iter() {
+            let half_width = 0.5 * (1.0 + i_over_i_sat).sqrt();
+            assert_approx_eq!(relative_force(*i_over_i_sat, half_width), 0.5, 0.01);
+            // At the natural half width, a saturated line has not yet fallen to half of its peak.
+            let expected = (1.0 + i_over_i_sat) / (2.0 + i_over_i_sat);
+            assert_approx_eq!(relative_force(*i_over_i_sat, 0.5), expected, 0.01);
+        }
+    }
+
     /// Calculates the scattering rate from a single beam at given intensity and detuning, and compares that to analytic theory.
     fn test_single_beam_scattering_rate(i_over_i_sat: f64, delta_over_gamma: f64) {
+        let i_sat = Rubidium87_780D2::saturation_intensity();
+        let intensity = i_sat * i_over_i_sat;
+        let delta = delta_over_gamma * Rubidium87_780D2::gamma();
+        let (total_scattered, measured_force) = single_beam_scattering(i_over_i_sat, delta_over_gamma);
+
+        let expected_scattered =
+            analytic_scattering_rate(intensity, i_sat, delta, Rubidium87_780D2::gamma());
+        assert_approx_eq!(
+            total_scattered,
+            expected_scattered,
+            expected_scattered.abs() * 0.05
+        );
+
+        // Compare the magnitude of the calculated force.
+        let k = 2.0 * std::f64::consts::PI * Rubidium87_780D2::frequency() / crate::constant::C;
+        let photon_momentum = crate::constant::HBAR * k;
+        let analytic_force = expected_scattered * photon_momentum;
+        assert_approx_eq!(
+            measured_force,
+            analytic_force,
+            analytic_force.abs() * 0.04
+        );
+    }
+
+    /// Simulates an atom at rest in a single beam of given intensity and detuning, and returns the rate at which it
+    /// scatters photons, in Hz, and the magnitude of the force on it, in SI units of N.
+    fn single_beam_scattering(i_over_i_sat: f64, delta_over_gamma: f64) -> (f64, f64) {
         const BEAM_NUMBER: usize = 1;
         let transition = Rubidium87_780D2;
         let i_sat = Rubidium87_780D2::saturation_intensity();
@@ -126,8 +173,6 @@ pub mod tests {
         // Second step to calculate values over completed atoms.
         sim.step();
 
-        let expected_scattered =
-            analytic_scattering_rate(intensity, i_sat, delta, Rubidium87_780D2::gamma());
         let total_scattered = sim
             .world
             .read_storage::<TotalPhotonsScattered<Rubidium87_780D2>>()
@@ -135,27 +180,14 @@ pub mod tests {
             .expect("Could not find atom in storage.")
             .total
             / dt;
-        assert_approx_eq!(
-            total_scattered,
-            expected_scattered,
-            expected_scattered.abs() * 0.05
-        );
-
-        // Compare the magnitude of the calculated force.
-        let k = 2.0 * std::f64::consts::PI * Rubidium87_780D2::frequency() / crate::constant::C;
-        let photon_momentum = crate::constant::HBAR * k;
-        let analytic_force = (expected_scattered * dt) * photon_momentum / dt;
-        let measured_force = sim
+        let force = sim
             .world
             .read_storage::<Force>()
             .get(atom)
             .expect("Atom does not have force component.")
-            .force;
-        assert_approx_eq!(
-            measured_force.norm(),
-            analytic_force,
-            analytic_force.abs() * 0.04
-        );
+            .force
+            .cast::<f64>();
+        (total_scattered, force.norm())
     }
 
     /// Analytic scattering rate for a two-level system. Returns photon-scattering rate in units of Hz.
@@ -8,6 +8,11 @@ extern crate nalgebra;
 use crate::atom::*;
 use crate::constant;
 use crate::initiate::NewlyCreated;
+use crate::output::file::BinaryConversion;
+use crate::parallel::{ForceSerial, MaybeParJoin};
+use crate::simulation::Plugin;
+use nalgebra::Vector3;
+use serde::Serialize;
 use specs::prelude::*;
 
 /// Tracks the number of the current integration step.
@@ -27,6 +32,56 @@ pub struct Timestep {
     pub delta: f64,
 }
 
+/// The elapsed time of the simulation.
+#[derive(Clone, Copy, Debug)]
+pub struct SimulationTime {
+    /// Number of integration steps that have been performed.
+    pub step: u64,
+    /// Elapsed simulation time, in SI units of seconds.
+    pub time: f64,
+}
+impl SimulationTime {
+    /// Calculates the elapsed simulation time from the current [Step] and [Timestep].
+    pub fn new(step: &Step, timestep: &Timestep) -> Self {
+        SimulationTime {
+            step: step.n,
+            time: step.n as f64 * timestep.delta,
+        }
+    }
+}
+
+/// A marker component that fixes an entity in place.
+///
+/// The integration systems do not update the [Position](struct.Position.html) or [Velocity](struct.Velocity.html)
+/// of pinned entities. Forces are still accumulated as normal, so a pinned atom can be used to probe the force at a point.
+#[derive(Component, Default)]
+#[storage(NullStorage)]
+pub struct Pinned;
+
+/// A component that constrains the motion of an entity to a plane or a line, for reduced-dimension simulations.
+///
+/// The integration systems zero the components of the [Velocity](struct.Velocity.html) and of the force along the
+/// frozen axes, so the [Position](struct.Position.html) does not change along them. As for [Pinned], the accumulated
+/// [Force](struct.Force.html) itself is not modified. Freezing all three axes holds the entity in place, with zero
+/// velocity.
+#[derive(Component, Clone, Copy, Debug)]
+#[storage(VecStorage)]
+pub struct FrozenAxes {
+    /// True for each of the `x`, `y` and `z` axes along which the entity cannot move.
+    pub mask: [bool; 3],
+}
+impl FrozenAxes {
+    /// Zeros the components of `vector` along the frozen axes.
+    pub fn constrain(&self, vector: Vector3<f64>) -> Vector3<f64> {
+        Vector3::from_fn(|i, _| if self.mask[i] { 0.0 } else { vector[i] })
+    }
+}
+
+/// Zeros the components of `vector` along the [FrozenAxes] of an entity, if it has any.
+fn constrain(frozen: Option<&FrozenAxes>, vector: Vector3<f64>) -> Vector3<f64> {
+    frozen.map_or(vector, |frozen| frozen.constrain(vector))
+}
+
 /// # Euler Integration
 ///
 /// The EulerIntegrationSystem integrates the classical equations of motion for particles using the euler method:
@@ -44,15 +99,24 @@ impl<'a> System<'a> for EulerIntegrationSystem {
         WriteExpect<'a, Step>,
         ReadStorage<'a, Force>,
         ReadStorage<'a, Mass>,
+        ReadStorage<'a, Pinned>,
+        ReadStorage<'a, FrozenAxes>,
+        Option<Read<'a, ForceSerial>>,
     );
 
-    fn run(&mut self, (mut pos, mut vel, t, mut step, force, mass): Self::SystemData) {
-        use rayon::prelude::*;
-
+    fn run(
+        &mut self,
+        (mut pos, mut vel, t, mut step, force, mass, pinned, frozen, force_serial): Self::SystemData,
+    ) {
         step.n += 1;
-        (&mut vel, &mut pos, &force, &mass).par_join().for_each(
-            |(vel, pos, force, mass)| {
-                euler_update(vel, pos, force, mass, t.delta);
+        (&mut vel, &mut pos, &force, &mass, !&pinned, frozen.maybe()).maybe_par_for_each(
+            force_serial.is_some(),
+            |(vel, pos, force, mass, _, frozen)| {
+                vel.vel = constrain(frozen, vel.vel.cast()).cast();
+                let force = Force {
+                    force: constrain(frozen, force.force.cast()).cast(),
+                };
+                euler_update(vel, pos, &force, mass, t.delta);
             },
         );
     }
@@ -76,22 +140,40 @@ impl<'a> System<'a> for VelocityVerletIntegratePositionSystem {
         ReadStorage<'a, Force>,
         WriteStorage<'a, OldForce>,
         ReadStorage<'a, Mass>,
+        ReadStorage<'a, Pinned>,
+        ReadStorage<'a, FrozenAxes>,
+        Option<Read<'a, ForceSerial>>,
     );
 
-    fn run(&mut self, (mut pos, vel, t, mut step, force, mut old_force, mass): Self::SystemData) {
-        use rayon::prelude::*;
-
+    fn run(
+        &mut self,
+        (
+            mut pos,
+            vel,
+            t,
+            mut step,
+            force,
+            mut old_force,
+            mass,
+            pinned,
+            frozen,
+            force_serial,
+        ): Self::SystemData,
+    ) {
         step.n += 1;
         let dt = t.delta;
 
-        (&mut pos, &vel, &mut old_force, &force, &mass)
-            .par_join()
-            .for_each(|(mut pos, vel, mut old_force, force, mass)| {
-                pos.pos = pos.pos
-                    + vel.vel * dt
-                    + force.force / (constant::AMU * mass.value) / 2.0 * dt * dt;
-                old_force.0 = *force;
-            });
+        (&mut pos, &vel, &mut old_force, &force, &mass, !&pinned, frozen.maybe())
+            .maybe_par_for_each(
+                force_serial.is_some(),
+                |(mut pos, vel, mut old_force, force, mass, _, frozen)| {
+                    let force = constrain(frozen, force.force.cast());
+                    pos.pos += (constrain(frozen, vel.vel.cast()) * dt
+                        + force / (constant::AMU * mass.value) / 2.0 * dt * dt)
+                        .cast();
+                    old_force.0 = Force { force: force.cast() };
+                },
+            );
     }
 }
 
@@ -110,21 +192,107 @@ impl<'a> System<'a> for VelocityVerletIntegrateVelocitySystem {
         ReadStorage<'a, Force>,
         ReadStorage<'a, OldForce>,
         ReadStorage<'a, Mass>,
+        ReadStorage<'a, Pinned>,
+        ReadStorage<'a, FrozenAxes>,
+        Option<Read<'a, ForceSerial>>,
     );
 
-    fn run(&mut self, (mut vel, t, force, old_force, mass): Self::SystemData) {
-        use rayon::prelude::*;
-
+    fn run(
+        &mut self,
+        (mut vel, t, force, old_force, mass, pinned, frozen, force_serial): Self::SystemData,
+    ) {
         let dt = t.delta;
 
-        (&mut vel, &force, &old_force, &mass).par_join().for_each(
-            |(vel, force, old_force, mass)| {
-                vel.vel += (force.force + old_force.0.force) / (constant::AMU * mass.value) / 2.0 * dt;
+        (&mut vel, &force, &old_force, &mass, !&pinned, frozen.maybe()).maybe_par_for_each(
+            force_serial.is_some(),
+            |(vel, force, old_force, mass, _, frozen)| {
+                vel.vel += ((force.force + old_force.0.force).cast::<f64>() / (constant::AMU * mass.value) / 2.0 * dt).cast();
+                vel.vel = constrain(frozen, vel.vel.cast()).cast();
             },
         );
     }
 }
 
+pub const CLAMP_STABILITY_SYSTEM_NAME: &str = "clamp_stability";
+
+/// A resource that limits the speed and distance from the origin of the atoms, to contain runaway integration.
+///
+/// Clamping is intended for numerical stability experiments: it keeps a blowup from crashing the simulation, and
+/// reports when and how often it occurs, which helps to decide whether the blowup is physical or numerical. Clamping
+/// is off unless the resource is inserted into the world.
+pub struct StabilityClamp {
+    /// Largest speed of an atom, in SI units of m/s.
+    pub max_speed: f64,
+    /// Largest distance of an atom from the origin, in SI units of m.
+    pub max_position: f64,
+    /// Number of times an atom's velocity or position has been clamped.
+    clamped: u64,
+}
+impl StabilityClamp {
+    /// Creates a new `StabilityClamp`.
+    ///
+    /// # Arguments
+    ///
+    /// `max_speed`: largest speed of an atom, in SI units of m/s.
+    ///
+    /// `max_position`: largest distance of an atom from the origin, in SI units of m.
+    pub fn new(max_speed: f64, max_position: f64) -> Self {
+        StabilityClamp {
+            max_speed,
+            max_position,
+            clamped: 0,
+        }
+    }
+
+    /// The number of times an atom's velocity or position has been clamped.
+    pub fn clamped(&self) -> u64 {
+        self.clamped
+    }
+}
+
+/// Clamps the speed and distance from the origin of the atoms to the limits of the [StabilityClamp] resource.
+///
+/// The direction of the velocity or position is kept, and a warning is printed each step in which clamping occurs.
+/// Does nothing if the [StabilityClamp] resource is not present.
+pub struct ClampStabilitySystem;
+impl<'a> System<'a> for ClampStabilitySystem {
+    type SystemData = (
+        WriteStorage<'a, Position>,
+        WriteStorage<'a, Velocity>,
+        ReadStorage<'a, Atom>,
+        ReadExpect<'a, Step>,
+        Option<Write<'a, StabilityClamp>>,
+    );
+
+    fn run(&mut self, (mut pos, mut vel, atoms, step, clamp): Self::SystemData) {
+        let mut clamp = match clamp {
+            Some(clamp) => clamp,
+            None => return,
+        };
+        let (max_speed, max_position) = (clamp.max_speed, clamp.max_position);
+        let mut clamped = 0;
+        for (pos, vel, _) in (&mut pos, &mut vel, &atoms).join() {
+            let speed = vel.vel.cast::<f64>().norm();
+            if speed > max_speed {
+                vel.vel = (vel.vel.cast::<f64>() * (max_speed / speed)).cast();
+                clamped += 1;
+            }
+            let distance = pos.pos.cast::<f64>().norm();
+            if distance > max_position {
+                pos.pos = (pos.pos.cast::<f64>() * (max_position / distance)).cast();
+                clamped += 1;
+            }
+        }
+        if clamped > 0 {
+            eprintln!(
+                "Warning: clamped the velocity or position of atoms {} times in step {}.",
+                clamped, step.n
+            );
+            clamp.clamped += clamped;
+        }
+    }
+}
+
 /// Adds [OldForce](OldForce.struct.html) components to newly created atoms.
 pub struct AddOldForceToNewAtomsSystem;
 impl<'a> System<'a> for AddOldForceToNewAtomsSystem {
@@ -148,10 +316,111 @@ impl Component for OldForce {
     type Storage = VecStorage<OldForce>;
 }
 
+pub const SYNCHRONIZE_VELOCITY_SYSTEM_NAME: &str = "synchronize_velocity";
+
+/// The velocity of an atom at the time of its [Position](struct.Position.html), for consistent snapshots.
+///
+/// The velocity-Verlet integrator updates the position at the start of each step and the velocity at the end, once
+/// the forces at the new position are known. Systems that run between the two, such as most outputs, see the
+/// position at the new step but the velocity at the previous step, which biases diagnostics that combine them, such
+/// as the total energy. The synchronized velocity is the half-step velocity `v + a dt / 2`, with which the position
+/// was advanced, advanced by a further half step with the same acceleration, so that it is accurate to second order
+/// in the timestep.
+///
+/// The [EulerIntegrationSystem] updates the position and velocity together, and needs no correction.
+///
+/// SI units (metres/second)
+#[derive(Clone, Copy, Serialize)]
+pub struct SynchronizedVelocity {
+    /// velocity vector in 3D in units of m/s
+    pub vel: Vector3<f64>,
+}
+impl Component for SynchronizedVelocity {
+    type Storage = VecStorage<Self>;
+}
+impl std::fmt::Display for SynchronizedVelocity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "({:?},{:?},{:?})", self.vel[0], self.vel[1], self.vel[2])
+    }
+}
+impl BinaryConversion for SynchronizedVelocity {
+    fn data(&self) -> Vec<f64> {
+        self.vel.iter().copied().collect()
+    }
+    fn field_names() -> Vec<&'static str> {
+        vec!["vx", "vy", "vz"]
+    }
+}
+
+/// Calculates the [SynchronizedVelocity] of each atom after the position integration of the velocity-Verlet
+/// integrator.
+///
+/// Pinned atoms do not move, so their synchronized velocity is their [Velocity](struct.Velocity.html).
+pub struct SynchronizeVelocitySystem;
+impl<'a> System<'a> for SynchronizeVelocitySystem {
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, Velocity>,
+        ReadStorage<'a, OldForce>,
+        ReadStorage<'a, Mass>,
+        ReadStorage<'a, Pinned>,
+        ReadStorage<'a, FrozenAxes>,
+        WriteStorage<'a, SynchronizedVelocity>,
+        ReadExpect<'a, Timestep>,
+    );
+
+    fn run(
+        &mut self,
+        (entities, velocities, old_forces, masses, pinned, frozen, mut synchronized, t): Self::SystemData,
+    ) {
+        for (entity, vel, old_force, mass, pinned, frozen) in (
+            &entities,
+            &velocities,
+            &old_forces,
+            &masses,
+            pinned.maybe(),
+            frozen.maybe(),
+        )
+            .join()
+        {
+            let vel = if pinned.is_some() {
+                vel.vel.cast::<f64>()
+            } else {
+                // The old force is the force with which the position was advanced this step.
+                let acceleration = old_force.0.force.cast::<f64>() / (constant::AMU * mass.value);
+                constrain(frozen, vel.vel.cast::<f64>() + acceleration * t.delta)
+            };
+            synchronized
+                .insert(entity, SynchronizedVelocity { vel })
+                .expect("Could not insert synchronized velocity.");
+        }
+    }
+}
+
+/// This plugin calculates the [SynchronizedVelocity] of each atom, for outputs and diagnostics that require the
+/// position and velocity at the same time.
+///
+/// Systems that read the [SynchronizedVelocity] should be added after this plugin, or depend on
+/// [SYNCHRONIZE_VELOCITY_SYSTEM_NAME].
+pub struct SynchronizedVelocityPlugin;
+impl Plugin for SynchronizedVelocityPlugin {
+    fn build(&self, builder: &mut crate::simulation::SimulationBuilder) {
+        builder.world.register::<SynchronizedVelocity>();
+        builder.dispatcher_builder.add(
+            SynchronizeVelocitySystem,
+            SYNCHRONIZE_VELOCITY_SYSTEM_NAME,
+            &[INTEGRATE_POSITION_SYSTEM_NAME],
+        );
+    }
+    fn deps(&self) -> Vec<Box<dyn Plugin>> {
+        Vec::new()
+    }
+}
+
 /// Performs the euler method to update [Velocity](struct.Velocity.html) and [Position](struct.Position.html) given an applied [Force](struct.Force.html).
 fn euler_update(vel: &mut Velocity, pos: &mut Position, force: &Force, mass: &Mass, dt: f64) {
-    pos.pos += vel.vel * dt;
-    vel.vel += force.force * dt / (constant::AMU * mass.value);
+    pos.pos += (vel.vel.cast::<f64>() * dt).cast();
+    vel.vel += (force.force.cast::<f64>() * dt / (constant::AMU * mass.value)).cast();
 }
 
 pub mod tests {
@@ -206,7 +475,9 @@ pub mod tests {
             .with(Velocity {
                 vel: Vector3::new(0.0, 0.0, 0.0),
             })
-            .with(Force { force })
+            .with(Force {
+                force: force.cast(),
+            })
             .with(Mass {
                 value: mass / constant::AMU,
             })
@@ -233,6 +504,7 @@ pub mod tests {
                 .get(atom)
                 .expect("atom not found.")
                 .vel
+                .cast::<f64>()
                 .norm(),
             expected_v.norm() * 0.01
         );
@@ -245,6 +517,7 @@ pub mod tests {
                 .get(atom)
                 .expect("atom not found.")
                 .pos
+                .cast::<f64>()
                 .norm(),
             expected_x.norm() * 0.01
         );
@@ -301,9 +574,9 @@ pub mod tests {
             .with(Velocity {
                 vel: Vector3::new(0.0, 0.0, 0.0),
             })
-            .with(Force { force })
+            .with(Force { force: force.cast() })
             .with(OldForce {
-                0: Force { force },
+                0: Force { force: force.cast() },
             })
             .with(Mass {
                 value: mass / constant::AMU,
@@ -331,6 +604,7 @@ pub mod tests {
                 .get(atom)
                 .expect("atom not found.")
                 .vel
+                .cast::<f64>()
                 .norm(),
             expected_v.norm() * 0.01
         );
@@ -343,8 +617,310 @@ pub mod tests {
                 .get(atom)
                 .expect("atom not found.")
                 .pos
+                .cast::<f64>()
                 .norm(),
             expected_x.norm() * 0.01
         );
     }
+
+    #[test]
+    fn test_pinned_atom_does_not_move() {
+        let mut world = World::new();
+
+        let mut dispatcher = DispatcherBuilder::new()
+            .with(
+                VelocityVerletIntegratePositionSystem,
+                "integrate_position",
+                &[],
+            )
+            .with(
+                VelocityVerletIntegrateVelocitySystem,
+                "integrate_velocity",
+                &["integrate_position"],
+            )
+            .with(EulerIntegrationSystem, "euler", &["integrate_velocity"])
+            .build();
+        dispatcher.setup(&mut world);
+
+        let force = Vector3::new(1.0, 0.0, 0.0);
+        let initial_position = Vector3::new(1.0, 2.0, 3.0);
+        let initial_velocity = Vector3::new(0.0, 1.0, 0.0);
+        let atom = world
+            .create_entity()
+            .with(Position {
+                pos: initial_position,
+            })
+            .with(Velocity {
+                vel: initial_velocity,
+            })
+            .with(Force {
+                force: force.cast(),
+            })
+            .with(OldForce::default())
+            .with(Mass {
+                value: 1.0 / constant::AMU,
+            })
+            .with(Pinned)
+            .build();
+
+        world.insert(Timestep { delta: 1.0e-3 });
+        world.insert(Step { n: 0 });
+
+        for _i in 0..100 {
+            dispatcher.dispatch(&world);
+            world.maintain();
+        }
+
+        assert_eq!(
+            world.read_storage::<Position>().get(atom).unwrap().pos,
+            initial_position
+        );
+        assert_eq!(
+            world.read_storage::<Velocity>().get(atom).unwrap().vel,
+            initial_velocity
+        );
+        assert_eq!(world.read_storage::<Force>().get(atom).unwrap().force, force);
+    }
+
+    #[test]
+    fn test_frozen_axis_confines_motion_to_plane() {
+        let mut world = World::new();
+
+        let mut dispatcher = DispatcherBuilder::new()
+            .with(
+                VelocityVerletIntegratePositionSystem,
+                "integrate_position",
+                &[],
+            )
+            .with(
+                VelocityVerletIntegrateVelocitySystem,
+                "integrate_velocity",
+                &["integrate_position"],
+            )
+            .build();
+        dispatcher.setup(&mut world);
+
+        let force = Vector3::new(1.0, -2.0, 3.0);
+        let initial_position = Vector3::new(1.0, 2.0, 3.0);
+        let create_atom = |world: &mut World, mask: [bool; 3]| {
+            world
+                .create_entity()
+                .with(Position {
+                    pos: initial_position.cast(),
+                })
+                .with(Velocity {
+                    vel: Vector3::new(0.5, 0.5, 0.5).cast(),
+                })
+                .with(Force {
+                    force: force.cast(),
+                })
+                .with(OldForce::default())
+                .with(Mass {
+                    value: 1.0 / constant::AMU,
+                })
+                .with(FrozenAxes { mask })
+                .build()
+        };
+        let planar = create_atom(&mut world, [false, false, true]);
+        let fixed = create_atom(&mut world, [true, true, true]);
+
+        world.insert(Timestep { delta: 1.0e-3 });
+        world.insert(Step { n: 0 });
+        for _i in 0..100 {
+            dispatcher.dispatch(&world);
+            world.maintain();
+        }
+
+        let positions = world.read_storage::<Position>();
+        let velocities = world.read_storage::<Velocity>();
+        let pos = positions.get(planar).unwrap().pos.cast::<f64>();
+        let vel = velocities.get(planar).unwrap().vel.cast::<f64>();
+        assert_eq!(pos[2], initial_position[2]);
+        assert_eq!(vel[2], 0.0);
+        assert!((pos[0] - initial_position[0]).abs() > 0.0);
+        assert!((pos[1] - initial_position[1]).abs() > 0.0);
+        assert_approx_eq::assert_approx_eq!(vel[0], 0.5 + force[0] * 0.1, 0.01);
+        assert_approx_eq::assert_approx_eq!(vel[1], 0.5 + force[1] * 0.1, 0.01);
+
+        assert_eq!(positions.get(fixed).unwrap().pos.cast::<f64>(), initial_position);
+        assert_eq!(velocities.get(fixed).unwrap().vel.cast::<f64>(), Vector3::zeros());
+        assert_eq!(world.read_storage::<Force>().get(fixed).unwrap().force.cast::<f64>(), force);
+    }
+
+    #[test]
+    fn test_synchronized_energy_is_conserved_in_harmonic_trap() {
+        use crate::custom_force::{CustomForceField, CustomForcePlugin};
+        use crate::simulation::SimulationBuilder;
+        use std::sync::{Arc, Mutex};
+
+        /// Records the raw and synchronized energy of the atoms, between the position and velocity integration.
+        struct RecordEnergySystem {
+            spring_constant: f64,
+            energies: Arc<Mutex<Vec<(f64, f64)>>>,
+        }
+        impl<'a> System<'a> for RecordEnergySystem {
+            type SystemData = (
+                ReadStorage<'a, Position>,
+                ReadStorage<'a, Velocity>,
+                ReadStorage<'a, SynchronizedVelocity>,
+                ReadStorage<'a, Mass>,
+            );
+            fn run(&mut self, (positions, velocities, synchronized, masses): Self::SystemData) {
+                for (pos, vel, synchronized, mass) in
+                    (&positions, &velocities, &synchronized, &masses).join()
+                {
+                    let potential = self.spring_constant * pos.pos.cast::<f64>().norm_squared() / 2.0;
+                    let kinetic = |v: Vector3<f64>| mass.value * constant::AMU * v.norm_squared() / 2.0;
+                    self.energies.lock().unwrap().push((
+                        potential + kinetic(vel.vel.cast()),
+                        potential + kinetic(synchronized.vel),
+                    ));
+                }
+            }
+        }
+
+        let mass = 87.0;
+        let spring_constant = mass * constant::AMU * (2.0 * constant::PI * 100.0).powi(2);
+        let energies = Arc::new(Mutex::new(Vec::new()));
+        let mut sim_builder = SimulationBuilder::default();
+        sim_builder.add_plugin(CustomForcePlugin);
+        sim_builder.add_plugin(SynchronizedVelocityPlugin);
+        sim_builder.dispatcher_builder.add(
+            RecordEnergySystem {
+                spring_constant,
+                energies: energies.clone(),
+            },
+            "record_energy",
+            &[SYNCHRONIZE_VELOCITY_SYSTEM_NAME],
+        );
+        let mut sim = sim_builder.build();
+        sim.world.insert(CustomForceField::new(move |pos, _vel| -spring_constant * pos));
+        sim.world.insert(Timestep { delta: 1.0e-5 });
+        sim.world
+            .create_entity()
+            .with(Position {
+                pos: Vector3::new(1.0e-3, 0.0, 0.0).cast(),
+            })
+            .with(Velocity {
+                vel: Vector3::new(0.0, 0.3, 0.0).cast(),
+            })
+            .with(Force::new())
+            .with(Mass { value: mass })
+            .with(Atom)
+            .with(NewlyCreated)
+            .build();
+        for _ in 0..2000 {
+            sim.step();
+        }
+
+        // Skip the first steps, in which the atom is initialised and the old force is not yet set.
+        let energies = energies.lock().unwrap();
+        let spread = |energy: &dyn Fn(&(f64, f64)) -> f64| {
+            let values: Vec<f64> = energies[5..].iter().map(energy).collect();
+            let max = values.iter().cloned().fold(f64::MIN, f64::max);
+            let min = values.iter().cloned().fold(f64::MAX, f64::min);
+            (max - min) / max
+        };
+        let raw = spread(&|energy| energy.0);
+        let synchronized = spread(&|energy| energy.1);
+        assert!(raw > 1.0e-3, "raw spread {}", raw);
+        assert!(synchronized < 0.1 * raw, "synchronized spread {}, raw spread {}", synchronized, raw);
+    }
+
+    #[test]
+    fn test_stability_clamp_limits_huge_velocity() {
+        let mut world = World::new();
+        let mut dispatcher = DispatcherBuilder::new()
+            .with(ClampStabilitySystem, CLAMP_STABILITY_SYSTEM_NAME, &[])
+            .build();
+        dispatcher.setup(&mut world);
+        world.insert(Step { n: 0 });
+
+        let atom = world
+            .create_entity()
+            .with(Position {
+                pos: Vector3::new(0.0, 0.0, 0.0).cast(),
+            })
+            .with(Velocity {
+                vel: Vector3::new(3.0e10, 4.0e10, 0.0).cast(),
+            })
+            .with(Atom)
+            .build();
+
+        // Clamping is off by default.
+        dispatcher.dispatch(&world);
+        world.maintain();
+        assert_eq!(
+            world.read_storage::<Velocity>().get(atom).unwrap().vel.cast::<f64>()[0],
+            3.0e10
+        );
+
+        world.insert(StabilityClamp::new(100.0, 1.0));
+        dispatcher.dispatch(&world);
+        world.maintain();
+
+        let vel = world.read_storage::<Velocity>().get(atom).unwrap().vel.cast::<f64>();
+        assert!((vel.norm() - 100.0).abs() < 1e-3);
+        assert!((vel[0] - 60.0).abs() < 1e-3);
+        assert_eq!(world.read_resource::<StabilityClamp>().clamped(), 1);
+    }
+
+    /// Tests that a free particle is integrated correctly when built with single precision components.
+    #[cfg(feature = "f32")]
+    #[test]
+    fn test_f32_free_particle_integration() {
+        let mut world = World::new();
+
+        let mut dispatcher = DispatcherBuilder::new()
+            .with(
+                VelocityVerletIntegratePositionSystem,
+                "integrate_position",
+                &[],
+            )
+            .with(
+                VelocityVerletIntegrateVelocitySystem,
+                "integrate_velocity",
+                &["integrate_position"],
+            )
+            .build();
+        dispatcher.setup(&mut world);
+
+        let velocity = Vector3::new(0.1, -0.2, 0.3);
+        let atom = world
+            .create_entity()
+            .with(Position {
+                pos: Vector3::new(0.0, 0.0, 0.0),
+            })
+            .with(Velocity { vel: velocity })
+            .with(Force::new())
+            .with(OldForce::default())
+            .with(Mass { value: 87.0 })
+            .build();
+
+        let dt = 1.0e-5;
+        world.insert(Timestep { delta: dt });
+        world.insert(Step { n: 0 });
+
+        let n_steps = 10_000;
+        for _i in 0..n_steps {
+            dispatcher.dispatch(&world);
+            world.maintain();
+        }
+
+        // Accumulated rounding error grows with the number of steps.
+        let tolerance = n_steps as f64 * f32::EPSILON as f64;
+        let expected_x = velocity.cast::<f64>() * (n_steps as f64 * dt);
+        let pos = world.read_storage::<Position>().get(atom).unwrap().pos;
+        for i in 0..3 {
+            assert_approx_eq::assert_approx_eq!(
+                pos[i] as f64,
+                expected_x[i],
+                expected_x.norm() * tolerance
+            );
+        }
+        assert_eq!(
+            world.read_storage::<Velocity>().get(atom).unwrap().vel,
+            velocity
+        );
+    }
 }
@@ -0,0 +1,144 @@
+//! An impulsive change of the velocity of all atoms, for launches and kick pulses.
+//!
+//! A [VelocityKick] adds the same velocity to every atom at a chosen time, for example to model the launch of an
+//! atomic fountain, a Bragg pulse, or the sudden displacement of a trap, which in the frame of the trap is a kick.
+//! The kick is instantaneous: it is applied at the end of the step during which the [SimulationTime] reaches
+//! `at_time`, once the velocities of that step have been integrated.
+
+use crate::atom::{Atom, Velocity};
+use crate::integrator::{
+    FrozenAxes, Pinned, SimulationTime, Step, Timestep, INTEGRATE_VELOCITY_SYSTEM_NAME,
+};
+use crate::simulation::Plugin;
+use nalgebra::Vector3;
+use specs::prelude::*;
+
+/// A resource that adds a velocity to all atoms at a given time, see [crate::kick].
+#[derive(Clone, Copy, Debug)]
+pub struct VelocityKick {
+    /// The velocity added to each atom, in SI units of m/s.
+    pub delta_v: Vector3<f64>,
+    /// The time of the kick, in SI units of s.
+    pub at_time: f64,
+}
+impl VelocityKick {
+    /// True if the kick fires during the step that ends at `time`, which started at `time - dt`.
+    ///
+    /// Each time lies in exactly one interval `(time - dt, time]`, so the kick fires on exactly one step. A kick
+    /// scheduled at or before the start of the simulation fires on the first step.
+    pub fn fires(&self, time: &SimulationTime, dt: f64) -> bool {
+        let previous = (time.step as f64 - 1.0) * dt;
+        self.at_time <= time.time && (time.step <= 1 || self.at_time > previous)
+    }
+}
+
+/// Adds the [VelocityKick] to the [Velocity] of each [Atom] on the step that it fires.
+///
+/// [Pinned] atoms are not kicked, and the kick is not applied along the [FrozenAxes] of an atom. Does nothing if
+/// the [VelocityKick] resource is not present. A kick that is inserted after its time has passed does not fire.
+pub struct ApplyVelocityKickSystem;
+impl<'a> System<'a> for ApplyVelocityKickSystem {
+    type SystemData = (
+        WriteStorage<'a, Velocity>,
+        ReadStorage<'a, Atom>,
+        ReadStorage<'a, Pinned>,
+        ReadStorage<'a, FrozenAxes>,
+        ReadExpect<'a, Step>,
+        ReadExpect<'a, Timestep>,
+        Option<Read<'a, VelocityKick>>,
+    );
+
+    fn run(
+        &mut self,
+        (mut velocities, atoms, pinned, frozen, step, timestep, kick): Self::SystemData,
+    ) {
+        let kick = match kick {
+            Some(kick) => *kick,
+            None => return,
+        };
+        if !kick.fires(&SimulationTime::new(&step, &timestep), timestep.delta) {
+            return;
+        }
+        for (vel, _, _, frozen) in (&mut velocities, &atoms, !&pinned, frozen.maybe()).join() {
+            let delta_v = frozen.map_or(kick.delta_v, |frozen| frozen.constrain(kick.delta_v));
+            vel.vel += delta_v.cast();
+        }
+    }
+}
+
+/// This plugin applies the [VelocityKick] resource, if present.
+///
+/// The kick is applied after the velocity integrator, so the end frame systems must be added to the
+/// [SimulationBuilder](crate::simulation::SimulationBuilder) before this plugin.
+///
+/// See also [crate::kick].
+pub struct VelocityKickPlugin;
+impl Plugin for VelocityKickPlugin {
+    fn build(&self, builder: &mut crate::simulation::SimulationBuilder) {
+        builder.dispatcher_builder.add(
+            ApplyVelocityKickSystem,
+            "apply_velocity_kick",
+            &[INTEGRATE_VELOCITY_SYSTEM_NAME],
+        );
+    }
+    fn deps(&self) -> Vec<Box<dyn Plugin>> {
+        Vec::new()
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use crate::atom::{Force, Mass, Position};
+    use crate::initiate::NewlyCreated;
+    use crate::simulation::SimulationBuilder;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_kick_fires_once() {
+        let dt = 1.0e-6;
+        // Between two steps, and exactly on a step.
+        for &(at_time, kick_step) in [(2.5e-6, 3), (5.0 * dt, 5)].iter() {
+            let mut sim_builder = SimulationBuilder::default();
+            sim_builder.add_end_frame_systems();
+            sim_builder.add_plugin(VelocityKickPlugin);
+            let mut sim = sim_builder.build();
+            sim.world.insert(Timestep { delta: dt });
+            let delta_v = Vector3::new(0.1, -0.2, 0.3);
+            sim.world.insert(VelocityKick { delta_v, at_time });
+
+            let initial: Vec<Vector3<f64>> = (0..10)
+                .map(|i| Vector3::new(i as f64, 0.0, -0.5 * i as f64))
+                .collect();
+            let atoms: Vec<Entity> = initial
+                .iter()
+                .map(|vel| {
+                    sim.world
+                        .create_entity()
+                        .with(Position::new())
+                        .with(Velocity { vel: vel.cast() })
+                        .with(Mass { value: 87.0 })
+                        .with(Force::new())
+                        .with(Atom)
+                        .with(NewlyCreated)
+                        .build()
+                })
+                .collect();
+
+            for n in 1..=10 {
+                sim.step();
+                let expected_kick = if n >= kick_step {
+                    delta_v
+                } else {
+                    Vector3::zeros()
+                };
+                let velocities = sim.world.read_storage::<Velocity>();
+                for (atom, vel) in atoms.iter().zip(initial.iter()) {
+                    let change = velocities.get(*atom).unwrap().vel.cast::<f64>() - vel;
+                    assert_approx_eq!((change - expected_kick).norm(), 0.0, 1e-12);
+                }
+            }
+        }
+    }
+}
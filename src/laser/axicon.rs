@@ -0,0 +1,257 @@
+//! Hollow conical beams, formed by focusing a beam through an axicon.
+//!
+//! An axicon refracts a collimated beam into a cone. Beyond the short region where the cone overlaps on the axis
+//! and forms a Bessel beam, the light is concentrated on a thin ring with a dark core, whose radius grows (or
+//! shrinks, for a converging cone) linearly along the beam. These hollow beams guide and funnel atoms: blue-detuned
+//! light confines atoms to the dark core, and red-detuned light to the bright ring.
+//!
+//! [HollowConicalBeam]s are sampled once their type is registered with a
+//! [BeamSourcePlugin](crate::laser::beam_source::BeamSourcePlugin), added before the
+//! [LaserPlugin](crate::laser::LaserPlugin).
+
+use nalgebra::Vector3;
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+
+use super::beam_source::{BeamModifiers, BeamSource, TransversePlane};
+use super::intensity_gradient::get_numerical_intensity_gradient;
+use crate::atom::Position;
+use crate::constant::PI;
+use crate::maths;
+
+/// A component that describes a hollow beam, with a ring-shaped intensity profile whose radius changes linearly
+/// along the beam.
+///
+/// At a distance `z` along the beam from the `intersection`, the intensity at a distance `rho` from the axis is
+/// `I = P exp(-(rho - R)^2 / (2 ring_width^2)) / A`, where the ring radius is
+/// `R = max(radius + z tan(cone_angle), 0)` and the area `A` normalizes the power in each plane to `power`.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug)]
+pub struct HollowConicalBeam {
+    /// A point on the axis of the beam, at which the ring has the radius `radius`, in SI units of m.
+    pub intersection: Vector3<f64>,
+    /// Direction the beam propagates, a unit vector.
+    pub direction: Vector3<f64>,
+    /// Radius of the ring at the `intersection`, in SI units of m.
+    pub radius: f64,
+    /// Half-angle of the cone, in radians. The ring grows along the beam for positive angles, and shrinks for
+    /// negative angles.
+    pub cone_angle: f64,
+    /// Standard deviation of the radial intensity profile of the ring, in SI units of m.
+    pub ring_width: f64,
+    /// Power of the beam, in SI units of W.
+    pub power: f64,
+}
+impl Component for HollowConicalBeam {
+    type Storage = HashMapStorage<Self>;
+}
+
+impl HollowConicalBeam {
+    /// The radius of the ring at a distance `z` along the beam from the `intersection`, in SI units of m.
+    pub fn ring_radius(&self, z: f64) -> f64 {
+        (self.radius + z * self.cone_angle.tan()).max(0.0)
+    }
+
+    /// The intensity at a distance `rho` from the axis of the beam, for a ring of radius `ring_radius`.
+    fn ring_intensity(&self, rho: f64, ring_radius: f64) -> f64 {
+        let sigma = self.ring_width;
+        let x = ring_radius / (2.0_f64.sqrt() * sigma);
+        // Integral of the unnormalized profile over the transverse plane.
+        let area = 2.0
+            * PI
+            * sigma
+            * (sigma * (-x * x).exp() + ring_radius * (PI / 2.0).sqrt() * (1.0 + maths::erf(x)));
+        self.power * (-(rho - ring_radius).powi(2) / (2.0 * sigma.powi(2))).exp() / area
+    }
+}
+
+impl BeamSource for HollowConicalBeam {
+    /// The intensity of the ring. A [CircularMask](crate::laser::gaussian::CircularMask) and an
+    /// [CircularAperture](crate::laser::gaussian::CircularAperture) are applied, and the other modifiers are
+    /// ignored.
+    fn intensity(&self, pos: &Position, modifiers: &BeamModifiers) -> f64 {
+        if let Some(aperture) = modifiers.aperture {
+            if !aperture.transmits(&self.intersection, &self.direction, pos) {
+                return 0.0;
+            }
+        }
+        let (rho, z) = maths::get_minimum_distance_line_point(
+            &pos.pos.cast(),
+            &self.intersection,
+            &self.direction,
+        );
+        if let Some(mask) = modifiers.mask {
+            if rho < mask.radius {
+                return 0.0;
+            }
+        }
+        self.ring_intensity(rho, self.ring_radius(z))
+    }
+
+    /// The gradient of the intensity, calculated by central differences.
+    fn gradient(&self, pos: &Position, _modifiers: &BeamModifiers) -> Vector3<f64> {
+        get_numerical_intensity_gradient(
+            |p| self.intensity(p, &BeamModifiers::default()),
+            &pos.pos.cast(),
+            self.gradient_step(),
+        )
+    }
+
+    /// A hundredth of the width of the ring.
+    fn gradient_step(&self) -> f64 {
+        0.01 * self.ring_width
+    }
+
+    /// The plane of the `intersection`, out to eight widths beyond the ring.
+    fn transverse_plane(&self) -> Option<TransversePlane> {
+        Some(TransversePlane {
+            power: self.power,
+            centre: self.intersection,
+            normal: self.direction,
+            half_width: self.radius + 8.0 * self.ring_width,
+            spacing: 0.2 * self.ring_width,
+        })
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use crate::atom::Atom;
+    use crate::dipole::DipoleLight;
+    use crate::initiate::NewlyCreated;
+    use crate::integrator::Timestep;
+    use crate::laser::beam_source::BeamSourcePlugin;
+    use crate::laser::index::LaserIndex;
+    use crate::laser::intensity::LaserIntensitySamplers;
+    use crate::laser::LaserPlugin;
+    use crate::simulation::SimulationBuilder;
+    use assert_approx_eq::assert_approx_eq;
+
+    fn beam() -> HollowConicalBeam {
+        HollowConicalBeam {
+            intersection: Vector3::new(0.0, 0.0, 0.0),
+            direction: Vector3::z(),
+            radius: 1.0e-3,
+            cone_angle: 0.01,
+            ring_width: 5.0e-5,
+            power: 0.1,
+        }
+    }
+
+    fn intensity(beam: &HollowConicalBeam, pos: Vector3<f64>) -> f64 {
+        beam.intensity(&Position { pos: pos.cast() }, &BeamModifiers::default())
+    }
+
+    #[test]
+    fn test_dark_core_and_bright_ring() {
+        let beam = beam();
+        for z in [-0.05, 0.0, 0.1].iter() {
+            let expected_radius = 1.0e-3 + z * 0.01_f64.tan();
+
+            // The brightest radius is at the ring, and the axis is dark.
+            let n = 4000;
+            let (peak_radius, peak) = (0..n)
+                .map(|i| i as f64 * 2.0 * expected_radius / n as f64)
+                .map(|rho| (rho, intensity(&beam, Vector3::new(rho, 0.0, *z))))
+                .fold(
+                    (0.0, 0.0),
+                    |best, sample| if sample.1 > best.1 { sample } else { best },
+                );
+            assert_approx_eq!(
+                peak_radius,
+                expected_radius,
+                2.0 * expected_radius / n as f64
+            );
+            assert!(intensity(&beam, Vector3::new(0.0, 0.0, *z)) < 1e-6 * peak);
+
+            // The ring is symmetric about the axis.
+            assert_approx_eq!(
+                intensity(&beam, Vector3::new(0.0, -expected_radius, *z)),
+                peak,
+                1e-6 * peak
+            );
+
+            // The power in each plane is conserved.
+            let dr = beam.ring_width / 50.0;
+            let power: f64 = (0..(4.0 * expected_radius / dr) as usize)
+                .map(|i| (i as f64 + 0.5) * dr)
+                .map(|rho| 2.0 * PI * rho * dr * intensity(&beam, Vector3::new(rho, 0.0, *z)))
+                .sum();
+            assert_approx_eq!(power, beam.power, 1e-4 * beam.power);
+        }
+
+        // A converging cone closes to a spot on the axis.
+        let converging = HollowConicalBeam {
+            cone_angle: -0.01,
+            ..beam
+        };
+        let focus = 1.0e-3 / 0.01_f64.tan();
+        assert_eq!(converging.ring_radius(focus + 0.01), 0.0);
+        assert!(
+            intensity(&converging, Vector3::new(0.0, 0.0, focus))
+                > intensity(&converging, Vector3::new(0.0, 0.0, 0.0))
+        );
+    }
+
+    #[test]
+    fn test_power_normalization() {
+        use crate::laser::beam_source::verify_power_normalization;
+
+        // A ring, and a converging cone that has closed to a spot on the axis.
+        let ring = beam();
+        let spot = HollowConicalBeam {
+            radius: 0.0,
+            cone_angle: -0.01,
+            ..beam()
+        };
+        for beam in [ring, spot].iter() {
+            assert_approx_eq!(verify_power_normalization(beam, None), 1.0, 0.01);
+        }
+    }
+
+    #[test]
+    fn test_hollow_conical_beam_is_sampled() {
+        let mut sim_builder = SimulationBuilder::default();
+        sim_builder.add_plugin(BeamSourcePlugin::<HollowConicalBeam, 1>::default());
+        sim_builder.add_plugin(LaserPlugin::<1>);
+        let mut sim = sim_builder.build();
+        sim.world.insert(Timestep { delta: 1.0e-6 });
+
+        let beam = beam();
+        sim.world
+            .create_entity()
+            .with(beam)
+            .with(DipoleLight {
+                wavelength: 1064e-9,
+            })
+            .with(LaserIndex::default())
+            .build();
+        let positions = [
+            Vector3::new(0.0, 0.0, 0.1),
+            Vector3::new(beam.ring_radius(0.1), 0.0, 0.1),
+        ];
+        let atoms: Vec<Entity> = positions
+            .iter()
+            .map(|pos| {
+                sim.world
+                    .create_entity()
+                    .with(Position { pos: pos.cast() })
+                    .with(Atom)
+                    .with(NewlyCreated)
+                    .build()
+            })
+            .collect();
+        sim.step();
+        sim.step();
+
+        let intensities = sim.world.read_storage::<LaserIntensitySamplers<1>>();
+        for (atom, pos) in atoms.iter().zip(positions.iter()) {
+            assert_approx_eq!(
+                intensities.get(*atom).unwrap().contents[0].intensity,
+                intensity(&beam, *pos),
+                1e-9 * intensity(&beam, positions[1])
+            );
+        }
+    }
+}
@@ -0,0 +1,465 @@
+//! A uniform interface to the intensity profiles of laser beams.
+//!
+//! The intensity and intensity gradient of each laser beam are sampled at the positions of atoms by systems which
+//! are generic over the [BeamSource] trait. Any component that implements [BeamSource] can describe a laser beam:
+//! add it to an entity with a [LaserIndex](crate::laser::index::LaserIndex), and register the type with a
+//! [BeamSourcePlugin]. [GaussianBeam](crate::laser::gaussian::GaussianBeam) is registered by the
+//! [LaserPlugin](crate::laser::LaserPlugin).
+//!
+//! Profiles with a stated power describe a [TransversePlane] that contains the beam, so that the normalization of
+//! their intensity can be checked with [verify_power_normalization].
+
+use std::any::type_name;
+use std::marker::PhantomData;
+
+use nalgebra::Vector3;
+use specs::prelude::*;
+use specs::storage::MaskedStorage;
+
+use super::frame::Frame;
+use super::gaussian::{
+    CircularAperture, CircularMask, InteractionCutoff, NonParaxialCorrection,
+};
+use super::intensity::SampleBeamSourceIntensitySystem;
+use super::intensity_gradient::SampleBeamSourceIntensityGradientSystem;
+use crate::atom::Position;
+use crate::dispatch::RecordingDispatcherBuilder;
+use crate::integrator::INTEGRATE_POSITION_SYSTEM_NAME;
+use crate::simulation::Plugin;
+
+/// Optional components of a beam entity that modify how the beam is sampled.
+#[derive(Clone, Copy, Default)]
+pub struct BeamModifiers<'a> {
+    /// A mask that blocks the centre of the beam.
+    pub mask: Option<&'a CircularMask>,
+    /// A mask that blocks the beam outside a radius.
+    pub aperture: Option<&'a CircularAperture>,
+    /// The reference frame of the beam, used for elliptical beams.
+    pub frame: Option<&'a Frame>,
+    /// The distance beyond which the beam does not interact with atoms.
+    pub cutoff: Option<&'a InteractionCutoff>,
+    /// The non-paraxial correction of a tightly focused beam.
+    pub non_paraxial: Option<&'a NonParaxialCorrection>,
+}
+
+/// A component that describes the intensity profile of a laser beam.
+///
+/// Implementations may ignore any [BeamModifiers] that do not apply to their profile.
+pub trait BeamSource: Component + Clone + Send + Sync {
+    /// The intensity of the beam at `pos`, in SI units of W/m^2.
+    fn intensity(&self, pos: &Position, modifiers: &BeamModifiers) -> f64;
+
+    /// The gradient of the intensity of the beam at `pos`, in SI units of W/m^3.
+    ///
+    /// Masks and cutoffs are not applied to gradients.
+    fn gradient(&self, pos: &Position, modifiers: &BeamModifiers) -> Vector3<f64>;
+
+    /// The step size used to differentiate the intensity numerically, in SI units of m.
+    ///
+    /// See [GradientMethod::Numerical](crate::laser::intensity_gradient::GradientMethod::Numerical).
+    fn gradient_step(&self) -> f64;
+
+    /// A plane transverse to the beam that contains all of its stated power, see [verify_power_normalization].
+    ///
+    /// The default is `None`, for profiles without a stated power.
+    fn transverse_plane(&self) -> Option<TransversePlane> {
+        None
+    }
+
+    /// The direction of propagation of the beam, a unit vector.
+    ///
+    /// The default is the normal of the [BeamSource::transverse_plane], so profiles without a stated power but with
+    /// a direction of propagation should override it.
+    fn direction(&self) -> Option<Vector3<f64>> {
+        self.transverse_plane().map(|plane| plane.normal.normalize())
+    }
+}
+
+/// A square region of a plane transverse to a [BeamSource], used to integrate the power of the beam.
+#[derive(Clone, Copy, Debug)]
+pub struct TransversePlane {
+    /// The stated power of the beam, in SI units of W.
+    pub power: f64,
+    /// The centre of the region, in SI units of m.
+    pub centre: Vector3<f64>,
+    /// The normal to the plane, the direction of the beam.
+    pub normal: Vector3<f64>,
+    /// Half of the width of the region, in SI units of m. The intensity outside the region must be negligible.
+    pub half_width: f64,
+    /// Spacing of the integration grid, in SI units of m, fine enough to resolve the profile.
+    pub spacing: f64,
+}
+
+/// Integrates the intensity of a beam over its [TransversePlane], and returns the ratio of the integrated power to
+/// the stated power, which is one for a correctly normalized profile.
+///
+/// The intensity is sampled at the centres of the cells of a square grid, with the beam's [Frame] if it has one and
+/// no other [BeamModifiers], so the ratio is accurate to the order of `(spacing / scale)^2`, where `scale` is the
+/// smallest length of the profile. Profiles that depend on the [Frame], such as an elliptical
+/// [crate::laser::gaussian::GaussianBeam], should be checked with one. New beam profiles should be checked with this
+/// function in their tests.
+///
+/// Panics if the beam does not have a [TransversePlane].
+pub fn verify_power_normalization<B: BeamSource>(beam: &B, frame: Option<&Frame>) -> f64 {
+    let plane = beam.transverse_plane().unwrap_or_else(|| {
+        panic!(
+            "Cannot verify the power of {}: it has no TransversePlane.",
+            type_name::<B>()
+        )
+    });
+    let normal = plane.normal.normalize();
+    // Any axis that is not parallel to the normal gives a basis of the plane.
+    let axis = if normal.x.abs() < 0.9 {
+        Vector3::x()
+    } else {
+        Vector3::y()
+    };
+    let u = normal.cross(&axis).normalize();
+    let v = normal.cross(&u);
+    let modifiers = BeamModifiers {
+        frame,
+        ..Default::default()
+    };
+    let n = (2.0 * plane.half_width / plane.spacing).ceil() as usize;
+    let spacing = 2.0 * plane.half_width / n as f64;
+    let mut power = 0.0;
+    for i in 0..n {
+        for j in 0..n {
+            let a = -plane.half_width + (i as f64 + 0.5) * spacing;
+            let b = -plane.half_width + (j as f64 + 0.5) * spacing;
+            let pos = Position {
+                pos: (plane.centre + a * u + b * v).cast(),
+            };
+            power += beam.intensity(&pos, &modifiers);
+        }
+    }
+    power * spacing * spacing / plane.power
+}
+
+/// The type and stated power of the profile of a beam, see [crate::query::list_beams].
+#[derive(Clone, Copy, Debug)]
+pub struct BeamProfile {
+    /// Name of the [BeamSource] type of the beam, for example `GaussianBeam`.
+    pub name: &'static str,
+    /// The plane that contains the stated power of the beam, see [BeamSource::transverse_plane].
+    pub plane: Option<TransversePlane>,
+    /// The direction of propagation of the beam, see [BeamSource::direction].
+    pub direction: Option<Vector3<f64>>,
+}
+
+/// Returns the [BeamProfile] of `entity`, if it has a [BeamSource] component of type `B`.
+pub fn beam_profile<B: BeamSource>(world: &World, entity: Entity) -> Option<BeamProfile> {
+    if !world.has_value::<MaskedStorage<B>>() {
+        return None;
+    }
+    world
+        .read_storage::<B>()
+        .get(entity)
+        .map(|beam| BeamProfile {
+            name: type_name::<B>().rsplit("::").next().unwrap(),
+            plane: beam.transverse_plane(),
+            direction: beam.direction(),
+        })
+}
+
+/// A beam type registered with a [BeamSourcePlugin].
+struct BeamSourceRegistration {
+    intensity_system: String,
+    gradient_system: String,
+    add_systems: fn(&mut RecordingDispatcherBuilder, &str, &str),
+    profile: fn(&World, Entity) -> Option<BeamProfile>,
+}
+
+/// The list of [BeamSource] types registered with [BeamSourcePlugin]s.
+///
+/// The [LaserPlugin](crate::laser::LaserPlugin) adds the sampling systems of each registered type, and then
+/// samples [GaussianBeam](crate::laser::gaussian::GaussianBeam)s, so systems which depend on the
+/// `sample_laser_intensity` and `sample_intensity_gradient` systems see all beam types.
+#[derive(Default)]
+pub struct BeamSourceRegistry {
+    sources: Vec<BeamSourceRegistration>,
+    closed: bool,
+}
+impl BeamSourceRegistry {
+    /// Adds the sampling systems of all registered types, and returns their names as
+    /// `(intensity_systems, gradient_systems)`. No more types can be registered afterwards.
+    pub(crate) fn add_systems_to_dispatch(
+        &mut self,
+        builder: &mut RecordingDispatcherBuilder,
+    ) -> (Vec<String>, Vec<String>) {
+        self.closed = true;
+        for source in self.sources.iter() {
+            (source.add_systems)(builder, &source.intensity_system, &source.gradient_system);
+        }
+        (
+            self.sources
+                .iter()
+                .map(|source| source.intensity_system.clone())
+                .collect(),
+            self.sources
+                .iter()
+                .map(|source| source.gradient_system.clone())
+                .collect(),
+        )
+    }
+
+    /// Returns the [BeamProfile] of `entity`, if it has a component of one of the registered types.
+    pub fn beam_profile(&self, world: &World, entity: Entity) -> Option<BeamProfile> {
+        self.sources
+            .iter()
+            .find_map(|source| (source.profile)(world, entity))
+    }
+}
+
+/// Adds the systems that sample the intensity and gradient of beams of type `B`.
+fn add_beam_source_systems<B, const N: usize>(
+    builder: &mut RecordingDispatcherBuilder,
+    intensity_system: &str,
+    gradient_system: &str,
+) where
+    B: BeamSource,
+{
+    builder.add(
+        SampleBeamSourceIntensitySystem::<B, N>::default(),
+        intensity_system,
+        &[
+            "index_lasers",
+            "initialise_laser_intensity",
+            INTEGRATE_POSITION_SYSTEM_NAME,
+        ],
+    );
+    builder.add(
+        SampleBeamSourceIntensityGradientSystem::<B, N>::default(),
+        gradient_system,
+        &["index_lasers"],
+    );
+}
+
+/// This plugin registers a [BeamSource] type, so that beams of that type are sampled.
+///
+/// The plugin must be added before the [LaserPlugin](crate::laser::LaserPlugin).
+///
+/// # Generic Arguments
+///
+/// * `B`: The beam type.
+///
+/// * `N`: The maximum number of laser beams (must match the `LaserPlugin`).
+pub struct BeamSourcePlugin<B, const N: usize>(PhantomData<B>)
+where
+    B: BeamSource;
+impl<B, const N: usize> Default for BeamSourcePlugin<B, N>
+where
+    B: BeamSource,
+{
+    fn default() -> Self {
+        BeamSourcePlugin(PhantomData)
+    }
+}
+impl<B, const N: usize> Plugin for BeamSourcePlugin<B, N>
+where
+    B: BeamSource,
+{
+    fn build(&self, builder: &mut crate::simulation::SimulationBuilder) {
+        let mut registry = builder
+            .world
+            .entry::<BeamSourceRegistry>()
+            .or_insert_with(BeamSourceRegistry::default);
+        if registry.closed {
+            panic!(
+                "Cannot add plugin {}: it must be added before the LaserPlugin.",
+                self.name()
+            );
+        }
+        registry.sources.push(BeamSourceRegistration {
+            intensity_system: format!("sample_laser_intensity_{}", type_name::<B>()),
+            gradient_system: format!("sample_intensity_gradient_{}", type_name::<B>()),
+            add_systems: add_beam_source_systems::<B, N>,
+            profile: beam_profile::<B>,
+        });
+    }
+    fn deps(&self) -> Vec<Box<dyn Plugin>> {
+        Vec::new()
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use crate::atom::Atom;
+    use crate::dipole::DipoleLight;
+    use crate::initiate::NewlyCreated;
+    use crate::integrator::Timestep;
+    use crate::laser::gaussian::{get_gaussian_beam_intensity, GaussianBeam};
+    use crate::laser::index::LaserIndex;
+    use crate::laser::intensity::{LaserIntensitySamplers, TotalIntensity};
+    use crate::laser::intensity_gradient::LaserIntensityGradientSamplers;
+    use crate::laser::LaserPlugin;
+    use crate::simulation::SimulationBuilder;
+    use assert_approx_eq::assert_approx_eq;
+
+    /// A user-defined beam, whose intensity increases linearly along x.
+    #[derive(Clone, Copy)]
+    struct RampBeam {
+        intensity: f64,
+        slope: f64,
+    }
+    impl Component for RampBeam {
+        type Storage = HashMapStorage<Self>;
+    }
+    impl BeamSource for RampBeam {
+        fn intensity(&self, pos: &Position, _modifiers: &BeamModifiers) -> f64 {
+            self.intensity + self.slope * pos.pos.cast::<f64>()[0]
+        }
+        fn gradient(&self, _pos: &Position, _modifiers: &BeamModifiers) -> Vector3<f64> {
+            Vector3::new(self.slope, 0.0, 0.0)
+        }
+        fn gradient_step(&self) -> f64 {
+            1.0e-6
+        }
+    }
+
+    #[test]
+    fn test_custom_beam_source_is_sampled() {
+        let mut sim_builder = SimulationBuilder::default();
+        sim_builder.add_plugin(BeamSourcePlugin::<RampBeam, 2>::default());
+        sim_builder.add_plugin(LaserPlugin::<2>);
+        let mut sim = sim_builder.build();
+        sim.world.insert(Timestep { delta: 1.0e-6 });
+
+        let gaussian = GaussianBeam {
+            intersection: Vector3::new(0.0, 0.0, 0.0),
+            e_radius: 1.0e-3,
+            power: 0.1,
+            direction: Vector3::z(),
+            rayleigh_range: f64::INFINITY,
+            ellipticity: 0.0,
+            focus_offset: 0.0,
+        };
+        sim.world
+            .create_entity()
+            .with(gaussian)
+            .with(LaserIndex::default())
+            .build();
+        let ramp = RampBeam {
+            intensity: 10.0,
+            slope: 2.0e3,
+        };
+        sim.world
+            .create_entity()
+            .with(ramp)
+            .with(DipoleLight {
+                wavelength: 1064e-9,
+            })
+            .with(LaserIndex::default())
+            .build();
+        let position = Position {
+            pos: Vector3::new(0.5e-3, 0.0, 0.0).cast(),
+        };
+        let atom = sim
+            .world
+            .create_entity()
+            .with(position.clone())
+            .with(Atom)
+            .with(NewlyCreated)
+            .build();
+        sim.step();
+        sim.step();
+
+        let indices = sim.world.read_storage::<LaserIndex>();
+        let ramps = sim.world.read_storage::<RampBeam>();
+        let ramp_index = (&indices, &ramps).join().next().unwrap().0.index;
+        let gaussian_index = 1 - ramp_index;
+        let intensities = sim.world.read_storage::<LaserIntensitySamplers<2>>();
+        let samplers = intensities.get(atom).unwrap().contents;
+        let ramp_intensity = ramp.intensity(&position, &BeamModifiers::default());
+        let gaussian_intensity = get_gaussian_beam_intensity(&gaussian, &position, None, None);
+        assert_eq!(samplers[ramp_index].intensity, ramp_intensity);
+        assert_approx_eq!(
+            samplers[gaussian_index].intensity,
+            gaussian_intensity,
+            1e-12 * gaussian_intensity
+        );
+        let total = sim.world.read_storage::<TotalIntensity>();
+        assert_approx_eq!(
+            total.get(atom).unwrap().value,
+            ramp_intensity + gaussian_intensity,
+            1e-12 * gaussian_intensity
+        );
+        let gradients = sim
+            .world
+            .read_storage::<LaserIntensityGradientSamplers<2>>();
+        assert_eq!(
+            gradients.get(atom).unwrap().contents[ramp_index].gradient,
+            Vector3::new(ramp.slope, 0.0, 0.0)
+        );
+    }
+
+    /// A user-defined beam with a uniform disk profile, whose intensity is scaled by `normalization`.
+    #[derive(Clone, Copy)]
+    struct DiskBeam {
+        power: f64,
+        radius: f64,
+        normalization: f64,
+    }
+    impl Component for DiskBeam {
+        type Storage = HashMapStorage<Self>;
+    }
+    impl BeamSource for DiskBeam {
+        fn intensity(&self, pos: &Position, _modifiers: &BeamModifiers) -> f64 {
+            let pos = pos.pos.cast::<f64>();
+            if pos[0].hypot(pos[1]) < self.radius {
+                self.normalization * self.power / (crate::constant::PI * self.radius.powi(2))
+            } else {
+                0.0
+            }
+        }
+        fn gradient(&self, _pos: &Position, _modifiers: &BeamModifiers) -> Vector3<f64> {
+            Vector3::new(0.0, 0.0, 0.0)
+        }
+        fn gradient_step(&self) -> f64 {
+            1.0e-6
+        }
+        fn transverse_plane(&self) -> Option<TransversePlane> {
+            Some(TransversePlane {
+                power: self.power,
+                centre: Vector3::new(0.0, 0.0, 1.0),
+                normal: Vector3::z(),
+                half_width: 1.1 * self.radius,
+                spacing: 1.0e-3 * self.radius,
+            })
+        }
+    }
+
+    #[test]
+    fn test_verify_power_normalization_detects_wrong_normalization() {
+        let disk = DiskBeam {
+            power: 0.5,
+            radius: 1.0e-3,
+            normalization: 1.0,
+        };
+        assert_approx_eq!(verify_power_normalization(&disk, None), 1.0, 0.01);
+        let doubled = DiskBeam {
+            normalization: 2.0,
+            ..disk
+        };
+        assert_approx_eq!(verify_power_normalization(&doubled, None), 2.0, 0.02);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_verify_power_normalization_requires_transverse_plane() {
+        verify_power_normalization(&RampBeam {
+            intensity: 1.0,
+            slope: 0.0,
+        }, None);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_beam_source_plugin_must_precede_laser_plugin() {
+        let mut sim_builder = SimulationBuilder::default();
+        sim_builder.add_plugin(LaserPlugin::<2>);
+        sim_builder.add_plugin(BeamSourcePlugin::<RampBeam, 2>::default());
+    }
+}
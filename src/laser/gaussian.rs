@@ -8,6 +8,8 @@ use nalgebra::Vector3;
 use specs::{Component, HashMapStorage};
 
 use crate::atom::Position;
+use crate::laser::beam_source::{BeamModifiers, BeamSource, TransversePlane};
+use crate::laser::intensity_gradient::{get_numerical_intensity_gradient, numerical_gradient_step};
 use crate::constant::EXP;
 use crate::constant::PI;
 use crate::maths;
@@ -27,6 +29,9 @@ pub struct GaussianBeam {
     pub intersection: Vector3<f64>,
 
     /// Direction the beam propagates with respect to cartesian `x,y,z` axes.
+    ///
+    /// This must be a unit vector. The constructors normalize the direction, but beams built directly
+    /// should use [GaussianBeam::normalized].
     pub direction: Vector3<f64>,
 
     /// Radius of the beam at which the intensity is 1/e of the peak value, SI units of m.
@@ -46,11 +51,30 @@ pub struct GaussianBeam {
 
     /// ellipticity
     pub ellipticity: f64,
+
+    /// Displacement of the beam waist along the propagation direction, in SI units of m.
+    ///
+    /// The waist of the beam is located at `intersection + focus_offset * direction`.
+    #[serde(default)]
+    pub focus_offset: f64,
 }
 impl Component for GaussianBeam {
     type Storage = HashMapStorage<Self>;
 }
 impl GaussianBeam {
+    /// Returns the beam with its `direction` normalized to a unit vector.
+    pub fn normalized(self) -> Self {
+        GaussianBeam {
+            direction: self.direction.normalize(),
+            ..self
+        }
+    }
+
+    /// Returns true if the `direction` of the beam is a unit vector.
+    pub fn has_unit_direction(&self) -> bool {
+        (self.direction.norm() - 1.0).abs() < 1e-9
+    }
+
     /// Create a GaussianBeam component by specifying the peak intensity, rather than power.
     ///
     /// # Arguments:
@@ -72,11 +96,12 @@ impl GaussianBeam {
         let power = 2.0 * std::f64::consts::PI * std.powi(2) * peak_intensity;
         GaussianBeam {
             intersection,
-            direction,
+            direction: direction.normalize(),
             power,
             e_radius,
             rayleigh_range: f64::INFINITY,
             ellipticity: 0.0,
+            focus_offset: 0.0,
         }
     }
 }
@@ -106,11 +131,12 @@ impl GaussianBeam {
         let power = 2.0 * std::f64::consts::PI * std.powi(2) * peak_intensity;
         GaussianBeam {
             intersection,
-            direction,
+            direction: direction.normalize(),
             power,
             e_radius,
             rayleigh_range: calculate_rayleigh_range(&wavelength, &e_radius),
             ellipticity: 0.0,
+            focus_offset: 0.0,
         }
     }
     /// Create a GaussianBeam component by specifying the peak intensity, rather than power.
@@ -143,13 +169,15 @@ impl GaussianBeam {
             e_radius,
             rayleigh_range: calculate_rayleigh_range(&wavelength, &e_radius),
             ellipticity: ellipiticity,
+            focus_offset: 0.0,
         }
     }
 }
 
 /// A component that covers the central portion of a laser beam.
 ///
-/// The mask is assumed to be coaxial to the GaussianBeam.
+/// The mask is assumed to be coaxial to the GaussianBeam. The covered centre forms the dark spot of the repump beam
+/// in a dark-SPOT MOT. Combine with a [CircularAperture] for an annular beam.
 #[derive(Clone, Copy)]
 pub struct CircularMask {
     /// Radius of the masked region in units of m.
@@ -159,6 +187,103 @@ impl Component for CircularMask {
     type Storage = HashMapStorage<Self>;
 }
 
+/// A component that blocks a laser beam outside a radius, the complement of a [CircularMask].
+///
+/// The aperture acts as an iris coaxial to the beam: the intensity is zero further than `radius` from the beam axis.
+/// The dark centre of a dark-SPOT repump beam is made with a [CircularMask]; a beam with both and a mask of a
+/// smaller radius is annular, with a dark centre and a dark exterior.
+#[derive(Deserialize, Serialize, Clone, Copy)]
+pub struct CircularAperture {
+    /// Radius of the transmitted region in units of m.
+    pub radius: f64,
+}
+impl Component for CircularAperture {
+    type Storage = HashMapStorage<Self>;
+}
+impl CircularAperture {
+    /// Returns true if the position is within the transmitted region of a beam through `intersection` along
+    /// `direction`.
+    pub fn transmits(&self, intersection: &Vector3<f64>, direction: &Vector3<f64>, pos: &Position) -> bool {
+        let (distance, _) =
+            maths::get_minimum_distance_line_point(&pos.pos.cast(), intersection, direction);
+        distance <= self.radius
+    }
+}
+
+/// A component that limits the distance from the axis of a laser beam at which it interacts with atoms.
+///
+/// The intensity of the beam is treated as zero for atoms further from the beam axis than `radius` times the
+/// local `1/e^2` radius of the beam, so that the intensity need not be calculated. The intensity at the cutoff is
+/// a fraction `exp(-2 radius^2)` of the on-axis intensity, so the cutoff should be large enough that the neglected
+/// intensity is insignificant, for example `radius = 3` neglects intensities below `1.5e-8` of the on-axis value.
+///
+/// Beams without this component have no cutoff.
+#[derive(Deserialize, Serialize, Clone, Copy)]
+pub struct InteractionCutoff {
+    /// Cutoff distance from the beam axis, in units of the local `1/e^2` radius of the beam.
+    pub radius: f64,
+}
+impl Component for InteractionCutoff {
+    type Storage = HashMapStorage<Self>;
+}
+impl InteractionCutoff {
+    /// Returns true if the position is within the cutoff distance of the beam axis.
+    pub fn is_within(&self, beam: &GaussianBeam, pos: &Position) -> bool {
+        let (distance, z) = maths::get_minimum_distance_line_point(
+            &pos.pos.cast(),
+            &beam.intersection,
+            &beam.direction,
+        );
+        let z = z - beam.focus_offset;
+        let beam_radius =
+            2.0_f64.sqrt() * beam.e_radius * (1.0 + (z / beam.rayleigh_range).powf(2.0)).powf(0.5);
+        distance <= self.radius * beam_radius
+    }
+}
+
+/// A component that corrects the intensity of a tightly focused [GaussianBeam] for the breakdown of the paraxial
+/// approximation.
+///
+/// The paraxial Gaussian beam neglects the longitudinal component of the electric field, which is of first order in
+/// the parameter `s = 1 / (k w0)`, where `w0` is the `1/e^2` radius of the waist. The correction adds the intensity
+/// of the longitudinal field, `|E_z|^2 = rho_p^2 / (z^2 + z_R^2) |E|^2`, where `rho_p` is the distance from the axis
+/// along the polarization, averaged over the polarization so that `rho_p^2 = rho^2 / 2`. The intensity is then
+/// normalized so that the power of the beam is unchanged, which divides it by `1 + s^2`.
+///
+/// The correction moves intensity from the axis to the wings of the beam: it is lower than the paraxial intensity
+/// within the `e_radius` of the axis, and higher outside. The peak intensity is reduced by the factor
+/// `1 / (1 + s^2)`. The correction is of order `s^2`, so it is negligible for weakly focused beams, and is accurate
+/// for a numerical aperture up to about 0.5. The Gouy phase does not change the intensity of a single beam, and
+/// affects only the interference between beams, which is not modelled.
+///
+/// Beams without this component have the paraxial intensity. The gradient of a corrected beam is calculated
+/// numerically.
+#[derive(Deserialize, Serialize, Clone, Copy, Default)]
+pub struct NonParaxialCorrection;
+impl Component for NonParaxialCorrection {
+    type Storage = HashMapStorage<Self>;
+}
+impl NonParaxialCorrection {
+    /// The square of the parameter `s = 1 / (k w0)` of the beam, which is `e_radius^2 / (2 rayleigh_range^2)`.
+    pub fn parameter_squared(beam: &GaussianBeam) -> f64 {
+        beam.e_radius.powi(2) / (2.0 * beam.rayleigh_range.powi(2))
+    }
+
+    /// The factor by which the paraxial intensity of the beam is multiplied at the position.
+    ///
+    /// Ellipticity is ignored, so the correction of an elliptical beam is that of a round beam.
+    pub fn factor(beam: &GaussianBeam, pos: &Position) -> f64 {
+        let (distance, z) = maths::get_minimum_distance_line_point(
+            &pos.pos.cast(),
+            &beam.intersection,
+            &beam.direction,
+        );
+        let z = z - beam.focus_offset;
+        (1.0 + distance.powi(2) / (2.0 * (z.powi(2) + beam.rayleigh_range.powi(2))))
+            / (1.0 + Self::parameter_squared(beam))
+    }
+}
+
 /// Returns the intensity of a gaussian laser beam at the specified position.
 pub fn get_gaussian_beam_intensity(
     beam: &GaussianBeam,
@@ -170,7 +295,7 @@ pub fn get_gaussian_beam_intensity(
         // checking if frame is given (for calculating ellipticity)
         Some(frame) => {
             let (x, y, z) = maths::get_relative_coordinates_line_point(
-                &pos.pos,
+                &pos.pos.cast(),
                 &beam.intersection,
                 &beam.direction,
                 frame,
@@ -186,13 +311,14 @@ pub fn get_gaussian_beam_intensity(
         // ellipticity will be ignored (i.e. treated as zero) if no `Frame` is supplied.
         None => {
             let (distance, z) = maths::get_minimum_distance_line_point(
-                &pos.pos,
+                &pos.pos.cast(),
                 &beam.intersection,
                 &beam.direction,
             );
             (z, distance * distance)
         }
     };
+    let z = z - beam.focus_offset;
     let power = match mask {
         Some(mask) => {
             if distance_squared.powf(0.5) < mask.radius {
@@ -221,14 +347,14 @@ pub fn get_gaussian_beam_intensity_gradient(
     pos: &Position,
     reference_frame: &Frame,
 ) -> Vector3<f64> {
-    let rela_coord = pos.pos - beam.intersection;
+    let rela_coord = pos.pos.cast::<f64>() - beam.intersection;
 
     // ellipticity treatment
     let semi_major_axis = 1.0 / (1.0 - beam.ellipticity.powf(2.0)).powf(0.5);
 
     let x = rela_coord.dot(&reference_frame.x_vector) / semi_major_axis.powf(0.5);
     let y = rela_coord.dot(&reference_frame.y_vector) * semi_major_axis.powf(0.5);
-    let z = rela_coord.dot(&beam.direction);
+    let z = rela_coord.dot(&beam.direction) - beam.focus_offset;
 
     let spot_size_squared =
         2.0 * beam.e_radius.powf(2.0) * (1. + (z / beam.rayleigh_range).powf(2.0));
@@ -241,6 +367,71 @@ pub fn get_gaussian_beam_intensity_gradient(
     intensity / spot_size_squared * vector
 }
 
+impl BeamSource for GaussianBeam {
+    fn intensity(&self, pos: &Position, modifiers: &BeamModifiers) -> f64 {
+        debug_assert!(
+            self.has_unit_direction(),
+            "GaussianBeam direction must be a unit vector, see GaussianBeam::normalized."
+        );
+        if let Some(aperture) = modifiers.aperture {
+            if !aperture.transmits(&self.intersection, &self.direction, pos) {
+                return 0.0;
+            }
+        }
+        match modifiers.cutoff {
+            Some(cutoff) if !cutoff.is_within(self, pos) => 0.0,
+            _ => {
+                let intensity =
+                    get_gaussian_beam_intensity(self, pos, modifiers.mask, modifiers.frame);
+                match modifiers.non_paraxial {
+                    Some(_) => intensity * NonParaxialCorrection::factor(self, pos),
+                    None => intensity,
+                }
+            }
+        }
+    }
+
+    /// The analytic gradient of the intensity. Beams without a [Frame], or with a [NonParaxialCorrection], are
+    /// differentiated numerically.
+    fn gradient(&self, pos: &Position, modifiers: &BeamModifiers) -> Vector3<f64> {
+        debug_assert!(
+            self.has_unit_direction(),
+            "GaussianBeam direction must be a unit vector, see GaussianBeam::normalized."
+        );
+        match (modifiers.frame, modifiers.non_paraxial) {
+            (Some(frame), None) => get_gaussian_beam_intensity_gradient(self, pos, frame),
+            (frame, non_paraxial) => {
+                let modifiers = BeamModifiers {
+                    frame,
+                    non_paraxial,
+                    ..Default::default()
+                };
+                get_numerical_intensity_gradient(
+                    |p| self.intensity(p, &modifiers),
+                    &pos.pos.cast(),
+                    self.gradient_step(),
+                )
+            }
+        }
+    }
+
+    fn gradient_step(&self) -> f64 {
+        numerical_gradient_step(self)
+    }
+
+    /// The focal plane, out to six `e_radius` from the axis along the semi-major axis of an elliptical beam.
+    fn transverse_plane(&self) -> Option<TransversePlane> {
+        let semi_major_axis = 1.0 / (1.0 - self.ellipticity.powf(2.0)).powf(0.5);
+        Some(TransversePlane {
+            power: self.power,
+            centre: self.intersection + self.focus_offset * self.direction,
+            normal: self.direction,
+            half_width: 6.0 * self.e_radius * semi_major_axis,
+            spacing: 0.05 * self.e_radius,
+        })
+    }
+}
+
 #[cfg(test)]
 pub mod tests {
 
@@ -262,6 +453,7 @@ pub mod tests {
             power: 100.0,
             rayleigh_range: calculate_rayleigh_range(&1064.0e-9, &70.71067812e-6),
             ellipticity: 0.0,
+            focus_offset: 0.0,
         };
         let pos1 = Position {
             pos: Vector3::new(10.0e-6, 0.0, 30.0e-6),
@@ -277,6 +469,48 @@ pub mod tests {
         assert_approx_eq!(gradient[2], -2.06143366e+08, 1e+6_f64);
     }
 
+    #[test]
+    fn test_focus_offset_moves_peak_intensity() {
+        let e_radius = 10.0e-6;
+        let rayleigh_range = calculate_rayleigh_range(&1064.0e-9, &e_radius);
+        let beam = GaussianBeam {
+            direction: Vector3::z(),
+            intersection: Vector3::new(0.0, 0.0, 0.0),
+            e_radius,
+            power: 1.0,
+            rayleigh_range,
+            ellipticity: 0.0,
+            focus_offset: 2.0 * rayleigh_range,
+        };
+        let peak_intensity = beam.power / (PI * e_radius.powi(2));
+
+        let focus = Position {
+            pos: (Vector3::z() * beam.focus_offset).cast(),
+        };
+        let intersection = Position {
+            pos: beam.intersection.cast(),
+        };
+        assert_approx_eq!(
+            get_gaussian_beam_intensity(&beam, &focus, None, None),
+            peak_intensity,
+            peak_intensity * 1e-9
+        );
+        assert_approx_eq!(
+            get_gaussian_beam_intensity(&beam, &intersection, None, None),
+            peak_intensity / 5.0,
+            peak_intensity * 1e-9
+        );
+
+        let frame = Frame {
+            x_vector: Vector3::x(),
+            y_vector: Vector3::y(),
+        };
+        let gradient = get_gaussian_beam_intensity_gradient(&beam, &focus, &frame);
+        assert_approx_eq!(gradient.norm(), 0.0, 1e-6);
+        let gradient = get_gaussian_beam_intensity_gradient(&beam, &intersection, &frame);
+        assert!(gradient[2] > 0.0);
+    }
+
     #[test]
     fn test_get_gaussian_beam_intensity() {
         let beam = GaussianBeam {
@@ -286,6 +520,7 @@ pub mod tests {
             power: 1.0,
             rayleigh_range: calculate_rayleigh_range(&1064.0e-9, &2.0),
             ellipticity: 0.0,
+            focus_offset: 0.0,
         };
 
         let pos1 = Position { pos: Vector3::x() };
@@ -300,7 +535,7 @@ pub mod tests {
         let pos2 = Position { pos: Vector3::y() };
         assert_approx_eq!(
             1.0 / (PI.powf(0.5) * beam.e_radius).powf(2.0)
-                * (-pos2.pos[1] / beam.e_radius.powf(2.0)).exp(),
+                * (-pos2.pos.cast::<f64>()[1] / beam.e_radius.powf(2.0)).exp(),
             get_gaussian_beam_intensity(&beam, &pos2, None, None),
             1e-6_f64
         );
@@ -315,14 +550,14 @@ pub mod tests {
 
         assert_approx_eq!(
             1.0 / (PI.powf(0.5) * beam.e_radius).powf(2.0)
-                * (-pos2.pos[1] / beam.e_radius.powf(2.0)).exp(),
+                * (-pos2.pos.cast::<f64>()[1] / beam.e_radius.powf(2.0)).exp(),
             get_gaussian_beam_intensity(&beam, &pos2, None, None),
             1e-6_f64
         );
         let rayleigh_range_2 = calculate_rayleigh_range(&1064.0e-6, &beam.e_radius);
 
         let pos3 = Position {
-            pos: Vector3::x() * rayleigh_range_2,
+            pos: (Vector3::x() * rayleigh_range_2).cast(),
         };
 
         // Test with a frame but ellipticity = 0
@@ -344,6 +579,7 @@ pub mod tests {
             power: 1.0,
             rayleigh_range: calculate_rayleigh_range(&1064.0e-9, &2.0),
             ellipticity: (3.0 / 4.0_f64).powf(0.5),
+            focus_offset: 0.0,
         };
 
         // checking if value on x-axis stays the same (as without ellipticity and frame)
@@ -373,6 +609,7 @@ pub mod tests {
             power: 1.0,
             rayleigh_range: calculate_rayleigh_range(&1064.0e-9, &2.0),
             ellipticity: (15.0 / 16.0_f64).powf(0.5),
+            focus_offset: 0.0,
         };
 
         // but we check along the de-focused axis (so intensity is lower than in symmetrical case)
@@ -391,4 +628,154 @@ pub mod tests {
             1e-6_f64
         );
     }
+
+    #[test]
+    fn test_non_unit_direction_is_normalized() {
+        let intersection = Vector3::new(0.0, 0.0, 0.0);
+        let unit = GaussianBeam::from_power_with_ellipticity_and_rayleigh_range(
+            intersection,
+            Vector3::x(),
+            5.0,
+            50.0e-6,
+            1064.0e-9,
+            0.0,
+        );
+        let long = GaussianBeam::from_power_with_ellipticity_and_rayleigh_range(
+            intersection,
+            2.0 * Vector3::x(),
+            5.0,
+            50.0e-6,
+            1064.0e-9,
+            0.0,
+        );
+        let hand_built = GaussianBeam {
+            direction: 2.0 * Vector3::x(),
+            ..unit
+        };
+        assert!(!hand_built.has_unit_direction());
+        let hand_built = hand_built.normalized();
+
+        let polarizability = crate::dipole::Polarizability::calculate_for(1064e-9, 780e-9, 6.065e6);
+        let frame = Frame::from_direction(Vector3::x(), Vector3::y());
+        let pos = Position {
+            pos: Vector3::new(30.0e-6, 20.0e-6, -10.0e-6).cast(),
+        };
+        let force = |beam: &GaussianBeam| {
+            polarizability.prefactor * get_gaussian_beam_intensity_gradient(beam, &pos, &frame)
+        };
+        for beam in [long, hand_built].iter() {
+            assert!(beam.has_unit_direction());
+            assert_eq!(force(beam), force(&unit));
+        }
+        assert_eq!(
+            GaussianBeam::from_peak_intensity(intersection, 2.0 * Vector3::x(), 1.0, 1.0e-3)
+                .direction,
+            Vector3::x()
+        );
+    }
+
+    #[test]
+    fn test_non_paraxial_correction() {
+        let wavelength = 1064.0e-9;
+        let beam = |e_radius: f64| {
+            GaussianBeam::from_power_with_ellipticity_and_rayleigh_range(
+                Vector3::new(0.0, 0.0, 0.0),
+                Vector3::z(),
+                1.0,
+                e_radius,
+                wavelength,
+                0.0,
+            )
+        };
+        let corrected = BeamModifiers {
+            non_paraxial: Some(&NonParaxialCorrection),
+            ..Default::default()
+        };
+        let intensities = |beam: &GaussianBeam, pos: Vector3<f64>| {
+            let pos = Position { pos: pos.cast() };
+            (
+                beam.intensity(&pos, &BeamModifiers::default()),
+                beam.intensity(&pos, &corrected),
+            )
+        };
+
+        // A weakly focused beam is unchanged.
+        let weak = beam(50.0e-6);
+        assert!(NonParaxialCorrection::parameter_squared(&weak) < 1e-5);
+        for &(rho, z) in [(0.0, 0.0), (50.0e-6, 0.0), (100.0e-6, 1.0e-3)].iter() {
+            let (paraxial, corrected) = intensities(&weak, Vector3::new(rho, 0.0, z));
+            assert_approx_eq!(corrected, paraxial, 1e-4 * paraxial);
+        }
+
+        // A tightly focused beam, with a numerical aperture of about 0.5, loses intensity on the axis to the wings.
+        let tight = beam(0.5e-6);
+        let s2 = NonParaxialCorrection::parameter_squared(&tight);
+        assert_approx_eq!(
+            s2,
+            (wavelength / (2.0 * PI * 2.0_f64.sqrt() * 0.5e-6)).powi(2),
+            1e-12
+        );
+        let (paraxial, corrected) = intensities(&tight, Vector3::new(0.0, 0.0, 0.0));
+        assert_approx_eq!(corrected, paraxial / (1.0 + s2), 1e-9 * paraxial);
+        assert!(corrected < 0.97 * paraxial);
+        let (paraxial, corrected) = intensities(&tight, Vector3::new(0.0, 0.4e-6, 0.0));
+        assert!(corrected < paraxial);
+        let (paraxial, corrected) = intensities(&tight, Vector3::new(0.0, 1.0e-6, 0.0));
+        assert!(corrected > 1.03 * paraxial);
+
+        // The power of the beam is unchanged.
+        let dr = tight.e_radius / 1000.0;
+        let power: f64 = (0..10000)
+            .map(|i| (i as f64 + 0.5) * dr)
+            .map(|rho| 2.0 * PI * rho * dr * intensities(&tight, Vector3::new(rho, 0.0, 0.0)).1)
+            .sum();
+        assert_approx_eq!(power, tight.power, 1e-6 * tight.power);
+    }
+
+    #[test]
+    fn test_power_normalization() {
+        use crate::laser::beam_source::verify_power_normalization;
+
+        // A collimated beam, and a tightly focused beam along an oblique axis with its focus displaced.
+        let collimated = GaussianBeam {
+            intersection: Vector3::new(0.0, 0.0, 0.0),
+            e_radius: 1.0e-3,
+            power: 0.1,
+            direction: Vector3::z(),
+            rayleigh_range: f64::INFINITY,
+            ellipticity: 0.0,
+            focus_offset: 0.0,
+        };
+        let focused = GaussianBeam {
+            focus_offset: 2.0e-3,
+            ..GaussianBeam::from_power_with_ellipticity_and_rayleigh_range(
+                Vector3::new(1.0e-3, -2.0e-3, 0.5e-3),
+                Vector3::new(1.0, 2.0, -0.5),
+                7.0,
+                2.0e-6,
+                1064.0e-9,
+                0.0,
+            )
+        };
+        for beam in [collimated, focused].iter() {
+            assert_approx_eq!(verify_power_normalization(beam, None), 1.0, 0.01);
+        }
+    }
+
+    #[test]
+    fn test_power_normalization_of_elliptical_beam() {
+        use crate::laser::beam_source::verify_power_normalization;
+
+        // a/b = 4, so the profile extends well beyond six `e_radius` along the semi-major axis.
+        let beam = GaussianBeam::from_power_with_ellipticity_and_rayleigh_range(
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 1.0, 0.0),
+            0.1,
+            1.0e-3,
+            1064.0e-9,
+            (15.0 / 16.0_f64).powf(0.5),
+        );
+        let frame = Frame::from_direction(beam.direction, Vector3::new(1.0, -1.0, 0.0));
+        assert_approx_eq!(verify_power_normalization(&beam, Some(&frame)), 1.0, 0.01);
+    }
 }
@@ -6,15 +6,44 @@
 extern crate rayon;
 extern crate serde;
 
+use std::marker::PhantomData;
+
+use super::beam_source::{BeamModifiers, BeamSource};
 use super::frame::Frame;
-use super::gaussian::{get_gaussian_beam_intensity, CircularMask, GaussianBeam};
+use super::gaussian::{
+    CircularAperture, CircularMask, GaussianBeam, InteractionCutoff, NonParaxialCorrection,
+};
 use crate::atom::Position;
+use crate::dipole::DipoleLight;
 use crate::laser::index::LaserIndex;
+use crate::laser_cooling::CoolingLight;
+use crate::parallel::{ForceSerial, MaybeParJoin};
 use serde::Serialize;
 use specs::prelude::*;
 
 const LASER_CACHE_SIZE: usize = 16;
 
+/// A resource that scales the power of all cooling beams and all dipole beams.
+///
+/// The intensities (and intensity gradients) of beams with a [CoolingLight] component are multiplied by
+/// `cooling`, and those of beams with a [DipoleLight] component by `dipole`. The factors may be changed while the
+/// simulation runs, for example by a feedback loop. If the resource is not present, both factors are 1.
+#[derive(Clone, Copy, Debug)]
+pub struct GlobalPowerScale {
+    /// Factor applied to the power of cooling beams.
+    pub cooling: f64,
+    /// Factor applied to the power of dipole beams.
+    pub dipole: f64,
+}
+impl Default for GlobalPowerScale {
+    fn default() -> Self {
+        GlobalPowerScale {
+            cooling: 1.0,
+            dipole: 1.0,
+        }
+    }
+}
+
 /// Represents the laser intensity at the position of the atom with respect to a certain laser beam
 #[derive(Clone, Copy, Serialize)]
 pub struct LaserIntensitySampler {
@@ -49,58 +78,112 @@ impl<const N: usize> Component for LaserIntensitySamplers<N> {
 pub struct InitialiseLaserIntensitySamplersSystem<const N: usize>;
 
 impl<'a, const N: usize> System<'a> for InitialiseLaserIntensitySamplersSystem<N> {
-    type SystemData = (WriteStorage<'a, LaserIntensitySamplers<N>>,);
-    fn run(&mut self, (mut samplers,): Self::SystemData) {
-        use rayon::prelude::*;
-
-        (&mut samplers).par_join().for_each(|mut sampler| {
+    type SystemData = (WriteStorage<'a, LaserIntensitySamplers<N>>, Option<Read<'a, ForceSerial>>);
+    fn run(&mut self, (mut samplers, force_serial): Self::SystemData) {
+        (&mut samplers).maybe_par_for_each(force_serial.is_some(), |mut sampler| {
             sampler.contents = [LaserIntensitySampler::default(); N];
         });
     }
 }
 
-/// System that calculates the intensity of CoolingLight entities, for example those with `GaussianBeam` components.
+/// System that calculates the intensity of laser beams of type `B`, for example [GaussianBeam]s.
 ///
-/// So far, the only intensity distribution implemented as a component for the use
-/// along with `CoolingLight` is `GaussianBeam`.
-/// However, in the future, other components will be implemented and this System can then be expanded
-/// to handle them as well.
-pub struct SampleLaserIntensitySystem<const N: usize>;
+/// The intensities are scaled by the [GlobalPowerScale], if present.
+///
+/// Each beam type implements [BeamSource], and is sampled by its own instance of this system, see
+/// [crate::laser::beam_source].
+///
+/// Beams with an `InteractionCutoff` contribute zero intensity to atoms beyond the cutoff.
+pub struct SampleBeamSourceIntensitySystem<B, const N: usize>(PhantomData<B>)
+where
+    B: BeamSource;
 
-impl<'a, const N: usize> System<'a> for SampleLaserIntensitySystem<N> {
+impl<B, const N: usize> Default for SampleBeamSourceIntensitySystem<B, N>
+where
+    B: BeamSource,
+{
+    fn default() -> Self {
+        SampleBeamSourceIntensitySystem(PhantomData)
+    }
+}
+
+/// Samples the intensity of [GaussianBeam]s.
+pub type SampleLaserIntensitySystem<const N: usize> = SampleBeamSourceIntensitySystem<GaussianBeam, N>;
+
+impl<'a, B, const N: usize> System<'a> for SampleBeamSourceIntensitySystem<B, N>
+where
+    B: BeamSource,
+{
     type SystemData = (
         Entities<'a>,
         ReadStorage<'a, LaserIndex>,
-        ReadStorage<'a, GaussianBeam>,
+        ReadStorage<'a, B>,
         ReadStorage<'a, CircularMask>,
+        ReadStorage<'a, CircularAperture>,
         ReadStorage<'a, Frame>,
+        ReadStorage<'a, InteractionCutoff>,
+        ReadStorage<'a, NonParaxialCorrection>,
+        ReadStorage<'a, CoolingLight>,
+        ReadStorage<'a, DipoleLight>,
         ReadStorage<'a, Position>,
         WriteStorage<'a, LaserIntensitySamplers<N>>,
+        Option<Read<'a, GlobalPowerScale>>,
+        Option<Read<'a, ForceSerial>>,
     );
 
     fn run(
         &mut self,
-        (entities, indices, gaussian, masks, frames, position, mut intensity_samplers): Self::SystemData,
+        (
+            entities,
+            indices,
+            beams,
+            masks,
+            apertures,
+            frames,
+            cutoffs,
+            corrections,
+            cooling_lights,
+            dipole_lights,
+            position,
+            mut intensity_samplers,
+            power_scale,
+            force_serial,
+        ): Self::SystemData,
     ) {
-        use rayon::prelude::*;
+        let power_scale = power_scale.map(|scale| *scale).unwrap_or_default();
 
         // There are typically only a small number of lasers in a simulation.
         // For a speedup, cache the required components into thread memory,
         // so they can be distributed to parallel workers during the atom loop.
-        type CachedLaser = (
+        type CachedLaser<B> = (
             LaserIndex,
-            GaussianBeam,
+            B,
             Option<CircularMask>,
+            Option<CircularAperture>,
             Option<Frame>,
+            Option<InteractionCutoff>,
+            Option<NonParaxialCorrection>,
+            f64,
         );
-        let laser_cache: Vec<CachedLaser> = (&entities, &indices, &gaussian)
+        let laser_cache: Vec<CachedLaser<B>> = (&entities, &indices, &beams)
             .join()
-            .map(|(laser_entity, index, gaussian)| {
+            .map(|(laser_entity, index, beam)| {
+                let mut scale = 1.0;
+                if cooling_lights.contains(laser_entity) {
+                    scale *= power_scale.cooling;
+                }
+                if dipole_lights.contains(laser_entity) {
+                    scale *= power_scale.dipole;
+                }
                 (
                     *index,
-                    *gaussian,
+                    beam.clone(),
                     masks.get(laser_entity).cloned(),
+                    apertures.get(laser_entity).cloned(),
                     frames.get(laser_entity).cloned(),
+                    cutoffs.get(laser_entity).cloned(),
+                    corrections.get(laser_entity).cloned(),
+                    scale,
                 )
             })
             .collect();
@@ -109,28 +192,69 @@ impl<'a, const N: usize> System<'a> for SampleLaserIntensitySystem<N> {
         for base_index in (0..laser_cache.len()).step_by(LASER_CACHE_SIZE) {
             let max_index = laser_cache.len().min(base_index + LASER_CACHE_SIZE);
             let slice = &laser_cache[base_index..max_index];
-            let mut laser_array = vec![laser_cache[0]; LASER_CACHE_SIZE];
-            laser_array[..max_index].copy_from_slice(slice);
-            let number_in_iteration = slice.len();
 
             (&mut intensity_samplers, &position)
-                .par_join()
-                .for_each(|(samplers, pos)| {
-                    for (index, gaussian, mask, frame) in
-                        laser_array.iter().take(number_in_iteration)
+                .maybe_par_for_each(force_serial.is_some(), |(samplers, pos)| {
+                    for (index, beam, mask, aperture, frame, cutoff, correction, scale) in
+                        slice.iter()
                     {
-                        samplers.contents[index.index].intensity = get_gaussian_beam_intensity(
-                            gaussian,
-                            pos,
-                            mask.as_ref(),
-                            frame.as_ref(),
-                        );
+                        let modifiers = BeamModifiers {
+                            mask: mask.as_ref(),
+                            aperture: aperture.as_ref(),
+                            frame: frame.as_ref(),
+                            cutoff: cutoff.as_ref(),
+                            non_paraxial: correction.as_ref(),
+                        };
+                        samplers.contents[index.index].intensity =
+                            scale * beam.intensity(pos, &modifiers);
                     }
                 });
         }
     }
 }
 
+/// The total intensity at the position of the atom, summed over all laser beams.
+#[derive(Clone, Copy, Serialize)]
+pub struct TotalIntensity {
+    /// Intensity in SI units of W/m^2
+    pub value: f64,
+}
+
+impl Default for TotalIntensity {
+    fn default() -> Self {
+        TotalIntensity { value: f64::NAN }
+    }
+}
+
+impl Component for TotalIntensity {
+    type Storage = VecStorage<Self>;
+}
+
+/// This system calculates the `TotalIntensity` of each atom from its `LaserIntensitySamplers`.
+///
+/// Only the samplers of indexed laser beams are summed, the remaining entries of the samplers are unused.
+pub struct CalculateTotalIntensitySystem<const N: usize>;
+
+impl<'a, const N: usize> System<'a> for CalculateTotalIntensitySystem<N> {
+    type SystemData = (
+        ReadStorage<'a, LaserIndex>,
+        ReadStorage<'a, LaserIntensitySamplers<N>>,
+        WriteStorage<'a, TotalIntensity>,
+        Option<Read<'a, ForceSerial>>,
+    );
+
+    fn run(&mut self, (indices, samplers, mut totals, force_serial): Self::SystemData) {
+        let active: Vec<usize> = indices.join().map(|index| index.index).collect();
+
+        (&samplers, &mut totals).maybe_par_for_each(force_serial.is_some(), |(samplers, total)| {
+            total.value = active
+                .iter()
+                .map(|index| samplers.contents[*index].intensity)
+                .sum();
+        });
+    }
+}
+
 #[cfg(test)]
 pub mod tests {
 
@@ -140,6 +264,16 @@ pub mod tests {
     extern crate nalgebra;
     use crate::laser::gaussian;
     use nalgebra::Vector3;
+    use crate::atom::{Atom, Force, Mass, Velocity};
+    use crate::dipole::{DipolePlugin, Polarizability};
+    use crate::initiate::NewlyCreated;
+    use crate::integrator::{Pinned, Timestep};
+    use crate::laser::LaserPlugin;
+    use crate::laser_cooling::force::EmissionForceOption;
+    use crate::laser_cooling::photons_scattered::ScatteringFluctuationsOption;
+    use crate::laser_cooling::LaserCoolingPlugin;
+    use crate::simulation::SimulationBuilder;
+    use crate::species::Rubidium87_780D2;
 
     /// Tests the correct implementation of the `SampleLaserIntensitySystem`
     #[test]
@@ -150,6 +284,11 @@ pub mod tests {
         test_world.register::<GaussianBeam>();
         test_world.register::<CircularMask>();
         test_world.register::<Frame>();
+        test_world.register::<CircularAperture>();
+        test_world.register::<InteractionCutoff>();
+        test_world.register::<NonParaxialCorrection>();
+        test_world.register::<CoolingLight>();
+        test_world.register::<DipoleLight>();
         test_world.register::<Position>();
         test_world.register::<LaserIntensitySamplers<{ DEFAULT_BEAM_LIMIT }>>();
 
@@ -166,6 +305,7 @@ pub mod tests {
                 power: 1.0,
                 rayleigh_range: gaussian::calculate_rayleigh_range(&461.0e-9, &2.0),
                 ellipticity: 0.0,
+                focus_offset: 0.0,
             })
             .build();
 
@@ -177,7 +317,7 @@ pub mod tests {
             })
             .build();
 
-        let mut system = SampleLaserIntensitySystem::<{ DEFAULT_BEAM_LIMIT }>;
+        let mut system = SampleLaserIntensitySystem::<{ DEFAULT_BEAM_LIMIT }>::default();
         system.run_now(&test_world);
         test_world.maintain();
         let sampler_storage =
@@ -191,6 +331,7 @@ pub mod tests {
                 power: 1.0,
                 rayleigh_range: gaussian::calculate_rayleigh_range(&461.0e-9, &2.0),
                 ellipticity: 0.0,
+                focus_offset: 0.0,
             },
             &Position { pos: Vector3::y() },
             None,
@@ -207,4 +348,333 @@ pub mod tests {
             1e-6_f64
         );
     }
+
+    #[test]
+    fn test_interaction_cutoff() {
+        let mut test_world = World::new();
+
+        test_world.register::<LaserIndex>();
+        test_world.register::<GaussianBeam>();
+        test_world.register::<CircularMask>();
+        test_world.register::<Frame>();
+        test_world.register::<CircularAperture>();
+        test_world.register::<InteractionCutoff>();
+        test_world.register::<NonParaxialCorrection>();
+        test_world.register::<CoolingLight>();
+        test_world.register::<DipoleLight>();
+        test_world.register::<Position>();
+        test_world.register::<LaserIntensitySamplers<{ DEFAULT_BEAM_LIMIT }>>();
+
+        let e_radius = 1.0e-3;
+        let beam = GaussianBeam {
+            direction: Vector3::new(1.0, 0.0, 0.0),
+            intersection: Vector3::new(0.0, 0.0, 0.0),
+            e_radius,
+            power: 1.0,
+            rayleigh_range: gaussian::calculate_rayleigh_range(&461.0e-9, &e_radius),
+            ellipticity: 0.0,
+            focus_offset: 0.0,
+        };
+        test_world
+            .create_entity()
+            .with(LaserIndex {
+                index: 0,
+                initiated: true,
+            })
+            .with(beam)
+            .with(InteractionCutoff { radius: 3.0 })
+            .build();
+
+        // The 1/e^2 radius of the beam is sqrt(2) * e_radius.
+        let inside = Position {
+            pos: Vector3::new(0.1, 2.0 * e_radius, 0.0).cast(),
+        };
+        let outside = Position {
+            pos: Vector3::new(0.1, 5.0 * e_radius, 0.0).cast(),
+        };
+        let create_atom = |world: &mut World, pos: &Position| {
+            world
+                .create_entity()
+                .with(pos.clone())
+                .with(LaserIntensitySamplers {
+                    contents: [LaserIntensitySampler::default(); DEFAULT_BEAM_LIMIT],
+                })
+                .build()
+        };
+        let atom_inside = create_atom(&mut test_world, &inside);
+        let atom_outside = create_atom(&mut test_world, &outside);
+
+        SampleLaserIntensitySystem::<{ DEFAULT_BEAM_LIMIT }>::default().run_now(&test_world);
+        test_world.maintain();
+        let sampler_storage =
+            test_world.read_storage::<LaserIntensitySamplers<{ DEFAULT_BEAM_LIMIT }>>();
+
+        assert_eq!(
+            sampler_storage.get(atom_inside).unwrap().contents[0].intensity,
+            gaussian::get_gaussian_beam_intensity(&beam, &inside, None, None)
+        );
+        assert!(gaussian::get_gaussian_beam_intensity(&beam, &outside, None, None) > 0.0);
+        assert_eq!(
+            sampler_storage.get(atom_outside).unwrap().contents[0].intensity,
+            0.0
+        );
+    }
+
+    #[test]
+    fn test_annular_beam_is_dark_inside_and_outside() {
+        let mut test_world = World::new();
+
+        test_world.register::<LaserIndex>();
+        test_world.register::<GaussianBeam>();
+        test_world.register::<CircularMask>();
+        test_world.register::<CircularAperture>();
+        test_world.register::<Frame>();
+        test_world.register::<InteractionCutoff>();
+        test_world.register::<NonParaxialCorrection>();
+        test_world.register::<CoolingLight>();
+        test_world.register::<DipoleLight>();
+        test_world.register::<Position>();
+        test_world.register::<LaserIntensitySamplers<{ DEFAULT_BEAM_LIMIT }>>();
+
+        let e_radius = 1.0e-2;
+        let beam = GaussianBeam {
+            direction: Vector3::new(1.0, 0.0, 0.0),
+            intersection: Vector3::new(0.0, 0.0, 0.0),
+            e_radius,
+            power: 1.0,
+            rayleigh_range: f64::INFINITY,
+            ellipticity: 0.0,
+            focus_offset: 0.0,
+        };
+        let dark_spot = 2.0e-3;
+        let iris = 5.0e-3;
+        let mut create_beam = |index: usize, aperture: Option<CircularAperture>| {
+            let mut builder = test_world
+                .create_entity()
+                .with(LaserIndex {
+                    index,
+                    initiated: true,
+                })
+                .with(beam)
+                .with(CircularMask { radius: dark_spot });
+            if let Some(aperture) = aperture {
+                builder = builder.with(aperture);
+            }
+            builder.build();
+        };
+        // A dark-spot beam, and the same beam through an iris.
+        create_beam(0, None);
+        create_beam(1, Some(CircularAperture { radius: iris }));
+
+        let radii = [0.0, 1.0e-3, 3.0e-3, 4.5e-3, 6.0e-3, 2.0e-2];
+        let atoms: Vec<Entity> = radii
+            .iter()
+            .map(|r| {
+                test_world
+                    .create_entity()
+                    .with(Position {
+                        pos: Vector3::new(0.1, 0.0, *r).cast(),
+                    })
+                    .with(LaserIntensitySamplers {
+                        contents: [LaserIntensitySampler::default(); DEFAULT_BEAM_LIMIT],
+                    })
+                    .build()
+            })
+            .collect();
+
+        SampleLaserIntensitySystem::<{ DEFAULT_BEAM_LIMIT }>::default().run_now(&test_world);
+        let sampler_storage =
+            test_world.read_storage::<LaserIntensitySamplers<{ DEFAULT_BEAM_LIMIT }>>();
+        for (atom, r) in atoms.iter().zip(radii.iter()) {
+            let samplers = sampler_storage.get(*atom).unwrap();
+            let unmasked = gaussian::get_gaussian_beam_intensity(
+                &beam,
+                &Position {
+                    pos: Vector3::new(0.1, 0.0, *r).cast(),
+                },
+                None,
+                None,
+            );
+            let dark_spot_intensity = if *r < dark_spot { 0.0 } else { unmasked };
+            let annular_intensity = if *r < dark_spot || *r > iris { 0.0 } else { unmasked };
+            assert!(unmasked > 0.0);
+            assert_eq!(samplers.contents[0].intensity, dark_spot_intensity);
+            assert_eq!(samplers.contents[1].intensity, annular_intensity);
+        }
+    }
+
+    #[test]
+    fn test_total_intensity_sums_active_beams() {
+        let mut test_world = World::new();
+
+        test_world.register::<LaserIndex>();
+        test_world.register::<GaussianBeam>();
+        test_world.register::<CircularMask>();
+        test_world.register::<Frame>();
+        test_world.register::<CircularAperture>();
+        test_world.register::<InteractionCutoff>();
+        test_world.register::<NonParaxialCorrection>();
+        test_world.register::<CoolingLight>();
+        test_world.register::<DipoleLight>();
+        test_world.register::<Position>();
+        test_world.register::<LaserIntensitySamplers<{ DEFAULT_BEAM_LIMIT }>>();
+        test_world.register::<TotalIntensity>();
+
+        for (index, direction) in [(0, Vector3::x()), (3, Vector3::z())].iter() {
+            test_world
+                .create_entity()
+                .with(LaserIndex {
+                    index: *index,
+                    initiated: true,
+                })
+                .with(GaussianBeam {
+                    direction: *direction,
+                    intersection: Vector3::new(0.0, 0.0, 0.0),
+                    e_radius: 2.0,
+                    power: 1.0 + *index as f64,
+                    rayleigh_range: gaussian::calculate_rayleigh_range(&461.0e-9, &2.0),
+                    ellipticity: 0.0,
+                    focus_offset: 0.0,
+                })
+                .build();
+        }
+
+        let atom1 = test_world
+            .create_entity()
+            .with(Position { pos: Vector3::y() })
+            .with(LaserIntensitySamplers {
+                contents: [LaserIntensitySampler::default(); crate::laser::DEFAULT_BEAM_LIMIT],
+            })
+            .with(TotalIntensity::default())
+            .build();
+
+        SampleLaserIntensitySystem::<{ DEFAULT_BEAM_LIMIT }>::default().run_now(&test_world);
+        CalculateTotalIntensitySystem::<{ DEFAULT_BEAM_LIMIT }>.run_now(&test_world);
+        test_world.maintain();
+
+        let samplers = test_world.read_storage::<LaserIntensitySamplers<{ DEFAULT_BEAM_LIMIT }>>();
+        let sampler = samplers.get(atom1).expect("entity not found");
+        let total = test_world
+            .read_storage::<TotalIntensity>()
+            .get(atom1)
+            .expect("entity not found")
+            .value;
+        assert!(sampler.contents[1].intensity.is_nan());
+        assert_approx_eq!(
+            total,
+            sampler.contents[0].intensity + sampler.contents[3].intensity,
+            1e-12_f64
+        );
+    }
+
+    /// Runs a simulation with a cooling beam and a dipole beam, and returns the force on a pinned atom and its
+    /// sampled `(cooling, dipole)` intensities.
+    fn force_with_power_scale(scale: Option<GlobalPowerScale>) -> (Vector3<f64>, (f64, f64)) {
+        let mut sim_builder = SimulationBuilder::default();
+        sim_builder.add_plugin(LaserPlugin::<2>);
+        sim_builder.add_plugin(LaserCoolingPlugin::<Rubidium87_780D2, 2>::default());
+        sim_builder.add_plugin(DipolePlugin::<2>);
+        let mut sim = sim_builder.build();
+        sim.world.insert(Timestep { delta: 1.0e-6 });
+        sim.world.insert(EmissionForceOption::Off);
+        sim.world.insert(ScatteringFluctuationsOption::Off);
+        if let Some(scale) = scale {
+            sim.world.insert(scale);
+        }
+
+        let cooling = sim
+            .world
+            .create_entity()
+            .with(GaussianBeam {
+                direction: Vector3::x(),
+                intersection: Vector3::new(0.0, 0.0, 0.0),
+                e_radius: 1.0e-2,
+                power: 0.01,
+                rayleigh_range: f64::INFINITY,
+                ellipticity: 0.0,
+                focus_offset: 0.0,
+            })
+            .with(CoolingLight::for_transition::<Rubidium87_780D2>(-6.0, 1))
+            .build();
+        let dipole = sim
+            .world
+            .create_entity()
+            .with(GaussianBeam {
+                direction: Vector3::z(),
+                intersection: Vector3::new(0.0, 0.0, 0.0),
+                e_radius: 50.0e-6,
+                power: 1.0,
+                rayleigh_range: f64::INFINITY,
+                ellipticity: 0.0,
+                focus_offset: 0.0,
+            })
+            .with(DipoleLight {
+                wavelength: 1064.0e-9,
+            })
+            .build();
+        let atom = sim
+            .world
+            .create_entity()
+            .with(Position {
+                pos: Vector3::new(0.0, 30.0e-6, 0.0).cast(),
+            })
+            .with(Velocity {
+                vel: Vector3::new(0.0, 0.0, 0.0).cast(),
+            })
+            .with(Force::new())
+            .with(Mass { value: 87.0 })
+            .with(Polarizability::calculate_for(1064.0e-9, 780.0e-9, 6.0e6))
+            .with(Rubidium87_780D2)
+            .with(Atom)
+            .with(Pinned)
+            .with(NewlyCreated)
+            .build();
+        sim.step();
+        sim.step();
+
+        let force = sim
+            .world
+            .read_storage::<Force>()
+            .get(atom)
+            .expect("entity not found")
+            .force
+            .cast::<f64>();
+        let indices = sim.world.read_storage::<LaserIndex>();
+        let samplers = sim.world.read_storage::<LaserIntensitySamplers<2>>();
+        let sampler = samplers.get(atom).expect("entity not found");
+        let intensity = |beam: Entity| sampler.contents[indices.get(beam).unwrap().index].intensity;
+        (force, (intensity(cooling), intensity(dipole)))
+    }
+
+    #[test]
+    fn test_global_power_scale() {
+        let (force, (cooling, dipole)) = force_with_power_scale(None);
+        assert!(force[0] > 0.0, "the cooling beam pushes the atom along x");
+        assert!(force[1] < 0.0, "the dipole beam pulls the atom towards its axis");
+
+        // The default scale preserves the behaviour.
+        let (default_force, _) = force_with_power_scale(Some(GlobalPowerScale::default()));
+        assert_eq!(default_force, force);
+
+        // Turning off the cooling beams leaves the dipole force unchanged.
+        let (scaled_force, (scaled_cooling, scaled_dipole)) =
+            force_with_power_scale(Some(GlobalPowerScale {
+                cooling: 0.0,
+                dipole: 1.0,
+            }));
+        assert_eq!(scaled_cooling, 0.0);
+        assert_eq!(scaled_dipole, dipole);
+        assert_approx_eq!(scaled_force[0], 0.0, 1e-9 * force[1].abs());
+        assert_approx_eq!(scaled_force[1], force[1], 1e-9 * force[1].abs());
+
+        // The dipole force scales with the dipole power.
+        let (scaled_force, (scaled_cooling, scaled_dipole)) =
+            force_with_power_scale(Some(GlobalPowerScale {
+                cooling: 1.0,
+                dipole: 0.5,
+            }));
+        assert_eq!(scaled_cooling, cooling);
+        assert_approx_eq!(scaled_dipole, 0.5 * dipole, 1e-9 * dipole);
+        assert_approx_eq!(scaled_force[1], 0.5 * force[1], 1e-9 * force[1].abs());
+    }
 }
@@ -1,16 +1,23 @@
 //! A module to calculate laser beam intensity gradients.
 //!
-//! Gradients are currently only calculated for beams marked as [DipoleLight](DipoleLight.struct.html).
+//! Gradients are only calculated for beams marked as [DipoleLight](DipoleLight.struct.html), for each beam type
+//! implementing [BeamSource].
+
+use std::marker::PhantomData;
 
 use specs::prelude::*;
 
 use crate::atom::Position;
 use crate::dipole::DipoleLight;
+use crate::laser::beam_source::{BeamModifiers, BeamSource};
 use crate::laser::frame::Frame;
-use crate::laser::gaussian::{get_gaussian_beam_intensity_gradient, GaussianBeam};
+use crate::laser::gaussian::{GaussianBeam, NonParaxialCorrection};
 use crate::laser::index::LaserIndex;
+use crate::laser::intensity::GlobalPowerScale;
+use crate::parallel::{ForceSerial, MaybeParJoin};
 use nalgebra::Vector3;
-use specs::{Component, Join, ReadStorage, System, VecStorage, WriteStorage};
+use serde::{Deserialize, Serialize};
+use specs::{Component, HashMapStorage, Join, ReadStorage, System, VecStorage, WriteStorage};
 
 /// Represents the laser intensity at the position of the atom with respect to a certain laser beam
 #[derive(Clone, Copy)]
@@ -38,38 +45,163 @@ impl<const N: usize> Component for LaserIntensityGradientSamplers<N> {
     type Storage = VecStorage<Self>;
 }
 
-/// Calculates the intensity gradient of each laser beam. The result is stored in the `LaserIntensityGradientSamplers` .
+/// The method used to calculate the intensity gradient of a laser beam.
+///
+/// Beams without this component use the analytic gradient.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum GradientMethod {
+    /// The gradient is calculated from the analytic expression for the beam profile.
+    #[default]
+    Analytic,
+    /// The gradient is calculated by numerical differentiation of the beam intensity, see [get_numerical_intensity_gradient].
+    ///
+    /// This allows beam profiles without an analytic gradient to be used.
+    Numerical,
+}
+impl Component for GradientMethod {
+    type Storage = HashMapStorage<Self>;
+}
+
+/// Calculates the gradient of an intensity profile by central differences, evaluating the
+/// intensity at `±delta` along each axis.
 ///
-/// So far, the only intensity distribution implemented is `GaussianBeam`. Additionally
-/// the system also uses `GaussianRayleighRange` for axial divergence and
-/// `Frame` to account for different ellipiticies in the future.
-/// The result is stored in the `LaserIntensityGradientSamplers` component that each
-/// atom is associated with.
-pub struct SampleGaussianLaserIntensityGradientSystem<const N: usize>;
-
-impl<'a, const N: usize> System<'a> for SampleGaussianLaserIntensityGradientSystem<N> {
+/// # Arguments
+///
+/// `intensity`: function returning the intensity at a position, in SI units of W/m^2.
+///
+/// `pos`: position at which to calculate the gradient, in SI units of m.
+///
+/// `delta`: step size, in SI units of m. See [numerical_gradient_step].
+pub fn get_numerical_intensity_gradient<F>(
+    intensity: F,
+    pos: &Vector3<f64>,
+    delta: f64,
+) -> Vector3<f64>
+where
+    F: Fn(&Position) -> f64,
+{
+    let mut gradient = Vector3::new(0.0, 0.0, 0.0);
+    for axis in 0..3 {
+        let mut step = Vector3::new(0.0, 0.0, 0.0);
+        step[axis] = delta;
+        let forward = intensity(&Position {
+            pos: (pos + step).cast(),
+        });
+        let backward = intensity(&Position {
+            pos: (pos - step).cast(),
+        });
+        gradient[axis] = (forward - backward) / (2.0 * delta);
+    }
+    gradient
+}
+
+/// The step size used to numerically differentiate the intensity of a [GaussianBeam], in SI units of m.
+///
+/// The truncation error of the central difference scales as `delta^2 / L^2`, where `L` is the length scale over
+/// which the intensity varies, while the round-off error scales as `epsilon L / delta`, where `epsilon` is the
+/// machine precision. The total error is minimised for `delta ~ epsilon^(1/3) L`. The shortest length scale of the
+/// beam is the `e_radius`, which is used for `L`.
+pub fn numerical_gradient_step(beam: &GaussianBeam) -> f64 {
+    f64::EPSILON.cbrt() * beam.e_radius
+}
+
+/// Calculates the intensity gradient of each laser beam of type `B`. The result is stored in the `LaserIntensityGradientSamplers` .
+///
+/// Gradients are only calculated for beams marked as [DipoleLight]. The `Frame` of the beam, if present, is
+/// passed to the beam to account for ellipticity. The gradient is calculated by [BeamSource::gradient], unless
+/// the beam has a [GradientMethod::Numerical] component. The result is stored in the
+/// `LaserIntensityGradientSamplers` component that each atom is associated with. The gradients are scaled by
+/// the dipole factor of the [GlobalPowerScale], if present.
+pub struct SampleBeamSourceIntensityGradientSystem<B, const N: usize>(PhantomData<B>)
+where
+    B: BeamSource;
+
+impl<B, const N: usize> Default for SampleBeamSourceIntensityGradientSystem<B, N>
+where
+    B: BeamSource,
+{
+    fn default() -> Self {
+        SampleBeamSourceIntensityGradientSystem(PhantomData)
+    }
+}
+
+/// Samples the intensity gradient of [GaussianBeam]s.
+pub type SampleGaussianLaserIntensityGradientSystem<const N: usize> =
+    SampleBeamSourceIntensityGradientSystem<GaussianBeam, N>;
+
+impl<'a, B, const N: usize> System<'a> for SampleBeamSourceIntensityGradientSystem<B, N>
+where
+    B: BeamSource,
+{
     type SystemData = (
         ReadStorage<'a, DipoleLight>,
         ReadStorage<'a, LaserIndex>,
-        ReadStorage<'a, GaussianBeam>,
+        ReadStorage<'a, B>,
         ReadStorage<'a, Frame>,
+        ReadStorage<'a, NonParaxialCorrection>,
+        ReadStorage<'a, GradientMethod>,
         ReadStorage<'a, Position>,
         WriteStorage<'a, LaserIntensityGradientSamplers<N>>,
+        Option<Read<'a, GlobalPowerScale>>,
+        Option<Read<'a, ForceSerial>>,
     );
 
     fn run(
         &mut self,
-        (dipole, index, gaussian, reference_frame, pos, mut sampler): Self::SystemData,
+        (
+            dipole,
+            index,
+            beams,
+            reference_frame,
+            corrections,
+            methods,
+            pos,
+            mut sampler,
+            power_scale,
+            force_serial,
+        ): Self::SystemData,
     ) {
-        use rayon::prelude::*;
-
-        for (_dipole, index, beam, reference) in
-            (&dipole, &index, &gaussian, &reference_frame).join()
+        let scale = power_scale.map_or(1.0, |power_scale| power_scale.dipole);
+        for (_dipole, index, beam, reference, correction, method) in (
+            &dipole,
+            &index,
+            &beams,
+            reference_frame.maybe(),
+            corrections.maybe(),
+            methods.maybe(),
+        )
+            .join()
         {
-            (&pos, &mut sampler).par_join().for_each(|(pos, sampler)| {
-                sampler.contents[index.index].gradient =
-                    get_gaussian_beam_intensity_gradient(beam, pos, reference);
-            });
+            let modifiers = BeamModifiers {
+                frame: reference,
+                non_paraxial: correction,
+                ..Default::default()
+            };
+            match method.copied().unwrap_or_default() {
+                GradientMethod::Analytic => {
+                    (&pos, &mut sampler).maybe_par_for_each(
+                        force_serial.is_some(),
+                        |(pos, sampler)| {
+                            sampler.contents[index.index].gradient =
+                                scale * beam.gradient(pos, &modifiers);
+                        },
+                    );
+                }
+                GradientMethod::Numerical => {
+                    let delta = beam.gradient_step();
+                    (&pos, &mut sampler).maybe_par_for_each(
+                        force_serial.is_some(),
+                        |(pos, sampler)| {
+                            sampler.contents[index.index].gradient = scale
+                                * get_numerical_intensity_gradient(
+                                    |p| beam.intensity(p, &modifiers),
+                                    &pos.pos.cast(),
+                                    delta,
+                                );
+                        },
+                    );
+                }
+            }
         }
     }
 }
@@ -78,6 +210,9 @@ pub mod tests {
     use crate::laser::DEFAULT_BEAM_LIMIT;
 
     use super::*;
+    use crate::laser::gaussian::{
+        get_gaussian_beam_intensity, get_gaussian_beam_intensity_gradient,
+    };
 
     extern crate specs;
     use assert_approx_eq::assert_approx_eq;
@@ -95,6 +230,8 @@ pub mod tests {
         test_world.register::<LaserIntensityGradientSamplers<{ DEFAULT_BEAM_LIMIT }>>();
         test_world.register::<Frame>();
         test_world.register::<DipoleLight>();
+        test_world.register::<GradientMethod>();
+        test_world.register::<NonParaxialCorrection>();
 
         let beam = GaussianBeam {
             direction: Vector3::z(),
@@ -106,6 +243,7 @@ pub mod tests {
                 &70.71067812e-6,
             ),
             ellipticity: 0.0,
+            focus_offset: 0.0,
         };
 
         test_world
@@ -134,7 +272,7 @@ pub mod tests {
                     crate::laser::DEFAULT_BEAM_LIMIT],
             })
             .build();
-        let mut system = SampleGaussianLaserIntensityGradientSystem::<{ DEFAULT_BEAM_LIMIT }>;
+        let mut system = SampleGaussianLaserIntensityGradientSystem::<{ DEFAULT_BEAM_LIMIT }>::default();
         system.run_now(&test_world);
         test_world.maintain();
         let sampler_storage =
@@ -183,6 +321,8 @@ pub mod tests {
         test_world.register::<LaserIntensityGradientSamplers<{ DEFAULT_BEAM_LIMIT }>>();
         test_world.register::<Frame>();
         test_world.register::<DipoleLight>();
+        test_world.register::<GradientMethod>();
+        test_world.register::<NonParaxialCorrection>();
 
         let beam = GaussianBeam {
             direction: Vector3::x(),
@@ -194,6 +334,7 @@ pub mod tests {
                 &70.71067812e-6,
             ),
             ellipticity: 0.0,
+            focus_offset: 0.0,
         };
 
         test_world
@@ -222,7 +363,7 @@ pub mod tests {
                     crate::laser::DEFAULT_BEAM_LIMIT],
             })
             .build();
-        let mut system = SampleGaussianLaserIntensityGradientSystem::<{ DEFAULT_BEAM_LIMIT }>;
+        let mut system = SampleGaussianLaserIntensityGradientSystem::<{ DEFAULT_BEAM_LIMIT }>::default();
         system.run_now(&test_world);
         test_world.maintain();
         let sampler_storage =
@@ -237,4 +378,31 @@ pub mod tests {
         assert_approx_eq!(-4.33992902e+13, sim_result_gradient[1], 1e+8_f64);
         assert_approx_eq!(-4.33992902e+13, sim_result_gradient[2], 1e+8_f64);
     }
+
+    #[test]
+    fn test_numerical_gradient_matches_analytic_gradient() {
+        let beam = GaussianBeam {
+            direction: Vector3::new(0.0, 1.0, 1.0).normalize(),
+            intersection: Vector3::new(0.0, 0.0, 0.0),
+            e_radius: 70.71067812e-6,
+            power: 100.0,
+            rayleigh_range: crate::laser::gaussian::calculate_rayleigh_range(
+                &1064.0e-9,
+                &70.71067812e-6,
+            ),
+            ellipticity: 0.0,
+            focus_offset: 0.0,
+        };
+        let frame = Frame::from_direction(beam.direction, Vector3::x());
+        let pos = Vector3::new(40.0e-6, -20.0e-6, 300.0e-6);
+
+        let analytic =
+            get_gaussian_beam_intensity_gradient(&beam, &Position { pos: pos.cast() }, &frame);
+        let numerical = get_numerical_intensity_gradient(
+            |p| get_gaussian_beam_intensity(&beam, p, None, Some(&frame)),
+            &pos,
+            numerical_gradient_step(&beam),
+        );
+        assert!((numerical - analytic).norm() < 1e-6 * analytic.norm());
+    }
 }
@@ -1,12 +1,18 @@
 //! Calculation and initialization of laser quantities, eg intensities and indexing.
 
+pub mod axicon;
+pub mod beam_source;
 pub mod frame;
 pub mod gaussian;
 pub mod index;
 pub mod intensity;
 pub mod intensity_gradient;
+pub mod perturb;
+pub mod rotating;
 pub mod sampler;
+pub mod tabulated;
 
+use crate::dispatch::SystemDispatcherBuilder;
 use crate::initiate::NewlyCreated;
 use crate::integrator::INTEGRATE_POSITION_SYSTEM_NAME;
 use crate::simulation::Plugin;
@@ -41,6 +47,7 @@ impl<'a, const N: usize> System<'a> for AttachLaserComponentsToNewlyCreatedAtoms
                     contents: [intensity::LaserIntensitySampler::default(); N],
                 },
             );
+            updater.insert(ent, intensity::TotalIntensity::default());
             updater.insert(
                 ent,
                 intensity_gradient::LaserIntensityGradientSamplers {
@@ -62,7 +69,11 @@ pub struct LaserPlugin<const N : usize>;
 impl<const N : usize> Plugin for LaserPlugin<N> {
     fn build(&self, builder: &mut crate::simulation::SimulationBuilder) {
         register_components(&mut builder.world);
-        add_systems_to_dispatch::<N>(&mut builder.dispatcher_builder, &[]);
+        let mut registry = builder
+            .world
+            .entry::<beam_source::BeamSourceRegistry>()
+            .or_insert_with(beam_source::BeamSourceRegistry::default);
+        add_systems_to_dispatch::<N>(&mut builder.dispatcher_builder, &mut registry, &[]);
     }
 
     fn deps(&self) -> Vec::<Box<dyn Plugin>> {
@@ -76,9 +87,12 @@ impl<const N : usize> Plugin for LaserPlugin<N> {
 ///
 /// `builder`: the dispatch builder to modify
 ///
+/// `registry`: the beam types to sample in addition to `GaussianBeam`
+///
 /// `deps`: any dependencies that must be completed before the systems run.
 fn add_systems_to_dispatch<const N: usize>(
-    builder: &mut DispatcherBuilder<'static, 'static>,
+    builder: &mut impl SystemDispatcherBuilder,
+    registry: &mut beam_source::BeamSourceRegistry,
     deps: &[&str],
 ) {
     builder.add(
@@ -87,6 +101,11 @@ fn add_systems_to_dispatch<const N: usize>(
         deps,
     );
     builder.add(index::IndexLasersSystem, "index_lasers", deps);
+    builder.add(
+        rotating::RotateBeamsSystem,
+        "rotate_beams",
+        &[INTEGRATE_POSITION_SYSTEM_NAME],
+    );
     builder.add(
         sampler::InitialiseLaserSamplerMasksSystem::<N>,
         "initialise_laser_sampler_masks",
@@ -102,19 +121,34 @@ fn add_systems_to_dispatch<const N: usize>(
         "fill_laser_sampler_masks",
         &["index_lasers", "initialise_laser_sampler_masks"],
     );
+    // Sample all registered beam types before gaussian beams, so that systems which depend on
+    // `sample_laser_intensity` and `sample_intensity_gradient` see every beam.
+    let mut systems = (Vec::new(), Vec::new());
+    builder.with_recording(&mut |builder| systems = registry.add_systems_to_dispatch(builder));
+    let (intensity_systems, gradient_systems) = systems;
+    let mut intensity_deps = vec![
+        "index_lasers",
+        "initialise_laser_intensity",
+        "rotate_beams",
+        INTEGRATE_POSITION_SYSTEM_NAME,
+    ];
+    intensity_deps.extend(intensity_systems.iter().map(String::as_str));
     builder.add(
-        intensity::SampleLaserIntensitySystem::<N>,
+        intensity::SampleLaserIntensitySystem::<N>::default(),
         "sample_laser_intensity",
-        &[
-            "index_lasers",
-            "initialise_laser_intensity",
-            INTEGRATE_POSITION_SYSTEM_NAME,
-        ],
+        &intensity_deps,
+    );
+    builder.add(
+        intensity::CalculateTotalIntensitySystem::<N>,
+        "calculate_total_intensity",
+        &["sample_laser_intensity"],
     );
+    let mut gradient_deps = vec!["index_lasers", "rotate_beams"];
+    gradient_deps.extend(gradient_systems.iter().map(String::as_str));
     builder.add(
-        intensity_gradient::SampleGaussianLaserIntensityGradientSystem::<N>,
+        intensity_gradient::SampleGaussianLaserIntensityGradientSystem::<N>::default(),
         "sample_intensity_gradient",
-        &["index_lasers"],
+        &gradient_deps,
     );
 }
 
@@ -122,5 +156,10 @@ fn add_systems_to_dispatch<const N: usize>(
 fn register_components(world: &mut World) {
     world.register::<gaussian::GaussianBeam>();
     world.register::<gaussian::CircularMask>();
+    world.register::<gaussian::CircularAperture>();
+    world.register::<gaussian::InteractionCutoff>();
+    world.register::<gaussian::NonParaxialCorrection>();
     world.register::<frame::Frame>();
+    world.register::<rotating::RotatingBeam>();
+    world.register::<intensity_gradient::GradientMethod>();
 }
@@ -0,0 +1,159 @@
+//! Random displacement of laser beams, for Monte Carlo studies of alignment errors.
+
+use super::gaussian::GaussianBeam;
+use nalgebra::Vector3;
+use rand::Rng;
+use rand_distr::{Distribution, Normal};
+use specs::prelude::*;
+
+/// The displacement applied to a laser beam by [perturb_beam_positions].
+#[derive(Clone, Copy, Debug)]
+pub struct BeamPositionOffset {
+    /// The entity of the laser beam.
+    pub entity: Entity,
+    /// The displacement of the beam `intersection`, in SI units of m.
+    pub offset: Vector3<f64>,
+}
+
+/// Shifts the `intersection` of each `GaussianBeam` in the world by a Gaussian random vector.
+///
+/// This is intended to be called once when setting up a simulation, to model shot-to-shot alignment errors.
+/// The beams are perturbed in order of entity id, so the offsets are reproducible for a given seed of `rng`.
+///
+/// Returns the applied offsets, for example for logging.
+///
+/// # Arguments
+///
+/// `rng`: random number generator used to draw the offsets.
+///
+/// `rms`: root-mean-square magnitude of the offsets, in SI units of m. Each Cartesian component of an offset
+/// has a standard deviation of `rms / sqrt(3)`.
+///
+/// Panics if `rms` is negative or not finite.
+pub fn perturb_beam_positions<R>(
+    world: &mut World,
+    rng: &mut R,
+    rms: f64,
+) -> Vec<BeamPositionOffset>
+where
+    R: Rng + ?Sized,
+{
+    assert!(
+        rms.is_finite() && rms >= 0.0,
+        "The rms beam displacement must be finite and non-negative, but was {}.",
+        rms
+    );
+    let normal = Normal::new(0.0, rms / 3.0_f64.sqrt()).unwrap();
+    let entities = world.entities();
+    let mut beams = world.write_storage::<GaussianBeam>();
+
+    let mut offsets = Vec::new();
+    for (entity, beam) in (&entities, &mut beams).join() {
+        let offset = Vector3::new(normal.sample(rng), normal.sample(rng), normal.sample(rng));
+        beam.intersection += offset;
+        offsets.push(BeamPositionOffset { entity, offset });
+    }
+    offsets
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use crate::atom::Position;
+    use crate::dipole::Polarizability;
+    use crate::laser::frame::Frame;
+    use crate::laser::gaussian::{calculate_rayleigh_range, get_gaussian_beam_intensity_gradient};
+    use assert_approx_eq::assert_approx_eq;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn create_beams(world: &mut World, number: usize) {
+        let e_radius = 50.0e-6;
+        for _ in 0..number {
+            world
+                .create_entity()
+                .with(GaussianBeam {
+                    intersection: Vector3::new(0.0, 0.0, 0.0),
+                    direction: Vector3::x(),
+                    e_radius,
+                    power: 5.0,
+                    rayleigh_range: calculate_rayleigh_range(&1064.0e-9, &e_radius),
+                    ellipticity: 0.0,
+                    focus_offset: 0.0,
+                })
+                .build();
+        }
+    }
+
+    #[test]
+    fn test_perturbed_offsets_have_requested_rms() {
+        let mut test_world = World::new();
+        test_world.register::<GaussianBeam>();
+        let number = 10_000;
+        create_beams(&mut test_world, number);
+
+        let rms = 10.0e-6;
+        let mut rng = StdRng::seed_from_u64(1);
+        let offsets = perturb_beam_positions(&mut test_world, &mut rng, rms);
+        assert_eq!(offsets.len(), number);
+
+        let mean_square =
+            offsets.iter().map(|o| o.offset.norm_squared()).sum::<f64>() / number as f64;
+        assert_approx_eq!(mean_square.sqrt(), rms, 0.03 * rms);
+
+        let beams = test_world.read_storage::<GaussianBeam>();
+        for offset in offsets.iter() {
+            assert_eq!(
+                beams.get(offset.entity).unwrap().intersection,
+                offset.offset
+            );
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "finite and non-negative")]
+    fn test_negative_rms_panics() {
+        let mut test_world = World::new();
+        test_world.register::<GaussianBeam>();
+        create_beams(&mut test_world, 1);
+        let mut rng = StdRng::seed_from_u64(1);
+        perturb_beam_positions(&mut test_world, &mut rng, -1.0e-6);
+    }
+
+    #[test]
+    fn test_perturbation_is_deterministic_and_changes_force() {
+        let perturb = |seed: u64| {
+            let mut test_world = World::new();
+            test_world.register::<GaussianBeam>();
+            create_beams(&mut test_world, 1);
+            let mut rng = StdRng::seed_from_u64(seed);
+            perturb_beam_positions(&mut test_world, &mut rng, 10.0e-6);
+            let beams = test_world.read_storage::<GaussianBeam>();
+            let beams: Vec<GaussianBeam> = (&beams).join().copied().collect();
+            beams[0]
+        };
+        let beam = perturb(7);
+        assert_eq!(beam.intersection, perturb(7).intersection);
+        assert_ne!(beam.intersection, perturb(8).intersection);
+
+        // An atom on the unperturbed beam axis feels no transverse force, but is pulled towards the
+        // displaced axis once the beam is perturbed.
+        let polarizability = Polarizability::calculate_for(1064e-9, 780e-9, 6.065e6);
+        let frame = Frame::from_direction(Vector3::x(), Vector3::y());
+        let atom = Position::new();
+        let mut unperturbed = beam;
+        unperturbed.intersection = Vector3::new(0.0, 0.0, 0.0);
+        let unperturbed_force = polarizability.prefactor
+            * get_gaussian_beam_intensity_gradient(&unperturbed, &atom, &frame);
+        assert_eq!(unperturbed_force[1], 0.0);
+        assert_eq!(unperturbed_force[2], 0.0);
+
+        let force =
+            polarizability.prefactor * get_gaussian_beam_intensity_gradient(&beam, &atom, &frame);
+        for i in 1..3 {
+            assert!(force[i] != 0.0);
+            assert_eq!(force[i].signum(), beam.intersection[i].signum());
+        }
+    }
+}
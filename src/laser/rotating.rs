@@ -0,0 +1,204 @@
+//! Laser beams whose position rotates about a fixed axis, for stirring a cloud.
+//!
+//! A beam stirring a trapped cloud injects angular momentum, for example to nucleate vortices in a condensate. An
+//! entity with a [GaussianBeam] and a [RotatingBeam] has its `intersection` moved each step by the
+//! [RotateBeamsSystem], so that it traces a circle of the given radius about the axis at the given angular frequency.
+//! The direction of the beam is not changed. Use [crate::diagnostics::angular_momentum] to measure the rotation of
+//! the cloud.
+
+use super::gaussian::GaussianBeam;
+use crate::integrator::{SimulationTime, Step, Timestep};
+use nalgebra::Vector3;
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+
+/// A component that rotates the position of a [GaussianBeam] about an axis.
+///
+/// At time `t`, the `intersection` of the beam is at `center + radius * (cos(w t) u + sin(w t) v)`, where `w` is
+/// the angular frequency and `u`, `v` are orthonormal vectors perpendicular to the axis with `u x v` along the axis,
+/// so that the beam rotates in the right-handed sense about the axis. A radius of zero gives a static beam at the
+/// center.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug)]
+pub struct RotatingBeam {
+    /// A point on the axis of rotation, in SI units of m.
+    pub center: Vector3<f64>,
+    /// Distance of the beam from the axis, in SI units of m.
+    pub radius: f64,
+    /// Angular frequency of the rotation, in SI units of rad/s.
+    pub angular_frequency: f64,
+    /// Direction of the axis of rotation, which need not be normalized.
+    pub axis: Vector3<f64>,
+}
+impl Component for RotatingBeam {
+    type Storage = HashMapStorage<Self>;
+}
+impl RotatingBeam {
+    /// The position of the beam at the given time, in SI units of s.
+    ///
+    /// Panics if the axis is zero.
+    pub fn position(&self, time: f64) -> Vector3<f64> {
+        let axis = self
+            .axis
+            .try_normalize(0.0)
+            .expect("The axis of a rotating beam must be non-zero.");
+        // The coordinate axis least aligned with the rotation axis gives a well-conditioned perpendicular.
+        let least_aligned = axis.iamin();
+        let u = axis.cross(&Vector3::ith(least_aligned, 1.0)).normalize();
+        let v = axis.cross(&u);
+        let phase = self.angular_frequency * time;
+        self.center + self.radius * (phase.cos() * u + phase.sin() * v)
+    }
+}
+
+/// Moves each [GaussianBeam] with a [RotatingBeam] to its position at the current [SimulationTime].
+pub struct RotateBeamsSystem;
+impl<'a> System<'a> for RotateBeamsSystem {
+    type SystemData = (
+        WriteStorage<'a, GaussianBeam>,
+        ReadStorage<'a, RotatingBeam>,
+        ReadExpect<'a, Step>,
+        ReadExpect<'a, Timestep>,
+    );
+
+    fn run(&mut self, (mut beams, rotations, step, timestep): Self::SystemData) {
+        let time = SimulationTime::new(&step, &timestep).time;
+        for (beam, rotation) in (&mut beams, &rotations).join() {
+            beam.intersection = rotation.position(time);
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use crate::atom::{Atom, Force, Mass, Position, Velocity};
+    use crate::constant;
+    use crate::diagnostics::angular_momentum;
+    use crate::dipole::{DipoleLight, DipolePlugin, Polarizability};
+    use crate::initiate::NewlyCreated;
+    use crate::laser::frame::Frame;
+    use crate::laser::LaserPlugin;
+    use crate::simulation::{Simulation, SimulationBuilder};
+    use assert_approx_eq::assert_approx_eq;
+
+    const DT: f64 = 1.0e-6;
+    const RADIUS: f64 = 20.0e-6;
+
+    /// Creates a simulation with a ring of atoms at rest about the z axis, stirred by a dipole beam along z.
+    fn create_simulation(rotation: RotatingBeam) -> (Simulation, Entity) {
+        let mut sim_builder = SimulationBuilder::default();
+        sim_builder.add_plugin(LaserPlugin::<1>);
+        sim_builder.add_plugin(DipolePlugin::<1>);
+        let mut sim = sim_builder.build();
+        sim.world.insert(Timestep { delta: DT });
+
+        let beam = sim
+            .world
+            .create_entity()
+            .with(GaussianBeam {
+                intersection: rotation.center,
+                e_radius: RADIUS,
+                power: 1.0e-3,
+                direction: Vector3::z(),
+                rayleigh_range: f64::INFINITY,
+                ellipticity: 0.0,
+                focus_offset: 0.0,
+            })
+            .with(DipoleLight {
+                wavelength: 1064.0e-9,
+            })
+            .with(Frame {
+                x_vector: Vector3::x(),
+                y_vector: Vector3::y(),
+            })
+            .with(rotation)
+            .build();
+
+        let number = 36;
+        for i in 0..number {
+            let angle = 2.0 * constant::PI * i as f64 / number as f64;
+            sim.world
+                .create_entity()
+                .with(Position {
+                    pos: (RADIUS * Vector3::new(angle.cos(), angle.sin(), 0.0)).cast(),
+                })
+                .with(Velocity {
+                    vel: Vector3::new(0.0, 0.0, 0.0).cast(),
+                })
+                .with(Force::new())
+                .with(Mass { value: 87.0 })
+                .with(Polarizability::calculate_for(1064e-9, 780e-9, 6.065e6))
+                .with(Atom)
+                .with(NewlyCreated)
+                .build();
+        }
+        (sim, beam)
+    }
+
+    fn stirrer(radius: f64) -> RotatingBeam {
+        RotatingBeam {
+            center: Vector3::new(0.0, 0.0, 0.0),
+            radius,
+            angular_frequency: 2.0 * constant::PI * 500.0,
+            axis: Vector3::z(),
+        }
+    }
+
+    #[test]
+    fn test_rotating_beam_traces_circle() {
+        let rotation = RotatingBeam {
+            center: Vector3::new(1.0e-3, 0.0, -2.0e-3),
+            radius: 1.0e-4,
+            angular_frequency: 2.0 * constant::PI * 1.0e3,
+            axis: Vector3::new(1.0, 1.0, 0.0),
+        };
+        let axis = rotation.axis.normalize();
+        let mut previous = rotation.position(0.0) - rotation.center;
+        for i in 1..100 {
+            let offset = rotation.position(i as f64 * 1.0e-5) - rotation.center;
+            assert_approx_eq!(offset.norm(), rotation.radius, 1e-12);
+            assert_approx_eq!(offset.dot(&axis), 0.0, 1e-12);
+            // Each step advances the beam by the same angle, in the right-handed sense about the axis.
+            let angle = previous.cross(&offset).dot(&axis).atan2(previous.dot(&offset));
+            assert_approx_eq!(angle, rotation.angular_frequency * 1.0e-5, 1e-9);
+            previous = offset;
+        }
+
+        let (mut sim, beam) = create_simulation(stirrer(RADIUS));
+        for _ in 0..10 {
+            sim.step();
+        }
+        let time = SimulationTime::new(
+            &sim.world.read_resource::<Step>(),
+            &sim.world.read_resource::<Timestep>(),
+        )
+        .time;
+        let intersection = sim
+            .world
+            .read_storage::<GaussianBeam>()
+            .get(beam)
+            .unwrap()
+            .intersection;
+        assert_eq!(intersection, stirrer(RADIUS).position(time));
+    }
+
+    #[test]
+    fn test_stirring_injects_angular_momentum() {
+        let (mut sim, beam) = create_simulation(stirrer(RADIUS));
+        let (mut static_sim, static_beam) = create_simulation(stirrer(0.0));
+        for _ in 0..500 {
+            sim.step();
+            static_sim.step();
+        }
+
+        let stirred = angular_momentum(&sim.world, Vector3::z(), Vector3::zeros());
+        let unstirred = angular_momentum(&static_sim.world, Vector3::z(), Vector3::zeros());
+        assert!(stirred > 0.0, "angular momentum {}", stirred);
+        assert!(unstirred.abs() < 1e-6 * stirred, "angular momentum {}", unstirred);
+
+        let beams = static_sim.world.read_storage::<GaussianBeam>();
+        assert_eq!(beams.get(static_beam).unwrap().intersection, Vector3::zeros());
+        assert!(sim.world.read_storage::<GaussianBeam>().get(beam).unwrap().intersection.norm() > 0.0);
+    }
+}
@@ -6,6 +6,7 @@ use specs::prelude::*;
 extern crate nalgebra;
 
 use crate::laser_cooling::CoolingLight;
+use crate::parallel::{ForceSerial, MaybeParJoin};
 
 /// Tracks which slots in the laser sampler arrays are currently used for cooling light.
 #[derive(Clone, Copy, Default, Serialize)]
@@ -26,12 +27,13 @@ impl<const N: usize> Component for CoolingLaserSamplerMasks<N> {
 pub struct InitialiseLaserSamplerMasksSystem<const N: usize>;
 
 impl<'a, const N: usize> System<'a> for InitialiseLaserSamplerMasksSystem<N> {
-    type SystemData = (WriteStorage<'a, CoolingLaserSamplerMasks<N>>,);
-
-    fn run(&mut self, (mut masks,): Self::SystemData) {
-        use rayon::prelude::*;
+    type SystemData = (
+        WriteStorage<'a, CoolingLaserSamplerMasks<N>>,
+        Option<Read<'a, ForceSerial>>,
+    );
 
-        (&mut masks).par_join().for_each(|mask| {
+    fn run(&mut self, (mut masks, force_serial): Self::SystemData) {
+        (&mut masks).maybe_par_for_each(force_serial.is_some(), |mask| {
             mask.contents = [LaserSamplerMask::default(); N];
         });
     }
@@ -45,12 +47,11 @@ impl<'a, const N: usize> System<'a> for FillLaserSamplerMasksSystem<N> {
         ReadStorage<'a, LaserIndex>,
         ReadStorage<'a, CoolingLight>,
         WriteStorage<'a, CoolingLaserSamplerMasks<N>>,
+        Option<Read<'a, ForceSerial>>,
     );
-    fn run(&mut self, (light_index, cooling, mut masks): Self::SystemData) {
-        use rayon::prelude::*;
-
+    fn run(&mut self, (light_index, cooling, mut masks, force_serial): Self::SystemData) {
         for (light_index, _) in (&light_index, &cooling).join() {
-            (&mut masks).par_join().for_each(|masks| {
+            (&mut masks).maybe_par_for_each(force_serial.is_some(), |masks| {
                 masks.contents[light_index.index] = LaserSamplerMask { filled: true };
             });
         }
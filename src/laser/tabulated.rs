@@ -0,0 +1,387 @@
+//! Laser beams with a tabulated intensity profile.
+//!
+//! The intensity profile of a beam measured in the lab, for example by imaging it onto a camera, can be loaded
+//! into an [IntensityTable] and used to describe a [TabulatedBeam]. The table is a regular grid of intensities
+//! in the plane transverse to the beam, which is interpolated bilinearly. The beam is collimated, so the
+//! intensity does not depend on the position along the beam.
+//!
+//! [TabulatedBeam]s are sampled once their type is registered with a
+//! [BeamSourcePlugin](crate::laser::beam_source::BeamSourcePlugin), added before the
+//! [LaserPlugin](crate::laser::LaserPlugin).
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+use std::sync::Arc;
+
+use nalgebra::Vector3;
+use specs::prelude::*;
+
+use super::beam_source::{BeamModifiers, BeamSource};
+use super::intensity_gradient::get_numerical_intensity_gradient;
+use crate::atom::Position;
+use crate::maths;
+
+/// A regular grid of intensities in the plane transverse to a beam.
+///
+/// The intensity at grid point `(i, j)` is at transverse coordinates `(u, v) = origin + (i du, j dv)`.
+#[derive(Clone, Debug)]
+pub struct IntensityTable {
+    /// Transverse coordinates `(u, v)` of the first grid point, in SI units of m.
+    pub origin: (f64, f64),
+    /// Spacing `(du, dv)` of the grid points, in SI units of m.
+    pub spacing: (f64, f64),
+    /// Number of grid points along `u`.
+    pub nu: usize,
+    /// Number of grid points along `v`.
+    pub nv: usize,
+    /// Intensities at the grid points in SI units of W/m^2, stored with `u` varying fastest.
+    pub values: Vec<f64>,
+}
+
+impl IntensityTable {
+    /// Creates a table from intensities in SI units of W/m^2, stored with `u` varying fastest.
+    ///
+    /// Panics if the table has fewer than two points along either axis, or if the number of `values` does not
+    /// match the size of the grid.
+    pub fn new(
+        origin: (f64, f64),
+        spacing: (f64, f64),
+        nu: usize,
+        nv: usize,
+        values: Vec<f64>,
+    ) -> Self {
+        assert!(
+            nu >= 2 && nv >= 2,
+            "An IntensityTable needs at least two points along each axis."
+        );
+        assert_eq!(
+            values.len(),
+            nu * nv,
+            "The number of values does not match the size of the IntensityTable."
+        );
+        IntensityTable {
+            origin,
+            spacing,
+            nu,
+            nv,
+            values,
+        }
+    }
+
+    /// Reads a table from a CSV file, see [IntensityTable::from_reader].
+    pub fn from_csv<P: AsRef<Path>>(
+        path: P,
+        origin: (f64, f64),
+        spacing: (f64, f64),
+    ) -> Result<Self, io::Error> {
+        Self::from_reader(BufReader::new(File::open(path)?), origin, spacing)
+    }
+
+    /// Reads a table of comma-separated intensities in SI units of W/m^2.
+    ///
+    /// Each line is a row of constant `v`, and each column is a value of `u`, as in an image of the beam.
+    /// Empty lines are ignored.
+    pub fn from_reader<R: BufRead>(
+        reader: R,
+        origin: (f64, f64),
+        spacing: (f64, f64),
+    ) -> Result<Self, io::Error> {
+        let mut values = Vec::new();
+        let mut nu = 0;
+        let mut nv = 0;
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let invalid = |message: &str| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{} in intensity table: {}", message, line),
+                )
+            };
+            let row = line
+                .split(',')
+                .map(|value| value.trim().parse::<f64>())
+                .collect::<Result<Vec<f64>, _>>()
+                .map_err(|_| invalid("invalid value"))?;
+            if nv == 0 {
+                nu = row.len();
+            } else if row.len() != nu {
+                return Err(invalid("row of different length"));
+            }
+            values.extend(row);
+            nv += 1;
+        }
+        if nu < 2 || nv < 2 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "intensity table needs at least two rows and two columns",
+            ));
+        }
+        Ok(IntensityTable::new(origin, spacing, nu, nv, values))
+    }
+
+    /// Interpolates the intensity bilinearly at transverse coordinates `(u, v)`, in SI units of m.
+    ///
+    /// Returns zero outside of the table.
+    pub fn interpolate(&self, u: f64, v: f64) -> f64 {
+        let fu = (u - self.origin.0) / self.spacing.0;
+        let fv = (v - self.origin.1) / self.spacing.1;
+        if !(0.0..=(self.nu - 1) as f64).contains(&fu)
+            || !(0.0..=(self.nv - 1) as f64).contains(&fv)
+        {
+            return 0.0;
+        }
+        let i = (fu.floor() as usize).min(self.nu - 2);
+        let j = (fv.floor() as usize).min(self.nv - 2);
+        let tu = fu - i as f64;
+        let tv = fv - j as f64;
+        let value = |i: usize, j: usize| self.values[j * self.nu + i];
+        (1.0 - tu) * (1.0 - tv) * value(i, j)
+            + tu * (1.0 - tv) * value(i + 1, j)
+            + (1.0 - tu) * tv * value(i, j + 1)
+            + tu * tv * value(i + 1, j + 1)
+    }
+}
+
+/// A component that describes a collimated laser beam with a tabulated intensity profile.
+///
+/// The transverse coordinates of the [IntensityTable] are `u = (r - intersection) . x_vector` and
+/// `v = (r - intersection) . (direction x x_vector)`. The table is shared between clones of the beam.
+#[derive(Clone, Debug)]
+pub struct TabulatedBeam {
+    /// A point on the axis of the beam, from which transverse coordinates are measured, in SI units of m.
+    pub intersection: Vector3<f64>,
+    /// Direction of the beam, a unit vector.
+    pub direction: Vector3<f64>,
+    /// Direction of the `u` axis of the table, a unit vector orthogonal to `direction`.
+    pub x_vector: Vector3<f64>,
+    /// The intensity profile of the beam.
+    pub table: Arc<IntensityTable>,
+}
+impl Component for TabulatedBeam {
+    type Storage = HashMapStorage<Self>;
+}
+
+impl TabulatedBeam {
+    /// Creates a beam with the given intensity profile. The `direction` and `x_vector` are normalized.
+    pub fn new(
+        intersection: Vector3<f64>,
+        direction: Vector3<f64>,
+        x_vector: Vector3<f64>,
+        table: IntensityTable,
+    ) -> Self {
+        TabulatedBeam {
+            intersection,
+            direction: direction.normalize(),
+            x_vector: x_vector.normalize(),
+            table: Arc::new(table),
+        }
+    }
+
+    /// The transverse coordinates `(u, v)` of a position, in SI units of m.
+    fn transverse_coordinates(&self, pos: &Vector3<f64>) -> (f64, f64) {
+        let relative = pos - self.intersection;
+        let y_vector = self.direction.cross(&self.x_vector);
+        (relative.dot(&self.x_vector), relative.dot(&y_vector))
+    }
+}
+
+impl BeamSource for TabulatedBeam {
+    /// The interpolated intensity. A [CircularMask](crate::laser::gaussian::CircularMask) and an
+    /// [CircularAperture](crate::laser::gaussian::CircularAperture) are applied, and the other modifiers are
+    /// ignored.
+    fn intensity(&self, pos: &Position, modifiers: &BeamModifiers) -> f64 {
+        if let Some(aperture) = modifiers.aperture {
+            if !aperture.transmits(&self.intersection, &self.direction, pos) {
+                return 0.0;
+            }
+        }
+        let pos = pos.pos.cast::<f64>();
+        if let Some(mask) = modifiers.mask {
+            let (distance, _) =
+                maths::get_minimum_distance_line_point(&pos, &self.intersection, &self.direction);
+            if distance < mask.radius {
+                return 0.0;
+            }
+        }
+        let (u, v) = self.transverse_coordinates(&pos);
+        self.table.interpolate(u, v)
+    }
+
+    /// The gradient of the interpolated intensity, calculated by central differences.
+    fn gradient(&self, pos: &Position, _modifiers: &BeamModifiers) -> Vector3<f64> {
+        get_numerical_intensity_gradient(
+            |p| self.intensity(p, &BeamModifiers::default()),
+            &pos.pos.cast(),
+            self.gradient_step(),
+        )
+    }
+
+    /// A tenth of the smaller grid spacing, so that the central difference gives the slope of a single cell.
+    fn gradient_step(&self) -> f64 {
+        0.1 * self.table.spacing.0.min(self.table.spacing.1)
+    }
+
+    fn direction(&self) -> Option<Vector3<f64>> {
+        Some(self.direction)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use crate::atom::Atom;
+    use crate::dipole::DipoleLight;
+    use crate::initiate::NewlyCreated;
+    use crate::integrator::Timestep;
+    use crate::laser::beam_source::BeamSourcePlugin;
+    use crate::laser::gaussian::{get_gaussian_beam_intensity, GaussianBeam};
+    use crate::laser::index::LaserIndex;
+    use crate::laser::intensity::LaserIntensitySamplers;
+    use crate::laser::intensity_gradient::LaserIntensityGradientSamplers;
+    use crate::laser::LaserPlugin;
+    use crate::simulation::SimulationBuilder;
+    use assert_approx_eq::assert_approx_eq;
+
+    fn gaussian() -> GaussianBeam {
+        GaussianBeam {
+            intersection: Vector3::new(0.0, 0.0, 0.0),
+            e_radius: 1.0e-3,
+            power: 0.1,
+            direction: Vector3::z(),
+            rayleigh_range: f64::INFINITY,
+            ellipticity: 0.0,
+            focus_offset: 0.0,
+        }
+    }
+
+    /// Writes the intensity of a [GaussianBeam] along z as a CSV table, and returns the origin and spacing.
+    fn gaussian_csv(
+        beam: &GaussianBeam,
+        n: usize,
+        half_width: f64,
+    ) -> (String, (f64, f64), (f64, f64)) {
+        let spacing = 2.0 * half_width / (n - 1) as f64;
+        let csv = (0..n)
+            .map(|j| {
+                (0..n)
+                    .map(|i| {
+                        let pos = Position {
+                            pos: Vector3::new(
+                                -half_width + i as f64 * spacing,
+                                -half_width + j as f64 * spacing,
+                                0.0,
+                            )
+                            .cast(),
+                        };
+                        format!("{:e}", get_gaussian_beam_intensity(beam, &pos, None, None))
+                    })
+                    .collect::<Vec<String>>()
+                    .join(",")
+            })
+            .collect::<Vec<String>>()
+            .join("\n");
+        (csv, (-half_width, -half_width), (spacing, spacing))
+    }
+
+    #[test]
+    fn test_tabulated_gaussian_matches_analytic_intensity() {
+        let beam = gaussian();
+        let (csv, origin, spacing) = gaussian_csv(&beam, 201, 4.0e-3);
+        let path = std::env::temp_dir().join("atomecs_test_tabulated_beam.csv");
+        std::fs::write(&path, csv).expect("could not write table");
+        let table = IntensityTable::from_csv(&path, origin, spacing).expect("could not read table");
+        std::fs::remove_file(&path).ok();
+        assert_eq!((table.nu, table.nv), (201, 201));
+        let tabulated = TabulatedBeam::new(beam.intersection, beam.direction, Vector3::x(), table);
+
+        // The error of bilinear interpolation is bounded by h^2 / 8 times the largest second derivative, which
+        // is 4 I_0 / w^2 for a Gaussian of 1/e^2 radius w = sqrt(2) e_radius.
+        let peak = get_gaussian_beam_intensity(&beam, &Position::new(), None, None);
+        let tolerance = spacing.0.powi(2) / 8.0 * 4.0 * peak / (2.0 * beam.e_radius.powi(2));
+        for pos in [
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(0.33e-3, -0.71e-3, 5.0e-3),
+            Vector3::new(-1.23e-3, 0.52e-3, -2.0e-2),
+            Vector3::new(2.17e-3, 1.91e-3, 0.0),
+        ]
+        .iter()
+        {
+            let pos = Position { pos: pos.cast() };
+            assert_approx_eq!(
+                tabulated.intensity(&pos, &BeamModifiers::default()),
+                get_gaussian_beam_intensity(&beam, &pos, None, None),
+                tolerance
+            );
+        }
+
+        // Positions outside the table have zero intensity.
+        for pos in [
+            Vector3::new(4.1e-3, 0.0, 0.0),
+            Vector3::new(0.0, -4.1e-3, 0.0),
+        ]
+        .iter()
+        {
+            let pos = Position { pos: pos.cast() };
+            assert_eq!(tabulated.intensity(&pos, &BeamModifiers::default()), 0.0);
+        }
+    }
+
+    #[test]
+    fn test_tabulated_beam_is_sampled() {
+        let mut sim_builder = SimulationBuilder::default();
+        sim_builder.add_plugin(BeamSourcePlugin::<TabulatedBeam, 1>::default());
+        sim_builder.add_plugin(LaserPlugin::<1>);
+        let mut sim = sim_builder.build();
+        sim.world.insert(Timestep { delta: 1.0e-6 });
+
+        // A ramp along x, which bilinear interpolation reproduces exactly.
+        let slope = 1.0e6;
+        let values = (0..3)
+            .flat_map(|_| (0..3).map(move |i| 1.0e3 + slope * (i as f64 - 1.0) * 1.0e-3))
+            .collect();
+        let table = IntensityTable::new((-1.0e-3, -1.0e-3), (1.0e-3, 1.0e-3), 3, 3, values);
+        sim.world
+            .create_entity()
+            .with(TabulatedBeam::new(
+                Vector3::new(0.0, 0.0, 0.0),
+                Vector3::z(),
+                Vector3::x(),
+                table,
+            ))
+            .with(DipoleLight {
+                wavelength: 1064e-9,
+            })
+            .with(LaserIndex::default())
+            .build();
+        let atom = sim
+            .world
+            .create_entity()
+            .with(Position {
+                pos: Vector3::new(0.5e-3, 0.2e-3, 0.1).cast(),
+            })
+            .with(Atom)
+            .with(NewlyCreated)
+            .build();
+        sim.step();
+        sim.step();
+
+        let intensities = sim.world.read_storage::<LaserIntensitySamplers<1>>();
+        assert_approx_eq!(
+            intensities.get(atom).unwrap().contents[0].intensity,
+            1.0e3 + slope * 0.5e-3,
+            1e-9
+        );
+        let gradients = sim
+            .world
+            .read_storage::<LaserIntensityGradientSamplers<1>>();
+        let gradient = gradients.get(atom).unwrap().contents[0].gradient;
+        assert_approx_eq!(gradient[0], slope, 1e-6 * slope);
+        assert_approx_eq!(gradient[1], 0.0, 1e-6 * slope);
+        assert_approx_eq!(gradient[2], 0.0, 1e-6 * slope);
+    }
+}
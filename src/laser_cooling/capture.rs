@@ -0,0 +1,269 @@
+//! Design-time estimates of the capture velocity of a MOT.
+//!
+//! [capture_velocity] estimates the largest speed of an atom that is stopped by a single cooling beam within the
+//! diameter of the beam, and [optimal_waist] searches for a beam waist that reaches a target capture velocity at
+//! fixed power. These are quick one-dimensional estimates for choosing beam parameters; use
+//! [characterize_mot](super::characterize::characterize_mot) to measure the capture velocity of a full simulation.
+
+use std::fmt;
+
+use super::scattering::{saturation_parameter, scattering_rate};
+use super::transition::AtomicTransition;
+use crate::constant::{AMU, HBAR, PI};
+
+/// Number of velocity steps per linewidth used to integrate the stopping distance.
+const STEPS_PER_LINEWIDTH: f64 = 1000.0;
+
+/// Number of waists sampled when searching for the maximum capture velocity.
+const WAIST_SAMPLES: usize = 64;
+
+/// Smallest and largest waists considered by [optimal_waist], in SI units of m.
+const WAIST_RANGE: (f64, f64) = (1.0e-4, 0.1);
+
+/// Number of bisection steps used to refine the waist.
+const BISECTION_STEPS: usize = 50;
+
+/// Estimates the capture velocity of a cooling beam, in SI units of m/s.
+///
+/// The atom travels along the axis of a beam propagating towards it, and is decelerated by the scattering force
+/// at the peak intensity of the beam, including the Doppler shift. The capture velocity is the launch speed of an
+/// atom that stops after travelling one beam diameter, `2 * waist`. The Zeeman shift is neglected, so the
+/// estimate applies to the slowing of the atoms as they enter the beam overlap region.
+///
+/// # Arguments
+///
+/// `power`: power of the beam, in SI units of W.
+///
+/// `detuning`: detuning of the beam from the transition, in MHz.
+///
+/// `waist`: `1/e^2` intensity radius of the beam, in SI units of m.
+///
+/// `mass`: mass of the atom, in atomic mass units.
+///
+/// # Generic Arguments
+///
+/// * `T`: The laser cooling transition.
+pub fn capture_velocity<T>(power: f64, detuning: f64, waist: f64, mass: f64) -> f64
+where
+    T: AtomicTransition,
+{
+    let gamma = T::gamma();
+    let wavenumber = 2.0 * PI / T::wavelength();
+    let detuning = 2.0 * PI * detuning * 1.0e6;
+    let s = saturation_parameter(2.0 * power / (PI * waist.powi(2)), T::saturation_intensity());
+    let deceleration = |speed: f64| {
+        HBAR * wavenumber * scattering_rate(gamma, detuning + wavenumber * speed, s) / (mass * AMU)
+    };
+
+    // Integrate the stopping distance `d(v) = int_0^v u / a(u) du` until it reaches the beam diameter.
+    let diameter = 2.0 * waist;
+    let dv = gamma / wavenumber / STEPS_PER_LINEWIDTH;
+    let (mut speed, mut distance) = (0.0, 0.0);
+    loop {
+        let step = dv * (speed + 0.5 * dv) / deceleration(speed + 0.5 * dv);
+        if distance + step >= diameter {
+            return speed + dv * (diameter - distance) / step;
+        }
+        distance += step;
+        speed += dv;
+    }
+}
+
+/// The reasons that [optimal_waist] may fail to find a waist.
+#[derive(Clone, Copy, Debug)]
+pub enum OptimalWaistError {
+    /// No waist reaches the target capture velocity at the given power and detuning.
+    TargetUnreachable {
+        /// The largest capture velocity of any waist, in SI units of m/s.
+        max_capture_velocity: f64,
+        /// The waist with the largest capture velocity, in SI units of m.
+        waist: f64,
+    },
+}
+impl fmt::Display for OptimalWaistError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OptimalWaistError::TargetUnreachable {
+                max_capture_velocity,
+                waist,
+            } => write!(
+                f,
+                "No beam waist reaches the target capture velocity: the largest capture velocity is {:.3} m/s, for a waist of {:.3e} m. Increase the power or change the detuning.",
+                max_capture_velocity, waist
+            ),
+        }
+    }
+}
+
+/// Finds the smallest beam waist whose [capture_velocity] reaches `target_capture_velocity`.
+///
+/// At fixed power, a small beam saturates the transition but stops the atoms over a short distance, while a large
+/// beam has a low intensity, so the capture velocity peaks at an intermediate waist. The waists between 100 um
+/// and 10 cm are sampled to find the peak, and the smallest waist that reaches the target is then refined by
+/// bisection. The smallest such waist leaves the most intensity to spare.
+///
+/// Returns the waist, the `1/e^2` intensity radius in SI units of m, or an error if no waist reaches the target.
+///
+/// # Arguments
+///
+/// `power`: power of the beam, in SI units of W.
+///
+/// `detuning`: detuning of the beam from the transition, in MHz.
+///
+/// `mass`: mass of the atom, in atomic mass units.
+///
+/// `target_capture_velocity`: the required capture velocity, in SI units of m/s.
+///
+/// # Generic Arguments
+///
+/// * `T`: The laser cooling transition.
+pub fn optimal_waist<T>(
+    power: f64,
+    detuning: f64,
+    mass: f64,
+    target_capture_velocity: f64,
+) -> Result<f64, OptimalWaistError>
+where
+    T: AtomicTransition,
+{
+    let capture = |waist: f64| capture_velocity::<T>(power, detuning, waist, mass);
+    let (min, max) = WAIST_RANGE;
+    let waists: Vec<f64> = (0..WAIST_SAMPLES)
+        .map(|i| min * (max / min).powf(i as f64 / (WAIST_SAMPLES - 1) as f64))
+        .collect();
+    let velocities: Vec<f64> = waists.iter().map(|&waist| capture(waist)).collect();
+
+    let first = match velocities
+        .iter()
+        .position(|&velocity| velocity >= target_capture_velocity)
+    {
+        Some(first) => first,
+        None => {
+            let (waist, max_capture_velocity) = waists
+                .iter()
+                .cloned()
+                .zip(velocities.iter().cloned())
+                .fold((f64::NAN, f64::MIN), |best, sample| {
+                    if sample.1 > best.1 {
+                        sample
+                    } else {
+                        best
+                    }
+                });
+            return Err(OptimalWaistError::TargetUnreachable {
+                max_capture_velocity,
+                waist,
+            });
+        }
+    };
+    if first == 0 {
+        return Ok(waists[0]);
+    }
+
+    let (mut low, mut high) = (waists[first - 1], waists[first]);
+    for _ in 0..BISECTION_STEPS {
+        let mid = 0.5 * (low + high);
+        if capture(mid) >= target_capture_velocity {
+            high = mid;
+        } else {
+            low = mid;
+        }
+    }
+    Ok(high)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use crate::atom::{Atom, Force, Mass, Position, Velocity};
+    use crate::initiate::NewlyCreated;
+    use crate::integrator::Timestep;
+    use crate::laser::gaussian::GaussianBeam;
+    use crate::laser::LaserPlugin;
+    use crate::laser_cooling::linear_response::with_mean_forces;
+    use crate::laser_cooling::{CoolingLight, LaserCoolingPlugin};
+    use crate::simulation::SimulationBuilder;
+    use crate::species::Rubidium87_780D2;
+    use nalgebra::Vector3;
+    use specs::prelude::*;
+
+    /// Launches an atom along the axis of a single beam, and returns true if it stops within the beam diameter.
+    fn is_stopped(power: f64, detuning: f64, waist: f64, speed: f64) -> bool {
+        let mut sim_builder = SimulationBuilder::default();
+        sim_builder.add_plugin(LaserPlugin::<6>);
+        sim_builder.add_plugin(LaserCoolingPlugin::<Rubidium87_780D2, 6>::default());
+        let mut sim = sim_builder.build();
+        sim.world.insert(Timestep { delta: 1.0e-6 });
+
+        sim.world
+            .create_entity()
+            .with(GaussianBeam {
+                intersection: Vector3::new(0.0, 0.0, 0.0),
+                e_radius: waist / 2.0_f64.sqrt(),
+                power,
+                direction: -Vector3::z(),
+                rayleigh_range: f64::INFINITY,
+                ellipticity: 0.0,
+                focus_offset: 0.0,
+            })
+            .with(CoolingLight::for_transition::<Rubidium87_780D2>(detuning, 1))
+            .build();
+        let atom = sim
+            .world
+            .create_entity()
+            .with(Position {
+                pos: Vector3::new(0.0, 0.0, -waist).cast(),
+            })
+            .with(Velocity {
+                vel: Vector3::new(0.0, 0.0, speed).cast(),
+            })
+            .with(Force::new())
+            .with(Mass { value: 87.0 })
+            .with(Atom)
+            .with(Rubidium87_780D2::default())
+            .with(NewlyCreated)
+            .build();
+
+        with_mean_forces(&mut sim, |sim| loop {
+            sim.step();
+            let z = sim.world.read_storage::<Position>().get(atom).unwrap().pos[2] as f64;
+            let vz = sim.world.read_storage::<Velocity>().get(atom).unwrap().vel[2] as f64;
+            if z >= waist {
+                return false;
+            }
+            if vz <= 0.0 {
+                return true;
+            }
+        })
+    }
+
+    #[test]
+    fn test_optimal_waist_reaches_target_capture_velocity() {
+        let (power, detuning, target) = (0.01, -12.0, 20.0);
+        let waist = optimal_waist::<Rubidium87_780D2>(power, detuning, 87.0, target).unwrap();
+        assert!(waist > WAIST_RANGE.0 && waist < WAIST_RANGE.1);
+        let velocity = capture_velocity::<Rubidium87_780D2>(power, detuning, waist, 87.0);
+        assert!((velocity - target).abs() / target < 1.0e-3, "{} m/s", velocity);
+
+        assert!(is_stopped(power, detuning, waist, 0.95 * target));
+        assert!(!is_stopped(power, detuning, waist, 1.05 * target));
+    }
+
+    #[test]
+    fn test_unreachable_target_is_an_error() {
+        match optimal_waist::<Rubidium87_780D2>(1.0e-3, -12.0, 87.0, 1000.0) {
+            Err(OptimalWaistError::TargetUnreachable {
+                max_capture_velocity,
+                waist,
+            }) => {
+                assert!(max_capture_velocity > 0.0 && max_capture_velocity < 1000.0);
+                assert_eq!(
+                    max_capture_velocity,
+                    capture_velocity::<Rubidium87_780D2>(1.0e-3, -12.0, waist, 87.0)
+                );
+            }
+            Ok(waist) => panic!("Unexpected waist {} m.", waist),
+        }
+    }
+}
@@ -0,0 +1,290 @@
+//! Characterizes a magneto-optical trap with a single call.
+//!
+//! [characterize_mot] combines the diagnostics that are usually compared against rate-equation models of a MOT:
+//! the spring constant and damping coefficient from the [linear_response], the capture velocity, and the
+//! equilibrium temperature of a cloud held in the trap.
+
+use nalgebra::Vector3;
+use specs::prelude::*;
+
+use super::linear_response::{linear_response, with_mean_forces, LinearResponseConfig};
+use super::transition::TransitionComponent;
+use crate::atom::{Atom, Force, Mass, Position, Velocity};
+use crate::constant::{AMU, PI};
+use crate::equilibrium::measure_temperature;
+use crate::initiate::NewlyCreated;
+use crate::simulation::Simulation;
+
+/// Configures the measurements made by [characterize_mot].
+pub struct MotCharacterizationConfig {
+    /// Configures the measurement of the spring constant and damping coefficient.
+    pub linear_response: LinearResponseConfig,
+    /// Axis along which the trap is characterized.
+    pub axis: Vector3<f64>,
+    /// Distance from the centre at which capture probe atoms are launched, in SI units of m.
+    ///
+    /// A probe atom is captured if it is within this distance of the centre at the end of the capture run.
+    pub capture_radius: f64,
+    /// Largest launch speed of the capture probe atoms, in SI units of m/s.
+    pub max_capture_velocity: f64,
+    /// Number of capture probe atoms, launched with speeds evenly spaced up to `max_capture_velocity`.
+    pub capture_probes: usize,
+    /// Number of steps simulated to determine whether the capture probe atoms are captured.
+    pub capture_steps: usize,
+    /// Number of atoms used to measure the equilibrium temperature.
+    pub temperature_atoms: usize,
+    /// Number of steps simulated to thermalize the atoms before the temperature is measured.
+    pub temperature_steps: usize,
+}
+
+/// The characteristics of a MOT measured by [characterize_mot].
+#[derive(Clone, Copy, Debug)]
+pub struct MotCharacteristics {
+    /// Spring constant of the trap, in SI units of N/m.
+    pub spring_constant: f64,
+    /// Damping coefficient of the trap, in SI units of kg/s.
+    pub damping_coefficient: f64,
+    /// Trap frequency `sqrt(kappa / m) / 2pi`, in SI units of Hz.
+    pub trap_frequency: f64,
+    /// Damping time `m / beta` of the velocity, in SI units of s.
+    pub damping_time: f64,
+    /// Largest launch speed at which a probe atom was captured, in SI units of m/s.
+    ///
+    /// Zero if no probe atom was captured.
+    pub capture_velocity: f64,
+    /// Equilibrium temperature of the cloud, in SI units of K.
+    pub temperature: f64,
+}
+
+/// Measures the damping time, trap frequency, capture velocity and temperature of a MOT.
+///
+/// The measurements are made in turn:
+///
+/// * The spring constant and damping coefficient are measured with [linear_response].
+///
+/// * Capture probe atoms are launched from `capture_radius` on the negative side of the centre towards the centre,
+///   with speeds up to `max_capture_velocity`. The fluctuations of the scattering and emission forces are disabled, so
+///   that the result is deterministic. The capture velocity is the largest launch speed of the atoms that remain
+///   within `capture_radius` of the centre after `capture_steps`.
+///
+/// * Atoms are created at rest at the centre, and held for `temperature_steps` with the fluctuation options of the
+///   world, before their temperature is measured. The steps should span several damping times.
+///
+/// All probe atoms are deleted afterwards. Any other atoms in the simulation are integrated as normal.
+///
+/// # Generic Arguments
+///
+/// * `T`: The laser cooling transition of the probe atoms.
+pub fn characterize_mot<T>(
+    simulation: &mut Simulation,
+    config: &MotCharacterizationConfig,
+) -> MotCharacteristics
+where
+    T: TransitionComponent,
+{
+    let axis = config.axis.normalize();
+    let center = config.linear_response.center;
+    let mass = config.linear_response.mass;
+
+    let (spring_constant, damping_coefficient) =
+        linear_response::<T>(simulation, &config.linear_response, axis);
+
+    let capture_velocity = with_mean_forces(simulation, |simulation| {
+        let speeds: Vec<f64> = (1..=config.capture_probes)
+            .map(|i| i as f64 * config.max_capture_velocity / config.capture_probes as f64)
+            .collect();
+        let probes: Vec<Entity> = speeds
+            .iter()
+            .map(|&speed| {
+                create_probe::<T>(
+                    simulation,
+                    center - config.capture_radius * axis,
+                    speed * axis,
+                    mass,
+                )
+            })
+            .collect();
+        for _ in 0..config.capture_steps {
+            simulation.step();
+        }
+        let captured = {
+            let positions = simulation.world.read_storage::<Position>();
+            speeds
+                .iter()
+                .zip(probes.iter())
+                .filter(|(_, probe)| match positions.get(**probe) {
+                    Some(pos) => (pos.pos.cast::<f64>() - center).norm() < config.capture_radius,
+                    None => false,
+                })
+                .map(|(speed, _)| *speed)
+                .fold(0.0, f64::max)
+        };
+        delete_probes(simulation, &probes);
+        captured
+    });
+
+    let atoms: Vec<Entity> = (0..config.temperature_atoms)
+        .map(|_| create_probe::<T>(simulation, center, Vector3::new(0.0, 0.0, 0.0), mass))
+        .collect();
+    for _ in 0..config.temperature_steps {
+        simulation.step();
+    }
+    let temperature = {
+        let velocities = simulation.world.read_storage::<Velocity>();
+        let samples: Vec<(Vector3<f64>, f64)> = atoms
+            .iter()
+            .filter_map(|atom| velocities.get(*atom))
+            .map(|vel| (vel.vel.cast::<f64>(), mass))
+            .collect();
+        measure_temperature(&samples)
+    };
+    delete_probes(simulation, &atoms);
+
+    MotCharacteristics {
+        spring_constant,
+        damping_coefficient,
+        trap_frequency: (spring_constant / (mass * AMU)).sqrt() / (2.0 * PI),
+        damping_time: mass * AMU / damping_coefficient,
+        capture_velocity,
+        temperature,
+    }
+}
+
+pub(super) fn create_probe<T>(
+    simulation: &mut Simulation,
+    pos: Vector3<f64>,
+    vel: Vector3<f64>,
+    mass: f64,
+) -> Entity
+where
+    T: TransitionComponent,
+{
+    simulation
+        .world
+        .create_entity()
+        .with(Position { pos: pos.cast() })
+        .with(Velocity { vel: vel.cast() })
+        .with(Force::new())
+        .with(Mass { value: mass })
+        .with(Atom)
+        .with(T::default())
+        .with(NewlyCreated)
+        .build()
+}
+
+pub(super) fn delete_probes(simulation: &mut Simulation, probes: &[Entity]) {
+    let entities = simulation.world.entities();
+    for probe in probes.iter() {
+        if entities.is_alive(*probe) {
+            entities
+                .delete(*probe)
+                .expect("Could not delete probe atom.");
+        }
+    }
+    drop(entities);
+    simulation.world.maintain();
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use crate::integrator::Timestep;
+    use crate::laser::gaussian::GaussianBeam;
+    use crate::laser::LaserPlugin;
+    use crate::laser_cooling::force::EmissionForceOption;
+    use crate::laser_cooling::photons_scattered::ScatteringFluctuationsOption;
+    use crate::laser_cooling::{CoolingLight, LaserCoolingPlugin};
+    use crate::magnetic::quadrupole::QuadrupoleField3D;
+    use crate::simulation::SimulationBuilder;
+    use crate::species::Rubidium87_780D2;
+
+    #[test]
+    fn test_characterize_rubidium_mot() {
+        let mut sim_builder = SimulationBuilder::default();
+        sim_builder.add_plugin(LaserPlugin::<6>);
+        sim_builder.add_plugin(LaserCoolingPlugin::<Rubidium87_780D2, 6>::default());
+        let mut sim = sim_builder.build();
+        sim.world.insert(Timestep { delta: 2.0e-6 });
+        sim.world.insert(EmissionForceOption::default());
+        sim.world.insert(ScatteringFluctuationsOption::default());
+
+        sim.world
+            .create_entity()
+            .with(QuadrupoleField3D::gauss_per_cm(10.0, Vector3::z()))
+            .with(Position::new())
+            .build();
+        let e_radius = 0.01;
+        for (direction, polarization) in [
+            (Vector3::x(), 1),
+            (-Vector3::x(), 1),
+            (Vector3::y(), 1),
+            (-Vector3::y(), 1),
+            (Vector3::z(), -1),
+            (-Vector3::z(), -1),
+        ]
+        .iter()
+        {
+            sim.world
+                .create_entity()
+                .with(GaussianBeam {
+                    intersection: Vector3::new(0.0, 0.0, 0.0),
+                    e_radius,
+                    power: 0.01,
+                    direction: *direction,
+                    rayleigh_range: f64::INFINITY,
+                    ellipticity: 0.0,
+                    focus_offset: 0.0,
+                })
+                .with(CoolingLight::for_transition::<Rubidium87_780D2>(
+                    -12.0,
+                    *polarization,
+                ))
+                .build();
+        }
+
+        let config = MotCharacterizationConfig {
+            linear_response: LinearResponseConfig {
+                center: Vector3::new(0.0, 0.0, 0.0),
+                displacement: 1.0e-5,
+                velocity: 1.0e-2,
+                mass: 87.0,
+            },
+            axis: Vector3::z(),
+            capture_radius: e_radius,
+            max_capture_velocity: 50.0,
+            capture_probes: 25,
+            capture_steps: 2000,
+            temperature_atoms: 50,
+            temperature_steps: 1000,
+        };
+        let characteristics = characterize_mot::<Rubidium87_780D2>(&mut sim, &config);
+
+        assert!(characteristics.spring_constant > 0.0);
+        assert!(characteristics.damping_coefficient > 0.0);
+        assert!(
+            characteristics.trap_frequency > 10.0 && characteristics.trap_frequency < 1.0e4,
+            "trap frequency {} Hz",
+            characteristics.trap_frequency
+        );
+        assert!(
+            characteristics.damping_time > 1.0e-5 && characteristics.damping_time < 1.0e-2,
+            "damping time {} s",
+            characteristics.damping_time
+        );
+        assert!(
+            characteristics.capture_velocity > 1.0 && characteristics.capture_velocity < 50.0,
+            "capture velocity {} m/s",
+            characteristics.capture_velocity
+        );
+        // Above the Doppler limit of 146 uK, but far below the temperature of a vapour.
+        assert!(
+            characteristics.temperature > 5.0e-5 && characteristics.temperature < 1.0e-2,
+            "temperature {} K",
+            characteristics.temperature
+        );
+
+        // The probe atoms are removed.
+        assert_eq!(sim.world.read_storage::<Atom>().join().count(), 0);
+    }
+}
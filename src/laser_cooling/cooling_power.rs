@@ -0,0 +1,142 @@
+//! Measures the rate at which the scattering force does work on the atoms.
+//!
+//! The power `F.v` of the absorption force, summed over all atoms, is an instantaneous diagnostic of the
+//! cooling rate. A negative total indicates that the cooling beams are removing energy from the cloud. The
+//! force of spontaneous emission averages to zero, and heats the cloud through its fluctuations instead, so it
+//! is not included.
+
+use std::marker::PhantomData;
+
+use super::force::absorption_force;
+use super::photons_scattered::ActualPhotonsScatteredVector;
+use super::repump::Dark;
+use super::transition::TransitionComponent;
+use super::wavevector::CoolingWavevectors;
+use crate::atom::Velocity;
+use crate::integrator::Timestep;
+use specs::prelude::*;
+
+/// A resource that holds the power of the absorption force summed over all atoms, see [crate::laser_cooling::cooling_power].
+///
+/// Insert this resource into the world to enable the calculation.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CoolingPower {
+    /// The total power, in SI units of W. Negative values indicate net cooling.
+    pub total: f64,
+}
+
+/// Calculates the [CoolingPower] each step, if the resource is present.
+#[derive(Default)]
+pub struct CalculateCoolingPowerSystem<T, const N: usize>(PhantomData<T>)
+where
+    T: TransitionComponent;
+
+impl<'a, T, const N: usize> System<'a> for CalculateCoolingPowerSystem<T, N>
+where
+    T: TransitionComponent,
+{
+    type SystemData = (
+        ReadExpect<'a, CoolingWavevectors>,
+        ReadStorage<'a, ActualPhotonsScatteredVector<T, N>>,
+        ReadStorage<'a, Velocity>,
+        ReadStorage<'a, Dark>,
+        ReadExpect<'a, Timestep>,
+        Option<Write<'a, CoolingPower>>,
+    );
+
+    fn run(
+        &mut self,
+        (wavevectors, actual_scattered_vector, velocities, dark, timestep, power): Self::SystemData,
+    ) {
+        let mut power = match power {
+            Some(power) => power,
+            None => return,
+        };
+        power.total = (&actual_scattered_vector, &velocities, !&dark)
+            .join()
+            .map(|(scattered, vel, _)| {
+                absorption_force(scattered, &wavevectors, timestep.delta)
+                    .dot(&vel.vel.cast::<f64>())
+            })
+            .sum();
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use crate::atom::{Atom, Force, Mass, Position};
+    use crate::initiate::NewlyCreated;
+    use crate::laser::gaussian::GaussianBeam;
+    use crate::laser::LaserPlugin;
+    use crate::laser_cooling::force::EmissionForceOption;
+    use crate::laser_cooling::{CoolingLight, LaserCoolingPlugin};
+    use crate::simulation::SimulationBuilder;
+    use crate::species::Rubidium87_780D2;
+    use nalgebra::Vector3;
+
+    #[test]
+    fn test_cooling_power_of_damped_atom() {
+        let mut sim_builder = SimulationBuilder::default();
+        sim_builder.add_plugin(LaserPlugin::<1>);
+        sim_builder.add_plugin(LaserCoolingPlugin::<Rubidium87_780D2, 1>::default());
+        let mut sim = sim_builder.build();
+        sim.world.insert(Timestep { delta: 1.0e-6 });
+        sim.world.insert(EmissionForceOption::Off);
+        sim.world.insert(CoolingPower::default());
+
+        // A red-detuned beam opposing the motion of the atom.
+        sim.world
+            .create_entity()
+            .with(GaussianBeam {
+                intersection: Vector3::new(0.0, 0.0, 0.0),
+                e_radius: 0.01,
+                power: 0.01,
+                direction: -Vector3::x(),
+                rayleigh_range: f64::INFINITY,
+                ellipticity: 0.0,
+                focus_offset: 0.0,
+            })
+            .with(CoolingLight::for_transition::<Rubidium87_780D2>(-6.0, 1))
+            .build();
+        let atom = sim
+            .world
+            .create_entity()
+            .with(Position {
+                pos: Vector3::new(0.0, 0.0, 0.0).cast(),
+            })
+            .with(Velocity {
+                vel: Vector3::new(5.0, 0.0, 0.0).cast(),
+            })
+            .with(Force::new())
+            .with(Mass { value: 87.0 })
+            .with(Atom)
+            .with(Rubidium87_780D2)
+            .with(NewlyCreated)
+            .build();
+
+        for _ in 0..10 {
+            let velocity = sim
+                .world
+                .read_storage::<Velocity>()
+                .get(atom)
+                .unwrap()
+                .vel
+                .cast::<f64>();
+            sim.step();
+            // The absorption force is the only force on the atom.
+            let force = sim
+                .world
+                .read_storage::<Force>()
+                .get(atom)
+                .unwrap()
+                .force
+                .cast::<f64>();
+            let power = sim.world.read_resource::<CoolingPower>().total;
+            assert!((power - force.dot(&velocity)).abs() <= 1e-9 * power.abs());
+        }
+        let power = sim.world.read_resource::<CoolingPower>().total;
+        assert!(power < 0.0);
+    }
+}
@@ -0,0 +1,233 @@
+//! Maps the fluorescence of a magneto-optical trap against the cooling detuning.
+//!
+//! [detuning_scan] reproduces the detuning scans made in experiments: for each detuning, the cooling light is
+//! retuned, probe atoms are loaded into the trap, and the fluorescence of the captured atoms is recorded once the
+//! cloud has settled. Close to resonance few atoms are captured, while far from resonance the captured atoms
+//! scatter few photons, so the fluorescence peaks at an intermediate red detuning.
+
+use nalgebra::Vector3;
+use specs::prelude::*;
+
+use super::characterize::{create_probe, delete_probes};
+use super::linear_response::with_mean_forces;
+use super::photons_scattered::TotalPhotonsScattered;
+use super::transition::TransitionComponent;
+use super::CoolingLight;
+use crate::atom::Position;
+use crate::integrator::Timestep;
+use crate::simulation::Simulation;
+
+/// Configures the measurements made by [detuning_scan].
+pub struct DetuningScanConfig {
+    /// Centre of the trap, in SI units of m.
+    pub center: Vector3<f64>,
+    /// Axis along which the probe atoms are launched.
+    pub axis: Vector3<f64>,
+    /// Mass of the probe atoms, in atomic mass units.
+    pub mass: f64,
+    /// Distance from the centre at which probe atoms are launched, in SI units of m.
+    ///
+    /// A probe atom is captured if it is within this distance of the centre at the end of the run.
+    pub capture_radius: f64,
+    /// Largest launch speed of the probe atoms, in SI units of m/s.
+    pub max_velocity: f64,
+    /// Number of probe atoms, launched with speeds evenly spaced up to `max_velocity`.
+    pub probes: usize,
+    /// Number of steps simulated at each detuning before the fluorescence is recorded.
+    ///
+    /// The steps should be long enough for the captured atoms to settle at the centre of the trap.
+    pub steps: usize,
+}
+
+/// Records the steady-state fluorescence of a MOT at each of the given cooling detunings.
+///
+/// For each detuning, every [CoolingLight] in the simulation is retuned to `detuning` from the transition `T`,
+/// keeping its polarization. Probe atoms are launched from `capture_radius` on the negative side of the centre
+/// towards the centre, with speeds up to `max_velocity`, and held for `steps`. The fluorescence is the total
+/// scattering rate of the probe atoms that remain within `capture_radius` of the centre, so that it is
+/// proportional to the steady-state number of captured atoms.
+///
+/// The fluctuations of the scattering and emission forces are disabled during the scan, so that the result is
+/// deterministic. The probe atoms are deleted after each detuning, and the cooling light is restored afterwards.
+///
+/// Returns the `(detuning, fluorescence)` pairs, with the detuning in MHz and the fluorescence in photons/s.
+///
+/// # Generic Arguments
+///
+/// * `T`: The laser cooling transition of the probe atoms.
+pub fn detuning_scan<T>(
+    simulation: &mut Simulation,
+    config: &DetuningScanConfig,
+    detunings: &[f64],
+) -> Vec<(f64, f64)>
+where
+    T: TransitionComponent,
+{
+    let axis = config.axis.normalize();
+    let original: Vec<(Entity, CoolingLight)> = {
+        let entities = simulation.world.entities();
+        let lights = simulation.world.read_storage::<CoolingLight>();
+        (&entities, &lights)
+            .join()
+            .map(|(entity, light)| (entity, *light))
+            .collect()
+    };
+
+    let scan: Vec<(f64, f64)> = with_mean_forces(simulation, |simulation| {
+        detunings
+            .iter()
+            .map(|&detuning| {
+                {
+                    let mut lights = simulation.world.write_storage::<CoolingLight>();
+                    for light in (&mut lights).join() {
+                        *light = CoolingLight::for_transition::<T>(detuning, light.polarization);
+                    }
+                }
+                (detuning, fluorescence::<T>(simulation, config, axis))
+            })
+            .collect()
+    });
+
+    let mut lights = simulation.world.write_storage::<CoolingLight>();
+    for (entity, light) in original {
+        lights
+            .insert(entity, light)
+            .expect("Could not restore cooling light.");
+    }
+    scan
+}
+
+fn fluorescence<T>(simulation: &mut Simulation, config: &DetuningScanConfig, axis: Vector3<f64>) -> f64
+where
+    T: TransitionComponent,
+{
+    let probes: Vec<Entity> = (1..=config.probes)
+        .map(|i| {
+            let speed = i as f64 * config.max_velocity / config.probes as f64;
+            create_probe::<T>(
+                simulation,
+                config.center - config.capture_radius * axis,
+                speed * axis,
+                config.mass,
+            )
+        })
+        .collect();
+    for _ in 0..config.steps {
+        simulation.step();
+    }
+    let rate = {
+        let positions = simulation.world.read_storage::<Position>();
+        let scattered = simulation.world.read_storage::<TotalPhotonsScattered<T>>();
+        let timestep = simulation.world.read_resource::<Timestep>();
+        probes
+            .iter()
+            .filter_map(|probe| match (positions.get(*probe), scattered.get(*probe)) {
+                (Some(pos), Some(scattered))
+                    if (pos.pos.cast::<f64>() - config.center).norm() < config.capture_radius =>
+                {
+                    Some(scattered.total / timestep.delta)
+                }
+                _ => None,
+            })
+            .sum()
+    };
+    delete_probes(simulation, &probes);
+    rate
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use crate::atom::Atom;
+    use crate::laser::gaussian::GaussianBeam;
+    use crate::laser::LaserPlugin;
+    use crate::laser_cooling::force::EmissionForceOption;
+    use crate::laser_cooling::photons_scattered::ScatteringFluctuationsOption;
+    use crate::laser_cooling::transition::AtomicTransition;
+    use crate::laser_cooling::LaserCoolingPlugin;
+    use crate::magnetic::quadrupole::QuadrupoleField3D;
+    use crate::simulation::SimulationBuilder;
+    use crate::species::Rubidium87_780D2;
+
+    #[test]
+    fn test_detuning_scan_peaks_at_red_detuning() {
+        let mut sim_builder = SimulationBuilder::default();
+        sim_builder.add_plugin(LaserPlugin::<6>);
+        sim_builder.add_plugin(LaserCoolingPlugin::<Rubidium87_780D2, 6>::default());
+        let mut sim = sim_builder.build();
+        sim.world.insert(Timestep { delta: 2.0e-6 });
+        sim.world.insert(EmissionForceOption::default());
+        sim.world.insert(ScatteringFluctuationsOption::default());
+
+        sim.world
+            .create_entity()
+            .with(QuadrupoleField3D::gauss_per_cm(10.0, Vector3::z()))
+            .with(Position::new())
+            .build();
+        let e_radius = 0.01;
+        for (direction, polarization) in [
+            (Vector3::x(), 1),
+            (-Vector3::x(), 1),
+            (Vector3::y(), 1),
+            (-Vector3::y(), 1),
+            (Vector3::z(), -1),
+            (-Vector3::z(), -1),
+        ]
+        .iter()
+        {
+            sim.world
+                .create_entity()
+                .with(GaussianBeam {
+                    intersection: Vector3::new(0.0, 0.0, 0.0),
+                    e_radius,
+                    power: 0.01,
+                    direction: *direction,
+                    rayleigh_range: f64::INFINITY,
+                    ellipticity: 0.0,
+                    focus_offset: 0.0,
+                })
+                .with(CoolingLight::for_transition::<Rubidium87_780D2>(
+                    -12.0,
+                    *polarization,
+                ))
+                .build();
+        }
+
+        let config = DetuningScanConfig {
+            center: Vector3::new(0.0, 0.0, 0.0),
+            axis: Vector3::z(),
+            mass: 87.0,
+            capture_radius: e_radius,
+            max_velocity: 50.0,
+            probes: 25,
+            steps: 2000,
+        };
+        let linewidth = Rubidium87_780D2::linewidth() / 1.0e6;
+        let detunings: Vec<f64> = [0.25, 0.5, 1.0, 1.5, 2.0, 3.0, 4.0, 6.0, 8.0, 12.0]
+            .iter()
+            .map(|gammas| -gammas * linewidth)
+            .collect();
+        let scan = detuning_scan::<Rubidium87_780D2>(&mut sim, &config, &detunings);
+
+        assert_eq!(scan.len(), detunings.len());
+        let (peak, _) = scan
+            .iter()
+            .cloned()
+            .fold((0.0, f64::MIN), |best, point| if point.1 > best.1 { point } else { best });
+        assert!(
+            peak <= -1.0 * linewidth && peak >= -6.0 * linewidth,
+            "fluorescence peaks at {} MHz",
+            peak
+        );
+        // Far from resonance, the few captured atoms scatter little light.
+        assert!(scan.last().unwrap().1 < scan.iter().map(|(_, f)| *f).fold(0.0, f64::max));
+
+        // The probe atoms are removed, and the cooling light is restored.
+        assert_eq!(sim.world.read_storage::<Atom>().join().count(), 0);
+        let expected = CoolingLight::for_transition::<Rubidium87_780D2>(-12.0, 1).wavelength;
+        for light in sim.world.read_storage::<CoolingLight>().join() {
+            assert_eq!(light.wavelength, expected);
+        }
+    }
+}
@@ -3,14 +3,11 @@ extern crate rayon;
 extern crate serde;
 use specs::prelude::*;
 
-use super::CoolingLight;
+use super::wavevector::CoolingWavevectors;
 use crate::atom::Velocity;
-use crate::laser::gaussian::GaussianBeam;
-use crate::laser::index::LaserIndex;
+use crate::parallel::{ForceSerial, MaybeParJoin};
 use serde::Serialize;
-use specs::{Component, Join, ReadStorage, System, VecStorage, WriteStorage};
-
-const LASER_CACHE_SIZE: usize = 16;
+use specs::{Component, ReadStorage, System, VecStorage, WriteStorage};
 
 /// Represents the Dopplershift of the atom with respect to each beam due to the atom velocity
 #[derive(Clone, Copy, Serialize)]
@@ -30,48 +27,26 @@ impl Default for DopplerShiftSampler {
 
 /// This system calculates the Doppler shift for each atom in each cooling beam.
 ///
-/// The result is stored in `DopplerShiftSamplers`
+/// The result is stored in `DopplerShiftSamplers`. The wavevectors of the cooling beams are read from
+/// the `CoolingWavevectors` resource.
 pub struct CalculateDopplerShiftSystem<const N: usize>;
 
 impl<'a, const N: usize> System<'a> for CalculateDopplerShiftSystem<N> {
     type SystemData = (
-        ReadStorage<'a, CoolingLight>,
-        ReadStorage<'a, LaserIndex>,
-        ReadStorage<'a, GaussianBeam>,
+        ReadExpect<'a, CoolingWavevectors>,
         WriteStorage<'a, DopplerShiftSamplers<N>>,
         ReadStorage<'a, Velocity>,
+        Option<Read<'a, ForceSerial>>,
     );
 
-    fn run(&mut self, (cooling, indices, gaussian, mut samplers, velocities): Self::SystemData) {
-        use rayon::prelude::*;
-
-        // There are typically only a small number of lasers in a simulation.
-        // For a speedup, cache the required components into thread memory,
-        // so they can be distributed to parallel workers during the atom loop.
-        type CachedLaser = (CoolingLight, LaserIndex, GaussianBeam);
-        let laser_cache: Vec<CachedLaser> = (&cooling, &indices, &gaussian)
-            .join()
-            .map(|(cooling, index, gaussian)| (*cooling, *index, *gaussian))
-            .collect();
-
-        // Perform the iteration over atoms, `LASER_CACHE_SIZE` at a time.
-        for base_index in (0..laser_cache.len()).step_by(LASER_CACHE_SIZE) {
-            let max_index = laser_cache.len().min(base_index + LASER_CACHE_SIZE);
-            let slice = &laser_cache[base_index..max_index];
-            let mut laser_array = vec![laser_cache[0]; LASER_CACHE_SIZE];
-            laser_array[..max_index].copy_from_slice(slice);
-            let number_in_iteration = slice.len();
-
-            (&mut samplers, &velocities)
-                .par_join()
-                .for_each(|(sampler, vel)| {
-                    for (cooling, index, gaussian) in laser_array.iter().take(number_in_iteration) {
-                        sampler.contents[index.index].doppler_shift = vel
-                            .vel
-                            .dot(&(gaussian.direction.normalize() * cooling.wavenumber()));
-                    }
-                })
-        }
+    fn run(&mut self, (wavevectors, mut samplers, velocities, force_serial): Self::SystemData) {
+        // The wavevector of each beam is cached once per step, see `CoolingWavevectors`.
+        (&mut samplers, &velocities)
+            .maybe_par_for_each(force_serial.is_some(), |(sampler, vel)| {
+                for (index, k_vector) in wavevectors.wavevectors.iter() {
+                    sampler.contents[*index].doppler_shift = vel.vel.cast::<f64>().dot(k_vector);
+                }
+            })
     }
 }
 
@@ -95,11 +70,9 @@ impl<const N: usize> Component for DopplerShiftSamplers<N> {
 pub struct InitialiseDopplerShiftSamplersSystem<const N: usize>;
 
 impl<'a, const N: usize> System<'a> for InitialiseDopplerShiftSamplersSystem<N> {
-    type SystemData = (WriteStorage<'a, DopplerShiftSamplers<N>>,);
-    fn run(&mut self, (mut samplers,): Self::SystemData) {
-        use rayon::prelude::*;
-
-        (&mut samplers).par_join().for_each(|mut sampler| {
+    type SystemData = (WriteStorage<'a, DopplerShiftSamplers<N>>, Option<Read<'a, ForceSerial>>);
+    fn run(&mut self, (mut samplers, force_serial): Self::SystemData) {
+        (&mut samplers).maybe_par_for_each(force_serial.is_some(), |mut sampler| {
             sampler.contents = [DopplerShiftSampler::default(); N];
         });
     }
@@ -110,6 +83,10 @@ pub mod tests {
 
     use super::*;
     use crate::constant::PI;
+    use crate::laser::gaussian::GaussianBeam;
+    use crate::laser::index::LaserIndex;
+    use crate::laser_cooling::standing_wave::BeamConfiguration;
+    use crate::laser_cooling::wavevector::CacheCoolingWavevectorsSystem;
     use crate::laser_cooling::CoolingLight;
     use assert_approx_eq::assert_approx_eq;
     extern crate nalgebra;
@@ -122,6 +99,7 @@ pub mod tests {
         test_world.register::<LaserIndex>();
         test_world.register::<CoolingLight>();
         test_world.register::<GaussianBeam>();
+        test_world.register::<BeamConfiguration>();
         test_world.register::<Velocity>();
         test_world.register::<DopplerShiftSamplers<{ DEFAULT_BEAM_LIMIT }>>();
 
@@ -143,6 +121,7 @@ pub mod tests {
                 power: 1.0,
                 rayleigh_range: gaussian::calculate_rayleigh_range(&wavelength, &2.0),
                 ellipticity: 0.0,
+                focus_offset: 0.0,
             })
             .build();
 
@@ -150,13 +129,16 @@ pub mod tests {
         let sampler1 = test_world
             .create_entity()
             .with(Velocity {
-                vel: Vector3::new(atom_velocity, 0.0, 0.0),
+                vel: Vector3::new(atom_velocity, 0.0, 0.0).cast(),
             })
             .with(DopplerShiftSamplers {
                 contents: [DopplerShiftSampler::default(); crate::laser::DEFAULT_BEAM_LIMIT],
             })
             .build();
 
+        let mut cache_system = CacheCoolingWavevectorsSystem;
+        System::setup(&mut cache_system, &mut test_world);
+        cache_system.run_now(&test_world);
         let mut system = CalculateDopplerShiftSystem::<{ DEFAULT_BEAM_LIMIT }>;
         system.run_now(&test_world);
         test_world.maintain();
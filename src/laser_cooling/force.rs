@@ -2,91 +2,85 @@
 
 use std::marker::PhantomData;
 
-use super::CoolingLight;
 use super::transition::{TransitionComponent};
+use super::wavevector::CoolingWavevectors;
 use crate::constant;
-use crate::laser::gaussian::GaussianBeam;
-use crate::laser::index::LaserIndex;
 use crate::laser_cooling::photons_scattered::ActualPhotonsScatteredVector;
+use crate::laser_cooling::scattering::force_per_beam;
 use nalgebra::Vector3;
 use rand_distr;
 use rand_distr::{Distribution, Normal, UnitSphere};
-use rayon;
 
 use specs::prelude::*;
 
 use crate::atom::Force;
-use crate::constant::HBAR;
 use crate::integrator::Timestep;
 
 use crate::laser_cooling::repump::*;
-
-const LASER_CACHE_SIZE: usize = 16;
+use crate::parallel::{ForceSerial, MaybeParJoin};
+use crate::rng::{DeterministicRng, RngStreams};
 
 /// This sytem calculates the forces from absorbing photons from the CoolingLight entities.
 ///
 /// The system assumes that the `ActualPhotonsScatteredVector` for each atom
-/// s already populated with the correct terms. Furthermore, it is assumed that a
-/// `CoolingLightIndex` is present and assigned for all cooling lasers, with an index
+/// s already populated with the correct terms. Furthermore, it is assumed that the
+/// wavevectors of the cooling lasers have been cached in the `CoolingWavevectors` resource, with indices
 /// corresponding to the entries in the `ActualPhotonsScatteredVector` vector.
 #[derive(Default)]
 pub struct CalculateAbsorptionForcesSystem<T, const N: usize>(PhantomData<T>) where T : TransitionComponent;
 
 impl<'a, T, const N: usize> System<'a> for CalculateAbsorptionForcesSystem<T, N> where T : TransitionComponent {
     type SystemData = (
-        ReadStorage<'a, LaserIndex>,
-        ReadStorage<'a, CoolingLight>,
-        ReadStorage<'a, GaussianBeam>,
+        ReadExpect<'a, CoolingWavevectors>,
         ReadStorage<'a, ActualPhotonsScatteredVector<T, N>>,
         WriteStorage<'a, Force>,
         ReadExpect<'a, Timestep>,
         ReadStorage<'a, Dark>,
+        Option<Read<'a, ForceSerial>>,
     );
 
     fn run(
         &mut self,
         (
-            cooling_index,
-            cooling_light,
-            gaussian_beam,
+            wavevectors,
             actual_scattered_vector,
             mut forces,
             timestep,
             _dark,
+            force_serial,
         ): Self::SystemData,
     ) {
-        use rayon::prelude::*;
-
-        // There are typically only a small number of lasers in a simulation.
-        // For a speedup, cache the required components into thread memory,
-        // so they can be distributed to parallel workers during the atom loop.
-        type CachedLaser = (CoolingLight, LaserIndex, GaussianBeam);
-        let laser_cache: Vec<CachedLaser> = (&cooling_light, &cooling_index, &gaussian_beam)
-            .join()
-            .map(|(cooling, index, gaussian)| (*cooling, *index, *gaussian))
-            .collect();
-
-        // Perform the iteration over atoms, `LASER_CACHE_SIZE` at a time.
-        for base_index in (0..laser_cache.len()).step_by(LASER_CACHE_SIZE) {
-            let max_index = laser_cache.len().min(base_index + LASER_CACHE_SIZE);
-            let slice = &laser_cache[base_index..max_index];
-            let mut laser_array = vec![laser_cache[0]; LASER_CACHE_SIZE];
-            laser_array[..max_index].copy_from_slice(slice);
-            let number_in_iteration = slice.len();
-
-            (&actual_scattered_vector, &mut forces, !&_dark)
-                .par_join()
-                .for_each(|(scattered, force, _)| {
-                    for (cooling, index, gaussian) in laser_array.iter().take(number_in_iteration) {
-                        let new_force = scattered.contents[index.index].scattered * HBAR
-                            / timestep.delta
-                            * gaussian.direction.normalize()
-                            * cooling.wavenumber();
-                        force.force += new_force;
-                    }
-                })
-        }
+        // The wavevector of each beam is cached once per step, see `CoolingWavevectors`.
+        (&actual_scattered_vector, &mut forces, !&_dark)
+            .maybe_par_for_each(force_serial.is_some(), |(scattered, force, _)| {
+                force.force +=
+                    absorption_force(scattered, &wavevectors, timestep.delta).cast();
+            })
+    }
+}
+
+/// Calculates the total force from absorbing photons from the cooling beams, in SI units of N.
+///
+/// # Arguments
+///
+/// `scattered`: the photons scattered from each beam by the atom during the step.
+///
+/// `wavevectors`: the cached wavevectors of the cooling beams.
+///
+/// `timestep`: duration of the step, in SI units of s.
+pub fn absorption_force<T, const N: usize>(
+    scattered: &ActualPhotonsScatteredVector<T, N>,
+    wavevectors: &CoolingWavevectors,
+    timestep: f64,
+) -> Vector3<f64>
+where
+    T: TransitionComponent,
+{
+    let mut force = Vector3::new(0.0, 0.0, 0.0);
+    for (index, k_vector) in wavevectors.wavevectors.iter() {
+        force += force_per_beam(scattered.contents[*index].scattered / timestep, *k_vector);
     }
+    force
 }
 
 /// A resource that indicates that the simulation should apply random forces
@@ -123,62 +117,78 @@ pub struct EmissionForceConfiguration {
 ///
 /// Uses an internal threshold of 5 to decide if the random vektor is iteratively
 /// produced or derived by random-walk formula and a single random unit vector.
+///
+/// The emission directions are drawn from the [DeterministicRng] resource if it is present, see [crate::rng].
 #[derive(Default)]
 pub struct ApplyEmissionForceSystem<T, const N: usize>(PhantomData<T>) where T : TransitionComponent;
 
 impl<'a, T, const N: usize> System<'a> for ApplyEmissionForceSystem<T, N> where T : TransitionComponent {
     type SystemData = (
         Option<Read<'a, EmissionForceOption>>,
+        Entities<'a>,
         WriteStorage<'a, Force>,
         ReadStorage<'a, ActualPhotonsScatteredVector<T, N>>,
         ReadStorage<'a, T>,
         ReadExpect<'a, Timestep>,
+        Option<Write<'a, DeterministicRng>>,
+        Option<Read<'a, ForceSerial>>,
     );
 
     fn run(
         &mut self,
-        (rand_opt, mut force, actual_scattered_vector, transition, timestep): Self::SystemData,
+        (
+            rand_opt,
+            entities,
+            mut force,
+            actual_scattered_vector,
+            transition,
+            timestep,
+            mut deterministic,
+            force_serial,
+        ): Self::SystemData,
     ) {
-        use rayon::prelude::*;
-
         match rand_opt {
             None => (),
             Some(opt) => {
                 match *opt {
                     EmissionForceOption::Off => {}
                     EmissionForceOption::On(configuration) => {
-                        (&mut force, &transition, &actual_scattered_vector)
-                            .par_join()
-                            .for_each(|(force, _atom_info, kick)| {
-                                let total: u64 = kick.calculate_total_scattered();
-                                let mut rng = rand::thread_rng();
-                                let omega = 2.0 * constant::PI * T::frequency();
-                                let force_one_kick =
-                                    constant::HBAR * omega / constant::C / timestep.delta;
-                                if total > configuration.explicit_threshold {
-                                    // see HSIUNG, HSIUNG,GORDUS,1960, A Closed General Solution of the Probability Distribution Function for
-                                    //Three-Dimensional Random Walk Processes*
-                                    let normal = Normal::new(
-                                        0.0,
-                                        (total as f64 * force_one_kick.powf(2.0) / 3.0).powf(0.5),
-                                    )
-                                    .unwrap();
-
-                                    let force_n_kicks = Vector3::new(
-                                        normal.sample(&mut rng),
-                                        normal.sample(&mut rng),
-                                        normal.sample(&mut rng),
-                                    );
-                                    force.force += force_n_kicks;
-                                } else {
-                                    // explicit random walk implementation
-                                    for _i in 0..total {
-                                        let v: [f64; 3] = UnitSphere.sample(&mut rng);
-                                        force.force +=
-                                            force_one_kick * Vector3::new(v[0], v[1], v[2]);
+                        let streams = RngStreams::new(deterministic.as_deref_mut());
+                        (&entities, &mut force, &transition, &actual_scattered_vector)
+                            .maybe_par_for_each(
+                                force_serial.is_some(),
+                                |(entity, force, _atom_info, kick)| {
+                                    let total: u64 = kick.calculate_total_scattered();
+                                    let mut rng = streams.entity_stream(entity);
+                                    let omega = 2.0 * constant::PI * T::frequency();
+                                    let force_one_kick =
+                                        constant::HBAR * omega / constant::C / timestep.delta;
+                                    if total > configuration.explicit_threshold {
+                                        // see HSIUNG, HSIUNG,GORDUS,1960, A Closed General Solution of the Probability Distribution Function for
+                                        //Three-Dimensional Random Walk Processes*
+                                        let normal = Normal::new(
+                                            0.0,
+                                            (total as f64 * force_one_kick.powf(2.0) / 3.0).powf(0.5),
+                                        )
+                                        .unwrap();
+
+                                        let force_n_kicks = Vector3::new(
+                                            normal.sample(&mut rng),
+                                            normal.sample(&mut rng),
+                                            normal.sample(&mut rng),
+                                        );
+                                        force.force += force_n_kicks.cast();
+                                    } else {
+                                        // explicit random walk implementation
+                                        for _i in 0..total {
+                                            let v: [f64; 3] = UnitSphere.sample(&mut rng);
+                                            force.force += (force_one_kick
+                                                * Vector3::new(v[0], v[1], v[2]))
+                                            .cast();
+                                        }
                                     }
-                                }
-                            });
+                                },
+                            );
                     }
                 }
             }
@@ -188,10 +198,13 @@ impl<'a, T, const N: usize> System<'a> for ApplyEmissionForceSystem<T, N> where
 
 #[cfg(test)]
 pub mod tests {
-    use super::CoolingLight;
+    use super::super::CoolingLight;
     use super::*;
     use crate::constant::{HBAR, PI};
+    use crate::laser::gaussian::GaussianBeam;
     use crate::laser::index::LaserIndex;
+    use crate::laser_cooling::standing_wave::BeamConfiguration;
+    use crate::laser_cooling::wavevector::CacheCoolingWavevectorsSystem;
     use crate::laser_cooling::photons_scattered::ActualPhotonsScattered;
     use crate::laser_cooling::transition::AtomicTransition;
     use crate::species::Strontium88_461;
@@ -210,6 +223,7 @@ pub mod tests {
         test_world.register::<LaserIndex>();
         test_world.register::<CoolingLight>();
         test_world.register::<GaussianBeam>();
+        test_world.register::<BeamConfiguration>();
         test_world.register::<ActualPhotonsScatteredVector<Strontium88_461, { DEFAULT_BEAM_LIMIT }>>();
         test_world.register::<Force>();
         test_world.register::<Dark>();
@@ -233,6 +247,7 @@ pub mod tests {
                 power: 1.0,
                 rayleigh_range: gaussian::calculate_rayleigh_range(&wavelength, &2.0),
                 ellipticity: 0.0,
+                focus_offset: 0.0,
             })
             .build();
 
@@ -248,6 +263,9 @@ pub mod tests {
             .with(Force::new())
             .build();
 
+        let mut cache_system = CacheCoolingWavevectorsSystem;
+        System::setup(&mut cache_system, &mut test_world);
+        cache_system.run_now(&test_world);
         let mut system = CalculateAbsorptionForcesSystem::<Strontium88_461, { DEFAULT_BEAM_LIMIT }>::default();
         system.run_now(&test_world);
         test_world.maintain();
@@ -255,12 +273,171 @@ pub mod tests {
 
         let actual_force_x = number_scattered * HBAR * 2. * PI / wavelength / time_delta;
         assert_approx_eq!(
-            sampler_storage.get(atom1).expect("entity not found").force[0],
+            sampler_storage
+                .get(atom1)
+                .expect("entity not found")
+                .force
+                .cast::<f64>()[0],
             actual_force_x,
             1e-20_f64
         );
     }
 
+    /// Tests that the forces calculated using the cached wavevectors are identical to
+    /// those calculated from the beam directions for each atom.
+    #[test]
+    fn test_absorption_forces_match_uncached_calculation() {
+        let mut test_world = World::new();
+
+        let time_delta = 1.0e-5;
+
+        test_world.register::<LaserIndex>();
+        test_world.register::<CoolingLight>();
+        test_world.register::<GaussianBeam>();
+        test_world.register::<BeamConfiguration>();
+        test_world.register::<ActualPhotonsScatteredVector<Strontium88_461, { DEFAULT_BEAM_LIMIT }>>();
+        test_world.register::<Force>();
+        test_world.register::<Dark>();
+        test_world.insert(Timestep { delta: time_delta });
+
+        let wavelength = Strontium88_461::wavelength();
+        let directions = [
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(-1.0, 0.3, 0.0),
+            Vector3::new(0.2, 0.5, -2.0),
+        ];
+        for (i, direction) in directions.iter().enumerate() {
+            test_world
+                .create_entity()
+                .with(CoolingLight {
+                    polarization: 1,
+                    wavelength,
+                })
+                .with(LaserIndex {
+                    index: i,
+                    initiated: true,
+                })
+                .with(GaussianBeam {
+                    direction: *direction,
+                    intersection: Vector3::new(0.0, 0.0, 0.0),
+                    e_radius: 2.0,
+                    power: 1.0,
+                    rayleigh_range: gaussian::calculate_rayleigh_range(&wavelength, &2.0),
+                    ellipticity: 0.0,
+                    focus_offset: 0.0,
+                })
+                .build();
+        }
+
+        let mut contents = [ActualPhotonsScattered::<Strontium88_461>::default(); DEFAULT_BEAM_LIMIT];
+        for (i, aps) in contents.iter_mut().enumerate() {
+            aps.scattered = 1_000.0 * (i + 1) as f64;
+        }
+        let atom1 = test_world
+            .create_entity()
+            .with(ActualPhotonsScatteredVector { contents })
+            .with(Force::new())
+            .build();
+
+        let mut cache_system = CacheCoolingWavevectorsSystem;
+        System::setup(&mut cache_system, &mut test_world);
+        cache_system.run_now(&test_world);
+        let mut system = CalculateAbsorptionForcesSystem::<Strontium88_461, { DEFAULT_BEAM_LIMIT }>::default();
+        system.run_now(&test_world);
+        test_world.maintain();
+
+        let cooling = CoolingLight {
+            polarization: 1,
+            wavelength,
+        };
+        let mut expected = Vector3::new(0.0, 0.0, 0.0);
+        for (i, direction) in directions.iter().enumerate() {
+            expected += force_per_beam(
+                contents[i].scattered / time_delta,
+                direction.normalize() * cooling.wavenumber(),
+            );
+        }
+        let force = test_world
+            .read_storage::<Force>()
+            .get(atom1)
+            .expect("entity not found")
+            .force;
+        assert_eq!(force, expected.cast());
+    }
+
+    /// Tests that each beam pushes the atom with the wavevector of its own wavelength, as in a two-colour MOT.
+    #[test]
+    fn test_absorption_force_uses_wavelength_of_each_beam() {
+        let mut test_world = World::new();
+
+        let time_delta = 1.0e-5;
+
+        test_world.register::<LaserIndex>();
+        test_world.register::<CoolingLight>();
+        test_world.register::<GaussianBeam>();
+        test_world.register::<BeamConfiguration>();
+        test_world.register::<ActualPhotonsScatteredVector<Strontium88_461, { DEFAULT_BEAM_LIMIT }>>();
+        test_world.register::<Force>();
+        test_world.register::<Dark>();
+        test_world.insert(Timestep { delta: time_delta });
+
+        // Two counter-propagating beams, detuned by +-1 GHz from the transition.
+        let beams = [
+            (CoolingLight::for_transition::<Strontium88_461>(1000.0, 1), 1.0),
+            (CoolingLight::for_transition::<Strontium88_461>(-1000.0, 1), -1.0),
+        ];
+        for (i, (cooling, sign)) in beams.iter().enumerate() {
+            test_world
+                .create_entity()
+                .with(*cooling)
+                .with(LaserIndex {
+                    index: i,
+                    initiated: true,
+                })
+                .with(GaussianBeam {
+                    direction: Vector3::new(*sign, 0.0, 0.0),
+                    intersection: Vector3::new(0.0, 0.0, 0.0),
+                    e_radius: 2.0,
+                    power: 1.0,
+                    rayleigh_range: gaussian::calculate_rayleigh_range(&cooling.wavelength, &2.0),
+                    ellipticity: 0.0,
+                    focus_offset: 0.0,
+                })
+                .build();
+        }
+
+        let number_scattered = 500.0;
+        let mut aps = ActualPhotonsScattered::<Strontium88_461>::default();
+        aps.scattered = number_scattered;
+        let atom1 = test_world
+            .create_entity()
+            .with(ActualPhotonsScatteredVector {
+                contents: [aps; DEFAULT_BEAM_LIMIT],
+            })
+            .with(Force::new())
+            .build();
+
+        let mut cache_system = CacheCoolingWavevectorsSystem;
+        System::setup(&mut cache_system, &mut test_world);
+        cache_system.run_now(&test_world);
+        let mut system = CalculateAbsorptionForcesSystem::<Strontium88_461, { DEFAULT_BEAM_LIMIT }>::default();
+        system.run_now(&test_world);
+        test_world.maintain();
+
+        // Equal scattering from the two beams only cancels if both had the wavevector of the transition.
+        let (blue, red) = (beams[0].0.wavenumber(), beams[1].0.wavenumber());
+        assert!(blue > red);
+        let expected = number_scattered * HBAR * (blue - red) / time_delta;
+        let force = test_world
+            .read_storage::<Force>()
+            .get(atom1)
+            .expect("entity not found")
+            .force
+            .cast::<f64>();
+        assert_approx_eq!(force[0], expected, 1e-6 * expected);
+        assert_approx_eq!(force[1], 0.0, 1e-6 * expected);
+    }
+
     /// Tests the correct implementation of the `ApplyEmissionForceSystem`
     #[test]
     fn test_apply_emission_forces_system() {
@@ -300,6 +477,7 @@ pub mod tests {
                 .get(atom1)
                 .expect("entity not found")
                 .force
+                .cast::<f64>()
                 .norm(),
             max_force_total / 2.0,
             // the outcome is random and will be somewhere between 0 and max_force_total
@@ -0,0 +1,216 @@
+//! Measures the linear response of a magneto-optical trap.
+//!
+//! Close to the centre of a MOT, the mean radiation force is linear in the displacement and velocity of an atom,
+//! `F = -kappa x - beta v`. The spring constant `kappa` gives the trap frequency `sqrt(kappa / m)`, and the
+//! damping coefficient `beta` gives the damping time `m / beta`.
+
+use nalgebra::Vector3;
+use specs::prelude::*;
+
+use super::force::EmissionForceOption;
+use super::photons_scattered::ScatteringFluctuationsOption;
+use super::transition::TransitionComponent;
+use crate::atom::{Atom, Force, Mass, Position, Velocity};
+use crate::initiate::NewlyCreated;
+use crate::integrator::Pinned;
+use crate::simulation::Simulation;
+
+/// Number of steps simulated to calculate the forces on newly created probe atoms.
+const PROBE_STEPS: usize = 2;
+
+/// Configures the measurement of the linear response, see [linear_response].
+pub struct LinearResponseConfig {
+    /// Centre of the trap, in SI units of m.
+    pub center: Vector3<f64>,
+    /// Displacement from the centre used to calculate the spring constant, in SI units of m.
+    pub displacement: f64,
+    /// Velocity used to calculate the damping coefficient, in SI units of m/s.
+    pub velocity: f64,
+    /// Mass of the probe atoms, in atomic mass units.
+    pub mass: f64,
+}
+
+/// Calculates the spring constant and damping coefficient of a MOT along an axis.
+///
+/// The mean force is calculated on pinned probe atoms displaced by `±displacement` along the axis, and moving
+/// with velocities `±velocity` along the axis. The spring constant `kappa` (in N/m) and damping coefficient
+/// `beta` (in kg/s) are the central finite differences `F = -kappa x` and `F = -beta v`. The displacement and
+/// velocity should be small enough that the Zeeman and Doppler shifts are much less than the linewidth.
+///
+/// The fluctuations of the scattering and emission forces are disabled while the forces are calculated, and then
+/// restored. The simulation is advanced by a few steps, during which any other atoms are integrated as normal.
+///
+/// Returns `(kappa, beta)`.
+///
+/// # Generic Arguments
+///
+/// * `T`: The laser cooling transition of the probe atoms.
+pub fn linear_response<T>(
+    simulation: &mut Simulation,
+    config: &LinearResponseConfig,
+    axis: Vector3<f64>,
+) -> (f64, f64)
+where
+    T: TransitionComponent,
+{
+    let axis = axis.normalize();
+    with_mean_forces(simulation, |simulation| {
+        let probes: Vec<Entity> = [
+            (config.displacement, 0.0),
+            (-config.displacement, 0.0),
+            (0.0, config.velocity),
+            (0.0, -config.velocity),
+        ]
+        .iter()
+        .map(|&(displacement, velocity)| {
+            simulation
+                .world
+                .create_entity()
+                .with(Position {
+                    pos: (config.center + displacement * axis).cast(),
+                })
+                .with(Velocity {
+                    vel: (velocity * axis).cast(),
+                })
+                .with(Force::new())
+                .with(Mass { value: config.mass })
+                .with(Atom)
+                .with(Pinned)
+                .with(T::default())
+                .with(NewlyCreated)
+                .build()
+        })
+        .collect();
+        for _ in 0..PROBE_STEPS {
+            simulation.step();
+        }
+
+        let forces: Vec<f64> = {
+            let storage = simulation.world.read_storage::<Force>();
+            probes
+                .iter()
+                .map(|probe| storage.get(*probe).unwrap().force.cast::<f64>().dot(&axis))
+                .collect()
+        };
+        let kappa = -(forces[0] - forces[1]) / (2.0 * config.displacement);
+        let beta = -(forces[2] - forces[3]) / (2.0 * config.velocity);
+
+        simulation
+            .world
+            .delete_entities(&probes)
+            .expect("Could not delete probe atoms.");
+        simulation.world.maintain();
+        (kappa, beta)
+    })
+}
+
+/// Runs `f` with the fluctuations of the scattering and emission forces disabled, and then restores the
+/// previous [EmissionForceOption] and [ScatteringFluctuationsOption].
+pub(crate) fn with_mean_forces<R>(
+    simulation: &mut Simulation,
+    f: impl FnOnce(&mut Simulation) -> R,
+) -> R {
+    let emission = simulation.world.remove::<EmissionForceOption>();
+    let fluctuations = simulation.world.remove::<ScatteringFluctuationsOption>();
+    simulation.world.insert(EmissionForceOption::Off);
+    simulation.world.insert(ScatteringFluctuationsOption::Off);
+
+    let result = f(simulation);
+
+    match emission {
+        Some(emission) => simulation.world.insert(emission),
+        None => {
+            simulation.world.remove::<EmissionForceOption>();
+        }
+    }
+    match fluctuations {
+        Some(fluctuations) => simulation.world.insert(fluctuations),
+        None => {
+            simulation.world.remove::<ScatteringFluctuationsOption>();
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use crate::constant::{HBAR, PI};
+    use crate::integrator::Timestep;
+    use crate::laser::gaussian::GaussianBeam;
+    use crate::laser::LaserPlugin;
+    use crate::laser_cooling::transition::AtomicTransition;
+    use crate::laser_cooling::{CoolingLight, LaserCoolingPlugin};
+    use crate::magnetic::quadrupole::QuadrupoleField3D;
+    use crate::simulation::SimulationBuilder;
+    use crate::species::Strontium88_461;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_linear_response_of_1d_mot() {
+        let mut sim_builder = SimulationBuilder::default();
+        sim_builder.add_plugin(LaserPlugin::<2>);
+        sim_builder.add_plugin(LaserCoolingPlugin::<Strontium88_461, 2>::default());
+        let mut sim = sim_builder.build();
+        sim.world.insert(Timestep { delta: 1.0e-6 });
+
+        let gradient = 0.15;
+        sim.world
+            .create_entity()
+            .with(QuadrupoleField3D {
+                gradient,
+                direction: Vector3::z(),
+            })
+            .with(Position::new())
+            .build();
+        let detuning = -12.0;
+        let power = 0.01;
+        let e_radius = 0.01;
+        for direction in [Vector3::z(), -Vector3::z()].iter() {
+            sim.world
+                .create_entity()
+                .with(GaussianBeam {
+                    intersection: Vector3::new(0.0, 0.0, 0.0),
+                    e_radius,
+                    power,
+                    direction: *direction,
+                    rayleigh_range: f64::INFINITY,
+                    ellipticity: 0.0,
+                    focus_offset: 0.0,
+                })
+                .with(CoolingLight::for_transition::<Strontium88_461>(
+                    detuning, -1,
+                ))
+                .build();
+        }
+
+        let config = LinearResponseConfig {
+            center: Vector3::new(0.0, 0.0, 0.0),
+            displacement: 1.0e-5,
+            velocity: 1.0e-2,
+            mass: 88.0,
+        };
+        let (kappa, beta) = linear_response::<Strontium88_461>(&mut sim, &config, Vector3::z());
+
+        // Low-intensity expressions, including the saturation of the two-level population by both beams.
+        let gamma = Strontium88_461::gamma();
+        let k = 2.0 * PI / Strontium88_461::wavelength();
+        let delta = 2.0 * PI * detuning * 1.0e6;
+        let s = power / (PI * e_radius.powi(2)) / Strontium88_461::saturation_intensity();
+        let lorentzian = 1.0 + 4.0 * delta.powi(2) / gamma.powi(2);
+        let expected_beta = -8.0 * HBAR * k.powi(2) * s * delta
+            / gamma
+            / lorentzian.powi(2)
+            / (1.0 + 2.0 * s / lorentzian);
+        // The field along the symmetry axis of the quadrupole has a gradient of twice `gradient`.
+        let expected_kappa = expected_beta * 2.0 * Strontium88_461::mup() * gradient / (HBAR * k);
+
+        assert_approx_eq!(beta, expected_beta, 1e-2 * expected_beta);
+        assert_approx_eq!(kappa, expected_kappa, 1e-2 * expected_kappa);
+
+        // The probe atoms are removed, and the fluctuation options restored.
+        assert_eq!(sim.world.read_storage::<Atom>().join().count(), 0);
+        assert!(sim.world.try_fetch::<EmissionForceOption>().is_none());
+    }
+}
@@ -4,6 +4,7 @@ use std::marker::PhantomData;
 
 use crate::laser::LaserPlugin;
 use crate::{constant, simulation::Plugin};
+use crate::dispatch::SystemDispatcherBuilder;
 use crate::initiate::NewlyCreated;
 use crate::integrator::INTEGRATE_POSITION_SYSTEM_NAME;
 use crate::laser::index::LaserIndex;
@@ -14,14 +15,25 @@ use transition::AtomicTransition;
 
 use self::transition::TransitionComponent;
 
+pub mod capture;
+pub mod characterize;
+pub mod cooling_power;
+pub mod detuning_scan;
 pub mod doppler;
 pub mod force;
+pub mod linear_response;
+pub mod mot;
 pub mod photons_scattered;
+pub mod polarization;
 pub mod rate;
 pub mod repump;
+pub mod retroreflection;
 pub mod sampler;
+pub mod scattering;
+pub mod standing_wave;
 pub mod twolevel;
 pub mod transition;
+pub mod wavevector;
 pub mod zeeman;
 
 /// A component representing light properties used for laser cooling.
@@ -43,6 +55,11 @@ pub struct CoolingLight {
     pub polarization: i32,
 
     /// wavelength of the laser light, in SI units of m.
+    ///
+    /// The wavevector of the beam, used for the absorption recoil and the Doppler shift, is calculated from this
+    /// wavelength, so beams of different colours push the atoms with different momenta. Spontaneously emitted
+    /// photons always carry the wavelength of the transition. Use [CoolingLight::for_transition] with zero
+    /// detuning for a beam resonant with the transition.
     pub wavelength: f64,
 }
 
@@ -123,6 +140,7 @@ impl<'a, T, const N: usize> System<'a> for AttachLaserCoolingComponentsToNewlyCr
             );
             updater.insert(ent, twolevel::TwoLevelPopulation::<T>::default());
             updater.insert(ent, photons_scattered::TotalPhotonsScattered::<T>::default());
+            updater.insert(ent, photons_scattered::ScatteredPhotons::default());
             updater.insert(
                 ent,
                 photons_scattered::ExpectedPhotonsScatteredVector::<T,N> {
@@ -185,7 +203,7 @@ impl<T, const N : usize> Plugin for LaserCoolingPlugin<T, N> where T : Transitio
 ///
 /// `deps`: any dependencies that must be completed before the systems run.
 fn add_systems_to_dispatch<T, const N: usize>(
-    builder: &mut DispatcherBuilder<'static, 'static>,
+    builder: &mut impl SystemDispatcherBuilder,
     deps: &[&str],
 )  where T : TransitionComponent {
     builder.add(
@@ -203,10 +221,15 @@ fn add_systems_to_dispatch<T, const N: usize>(
         "initialise_rate_coefficients",
         deps,
     );
+    builder.add(
+        wavevector::CacheCoolingWavevectorsSystem,
+        "cache_cooling_wavevectors",
+        &["index_lasers"],
+    );
     builder.add(
         doppler::CalculateDopplerShiftSystem::<N>,
         "calculate_doppler_shift",
-        &["index_lasers"],
+        &["cache_cooling_wavevectors"],
     );
     builder.add(
         zeeman::CalculateZeemanShiftSystem::<T>::default(),
@@ -233,6 +256,11 @@ fn add_systems_to_dispatch<T, const N: usize>(
         "calculate_total_photons",
         &["calculate_twolevel"],
     );
+    builder.add(
+        photons_scattered::AccumulateScatteredPhotonsSystem::<T>::default(),
+        "accumulate_scattered_photons",
+        &["calculate_total_photons"],
+    );
     builder.add(
         photons_scattered::CalculateExpectedPhotonsScatteredSystem::<T, N>::default(),
         "calculate_expected_photons",
@@ -250,7 +278,25 @@ fn add_systems_to_dispatch<T, const N: usize>(
     builder.add(
         force::CalculateAbsorptionForcesSystem::<T, N>::default(),
         "calculate_absorption_forces",
-        &["calculate_actual_photons", INTEGRATE_POSITION_SYSTEM_NAME],
+        &[
+            "calculate_actual_photons",
+            "cache_cooling_wavevectors",
+            INTEGRATE_POSITION_SYSTEM_NAME,
+        ],
+    );
+    builder.add(
+        standing_wave::ApplyStandingWaveDipoleForceSystem::<T, N>::default(),
+        "calculate_standing_wave_dipole_forces",
+        &[
+            "sample_laser_intensity",
+            "calculate_absorption_forces",
+            INTEGRATE_POSITION_SYSTEM_NAME,
+        ],
+    );
+    builder.add(
+        cooling_power::CalculateCoolingPowerSystem::<T, N>::default(),
+        "calculate_cooling_power",
+        &["calculate_actual_photons", "cache_cooling_wavevectors"],
     );
     builder.add(
         repump::RepumpSystem::<T>::default(),
@@ -275,6 +321,11 @@ fn add_systems_to_dispatch<T, const N: usize>(
         "attach_cooling_index",
         deps,
     );
+    builder.add(
+        retroreflection::CreateReturnBeamsSystem,
+        "create_return_beams",
+        deps,
+    );
 }
 
 #[cfg(test)]
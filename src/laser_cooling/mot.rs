@@ -0,0 +1,357 @@
+//! Assigns the polarizations of the cooling beams of a magneto-optical trap.
+//!
+//! A MOT only traps atoms when the handedness of each beam matches the sign of the magnetic field gradient along
+//! the beam. For a [QuadrupoleField3D] with gradient `b` and symmetry axis `a`, the field along a beam direction
+//! `d` changes at the rate `b (1 - 3 (d.a)^2)`, so beams along the symmetry axis need the opposite polarization to
+//! beams in the radial plane. [assign_mot_polarizations] sets the [CoolingLight::polarization] of every cooling
+//! beam from this rule, and [check_restoring_force] confirms that the resulting force pushes atoms back to the
+//! centre of the trap.
+
+use std::fmt;
+
+use nalgebra::Vector3;
+use specs::prelude::*;
+
+use super::characterize::{create_probe, delete_probes};
+use super::linear_response::with_mean_forces;
+use super::transition::TransitionComponent;
+use super::CoolingLight;
+use crate::atom::Force;
+use crate::integrator::Pinned;
+use crate::laser::gaussian::GaussianBeam;
+use crate::magnetic::quadrupole::QuadrupoleField3D;
+use crate::simulation::Simulation;
+
+/// Number of steps simulated to calculate the forces on newly created probe atoms.
+const PROBE_STEPS: usize = 2;
+
+/// Beams closer than this to the magic angle of the quadrupole, measured by `|1 - 3 cos^2(theta)|`, see no field
+/// gradient and cannot be assigned a polarization.
+const MAGIC_ANGLE_TOLERANCE: f64 = 1.0e-3;
+
+/// The reasons that the polarizations of a MOT may be invalid.
+#[derive(Clone, Copy, Debug)]
+pub enum MotPolarizationError {
+    /// The beam is at the magic angle to the quadrupole axis, where the field does not change along the beam.
+    MagicAngle {
+        /// Direction of the beam.
+        direction: Vector3<f64>,
+    },
+    /// The mean force along the beam pushes atoms away from the centre of the trap.
+    NotRestoring {
+        /// Direction of the beam.
+        direction: Vector3<f64>,
+        /// Spring constant along the beam, in SI units of N/m. A trapping force has a positive spring constant.
+        spring_constant: f64,
+    },
+}
+impl fmt::Display for MotPolarizationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MotPolarizationError::MagicAngle { direction } => write!(
+                f,
+                "The beam along {:?} is at the magic angle to the quadrupole axis, so no polarization traps atoms along it.",
+                direction
+            ),
+            MotPolarizationError::NotRestoring {
+                direction,
+                spring_constant,
+            } => write!(
+                f,
+                "The force along {:?} is anti-trapping, with a spring constant of {:.3e} N/m. Check the polarizations of the beams.",
+                direction, spring_constant
+            ),
+        }
+    }
+}
+
+/// Returns the polarization of a cooling beam along `direction` that traps atoms in the `quadrupole` field.
+///
+/// Returns `None` if the beam is at the magic angle to the quadrupole axis.
+pub fn mot_polarization(direction: &Vector3<f64>, quadrupole: &QuadrupoleField3D) -> Option<i32> {
+    let cos_theta = direction.normalize().dot(&quadrupole.direction.normalize());
+    let projection = 1.0 - 3.0 * cos_theta.powi(2);
+    if projection.abs() < MAGIC_ANGLE_TOLERANCE || quadrupole.gradient == 0.0 {
+        return None;
+    }
+    if quadrupole.gradient * projection > 0.0 {
+        Some(1)
+    } else {
+        Some(-1)
+    }
+}
+
+/// Sets the [CoolingLight::polarization] of every cooling beam in the world to trap atoms in the `quadrupole` field.
+///
+/// The polarizations are only changed if every beam can be assigned a polarization. Use [check_restoring_force]
+/// to confirm that the assigned polarizations trap atoms of a given transition.
+pub fn assign_mot_polarizations(
+    world: &mut World,
+    quadrupole: &QuadrupoleField3D,
+) -> Result<(), MotPolarizationError> {
+    let beams = world.read_storage::<GaussianBeam>();
+    let mut cooling_lights = world.write_storage::<CoolingLight>();
+
+    let polarizations = (&beams, &cooling_lights)
+        .join()
+        .map(|(beam, _)| {
+            mot_polarization(&beam.direction, quadrupole).ok_or(MotPolarizationError::MagicAngle {
+                direction: beam.direction,
+            })
+        })
+        .collect::<Result<Vec<i32>, MotPolarizationError>>()?;
+
+    for ((_, cooling), polarization) in (&beams, &mut cooling_lights)
+        .join()
+        .zip(polarizations.into_iter())
+    {
+        cooling.polarization = polarization;
+    }
+    Ok(())
+}
+
+/// Checks that the mean force on atoms displaced from `center` along each cooling beam is restoring.
+///
+/// The mean force is calculated on pinned probe atoms at rest, displaced by `±displacement` along the direction of
+/// each cooling beam. Counter-propagating beams are only checked once. The fluctuations of the scattering and
+/// emission forces are disabled while the forces are calculated, and the simulation is advanced by a few steps.
+///
+/// # Generic Arguments
+///
+/// * `T`: The laser cooling transition of the probe atoms.
+///
+/// # Arguments
+///
+/// `center`: centre of the trap, in SI units of m.
+///
+/// `displacement`: displacement of the probe atoms from the centre, in SI units of m.
+///
+/// `mass`: mass of the probe atoms, in atomic mass units.
+pub fn check_restoring_force<T>(
+    simulation: &mut Simulation,
+    center: Vector3<f64>,
+    displacement: f64,
+    mass: f64,
+) -> Result<(), MotPolarizationError>
+where
+    T: TransitionComponent,
+{
+    let mut axes: Vec<Vector3<f64>> = Vec::new();
+    {
+        let beams = simulation.world.read_storage::<GaussianBeam>();
+        let cooling_lights = simulation.world.read_storage::<CoolingLight>();
+        for (beam, _) in (&beams, &cooling_lights).join() {
+            let direction = beam.direction.normalize();
+            if !axes
+                .iter()
+                .any(|axis| axis.dot(&direction).abs() > 1.0 - 1.0e-9)
+            {
+                axes.push(direction);
+            }
+        }
+    }
+
+    let spring_constants: Vec<f64> = with_mean_forces(simulation, |simulation| {
+        let probes: Vec<Entity> = axes
+            .iter()
+            .flat_map(|axis| [displacement, -displacement].map(|d| center + d * axis))
+            .map(|pos| create_probe::<T>(simulation, pos, Vector3::zeros(), mass))
+            .collect();
+        {
+            let mut pinned = simulation.world.write_storage::<Pinned>();
+            for probe in probes.iter() {
+                pinned
+                    .insert(*probe, Pinned)
+                    .expect("Could not pin probe atom.");
+            }
+        }
+        for _ in 0..PROBE_STEPS {
+            simulation.step();
+        }
+
+        let spring_constants = {
+            let forces = simulation.world.read_storage::<Force>();
+            axes.iter()
+                .zip(probes.chunks(2))
+                .map(|(axis, pair)| {
+                    let force =
+                        |probe: Entity| forces.get(probe).unwrap().force.cast::<f64>().dot(axis);
+                    -(force(pair[0]) - force(pair[1])) / (2.0 * displacement)
+                })
+                .collect()
+        };
+        delete_probes(simulation, &probes);
+        spring_constants
+    });
+
+    match axes
+        .iter()
+        .zip(spring_constants.iter())
+        .find(|(_, &spring_constant)| spring_constant <= 0.0)
+    {
+        Some((direction, &spring_constant)) => Err(MotPolarizationError::NotRestoring {
+            direction: *direction,
+            spring_constant,
+        }),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use crate::atom::{Atom, Position};
+    use crate::integrator::Timestep;
+    use crate::laser::LaserPlugin;
+    use crate::laser_cooling::LaserCoolingPlugin;
+    use crate::simulation::SimulationBuilder;
+    use crate::species::Rubidium87_780D2;
+
+    fn create_mot(quadrupole: QuadrupoleField3D) -> Simulation {
+        let mut sim_builder = SimulationBuilder::default();
+        sim_builder.add_plugin(LaserPlugin::<6>);
+        sim_builder.add_plugin(LaserCoolingPlugin::<Rubidium87_780D2, 6>::default());
+        let mut sim = sim_builder.build();
+        sim.world.insert(Timestep { delta: 1.0e-6 });
+
+        sim.world
+            .create_entity()
+            .with(quadrupole)
+            .with(Position::new())
+            .build();
+        for direction in [
+            Vector3::x(),
+            -Vector3::x(),
+            Vector3::y(),
+            -Vector3::y(),
+            Vector3::z(),
+            -Vector3::z(),
+        ]
+        .iter()
+        {
+            sim.world
+                .create_entity()
+                .with(GaussianBeam {
+                    intersection: Vector3::new(0.0, 0.0, 0.0),
+                    e_radius: 0.01,
+                    power: 0.01,
+                    direction: *direction,
+                    rayleigh_range: f64::INFINITY,
+                    ellipticity: 0.0,
+                    focus_offset: 0.0,
+                })
+                .with(CoolingLight::for_transition::<Rubidium87_780D2>(-12.0, 0))
+                .build();
+        }
+        sim
+    }
+
+    #[test]
+    fn test_mot_polarization_follows_field_gradient() {
+        let quadrupole = QuadrupoleField3D::gauss_per_cm(10.0, Vector3::z());
+        assert_eq!(mot_polarization(&Vector3::z(), &quadrupole), Some(-1));
+        assert_eq!(mot_polarization(&-Vector3::z(), &quadrupole), Some(-1));
+        assert_eq!(mot_polarization(&Vector3::x(), &quadrupole), Some(1));
+        assert_eq!(
+            mot_polarization(&Vector3::new(1.0, 1.0, 0.0), &quadrupole),
+            Some(1)
+        );
+
+        let reversed = QuadrupoleField3D::gauss_per_cm(-10.0, Vector3::z());
+        assert_eq!(mot_polarization(&Vector3::z(), &reversed), Some(1));
+        assert_eq!(mot_polarization(&Vector3::x(), &reversed), Some(-1));
+
+        // A beam along the body diagonal is at the magic angle.
+        assert_eq!(
+            mot_polarization(&Vector3::new(1.0, 1.0, 1.0), &quadrupole),
+            None
+        );
+    }
+
+    #[test]
+    fn test_assigned_polarizations_are_restoring() {
+        let quadrupole = QuadrupoleField3D::gauss_per_cm(10.0, Vector3::z());
+        let mut sim = create_mot(quadrupole);
+        assign_mot_polarizations(&mut sim.world, &quadrupole)
+            .expect("Could not assign polarizations.");
+
+        {
+            let beams = sim.world.read_storage::<GaussianBeam>();
+            let cooling_lights = sim.world.read_storage::<CoolingLight>();
+            for (beam, cooling) in (&beams, &cooling_lights).join() {
+                let expected = if beam.direction.z != 0.0 { -1 } else { 1 };
+                assert_eq!(cooling.polarization, expected);
+            }
+        }
+
+        let result =
+            check_restoring_force::<Rubidium87_780D2>(&mut sim, Vector3::zeros(), 1.0e-4, 87.0);
+        assert!(result.is_ok(), "{}", result.unwrap_err());
+
+        // The probe atoms are removed.
+        assert_eq!(sim.world.read_storage::<Atom>().join().count(), 0);
+    }
+
+    #[test]
+    fn test_wrong_polarizations_are_detected() {
+        let quadrupole = QuadrupoleField3D::gauss_per_cm(10.0, Vector3::z());
+        let mut sim = create_mot(quadrupole);
+        assign_mot_polarizations(&mut sim.world, &quadrupole)
+            .expect("Could not assign polarizations.");
+
+        // Flip the axial beams, which makes the trap anti-trapping along the quadrupole axis.
+        {
+            let beams = sim.world.read_storage::<GaussianBeam>();
+            let mut cooling_lights = sim.world.write_storage::<CoolingLight>();
+            for (beam, cooling) in (&beams, &mut cooling_lights).join() {
+                if beam.direction.z != 0.0 {
+                    cooling.polarization = -cooling.polarization;
+                }
+            }
+        }
+
+        match check_restoring_force::<Rubidium87_780D2>(&mut sim, Vector3::zeros(), 1.0e-4, 87.0) {
+            Err(MotPolarizationError::NotRestoring {
+                direction,
+                spring_constant,
+            }) => {
+                assert!(direction.z.abs() > 0.99);
+                assert!(spring_constant < 0.0);
+            }
+            other => panic!(
+                "Expected an anti-trapping force, got {:?}",
+                other.map(|_| ())
+            ),
+        }
+    }
+
+    #[test]
+    fn test_magic_angle_beam_is_rejected() {
+        let quadrupole = QuadrupoleField3D::gauss_per_cm(10.0, Vector3::z());
+        let mut sim = create_mot(quadrupole);
+        sim.world
+            .create_entity()
+            .with(GaussianBeam {
+                intersection: Vector3::new(0.0, 0.0, 0.0),
+                e_radius: 0.01,
+                power: 0.01,
+                direction: Vector3::new(1.0, 1.0, 1.0).normalize(),
+                rayleigh_range: f64::INFINITY,
+                ellipticity: 0.0,
+                focus_offset: 0.0,
+            })
+            .with(CoolingLight::for_transition::<Rubidium87_780D2>(-12.0, 0))
+            .build();
+
+        assert!(matches!(
+            assign_mot_polarizations(&mut sim.world, &quadrupole),
+            Err(MotPolarizationError::MagicAngle { .. })
+        ));
+        // The polarizations are left unchanged.
+        let cooling_lights = sim.world.read_storage::<CoolingLight>();
+        assert!(cooling_lights
+            .join()
+            .all(|cooling| cooling.polarization == 0));
+    }
+}
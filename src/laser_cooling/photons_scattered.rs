@@ -2,13 +2,14 @@
 
 extern crate rayon;
 
-use rand;
 use rand_distr::{Distribution, Poisson};
 
 use crate::{integrator::Timestep};
 use crate::laser::sampler::CoolingLaserSamplerMasks;
 use crate::laser_cooling::rate::RateCoefficients;
 use crate::laser_cooling::twolevel::TwoLevelPopulation;
+use crate::parallel::{ForceSerial, MaybeParJoin};
+use crate::rng::{DeterministicRng, RngStreams};
 use serde::{Deserialize, Serialize};
 use specs::prelude::*;
 use std::fmt;
@@ -54,21 +55,25 @@ where T: TransitionComponent {
         ReadStorage<'a, T>,
         ReadStorage<'a, TwoLevelPopulation<T>>,
         WriteStorage<'a, TotalPhotonsScattered<T>>,
+        Option<Read<'a, ForceSerial>>,
     );
 
     fn run(
         &mut self,
-        (timestep, transition, twolevel_population, mut total_photons_scattered): Self::SystemData,
+        (
+            timestep,
+            transition,
+            twolevel_population,
+            mut total_photons_scattered,
+            force_serial,
+        ): Self::SystemData,
     ) {
-        use rayon::prelude::*;
-
         (
             &transition,
             &twolevel_population,
             &mut total_photons_scattered,
         )
-            .par_join()
-            .for_each(|(_atominfo, twolevel, total)| {
+            .maybe_par_for_each(force_serial.is_some(), |(_atominfo, twolevel, total)| {
                 total.total = timestep.delta * T::gamma() * twolevel.excited;
             });
     }
@@ -119,11 +124,12 @@ impl<T, const N: usize> fmt::Display for ExpectedPhotonsScatteredVector<T, N> wh
 #[derive(Default)]
 pub struct InitialiseExpectedPhotonsScatteredVectorSystem<T, const N: usize>(PhantomData<T>) where T : TransitionComponent;
 impl<'a, T, const N: usize> System<'a> for InitialiseExpectedPhotonsScatteredVectorSystem<T, N> where T : TransitionComponent {
-    type SystemData = (WriteStorage<'a, ExpectedPhotonsScatteredVector<T, N>>,);
-    fn run(&mut self, (mut expected_photons,): Self::SystemData) {
-        use rayon::prelude::*;
-
-        (&mut expected_photons).par_join().for_each(|mut expected| {
+    type SystemData = (
+        WriteStorage<'a, ExpectedPhotonsScatteredVector<T, N>>,
+        Option<Read<'a, ForceSerial>>,
+    );
+    fn run(&mut self, (mut expected_photons, force_serial): Self::SystemData) {
+        (&mut expected_photons).maybe_par_for_each(force_serial.is_some(), |mut expected| {
             expected.contents = [ExpectedPhotonsScattered::default(); N];
         });
     }
@@ -141,22 +147,26 @@ impl<'a, T, const N: usize> System<'a> for CalculateExpectedPhotonsScatteredSyst
         ReadStorage<'a, TotalPhotonsScattered<T>>,
         ReadStorage<'a, CoolingLaserSamplerMasks<N>>,
         WriteStorage<'a, ExpectedPhotonsScatteredVector<T, N>>,
+        Option<Read<'a, ForceSerial>>,
     );
 
     fn run(
         &mut self,
-        (rate_coefficients, total_photons_scattered, masks, mut expected_photons_vector): Self::SystemData,
+        (
+            rate_coefficients,
+            total_photons_scattered,
+            masks,
+            mut expected_photons_vector,
+            force_serial,
+        ): Self::SystemData,
     ) {
-        use rayon::prelude::*;
-
         (
             &rate_coefficients,
             &total_photons_scattered,
             &masks,
             &mut expected_photons_vector,
         )
-            .par_join()
-            .for_each(|(rates, total, mask, expected)| {
+            .maybe_par_for_each(force_serial.is_some(), |(rates, total, mask, expected)| {
                 let mut sum_rates: f64 = 0.;
 
                 for index in 0..rates.contents.len() {
@@ -167,8 +177,12 @@ impl<'a, T, const N: usize> System<'a> for CalculateExpectedPhotonsScatteredSyst
 
                 for index in 0..expected.contents.len() {
                     if mask.contents[index].filled {
-                        expected.contents[index].scattered =
-                            rates.contents[index].rate / sum_rates * total.total;
+                        // No photons are scattered if no beam drives the transition, eg all beams are off.
+                        expected.contents[index].scattered = if sum_rates > 0.0 {
+                            rates.contents[index].rate / sum_rates * total.total
+                        } else {
+                            0.0
+                        };
                     }
                 }
             });
@@ -249,67 +263,132 @@ impl Default for ScatteringFluctuationsOption {
     }
 }
 
+/// A resource that sets the scattering rate below which the photons scattered from a cooling beam are neglected.
+///
+/// In configurations with many weak or distant beams, most beams scatter a negligible number of photons. The
+/// [CalculateActualPhotonsScatteredSystem] sets the photons scattered from such beams to zero without sampling
+/// them, which trades a negligible loss of accuracy for speed. The default threshold of zero neglects no beams.
+#[derive(Clone, Copy, Default)]
+pub struct ScatteringRateThreshold {
+    /// Fraction of the maximum scattering rate `gamma / 2` below which the photons of a beam are neglected.
+    pub fraction: f64,
+}
+
 /// Calcutates the actual number of photons scattered by each CoolingLight entity in one iteration step
 /// by drawing from a Poisson Distribution that has `ExpectedPhotonsScattered` as the lambda parameter.
+///
+/// The numbers are drawn from the [DeterministicRng] resource if it is present, see [crate::rng].
+///
+/// Beams that are expected to scatter photons at a rate below the [ScatteringRateThreshold], if present,
+/// scatter no photons, and no number is drawn for them.
 #[derive(Default)]
 pub struct CalculateActualPhotonsScatteredSystem<T, const N: usize>(PhantomData<T>) where T : TransitionComponent;
 
 impl<'a, T, const N: usize> System<'a> for CalculateActualPhotonsScatteredSystem<T, N> where T : TransitionComponent {
     type SystemData = (
         Option<Read<'a, ScatteringFluctuationsOption>>,
+        Entities<'a>,
         ReadStorage<'a, ExpectedPhotonsScatteredVector<T, N>>,
         WriteStorage<'a, ActualPhotonsScatteredVector<T, N>>,
+        ReadExpect<'a, Timestep>,
+        Option<Read<'a, ScatteringRateThreshold>>,
+        Option<Write<'a, DeterministicRng>>,
+        Option<Read<'a, ForceSerial>>,
     );
 
     fn run(
         &mut self,
-        (fluctuations_option, expected_photons_vector, mut actual_photons_vector): Self::SystemData,
+        (
+            fluctuations_option,
+            entities,
+            expected_photons_vector,
+            mut actual_photons_vector,
+            timestep,
+            threshold,
+            mut deterministic,
+            force_serial,
+        ): Self::SystemData,
     ) {
-        use rayon::prelude::*;
-
-        match fluctuations_option {
-            None => {
+        // Expected number of photons scattered in the step, below which a beam is neglected.
+        let threshold = threshold.map(|threshold| *threshold).unwrap_or_default();
+        let negligible = threshold.fraction * T::gamma() / 2.0 * timestep.delta;
+        match fluctuations_option.map(|option| *option) {
+            None | Some(ScatteringFluctuationsOption::Off) => {
                 (&expected_photons_vector, &mut actual_photons_vector)
-                    .par_join()
-                    .for_each(|(expected, actual)| {
+                    .maybe_par_for_each(force_serial.is_some(), |(expected, actual)| {
                         for index in 0..expected.contents.len() {
-                            actual.contents[index].scattered = expected.contents[index].scattered;
+                            let expected = expected.contents[index].scattered;
+                            actual.contents[index].scattered =
+                                if expected < negligible { 0.0 } else { expected };
+                        }
+                    });
+            }
+            Some(ScatteringFluctuationsOption::On) => {
+                let streams = RngStreams::new(deterministic.as_deref_mut());
+                (&entities, &expected_photons_vector, &mut actual_photons_vector)
+                    .maybe_par_for_each(force_serial.is_some(), |(entity, expected, actual)| {
+                        let mut rng = streams.entity_stream(entity);
+                        for index in 0..expected.contents.len() {
+                            let lambda = expected.contents[index].scattered;
+                            actual.contents[index].scattered =
+                                if lambda <= 1.0e-5 || lambda < negligible || lambda.is_nan() {
+                                    0.0
+                                } else {
+                                    let poisson = Poisson::new(lambda).unwrap();
+                                    let drawn_number = poisson.sample(&mut rng);
+                                    drawn_number as f64
+                                }
                         }
                     });
             }
-            Some(rand_option) => match *rand_option {
-                ScatteringFluctuationsOption::Off => {
-                    (&expected_photons_vector, &mut actual_photons_vector)
-                        .par_join()
-                        .for_each(|(expected, actual)| {
-                            for index in 0..expected.contents.len() {
-                                actual.contents[index].scattered =
-                                    expected.contents[index].scattered;
-                            }
-                        });
-                }
-                ScatteringFluctuationsOption::On => {
-                    (&expected_photons_vector, &mut actual_photons_vector)
-                        .par_join()
-                        .for_each(|(expected, actual)| {
-                            for index in 0..expected.contents.len() {
-                                let lambda = expected.contents[index].scattered;
-                                actual.contents[index].scattered =
-                                    if lambda <= 1.0e-5 || lambda.is_nan() {
-                                        0.0
-                                    } else {
-                                        let poisson = Poisson::new(lambda).unwrap();
-                                        let drawn_number = poisson.sample(&mut rand::thread_rng());
-                                        drawn_number as f64
-                                    }
-                            }
-                        });
-                }
-            },
         }
     }
 }
 
+/// The cumulative number of photons an atom has scattered since it was created, from all beams and transitions.
+///
+/// Fractional counts are allowed, as the count is incremented by the expected number of photons scattered each step.
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+pub struct ScatteredPhotons {
+    /// Number of photons scattered by the atom
+    pub count: f64,
+}
+
+impl Component for ScatteredPhotons {
+    type Storage = VecStorage<Self>;
+}
+
+impl fmt::Display for ScatteredPhotons {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.count)
+    }
+}
+
+/// Adds the photons scattered by the transition `T` in this step to the `ScatteredPhotons` count of each atom.
+///
+/// The number added is the `TotalPhotonsScattered`, i.e. `scattering_rate * dt` summed over all beams.
+#[derive(Default)]
+pub struct AccumulateScatteredPhotonsSystem<T>(PhantomData<T>) where T : TransitionComponent;
+impl<'a, T> System<'a> for AccumulateScatteredPhotonsSystem<T> where T : TransitionComponent {
+    type SystemData = (
+        ReadStorage<'a, TotalPhotonsScattered<T>>,
+        WriteStorage<'a, ScatteredPhotons>,
+        Option<Read<'a, ForceSerial>>,
+    );
+
+    fn run(
+        &mut self,
+        (total_photons_scattered, mut scattered_photons, force_serial): Self::SystemData,
+    ) {
+        (&total_photons_scattered, &mut scattered_photons)
+            .maybe_par_for_each(force_serial.is_some(), |(total, scattered)| {
+                if !total.total.is_nan() {
+                    scattered.count += total.total;
+                }
+            });
+    }
+}
+
 #[cfg(test)]
 pub mod tests {
 
@@ -407,4 +486,107 @@ pub mod tests {
             1e-5_f64
         );
     }
+
+    /// Samples the photons scattered from two beams that are expected to scatter `expected[i]` photons.
+    fn actual_photons_with_threshold(
+        expected: [f64; 2],
+        fluctuations: ScatteringFluctuationsOption,
+        threshold: Option<f64>,
+    ) -> [f64; 2] {
+        let mut test_world = World::new();
+        test_world.register::<ExpectedPhotonsScatteredVector<Strontium88_461, 2>>();
+        test_world.register::<ActualPhotonsScatteredVector<Strontium88_461, 2>>();
+        test_world.insert(Timestep { delta: 1.0e-5 });
+        test_world.insert(fluctuations);
+        test_world.insert(DeterministicRng::new(7));
+        if let Some(fraction) = threshold {
+            test_world.insert(ScatteringRateThreshold { fraction });
+        }
+
+        let mut contents = [ExpectedPhotonsScattered::<Strontium88_461>::default(); 2];
+        for (content, number) in contents.iter_mut().zip(expected.iter()) {
+            content.scattered = *number;
+        }
+        let atom = test_world
+            .create_entity()
+            .with(ExpectedPhotonsScatteredVector { contents })
+            .with(ActualPhotonsScatteredVector {
+                contents: [ActualPhotonsScattered::<Strontium88_461>::default(); 2],
+            })
+            .build();
+
+        let mut system = CalculateActualPhotonsScatteredSystem::<Strontium88_461, 2>::default();
+        system.run_now(&test_world);
+        let storage = test_world.read_storage::<ActualPhotonsScatteredVector<Strontium88_461, 2>>();
+        let actual = storage.get(atom).expect("entity not found");
+        [actual.contents[0].scattered, actual.contents[1].scattered]
+    }
+
+    /// Tests that beams scattering below the `ScatteringRateThreshold` are neglected, and others are unchanged.
+    #[test]
+    fn test_scattering_rate_threshold_skips_negligible_beams() {
+        // A near beam scattering at a tenth of the maximum rate, and a far beam scattering at 1e-4 of it.
+        let max_scattered = Strontium88_461::gamma() / 2.0 * 1.0e-5;
+        let expected = [0.1 * max_scattered, 1.0e-4 * max_scattered];
+
+        let options = [ScatteringFluctuationsOption::Off, ScatteringFluctuationsOption::On];
+        for &fluctuations in options.iter() {
+            let exact = actual_photons_with_threshold(expected, fluctuations, None);
+            assert_eq!(actual_photons_with_threshold(expected, fluctuations, Some(0.0)), exact);
+
+            let thresholded = actual_photons_with_threshold(expected, fluctuations, Some(1.0e-3));
+            assert_eq!(thresholded[0], exact[0]);
+            assert_eq!(thresholded[1], 0.0);
+        }
+        let exact = actual_photons_with_threshold(expected, ScatteringFluctuationsOption::Off, None);
+        assert!(exact[1] > 0.0);
+    }
+
+    /// Tests that `ScatteredPhotons` accumulates `rate * time` for an atom on resonance.
+    #[test]
+    fn test_accumulate_scattered_photons_system() {
+        use crate::laser_cooling::scattering::{rate_coefficient, scattering_rate};
+
+        let mut test_world = World::new();
+
+        let time_delta = 1.0e-6;
+        test_world.register::<TwoLevelPopulation<Strontium88_461>>();
+        test_world.register::<Strontium88_461>();
+        test_world.register::<TotalPhotonsScattered<Strontium88_461>>();
+        test_world.register::<ScatteredPhotons>();
+        test_world.insert(Timestep { delta: time_delta });
+
+        // Steady-state population of an atom illuminated on resonance by two beams.
+        let gamma = Strontium88_461::gamma();
+        let s = 0.5;
+        let rate = 2.0 * rate_coefficient(gamma, 0.0, s);
+        let mut tlp = TwoLevelPopulation::<Strontium88_461>::default();
+        tlp.excited = rate / (gamma + 2.0 * rate);
+        tlp.calculate_ground_state();
+
+        let atom1 = test_world
+            .create_entity()
+            .with(TotalPhotonsScattered::<Strontium88_461>::default())
+            .with(ScatteredPhotons::default())
+            .with(Strontium88_461)
+            .with(tlp)
+            .build();
+
+        let mut total_system = CalculateMeanTotalPhotonsScatteredSystem::<Strontium88_461>::default();
+        let mut accumulate_system = AccumulateScatteredPhotonsSystem::<Strontium88_461>::default();
+        let steps = 1000;
+        for _ in 0..steps {
+            total_system.run_now(&test_world);
+            accumulate_system.run_now(&test_world);
+            test_world.maintain();
+        }
+
+        let expected = scattering_rate(gamma, 0.0, 2.0 * s) * steps as f64 * time_delta;
+        let count = test_world
+            .read_storage::<ScatteredPhotons>()
+            .get(atom1)
+            .expect("entity not found")
+            .count;
+        assert_approx_eq!(count / expected, 1.0, 1e-9);
+    }
 }
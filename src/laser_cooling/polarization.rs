@@ -0,0 +1,303 @@
+//! Cooling beams with a polarization that varies in space.
+//!
+//! By default, a cooling beam has a fixed circular polarization, given by [CoolingLight::polarization]. The
+//! polarization of the light field formed by overlapping beams varies in space, which is the origin of
+//! sub-Doppler cooling in optical molasses. For example, two counter-propagating beams with orthogonal linear
+//! polarizations (lin⊥lin) produce a polarization that cycles between linear, σ+, orthogonal linear and σ- every
+//! half wavelength along their axis.
+//!
+//! A beam with a [PolarizationGradient] has the local polarization `(x + e^(i phi(r)) y) / sqrt(2)`, which is
+//! decomposed into σ+, σ- and π components along the local magnetic field to calculate the scattering rates.
+//!
+//! [CoolingLight::polarization]: super::CoolingLight::polarization
+
+use nalgebra::{Complex, Vector3};
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+
+use crate::constant::PI;
+use crate::laser::gaussian::GaussianBeam;
+
+/// A component that gives a cooling beam a polarization that depends on position.
+///
+/// The local polarization is `(x_vector + e^(i phi) y_vector) / sqrt(2)`, where the relative phase
+/// `phi = phase + phase_gradient . (r - intersection)` is measured from the `intersection` of the beam.
+/// The [CoolingLight::polarization] of a beam with this component is ignored.
+///
+/// [CoolingLight::polarization]: super::CoolingLight::polarization
+#[derive(Deserialize, Serialize, Clone, Copy, Debug)]
+pub struct PolarizationGradient {
+    /// First polarization axis, a unit vector.
+    pub x_vector: Vector3<f64>,
+    /// Second polarization axis, a unit vector orthogonal to `x_vector`.
+    pub y_vector: Vector3<f64>,
+    /// Phase of the `y_vector` component relative to the `x_vector` component at the beam intersection, in radians.
+    pub phase: f64,
+    /// Gradient of the relative phase, in SI units of rad/m.
+    pub phase_gradient: Vector3<f64>,
+}
+impl Component for PolarizationGradient {
+    type Storage = HashMapStorage<Self>;
+}
+
+impl PolarizationGradient {
+    /// A uniform circular polarization, equivalent to a [CoolingLight] with the same `polarization`.
+    ///
+    /// # Arguments
+    ///
+    /// `direction`: direction of the beam.
+    ///
+    /// `x_vector`: any vector orthogonal to the beam direction.
+    ///
+    /// `polarization`: 1 for σ+, -1 for σ-, with respect to the beam direction.
+    ///
+    /// [CoolingLight]: super::CoolingLight
+    pub fn circular(direction: Vector3<f64>, x_vector: Vector3<f64>, polarization: i32) -> Self {
+        let direction = direction.normalize();
+        let x_vector = x_vector.normalize();
+        PolarizationGradient {
+            x_vector,
+            y_vector: direction.cross(&x_vector),
+            phase: polarization as f64 * PI / 2.0,
+            phase_gradient: Vector3::new(0.0, 0.0, 0.0),
+        }
+    }
+
+    /// The polarization of two counter-propagating beams with orthogonal linear polarizations.
+    ///
+    /// The polarization is linear along `x_vector + y_vector` at the intersection, and cycles through σ-, linear
+    /// along `x_vector - y_vector` and σ+ with a period of half a wavelength along `direction`. Add the same
+    /// component to both beams of the pair.
+    ///
+    /// # Arguments
+    ///
+    /// `direction`: direction of the beam polarized along `x_vector`.
+    ///
+    /// `x_vector`: polarization of the beam travelling along `direction`, orthogonal to it.
+    ///
+    /// `wavelength`: wavelength of the beams, in SI units of m.
+    pub fn lin_perp_lin(direction: Vector3<f64>, x_vector: Vector3<f64>, wavelength: f64) -> Self {
+        let direction = direction.normalize();
+        let x_vector = x_vector.normalize();
+        PolarizationGradient {
+            x_vector,
+            y_vector: direction.cross(&x_vector),
+            phase: 0.0,
+            phase_gradient: -4.0 * PI / wavelength * direction,
+        }
+    }
+
+    /// The local polarization vector of the beam at `pos`, in SI units of m.
+    pub fn polarization(&self, beam: &GaussianBeam, pos: &Vector3<f64>) -> Vector3<Complex<f64>> {
+        let phi = self.phase + self.phase_gradient.dot(&(pos - beam.intersection));
+        let phase = Complex::new(phi.cos(), phi.sin());
+        (self.x_vector.map(|x| Complex::new(x, 0.0)) + self.y_vector.map(|y| phase * y))
+            / Complex::new(2.0_f64.sqrt(), 0.0)
+    }
+}
+
+/// Decomposes a polarization into the fractions that drive σ+, σ- and π transitions.
+///
+/// The quantization axis is the direction of the magnetic `field`. If the field vanishes, there is no preferred
+/// axis, and the fractions are those of a circularly polarized beam perpendicular to the quantization axis.
+///
+/// Returns the fractions `(σ+, σ-, π)`, which sum to 1 for a unit polarization vector.
+pub fn polarization_weights(
+    polarization: &Vector3<Complex<f64>>,
+    field: &Vector3<f64>,
+) -> (f64, f64, f64) {
+    if field.norm_squared() < (10.0 * f64::EPSILON) {
+        return (0.25, 0.25, 0.5);
+    }
+    let axis = field.normalize();
+    let reference = if axis.x.abs() < 0.9 {
+        Vector3::x()
+    } else {
+        Vector3::y()
+    };
+    let u = axis.cross(&reference).normalize();
+    let v = axis.cross(&u);
+    let project = |basis: Vector3<Complex<f64>>| {
+        polarization
+            .iter()
+            .zip(basis.iter())
+            .map(|(a, b)| a * b)
+            .sum::<Complex<f64>>()
+            .norm_sqr()
+    };
+    let circular = |sign: f64| project(u.zip_map(&v, |u, v| Complex::new(u, -sign * v))) / 2.0;
+    (
+        circular(1.0),
+        circular(-1.0),
+        project(axis.map(|b| Complex::new(b, 0.0))),
+    )
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use crate::atom::Position;
+    use crate::laser::index::LaserIndex;
+    use crate::laser::intensity::{LaserIntensitySampler, LaserIntensitySamplers};
+    use crate::laser_cooling::rate::{
+        CalculateRateCoefficientsSystem, RateCoefficient, RateCoefficients,
+    };
+    use crate::laser_cooling::sampler::{LaserDetuningSampler, LaserDetuningSamplers};
+    use crate::laser_cooling::CoolingLight;
+    use crate::magnetic::MagneticFieldSampler;
+    use crate::species::Strontium88_461;
+    use assert_approx_eq::assert_approx_eq;
+    use nalgebra::Matrix3;
+
+    fn beam(direction: Vector3<f64>) -> GaussianBeam {
+        GaussianBeam {
+            direction,
+            intersection: Vector3::new(0.0, 0.0, 0.0),
+            e_radius: 2.0,
+            power: 1.0,
+            rayleigh_range: f64::INFINITY,
+            ellipticity: 0.0,
+            focus_offset: 0.0,
+        }
+    }
+
+    #[test]
+    fn test_circular_polarization_weights() {
+        let direction = Vector3::new(1.0, 2.0, -0.5).normalize();
+        let x_vector = direction.cross(&Vector3::z());
+        for polarization in [1, -1].iter() {
+            let gradient = PolarizationGradient::circular(direction, x_vector, *polarization);
+            let local = gradient.polarization(&beam(direction), &Vector3::new(0.3, 0.1, 0.2));
+            for field in [
+                Vector3::new(0.0, 0.0, 1.0),
+                Vector3::new(1.0, -1.0, 3.0),
+                direction,
+                -direction,
+            ]
+            .iter()
+            {
+                let costheta = direction.dot(&field.normalize());
+                let p = *polarization as f64;
+                let (plus, minus, pi) = polarization_weights(&local, field);
+                assert_approx_eq!(plus, 0.25 * (p * costheta + 1.0).powi(2), 1e-12);
+                assert_approx_eq!(minus, 0.25 * (p * costheta - 1.0).powi(2), 1e-12);
+                assert_approx_eq!(pi, 0.5 * (1.0 - costheta.powi(2)), 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn test_lin_perp_lin_polarization_rotates() {
+        let wavelength = 461.0e-9;
+        let direction = Vector3::z();
+        let gradient = PolarizationGradient::lin_perp_lin(direction, Vector3::x(), wavelength);
+        let beam = beam(direction);
+        let weights = |z: f64, field: Vector3<f64>| {
+            polarization_weights(
+                &gradient.polarization(&beam, &Vector3::new(0.0, 0.0, z)),
+                &field,
+            )
+        };
+        let diagonal = Vector3::new(1.0, 1.0, 0.0);
+        let antidiagonal = Vector3::new(1.0, -1.0, 0.0);
+
+        // Linear along x + y, then σ-, linear along x - y, σ+, and back every half wavelength.
+        let expected = [
+            (diagonal, (0.0, 0.0, 1.0)),
+            (direction, (0.0, 1.0, 0.0)),
+            (antidiagonal, (0.0, 0.0, 1.0)),
+            (direction, (1.0, 0.0, 0.0)),
+        ];
+        for period in 0..3 {
+            for (i, (field, (plus, minus, pi))) in expected.iter().enumerate() {
+                let z = (period as f64 / 2.0 + i as f64 / 8.0) * wavelength;
+                let (p, m, q) = weights(z, *field);
+                assert_approx_eq!(p, plus, 1e-9);
+                assert_approx_eq!(m, minus, 1e-9);
+                assert_approx_eq!(q, pi, 1e-9);
+            }
+        }
+        // Between the special points, the polarization is elliptical.
+        let (plus, minus, _) = weights(wavelength / 16.0, direction);
+        assert!(plus > 0.0 && minus > plus);
+    }
+
+    #[test]
+    fn test_uniform_gradient_reproduces_cooling_light_polarization() {
+        let mut test_world = World::new();
+        test_world.register::<LaserIndex>();
+        test_world.register::<CoolingLight>();
+        test_world.register::<GaussianBeam>();
+        test_world.register::<PolarizationGradient>();
+        test_world.register::<LaserDetuningSamplers<Strontium88_461, 2>>();
+        test_world.register::<LaserIntensitySamplers<2>>();
+        test_world.register::<Strontium88_461>();
+        test_world.register::<MagneticFieldSampler>();
+        test_world.register::<Position>();
+        test_world.register::<RateCoefficients<Strontium88_461, 2>>();
+
+        let direction = Vector3::new(1.0, 0.0, 1.0).normalize();
+        let cooling = CoolingLight {
+            polarization: -1,
+            wavelength: 461e-9,
+        };
+        test_world
+            .create_entity()
+            .with(cooling)
+            .with(LaserIndex {
+                index: 0,
+                initiated: true,
+            })
+            .with(beam(direction))
+            .build();
+        test_world
+            .create_entity()
+            .with(cooling)
+            .with(LaserIndex {
+                index: 1,
+                initiated: true,
+            })
+            .with(beam(direction))
+            .with(PolarizationGradient::circular(direction, Vector3::y(), -1))
+            .build();
+
+        let mut detuning = LaserDetuningSampler::<Strontium88_461>::default();
+        detuning.detuning_sigma_plus = -1.0e7;
+        detuning.detuning_sigma_minus = -3.0e7;
+        detuning.detuning_pi = -2.0e7;
+        let atom = test_world
+            .create_entity()
+            .with(LaserDetuningSamplers {
+                contents: [detuning; 2],
+            })
+            .with(LaserIntensitySamplers {
+                contents: [LaserIntensitySampler { intensity: 1.0 }; 2],
+            })
+            .with(Strontium88_461)
+            .with(MagneticFieldSampler {
+                field: Vector3::new(0.2, 0.5, 1.0),
+                magnitude: 1.0,
+                gradient: Vector3::new(0.0, 0.0, 0.0),
+                jacobian: Matrix3::zeros(),
+            })
+            .with(Position {
+                pos: Vector3::new(1.0e-3, 0.0, 2.0e-3).cast(),
+            })
+            .with(RateCoefficients {
+                contents: [RateCoefficient::<Strontium88_461>::default(); 2],
+            })
+            .build();
+
+        let mut system = CalculateRateCoefficientsSystem::<Strontium88_461, 2>::default();
+        system.run_now(&test_world);
+
+        let rates = test_world
+            .read_storage::<RateCoefficients<Strontium88_461, 2>>()
+            .get(atom)
+            .unwrap()
+            .contents;
+        assert!(rates[0].rate > 0.0);
+        assert_approx_eq!(rates[0].rate, rates[1].rate, 1e-9 * rates[0].rate);
+    }
+}
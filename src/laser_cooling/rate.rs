@@ -5,12 +5,16 @@ extern crate serde;
 use std::marker::PhantomData;
 
 use super::CoolingLight;
+use super::polarization::{polarization_weights, PolarizationGradient};
 use super::transition::{TransitionComponent};
+use crate::atom::Position;
 use crate::laser::gaussian::GaussianBeam;
 use crate::laser::index::LaserIndex;
 use crate::laser::intensity::LaserIntensitySamplers;
 use crate::laser_cooling::sampler::LaserDetuningSamplers;
+use crate::laser_cooling::scattering::{rate_coefficient, saturation_parameter};
 use crate::magnetic::MagneticFieldSampler;
+use crate::parallel::{ForceSerial, MaybeParJoin};
 use serde::Serialize;
 use specs::prelude::*;
 
@@ -51,13 +55,10 @@ impl<T, const N: usize> Component for RateCoefficients<T, N> where T : Transitio
 pub struct InitialiseRateCoefficientsSystem<T, const N: usize>(PhantomData<T>) where T : TransitionComponent;
 
 impl<'a, T, const N: usize> System<'a> for InitialiseRateCoefficientsSystem<T, N> where T : TransitionComponent {
-    type SystemData = (WriteStorage<'a, RateCoefficients<T, N>>,);
-    fn run(&mut self, (mut rate_coefficients,): Self::SystemData) {
-        use rayon::prelude::*;
-
+    type SystemData = (WriteStorage<'a, RateCoefficients<T, N>>, Option<Read<'a, ForceSerial>>);
+    fn run(&mut self, (mut rate_coefficients, force_serial): Self::SystemData) {
         (&mut rate_coefficients)
-            .par_join()
-            .for_each(|mut rate_coefficient| {
+            .maybe_par_for_each(force_serial.is_some(), |mut rate_coefficient| {
                 rate_coefficient.contents = [RateCoefficient::default(); N];
             });
     }
@@ -71,6 +72,10 @@ impl<'a, T, const N: usize> System<'a> for InitialiseRateCoefficientsSystem<T, N
 /// This is also the System that currently takes care of handling the polarizations correctly.
 /// The polarization is projected onto the quantization axis given by the local magnetic
 /// field vector. For fully polarized CoolingLight all projection pre-factors add up to 1.
+/// Beams with a [PolarizationGradient] use the polarization at the position of the atom instead.
+///
+/// The rate coefficients use the natural linewidth. Power broadening at high intensity is already
+/// produced by the saturation in [crate::laser_cooling::twolevel::CalculateTwoLevelPopulationSystem].
 #[derive(Default)]
 pub struct CalculateRateCoefficientsSystem<T, const N: usize>(PhantomData<T>) where T : TransitionComponent;
 
@@ -83,7 +88,10 @@ impl<'a, T, const N: usize> System<'a> for CalculateRateCoefficientsSystem<T, N>
         ReadStorage<'a, T>,
         ReadStorage<'a, GaussianBeam>,
         ReadStorage<'a, MagneticFieldSampler>,
+        ReadStorage<'a, PolarizationGradient>,
+        ReadStorage<'a, Position>,
         WriteStorage<'a, RateCoefficients<T, N>>,
+        Option<Read<'a, ForceSerial>>,
     );
     fn run(
         &mut self,
@@ -95,49 +103,76 @@ impl<'a, T, const N: usize> System<'a> for CalculateRateCoefficientsSystem<T, N>
             atomic_transition,
             gaussian_beam,
             magnetic_field_sampler,
+            polarization_gradients,
+            positions,
             mut rate_coefficients,
+            force_serial,
         ): Self::SystemData,
     ) {
-        use rayon::prelude::*;
-
-        for (cooling, index, gaussian) in (&cooling_light, &cooling_index, &gaussian_beam).join() {
+        for (cooling, index, gaussian, gradient) in (
+            &cooling_light,
+            &cooling_index,
+            &gaussian_beam,
+            polarization_gradients.maybe(),
+        )
+            .join()
+        {
             (
                 &laser_detunings,
                 &laser_intensities,
                 &atomic_transition,
                 &magnetic_field_sampler,
+                positions.maybe(),
                 &mut rate_coefficients,
             )
-                .par_join()
-                .for_each(|(detunings, intensities, _atominfo, bfield, rates)| {
-                    let beam_direction_vector = gaussian.direction.normalize();
-                    let costheta = if bfield.field.norm_squared() < (10.0 * f64::EPSILON) {
-                        0.0
-                    } else {
-                        beam_direction_vector
-                            .normalize()
-                            .dot(&bfield.field.normalize())
-                    };
-
-                    let prefactor =
-                        T::rate_prefactor() * intensities.contents[index.index].intensity;
-                    let gamma = T::gamma();
-
-                    let scatter1 =
-                        0.25 * (cooling.polarization as f64 * costheta + 1.).powf(2.) * prefactor
-                            / (detunings.contents[index.index].detuning_sigma_plus.powi(2)
-                                + (gamma / 2.0).powi(2));
-
-                    let scatter2 =
-                        0.25 * (cooling.polarization as f64 * costheta - 1.).powi(2) * prefactor
-                            / (detunings.contents[index.index].detuning_sigma_minus.powi(2)
-                                + (gamma / 2.0).powi(2));
-
-                    let scatter3 = 0.5 * (1. - costheta.powf(2.)) * prefactor
-                        / (detunings.contents[index.index].detuning_pi.powi(2)
-                            + (gamma / 2.0).powi(2));
-                    rates.contents[index.index].rate = scatter1 + scatter2 + scatter3;
-                });
+                .maybe_par_for_each(
+                    force_serial.is_some(),
+                    |(detunings, intensities, _atominfo, bfield, pos, rates)| {
+                        let beam_direction_vector = gaussian.direction.normalize();
+                        let costheta = if bfield.field.norm_squared() < (10.0 * f64::EPSILON) {
+                            0.0
+                        } else {
+                            beam_direction_vector
+                                .normalize()
+                                .dot(&bfield.field.normalize())
+                        };
+                        let (weight_plus, weight_minus, weight_pi) = match (gradient, pos) {
+                            (Some(gradient), Some(pos)) => polarization_weights(
+                                &gradient.polarization(gaussian, &pos.pos.cast()),
+                                &bfield.field,
+                            ),
+                            _ => (
+                                0.25 * (cooling.polarization as f64 * costheta + 1.).powf(2.),
+                                0.25 * (cooling.polarization as f64 * costheta - 1.).powi(2),
+                                0.5 * (1. - costheta.powf(2.)),
+                            ),
+                        };
+
+                        let s = saturation_parameter(
+                            intensities.contents[index.index].intensity,
+                            T::saturation_intensity(),
+                        );
+                        let gamma = T::gamma();
+
+                        let scatter1 = weight_plus
+                            * rate_coefficient(
+                                gamma,
+                                detunings.contents[index.index].detuning_sigma_plus,
+                                s,
+                            );
+
+                        let scatter2 = weight_minus
+                            * rate_coefficient(
+                                gamma,
+                                detunings.contents[index.index].detuning_sigma_minus,
+                                s,
+                            );
+
+                        let scatter3 = weight_pi
+                            * rate_coefficient(gamma, detunings.contents[index.index].detuning_pi, s);
+                        rates.contents[index.index].rate = scatter1 + scatter2 + scatter3;
+                    },
+                );
         }
     }
 }
@@ -172,6 +207,8 @@ pub mod tests {
         test_world.register::<LaserIntensitySamplers<{ DEFAULT_BEAM_LIMIT }>>();
         test_world.register::<Strontium88_461>();
         test_world.register::<MagneticFieldSampler>();
+        test_world.register::<PolarizationGradient>();
+        test_world.register::<Position>();
         test_world.register::<RateCoefficients<Strontium88_461, { DEFAULT_BEAM_LIMIT }>>();
 
         let wavelength = 461e-9;
@@ -192,6 +229,7 @@ pub mod tests {
                 power: 1.0,
                 rayleigh_range: 1.0,
                 ellipticity: 0.0,
+                focus_offset: 0.0,
             })
             .build();
 
@@ -5,6 +5,7 @@ use std::marker::PhantomData;
 use rand;
 extern crate specs;
 use crate::laser_cooling::photons_scattered::TotalPhotonsScattered;
+use crate::parallel::{ForceSerial, MaybeParJoin};
 use rand::Rng;
 use specs::{Component, Entities, LazyUpdate, Read, ReadStorage, System, VecStorage};
 
@@ -42,15 +43,14 @@ impl<'a, T> System<'a> for RepumpSystem<T> where T : TransitionComponent {
         Read<'a, LazyUpdate>,
         ReadStorage<'a, TotalPhotonsScattered<T>>,
         Entities<'a>,
+        Option<Read<'a, ForceSerial>>,
     );
-    fn run(&mut self, (repump_opt, lazy, num, ent): Self::SystemData) {
-        use rayon::prelude::*;
-        use specs::ParJoin;
+    fn run(&mut self, (repump_opt, lazy, num, ent, force_serial): Self::SystemData) {
 
         match repump_opt {
             None => (),
             Some(repump) => {
-                (&ent, &num).par_join().for_each(|(ent, num)| {
+                (&ent, &num).maybe_par_for_each(force_serial.is_some(), |(ent, num)| {
                     if repump.if_loss(num.total) {
                         lazy.insert(ent, Dark {})
                     }
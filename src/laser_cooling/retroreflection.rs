@@ -0,0 +1,186 @@
+//! Retroreflected cooling beams with imperfect return power.
+//!
+//! Molasses and MOT beams are often retroreflected, and optical losses on the return path make the reflected
+//! beam weaker than the incident beam. The imbalance produces a net push along the incident beam, which displaces
+//! the trap. A cooling beam with a [Retroreflection] component is reflected back along its axis by the
+//! [CreateReturnBeamsSystem], which adds a counter-propagating traveling-wave beam with the same waist, wavelength
+//! and polarization, and `efficiency` times the power of the incident beam.
+//!
+//! The return beam is created on the first steps of the simulation. Unlike a [BeamConfiguration::StandingWave]
+//! beam, the incident and return beams are independent traveling waves, which include Doppler cooling but neglect
+//! interference.
+//!
+//! [BeamConfiguration::StandingWave]: super::standing_wave::BeamConfiguration::StandingWave
+
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+
+use super::CoolingLight;
+use crate::laser::gaussian::GaussianBeam;
+use crate::laser::index::LaserIndex;
+
+/// A component that retroreflects a cooling beam, see [crate::laser_cooling::retroreflection].
+#[derive(Deserialize, Serialize, Clone, Copy, Debug)]
+pub struct Retroreflection {
+    /// Ratio of the power of the return beam to the power of the incident beam, from 0 to 1.
+    pub efficiency: f64,
+}
+impl Component for Retroreflection {
+    type Storage = HashMapStorage<Self>;
+}
+
+/// A component marking a retroreflected beam for which the return beam has been created.
+#[derive(Default)]
+pub struct ReturnBeamCreated;
+impl Component for ReturnBeamCreated {
+    type Storage = NullStorage<Self>;
+}
+
+/// Creates the return beam of each cooling beam with a [Retroreflection].
+///
+/// Panics if the efficiency of a retroreflection is not between 0 and 1.
+pub struct CreateReturnBeamsSystem;
+impl<'a> System<'a> for CreateReturnBeamsSystem {
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, CoolingLight>,
+        ReadStorage<'a, GaussianBeam>,
+        ReadStorage<'a, Retroreflection>,
+        ReadStorage<'a, ReturnBeamCreated>,
+        Read<'a, LazyUpdate>,
+    );
+
+    fn run(
+        &mut self,
+        (entities, cooling, gaussian, retroreflections, created, updater): Self::SystemData,
+    ) {
+        for (incident, cooling, gaussian, retroreflection, _) in
+            (&entities, &cooling, &gaussian, &retroreflections, !&created).join()
+        {
+            if !(0.0..=1.0).contains(&retroreflection.efficiency) {
+                panic!(
+                    "Retroreflection efficiency {} is not between 0 and 1.",
+                    retroreflection.efficiency
+                );
+            }
+            let reflected = entities.create();
+            updater.insert(
+                reflected,
+                GaussianBeam {
+                    direction: -gaussian.direction,
+                    power: gaussian.power * retroreflection.efficiency,
+                    ..*gaussian
+                },
+            );
+            updater.insert(reflected, *cooling);
+            updater.insert(reflected, LaserIndex::default());
+            updater.insert(incident, ReturnBeamCreated);
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use crate::atom::{Atom, Force, Mass, Position, Velocity};
+    use crate::initiate::NewlyCreated;
+    use crate::integrator::{Pinned, Timestep};
+    use crate::laser::LaserPlugin;
+    use crate::laser_cooling::linear_response::with_mean_forces;
+    use crate::laser_cooling::LaserCoolingPlugin;
+    use crate::simulation::{Simulation, SimulationBuilder};
+    use crate::species::Rubidium87_780D2;
+    use assert_approx_eq::assert_approx_eq;
+    use nalgebra::Vector3;
+
+    const POWER: f64 = 0.01;
+    const DETUNING: f64 = -12.0;
+
+    fn create_simulation() -> Simulation {
+        let mut sim_builder = SimulationBuilder::default();
+        sim_builder.add_plugin(LaserPlugin::<4>);
+        sim_builder.add_plugin(LaserCoolingPlugin::<Rubidium87_780D2, 4>::default());
+        let mut sim = sim_builder.build();
+        sim.world.insert(Timestep { delta: 1.0e-6 });
+        sim
+    }
+
+    fn beam(direction: Vector3<f64>, power: f64) -> GaussianBeam {
+        GaussianBeam {
+            intersection: Vector3::new(0.0, 0.0, 0.0),
+            e_radius: 0.01,
+            power,
+            direction,
+            rayleigh_range: f64::INFINITY,
+            ellipticity: 0.0,
+            focus_offset: 0.0,
+        }
+    }
+
+    /// Returns the mean force on an atom at rest at the origin.
+    fn mean_force(sim: &mut Simulation) -> Vector3<f64> {
+        let atom = sim
+            .world
+            .create_entity()
+            .with(Position {
+                pos: Vector3::new(0.0, 0.0, 0.0).cast(),
+            })
+            .with(Velocity {
+                vel: Vector3::new(0.0, 0.0, 0.0).cast(),
+            })
+            .with(Force::new())
+            .with(Mass { value: 87.0 })
+            .with(Atom)
+            .with(Pinned)
+            .with(Rubidium87_780D2::default())
+            .with(NewlyCreated)
+            .build();
+        with_mean_forces(sim, |sim| {
+            for _ in 0..5 {
+                sim.step();
+            }
+        });
+        let force = sim.world.read_storage::<Force>().get(atom).unwrap().force;
+        force.cast::<f64>()
+    }
+
+    fn retroreflected_force(efficiency: f64) -> Vector3<f64> {
+        let mut sim = create_simulation();
+        sim.world
+            .create_entity()
+            .with(beam(Vector3::x(), POWER))
+            .with(CoolingLight::for_transition::<Rubidium87_780D2>(DETUNING, 1))
+            .with(Retroreflection { efficiency })
+            .build();
+        mean_force(&mut sim)
+    }
+
+    #[test]
+    fn test_imperfect_retroreflection_pushes_along_incident_beam() {
+        let force = retroreflected_force(0.9);
+
+        // The same beams, created explicitly as a counter-propagating pair.
+        let mut sim = create_simulation();
+        for (direction, power) in [(Vector3::x(), POWER), (-Vector3::x(), 0.9 * POWER)].iter() {
+            sim.world
+                .create_entity()
+                .with(beam(*direction, *power))
+                .with(CoolingLight::for_transition::<Rubidium87_780D2>(DETUNING, 1))
+                .build();
+        }
+        let expected = mean_force(&mut sim);
+
+        assert!(force[0] > 0.0, "force {} N", force[0]);
+        assert_approx_eq!(force[0], expected[0], 1e-6 * expected[0].abs());
+        assert_approx_eq!(force[1], 0.0, 1e-6 * force[0]);
+        assert_approx_eq!(force[2], 0.0, 1e-6 * force[0]);
+    }
+
+    #[test]
+    fn test_perfect_retroreflection_is_balanced() {
+        let unbalanced = retroreflected_force(0.9);
+        let force = retroreflected_force(1.0);
+        assert_approx_eq!(force[0], 0.0, 1e-9 * unbalanced[0]);
+    }
+}
@@ -5,6 +5,7 @@ use super::transition::TransitionComponent;
 use crate::constant;
 use crate::laser::index::LaserIndex;
 use crate::laser_cooling::doppler::DopplerShiftSamplers;
+use crate::parallel::{ForceSerial, MaybeParJoin};
 use super::zeeman::ZeemanShiftSampler;
 use specs::prelude::*;
 use specs::{Component, Join, ReadStorage, System, VecStorage, WriteStorage};
@@ -54,11 +55,12 @@ impl<T, const N: usize> Component for LaserDetuningSamplers<T, N> where T : Tran
 pub struct InitialiseLaserDetuningSamplersSystem<T, const N: usize>(PhantomData<T>) where T : TransitionComponent;
 
 impl<'a, T, const N: usize> System<'a> for InitialiseLaserDetuningSamplersSystem<T, N> where T : TransitionComponent {
-    type SystemData = (WriteStorage<'a, LaserDetuningSamplers<T, N>>,);
-    fn run(&mut self, (mut samplers,): Self::SystemData) {
-        use rayon::prelude::*;
-
-        (&mut samplers).par_join().for_each(|mut sampler| {
+    type SystemData = (
+        WriteStorage<'a, LaserDetuningSamplers<T, N>>,
+        Option<Read<'a, ForceSerial>>,
+    );
+    fn run(&mut self, (mut samplers, force_serial): Self::SystemData) {
+        (&mut samplers).maybe_par_for_each(force_serial.is_some(), |mut sampler| {
             sampler.contents = [LaserDetuningSampler::default(); N];
         });
     }
@@ -76,6 +78,7 @@ impl<'a, T, const N: usize> System<'a> for CalculateLaserDetuningSystem<T, N> wh
         ReadStorage<'a, DopplerShiftSamplers<N>>,
         ReadStorage<'a, ZeemanShiftSampler<T>>,
         WriteStorage<'a, LaserDetuningSamplers<T, N>>,
+        Option<Read<'a, ForceSerial>>,
     );
 
     fn run(
@@ -87,10 +90,9 @@ impl<'a, T, const N: usize> System<'a> for CalculateLaserDetuningSystem<T, N> wh
             doppler_samplers,
             zeeman_sampler,
             mut detuning_samplers,
+            force_serial,
         ): Self::SystemData,
     ) {
-        use rayon::prelude::*;
-
         // There are typically only a small number of lasers in a simulation.
         // For a speedup, cache the required components into thread memory,
         // so they can be distributed to parallel workers during the atom loop.
@@ -114,8 +116,8 @@ impl<'a, T, const N: usize> System<'a> for CalculateLaserDetuningSystem<T, N> wh
                 &zeeman_sampler,
                 &transitions,
             )
-                .par_join()
-                .for_each(
+                .maybe_par_for_each(
+                    force_serial.is_some(),
                     |(detuning_sampler, doppler_samplers, zeeman_sampler, _transitions)| {
                         for (index, cooling) in laser_array.iter().take(number_in_iteration) {
                             let without_zeeman = 2.0
@@ -0,0 +1,92 @@
+//! Pure functions describing the scattering of light by a two-level atom.
+//!
+//! Detunings and linewidths are angular frequencies, in SI units of rad/s.
+
+use crate::constant::HBAR;
+use nalgebra::Vector3;
+
+/// The saturation parameter `s = I / I_sat` of a transition.
+///
+/// # Arguments
+///
+/// `intensity`: intensity of the light, in SI units of W/m^2.
+///
+/// `saturation_intensity`: saturation intensity of the transition, in SI units of W/m^2.
+pub fn saturation_parameter(intensity: f64, saturation_intensity: f64) -> f64 {
+    intensity / saturation_intensity
+}
+
+/// The excitation rate of a two-level atom in the limit of low intensity, in Hz.
+///
+/// This is the rate coefficient used by the rate equations, and does not include saturation.
+///
+/// # Arguments
+///
+/// `gamma`: natural linewidth of the transition, `2 pi` times the linewidth in Hz.
+///
+/// `detuning`: detuning of the light from the transition, in rad/s.
+///
+/// `s`: saturation parameter, see [saturation_parameter].
+pub fn rate_coefficient(gamma: f64, detuning: f64, s: f64) -> f64 {
+    gamma / 2.0 * s / (1.0 + 4.0 * detuning.powi(2) / gamma.powi(2))
+}
+
+/// The steady-state photon scattering rate of a two-level atom, in Hz.
+///
+/// `rate = gamma/2 * s / (1 + s + 4 * detuning^2 / gamma^2)`
+///
+/// # Arguments
+///
+/// `gamma`: natural linewidth of the transition, `2 pi` times the linewidth in Hz.
+///
+/// `detuning`: detuning of the light from the transition, in rad/s.
+///
+/// `s`: saturation parameter, see [saturation_parameter].
+pub fn scattering_rate(gamma: f64, detuning: f64, s: f64) -> f64 {
+    gamma / 2.0 * s / (1.0 + s + 4.0 * detuning.powi(2) / gamma.powi(2))
+}
+
+/// The radiation pressure force exerted by a single beam, in SI units of N.
+///
+/// # Arguments
+///
+/// `rate`: rate at which photons are scattered from the beam, in Hz.
+///
+/// `k_vector`: wavevector of the beam, in SI units of rad/m.
+pub fn force_per_beam(rate: f64, k_vector: Vector3<f64>) -> Vector3<f64> {
+    HBAR * rate * k_vector
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::constant::PI;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_scattering_rate_on_resonance() {
+        let gamma = 2.0 * PI * 6.065e6;
+        assert_approx_eq!(scattering_rate(gamma, 0.0, 1.0), gamma / 4.0, 1e-6);
+        // At high intensity, the rate saturates at gamma / 2.
+        assert_approx_eq!(scattering_rate(gamma, 0.0, 1e9) / (gamma / 2.0), 1.0, 1e-6);
+    }
+
+    #[test]
+    fn test_scattering_rate_lorentzian_tail() {
+        let gamma = 2.0 * PI * 6.065e6;
+        let s = 0.1;
+        let detuning = 1000.0 * gamma;
+        let tail = s * gamma.powi(3) / (8.0 * detuning.powi(2));
+        assert_approx_eq!(scattering_rate(gamma, detuning, s) / tail, 1.0, 1e-5);
+        assert_approx_eq!(rate_coefficient(gamma, detuning, s) / tail, 1.0, 1e-5);
+    }
+
+    #[test]
+    fn test_saturation_parameter_and_force() {
+        assert_approx_eq!(saturation_parameter(33.38, 16.69), 2.0, 1e-12);
+        let k = Vector3::new(0.0, 0.0, 2.0 * PI / 780e-9);
+        let force = force_per_beam(1.0e6, k);
+        assert_approx_eq!(force[2], HBAR * 1.0e6 * k[2], 1e-30);
+        assert_approx_eq!(force[0], 0.0, 1e-30);
+    }
+}
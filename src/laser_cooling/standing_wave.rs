@@ -0,0 +1,239 @@
+//! Retroreflected cooling beams, which form a standing wave.
+//!
+//! By default, a cooling beam is a traveling wave, and the photons it scatters push the atom along its wavevector.
+//! A beam with [BeamConfiguration::StandingWave] is retroreflected onto itself. The atom absorbs photons equally
+//! from the incident and reflected beams, so there is no net radiation pressure. Instead, the interference of the
+//! two beams produces an intensity pattern `4 I cos^2(k.(r - r0))`, and the atom experiences a dipole force
+//! towards the antinodes (red detuning) or nodes (blue detuning) of the standing wave.
+//!
+//! The Doppler shifts of the incident and reflected beams are equal and opposite, and are neglected for a
+//! standing-wave beam. To include Doppler cooling from a retroreflected pair, model the incident and reflected
+//! beams as two counter-propagating traveling-wave beams.
+
+use std::marker::PhantomData;
+
+use super::transition::TransitionComponent;
+use super::wavevector::wavevector;
+use super::CoolingLight;
+use crate::atom::{Force, Position};
+use crate::constant;
+use crate::laser::gaussian::GaussianBeam;
+use crate::laser::index::LaserIndex;
+use crate::laser::intensity::LaserIntensitySamplers;
+use crate::parallel::{ForceSerial, MaybeParJoin};
+use nalgebra::Vector3;
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+
+/// A component that sets whether a cooling beam is a traveling wave or a retroreflected standing wave.
+///
+/// Cooling beams without this component are traveling waves. See [crate::laser_cooling::standing_wave].
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum BeamConfiguration {
+    /// A single beam, which pushes atoms along its wavevector.
+    #[default]
+    TravelingWave,
+    /// A beam retroreflected onto itself, which exerts no net push but a position-dependent dipole force.
+    StandingWave,
+}
+impl Component for BeamConfiguration {
+    type Storage = HashMapStorage<Self>;
+}
+
+/// Calculates the dipole force on a two-level atom in a standing wave, in SI units of N.
+///
+/// The force is `-grad U`, with the dipole potential `U = (hbar delta / 2) ln(1 + s / (1 + (2 delta / gamma)^2))`
+/// for the local saturation parameter `s = 4 s0 cos^2(k.(r - r0))`. The gradient of the beam envelope is neglected.
+///
+/// # Arguments
+///
+/// `k_vector`: wavevector of the incident beam, in SI units of rad/m.
+///
+/// `displacement`: position of the atom relative to an antinode of the standing wave, in SI units of m.
+///
+/// `saturation`: saturation parameter `s0 = I / I_sat` of the incident beam.
+///
+/// `detuning`: angular detuning of the light from the transition, in SI units of rad/s.
+///
+/// `gamma`: angular linewidth of the transition, in SI units of rad/s.
+pub fn standing_wave_dipole_force(
+    k_vector: &Vector3<f64>,
+    displacement: &Vector3<f64>,
+    saturation: f64,
+    detuning: f64,
+    gamma: f64,
+) -> Vector3<f64> {
+    let phase = k_vector.dot(displacement);
+    let local_saturation = 4.0 * saturation * phase.cos().powi(2);
+    let denominator = 1.0 + (2.0 * detuning / gamma).powi(2) + local_saturation;
+    constant::HBAR * detuning / 2.0 * 4.0 * saturation * (2.0 * phase).sin() / denominator
+        * k_vector
+}
+
+/// Applies the dipole force from cooling beams with [BeamConfiguration::StandingWave].
+///
+/// The incident beam of each standing wave is assumed to have an antinode at its `intersection`.
+#[derive(Default)]
+pub struct ApplyStandingWaveDipoleForceSystem<T, const N: usize>(PhantomData<T>)
+where
+    T: TransitionComponent;
+
+impl<'a, T, const N: usize> System<'a> for ApplyStandingWaveDipoleForceSystem<T, N>
+where
+    T: TransitionComponent,
+{
+    type SystemData = (
+        ReadStorage<'a, CoolingLight>,
+        ReadStorage<'a, LaserIndex>,
+        ReadStorage<'a, GaussianBeam>,
+        ReadStorage<'a, BeamConfiguration>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, LaserIntensitySamplers<N>>,
+        ReadStorage<'a, T>,
+        WriteStorage<'a, Force>,
+        Option<Read<'a, ForceSerial>>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            cooling,
+            indices,
+            gaussian,
+            configurations,
+            positions,
+            intensity_samplers,
+            transitions,
+            mut forces,
+            force_serial,
+        ): Self::SystemData,
+    ) {
+        let standing_waves: Vec<(usize, Vector3<f64>, Vector3<f64>, f64)> =
+            (&cooling, &indices, &gaussian, &configurations)
+                .join()
+                .filter(|(_, _, _, configuration)| {
+                    **configuration == BeamConfiguration::StandingWave
+                })
+                .map(|(cooling, index, gaussian, _)| {
+                    let detuning = 2.0 * constant::PI * (cooling.frequency() - T::frequency());
+                    (
+                        index.index,
+                        wavevector(cooling, gaussian),
+                        gaussian.intersection,
+                        detuning,
+                    )
+                })
+                .collect();
+        if standing_waves.is_empty() {
+            return;
+        }
+
+        (&positions, &intensity_samplers, &transitions, &mut forces).maybe_par_for_each(
+            force_serial.is_some(),
+            |(pos, samplers, _, force)| {
+                for (index, k_vector, intersection, detuning) in standing_waves.iter() {
+                    let saturation =
+                        samplers.contents[*index].intensity / T::saturation_intensity();
+                    let new_force = standing_wave_dipole_force(
+                        k_vector,
+                        &(pos.pos.cast::<f64>() - intersection),
+                        saturation,
+                        *detuning,
+                        T::gamma(),
+                    );
+                    force.force += new_force.cast();
+                }
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use crate::atom::{Atom, Mass, Velocity};
+    use crate::initiate::NewlyCreated;
+    use crate::integrator::Timestep;
+    use crate::laser::LaserPlugin;
+    use crate::laser_cooling::force::EmissionForceOption;
+    use crate::laser_cooling::transition::AtomicTransition;
+    use crate::laser_cooling::LaserCoolingPlugin;
+    use crate::simulation::SimulationBuilder;
+    use crate::species::Rubidium87_780D2;
+
+    /// Simulates a few steps for an initially stationary atom in a single cooling beam, returning the force on the atom.
+    fn force_on_stationary_atom(
+        configuration: BeamConfiguration,
+        position: Vector3<f64>,
+    ) -> Vector3<f64> {
+        let mut sim_builder = SimulationBuilder::default();
+        sim_builder.add_plugin(LaserPlugin::<1>);
+        sim_builder.add_plugin(LaserCoolingPlugin::<Rubidium87_780D2, 1>::default());
+        let mut sim = sim_builder.build();
+        sim.world.insert(Timestep { delta: 1.0e-6 });
+        sim.world.insert(EmissionForceOption::Off);
+
+        sim.world
+            .create_entity()
+            .with(GaussianBeam {
+                intersection: Vector3::new(0.0, 0.0, 0.0),
+                e_radius: 0.01,
+                power: 0.01,
+                direction: Vector3::x(),
+                rayleigh_range: f64::INFINITY,
+                ellipticity: 0.0,
+                focus_offset: 0.0,
+            })
+            .with(CoolingLight::for_transition::<Rubidium87_780D2>(-6.0, 1))
+            .with(configuration)
+            .build();
+        let atom = sim
+            .world
+            .create_entity()
+            .with(Position {
+                pos: position.cast(),
+            })
+            .with(Velocity {
+                vel: Vector3::new(0.0, 0.0, 0.0).cast(),
+            })
+            .with(Force::new())
+            .with(Mass { value: 87.0 })
+            .with(Atom)
+            .with(Rubidium87_780D2)
+            .with(NewlyCreated)
+            .build();
+
+        for _ in 0..3 {
+            sim.step();
+        }
+        let force = sim.world.read_storage::<Force>().get(atom).unwrap().force;
+        force.cast::<f64>()
+    }
+
+    #[test]
+    fn test_standing_wave_has_no_net_push_at_antinode() {
+        let antinode = Vector3::new(0.0, 0.0, 0.0);
+        let traveling = force_on_stationary_atom(BeamConfiguration::TravelingWave, antinode);
+        assert!(traveling[0] > 0.0);
+
+        let standing = force_on_stationary_atom(BeamConfiguration::StandingWave, antinode);
+        assert_eq!(standing, Vector3::new(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_red_detuned_standing_wave_attracts_to_antinode() {
+        let wavelength = Rubidium87_780D2::wavelength();
+        let k_vector = Vector3::x() * 2.0 * constant::PI / wavelength;
+        let gamma = Rubidium87_780D2::gamma();
+        let detuning = -gamma;
+
+        let force = |x: f64| {
+            standing_wave_dipole_force(&k_vector, &Vector3::new(x, 0.0, 0.0), 1.0, detuning, gamma)
+        };
+        assert_eq!(force(0.0)[0], 0.0);
+        assert!(force(wavelength / 16.0)[0] < 0.0);
+        assert!(force(-wavelength / 16.0)[0] > 0.0);
+        assert!(force(wavelength / 4.0)[0].abs() < 1e-9 * force(wavelength / 16.0)[0].abs());
+    }
+}
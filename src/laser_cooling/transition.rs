@@ -1,4 +1,6 @@
+use crate::constant::{AMU, BOLTZCONST, C, HBAR, PI};
 use specs::prelude::*;
+use std::fmt;
 
 /// Physical constants of an atomic transition used for laser cooling.
 pub trait AtomicTransition {
@@ -26,6 +28,18 @@ pub trait AtomicTransition {
     fn gamma() -> f64;
     /// Wavelength of the laser cooling transition, m.
     fn wavelength() -> f64;
+    /// The Doppler cooling limit `hbar * gamma / (2 k_B)`, in units of K.
+    fn doppler_temperature() -> f64 {
+        HBAR * Self::gamma() / (2.0 * BOLTZCONST)
+    }
+    /// The recoil temperature `(hbar k)^2 / (m k_B)` of an atom of `mass` (in atomic mass units), in units of K.
+    fn recoil_temperature(mass: f64) -> f64 {
+        recoil_temperature(Self::wavelength(), mass)
+    }
+}
+
+fn recoil_temperature(wavelength: f64, mass: f64) -> f64 {
+    (HBAR * 2.0 * PI / wavelength).powi(2) / (mass * AMU * BOLTZCONST)
 }
 
 /// A transition which can be used as a component.
@@ -88,4 +102,218 @@ macro_rules! transition {
             type Storage = specs::VecStorage<Self>;
         }
     };
-}
\ No newline at end of file
+}
+
+/// The constants of an atomic transition, held as values rather than as a type.
+///
+/// Use [TransitionParameters::builder] to define a one-off species inline, for example to evaluate design-time
+/// functions for a transition without a preset. The simulation systems are generic over a transition type, so
+/// species used in a simulation are still defined with the [transition!](crate::transition) macro.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TransitionParameters {
+    /// Frequency of the transition, in Hz.
+    pub frequency: f64,
+    /// Linewidth of the transition, in Hz.
+    pub linewidth: f64,
+    /// Saturation intensity, in units of W/m^2.
+    pub saturation_intensity: f64,
+    /// Magnetic moments of the sigma+, sigma- and pi transitions, in units of J/T, see [AtomicTransition::mup].
+    pub mup: f64,
+    pub mum: f64,
+    pub muz: f64,
+}
+impl TransitionParameters {
+    /// Starts building the parameters of a transition, see [TransitionBuilder].
+    pub fn builder() -> TransitionBuilder {
+        TransitionBuilder::default()
+    }
+
+    /// The parameters of the transition `T`.
+    pub fn of<T: AtomicTransition>() -> Self {
+        TransitionParameters {
+            frequency: T::frequency(),
+            linewidth: T::linewidth(),
+            saturation_intensity: T::saturation_intensity(),
+            mup: T::mup(),
+            mum: T::mum(),
+            muz: T::muz(),
+        }
+    }
+
+    /// Wavelength of the transition, m.
+    pub fn wavelength(&self) -> f64 {
+        C / self.frequency
+    }
+
+    /// The factor Gamma, equal to 2 pi times the linewidth.
+    pub fn gamma(&self) -> f64 {
+        2.0 * PI * self.linewidth
+    }
+
+    /// Precalculated prefactor used in the determination of rate coefficients.
+    pub fn rate_prefactor(&self) -> f64 {
+        self.gamma().powi(3) / (self.saturation_intensity * 8.0)
+    }
+
+    /// The Doppler cooling limit `hbar * gamma / (2 k_B)`, in units of K.
+    pub fn doppler_temperature(&self) -> f64 {
+        HBAR * self.gamma() / (2.0 * BOLTZCONST)
+    }
+
+    /// The recoil temperature `(hbar k)^2 / (m k_B)` of an atom of `mass` (in atomic mass units), in units of K.
+    pub fn recoil_temperature(&self, mass: f64) -> f64 {
+        recoil_temperature(self.wavelength(), mass)
+    }
+}
+
+/// The reasons that a [TransitionBuilder] may fail to build.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TransitionBuilderError {
+    /// A required parameter was not set.
+    Missing(&'static str),
+    /// A parameter that must be positive and finite was not.
+    NotPositive(&'static str, f64),
+    /// A magnetic moment was not finite.
+    NotFinite(&'static str, f64),
+}
+impl fmt::Display for TransitionBuilderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TransitionBuilderError::Missing(name) => {
+                write!(f, "The {} of the transition was not set.", name)
+            }
+            TransitionBuilderError::NotPositive(name, value) => write!(
+                f,
+                "The {} of the transition must be positive and finite, but was {}.",
+                name, value
+            ),
+            TransitionBuilderError::NotFinite(name, value) => write!(
+                f,
+                "The {} of the transition must be finite, but was {}.",
+                name, value
+            ),
+        }
+    }
+}
+
+/// Builds validated [TransitionParameters].
+///
+/// The frequency, linewidth and saturation intensity are required. The magnetic moments default to zero, for a
+/// transition with no Zeeman shift.
+#[derive(Clone, Copy, Default)]
+pub struct TransitionBuilder {
+    frequency: Option<f64>,
+    linewidth: Option<f64>,
+    saturation_intensity: Option<f64>,
+    magnetic_moments: (f64, f64, f64),
+}
+impl TransitionBuilder {
+    /// Frequency of the transition, in Hz.
+    pub fn frequency(mut self, frequency: f64) -> Self {
+        self.frequency = Some(frequency);
+        self
+    }
+
+    /// Linewidth of the transition, in Hz.
+    pub fn linewidth(mut self, linewidth: f64) -> Self {
+        self.linewidth = Some(linewidth);
+        self
+    }
+
+    /// Saturation intensity, in units of W/m^2.
+    pub fn saturation_intensity(mut self, saturation_intensity: f64) -> Self {
+        self.saturation_intensity = Some(saturation_intensity);
+        self
+    }
+
+    /// Magnetic moments of the sigma+, sigma- and pi transitions, in units of J/T.
+    pub fn magnetic_moments(mut self, mup: f64, mum: f64, muz: f64) -> Self {
+        self.magnetic_moments = (mup, mum, muz);
+        self
+    }
+
+    /// Validates the parameters, and returns an error describing the first missing or invalid parameter.
+    pub fn build(self) -> Result<TransitionParameters, TransitionBuilderError> {
+        let positive = |name: &'static str, value: Option<f64>| match value {
+            None => Err(TransitionBuilderError::Missing(name)),
+            Some(value) if value > 0.0 && value.is_finite() => Ok(value),
+            Some(value) => Err(TransitionBuilderError::NotPositive(name, value)),
+        };
+        let finite = |name: &'static str, value: f64| {
+            if value.is_finite() {
+                Ok(value)
+            } else {
+                Err(TransitionBuilderError::NotFinite(name, value))
+            }
+        };
+        let (mup, mum, muz) = self.magnetic_moments;
+        Ok(TransitionParameters {
+            frequency: positive("frequency", self.frequency)?,
+            linewidth: positive("linewidth", self.linewidth)?,
+            saturation_intensity: positive("saturation intensity", self.saturation_intensity)?,
+            mup: finite("mup", mup)?,
+            mum: finite("mum", mum)?,
+            muz: finite("muz", muz)?,
+        })
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use crate::species::Rubidium87_780D2;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_rubidium_doppler_temperature() {
+        assert_approx_eq!(Rubidium87_780D2::doppler_temperature(), 146e-6, 1e-6);
+    }
+
+    #[test]
+    fn test_rubidium_recoil_temperature() {
+        assert_approx_eq!(Rubidium87_780D2::recoil_temperature(86.909), 362e-9, 1e-9);
+        assert_approx_eq!(
+            TransitionParameters::of::<Rubidium87_780D2>().recoil_temperature(86.909),
+            Rubidium87_780D2::recoil_temperature(86.909),
+            1e-15
+        );
+    }
+
+    #[test]
+    fn test_transition_builder() {
+        use crate::constant::BOHRMAG;
+
+        let parameters = TransitionParameters::builder()
+            .frequency(384_228_115_202_521.0)
+            .linewidth(6.065e6)
+            .saturation_intensity(16.69)
+            .magnetic_moments(BOHRMAG, -BOHRMAG, 0.0)
+            .build()
+            .unwrap();
+        assert_eq!(parameters, TransitionParameters::of::<Rubidium87_780D2>());
+        assert_approx_eq!(parameters.wavelength(), Rubidium87_780D2::wavelength(), 1e-15);
+        assert_approx_eq!(
+            parameters.rate_prefactor() / Rubidium87_780D2::rate_prefactor(),
+            1.0,
+            1e-12
+        );
+
+        let missing = TransitionParameters::builder()
+            .linewidth(6.065e6)
+            .saturation_intensity(16.69)
+            .build();
+        assert_eq!(missing, Err(TransitionBuilderError::Missing("frequency")));
+        assert!(missing.unwrap_err().to_string().contains("frequency"));
+
+        let negative = TransitionParameters::builder()
+            .frequency(384_228_115_202_521.0)
+            .linewidth(-1.0)
+            .saturation_intensity(16.69)
+            .build();
+        assert_eq!(
+            negative,
+            Err(TransitionBuilderError::NotPositive("linewidth", -1.0))
+        );
+    }
+}
@@ -4,6 +4,7 @@ extern crate rayon;
 
 use crate::laser::sampler::CoolingLaserSamplerMasks;
 use crate::laser_cooling::rate::RateCoefficients;
+use crate::parallel::{ForceSerial, MaybeParJoin};
 use serde::{Deserialize, Serialize};
 use specs::prelude::*;
 use std::{fmt, marker::PhantomData};
@@ -54,6 +55,10 @@ impl<T> Component for TwoLevelPopulation<T> where T : TransitionComponent + 'sta
 }
 
 /// Calculates the TwoLevelPopulation from the natural linewidth and the `RateCoefficients`
+///
+/// The excited population never exceeds 1/2, so the photons scattered from each beam, and the resulting absorption
+/// force, are limited by the saturated scattering rate `gamma / 2`. An infinite rate coefficient, for example from
+/// an extreme beam intensity, saturates the transition.
 #[derive(Default)]
 pub struct CalculateTwoLevelPopulationSystem<T, const N: usize>(PhantomData<T>) where T: TransitionComponent;
 
@@ -63,22 +68,26 @@ impl<'a, T, const N: usize> System<'a> for CalculateTwoLevelPopulationSystem<T,
         ReadStorage<'a, RateCoefficients<T, N>>,
         ReadStorage<'a, CoolingLaserSamplerMasks<N>>,
         WriteStorage<'a, TwoLevelPopulation<T>>,
+        Option<Read<'a, ForceSerial>>,
     );
 
     fn run(
         &mut self,
-        (transition, rate_coefficients, masks, mut twolevel_population): Self::SystemData,
+        (
+            transition,
+            rate_coefficients,
+            masks,
+            mut twolevel_population,
+            force_serial,
+        ): Self::SystemData,
     ) {
-        use rayon::prelude::*;
-
         (
             &transition,
             &rate_coefficients,
             &masks,
             &mut twolevel_population,
         )
-            .par_join()
-            .for_each(|(_transition, rates, mask, twolevel)| {
+            .maybe_par_for_each(force_serial.is_some(), |(_transition, rates, mask, twolevel)| {
                 let mut sum_rates: f64 = 0.;
 
                 for count in 0..rates.contents.len() {
@@ -86,7 +95,11 @@ impl<'a, T, const N: usize> System<'a> for CalculateTwoLevelPopulationSystem<T,
                         sum_rates += rates.contents[count].rate;
                     }
                 }
-                twolevel.excited = sum_rates / (T::gamma() + 2. * sum_rates);
+                twolevel.excited = if sum_rates.is_infinite() {
+                    0.5
+                } else {
+                    sum_rates / (T::gamma() + 2. * sum_rates)
+                };
                 twolevel.calculate_ground_state();
             });
     }
@@ -194,4 +207,40 @@ pub mod tests {
             0.01
         );
     }
+
+    /// Tests that an infinite rate coefficient saturates the transition rather than giving a NaN population.
+    #[test]
+    fn test_popn_infinite_rate_saturates() {
+        let mut test_world = World::new();
+        test_world.register::<RateCoefficients<Rubidium87_780D2, { DEFAULT_BEAM_LIMIT }>>();
+        test_world.register::<Rubidium87_780D2>();
+        test_world.register::<CoolingLaserSamplerMasks<{ DEFAULT_BEAM_LIMIT }>>();
+        test_world.register::<TwoLevelPopulation<Rubidium87_780D2>>();
+
+        let mut active_lasers = [LaserSamplerMask { filled: false }; DEFAULT_BEAM_LIMIT];
+        active_lasers[0] = LaserSamplerMask { filled: true };
+
+        let mut rc = RateCoefficient::<Rubidium87_780D2>::default();
+        rc.rate = f64::INFINITY;
+
+        let atom1 = test_world
+            .create_entity()
+            .with(RateCoefficients {
+                contents: [rc; DEFAULT_BEAM_LIMIT],
+            })
+            .with(Rubidium87_780D2)
+            .with(CoolingLaserSamplerMasks {
+                contents: active_lasers,
+            })
+            .with(TwoLevelPopulation::<Rubidium87_780D2>::default())
+            .build();
+
+        let mut system = CalculateTwoLevelPopulationSystem::<Rubidium87_780D2, { DEFAULT_BEAM_LIMIT }>::default();
+        system.run_now(&test_world);
+        test_world.maintain();
+        let sampler_storage = test_world.read_storage::<TwoLevelPopulation<Rubidium87_780D2>>();
+        let population = sampler_storage.get(atom1).expect("entity not found");
+        assert_eq!(population.excited, 0.5);
+        assert_eq!(population.ground, 0.5);
+    }
 }
@@ -0,0 +1,103 @@
+//! Caching of the wavevectors of cooling beams.
+//!
+//! The wavevector of each beam is the same for every atom, so it is calculated once per step
+//! rather than once per atom.
+//!
+//! A standing-wave beam exerts no net push and its Doppler shift is neglected, so its cached wavevector is zero,
+//! see [crate::laser_cooling::standing_wave].
+
+use super::standing_wave::BeamConfiguration;
+use super::CoolingLight;
+use crate::laser::gaussian::GaussianBeam;
+use crate::laser::index::LaserIndex;
+use nalgebra::Vector3;
+use specs::prelude::*;
+
+/// A resource that holds the wavevector of each cooling beam.
+#[derive(Clone, Default)]
+pub struct CoolingWavevectors {
+    /// The [LaserIndex] and wavevector of each cooling beam, with wavevectors in SI units of rad/m.
+    pub wavevectors: Vec<(usize, Vector3<f64>)>,
+}
+
+/// Calculates the wavevector of a cooling beam, in SI units of rad/m.
+pub fn wavevector(cooling: &CoolingLight, gaussian: &GaussianBeam) -> Vector3<f64> {
+    gaussian.direction.normalize() * cooling.wavenumber()
+}
+
+/// Calculates the wavevectors of the cooling beams and stores them in the [CoolingWavevectors] resource.
+///
+/// This system must run after the lasers are indexed.
+pub struct CacheCoolingWavevectorsSystem;
+impl<'a> System<'a> for CacheCoolingWavevectorsSystem {
+    type SystemData = (
+        ReadStorage<'a, CoolingLight>,
+        ReadStorage<'a, LaserIndex>,
+        ReadStorage<'a, GaussianBeam>,
+        ReadStorage<'a, BeamConfiguration>,
+        Write<'a, CoolingWavevectors>,
+    );
+
+    fn run(&mut self, (cooling, indices, gaussian, configurations, mut cache): Self::SystemData) {
+        cache.wavevectors.clear();
+        for (cooling, index, gaussian, configuration) in
+            (&cooling, &indices, &gaussian, configurations.maybe()).join()
+        {
+            let k_vector = match configuration {
+                Some(BeamConfiguration::StandingWave) => Vector3::new(0.0, 0.0, 0.0),
+                _ => wavevector(cooling, gaussian),
+            };
+            cache.wavevectors.push((index.index, k_vector));
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use crate::laser::gaussian::calculate_rayleigh_range;
+
+    #[test]
+    fn test_cache_cooling_wavevectors() {
+        let mut test_world = World::new();
+        test_world.register::<LaserIndex>();
+        test_world.register::<CoolingLight>();
+        test_world.register::<GaussianBeam>();
+        test_world.register::<BeamConfiguration>();
+
+        let wavelength = 780e-9;
+        test_world
+            .create_entity()
+            .with(CoolingLight {
+                polarization: 1,
+                wavelength,
+            })
+            .with(LaserIndex {
+                index: 3,
+                initiated: true,
+            })
+            .with(GaussianBeam {
+                direction: Vector3::new(0.0, 2.0, 0.0),
+                intersection: Vector3::new(0.0, 0.0, 0.0),
+                e_radius: 2.0,
+                power: 1.0,
+                rayleigh_range: calculate_rayleigh_range(&wavelength, &2.0),
+                ellipticity: 0.0,
+                focus_offset: 0.0,
+            })
+            .build();
+
+        let mut system = CacheCoolingWavevectorsSystem;
+        System::setup(&mut system, &mut test_world);
+        system.run_now(&test_world);
+
+        let cache = test_world.read_resource::<CoolingWavevectors>();
+        assert_eq!(cache.wavevectors.len(), 1);
+        assert_eq!(cache.wavevectors[0].0, 3);
+        assert_eq!(
+            cache.wavevectors[0].1,
+            Vector3::new(0.0, 2.0 * crate::constant::PI / wavelength, 0.0)
+        );
+    }
+}
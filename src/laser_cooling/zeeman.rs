@@ -5,6 +5,7 @@ use std::marker::PhantomData;
 use crate::magnetic::MagneticFieldSampler;
 use crate::constant::HBAR;
 use crate::initiate::NewlyCreated;
+use crate::parallel::{ForceSerial, MaybeParJoin};
 use serde::Serialize;
 use specs::prelude::*;
 
@@ -66,21 +67,24 @@ impl<'a, T> System<'a> for CalculateZeemanShiftSystem<T> where T : TransitionCom
         WriteStorage<'a, ZeemanShiftSampler<T>>,
         ReadStorage<'a, MagneticFieldSampler>,
         ReadStorage<'a, T>,
+        Option<Read<'a, ForceSerial>>,
     );
 
     fn run(
         &mut self,
-        (mut zeeman_sampler, magnetic_field_sampler, atomic_transition): Self::SystemData,
+        (
+            mut zeeman_sampler,
+            magnetic_field_sampler,
+            atomic_transition,
+            force_serial,
+        ): Self::SystemData,
     ) {
-        use rayon::prelude::*;
-
         (
             &mut zeeman_sampler,
             &magnetic_field_sampler,
             &atomic_transition,
         )
-            .par_join()
-            .for_each(|(zeeman, magnetic_field, _transition)| {
+            .maybe_par_for_each(force_serial.is_some(), |(zeeman, magnetic_field, _transition)| {
                 zeeman.sigma_plus = T::mup() / HBAR * magnetic_field.magnitude;
                 zeeman.sigma_minus = T::mum() / HBAR * magnetic_field.magnitude;
                 zeeman.sigma_pi = T::muz() / HBAR * magnetic_field.magnitude;
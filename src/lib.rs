@@ -8,22 +8,41 @@ extern crate atomecs_derive;
 
 pub mod atom;
 pub mod atom_sources;
+pub mod blowaway;
+pub mod callbacks;
 pub mod collisions;
 pub mod constant;
+pub mod custom_force;
 pub mod destructor;
+pub mod diagnostics;
+pub mod dispatch;
 pub mod dipole;
+pub mod energy;
 //pub mod ecs;
+pub mod equilibrium;
+pub mod evaporation;
+pub mod excursion;
 pub mod gravity;
 pub mod initiate;
 pub mod integration_tests;
 pub mod integrator;
+pub mod kick;
 pub mod laser;
 pub mod laser_cooling;
 pub mod magnetic;
 pub mod maths;
+pub mod merge;
+pub mod minimum;
 pub mod output;
+pub mod parallel;
+pub mod periodic;
+pub mod query;
 pub mod ramp;
+pub mod replay;
+pub mod rng;
 pub mod shapes;
 pub mod sim_region;
 pub mod species;
 pub mod simulation;
+pub mod thermostat;
+pub mod zeeman_slower;
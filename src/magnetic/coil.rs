@@ -0,0 +1,219 @@
+//! Magnetic fields of a pair of coaxial current loops
+
+use crate::atom::Position;
+use crate::constant::MU0;
+use crate::magnetic::MagneticFieldSampler;
+use crate::parallel::{ForceSerial, MaybeParJoin};
+use nalgebra::{Matrix3, Vector3};
+use specs::{Component, HashMapStorage, Join, Read, ReadStorage, System, WriteStorage};
+
+/// A component representing a pair of coaxial circular current loops.
+///
+/// The loops are centred on the entity's [Position], and are placed at `-separation / 2` (loop `a`) and
+/// `+separation / 2` (loop `b`) along `direction`. A positive current circulates right-handedly about `direction`.
+///
+/// Opposite currents of equal size form an anti-Helmholtz pair, with a quadrupole field zero at the centre.
+/// An imbalance between the currents shifts the field zero along the axis, towards the weaker loop.
+///
+/// The field is calculated from the Biot-Savart law for the on-axis field of each loop, expanded to lowest order
+/// in the distance from the axis. It is accurate for atoms close to the axis compared to the loop radius.
+#[derive(Clone, Copy)]
+pub struct CoilPair {
+    /// Distance between the two loops, in SI units of m.
+    pub separation: f64,
+    /// Radius of each loop, in SI units of m.
+    pub radius: f64,
+    /// Current in loop `a`, at `-separation / 2` along `direction`, in SI units of A.
+    pub current_a: f64,
+    /// Current in loop `b`, at `+separation / 2` along `direction`, in SI units of A.
+    pub current_b: f64,
+    /// A unit vector pointing along the symmetry axis of the pair, from loop `a` to loop `b`.
+    pub direction: Vector3<f64>,
+}
+impl CoilPair {
+    /// Creates an anti-Helmholtz `CoilPair`, with `current` in loop `a` and `-current` in loop `b`.
+    pub fn anti_helmholtz(
+        separation: f64,
+        radius: f64,
+        current: f64,
+        direction: Vector3<f64>,
+    ) -> Self {
+        Self {
+            separation,
+            radius,
+            current_a: current,
+            current_b: -current,
+            direction: direction.normalize(),
+        }
+    }
+
+    /// Calculates the magnetic field and its jacobian at `pos`, for a pair centred at `centre`.
+    pub fn calculate_field(
+        &self,
+        pos: Vector3<f64>,
+        centre: Vector3<f64>,
+    ) -> (Vector3<f64>, Matrix3<f64>) {
+        let direction = self.direction.normalize();
+        let delta = pos - centre;
+        let z = delta.dot(&direction);
+        let rho = delta - z * direction;
+        let axial = direction * direction.transpose();
+        let transverse = Matrix3::identity() - axial;
+
+        let mut field = Vector3::zeros();
+        let mut jacobian = Matrix3::zeros();
+        for (offset, current) in [
+            (-0.5 * self.separation, self.current_a),
+            (0.5 * self.separation, self.current_b),
+        ]
+        .iter()
+        {
+            let (b, db, d2b) = on_axis_field(z - offset, self.radius, *current);
+            // Bz = B - rho^2 B'' / 4 and B_rho = - rho B' / 2, which is divergence and curl free near the axis.
+            field += (b - 0.25 * rho.norm_squared() * d2b) * direction - 0.5 * db * rho;
+            jacobian += db * (axial - 0.5 * transverse)
+                - 0.5 * d2b * (direction * rho.transpose() + rho * direction.transpose());
+        }
+        (field, jacobian)
+    }
+}
+
+impl Component for CoilPair {
+    type Storage = HashMapStorage<Self>;
+}
+
+/// Calculates the on-axis field of a single current loop, and its first and second derivatives along the axis.
+///
+/// # Arguments
+///
+/// `z`: axial distance from the plane of the loop, m
+///
+/// `radius`: radius of the loop, m
+///
+/// `current`: current in the loop, A
+fn on_axis_field(z: f64, radius: f64, current: f64) -> (f64, f64, f64) {
+    let k = 0.5 * MU0 * current * radius.powi(2);
+    let s = radius.powi(2) + z.powi(2);
+    (
+        k * s.powf(-1.5),
+        -3.0 * k * z * s.powf(-2.5),
+        3.0 * k * (4.0 * z.powi(2) - radius.powi(2)) * s.powf(-3.5),
+    )
+}
+
+/// Updates the values of magnetic field samplers to include the fields of [CoilPair]s in the world.
+pub struct SampleCoilPairFieldSystem;
+
+impl<'a> System<'a> for SampleCoilPairFieldSystem {
+    type SystemData = (
+        WriteStorage<'a, MagneticFieldSampler>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, CoilPair>,
+        Option<Read<'a, ForceSerial>>,
+    );
+    fn run(&mut self, (mut sampler, pos, coils, force_serial): Self::SystemData) {
+        for (centre, coil) in (&pos, &coils).join() {
+            let centre = centre.pos.cast::<f64>();
+            (&pos, &mut sampler).maybe_par_for_each(force_serial.is_some(), |(pos, sampler)| {
+                let (field, jacobian) = coil.calculate_field(pos.pos.cast::<f64>(), centre);
+                sampler.field += field;
+                sampler.jacobian += jacobian;
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use assert_approx_eq::assert_approx_eq;
+    use specs::prelude::*;
+
+    #[test]
+    fn test_anti_helmholtz_gradient() {
+        let (separation, radius, current) = (0.05, 0.04, 100.0);
+        let coil = CoilPair::anti_helmholtz(separation, radius, current, Vector3::z());
+        let centre = Vector3::new(0.0, 0.0, 0.0);
+        let (field, jacobian) = coil.calculate_field(centre, centre);
+
+        // A pure quadrupole at the centre, with axial gradient 3 mu0 I R^2 d / (R^2 + d^2)^(5/2).
+        let d = 0.5 * separation;
+        let gradient =
+            3.0 * MU0 * current * radius.powi(2) * d / (radius.powi(2) + d.powi(2)).powf(2.5);
+        assert_approx_eq!(field.norm(), 0.0, 1e-15);
+        assert_approx_eq!(jacobian[(2, 2)], -gradient, 1e-9);
+        assert_approx_eq!(jacobian[(0, 0)], 0.5 * gradient, 1e-9);
+        assert_approx_eq!(jacobian[(1, 1)], 0.5 * gradient, 1e-9);
+
+        // The analytic jacobian agrees with a numerical derivative of the field close to the axis.
+        let pos = Vector3::new(1.0e-4, -2.0e-4, 3.0e-4);
+        let (_, jacobian) = coil.calculate_field(pos, centre);
+        let step = 1.0e-7;
+        for i in 0..3 {
+            let mut dr = Vector3::zeros();
+            dr[i] = step;
+            let (b_plus, _) = coil.calculate_field(pos + dr, centre);
+            let (b_minus, _) = coil.calculate_field(pos - dr, centre);
+            let numerical = (b_plus - b_minus) / (2.0 * step);
+            for j in 0..3 {
+                assert_approx_eq!(jacobian[(j, i)], numerical[j], 1e-3 * gradient);
+            }
+        }
+    }
+
+    #[test]
+    fn test_current_imbalance_shifts_zero() {
+        let centre = Vector3::new(0.0, 0.0, 0.0);
+        let coil = CoilPair {
+            separation: 0.05,
+            radius: 0.04,
+            current_a: 100.0,
+            current_b: -90.0,
+            direction: Vector3::z(),
+        };
+        let (field, _) = coil.calculate_field(centre, centre);
+        assert!(field.norm() > 0.0);
+
+        // Locate the field zero on the axis with Newton's method; it moves towards the weaker loop.
+        let mut z = 0.0;
+        for _ in 0..20 {
+            let (field, jacobian) = coil.calculate_field(Vector3::new(0.0, 0.0, z), centre);
+            z -= field[2] / jacobian[(2, 2)];
+        }
+        let (zero_field, _) = coil.calculate_field(Vector3::new(0.0, 0.0, z), centre);
+        assert!(z > 0.0 && z < 0.025, "zero at z = {} m", z);
+        assert!(zero_field.norm() < 1e-9 * field.norm());
+    }
+
+    #[test]
+    fn test_coil_pair_system() {
+        let mut test_world = World::new();
+        test_world.register::<CoilPair>();
+        test_world.register::<Position>();
+        test_world.register::<MagneticFieldSampler>();
+
+        let coil = CoilPair::anti_helmholtz(0.05, 0.04, 100.0, Vector3::x());
+        let centre = Vector3::new(0.0, 0.01, 0.0);
+        test_world
+            .create_entity()
+            .with(coil)
+            .with(Position { pos: centre.cast() })
+            .build();
+        let pos = Vector3::new(1.0e-3, 0.011, -1.0e-3);
+        let sampler = test_world
+            .create_entity()
+            .with(Position { pos: pos.cast() })
+            .with(MagneticFieldSampler::default())
+            .build();
+
+        let mut system = SampleCoilPairFieldSystem;
+        system.run_now(&test_world);
+        let samplers = test_world.read_storage::<MagneticFieldSampler>();
+        let (field, jacobian) = coil.calculate_field(pos, centre);
+        let sampler = samplers.get(sampler).expect("entity not found");
+        assert_eq!(sampler.field, field);
+        assert_eq!(sampler.jacobian, jacobian);
+        assert!(field[0] < 0.0);
+    }
+}
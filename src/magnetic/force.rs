@@ -3,12 +3,18 @@
 //! entity with a MagneticDipole component.
 //! The magnetic force is not added by default to the builder so must be explicitly included and
 //! must depend on the magnetics_gradient system.
+//!
+//! The gradient of |B| is discontinuous at a field zero, such as the centre of a quadrupole trap, where the force
+//! changes direction abruptly and an atom passing close to the zero receives a large impulse. Inserting a
+//! [MagneticForceSoftening] resource smooths the force within a softening length of the zero.
 #![allow(non_snake_case)]
 
 use super::MagneticFieldSampler;
 use crate::atom::Force;
 use crate::constant;
-use specs::{Component, ReadStorage, System, VecStorage, WriteStorage};
+use crate::parallel::{ForceSerial, MaybeParJoin};
+use nalgebra::Vector3;
+use specs::{Component, Read, ReadStorage, System, VecStorage, WriteStorage};
 
 /// Component that represents the magnetic dipole moment of an atom.
 #[derive(Clone)]
@@ -21,23 +27,46 @@ impl Component for MagneticDipole {
     type Storage = VecStorage<Self>;
 }
 
+/// A resource that softens the magnetic force near a field zero.
+///
+/// The field magnitude is replaced by the softened magnitude `sqrt(|B|^2 + (length * |grad |B||)^2)`, so the force
+/// is scaled by `|B| / sqrt(|B|^2 + (length * |grad |B||)^2)`. For a quadrupole field this is the force at distance
+/// `r` from the zero scaled by `r / sqrt(r^2 + length^2)`: the force falls linearly to zero within the softening
+/// length, and is unchanged far from the zero. A length of zero gives the bare force.
+#[derive(Clone, Copy)]
+pub struct MagneticForceSoftening {
+    /// Softening length, in SI units of m.
+    pub length: f64,
+}
+impl MagneticForceSoftening {
+    /// Returns the factor by which the magnetic force is scaled for the given field magnitude and gradient.
+    pub fn factor(&self, magnitude: f64, gradient: &Vector3<f64>) -> f64 {
+        let softening = self.length * gradient.norm();
+        if softening <= 0.0 {
+            return 1.0;
+        }
+        magnitude / (magnitude.powi(2) + softening.powi(2)).sqrt()
+    }
+}
+
 pub struct ApplyMagneticForceSystem;
 impl<'a> System<'a> for ApplyMagneticForceSystem {
     type SystemData = (
         WriteStorage<'a, Force>,
         ReadStorage<'a, MagneticFieldSampler>,
         ReadStorage<'a, MagneticDipole>,
+        Option<Read<'a, ForceSerial>>,
+        Option<Read<'a, MagneticForceSoftening>>,
     );
 
-    fn run(&mut self, (mut forces, samplers, dipoles): Self::SystemData) {
-        use rayon::prelude::*;
-        use specs::ParJoin;
-
+    fn run(&mut self, (mut forces, samplers, dipoles, force_serial, softening): Self::SystemData) {
+        let softening = softening.map(|softening| *softening);
         (&mut forces, &samplers, &dipoles)
-            .par_join()
-            .for_each(|(force, sampler, dipole)| {
-                let dipole_force = -dipole.mFgF * constant::BOHRMAG * sampler.gradient;
-                force.force += dipole_force;
+            .maybe_par_for_each(force_serial.is_some(), |(force, sampler, dipole)| {
+                let factor = softening
+                    .map_or(1.0, |softening| softening.factor(sampler.magnitude, &sampler.gradient));
+                let dipole_force = -dipole.mFgF * constant::BOHRMAG * factor * sampler.gradient;
+                force.force += dipole_force.cast();
             });
     }
 }
@@ -51,7 +80,7 @@ pub mod tests {
     use assert_approx_eq::assert_approx_eq;
     use specs::prelude::*;
     extern crate nalgebra;
-    use nalgebra::{Matrix3, Vector3};
+    use nalgebra::Matrix3;
 
     //Test correct force in an external magnetic gradient
     #[test]
@@ -76,7 +105,11 @@ pub mod tests {
         system.run_now(&test_world);
         test_world.maintain();
         let force_storage = test_world.read_storage::<Force>();
-        let force = force_storage.get(atom1).expect("entity not found").force;
+        let force = force_storage
+            .get(atom1)
+            .expect("entity not found")
+            .force
+            .cast::<f64>();
 
         let real_force = Vector3::new(
             -0.5 * constant::BOHRMAG,
@@ -87,4 +120,63 @@ pub mod tests {
         assert_approx_eq!(force[1], real_force[1], 1e-10_f64);
         assert_approx_eq!(force[2], real_force[2], 1e-10_f64);
     }
+
+    /// Returns the x component of the force on an atom at `x` on the axis of a quadrupole field with gradient `gradient`.
+    fn quadrupole_force(x: f64, gradient: f64, softening: Option<f64>) -> f64 {
+        let mut test_world = World::new();
+        test_world.register::<MagneticFieldSampler>();
+        test_world.register::<MagneticDipole>();
+        test_world.register::<Force>();
+        if let Some(length) = softening {
+            test_world.insert(MagneticForceSoftening { length });
+        }
+        let atom = test_world
+            .create_entity()
+            .with(MagneticFieldSampler {
+                field: Vector3::new(gradient * x, 0.0, 0.0),
+                magnitude: gradient * x.abs(),
+                gradient: Vector3::new(gradient * x.signum(), 0.0, 0.0),
+                jacobian: Matrix3::zeros(),
+            })
+            .with(MagneticDipole { mFgF: 1.0 })
+            .with(Force::new())
+            .build();
+        ApplyMagneticForceSystem.run_now(&test_world);
+        let force = test_world.read_storage::<Force>().get(atom).unwrap().force[0];
+        force as f64
+    }
+
+    #[test]
+    fn test_softened_force_is_continuous_through_zero() {
+        let gradient = 0.2;
+        let length = 1.0e-5;
+        let bare = constant::BOHRMAG * gradient;
+
+        let at_zero = quadrupole_force(0.0, gradient, Some(length));
+        assert!(at_zero.is_finite());
+        assert_approx_eq!(at_zero, 0.0, 1e-12 * bare);
+
+        // Either side of the zero, the force tends to zero rather than jumping between +-bare.
+        let left = quadrupole_force(-1.0e-9, gradient, Some(length));
+        let right = quadrupole_force(1.0e-9, gradient, Some(length));
+        assert!(left.abs() < 1e-3 * bare && right.abs() < 1e-3 * bare);
+        assert!(left > 0.0 && right < 0.0);
+
+        // Within the softening length, the force is linear in the displacement.
+        let near = quadrupole_force(1.0e-7, gradient, Some(length));
+        assert_approx_eq!(near, 100.0 * right, 1e-3 * near.abs());
+
+        // Far from the zero, the force is unchanged.
+        let far = quadrupole_force(1.0e-2, gradient, Some(length));
+        assert_approx_eq!(far, -bare, 1e-6 * bare);
+    }
+
+    #[test]
+    fn test_zero_softening_length_gives_bare_force() {
+        let gradient = 0.2;
+        let bare = constant::BOHRMAG * gradient;
+        assert_approx_eq!(quadrupole_force(1.0e-9, gradient, Some(0.0)), -bare, 1e-12 * bare);
+        assert_approx_eq!(quadrupole_force(-1.0e-9, gradient, Some(0.0)), bare, 1e-12 * bare);
+        assert_approx_eq!(quadrupole_force(1.0e-9, gradient, None), -bare, 1e-12 * bare);
+    }
 }
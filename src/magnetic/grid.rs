@@ -73,7 +73,7 @@ impl<'a> System<'a> for SampleMagneticGridSystem {
     fn run(&mut self, (mut sampler, pos, grids): Self::SystemData) {
         for grid in (&grids).join() {
             for (pos, sampler) in (&pos, &mut sampler).join() {
-                let field = grid.get_field(&pos.pos);
+                let field = grid.get_field(&pos.pos.cast());
                 sampler.field += field;
             }
         }
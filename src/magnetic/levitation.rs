@@ -0,0 +1,132 @@
+//! Magnetic levitation of atoms against gravity.
+//!
+//! A quadrupole field with its symmetry axis along `z` has a field magnitude that increases linearly
+//! with distance from the node, `|B| = 2 * gradient * |z|` along the axis. Below the node, a weak-field
+//! seeking state (`m_F * g_F > 0`) experiences a constant upwards force, which cancels gravity for the
+//! gradient returned by [levitation_gradient].
+
+use crate::constant;
+use crate::gravity::Gravity;
+use crate::magnetic::quadrupole::QuadrupoleField3D;
+use nalgebra::Vector3;
+
+/// Calculates the quadrupole gradient required to levitate an atom against gravity.
+///
+/// Returns the gradient in units of Tesla/m, in the convention used by [QuadrupoleField3D], for which the
+/// field gradient along the symmetry axis is twice this value.
+///
+/// Panics if the state cannot be levitated, which requires a weak-field seeking state with `m_F * g_F > 0`.
+///
+/// # Arguments
+///
+/// `mass`: mass of the atom, in atomic mass units.
+///
+/// `m_f`: Zeeman sublevel of the atom.
+///
+/// `g_f`: Lande g-factor of the hyperfine state.
+pub fn levitation_gradient(mass: f64, m_f: f64, g_f: f64) -> f64 {
+    levitation_gradient_for(mass, m_f, g_f, &Gravity::default())
+}
+
+/// Calculates the quadrupole gradient required to levitate an atom against the given [Gravity].
+///
+/// The gradient depends only on the magnitude of the acceleration, and the symmetry axis of the quadrupole must be
+/// aligned with gravity. Without gravity, see [Gravity::zero], no levitation is needed and the gradient is zero for any state.
+///
+/// Panics if gravity is not zero and the state cannot be levitated. See [levitation_gradient] for the other
+/// arguments.
+pub fn levitation_gradient_for(mass: f64, m_f: f64, g_f: f64, gravity: &Gravity) -> f64 {
+    if gravity.is_zero() {
+        return 0.0;
+    }
+    let m_f_g_f = m_f * g_f;
+    if m_f_g_f <= 0.0 {
+        panic!(
+            "State with mF*gF={} cannot be levitated: only weak-field seeking states with mF*gF > 0 can be levitated.",
+            m_f_g_f
+        );
+    }
+    mass * constant::AMU * gravity.acceleration.norm() / (2.0 * m_f_g_f * constant::BOHRMAG)
+}
+
+/// Creates a [QuadrupoleField3D] that levitates an atom against gravity.
+///
+/// The symmetry axis of the quadrupole is vertical, and atoms are levitated in the region below the node.
+/// See [levitation_gradient] for the arguments.
+pub fn levitating_quadrupole(mass: f64, m_f: f64, g_f: f64) -> QuadrupoleField3D {
+    QuadrupoleField3D {
+        gradient: levitation_gradient(mass, m_f, g_f),
+        direction: Vector3::z(),
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use crate::atom::{Force, Mass, Position};
+    use crate::gravity::{ApplyGravitationalForceSystem, ApplyGravityOption};
+    use crate::magnetic::force::{ApplyMagneticForceSystem, MagneticDipole};
+    use crate::magnetic::quadrupole::Sample3DQuadrupoleFieldSystem;
+    use crate::magnetic::{
+        CalculateMagneticFieldMagnitudeSystem, CalculateMagneticMagnitudeGradientSystem,
+        MagneticFieldSampler,
+    };
+    use assert_approx_eq::assert_approx_eq;
+    use specs::prelude::*;
+
+    #[test]
+    fn test_levitated_atom_has_no_net_vertical_force() {
+        let mut test_world = World::new();
+        test_world.register::<Position>();
+        test_world.register::<QuadrupoleField3D>();
+        test_world.register::<MagneticFieldSampler>();
+        test_world.register::<MagneticDipole>();
+        test_world.register::<Mass>();
+        test_world.register::<crate::atom::GravitationalMass>();
+        test_world.register::<Force>();
+        test_world.insert(ApplyGravityOption);
+
+        let mass = 87.0;
+        let (m_f, g_f) = (2.0, 0.5);
+        test_world
+            .create_entity()
+            .with(Position {
+                pos: Vector3::new(0.0, 0.0, 0.0),
+            })
+            .with(levitating_quadrupole(mass, m_f, g_f))
+            .build();
+        let atom = test_world
+            .create_entity()
+            .with(Position {
+                pos: Vector3::new(0.0, 0.0, -1.0e-3),
+            })
+            .with(MagneticFieldSampler::default())
+            .with(MagneticDipole { mFgF: m_f * g_f })
+            .with(Mass { value: mass })
+            .with(Force::new())
+            .build();
+
+        Sample3DQuadrupoleFieldSystem.run_now(&test_world);
+        CalculateMagneticFieldMagnitudeSystem.run_now(&test_world);
+        CalculateMagneticMagnitudeGradientSystem.run_now(&test_world);
+        ApplyMagneticForceSystem.run_now(&test_world);
+        ApplyGravitationalForceSystem.run_now(&test_world);
+        test_world.maintain();
+
+        let force = test_world
+            .read_storage::<Force>()
+            .get(atom)
+            .expect("entity not found")
+            .force
+            .cast::<f64>();
+        let weight = mass * constant::AMU * constant::GC;
+        assert_approx_eq!(force[2] / weight, 0.0, 1e-6);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_strong_field_seeker_cannot_be_levitated() {
+        levitation_gradient(87.0, -1.0, 0.5);
+    }
+}
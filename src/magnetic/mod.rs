@@ -4,19 +4,28 @@ extern crate nalgebra;
 
 use specs::prelude::*;
 
+use crate::dispatch::SystemDispatcherBuilder;
 use crate::integrator::INTEGRATE_POSITION_SYSTEM_NAME;
+use crate::parallel::{ForceSerial, MaybeParJoin};
 use crate::{initiate::NewlyCreated, simulation::Plugin};
 use nalgebra::{Matrix3, Vector3};
 use specs::{
-    Component, DispatcherBuilder, Entities, Join, LazyUpdate, Read, ReadStorage, System,
+    Component, Entities, Join, LazyUpdate, Read, ReadStorage, System,
     VecStorage, World, WriteStorage,
 };
 
+pub mod coil;
 pub mod force;
 pub mod grid;
+pub mod levitation;
+pub mod profile;
 pub mod quadrupole;
+pub mod slower;
 pub mod top;
 pub mod uniform;
+
+pub use levitation::{levitation_gradient, levitation_gradient_for};
+pub use profile::export_field_profile;
 use std::fmt;
 
 /// A component that stores the magnetic field at an entity's location.
@@ -72,11 +81,9 @@ impl Default for MagneticFieldSampler {
 pub struct ClearMagneticFieldSamplerSystem;
 
 impl<'a> System<'a> for ClearMagneticFieldSamplerSystem {
-    type SystemData = WriteStorage<'a, MagneticFieldSampler>;
-    fn run(&mut self, mut sampler: Self::SystemData) {
-        use rayon::prelude::*;
-
-        (&mut sampler).par_join().for_each(|mut sampler| {
+    type SystemData = (WriteStorage<'a, MagneticFieldSampler>, Option<Read<'a, ForceSerial>>);
+    fn run(&mut self, (mut sampler, force_serial): Self::SystemData) {
+        (&mut sampler).maybe_par_for_each(force_serial.is_some(), |mut sampler| {
             sampler.magnitude = 0.;
             sampler.field = Vector3::new(0.0, 0.0, 0.0);
             sampler.gradient = Vector3::new(0.0, 0.0, 0.0);
@@ -92,11 +99,9 @@ impl<'a> System<'a> for ClearMagneticFieldSamplerSystem {
 pub struct CalculateMagneticFieldMagnitudeSystem;
 
 impl<'a> System<'a> for CalculateMagneticFieldMagnitudeSystem {
-    type SystemData = WriteStorage<'a, MagneticFieldSampler>;
-    fn run(&mut self, mut sampler: Self::SystemData) {
-        use rayon::prelude::*;
-
-        (&mut sampler).par_join().for_each(|mut sampler| {
+    type SystemData = (WriteStorage<'a, MagneticFieldSampler>, Option<Read<'a, ForceSerial>>);
+    fn run(&mut self, (mut sampler, force_serial): Self::SystemData) {
+        (&mut sampler).maybe_par_for_each(force_serial.is_some(), |mut sampler| {
             sampler.magnitude = sampler.field.norm();
             if sampler.magnitude.is_nan() {
                 sampler.magnitude = 0.0;
@@ -111,11 +116,9 @@ impl<'a> System<'a> for CalculateMagneticFieldMagnitudeSystem {
 pub struct CalculateMagneticMagnitudeGradientSystem;
 
 impl<'a> System<'a> for CalculateMagneticMagnitudeGradientSystem {
-    type SystemData = WriteStorage<'a, MagneticFieldSampler>;
-    fn run(&mut self, mut sampler: Self::SystemData) {
-        use rayon::prelude::*;
-
-        (&mut sampler).par_join().for_each(|mut sampler| {
+    type SystemData = (WriteStorage<'a, MagneticFieldSampler>, Option<Read<'a, ForceSerial>>);
+    fn run(&mut self, (mut sampler, force_serial): Self::SystemData) {
+        (&mut sampler).maybe_par_for_each(force_serial.is_some(), |mut sampler| {
             let mut gradient = Vector3::new(0.0, 0.0, 0.0);
             for i in 0..3 {
                 gradient[i] =
@@ -151,7 +154,7 @@ impl<'a> System<'a> for AttachFieldSamplersToNewlyCreatedAtomsSystem {
 ///
 /// `deps`: any dependencies that must be completed before the magnetics systems run.
 fn add_magnetics_systems_to_dispatch(
-    builder: &mut DispatcherBuilder<'static, 'static>,
+    builder: &mut impl SystemDispatcherBuilder,
     deps: &[&str],
 ) {
     builder.add(ClearMagneticFieldSamplerSystem, "magnetics_clear", deps);
@@ -168,20 +171,30 @@ fn add_magnetics_systems_to_dispatch(
         "magnetics_2dquadrupole",
         &["magnetics_quadrupole"],
     );
+    builder.add(
+        coil::SampleCoilPairFieldSystem,
+        "magnetics_coil_pair",
+        &["magnetics_2dquadrupole"],
+    );
     builder.add(
         uniform::UniformMagneticFieldSystem,
         "magnetics_uniform",
-        &["magnetics_2dquadrupole"],
+        &["magnetics_coil_pair"],
     );
     builder.add(
         top::TimeOrbitingPotentialSystem,
         "magnetics_top",
         &["magnetics_uniform"],
     );
+    builder.add(
+        slower::SampleZeemanSlowerFieldSystem,
+        "magnetics_slower",
+        &["magnetics_top"],
+    );
     builder.add(
         grid::SampleMagneticGridSystem,
         "magnetics_grid",
-        &["magnetics_top", INTEGRATE_POSITION_SYSTEM_NAME],
+        &["magnetics_slower", INTEGRATE_POSITION_SYSTEM_NAME],
     );
     builder.add(
         CalculateMagneticFieldMagnitudeSystem,
@@ -196,7 +209,7 @@ fn add_magnetics_systems_to_dispatch(
 }
 
 /// Adds the additional systems required by magnetics to the dispatcher.
-fn add_magnetic_trap_systems_to_dispatch(builder: &mut DispatcherBuilder<'static, 'static>) {
+fn add_magnetic_trap_systems_to_dispatch(builder: &mut impl SystemDispatcherBuilder) {
     builder.add(
         CalculateMagneticMagnitudeGradientSystem,
         "magnetics_gradient",
@@ -214,7 +227,9 @@ fn register_magnetics_components(world: &mut World) {
     world.register::<uniform::UniformMagneticField>();
     world.register::<quadrupole::QuadrupoleField3D>();
     world.register::<quadrupole::QuadrupoleField2D>();
+    world.register::<coil::CoilPair>();
     world.register::<top::TimeOrbitingPotential>();
+    world.register::<slower::ZeemanSlowerField>();
     world.register::<MagneticFieldSampler>();
     world.register::<grid::PrecalculatedMagneticFieldGrid>();
     world.register::<force::MagneticDipole>();
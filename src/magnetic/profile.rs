@@ -0,0 +1,240 @@
+//! Export of magnetic field profiles, independent of the atoms in the simulation.
+//!
+//! This is a diagnostic tool for designing slowers and traps, and is not part of the dynamics loop.
+
+use crate::atom::Position;
+use crate::integrator::{Step, Timestep};
+use crate::magnetic::{coil, grid, quadrupole, slower, top, uniform};
+use crate::magnetic::{
+    CalculateMagneticFieldMagnitudeSystem, ClearMagneticFieldSamplerSystem, MagneticFieldSampler,
+};
+use nalgebra::Vector3;
+use specs::prelude::*;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// Helpers to generate sample points along simple geometries.
+pub struct SamplePoints;
+impl SamplePoints {
+    /// `n` points equally spaced along the line from `start` to `end`, inclusive.
+    pub fn line(start: Vector3<f64>, end: Vector3<f64>, n: usize) -> Vec<Vector3<f64>> {
+        match n {
+            0 => Vec::new(),
+            1 => vec![start],
+            _ => (0..n)
+                .map(|i| start + (end - start) * (i as f64 / (n - 1) as f64))
+                .collect(),
+        }
+    }
+
+    /// A grid of `nu * nv` points in the plane spanned by the edges `u` and `v` from `origin`, inclusive.
+    pub fn plane(
+        origin: Vector3<f64>,
+        u: Vector3<f64>,
+        v: Vector3<f64>,
+        nu: usize,
+        nv: usize,
+    ) -> Vec<Vector3<f64>> {
+        let mut points = Vec::new();
+        for p in Self::line(origin, origin + v, nv) {
+            points.append(&mut Self::line(p, p + u, nu));
+        }
+        points
+    }
+
+    /// A grid of points filling the cuboid between the corners `min` and `max`, inclusive.
+    ///
+    /// `n` is the number of points along each of the `x,y,z` axes.
+    pub fn grid(min: Vector3<f64>, max: Vector3<f64>, n: [usize; 3]) -> Vec<Vector3<f64>> {
+        let delta = max - min;
+        let mut points = Vec::new();
+        for z in Self::line(min, Vector3::new(min[0], min[1], max[2]), n[2]) {
+            points.append(&mut Self::plane(
+                z,
+                Vector3::new(delta[0], 0.0, 0.0),
+                Vector3::new(0.0, delta[1], 0.0),
+                n[0],
+                n[1],
+            ));
+        }
+        points
+    }
+}
+
+fn run_system<S>(mut system: S, world: &mut World)
+where
+    S: for<'a> System<'a>,
+{
+    system.setup(world);
+    system.run_now(world);
+}
+
+/// Evaluates the total magnetic field from all sources in the world at each of the given points.
+///
+/// Fields are returned in units of Tesla. Time-dependent sources, such as a time-orbiting potential,
+/// are only included if the world contains the `Step` and `Timestep` resources.
+pub fn sample_field(world: &mut World, sample_points: &[Vector3<f64>]) -> Vec<Vector3<f64>> {
+    world.register::<Position>();
+    world.register::<MagneticFieldSampler>();
+    let probes: Vec<Entity> = sample_points
+        .iter()
+        .map(|point| {
+            world
+                .create_entity()
+                .with(Position { pos: point.cast() })
+                .with(MagneticFieldSampler::default())
+                .build()
+        })
+        .collect();
+
+    // The field systems run in the same order as in the magnetics dispatch.
+    run_system(ClearMagneticFieldSamplerSystem, world);
+    run_system(quadrupole::Sample3DQuadrupoleFieldSystem, world);
+    run_system(quadrupole::Sample2DQuadrupoleFieldSystem, world);
+    run_system(coil::SampleCoilPairFieldSystem, world);
+    run_system(uniform::UniformMagneticFieldSystem, world);
+    if world.has_value::<Step>() && world.has_value::<Timestep>() {
+        run_system(top::TimeOrbitingPotentialSystem, world);
+    }
+    run_system(slower::SampleZeemanSlowerFieldSystem, world);
+    run_system(grid::SampleMagneticGridSystem, world);
+    run_system(CalculateMagneticFieldMagnitudeSystem, world);
+
+    let fields = {
+        let samplers = world.read_storage::<MagneticFieldSampler>();
+        probes
+            .iter()
+            .map(|probe| samplers.get(*probe).expect("probe not found").field)
+            .collect()
+    };
+
+    world
+        .delete_entities(&probes)
+        .expect("Could not delete field probes.");
+    world.maintain();
+    fields
+}
+
+/// Evaluates the total magnetic field from all sources at the given points, and writes the result to a file.
+///
+/// Each line of the file contains the comma-separated position `x,y,z` in m, followed by the field `Bx,By,Bz` in Tesla.
+/// Sample points can be generated using [SamplePoints].
+pub fn export_field_profile(
+    world: &mut World,
+    path: impl AsRef<Path>,
+    sample_points: &[Vector3<f64>],
+) -> io::Result<()> {
+    let fields = sample_field(world, sample_points);
+    let mut writer = BufWriter::new(File::create(path)?);
+    writeln!(writer, "x,y,z,bx,by,bz")?;
+    for (pos, field) in sample_points.iter().zip(fields.iter()) {
+        writeln!(
+            writer,
+            "{:?},{:?},{:?},{:?},{:?},{:?}",
+            pos[0], pos[1], pos[2], field[0], field[1], field[2]
+        )?;
+    }
+    writer.flush()
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::magnetic::quadrupole::QuadrupoleField3D;
+    use crate::magnetic::slower::ZeemanSlowerField;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_sample_points() {
+        let line =
+            SamplePoints::line(Vector3::new(0.0, 0.0, 0.0), Vector3::new(1.0, 0.0, 0.0), 5);
+        assert_eq!(line.len(), 5);
+        assert_approx_eq!(line[1][0], 0.25, 1e-12);
+
+        let grid = SamplePoints::grid(
+            Vector3::new(-1.0, -1.0, -1.0),
+            Vector3::new(1.0, 1.0, 1.0),
+            [3, 4, 5],
+        );
+        assert_eq!(grid.len(), 60);
+        assert_eq!(grid[59], Vector3::new(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn test_export_quadrupole_field_profile() {
+        let mut world = World::new();
+        world.register::<Position>();
+        world.register::<QuadrupoleField3D>();
+        let gradient = 0.2;
+        world
+            .create_entity()
+            .with(Position {
+                pos: Vector3::new(0.0, 0.0, 0.0),
+            })
+            .with(QuadrupoleField3D {
+                gradient,
+                direction: Vector3::z(),
+            })
+            .build();
+
+        let points = SamplePoints::grid(
+            Vector3::new(-1e-2, -2e-2, -3e-2),
+            Vector3::new(1e-2, 2e-2, 3e-2),
+            [3, 3, 3],
+        );
+        let path = std::env::temp_dir().join("atomecs_test_field_profile.csv");
+        export_field_profile(&mut world, &path, &points).expect("could not export profile");
+
+        let contents = std::fs::read_to_string(&path).expect("could not read profile");
+        let rows: Vec<Vec<f64>> = contents
+            .lines()
+            .skip(1)
+            .map(|line| line.split(',').map(|v| v.parse().unwrap()).collect())
+            .collect();
+        assert_eq!(rows.len(), points.len());
+        for row in rows {
+            assert_approx_eq!(row[3], gradient * row[0], 1e-12);
+            assert_approx_eq!(row[4], gradient * row[1], 1e-12);
+            assert_approx_eq!(row[5], -2.0 * gradient * row[2], 1e-12);
+        }
+
+        // The probes used to sample the field are removed afterwards.
+        assert_eq!((&world.read_storage::<MagneticFieldSampler>()).join().count(), 0);
+        std::fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn test_sample_zeeman_slower_field_profile() {
+        let mut world = World::new();
+        world.register::<Position>();
+        world.register::<ZeemanSlowerField>();
+        let entrance = Vector3::new(0.1, 0.0, 0.0);
+        let slower = ZeemanSlowerField {
+            direction: Vector3::x(),
+            length: 0.5,
+            bias: -0.01,
+            amplitude: 0.03,
+            final_ratio: 0.2,
+        };
+        world
+            .create_entity()
+            .with(Position { pos: entrance.cast() })
+            .with(slower)
+            .build();
+
+        let points = SamplePoints::line(
+            entrance - 0.1 * Vector3::x(),
+            entrance + 0.6 * Vector3::x(),
+            15,
+        );
+        let fields = sample_field(&mut world, &points);
+        assert_eq!(fields.len(), points.len());
+        for (point, field) in points.iter().zip(fields.iter()) {
+            let expected = slower.calculate_field(*point, entrance);
+            assert_approx_eq!((field - expected).norm(), 0.0, 1e-12);
+        }
+        // The profile is non-zero inside the slower.
+        assert!(fields[7].norm() > 0.0);
+    }
+}
@@ -7,9 +7,10 @@ use crate::atom::Position;
 use serde::Serialize;
 
 use crate::magnetic::MagneticFieldSampler;
+use crate::parallel::{ForceSerial, MaybeParJoin};
 use crate::ramp::Lerp;
 use nalgebra::{Matrix3, Unit, Vector3};
-use specs::{Component, HashMapStorage, Join, ReadStorage, System, WriteStorage};
+use specs::{Component, HashMapStorage, Join, Read, ReadStorage, System, WriteStorage};
 
 /// A component representing a 3D quadrupole field.
 #[derive(Serialize, Clone, Copy, Lerp)]
@@ -67,18 +68,18 @@ impl<'a> System<'a> for Sample3DQuadrupoleFieldSystem {
         WriteStorage<'a, MagneticFieldSampler>,
         ReadStorage<'a, Position>,
         ReadStorage<'a, QuadrupoleField3D>,
+        Option<Read<'a, ForceSerial>>,
     );
-    fn run(&mut self, (mut sampler, pos, quadrupole): Self::SystemData) {
-        use rayon::prelude::*;
-        use specs::ParJoin;
+    fn run(&mut self, (mut sampler, pos, quadrupole, force_serial): Self::SystemData) {
 
         for (centre, quadrupole) in (&pos, &quadrupole).join() {
+            let centre = centre.pos.cast::<f64>();
             (&pos, &mut sampler)
-                .par_join()
-                .for_each(|(pos, sampler)| {
+                .maybe_par_for_each(force_serial.is_some(), |(pos, sampler)| {
+                    let pos = pos.pos.cast::<f64>();
                     let quad_field = Sample3DQuadrupoleFieldSystem::calculate_field(
-                        pos.pos,
-                        centre.pos,
+                        pos,
+                        centre,
                         quadrupole.gradient,
                         quadrupole.direction,
                     );
@@ -90,20 +91,20 @@ impl<'a> System<'a> for Sample3DQuadrupoleFieldSystem {
                                       // Strictly speaking to be accurate it depends on the length scale over which
                                       // the magnetic field changes
                     for i in 0..3 {
-                        let mut pos_plus_dr = pos.pos;
-                        let mut pos_minus_dr = pos.pos;
+                        let mut pos_plus_dr = pos;
+                        let mut pos_minus_dr = pos;
                         pos_plus_dr[i] += delta;
                         pos_minus_dr[i] -= delta;
 
                         let b_plus_dr = Sample3DQuadrupoleFieldSystem::calculate_field(
                             pos_plus_dr,
-                            centre.pos,
+                            centre,
                             quadrupole.gradient,
                             quadrupole.direction,
                         );
                         let b_minus_dr = Sample3DQuadrupoleFieldSystem::calculate_field(
                             pos_minus_dr,
-                            centre.pos,
+                            centre,
                             quadrupole.gradient,
                             quadrupole.direction,
                         );
@@ -196,8 +197,8 @@ impl<'a> System<'a> for Sample2DQuadrupoleFieldSystem {
         for (centre, quadrupole) in (&pos, &quadrupole).join() {
             for (pos, sampler) in (&pos, &mut sampler).join() {
                 let quad_field = Self::calculate_field(
-                    pos.pos,
-                    centre.pos,
+                    pos.pos.cast(),
+                    centre.pos.cast(),
                     quadrupole.gradient,
                     quadrupole.direction_in,
                     quadrupole.direction_out,
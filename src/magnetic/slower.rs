@@ -0,0 +1,157 @@
+//! The tapered magnetic field of a Zeeman slower.
+
+use crate::atom::Position;
+use crate::constant::HBAR;
+use crate::laser_cooling::transition::AtomicTransition;
+use crate::magnetic::MagneticFieldSampler;
+use crate::parallel::{ForceSerial, MaybeParJoin};
+use nalgebra::Vector3;
+use specs::{Component, HashMapStorage, Join, Read, ReadStorage, System, WriteStorage};
+
+/// A component representing the field of a decreasing-field Zeeman slower.
+///
+/// The slower starts at the entity's [Position], and extends a distance `length` along `direction`. Within the
+/// slower, at a distance `z` along the axis, the field points along `direction` with magnitude
+///
+/// `B(z) = bias + amplitude * sqrt(1 - (1 - final_ratio^2) z / length)`
+///
+/// so that an atom decelerating uniformly from the capture velocity to `final_ratio` times the capture velocity
+/// remains resonant with the slowing beam. The field is zero outside the slower.
+#[derive(Clone, Copy)]
+pub struct ZeemanSlowerField {
+    /// A unit vector along the axis of the slower, from its entrance to its exit.
+    pub direction: Vector3<f64>,
+    /// Length of the slower, in SI units of m.
+    pub length: f64,
+    /// Uniform component of the field, in SI units of T.
+    pub bias: f64,
+    /// Tapered component of the field at the entrance of the slower, in SI units of T.
+    pub amplitude: f64,
+    /// Ratio of the final velocity to the capture velocity of the slower.
+    pub final_ratio: f64,
+}
+impl ZeemanSlowerField {
+    /// Designs the field of a slower for the transition `T`, driven on the sigma+ transition by a slowing beam that
+    /// propagates against `direction`.
+    ///
+    /// Panics if the field would change sign within the slower, which happens if the slowing beam is detuned
+    /// further to the red than the Doppler shift `k * final_velocity`.
+    ///
+    /// # Arguments
+    ///
+    /// `direction`: axis of the slower, from its entrance to its exit.
+    ///
+    /// `length`: length of the slower, in SI units of m.
+    ///
+    /// `capture_velocity`: velocity of the atoms resonant at the entrance, in SI units of m/s.
+    ///
+    /// `final_velocity`: velocity of the atoms resonant at the exit, in SI units of m/s.
+    ///
+    /// `detuning`: detuning of the slowing beam from the transition, in MHz.
+    pub fn design<T>(
+        direction: Vector3<f64>,
+        length: f64,
+        capture_velocity: f64,
+        final_velocity: f64,
+        detuning: f64,
+    ) -> Self
+    where
+        T: AtomicTransition,
+    {
+        let wavenumber = 2.0 * std::f64::consts::PI / T::wavelength();
+        let detuning = 2.0 * std::f64::consts::PI * detuning * 1.0e6;
+        let field = ZeemanSlowerField {
+            direction: direction.normalize(),
+            length,
+            bias: HBAR * detuning / T::mup(),
+            amplitude: HBAR * wavenumber * capture_velocity / T::mup(),
+            final_ratio: final_velocity / capture_velocity,
+        };
+        if field.bias + field.amplitude * field.final_ratio < 0.0 {
+            panic!(
+                "The Zeeman slower field changes sign: the detuning {:.3e} rad/s is further to the red than the Doppler shift {:.3e} rad/s of the final velocity.",
+                detuning,
+                wavenumber * final_velocity
+            );
+        }
+        field
+    }
+
+    /// Calculates the magnetic field at `pos`, for a slower with its entrance at `entrance`.
+    pub fn calculate_field(&self, pos: Vector3<f64>, entrance: Vector3<f64>) -> Vector3<f64> {
+        let direction = self.direction.normalize();
+        let z = (pos - entrance).dot(&direction);
+        if z < 0.0 || z > self.length {
+            return Vector3::zeros();
+        }
+        let taper = (1.0 - (1.0 - self.final_ratio.powi(2)) * z / self.length).sqrt();
+        (self.bias + self.amplitude * taper) * direction
+    }
+}
+
+impl Component for ZeemanSlowerField {
+    type Storage = HashMapStorage<Self>;
+}
+
+/// Updates the values of magnetic field samplers to include the fields of [ZeemanSlowerField]s in the world.
+///
+/// The gradient of the field is not calculated, so the magnetic force on the atoms is not included.
+pub struct SampleZeemanSlowerFieldSystem;
+
+impl<'a> System<'a> for SampleZeemanSlowerFieldSystem {
+    type SystemData = (
+        WriteStorage<'a, MagneticFieldSampler>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, ZeemanSlowerField>,
+        Option<Read<'a, ForceSerial>>,
+    );
+    fn run(&mut self, (mut sampler, pos, slowers, force_serial): Self::SystemData) {
+        for (entrance, slower) in (&pos, &slowers).join() {
+            let entrance = entrance.pos.cast::<f64>();
+            (&pos, &mut sampler).maybe_par_for_each(force_serial.is_some(), |(pos, sampler)| {
+                sampler.field += slower.calculate_field(pos.pos.cast::<f64>(), entrance);
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use crate::species::Rubidium87_780D2;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_slower_field_keeps_decelerating_atoms_resonant() {
+        let (length, capture, final_velocity, detuning) = (0.5, 200.0, 30.0, -30.0);
+        let slower = ZeemanSlowerField::design::<Rubidium87_780D2>(
+            Vector3::x(),
+            length,
+            capture,
+            final_velocity,
+            detuning,
+        );
+        let wavenumber = 2.0 * std::f64::consts::PI / Rubidium87_780D2::wavelength();
+        let entrance = Vector3::new(0.1, 0.0, 0.0);
+        for i in 0..=10 {
+            let z = length * i as f64 / 10.0;
+            // The velocity of an atom decelerating uniformly from the capture velocity to the final velocity.
+            let velocity =
+                (capture.powi(2) - (capture.powi(2) - final_velocity.powi(2)) * z / length).sqrt();
+            let field = slower.calculate_field(entrance + z * Vector3::x(), entrance);
+            let shift = 2.0 * std::f64::consts::PI * detuning * 1.0e6 + wavenumber * velocity
+                - Rubidium87_780D2::mup() / HBAR * field.norm();
+            assert_approx_eq!(shift, 0.0, 1.0e3);
+            assert_approx_eq!(field[1], 0.0, 1e-12);
+        }
+        assert_eq!(
+            slower.calculate_field(entrance - 0.01 * Vector3::x(), entrance),
+            Vector3::zeros()
+        );
+        assert_eq!(
+            slower.calculate_field(entrance + (length + 0.01) * Vector3::x(), entrance),
+            Vector3::zeros()
+        );
+    }
+}
@@ -9,9 +9,10 @@ extern crate specs;
 use crate::constant::PI;
 use crate::integrator::{Step, Timestep};
 use crate::magnetic::MagneticFieldSampler;
+use crate::parallel::{ForceSerial, MaybeParJoin};
 use crate::ramp::Lerp;
 use nalgebra::Vector3;
-use specs::{Component, HashMapStorage, Join, ReadExpect, ReadStorage, System, WriteStorage};
+use specs::{Component, HashMapStorage, Join, Read, ReadExpect, ReadStorage, System, WriteStorage};
 
 /// A component representing a Time-Orbiting Potential (TOP)
 #[derive(Clone, Lerp)]
@@ -41,13 +42,12 @@ impl<'a> System<'a> for TimeOrbitingPotentialSystem {
         ReadStorage<'a, TimeOrbitingPotential>,
         ReadExpect<'a, Timestep>,
         ReadExpect<'a, Step>,
+        Option<Read<'a, ForceSerial>>,
     );
-    fn run(&mut self, (mut samplers, tops, timestep, step): Self::SystemData) {
-        use rayon::prelude::*;
-        use specs::ParJoin;
+    fn run(&mut self, (mut samplers, tops, timestep, step, force_serial): Self::SystemData) {
 
         for top in (&tops).join() {
-            (&mut samplers).par_join().for_each(|sampler| {
+            (&mut samplers).maybe_par_for_each(force_serial.is_some(), |sampler| {
                 let time = timestep.delta * step.n as f64;
                 let top_field = top.amplitude
                     * Vector3::new(
@@ -3,9 +3,10 @@
 extern crate nalgebra;
 extern crate specs;
 use super::MagneticFieldSampler;
+use crate::parallel::{ForceSerial, MaybeParJoin};
 use crate::ramp::Lerp;
 use nalgebra::Vector3;
-use specs::{Component, HashMapStorage, Join, ReadStorage, System, WriteStorage};
+use specs::{Component, HashMapStorage, Join, Read, ReadStorage, System, WriteStorage};
 
 /// A component representing a uniform bias field, of the form `B = [ B_x, B_y, B_z ]`
 #[derive(Clone, Lerp)]
@@ -39,13 +40,12 @@ impl<'a> System<'a> for UniformMagneticFieldSystem {
     type SystemData = (
         WriteStorage<'a, MagneticFieldSampler>,
         ReadStorage<'a, UniformMagneticField>,
+        Option<Read<'a, ForceSerial>>,
     );
-    fn run(&mut self, (mut samplers, fields): Self::SystemData) {
-        use rayon::prelude::*;
-        use specs::ParJoin;
+    fn run(&mut self, (mut samplers, fields, force_serial): Self::SystemData) {
 
         for field in (&fields).join() {
-            (&mut samplers).par_join().for_each(|sampler| {
+            (&mut samplers).maybe_par_for_each(force_serial.is_some(), |sampler| {
                 sampler.field += field.field;
             });
         }
@@ -55,6 +55,17 @@ pub fn gaussian_dis(std: f64, distance_squared: f64) -> f64 {
     1.0 / (2.0 * PI * std * std) * EXP.powf(-distance_squared / 2.0 / (std * std))
 }
 
+/// The error function, with an absolute error below 1.5e-7.
+///
+/// Uses the rational approximation 7.1.26 of Abramowitz and Stegun.
+pub fn erf(x: f64) -> f64 {
+    let t = 1.0 / (1.0 + 0.3275911 * x.abs());
+    let polynomial = t
+        * (0.254829592
+            + t * (-0.284496736 + t * (1.421413741 + t * (-1.453152027 + t * 1.061405429))));
+    (1.0 - polynomial * (-x * x).exp()).copysign(x)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -67,4 +78,24 @@ mod tests {
         let (distance, _) = get_minimum_distance_line_point(&pos, &centre, &dir);
         assert!(distance > 0.942, "{}", distance < 0.943);
     }
+
+    #[test]
+    fn test_erf() {
+        for (x, expected) in [
+            (0.0, 0.0),
+            (0.5, 0.5204998778),
+            (1.0, 0.8427007929),
+            (2.0, 0.9953222650),
+            (-1.0, -0.8427007929),
+        ]
+        .iter()
+        {
+            assert!(
+                (erf(*x) - expected).abs() < 2e-7,
+                "erf({}) = {}",
+                x,
+                erf(*x)
+            );
+        }
+    }
 }
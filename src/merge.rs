@@ -0,0 +1,216 @@
+//! Merges the atoms of separately prepared worlds.
+//!
+//! [merge_atoms] copies every [Atom] of a source world into a target world, for example to collide two clouds
+//! that were prepared in separate simulations, or to build up a complex initial state. The core atom components
+//! are copied, and the merged atoms are given new [AtomId]s so that the ids in the target remain distinct.
+//! Components that are specific to a plugin, such as the laser cooling transition of the atoms, are copied with
+//! [merge_component].
+//!
+//! The merged atoms are marked as [NewlyCreated], so that the plugins of the target simulation attach their
+//! per-atom components on the next step.
+
+use specs::prelude::*;
+use specs::storage::MaskedStorage;
+
+use crate::atom::{
+    Atom, AtomId, Force, InitialVelocity, Mass, Position, StatisticalWeight, SuperAtomWeight,
+    Velocity,
+};
+use crate::initiate::NewlyCreated;
+use nalgebra::Vector3;
+
+/// Reads the storage of `C` in the world, or returns `None` if `C` has not been registered.
+fn read_if_registered<C: Component>(world: &World) -> Option<ReadStorage<C>> {
+    if world.has_value::<MaskedStorage<C>>() {
+        Some(world.read_storage::<C>())
+    } else {
+        None
+    }
+}
+
+/// Copies all atoms of `source` into `target`, and returns pairs of the source entity and the new target entity.
+///
+/// The [Position], [Velocity], [Force], [Mass], [InitialVelocity], [StatisticalWeight] and [SuperAtomWeight] of
+/// each atom are copied. An atom without a [Position], [Velocity] or [Force] is given a zero value, as required
+/// by the integrator. The merged atoms are numbered with new [AtomId]s after the largest id in `target`, whether
+/// or not they had an id in `source`.
+///
+/// Use [merge_component] with the returned pairs to copy other components.
+pub fn merge_atoms(target: &mut World, source: &World) -> Vec<(Entity, Entity)> {
+    target.register::<Atom>();
+    target.register::<AtomId>();
+    target.register::<Position>();
+    target.register::<Velocity>();
+    target.register::<Force>();
+    target.register::<Mass>();
+    target.register::<InitialVelocity>();
+    target.register::<StatisticalWeight>();
+    target.register::<SuperAtomWeight>();
+    target.register::<NewlyCreated>();
+
+    let mut next_id = (&target.read_storage::<AtomId>())
+        .join()
+        .map(|id| id.id + 1)
+        .max()
+        .unwrap_or(0);
+
+    let entities = source.entities();
+    let atoms = match read_if_registered::<Atom>(source) {
+        Some(atoms) => atoms,
+        None => return Vec::new(),
+    };
+    let positions = read_if_registered::<Position>(source);
+    let velocities = read_if_registered::<Velocity>(source);
+    let forces = read_if_registered::<Force>(source);
+    let masses = read_if_registered::<Mass>(source);
+    let initial_velocities = read_if_registered::<InitialVelocity>(source);
+    let weights = read_if_registered::<StatisticalWeight>(source);
+    let super_atoms = read_if_registered::<SuperAtomWeight>(source);
+
+    let mut merged = Vec::new();
+    for (entity, _) in (&entities, &atoms).join() {
+        let mut builder = target
+            .create_entity()
+            .with(Atom)
+            .with(AtomId { id: next_id })
+            .with(
+                positions
+                    .as_ref()
+                    .and_then(|storage| storage.get(entity))
+                    .cloned()
+                    .unwrap_or(Position {
+                        pos: Vector3::zeros(),
+                    }),
+            )
+            .with(
+                velocities
+                    .as_ref()
+                    .and_then(|storage| storage.get(entity))
+                    .copied()
+                    .unwrap_or(Velocity {
+                        vel: Vector3::zeros(),
+                    }),
+            )
+            .with(
+                forces
+                    .as_ref()
+                    .and_then(|storage| storage.get(entity))
+                    .copied()
+                    .unwrap_or_default(),
+            )
+            .with(NewlyCreated);
+        if let Some(mass) = masses.as_ref().and_then(|storage| storage.get(entity)) {
+            builder = builder.with(mass.clone());
+        }
+        if let Some(initial) = initial_velocities
+            .as_ref()
+            .and_then(|storage| storage.get(entity))
+        {
+            builder = builder.with(InitialVelocity { vel: initial.vel });
+        }
+        if let Some(weight) = weights.as_ref().and_then(|storage| storage.get(entity)) {
+            builder = builder.with(*weight);
+        }
+        if let Some(weight) = super_atoms.as_ref().and_then(|storage| storage.get(entity)) {
+            builder = builder.with(*weight);
+        }
+        merged.push((entity, builder.build()));
+        next_id += 1;
+    }
+    merged
+}
+
+/// Copies the component `C` of atoms merged by [merge_atoms] from `source` to `target`.
+///
+/// Atoms that do not have `C` in `source` are left without it in `target`.
+pub fn merge_component<C>(target: &mut World, source: &World, merged: &[(Entity, Entity)])
+where
+    C: Component + Clone,
+    C::Storage: Default,
+{
+    target.register::<C>();
+    let from = match read_if_registered::<C>(source) {
+        Some(from) => from,
+        None => return,
+    };
+    let mut to = target.write_storage::<C>();
+    for (source_entity, target_entity) in merged.iter() {
+        if let Some(component) = from.get(*source_entity) {
+            to.insert(*target_entity, component.clone())
+                .expect("Could not insert merged component.");
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use crate::species::Rubidium87_780D2;
+    use assert_approx_eq::assert_approx_eq;
+    use hashbrown::HashSet;
+
+    fn create_cloud(offset: f64, with_ids: bool) -> World {
+        let mut world = World::new();
+        world.register::<Atom>();
+        world.register::<AtomId>();
+        world.register::<Position>();
+        world.register::<Velocity>();
+        world.register::<Mass>();
+        world.register::<Rubidium87_780D2>();
+        for i in 0..10 {
+            let mut builder = world
+                .create_entity()
+                .with(Atom)
+                .with(Position {
+                    pos: Vector3::new(offset, i as f64 * 1.0e-4, 0.0).cast(),
+                })
+                .with(Velocity {
+                    vel: Vector3::new(-offset, 0.0, 0.0).cast(),
+                })
+                .with(Mass { value: 87.0 })
+                .with(Rubidium87_780D2::default());
+            if with_ids {
+                builder = builder.with(AtomId { id: i });
+            }
+            builder.build();
+        }
+        // A laser beam, which is not an atom and is not merged.
+        world
+            .create_entity()
+            .with(Position {
+                pos: Vector3::new(0.0, 0.0, 0.0).cast(),
+            })
+            .build();
+        world
+    }
+
+    #[test]
+    fn test_merge_two_clouds() {
+        let mut target = create_cloud(1.0e-3, true);
+        let source = create_cloud(-1.0e-3, false);
+        let merged = merge_atoms(&mut target, &source);
+        merge_component::<Rubidium87_780D2>(&mut target, &source, &merged);
+        target.maintain();
+        assert_eq!(merged.len(), 10);
+
+        let atoms = target.read_storage::<Atom>();
+        let ids = target.read_storage::<AtomId>();
+        let positions = target.read_storage::<Position>();
+        let velocities = target.read_storage::<Velocity>();
+        let forces = target.read_storage::<Force>();
+        let transitions = target.read_storage::<Rubidium87_780D2>();
+        assert_eq!((&atoms).join().count(), 20);
+        let distinct: HashSet<u64> = (&atoms, &ids).join().map(|(_, id)| id.id).collect();
+        assert_eq!(distinct.len(), 20);
+
+        for (_, target_entity) in merged.iter() {
+            let pos = positions.get(*target_entity).unwrap().pos.cast::<f64>();
+            let vel = velocities.get(*target_entity).unwrap().vel.cast::<f64>();
+            assert_approx_eq!(pos[0], -1.0e-3, 1e-9);
+            assert_approx_eq!(vel[0], 1.0e-3, 1e-9);
+            assert_eq!(forces.get(*target_entity).unwrap().force, Vector3::zeros());
+            assert!(transitions.contains(*target_entity));
+        }
+    }
+}
@@ -0,0 +1,393 @@
+//! Locates the minimum of the trapping potential for arbitrary combinations of forces.
+//!
+//! For configurations combining several beams, gravity and magnetic fields, the position of the trap minimum has
+//! no closed form. [find_potential_minimum] finds it by gradient descent on the total force, which is measured
+//! with pinned probe atoms, in the same way as [linear_response](crate::laser_cooling::linear_response).
+
+use std::fmt;
+
+use nalgebra::{Matrix3, Vector3};
+use specs::prelude::*;
+
+use crate::atom::{Atom, Force, Mass, Position, Velocity};
+use crate::initiate::NewlyCreated;
+use crate::integrator::Pinned;
+use crate::laser_cooling::linear_response::with_mean_forces;
+use crate::simulation::Simulation;
+
+/// Number of steps simulated to calculate the forces on newly created probe atoms.
+const PROBE_STEPS: usize = 2;
+
+/// Configures the search for the potential minimum, see [find_potential_minimum].
+pub struct MinimumSearchConfig {
+    /// Position at which the search starts, in SI units of m.
+    pub start: Vector3<f64>,
+    /// Mass of the probe atoms, in atomic mass units.
+    pub mass: f64,
+    /// The search has converged once the magnitude of the force is below this value, in SI units of N.
+    pub force_tolerance: f64,
+    /// Displacement used to calculate the curvature of the potential, in SI units of m.
+    pub curvature_step: f64,
+    /// The search fails if the probe moves further than this distance from `start`, in SI units of m.
+    pub max_distance: f64,
+    /// Largest number of descent steps.
+    pub max_iterations: usize,
+}
+
+/// The reasons that [find_potential_minimum] may fail to find a stable minimum.
+#[derive(Clone, Copy, Debug)]
+pub enum MinimumSearchError {
+    /// The force vanishes at `position`, but the potential curves downwards along at least one direction.
+    Saddle {
+        /// Position of the stationary point, in SI units of m.
+        position: Vector3<f64>,
+        /// Eigenvalues of the curvature of the potential, in SI units of N/m, in ascending order.
+        curvatures: Vector3<f64>,
+    },
+    /// The probe moved further than the maximum distance from the start, so there is no minimum nearby.
+    Runaway {
+        /// Position of the probe when the search was abandoned, in SI units of m.
+        position: Vector3<f64>,
+    },
+    /// The force did not fall below the tolerance within the maximum number of iterations.
+    NotConverged {
+        /// Position of the probe when the search was abandoned, in SI units of m.
+        position: Vector3<f64>,
+        /// Force on the probe when the search was abandoned, in SI units of N.
+        force: Vector3<f64>,
+    },
+    /// The force on a probe near `position` is infinite or NaN, so the potential cannot be descended.
+    NonFiniteForce {
+        /// Position of the probe, in SI units of m.
+        position: Vector3<f64>,
+    },
+}
+impl fmt::Display for MinimumSearchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MinimumSearchError::Saddle {
+                position,
+                curvatures,
+            } => write!(
+                f,
+                "The potential has a saddle point at {:?} m, with curvatures {:?} N/m.",
+                position.as_slice(),
+                curvatures.as_slice()
+            ),
+            MinimumSearchError::Runaway { position } => write!(
+                f,
+                "No potential minimum was found: the probe ran away to {:?} m.",
+                position.as_slice()
+            ),
+            MinimumSearchError::NotConverged { position, force } => write!(
+                f,
+                "The search for the potential minimum did not converge: the force at {:?} m is {:?} N.",
+                position.as_slice(),
+                force.as_slice()
+            ),
+            MinimumSearchError::NonFiniteForce { position } => write!(
+                f,
+                "The force on the probe at {:?} m is not finite.",
+                position.as_slice()
+            ),
+        }
+    }
+}
+
+/// Finds the stable equilibrium of the total force field nearest to `config.start`.
+///
+/// The force and its gradient are measured with pinned probe atoms, each built with `probe`, which should add the
+/// components that determine the forces on the atom, for example a [Polarizability](crate::dipole::Polarizability)
+/// or laser cooling transition. The probe atoms already have a [Position], [Velocity], [Force], [Mass] and [Atom].
+///
+/// Each iteration takes a gradient descent step `dx = F / k_max`, where `k_max` is the largest curvature of the
+/// potential at the probe. Once the force falls below the tolerance, the curvature of the potential is checked
+/// to distinguish a minimum from a saddle point.
+///
+/// The fluctuations of the scattering and emission forces are disabled during the search, and then restored. The
+/// simulation is advanced by one step per iteration, during which any other atoms are integrated as normal.
+///
+/// Returns the position of the minimum, in SI units of m.
+pub fn find_potential_minimum<F>(
+    simulation: &mut Simulation,
+    config: &MinimumSearchConfig,
+    probe: F,
+) -> Result<Vector3<f64>, MinimumSearchError>
+where
+    F: Fn(EntityBuilder) -> EntityBuilder,
+{
+    with_mean_forces(simulation, |simulation| {
+        let offsets = probe_offsets(config.curvature_step);
+        let probes: Vec<Entity> = offsets
+            .iter()
+            .map(|offset| {
+                let builder = simulation
+                    .world
+                    .create_entity()
+                    .with(Position {
+                        pos: (config.start + offset).cast(),
+                    })
+                    .with(Velocity {
+                        vel: Vector3::new(0.0, 0.0, 0.0).cast(),
+                    })
+                    .with(Force::new())
+                    .with(Mass { value: config.mass })
+                    .with(Atom)
+                    .with(Pinned)
+                    .with(NewlyCreated);
+                probe(builder).build()
+            })
+            .collect();
+        for _ in 0..PROBE_STEPS - 1 {
+            simulation.step();
+        }
+
+        let result = descend(simulation, config, &probes, &offsets);
+
+        simulation
+            .world
+            .delete_entities(&probes)
+            .expect("Could not delete probe atoms.");
+        simulation.world.maintain();
+        result
+    })
+}
+
+/// The displacements of the probe atoms from the search position.
+fn probe_offsets(step: f64) -> Vec<Vector3<f64>> {
+    let mut offsets = vec![Vector3::new(0.0, 0.0, 0.0)];
+    for axis in 0..3 {
+        for sign in [1.0, -1.0].iter() {
+            let mut offset = Vector3::new(0.0, 0.0, 0.0);
+            offset[axis] = sign * step;
+            offsets.push(offset);
+        }
+    }
+    offsets
+}
+
+fn descend(
+    simulation: &mut Simulation,
+    config: &MinimumSearchConfig,
+    probes: &[Entity],
+    offsets: &[Vector3<f64>],
+) -> Result<Vector3<f64>, MinimumSearchError> {
+    let mut position = config.start;
+    let mut step_size: Option<f64> = None;
+    let mut blind_step = config.curvature_step;
+    let mut force = Vector3::new(0.0, 0.0, 0.0);
+    for _ in 0..config.max_iterations {
+        {
+            let mut positions = simulation.world.write_storage::<Position>();
+            for (probe, offset) in probes.iter().zip(offsets.iter()) {
+                positions.get_mut(*probe).unwrap().pos = (position + offset).cast();
+            }
+        }
+        simulation.step();
+        let forces: Vec<Vector3<f64>> = {
+            let storage = simulation.world.read_storage::<Force>();
+            probes
+                .iter()
+                .map(|probe| storage.get(*probe).unwrap().force.cast::<f64>())
+                .collect()
+        };
+        if forces
+            .iter()
+            .any(|force| force.iter().any(|component| !component.is_finite()))
+        {
+            return Err(MinimumSearchError::NonFiniteForce { position });
+        }
+        force = forces[0];
+
+        // The curvature of the potential is minus the gradient of the force.
+        let mut curvature = Matrix3::<f64>::zeros();
+        for axis in 0..3 {
+            let gradient =
+                (forces[2 * axis + 1] - forces[2 * axis + 2]) / (2.0 * config.curvature_step);
+            curvature.set_column(axis, &-gradient);
+        }
+        let curvature = 0.5 * (curvature + curvature.transpose());
+        let mut curvatures = curvature.symmetric_eigenvalues();
+        curvatures
+            .as_mut_slice()
+            .sort_by(|a, b| a.total_cmp(b));
+
+        if force.norm() < config.force_tolerance {
+            return if curvatures[0] > 0.0 {
+                Ok(position)
+            } else {
+                Err(MinimumSearchError::Saddle {
+                    position,
+                    curvatures,
+                })
+            };
+        }
+
+        // Where the potential is not convex along any direction, keep the previous step size. If there is none,
+        // follow the force with steps of increasing length.
+        if curvatures[2] > 0.0 {
+            step_size = Some(1.0 / curvatures[2]);
+        }
+        let dx = match step_size {
+            Some(step_size) => step_size * force,
+            None => {
+                blind_step *= 2.0;
+                blind_step * force.normalize()
+            }
+        };
+        position += dx;
+        if (position - config.start).norm() > config.max_distance {
+            return Err(MinimumSearchError::Runaway { position });
+        }
+    }
+    Err(MinimumSearchError::NotConverged { position, force })
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use crate::constant::{AMU, GC};
+    use crate::custom_force::{CustomForceField, CustomForcePlugin};
+    use crate::dipole::{DipoleLight, DipolePlugin, Polarizability};
+    use crate::gravity::ApplyGravityOption;
+    use crate::integrator::Timestep;
+    use crate::laser::frame::Frame;
+    use crate::laser::gaussian::{get_gaussian_beam_intensity, GaussianBeam};
+    use crate::laser::LaserPlugin;
+    use crate::simulation::SimulationBuilder;
+
+    fn config(start: Vector3<f64>) -> MinimumSearchConfig {
+        MinimumSearchConfig {
+            start,
+            mass: 87.0,
+            force_tolerance: 1.0e-29,
+            curvature_step: 1.0e-7,
+            max_distance: 1.0e-3,
+            max_iterations: 200,
+        }
+    }
+
+    #[test]
+    fn test_minimum_of_crossed_dipole_trap_with_gravity() {
+        let mut sim_builder = SimulationBuilder::default();
+        sim_builder.add_plugin(LaserPlugin::<2>);
+        sim_builder.add_plugin(DipolePlugin::<2>);
+        let mut sim = sim_builder.build();
+        sim.world.insert(Timestep { delta: 1.0e-6 });
+        sim.world.insert(ApplyGravityOption);
+
+        let e_radius = 40.0e-6;
+        let beams: Vec<GaussianBeam> = [(Vector3::x(), Vector3::y()), (Vector3::y(), Vector3::x())]
+            .iter()
+            .map(|(direction, x_vector)| {
+                let beam = GaussianBeam {
+                    intersection: Vector3::new(0.0, 0.0, 0.0),
+                    e_radius,
+                    power: 0.2,
+                    direction: *direction,
+                    rayleigh_range: f64::INFINITY,
+                    ellipticity: 0.0,
+                    focus_offset: 0.0,
+                };
+                sim.world
+                    .create_entity()
+                    .with(beam)
+                    .with(DipoleLight {
+                        wavelength: 1064.0e-9,
+                    })
+                    .with(Frame {
+                        x_vector: *x_vector,
+                        y_vector: direction.cross(x_vector),
+                    })
+                    .build();
+                beam
+            })
+            .collect();
+        let polarizability = Polarizability::calculate_for(1064e-9, 780e-9, 6.065e6);
+
+        let minimum = find_potential_minimum(
+            &mut sim,
+            &config(Vector3::new(2.0e-6, -3.0e-6, 1.0e-6)),
+            |builder| builder.with(polarizability),
+        )
+        .expect("No minimum found.");
+
+        // On the vertical axis, U(z) = -2 U0 exp(-z^2 / e_radius^2) + m g z. The sag solves
+        // 4 U0 z / e_radius^2 exp(-z^2 / e_radius^2) = -m g, which is found by bisection.
+        let depth = polarizability.prefactor
+            * get_gaussian_beam_intensity(&beams[0], &Position::new(), None, None);
+        let weight = 87.0 * AMU * GC;
+        let net_force =
+            |z: f64| -4.0 * depth * z / e_radius.powi(2) * (-(z / e_radius).powi(2)).exp() - weight;
+        let (mut low, mut high) = (-e_radius / 2.0_f64.sqrt(), 0.0);
+        for _ in 0..100 {
+            let mid = 0.5 * (low + high);
+            if net_force(mid) > 0.0 {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+        let sag = 0.5 * (low + high);
+        assert!(sag < -1.0e-6, "sag {} m", sag);
+        assert!((minimum[0]).abs() < 1.0e-8, "minimum {:?}", minimum);
+        assert!((minimum[1]).abs() < 1.0e-8, "minimum {:?}", minimum);
+        assert!(
+            (minimum[2] - sag).abs() < 0.01 * sag.abs(),
+            "minimum {:?}, sag {}",
+            minimum,
+            sag
+        );
+        // The probe atoms are removed.
+        assert_eq!(sim.world.read_storage::<Atom>().join().count(), 0);
+    }
+
+    fn custom_force_simulation<F>(f: F) -> Simulation
+    where
+        F: Fn(Vector3<f64>, Vector3<f64>) -> Vector3<f64> + Send + Sync + 'static,
+    {
+        let mut sim_builder = SimulationBuilder::default();
+        sim_builder.add_plugin(CustomForcePlugin);
+        let mut sim = sim_builder.build();
+        sim.world.insert(Timestep { delta: 1.0e-6 });
+        sim.world.insert(CustomForceField::new(f));
+        sim
+    }
+
+    #[test]
+    fn test_saddle_point_is_reported() {
+        let k = 1.0e-20;
+        let mut sim = custom_force_simulation(move |pos, _| {
+            Vector3::new(-k * pos[0], k * pos[1], -k * pos[2])
+        });
+        match find_potential_minimum(&mut sim, &config(Vector3::new(1.0e-6, 0.0, -2.0e-6)), |b| b) {
+            Err(MinimumSearchError::Saddle {
+                position,
+                curvatures,
+            }) => {
+                assert!(position.norm() < 1.0e-8);
+                assert!(curvatures[0] < 0.0 && curvatures[2] > 0.0);
+            }
+            other => panic!("expected a saddle point, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_runaway_is_reported() {
+        let mut sim = custom_force_simulation(|_, _| Vector3::new(0.0, 0.0, -1.0e-25));
+        match find_potential_minimum(&mut sim, &config(Vector3::new(0.0, 0.0, 0.0)), |b| b) {
+            Err(MinimumSearchError::Runaway { .. }) => {}
+            other => panic!("expected a runaway, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_non_finite_force_is_reported() {
+        let mut sim = custom_force_simulation(|pos, _| Vector3::new(0.0, 0.0, 1.0e-30 / pos[2]));
+        match find_potential_minimum(&mut sim, &config(Vector3::new(0.0, 0.0, 0.0)), |b| b) {
+            Err(MinimumSearchError::NonFiniteForce { .. }) => {}
+            other => panic!("expected a non-finite force, found {:?}", other),
+        }
+    }
+}
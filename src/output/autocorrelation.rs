@@ -0,0 +1,281 @@
+//! The velocity autocorrelation function of tracer atoms.
+//!
+//! The velocity autocorrelation function `C(t) = <v(0) . v(t)>` describes how quickly an atom forgets its velocity,
+//! for example through collisions or the damping of an optical molasses. Its integral gives the diffusion
+//! coefficient, `D = 1/3 int_0^inf C(t) dt`.
+//!
+//! The [VelocityAutocorrelationSystem] records the velocity of each atom marked with the [Tracer] component every
+//! step, and writes the autocorrelation function to a file when the system is dropped at the end of the simulation.
+//! The full velocity history of each tracer is kept in memory, so only a few atoms should be traced in long runs.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+
+use super::tracer::Tracer;
+use crate::atom::{AtomId, Velocity};
+use crate::integrator::{Timestep, INTEGRATE_VELOCITY_SYSTEM_NAME};
+use crate::simulation::Plugin;
+use hashbrown::HashMap;
+use nalgebra::Vector3;
+use specs::prelude::*;
+
+/// Accumulates velocity time series, and calculates their autocorrelation function.
+///
+/// The autocorrelation at a lag of `k` steps is averaged over all atoms and all time origins for which the series
+/// extends `k` steps later. The series of an atom that is deleted during the run ends at its deletion, so it
+/// contributes only to the lags that it spans.
+pub struct VelocityAutocorrelation {
+    /// The largest lag for which the autocorrelation is calculated, in steps.
+    pub max_lag: usize,
+    /// The velocity series of each atom, keyed by [AtomId], in SI units of m/s.
+    series: HashMap<u64, Vec<Vector3<f64>>>,
+}
+impl VelocityAutocorrelation {
+    /// Creates an empty accumulator, which calculates the autocorrelation for lags of up to `max_lag` steps.
+    pub fn new(max_lag: usize) -> Self {
+        VelocityAutocorrelation {
+            max_lag,
+            series: HashMap::new(),
+        }
+    }
+
+    /// Appends a velocity, in SI units of m/s, to the series of the atom with the given `id`.
+    pub fn record(&mut self, id: u64, velocity: Vector3<f64>) {
+        self.series.entry(id).or_insert_with(Vec::new).push(velocity);
+    }
+
+    /// Returns true if no velocity has been recorded.
+    pub fn is_empty(&self) -> bool {
+        self.series.is_empty()
+    }
+
+    /// The autocorrelation `<v(0) . v(t)>` for lags of `0..=max_lag` steps, in SI units of m^2/s^2.
+    ///
+    /// The list ends at the longest lag spanned by any series, if this is shorter than `max_lag`.
+    pub fn correlation(&self) -> Vec<f64> {
+        let mut sums = vec![0.0; self.max_lag + 1];
+        let mut counts = vec![0u64; self.max_lag + 1];
+        for series in self.series.values() {
+            for lag in 0..=self.max_lag.min(series.len().saturating_sub(1)) {
+                for origin in 0..series.len() - lag {
+                    sums[lag] += series[origin].dot(&series[origin + lag]);
+                }
+                counts[lag] += (series.len() - lag) as u64;
+            }
+        }
+        sums.iter()
+            .zip(counts.iter())
+            .take_while(|(_, count)| **count > 0)
+            .map(|(sum, count)| sum / *count as f64)
+            .collect()
+    }
+
+    /// The diffusion coefficient `1/3 int C(t) dt`, in SI units of m^2/s, integrated up to the longest lag.
+    ///
+    /// The integral is calculated with the trapezium rule, for a step duration `dt` in SI units of s. The
+    /// autocorrelation must have decayed to zero within `max_lag` steps for the result to be meaningful.
+    pub fn diffusion_coefficient(&self, dt: f64) -> f64 {
+        let correlation = self.correlation();
+        let integral: f64 = correlation
+            .windows(2)
+            .map(|pair| (pair[0] + pair[1]) / 2.0 * dt)
+            .sum();
+        integral / 3.0
+    }
+
+    /// Writes the autocorrelation to a comma-separated file with columns `lag`, `time` (in s) and `correlation`
+    /// (in m^2/s^2), for a step duration `dt` in SI units of s.
+    pub fn write(&self, file_name: &std::path::Path, dt: f64) -> Result<(), io::Error> {
+        let mut writer = BufWriter::new(File::create(file_name)?);
+        writeln!(writer, "lag,time,correlation")?;
+        for (lag, correlation) in self.correlation().iter().enumerate() {
+            writeln!(writer, "{},{:e},{:e}", lag, lag as f64 * dt, correlation)?;
+        }
+        writer.flush()
+    }
+}
+
+/// Records the velocities of [Tracer] atoms each step, and writes their [VelocityAutocorrelation] to a file when
+/// dropped.
+///
+/// Tracers without an [AtomId] are ignored.
+pub struct VelocityAutocorrelationSystem {
+    pub autocorrelation: VelocityAutocorrelation,
+    file_name: PathBuf,
+    /// The duration of the timestep, recorded from the [Timestep] resource.
+    dt: f64,
+}
+impl VelocityAutocorrelationSystem {
+    /// Writes the autocorrelation for lags of up to `max_lag` steps to `file_name`.
+    pub fn new(file_name: PathBuf, max_lag: usize) -> Self {
+        VelocityAutocorrelationSystem {
+            autocorrelation: VelocityAutocorrelation::new(max_lag),
+            file_name,
+            dt: 0.0,
+        }
+    }
+}
+impl<'a> System<'a> for VelocityAutocorrelationSystem {
+    type SystemData = (
+        ReadStorage<'a, Tracer>,
+        ReadStorage<'a, AtomId>,
+        ReadStorage<'a, Velocity>,
+        ReadExpect<'a, Timestep>,
+    );
+
+    fn run(&mut self, (tracers, ids, velocities, timestep): Self::SystemData) {
+        self.dt = timestep.delta;
+        for (_, id, vel) in (&tracers, &ids, &velocities).join() {
+            self.autocorrelation.record(id.id, vel.vel.cast());
+        }
+    }
+}
+impl Drop for VelocityAutocorrelationSystem {
+    fn drop(&mut self) {
+        if self.autocorrelation.is_empty() {
+            return;
+        }
+        if let Err(why) = self.autocorrelation.write(&self.file_name, self.dt) {
+            if !std::thread::panicking() {
+                panic!("couldn't write {}: {}", self.file_name.display(), why);
+            }
+        }
+    }
+}
+
+/// This plugin writes the velocity autocorrelation function of [Tracer] atoms to a file at the end of the
+/// simulation.
+///
+/// The velocities are recorded after the velocity integrator, so the end frame systems must be added to the
+/// [SimulationBuilder](crate::simulation::SimulationBuilder) before this plugin.
+///
+/// See also [crate::output::autocorrelation].
+pub struct VelocityAutocorrelationPlugin {
+    file_name: String,
+    max_lag: usize,
+}
+impl VelocityAutocorrelationPlugin {
+    /// Writes the autocorrelation for lags of up to `max_lag` steps to `file_name`.
+    pub fn new(file_name: String, max_lag: usize) -> Self {
+        VelocityAutocorrelationPlugin { file_name, max_lag }
+    }
+}
+impl Plugin for VelocityAutocorrelationPlugin {
+    fn build(&self, builder: &mut crate::simulation::SimulationBuilder) {
+        builder.world.register::<Tracer>();
+        builder.dispatcher_builder.add(
+            VelocityAutocorrelationSystem::new(PathBuf::from(&self.file_name), self.max_lag),
+            "velocity_autocorrelation",
+            &[INTEGRATE_VELOCITY_SYSTEM_NAME],
+        );
+    }
+    fn deps(&self) -> Vec<Box<dyn Plugin>> {
+        Vec::new()
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use crate::atom::{Atom, Force, Mass, Position};
+    use crate::constant::AMU;
+    use crate::custom_force::{CustomForceField, CustomForcePlugin};
+    use crate::simulation::SimulationBuilder;
+    use assert_approx_eq::assert_approx_eq;
+
+    /// Runs a simulation of two tracers with damping rate `gamma`, deleting the second after `deleted_after`
+    /// steps, and returns the autocorrelation read back from the file.
+    fn simulate(gamma: f64, steps: usize, deleted_after: usize, max_lag: usize) -> Vec<f64> {
+        let file_name = std::env::temp_dir().join(format!("atomecs_test_vacf_{}.csv", gamma));
+        let mut sim_builder = SimulationBuilder::default();
+        sim_builder.add_plugin(CustomForcePlugin);
+        sim_builder.add_end_frame_systems();
+        sim_builder.add_plugin(VelocityAutocorrelationPlugin::new(
+            file_name.to_str().unwrap().to_string(),
+            max_lag,
+        ));
+        let mut sim = sim_builder.build();
+        sim.world.insert(Timestep { delta: 1.0e-6 });
+        let mass = 87.0;
+        sim.world.insert(CustomForceField::new(move |_pos, vel| {
+            -gamma * mass * AMU * vel
+        }));
+
+        let mut atoms = Vec::new();
+        for id in 0..2 {
+            atoms.push(
+                sim.world
+                    .create_entity()
+                    .with(Position::new())
+                    .with(Velocity {
+                        vel: Vector3::new(1.0, 2.0, 0.0).cast(),
+                    })
+                    .with(Force::new())
+                    .with(Mass { value: mass })
+                    .with(Atom)
+                    .with(AtomId { id })
+                    .with(Tracer)
+                    .build(),
+            );
+        }
+        for step in 0..steps {
+            if step == deleted_after {
+                sim.world.delete_entity(atoms[1]).unwrap();
+            }
+            sim.step();
+        }
+        drop(sim);
+
+        let contents = std::fs::read_to_string(&file_name).expect("Could not read autocorrelation.");
+        std::fs::remove_file(&file_name).ok();
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines[0], "lag,time,correlation");
+        lines[1..]
+            .iter()
+            .map(|line| line.split(',').nth(2).unwrap().parse::<f64>().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_free_atom_has_flat_autocorrelation() {
+        let correlation = simulate(0.0, 100, 10, 50);
+        assert_eq!(correlation.len(), 51);
+        for value in correlation.iter() {
+            assert_approx_eq!(*value, 5.0, 1e-9);
+        }
+
+        let mut autocorrelation = VelocityAutocorrelation::new(4);
+        for _ in 0..10 {
+            autocorrelation.record(0, Vector3::new(1.0, 2.0, 0.0));
+        }
+        assert_approx_eq!(autocorrelation.diffusion_coefficient(0.5), 5.0 * 2.0 / 3.0, 1e-12);
+    }
+
+    #[test]
+    fn test_damped_atom_autocorrelation_decays() {
+        let gamma = 1.0e4;
+        let correlation = simulate(gamma, 400, 400, 300);
+        assert_eq!(correlation.len(), 301);
+        assert!(correlation.windows(2).all(|pair| pair[1] < pair[0]));
+        assert!(correlation[300] < 0.5 * correlation[0]);
+    }
+
+    #[test]
+    fn test_deleted_series_is_truncated() {
+        let mut autocorrelation = VelocityAutocorrelation::new(5);
+        for _ in 0..10 {
+            autocorrelation.record(0, Vector3::new(1.0, 0.0, 0.0));
+        }
+        for _ in 0..3 {
+            autocorrelation.record(1, Vector3::new(0.0, 3.0, 0.0));
+        }
+        let correlation = autocorrelation.correlation();
+        // Lags 0-2 include both atoms, weighted by their number of time origins.
+        assert_approx_eq!(correlation[0], (10.0 + 3.0 * 9.0) / 13.0, 1e-12);
+        assert_approx_eq!(correlation[2], (8.0 + 9.0) / 9.0, 1e-12);
+        assert_approx_eq!(correlation[3], 1.0, 1e-12);
+        assert_eq!(correlation.len(), 6);
+    }
+}
@@ -0,0 +1,171 @@
+//! Measures the center of mass and size of the atom cloud each step.
+//!
+//! The [CloudGeometry] resource tracks the position and shape of the cloud directly, for example to observe
+//! the sloshing (center-of-mass) and breathing (size) modes of atoms in a trap.
+
+use crate::atom::{Atom, Mass, Position};
+use crate::integrator::INTEGRATE_POSITION_SYSTEM_NAME;
+use crate::simulation::Plugin;
+use nalgebra::Vector3;
+use specs::prelude::*;
+
+/// A resource that holds the center of mass and size of the atom cloud, see [CalculateCloudGeometrySystem].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CloudGeometry {
+    /// Center of the cloud, in SI units of m.
+    pub center: Vector3<f64>,
+    /// Root-mean-square displacement of the atoms from the center along each axis, in SI units of m.
+    pub rms_size: Vector3<f64>,
+}
+
+/// Calculates the [CloudGeometry] of the atoms each step.
+///
+/// The cloud geometry is left unchanged if there are no atoms.
+pub struct CalculateCloudGeometrySystem {
+    /// If true, each atom is weighted by its [Mass]. Otherwise, all atoms are weighted equally.
+    pub mass_weighted: bool,
+}
+impl<'a> System<'a> for CalculateCloudGeometrySystem {
+    type SystemData = (
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Mass>,
+        ReadStorage<'a, Atom>,
+        Write<'a, CloudGeometry>,
+    );
+
+    fn run(&mut self, (positions, masses, atoms, mut geometry): Self::SystemData) {
+        let samples: Vec<(Vector3<f64>, f64)> = (&positions, masses.maybe(), &atoms)
+            .join()
+            .map(|(pos, mass, _)| {
+                let weight = match (self.mass_weighted, mass) {
+                    (true, Some(mass)) => mass.value,
+                    _ => 1.0,
+                };
+                (pos.pos.cast::<f64>(), weight)
+            })
+            .collect();
+        let total_weight: f64 = samples.iter().map(|(_, weight)| weight).sum();
+        if samples.is_empty() || total_weight <= 0.0 {
+            return;
+        }
+
+        let center = samples
+            .iter()
+            .fold(Vector3::new(0.0, 0.0, 0.0), |sum, (pos, weight)| {
+                sum + pos * *weight
+            })
+            / total_weight;
+        let variance = samples
+            .iter()
+            .fold(Vector3::new(0.0, 0.0, 0.0), |sum, (pos, weight)| {
+                let displacement = pos - center;
+                sum + displacement.component_mul(&displacement) * *weight
+            })
+            / total_weight;
+        geometry.center = center;
+        geometry.rms_size = variance.map(f64::sqrt);
+    }
+}
+
+/// This plugin calculates the [CloudGeometry] of the atoms each step.
+///
+/// See also [crate::output::cloud_geometry].
+#[derive(Default)]
+pub struct CloudGeometryPlugin {
+    /// If true, the center and size of the cloud are weighted by the [Mass] of each atom.
+    pub mass_weighted: bool,
+}
+impl Plugin for CloudGeometryPlugin {
+    fn build(&self, builder: &mut crate::simulation::SimulationBuilder) {
+        builder.world.insert(CloudGeometry::default());
+        builder.dispatcher_builder.add(
+            CalculateCloudGeometrySystem {
+                mass_weighted: self.mass_weighted,
+            },
+            "calculate_cloud_geometry",
+            &[INTEGRATE_POSITION_SYSTEM_NAME],
+        );
+    }
+    fn deps(&self) -> Vec<Box<dyn Plugin>> {
+        Vec::new()
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use rand_distr::{Distribution, Normal};
+
+    fn create_world() -> World {
+        let mut test_world = World::new();
+        test_world.register::<Position>();
+        test_world.register::<Mass>();
+        test_world.register::<Atom>();
+        test_world
+    }
+
+    #[test]
+    fn test_cloud_geometry_of_gaussian_cloud() {
+        let mut test_world = create_world();
+        let center = Vector3::new(1.0e-3, -2.0e-3, 0.5e-3);
+        let sizes = Vector3::new(1.0e-4, 2.0e-4, 4.0e-4);
+        let mut rng = StdRng::seed_from_u64(1);
+        let number = 10_000;
+        for _ in 0..number {
+            let mut pos = Vector3::new(0.0, 0.0, 0.0);
+            for axis in 0..3 {
+                pos[axis] = Normal::new(center[axis], sizes[axis])
+                    .unwrap()
+                    .sample(&mut rng);
+            }
+            test_world
+                .create_entity()
+                .with(Position { pos: pos.cast() })
+                .with(Mass { value: 87.0 })
+                .with(Atom)
+                .build();
+        }
+
+        let mut system = CalculateCloudGeometrySystem {
+            mass_weighted: false,
+        };
+        System::setup(&mut system, &mut test_world);
+        system.run_now(&test_world);
+
+        let geometry = *test_world.read_resource::<CloudGeometry>();
+        let n = number as f64;
+        for axis in 0..3 {
+            // The standard errors of the mean and of the standard deviation are sigma/sqrt(N) and sigma/sqrt(2N).
+            assert!((geometry.center[axis] - center[axis]).abs() < 4.0 * sizes[axis] / n.sqrt());
+            assert!(
+                (geometry.rms_size[axis] - sizes[axis]).abs()
+                    < 4.0 * sizes[axis] / (2.0 * n).sqrt()
+            );
+        }
+    }
+
+    #[test]
+    fn test_cloud_geometry_of_single_atom() {
+        let mut test_world = create_world();
+        let pos = Vector3::new(1.0, 2.0, 3.0);
+        test_world
+            .create_entity()
+            .with(Position { pos: pos.cast() })
+            .with(Mass { value: 87.0 })
+            .with(Atom)
+            .build();
+
+        let mut system = CalculateCloudGeometrySystem {
+            mass_weighted: true,
+        };
+        System::setup(&mut system, &mut test_world);
+        system.run_now(&test_world);
+
+        let geometry = *test_world.read_resource::<CloudGeometry>();
+        assert_eq!(geometry.center, pos);
+        assert_eq!(geometry.rms_size, Vector3::new(0.0, 0.0, 0.0));
+    }
+}
@@ -2,22 +2,84 @@
 
 use crate::atom::*;
 use crate::integrator::{Step, Timestep};
-use specs::{Join, ReadExpect, ReadStorage, System};
+use crate::output::trigger::{OutputSchedule, OutputTrigger};
+use specs::{Join, ReadExpect, ReadStorage, System, World, WorldExt};
 
 /// A system that writes diagnostic output to the console window.
-pub struct ConsoleOutputSystem;
+///
+/// If any atom has a [StatisticalWeight] or [SuperAtomWeight], the number of real atoms that they represent is
+/// also written.
+///
+/// By default, the output is written every 100 steps. See
+/// [SimulationBuilder::set_console_output](crate::simulation::SimulationBuilder::set_console_output) to write it on
+/// other steps. `ConsoleOutputSystem::default()` creates the system that was the unit struct `ConsoleOutputSystem`.
+pub struct ConsoleOutputSystem {
+    /// Decides on which steps the output is written.
+    schedule: OutputSchedule,
+}
+
+impl ConsoleOutputSystem {
+    /// Creates a [ConsoleOutputSystem] that writes the output on the steps given by the [OutputTrigger].
+    pub fn new(trigger: OutputTrigger) -> Self {
+        ConsoleOutputSystem {
+            schedule: OutputSchedule::new(trigger),
+        }
+    }
+}
+
+impl Default for ConsoleOutputSystem {
+    fn default() -> Self {
+        Self::new(OutputTrigger::EveryNSteps(100))
+    }
+}
+
+/// Writes the number of atoms simulated on the given step.
+fn print_atom_number(
+    step: u64,
+    atom: &ReadStorage<Atom>,
+    weights: &ReadStorage<StatisticalWeight>,
+    super_atoms: &ReadStorage<SuperAtomWeight>,
+) {
+    let atom_number = atom.join().count();
+    let weighted =
+        (atom, weights).join().next().is_some() || (atom, super_atoms).join().next().is_some();
+    if !weighted {
+        println!("Step {}: simulating {} atoms.", step, atom_number);
+    } else {
+        let real_atom_number: f64 = (atom, weights.maybe(), super_atoms.maybe())
+            .join()
+            .map(|(_, weight, super_atom)| total_weight(weight, super_atom))
+            .sum();
+        println!(
+            "Step {}: simulating {} atoms, representing {:e} real atoms.",
+            step, atom_number, real_atom_number
+        );
+    }
+}
 
 impl<'a> System<'a> for ConsoleOutputSystem {
     type SystemData = (
         ReadStorage<'a, Atom>,
+        ReadStorage<'a, StatisticalWeight>,
+        ReadStorage<'a, SuperAtomWeight>,
         ReadExpect<'a, Step>,
         ReadExpect<'a, Timestep>,
     );
-    fn run(&mut self, (atom, step, timestep): Self::SystemData) {
+    fn run(&mut self, (atom, weights, super_atoms, step, timestep): Self::SystemData) {
         let _time = timestep.delta * step.n as f64;
-        if step.n % 100 == 0 {
-            let atom_number = (&atom).join().count();
-            println!("Step {}: simulating {} atoms.", step.n, atom_number);
+        if self.schedule.is_due(step.n) {
+            print_atom_number(step.n, &atom, &weights, &super_atoms);
+        }
+    }
+
+    fn dispose(self, world: &mut World) {
+        if let Some(step) = self.schedule.final_frame() {
+            print_atom_number(
+                step,
+                &world.read_storage::<Atom>(),
+                &world.read_storage::<StatisticalWeight>(),
+                &world.read_storage::<SuperAtomWeight>(),
+            );
         }
     }
 }
@@ -0,0 +1,364 @@
+//! Virtual detectors that record atoms crossing a plane, for time-of-flight and beam measurements.
+//!
+//! A [DetectionPlane] records each atom that crosses it, in either direction, as a [Detection] in the [Detections]
+//! resource. Crossings are detected between steps, and the time and velocity of the crossing are interpolated
+//! linearly between the start and the end of the step, so the arrival times are not limited by the timestep. An atom
+//! that crosses a plane several times, for example one that is launched through the plane and falls back, is
+//! recorded on each crossing.
+//!
+//! The atoms are not affected by the detectors. The [Detections] can be written to a comma-separated file with
+//! [Detections::write_csv].
+
+use std::io::{self, Write as IoWrite};
+
+use crate::atom::{Atom, AtomId, Position, Velocity};
+use crate::integrator::{SimulationTime, Step, Timestep, INTEGRATE_VELOCITY_SYSTEM_NAME};
+use crate::simulation::Plugin;
+use hashbrown::HashMap;
+use nalgebra::Vector3;
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+
+const HEADER: &str = "detector,atom,time,vx,vy,vz";
+
+/// A component that records the atoms crossing a plane, see [crate::output::detection].
+#[derive(Deserialize, Serialize, Clone, Copy, Debug)]
+pub struct DetectionPlane {
+    /// A point in the plane, in SI units of m.
+    pub point: Vector3<f64>,
+    /// The normal to the plane. Crossings along the normal and against it are both recorded.
+    ///
+    /// The normal need not be a unit vector, but must be non-zero and finite; the [DetectPlaneCrossingsSystem]
+    /// panics otherwise.
+    pub normal: Vector3<f64>,
+}
+impl Component for DetectionPlane {
+    type Storage = HashMapStorage<Self>;
+}
+impl DetectionPlane {
+    /// Creates a plane through `point` with the given `normal`, which is normalized.
+    ///
+    /// Panics if the normal is zero or not finite.
+    pub fn new(point: Vector3<f64>, normal: Vector3<f64>) -> Self {
+        let plane = DetectionPlane { point, normal };
+        plane.validate();
+        DetectionPlane {
+            normal: normal.normalize(),
+            ..plane
+        }
+    }
+
+    fn validate(&self) {
+        let norm = self.normal.norm();
+        assert!(
+            norm.is_finite() && norm > 0.0,
+            "The normal of a DetectionPlane must be non-zero and finite, but was {:?}.",
+            self.normal.as_slice()
+        );
+    }
+
+    /// The distance of a point from the plane, along the normal, in SI units of m.
+    fn signed_distance(&self, pos: &Vector3<f64>) -> f64 {
+        (pos - self.point).dot(&self.normal.normalize())
+    }
+}
+
+/// An atom crossing a [DetectionPlane].
+#[derive(Clone, Copy, Debug)]
+pub struct Detection {
+    /// The entity of the [DetectionPlane].
+    pub detector: Entity,
+    /// The entity of the atom.
+    pub atom: Entity,
+    /// The [AtomId] of the atom, if it has one.
+    pub atom_id: Option<u64>,
+    /// The time at which the atom crossed the plane, in SI units of s.
+    pub time: f64,
+    /// The velocity of the atom as it crossed the plane, in SI units of m/s.
+    pub velocity: Vector3<f64>,
+    /// True if the atom crossed the plane along its normal.
+    pub along_normal: bool,
+}
+
+/// A resource holding the [Detection]s of all [DetectionPlane]s, in the order they occurred within each step.
+#[derive(Default)]
+pub struct Detections {
+    /// The detections of all planes, in the order of the steps in which they occurred. The system never removes
+    /// detections, so in long simulations they can be cleared once processed.
+    pub events: Vec<Detection>,
+}
+impl Detections {
+    /// The detections of the given [DetectionPlane].
+    pub fn of(&self, detector: Entity) -> impl Iterator<Item = &Detection> {
+        self.events
+            .iter()
+            .filter(move |detection| detection.detector == detector)
+    }
+
+    /// Writes the detections as comma-separated values, with columns `detector,atom,time,vx,vy,vz`.
+    ///
+    /// The `detector` is the id of the [Entity] of the plane, and the `atom` is the [AtomId] of the atom if it has
+    /// one, or otherwise the id of its [Entity].
+    pub fn write_csv<W: IoWrite>(&self, writer: &mut W) -> Result<(), io::Error> {
+        writeln!(writer, "{}", HEADER)?;
+        for detection in self.events.iter() {
+            let atom = detection
+                .atom_id
+                .unwrap_or_else(|| detection.atom.id() as u64);
+            writeln!(
+                writer,
+                "{},{},{:e},{:e},{:e},{:e}",
+                detection.detector.id(),
+                atom,
+                detection.time,
+                detection.velocity[0],
+                detection.velocity[1],
+                detection.velocity[2]
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Records the atoms that crossed each [DetectionPlane] during the step, in the [Detections] resource.
+///
+/// The system keeps the position and velocity of each atom at the end of the previous step. Atoms created during
+/// the step are first checked on the next step.
+#[derive(Default)]
+pub struct DetectPlaneCrossingsSystem {
+    /// The position and velocity of each atom at the end of the previous step.
+    previous: HashMap<Entity, (Vector3<f64>, Vector3<f64>)>,
+}
+
+impl<'a> System<'a> for DetectPlaneCrossingsSystem {
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, DetectionPlane>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Velocity>,
+        ReadStorage<'a, AtomId>,
+        ReadStorage<'a, Atom>,
+        ReadExpect<'a, Step>,
+        ReadExpect<'a, Timestep>,
+        Write<'a, Detections>,
+    );
+
+    fn run(
+        &mut self,
+        (entities, planes, positions, velocities, ids, atoms, step, timestep, mut detections): Self::SystemData,
+    ) {
+        for plane in (&planes).join() {
+            plane.validate();
+        }
+        let time = SimulationTime::new(&step, &timestep).time;
+        let mut current = HashMap::new();
+        for (atom, pos, vel, id, _) in
+            (&entities, &positions, &velocities, ids.maybe(), &atoms).join()
+        {
+            let (pos, vel) = (pos.pos.cast::<f64>(), vel.vel.cast::<f64>());
+            current.insert(atom, (pos, vel));
+            let (old_pos, old_vel) = match self.previous.get(&atom) {
+                Some(previous) => *previous,
+                None => continue,
+            };
+            for (detector, plane) in (&entities, &planes).join() {
+                let (before, after) =
+                    (plane.signed_distance(&old_pos), plane.signed_distance(&pos));
+                if (before < 0.0) == (after < 0.0) {
+                    continue;
+                }
+                // The fraction of the step at which the atom reached the plane.
+                let fraction = before / (before - after);
+                detections.events.push(Detection {
+                    detector,
+                    atom,
+                    atom_id: id.map(|id| id.id),
+                    time: time - (1.0 - fraction) * timestep.delta,
+                    velocity: old_vel + fraction * (vel - old_vel),
+                    along_normal: after >= 0.0,
+                });
+            }
+        }
+        self.previous = current;
+    }
+}
+
+/// This plugin records the atoms crossing [DetectionPlane]s in the [Detections] resource.
+///
+/// The crossings are detected once the velocities have been integrated, so the end frame systems must be added to
+/// the [SimulationBuilder](crate::simulation::SimulationBuilder) before this plugin.
+///
+/// See also [crate::output::detection].
+pub struct DetectionPlanePlugin;
+impl Plugin for DetectionPlanePlugin {
+    fn build(&self, builder: &mut crate::simulation::SimulationBuilder) {
+        builder.world.register::<DetectionPlane>();
+        builder.world.insert(Detections::default());
+        builder.dispatcher_builder.add(
+            DetectPlaneCrossingsSystem::default(),
+            "detect_plane_crossings",
+            &[INTEGRATE_VELOCITY_SYSTEM_NAME],
+        );
+    }
+    fn deps(&self) -> Vec<Box<dyn Plugin>> {
+        Vec::new()
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use crate::atom::{Force, Mass};
+    use crate::constant::GC;
+    use crate::gravity::ApplyGravityOption;
+    use crate::initiate::NewlyCreated;
+    use crate::simulation::{Simulation, SimulationBuilder};
+    use assert_approx_eq::assert_approx_eq;
+
+    fn simulation(dt: f64) -> Simulation {
+        let mut sim_builder = SimulationBuilder::default();
+        sim_builder.add_end_frame_systems();
+        sim_builder.add_plugin(DetectionPlanePlugin);
+        let mut sim = sim_builder.build();
+        sim.world.insert(Timestep { delta: dt });
+        sim
+    }
+
+    fn add_atom(sim: &mut Simulation, pos: Vector3<f64>, vel: Vector3<f64>, id: u64) -> Entity {
+        sim.world
+            .create_entity()
+            .with(Position { pos: pos.cast() })
+            .with(Velocity { vel: vel.cast() })
+            .with(Force::new())
+            .with(Mass { value: 87.0 })
+            .with(AtomId { id })
+            .with(Atom)
+            .with(NewlyCreated)
+            .build()
+    }
+
+    #[test]
+    #[should_panic(expected = "non-zero and finite")]
+    fn test_zero_normal_is_rejected() {
+        let mut sim = simulation(1.0e-4);
+        sim.world
+            .create_entity()
+            .with(DetectionPlane {
+                point: Vector3::new(0.0, 0.0, 0.0),
+                normal: Vector3::new(0.0, 0.0, 0.0),
+            })
+            .build();
+        sim.step();
+    }
+
+    #[test]
+    fn test_crossing_times_of_free_atoms() {
+        let dt = 1.0e-4;
+        let mut sim = simulation(dt);
+        let normal = Vector3::new(1.0, 1.0, 0.0).normalize();
+        let point = Vector3::new(0.01, 0.0, 0.0);
+        let detector = sim
+            .world
+            .create_entity()
+            .with(DetectionPlane { point, normal })
+            .build();
+
+        // Atoms fly through the plane at different speeds, starting off the grid of steps.
+        let speeds: [f64; 4] = [0.3, 0.7, 1.3, -0.9];
+        for (i, &speed) in speeds.iter().enumerate() {
+            let start = point - speed.signum() * 0.002 * (i + 1) as f64 * normal;
+            add_atom(&mut sim, start, speed * normal, i as u64);
+        }
+        for _ in 0..200 {
+            sim.step();
+        }
+
+        let detections = sim.world.read_resource::<Detections>();
+        assert_eq!(detections.of(detector).count(), speeds.len());
+        for (i, &speed) in speeds.iter().enumerate() {
+            let detection = detections
+                .of(detector)
+                .find(|detection| detection.atom_id == Some(i as u64))
+                .unwrap();
+            // New atoms start moving on the second step, once their old force is known.
+            let expected = dt + 0.002 * (i + 1) as f64 / speed.abs();
+            assert_approx_eq!(detection.time, expected, 1e-12);
+            assert_approx_eq!((detection.velocity - speed * normal).norm(), 0.0, 1e-12);
+            assert_eq!(detection.along_normal, speed > 0.0);
+        }
+
+        let mut csv = Vec::new();
+        detections.write_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert_eq!(csv.lines().next(), Some(HEADER));
+        assert_eq!(csv.lines().count(), speeds.len() + 1);
+    }
+
+    #[test]
+    fn test_recrossing_is_recorded() {
+        let dt = 1.0e-4;
+        let mut sim = simulation(dt);
+        sim.world.insert(ApplyGravityOption);
+        let detector = sim
+            .world
+            .create_entity()
+            .with(DetectionPlane {
+                point: Vector3::new(0.0, 0.0, 0.0),
+                normal: Vector3::z(),
+            })
+            .build();
+
+        // An atom launched upwards through the plane falls back through it.
+        let atom = add_atom(
+            &mut sim,
+            Vector3::new(0.0, 0.0, -1.0e-3),
+            Vector3::new(0.0, 0.0, 0.5),
+            0,
+        );
+        let sample = |sim: &Simulation| {
+            let positions = sim.world.read_storage::<Position>();
+            let velocities = sim.world.read_storage::<Velocity>();
+            (
+                positions.get(atom).unwrap().pos.cast::<f64>()[2],
+                velocities.get(atom).unwrap().vel.cast::<f64>()[2],
+            )
+        };
+        let mut trajectory = vec![sample(&sim)];
+        for _ in 0..1200 {
+            sim.step();
+            trajectory.push(sample(&sim));
+        }
+
+        // Interpolate the crossings of the sampled trajectory by hand.
+        let mut expected = Vec::new();
+        for n in 1..trajectory.len() {
+            let ((z0, v0), (z1, v1)) = (trajectory[n - 1], trajectory[n]);
+            if (z0 < 0.0) != (z1 < 0.0) {
+                let fraction = z0 / (z0 - z1);
+                expected.push(((n as f64 - 1.0 + fraction) * dt, v0 + fraction * (v1 - v0)));
+            }
+        }
+        assert_eq!(expected.len(), 2);
+
+        let detections: Vec<Detection> = sim
+            .world
+            .read_resource::<Detections>()
+            .of(detector)
+            .copied()
+            .collect();
+        assert_eq!(detections.len(), 2);
+        for (detection, (time, velocity)) in detections.iter().zip(expected.iter()) {
+            assert_approx_eq!(detection.time, time, 1e-12);
+            assert_approx_eq!(detection.velocity[2], velocity, 1e-12);
+            assert_eq!(detection.along_normal, *velocity > 0.0);
+        }
+        // The atom rises through the plane, and falls back after 2 v / g, where v is its speed at the plane.
+        assert!(detections[0].along_normal && !detections[1].along_normal);
+        assert_approx_eq!(
+            detections[1].time - detections[0].time,
+            2.0 * detections[0].velocity[2] / GC,
+            2.0 * dt
+        );
+    }
+}
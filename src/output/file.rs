@@ -1,41 +1,83 @@
 //! Writes output files containing atomic trajectories.
+//!
+//! Output files can be compressed with gzip or zstd, see [OutputCompression]. The compression of a [Binary] file is
+//! recorded in its [BinarySchema], and the [BinaryOutputReader] decompresses the file accordingly.
 use crate::atom::Atom;
 use crate::integrator::Step;
+use crate::output::trigger::{OutputSchedule, OutputTrigger};
 use crate::simulation::Plugin;
 use nalgebra::Vector3;
-use specs::{Component, Entities, Entity, Join, ReadExpect, ReadStorage, System};
+use specs::{Component, Entities, Entity, Join, ReadExpect, ReadStorage, System, World, WorldExt};
+use serde::{Deserialize, Serialize};
+use std::any::type_name;
 use std::fmt::Display;
 use std::fs::File;
 use std::io;
+use std::io::BufReader;
 use std::io::BufWriter;
+use std::io::Read;
 use std::io::Write;
 use std::marker::PhantomData;
 use std::path::Path;
+use std::sync::mpsc::{channel, Sender};
+use std::thread::JoinHandle;
 
 extern crate byteorder;
-use byteorder::{LittleEndian, WriteBytesExt};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
 
 /// A system that writes simulation data to file.
 ///
-/// This system writes data `C` of entities associated with `A` to a file at a defined interval.
-/// The data type `C` must be a [Component](specs::Component) and implement the
+/// This system writes data `C` of entities associated with `A` to a file on the steps given by its
+/// [OutputSchedule]. The data type `C` must be a [Component](specs::Component) and implement the
 /// [Clone](struct.Clone.html) trait.
+///
+/// If the schedule writes a final frame, see [OutputSchedule::writes_final_frame], the state at the end of the run
+/// is written when the system is disposed, if the last step was not already written.
 pub struct OutputSystem<C: Component + Clone, W: Write, F: Format<C, W>, A = Atom> {
-    /// Number of integration steps between each file output.
-    interval: u64,
-    /// The [Write](std::io::Write)able output stream.
+    /// Decides on which steps the file is written.
+    schedule: OutputSchedule,
     atom_flag: PhantomData<A>,
+    /// The [Write](std::io::Write)able output stream.
     stream: W,
     formatter: PhantomData<F>,
     marker: PhantomData<C>,
 }
+impl<C, W, F, A> OutputSystem<C, W, F, A>
+where
+    C: Component + Clone,
+    W: Write,
+    F: Format<C, W>,
+{
+    /// Creates a new [OutputSystem], which writes to `stream` on the steps given by `schedule`.
+    pub fn new(stream: W, schedule: OutputSchedule) -> Self {
+        OutputSystem {
+            schedule,
+            atom_flag: PhantomData,
+            stream,
+            formatter: PhantomData,
+            marker: PhantomData,
+        }
+    }
+
+    fn write_frame(&mut self, frame: OutputFrame<C>) {
+        F::write_frame_header(&mut self.stream, frame.step, frame.atom_number)
+            .expect("Could not write.");
+        for (atom, data) in frame.atoms {
+            F::write_atom(&mut self.stream, atom, data).expect("Could not write.");
+        }
+    }
+}
 
 pub struct FileOutputPlugin<C,F,A>
     where C: Component + Clone,
-    F: Format<C, BufWriter<File>>
+    F: Format<C, BufWriter<OutputFile>>
 {
     file_name: String,
-    interval: u64,
+    trigger: OutputTrigger,
+    background: bool,
+    compression: OutputCompression,
     phantom_c: PhantomData<C>,
     phantom_f: PhantomData<F>,
     phantom_a: PhantomData<A>
@@ -44,32 +86,70 @@ impl<C,F,A> FileOutputPlugin<C,F,A>
     where 
         C: Component + Clone,
         A: Component,
-        F: Format<C, BufWriter<File>> 
+        F: Format<C, BufWriter<OutputFile>> 
 {
     pub fn new(file_name: String, interval: u64) -> FileOutputPlugin<C,F,A>
     {
         FileOutputPlugin {
             file_name,
-            interval,
+            trigger: OutputTrigger::EveryNSteps(interval),
+            background: false,
+            compression: OutputCompression::None,
             phantom_a: PhantomData,
             phantom_c: PhantomData,
             phantom_f: PhantomData 
         }
     }
+
+    /// Writes the file on a background thread, see [BackgroundOutputSystem].
+    pub fn on_background_thread(mut self) -> Self {
+        self.background = true;
+        self
+    }
+
+    /// Compresses the file, see [OutputCompression].
+    pub fn with_compression(mut self, compression: OutputCompression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Writes the file on the steps given by the [OutputTrigger], instead of every `interval` steps.
+    pub fn with_trigger(mut self, trigger: OutputTrigger) -> Self {
+        self.trigger = trigger;
+        self
+    }
 }
 
 impl<C,F,A> Plugin for FileOutputPlugin<C,F,A> 
 where 
     C: Component + Clone + Sync + Send + 'static,
     A: Component + Sync + Send + 'static,
-    F: Format<C, BufWriter<File>> + Sync + Send + 'static
+    F: Format<C, BufWriter<OutputFile>> + Sync + Send + 'static
 {
     fn build(&self, builder: &mut crate::simulation::SimulationBuilder) {
-        builder.dispatcher_builder.add(
-            new_with_filter::<C, F, A>(self.file_name.clone(), self.interval),
-            "",
-            &[],
-        );
+        if let Err(why) = F::write_schema(&self.file_name, self.compression) {
+            panic!("couldn't write schema of {}: {}", self.file_name, why);
+        }
+        if self.background {
+            builder.dispatcher_builder.add(
+                BackgroundOutputSystem::<C, F, A>::with_schedule(
+                    create_file(&self.file_name, self.compression),
+                    OutputSchedule::new(self.trigger),
+                ),
+                "",
+                &[],
+            );
+        } else {
+            builder.dispatcher_builder.add(
+                new_with_filter::<C, F, A>(
+                    self.file_name.clone(),
+                    OutputSchedule::new(self.trigger),
+                    self.compression,
+                ),
+                "",
+                &[],
+            );
+        }
     }
     fn deps(&self) -> Vec::<Box<dyn Plugin>> {
         Vec::new()
@@ -79,34 +159,124 @@ where
 /// Creates a new [OutputSystem](struct.OutputSystem.html) to write per-entity [Component](specs::Component) data
 /// according to the specified [Format](struct.Format.html).
 ///
-/// The interval specifies how often, in integration steps, the file should be written.
+/// The schedule specifies on which steps the file should be written.
 ///
 /// Only component data of entities associated with a component given by `A` is written down.
 ///
-/// For example, `new_with_filter::<Position, Text, Atom>("pos.txt", 10).
+/// For example, `new_with_filter::<Position, Text, Atom>("pos.txt", schedule, OutputCompression::None)`.
 fn new_with_filter<C, F, A>(
     file_name: String,
-    interval: u64,
-) -> OutputSystem<C, BufWriter<File>, F, A>
+    schedule: OutputSchedule,
+    compression: OutputCompression,
+) -> OutputSystem<C, BufWriter<OutputFile>, F, A>
 where
     C: Component + Clone,
     A: Component,
-    F: Format<C, BufWriter<File>>,
+    F: Format<C, BufWriter<OutputFile>>,
 {
-    let path = Path::new(&file_name);
+    OutputSystem::new(create_file(&file_name, compression), schedule)
+}
+
+/// Compression of an output file.
+///
+/// Compression substantially reduces the size of long outputs, at some cost in CPU time. The default is no
+/// compression, which writes the same files as before compression was supported.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OutputCompression {
+    /// The file is written uncompressed.
+    None,
+    /// The file is compressed with gzip, at the default level.
+    Gzip,
+    /// The file is compressed with zstd, at the default level.
+    Zstd,
+}
+impl Default for OutputCompression {
+    fn default() -> Self {
+        OutputCompression::None
+    }
+}
+
+/// An output file, which compresses the data written to it according to its [OutputCompression].
+///
+/// The compressed stream is finished when the file is dropped.
+pub enum OutputFile {
+    Uncompressed(File),
+    Gzip(GzEncoder<File>),
+    Zstd(zstd::Encoder<'static, File>),
+}
+impl OutputFile {
+    /// Wraps `file` so that the data written to it is compressed.
+    pub fn new(file: File, compression: OutputCompression) -> Result<Self, io::Error> {
+        Ok(match compression {
+            OutputCompression::None => OutputFile::Uncompressed(file),
+            OutputCompression::Gzip => {
+                OutputFile::Gzip(GzEncoder::new(file, flate2::Compression::default()))
+            }
+            OutputCompression::Zstd => OutputFile::Zstd(zstd::Encoder::new(file, 0)?),
+        })
+    }
+
+    /// Writes the end of the compressed stream. No data may be written afterwards.
+    fn finish(&mut self) -> Result<(), io::Error> {
+        match self {
+            OutputFile::Uncompressed(file) => file.flush(),
+            OutputFile::Gzip(encoder) => encoder.try_finish(),
+            OutputFile::Zstd(encoder) => encoder.do_finish(),
+        }
+    }
+}
+impl Write for OutputFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            OutputFile::Uncompressed(file) => file.write(buf),
+            OutputFile::Gzip(encoder) => encoder.write(buf),
+            OutputFile::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            OutputFile::Uncompressed(file) => file.flush(),
+            OutputFile::Gzip(encoder) => encoder.flush(),
+            OutputFile::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
+impl Drop for OutputFile {
+    fn drop(&mut self) {
+        if let Err(why) = self.finish() {
+            if !std::thread::panicking() {
+                panic!("couldn't finish output file: {}", why);
+            }
+        }
+    }
+}
+
+/// Opens the file at the given path for reading, decompressing it according to the [OutputCompression] it was
+/// written with.
+///
+/// The compression is not detected from the contents of the file, because an uncompressed binary file can start
+/// with the same bytes as a compressed stream. For [Binary] files, it is recorded in the [BinarySchema].
+pub fn open_output_file(
+    file_name: &str,
+    compression: OutputCompression,
+) -> Result<Box<dyn Read + Send>, io::Error> {
+    let file = File::open(file_name)?;
+    Ok(match compression {
+        OutputCompression::None => Box::new(file),
+        OutputCompression::Gzip => Box::new(MultiGzDecoder::new(BufReader::new(file))),
+        OutputCompression::Zstd => Box::new(zstd::Decoder::new(file)?),
+    })
+}
+
+/// Creates the file at the given path, wrapped in a buffered writer.
+fn create_file(file_name: &str, compression: OutputCompression) -> BufWriter<OutputFile> {
+    let path = Path::new(file_name);
     let display = path.display();
-    let file = match File::create(&path) {
+    let file = match File::create(&path).and_then(|file| OutputFile::new(file, compression)) {
         Err(why) => panic!("couldn't open {}: {}", display, why),
         Ok(file) => file,
     };
-    let writer = BufWriter::new(file);
-    OutputSystem {
-        interval,
-        atom_flag: PhantomData,
-        stream: writer,
-        formatter: PhantomData,
-        marker: PhantomData,
-    }
+    BufWriter::new(file)
 }
 
 impl<'a, C, A, W, F> System<'a> for OutputSystem<C, W, F, A>
@@ -124,13 +294,158 @@ where
     );
 
     fn run(&mut self, (entities, data, atom_flags, step): Self::SystemData) {
-        if step.n % self.interval == 0 {
-            let atom_number = (&atom_flags).join().count();
-            F::write_frame_header(&mut self.stream, step.n, atom_number).expect("Could not write.");
+        if self.schedule.is_due(step.n) {
+            self.write_frame(OutputFrame::collect(step.n, &entities, &data, &atom_flags));
+        }
+    }
+
+    fn dispose(mut self, world: &mut World) {
+        if let Some(frame) = OutputFrame::collect_final::<A>(&self.schedule, world) {
+            self.write_frame(frame);
+        }
+    }
+}
+
+/// A frame of output data, written by an [OutputSystem] or sent from a [BackgroundOutputSystem] to its writer
+/// thread.
+struct OutputFrame<C> {
+    step: u64,
+    atom_number: usize,
+    atoms: Vec<(Entity, C)>,
+}
+impl<C: Component + Clone> OutputFrame<C> {
+    /// Copies the data of each entity associated with `A`.
+    fn collect<A: Component>(
+        step: u64,
+        entities: &Entities,
+        data: &ReadStorage<C>,
+        atom_flags: &ReadStorage<A>,
+    ) -> Self {
+        OutputFrame {
+            step,
+            atom_number: atom_flags.join().count(),
+            atoms: (data, atom_flags, entities)
+                .join()
+                .map(|(data, _, ent)| (ent, data.clone()))
+                .collect(),
+        }
+    }
+
+    /// Copies the data at the end of the run, if the `schedule` must still write a final frame.
+    fn collect_final<A: Component>(schedule: &OutputSchedule, world: &World) -> Option<Self> {
+        let step = schedule.final_frame()?;
+        Some(OutputFrame::collect(
+            step,
+            &world.entities(),
+            &world.read_storage::<C>(),
+            &world.read_storage::<A>(),
+        ))
+    }
+}
+
+/// A system that writes simulation data to file on a dedicated background thread.
+///
+/// This behaves as an [OutputSystem], except that the system only clones the data of each frame and sends it
+/// over a channel, so the physics is not blocked on serialization or disk I/O. Frames are queued without limit
+/// if the writer falls behind.
+///
+/// When the system is dropped, for example at the end of the simulation, the remaining frames are written, the
+/// stream is flushed and the writer thread is joined, so no frames are lost. As for the [OutputSystem], the state
+/// at the end of the run is written when the system is disposed if the [OutputSchedule] writes a final frame.
+pub struct BackgroundOutputSystem<C: Component + Clone, F, A = Atom> {
+    /// Decides on which steps the output is written.
+    schedule: OutputSchedule,
+    sender: Option<Sender<OutputFrame<C>>>,
+    writer_thread: Option<JoinHandle<()>>,
+    atom_flag: PhantomData<A>,
+    formatter: PhantomData<F>,
+}
+impl<C, F, A> BackgroundOutputSystem<C, F, A>
+where
+    C: Component + Clone + Send + 'static,
+{
+    /// Creates a new [BackgroundOutputSystem], which writes to `writer` on a new thread.
+    ///
+    /// The interval specifies how often, in integration steps, the output should be written.
+    pub fn new<W>(writer: W, interval: u64) -> Self
+    where
+        W: Write + Send + 'static,
+        F: Format<C, W> + 'static,
+    {
+        Self::with_schedule(writer, OutputSchedule::new(OutputTrigger::EveryNSteps(interval)))
+    }
+
+    /// Creates a new [BackgroundOutputSystem], which writes to `writer` on a new thread on the steps given by
+    /// `schedule`.
+    pub fn with_schedule<W>(writer: W, schedule: OutputSchedule) -> Self
+    where
+        W: Write + Send + 'static,
+        F: Format<C, W> + 'static,
+    {
+        let (sender, receiver) = channel::<OutputFrame<C>>();
+        let writer_thread = std::thread::spawn(move || {
+            let mut writer = writer;
+            for frame in receiver {
+                F::write_frame_header(&mut writer, frame.step, frame.atom_number)
+                    .expect("Could not write.");
+                for (atom, data) in frame.atoms {
+                    F::write_atom(&mut writer, atom, data).expect("Could not write.");
+                }
+            }
+            writer.flush().expect("Could not flush.");
+        });
+        BackgroundOutputSystem {
+            schedule,
+            sender: Some(sender),
+            writer_thread: Some(writer_thread),
+            atom_flag: PhantomData,
+            formatter: PhantomData,
+        }
+    }
+}
+
+impl<'a, C, F, A> System<'a> for BackgroundOutputSystem<C, F, A>
+where
+    C: Component + Clone,
+    A: Component,
+{
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, C>,
+        ReadStorage<'a, A>,
+        ReadExpect<'a, Step>,
+    );
+
+    fn run(&mut self, (entities, data, atom_flags, step): Self::SystemData) {
+        if self.schedule.is_due(step.n) {
+            self.send(OutputFrame::collect(step.n, &entities, &data, &atom_flags));
+        }
+    }
+
+    fn dispose(self, world: &mut World) {
+        if let Some(frame) = OutputFrame::collect_final::<A>(&self.schedule, world) {
+            self.send(frame);
+        }
+    }
+}
+
+impl<C: Component + Clone, F, A> BackgroundOutputSystem<C, F, A> {
+    fn send(&self, frame: OutputFrame<C>) {
+        self.sender
+            .as_ref()
+            .unwrap()
+            .send(frame)
+            .expect("Output writer thread has stopped.");
+    }
+}
 
-            // write each entity
-            for (data, _, ent) in (&data, &atom_flags, &entities).join() {
-                F::write_atom(&mut self.stream, ent, data.clone()).expect("Could not write.");
+impl<C: Component + Clone, F, A> Drop for BackgroundOutputSystem<C, F, A> {
+    fn drop(&mut self) {
+        // Closing the channel ends the writer thread once all frames are written.
+        self.sender.take();
+        if let Some(writer_thread) = self.writer_thread.take() {
+            if writer_thread.join().is_err() && !std::thread::panicking() {
+                panic!("Output writer thread panicked.");
             }
         }
     }
@@ -146,6 +461,11 @@ where
     fn write_frame_header(writer: &mut W, step: u64, atom_number: usize) -> Result<(), io::Error>;
     /// Writes data associated with an atom.
     fn write_atom(writer: &mut W, atom: Entity, data: C) -> Result<(), io::Error>;
+    /// Writes a file describing the layout of the output file `file_name`, and the compression it is written
+    /// with, if the format needs one.
+    fn write_schema(_file_name: &str, _compression: OutputCompression) -> Result<(), io::Error> {
+        Ok(())
+    }
 }
 
 /// Prints files in a [Format](struct.Format.html) that is human readable.
@@ -228,8 +548,94 @@ type Endianness = LittleEndian;
 
 pub trait BinaryConversion {
     fn data(&self) -> Vec<f64>;
+    /// The names of the elements of [BinaryConversion::data], in order.
+    ///
+    /// The names are written to the [BinarySchema] of a [Binary] file. By default the elements are not named, and no
+    /// schema is written, so the file cannot be read by a [BinaryOutputReader].
+    fn field_names() -> Vec<&'static str>
+    where
+        Self: Sized,
+    {
+        Vec::new()
+    }
+}
+
+/// A named value in a [BinarySchema], and its data type.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SchemaField {
+    pub name: String,
+    /// The data type, one of `u64`, `u32`, `i32`, `f64` or `f32`.
+    pub dtype: String,
+}
+impl SchemaField {
+    fn new(name: &str, dtype: &str) -> Self {
+        SchemaField {
+            name: name.to_string(),
+            dtype: dtype.to_string(),
+        }
+    }
+}
+
+/// The data written for each atom by a component in a [Binary] file.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SchemaComponent {
+    /// The name of the component type.
+    pub name: String,
+    /// The values of the component, in the order they are written.
+    pub fields: Vec<SchemaField>,
+}
+
+/// Describes the layout of a [Binary] output file, see [Binary].
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct BinarySchema {
+    /// Byte order of all values, `little` or `big`.
+    pub endianness: String,
+    /// The values written at the start of each frame.
+    pub frame_header: Vec<SchemaField>,
+    /// The values written before the component data of each atom.
+    pub atom_header: Vec<SchemaField>,
+    /// The components written for each atom, in order.
+    pub components: Vec<SchemaComponent>,
+    /// The compression of the file. Schemas written before compression was supported describe uncompressed files.
+    #[serde(default)]
+    pub compression: OutputCompression,
 }
+impl BinarySchema {
+    /// The schema of a [Binary] file of the component `C`.
+    pub fn of<C: BinaryConversion>() -> Self {
+        BinarySchema {
+            endianness: "little".to_string(),
+            frame_header: vec![
+                SchemaField::new("step", "u64"),
+                SchemaField::new("atom_number", "u64"),
+            ],
+            atom_header: vec![
+                SchemaField::new("generation", "i32"),
+                SchemaField::new("id", "u32"),
+            ],
+            components: vec![SchemaComponent {
+                name: type_name::<C>().rsplit("::").next().unwrap().to_string(),
+                fields: C::field_names()
+                    .iter()
+                    .map(|name| SchemaField::new(name, "f64"))
+                    .collect(),
+            }],
+            compression: OutputCompression::None,
+        }
+    }
 
+    /// The path of the schema describing the binary file `file_name`.
+    pub fn path(file_name: &str) -> String {
+        format!("{}.schema.json", file_name)
+    }
+}
+
+/// Writes files in a compact binary [Format].
+///
+/// Each frame begins with the step number and the atom number, followed by the generation and id of each atom and
+/// the elements of its [BinaryConversion::data]. The layout is written alongside the output to a JSON
+/// [BinarySchema], in the file `<file_name>.schema.json`, which is read by the [BinaryOutputReader]. The schema is
+/// only written for components that name their elements, see [BinaryConversion::field_names].
 pub struct Binary {}
 impl<C, W> Format<C, W> for Binary
 where
@@ -250,4 +656,474 @@ where
         }
         Ok(())
     }
+
+    fn write_schema(file_name: &str, compression: OutputCompression) -> Result<(), io::Error> {
+        if C::field_names().is_empty() {
+            // The layout of unnamed elements is unknown, so remove any schema left by an earlier run.
+            return match std::fs::remove_file(BinarySchema::path(file_name)) {
+                Err(why) if why.kind() != io::ErrorKind::NotFound => Err(why),
+                _ => Ok(()),
+            };
+        }
+        let writer = BufWriter::new(File::create(BinarySchema::path(file_name))?);
+        let schema = BinarySchema {
+            compression,
+            ..BinarySchema::of::<C>()
+        };
+        serde_json::to_writer_pretty(writer, &schema)?;
+        Ok(())
+    }
+}
+
+/// The data of an atom in a [BinaryFrame].
+#[derive(Clone, Debug, PartialEq)]
+pub struct BinaryAtom {
+    /// The [Entity](specs::Entity) generation of the atom.
+    pub generation: i32,
+    /// The [Entity](specs::Entity) id of the atom.
+    pub id: u32,
+    /// The values of the components of the atom, in the order of the [BinarySchema::components].
+    pub data: Vec<f64>,
+}
+
+/// A frame read from a [Binary] file by a [BinaryOutputReader].
+#[derive(Clone, Debug, PartialEq)]
+pub struct BinaryFrame {
+    pub step: u64,
+    pub atoms: Vec<BinaryAtom>,
+}
+
+/// Reads the frames of a [Binary] output file, using the layout given by its [BinarySchema].
+pub struct BinaryOutputReader<R: Read> {
+    pub schema: BinarySchema,
+    reader: R,
+}
+impl BinaryOutputReader<BufReader<Box<dyn Read + Send>>> {
+    /// Opens the binary file `file_name`, and reads its schema from `<file_name>.schema.json`.
+    ///
+    /// Compressed files are decompressed according to [BinarySchema::compression], see [open_output_file].
+    pub fn open(file_name: &str) -> Result<Self, io::Error> {
+        let schema: BinarySchema = serde_json::from_reader(BufReader::new(File::open(
+            BinarySchema::path(file_name),
+        )?))?;
+        let file = open_output_file(file_name, schema.compression)?;
+        BinaryOutputReader::new(schema, BufReader::new(file))
+    }
+}
+impl<R: Read> BinaryOutputReader<R> {
+    /// Creates a reader of the binary data in `reader`, with the layout given by `schema`.
+    pub fn new(schema: BinarySchema, reader: R) -> Result<Self, io::Error> {
+        if schema.endianness != "little" {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Unsupported endianness {}.", schema.endianness),
+            ));
+        }
+        Ok(BinaryOutputReader { schema, reader })
+    }
+
+    fn read_value(&mut self, field: &SchemaField) -> Result<f64, io::Error> {
+        Ok(match field.dtype.as_str() {
+            "u64" => self.reader.read_u64::<Endianness>()? as f64,
+            "u32" => self.reader.read_u32::<Endianness>()? as f64,
+            "i32" => self.reader.read_i32::<Endianness>()? as f64,
+            "f64" => self.reader.read_f64::<Endianness>()?,
+            "f32" => self.reader.read_f32::<Endianness>()? as f64,
+            dtype => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Unsupported data type {} of {}.", dtype, field.name),
+                ))
+            }
+        })
+    }
+
+    /// Reads the named values of a header.
+    fn read_header(&mut self, fields: &[SchemaField]) -> Result<Vec<(String, f64)>, io::Error> {
+        fields
+            .iter()
+            .map(|field| Ok((field.name.clone(), self.read_value(field)?)))
+            .collect()
+    }
+
+    /// Reads the next frame, or returns `None` at the end of the file.
+    pub fn next_frame(&mut self) -> Result<Option<BinaryFrame>, io::Error> {
+        let frame_header = self.schema.frame_header.clone();
+        let header = match self.read_header(&frame_header) {
+            Ok(header) => header,
+            Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(error) => return Err(error),
+        };
+        let find = |header: &[(String, f64)], name: &str| {
+            header
+                .iter()
+                .find(|(field, _)| field == name)
+                .map(|(_, value)| *value)
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("The schema has no {} field.", name),
+                    )
+                })
+        };
+        let step = find(&header, "step")? as u64;
+        let atom_number = find(&header, "atom_number")? as usize;
+
+        let atom_header = self.schema.atom_header.clone();
+        let fields: Vec<SchemaField> = self
+            .schema
+            .components
+            .iter()
+            .flat_map(|component| component.fields.iter().cloned())
+            .collect();
+        let mut atoms = Vec::with_capacity(atom_number);
+        for _ in 0..atom_number {
+            let header = self.read_header(&atom_header)?;
+            let data = fields
+                .iter()
+                .map(|field| self.read_value(field))
+                .collect::<Result<Vec<f64>, io::Error>>()?;
+            atoms.push(BinaryAtom {
+                generation: find(&header, "generation")? as i32,
+                id: find(&header, "id")? as u32,
+                data,
+            });
+        }
+        Ok(Some(BinaryFrame { step, atoms }))
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use crate::atom::Position;
+    use specs::{Builder, RunNow, World, WorldExt};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    /// A writer that is slow to write, and which shares its contents.
+    #[derive(Clone, Default)]
+    struct SlowWriter {
+        contents: Arc<Mutex<Vec<u8>>>,
+    }
+    impl Write for SlowWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            std::thread::sleep(Duration::from_micros(200));
+            self.contents.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_binary_schema_describes_position_and_velocity_output() {
+        use crate::atom::{Force, Mass, Velocity};
+        use crate::integrator::Timestep;
+        use crate::simulation::SimulationBuilder;
+
+        let directory = std::env::temp_dir();
+        let position_file = directory
+            .join("atomecs_test_binary_position.bin")
+            .to_str()
+            .unwrap()
+            .to_string();
+        let velocity_file = directory
+            .join("atomecs_test_binary_velocity.bin")
+            .to_str()
+            .unwrap()
+            .to_string();
+
+        let mut sim_builder = SimulationBuilder::default();
+        sim_builder.add_plugin(FileOutputPlugin::<Position, Binary, Atom>::new(
+            position_file.clone(),
+            2,
+        ));
+        sim_builder.add_plugin(FileOutputPlugin::<Velocity, Binary, Atom>::new(
+            velocity_file.clone(),
+            2,
+        ));
+        let mut sim = sim_builder.build();
+        sim.world.insert(Timestep { delta: 1.0e-3 });
+        for i in 0..3 {
+            sim.world
+                .create_entity()
+                .with(Position {
+                    pos: Vector3::new(i as f64, 0.0, 0.0).cast(),
+                })
+                .with(Velocity {
+                    vel: Vector3::new(0.0, 0.0, 1.0).cast(),
+                })
+                .with(Force::new())
+                .with(Mass { value: 87.0 })
+                .with(Atom)
+                .build();
+        }
+        for _ in 0..6 {
+            sim.step();
+        }
+        // Dropping the simulation flushes the output.
+        drop(sim);
+
+        let mut positions = BinaryOutputReader::open(&position_file).unwrap();
+        let mut velocities = BinaryOutputReader::open(&velocity_file).unwrap();
+        assert_eq!(positions.schema, BinarySchema::of::<Position>());
+        assert_eq!(positions.schema.components[0].name, "Position");
+        let names: Vec<&str> = velocities.schema.components[0]
+            .fields
+            .iter()
+            .map(|field| field.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["vx", "vy", "vz"]);
+
+        let mut steps = Vec::new();
+        while let Some(frame) = positions.next_frame().unwrap() {
+            let velocity_frame = velocities.next_frame().unwrap().unwrap();
+            assert_eq!(frame.step, velocity_frame.step);
+            assert_eq!(frame.atoms.len(), 3);
+            for (atom, velocity) in frame.atoms.iter().zip(velocity_frame.atoms.iter()) {
+                assert_eq!(atom.id, velocity.id);
+                assert_eq!(atom.data.len(), 3);
+                assert_eq!(velocity.data, vec![0.0, 0.0, 1.0]);
+            }
+            steps.push(frame.step);
+        }
+        assert_eq!(steps, vec![2, 4, 6]);
+        assert!(velocities.next_frame().unwrap().is_none());
+
+        for file in [&position_file, &velocity_file].iter() {
+            std::fs::remove_file(file).ok();
+            std::fs::remove_file(BinarySchema::path(file)).ok();
+        }
+    }
+
+    #[test]
+    fn test_compressed_binary_output_reads_back_identically() {
+        use crate::atom::{Force, Mass, Velocity};
+        use crate::integrator::Timestep;
+        use crate::simulation::SimulationBuilder;
+
+        let compressions = [
+            OutputCompression::None,
+            OutputCompression::Gzip,
+            OutputCompression::Zstd,
+        ];
+        let files: Vec<String> = compressions
+            .iter()
+            .map(|compression| {
+                std::env::temp_dir()
+                    .join(format!("atomecs_test_compressed_{:?}.bin", compression))
+                    .to_str()
+                    .unwrap()
+                    .to_string()
+            })
+            .collect();
+
+        let mut sim_builder = SimulationBuilder::default();
+        for (file, compression) in files.iter().zip(compressions.iter()) {
+            let plugin = FileOutputPlugin::<Position, Binary, Atom>::new(file.clone(), 1)
+                .with_compression(*compression);
+            sim_builder.add_plugin(plugin);
+        }
+        // The compression of background outputs is finished when the writer thread ends.
+        sim_builder.add_plugin(
+            FileOutputPlugin::<Position, Binary, Atom>::new(
+                std::env::temp_dir()
+                    .join("atomecs_test_compressed_background.bin")
+                    .to_str()
+                    .unwrap()
+                    .to_string(),
+                1,
+            )
+            .with_compression(OutputCompression::Zstd)
+            .on_background_thread(),
+        );
+        let mut sim = sim_builder.build();
+        sim.world.insert(Timestep { delta: 1.0e-3 });
+        for i in 0..100 {
+            sim.world
+                .create_entity()
+                .with(Position {
+                    pos: Vector3::new(i as f64 * 1.0e-3, 0.0, 0.0).cast(),
+                })
+                .with(Velocity {
+                    vel: Vector3::new(0.1, -0.2, 0.3).cast(),
+                })
+                .with(Force::new())
+                .with(Mass { value: 87.0 })
+                .with(Atom)
+                .build();
+        }
+        for _ in 0..20 {
+            sim.step();
+        }
+        drop(sim);
+
+        let read_all = |file: &str| {
+            let mut reader = BinaryOutputReader::open(file).unwrap();
+            let mut frames = Vec::new();
+            while let Some(frame) = reader.next_frame().unwrap() {
+                frames.push(frame);
+            }
+            frames
+        };
+        let uncompressed = read_all(&files[0]);
+        assert_eq!(uncompressed.len(), 20);
+        assert_eq!(uncompressed[0].atoms.len(), 100);
+        let uncompressed_size = std::fs::metadata(&files[0]).unwrap().len();
+        let background = std::env::temp_dir()
+            .join("atomecs_test_compressed_background.bin")
+            .to_str()
+            .unwrap()
+            .to_string();
+        for file in files[1..].iter().chain(std::iter::once(&background)) {
+            assert_eq!(read_all(file), uncompressed);
+            assert!(std::fs::metadata(file).unwrap().len() < uncompressed_size);
+        }
+
+        for file in files.iter().chain(std::iter::once(&background)) {
+            std::fs::remove_file(file).ok();
+            std::fs::remove_file(BinarySchema::path(file)).ok();
+        }
+    }
+
+    /// An uncompressed file whose first step starts with the bytes of the gzip magic number is read as uncompressed.
+    #[test]
+    fn test_uncompressed_binary_output_starting_like_gzip() {
+        let file_name = std::env::temp_dir()
+            .join("atomecs_test_gzip_like_step.bin")
+            .to_str()
+            .unwrap()
+            .to_string();
+        let step = 0x8b1f;
+        {
+            type BinaryFile = BufWriter<OutputFile>;
+            let mut writer = create_file(&file_name, OutputCompression::None);
+            <Binary as Format<Position, BinaryFile>>::write_schema(&file_name, OutputCompression::None)
+                .unwrap();
+            <Binary as Format<Position, BinaryFile>>::write_frame_header(&mut writer, step, 0).unwrap();
+        }
+        assert_eq!(&std::fs::read(&file_name).unwrap()[..2], &[0x1f, 0x8b]);
+
+        let mut reader = BinaryOutputReader::open(&file_name).unwrap();
+        assert_eq!(reader.schema.compression, OutputCompression::None);
+        let frame = reader.next_frame().unwrap().unwrap();
+        assert_eq!(frame.step, step);
+        assert!(frame.atoms.is_empty());
+        assert!(reader.next_frame().unwrap().is_none());
+
+        std::fs::remove_file(&file_name).ok();
+        std::fs::remove_file(BinarySchema::path(&file_name)).ok();
+    }
+
+    /// A component that implements only the required methods of [BinaryConversion].
+    #[derive(Clone)]
+    struct Unnamed;
+    impl Component for Unnamed {
+        type Storage = specs::VecStorage<Self>;
+    }
+    impl BinaryConversion for Unnamed {
+        fn data(&self) -> Vec<f64> {
+            vec![1.0, 2.0]
+        }
+    }
+
+    /// No schema is written for components whose elements are not named, and a stale schema is removed.
+    #[test]
+    fn test_unnamed_fields_write_no_schema() {
+        let file_name = std::env::temp_dir()
+            .join("atomecs_test_unnamed_fields.bin")
+            .to_str()
+            .unwrap()
+            .to_string();
+        std::fs::write(BinarySchema::path(&file_name), "{}").unwrap();
+        <Binary as Format<Unnamed, Vec<u8>>>::write_schema(&file_name, OutputCompression::None).unwrap();
+        assert!(!Path::new(&BinarySchema::path(&file_name)).exists());
+        assert!(BinaryOutputReader::open(&file_name).is_err());
+    }
+
+    #[test]
+    fn test_background_writer_writes_all_frames() {
+        let mut test_world = World::new();
+        test_world.register::<Position>();
+        test_world.register::<Atom>();
+        for i in 0..5 {
+            test_world
+                .create_entity()
+                .with(Position {
+                    pos: Vector3::new(i as f64, 0.0, 0.0).cast(),
+                })
+                .with(Atom)
+                .build();
+        }
+
+        let writer = SlowWriter::default();
+        let steps = 50;
+        let mut system =
+            BackgroundOutputSystem::<Position, Text, Atom>::new(writer.clone(), 1);
+        for n in 0..steps {
+            test_world.insert(Step { n });
+            system.run_now(&test_world);
+        }
+        drop(system);
+
+        let contents = String::from_utf8(writer.contents.lock().unwrap().clone()).unwrap();
+        let headers: Vec<&str> = contents
+            .lines()
+            .filter(|line| line.starts_with("step-"))
+            .collect();
+        assert_eq!(headers.len(), steps as usize);
+        assert_eq!(headers[steps as usize - 1], format!("step-{}, 5", steps - 1));
+        assert_eq!(contents.lines().count(), 6 * steps as usize);
+    }
+
+    #[test]
+    fn test_wall_clock_trigger_writes_final_frame() {
+        use crate::output::trigger::tests::MockClock;
+
+        let mut test_world = World::new();
+        test_world.register::<Position>();
+        test_world.register::<Atom>();
+        test_world
+            .create_entity()
+            .with(Position::new())
+            .with(Atom)
+            .build();
+
+        let clock = MockClock::default();
+        let trigger = OutputTrigger::EveryWallClockSeconds(2.0);
+        let writer = SlowWriter::default();
+        let background_writer = SlowWriter::default();
+        let mut system = OutputSystem::<Position, _, Text, Atom>::new(
+            writer.clone(),
+            OutputSchedule::with_clock(trigger, Box::new(clock.clone())),
+        );
+        let mut background = BackgroundOutputSystem::<Position, Text, Atom>::with_schedule(
+            background_writer.clone(),
+            OutputSchedule::with_clock(trigger, Box::new(clock.clone())),
+        );
+        // Each step takes 0.7 s of wall-clock time.
+        for n in 1..=9 {
+            test_world.insert(Step { n });
+            system.run_now(&test_world);
+            background.run_now(&test_world);
+            clock.advance(0.7);
+        }
+        System::dispose(system, &mut test_world);
+        System::dispose(background, &mut test_world);
+
+        for writer in [writer, background_writer].iter() {
+            let contents = String::from_utf8(writer.contents.lock().unwrap().clone()).unwrap();
+            let headers: Vec<&str> = contents
+                .lines()
+                .filter(|line| line.starts_with("step-"))
+                .collect();
+            assert_eq!(
+                headers,
+                vec!["step-1, 1", "step-4, 1", "step-7, 1", "step-9, 1"]
+            );
+        }
+    }
 }
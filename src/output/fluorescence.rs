@@ -0,0 +1,297 @@
+//! Writes simulated fluorescence images of the atom cloud.
+//!
+//! Each atom contributes to the image in proportion to its photon scattering rate, so that atoms which do not
+//! scatter light, including atoms in a [Dark](crate::laser_cooling::repump::Dark) state, are invisible. The
+//! positions of the atoms are projected onto the image plane of a [FluorescenceCamera], and blurred by a Gaussian
+//! point-spread function, which models the finite resolution of the imaging system. The images can be compared
+//! directly to fluorescence pictures taken in experiments.
+//!
+//! The [FluorescenceImageSystem] writes one image per output step, as a line `step,<n>` followed by one
+//! comma-separated line per row of pixels. Pixel values are the photon scattering rate imaged onto the pixel, in
+//! photons/s, before the collection efficiency of the imaging system.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::marker::PhantomData;
+
+use crate::atom::Position;
+use crate::constant::PI;
+use crate::integrator::{Step, Timestep, INTEGRATE_POSITION_SYSTEM_NAME};
+use crate::laser_cooling::photons_scattered::TotalPhotonsScattered;
+use crate::laser_cooling::repump::Dark;
+use crate::laser_cooling::transition::TransitionComponent;
+use crate::simulation::Plugin;
+use nalgebra::Vector3;
+use specs::prelude::*;
+
+/// The geometry and resolution of a simulated fluorescence camera.
+#[derive(Clone, Copy, Debug)]
+pub struct FluorescenceCamera {
+    /// Point imaged onto the center of the image, in SI units of m.
+    pub center: Vector3<f64>,
+    /// Direction in the object plane along the rows of the image, ie of increasing column index.
+    pub horizontal: Vector3<f64>,
+    /// Direction in the object plane along the columns of the image, ie of increasing row index.
+    pub vertical: Vector3<f64>,
+    /// Size of a pixel in the object plane, in SI units of m.
+    pub pixel_size: f64,
+    /// Number of pixels along each row.
+    pub width: usize,
+    /// Number of pixels along each column.
+    pub height: usize,
+    /// Standard deviation of the Gaussian point-spread function in the object plane, in SI units of m.
+    pub psf_width: f64,
+}
+impl FluorescenceCamera {
+    /// The position in the object plane of the center of the pixel in `row` and `column`, relative to `center`.
+    fn pixel_center(&self, row: usize, column: usize) -> (f64, f64) {
+        (
+            (column as f64 + 0.5 - 0.5 * self.width as f64) * self.pixel_size,
+            (row as f64 + 0.5 - 0.5 * self.height as f64) * self.pixel_size,
+        )
+    }
+
+    /// Calculates the fluorescence image of a collection of atoms.
+    ///
+    /// Returns the image as a vector of rows, each holding the photon scattering rate imaged onto each pixel.
+    ///
+    /// # Arguments
+    ///
+    /// `atoms`: positions (in m) and photon scattering rates (in Hz) of the atoms.
+    pub fn image(&self, atoms: &[(Vector3<f64>, f64)]) -> Vec<Vec<f64>> {
+        let horizontal = self.horizontal.normalize();
+        let vertical = self.vertical.normalize();
+        let sigma = self.psf_width;
+        let density = |x: f64| (-x.powi(2) / (2.0 * sigma.powi(2))).exp() / ((2.0 * PI).sqrt() * sigma);
+
+        let mut image = vec![vec![0.0; self.width]; self.height];
+        for (pos, rate) in atoms.iter() {
+            if *rate <= 0.0 {
+                continue;
+            }
+            let displacement = pos - self.center;
+            let (x, y) = (displacement.dot(&horizontal), displacement.dot(&vertical));
+            for (row, pixels) in image.iter_mut().enumerate() {
+                for (column, pixel) in pixels.iter_mut().enumerate() {
+                    let (px, py) = self.pixel_center(row, column);
+                    *pixel += rate * density(px - x) * density(py - y) * self.pixel_size.powi(2);
+                }
+            }
+        }
+        image
+    }
+}
+
+/// A system that writes fluorescence images of the atoms with transition `T` at a defined interval.
+///
+/// The scattering rate of each atom is calculated from its [TotalPhotonsScattered] this step.
+pub struct FluorescenceImageSystem<T, W>
+where
+    T: TransitionComponent,
+    W: Write,
+{
+    /// The camera used to image the atoms.
+    camera: FluorescenceCamera,
+    /// Number of integration steps between each image.
+    interval: u64,
+    /// The [Write](std::io::Write)able output stream.
+    stream: W,
+    phantom: PhantomData<T>,
+}
+impl<T, W> FluorescenceImageSystem<T, W>
+where
+    T: TransitionComponent,
+    W: Write,
+{
+    pub fn new(camera: FluorescenceCamera, stream: W, interval: u64) -> Self {
+        FluorescenceImageSystem {
+            camera,
+            interval,
+            stream,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<'a, T, W> System<'a> for FluorescenceImageSystem<T, W>
+where
+    T: TransitionComponent,
+    W: Write,
+{
+    type SystemData = (
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, TotalPhotonsScattered<T>>,
+        ReadStorage<'a, Dark>,
+        ReadExpect<'a, Step>,
+        ReadExpect<'a, Timestep>,
+    );
+
+    fn run(&mut self, (positions, scattered, dark, step, timestep): Self::SystemData) {
+        if step.n % self.interval != 0 {
+            return;
+        }
+        let atoms: Vec<(Vector3<f64>, f64)> = (&positions, &scattered, !&dark)
+            .join()
+            .filter(|(_, scattered, _)| scattered.total.is_finite())
+            .map(|(pos, scattered, _)| (pos.pos.cast::<f64>(), scattered.total / timestep.delta))
+            .collect();
+        writeln!(self.stream, "step,{}", step.n).expect("Could not write.");
+        for row in self.camera.image(&atoms) {
+            let values: Vec<String> = row.iter().map(|value| format!("{:e}", value)).collect();
+            writeln!(self.stream, "{}", values.join(",")).expect("Could not write.");
+        }
+    }
+}
+
+/// This plugin writes fluorescence images of the atoms with transition `T` to a file.
+///
+/// The plugin must be added after the [LaserCoolingPlugin](crate::laser_cooling::LaserCoolingPlugin) for `T`.
+///
+/// See also [crate::output::fluorescence].
+pub struct FluorescenceImagePlugin<T>
+where
+    T: TransitionComponent,
+{
+    camera: FluorescenceCamera,
+    file_name: String,
+    interval: u64,
+    phantom: PhantomData<T>,
+}
+impl<T> FluorescenceImagePlugin<T>
+where
+    T: TransitionComponent,
+{
+    /// Writes images taken with `camera` to `file_name` every `interval` integration steps.
+    pub fn new(camera: FluorescenceCamera, file_name: String, interval: u64) -> Self {
+        FluorescenceImagePlugin {
+            camera,
+            file_name,
+            interval,
+            phantom: PhantomData,
+        }
+    }
+}
+impl<T> Plugin for FluorescenceImagePlugin<T>
+where
+    T: TransitionComponent,
+{
+    fn build(&self, builder: &mut crate::simulation::SimulationBuilder) {
+        let file = match File::create(&self.file_name) {
+            Err(why) => panic!("couldn't open {}: {}", self.file_name, why),
+            Ok(file) => file,
+        };
+        builder.dispatcher_builder.add(
+            FluorescenceImageSystem::<T, _>::new(self.camera, BufWriter::new(file), self.interval),
+            "fluorescence_image",
+            &[INTEGRATE_POSITION_SYSTEM_NAME, "calculate_total_photons"],
+        );
+    }
+    fn deps(&self) -> Vec<Box<dyn Plugin>> {
+        Vec::new()
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use assert_approx_eq::assert_approx_eq;
+
+    fn camera() -> FluorescenceCamera {
+        FluorescenceCamera {
+            center: Vector3::new(0.0, 0.0, 0.0),
+            horizontal: Vector3::x(),
+            vertical: Vector3::z(),
+            pixel_size: 5.0e-6,
+            width: 80,
+            height: 60,
+            psf_width: 20.0e-6,
+        }
+    }
+
+    #[test]
+    fn test_single_atom_images_to_gaussian_spot() {
+        let camera = camera();
+        let (position, rate) = (Vector3::new(30.0e-6, 1.0e-3, -20.0e-6), 1.0e7);
+        let image = camera.image(&[(position, rate)]);
+        assert_eq!(image.len(), camera.height);
+        assert!(image.iter().all(|row| row.len() == camera.width));
+
+        let mut total = 0.0;
+        let mut mean = (0.0, 0.0);
+        for (row, pixels) in image.iter().enumerate() {
+            for (column, pixel) in pixels.iter().enumerate() {
+                let (x, y) = camera.pixel_center(row, column);
+                total += pixel;
+                mean = (mean.0 + pixel * x, mean.1 + pixel * y);
+            }
+        }
+        mean = (mean.0 / total, mean.1 / total);
+        let mut variance = (0.0, 0.0);
+        for (row, pixels) in image.iter().enumerate() {
+            for (column, pixel) in pixels.iter().enumerate() {
+                let (x, y) = camera.pixel_center(row, column);
+                variance = (
+                    variance.0 + pixel * (x - mean.0).powi(2),
+                    variance.1 + pixel * (y - mean.1).powi(2),
+                );
+            }
+        }
+        variance = (variance.0 / total, variance.1 / total);
+
+        // The spot is centered on the projected position, and all of the light falls within the image.
+        assert_approx_eq!(total, rate, rate * 1e-3);
+        assert_approx_eq!(mean.0, position[0], 1e-8);
+        assert_approx_eq!(mean.1, position[2], 1e-8);
+        assert_approx_eq!(variance.0.sqrt(), camera.psf_width, camera.psf_width * 1e-2);
+        assert_approx_eq!(variance.1.sqrt(), camera.psf_width, camera.psf_width * 1e-2);
+    }
+
+    #[test]
+    fn test_dark_atoms_are_invisible() {
+        let camera = camera();
+        let image = camera.image(&[(Vector3::new(0.0, 0.0, 0.0), 0.0)]);
+        assert!(image.iter().flatten().all(|pixel| *pixel == 0.0));
+    }
+
+    /// Tests that atoms in a `Dark` state are not imaged, even though their `TotalPhotonsScattered` is filled.
+    #[test]
+    fn test_system_skips_atoms_in_dark_state() {
+        use crate::species::Rubidium87_780D2;
+
+        let mut test_world = World::new();
+        test_world.register::<Position>();
+        test_world.register::<TotalPhotonsScattered<Rubidium87_780D2>>();
+        test_world.register::<Dark>();
+        let time_delta = 1.0e-6;
+        test_world.insert(Step { n: 0 });
+        test_world.insert(Timestep { delta: time_delta });
+
+        let scattered = 1.0;
+        for dark in [false, true].iter() {
+            let mut total = TotalPhotonsScattered::<Rubidium87_780D2>::default();
+            total.total = scattered;
+            let builder = test_world
+                .create_entity()
+                .with(Position { pos: Vector3::new(0.0, 0.0, 0.0) })
+                .with(total);
+            if *dark {
+                builder.with(Dark).build();
+            } else {
+                builder.build();
+            }
+        }
+
+        let mut system = FluorescenceImageSystem::<Rubidium87_780D2, _>::new(camera(), Vec::new(), 1);
+        system.run_now(&test_world);
+        let output = String::from_utf8(system.stream).expect("Output is not valid UTF-8.");
+        let total: f64 = output
+            .lines()
+            .skip(1)
+            .flat_map(|line| line.split(','))
+            .map(|value| value.parse::<f64>().expect("Could not parse pixel."))
+            .sum();
+        let rate = scattered / time_delta;
+        assert_approx_eq!(total, rate, rate * 1e-3);
+    }
+}
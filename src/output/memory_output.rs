@@ -2,11 +2,12 @@
 
 use crate::atom::*;
 use crate::integrator::Step;
+use crate::output::trigger::OutputSchedule;
 use specs::{Component, Entities, Join, ReadExpect, ReadStorage, System};
 
 /// A system that stores atomic trajectories in memory.
 ///
-/// This system stores per-atom data `T` at defined intervals.
+/// This system stores per-atom data `T` every `interval` steps, or on the steps given by an [OutputSchedule].
 /// The data type `T` must be a [Component](specs::Component), and
 /// implement the Clone trait.
 ///
@@ -18,10 +19,14 @@ use specs::{Component, Entities, Join, ReadExpect, ReadStorage, System};
 ///
 /// A better alternative is to use the [FileOutputSystem](crate::output::file_output::FileOutputSystem).
 pub struct MemoryOutputSystem<T: Component + Clone> {
-    /// The [FileOutputSystem](struct.FileOutputSystem.html) writes to file every time
-    /// this number of steps are completed.
+    /// The data is stored every time this number of steps are completed.
+    ///
+    /// Ignored if the system was created with [MemoryOutputSystem::with_schedule].
     pub interval: u64,
 
+    /// Decides on which steps the data is stored, if not every `interval` steps.
+    schedule: Option<OutputSchedule>,
+
     /// Data stored in the file output system.
     payload: Vec<Vec<T>>,
 }
@@ -30,9 +35,20 @@ impl<T> MemoryOutputSystem<T>
 where
     T: Component + Clone,
 {
+    /// Creates a [MemoryOutputSystem] that stores the data every `interval` steps.
     pub fn new(interval: u64) -> Self {
         MemoryOutputSystem {
             interval,
+            schedule: None,
+            payload: Vec::new(),
+        }
+    }
+
+    /// Creates a [MemoryOutputSystem] that stores the data on the steps given by the [OutputSchedule].
+    pub fn with_schedule(schedule: OutputSchedule) -> Self {
+        MemoryOutputSystem {
+            interval: 0,
+            schedule: Some(schedule),
             payload: Vec::new(),
         }
     }
@@ -50,7 +66,11 @@ where
     );
 
     fn run(&mut self, (entities, data, atoms, step): Self::SystemData) {
-        if step.n % self.interval == 0 {
+        let due = match self.schedule.as_mut() {
+            Some(schedule) => schedule.is_due(step.n),
+            None => step.n % self.interval == 0,
+        };
+        if due {
             // Lump the atom vector into memory.
             let mut vec = Vec::new();
             for (data, _, _) in (&data, &atoms, &entities).join() {
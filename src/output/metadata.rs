@@ -0,0 +1,238 @@
+//! Writes a metadata file describing the configuration of a run, so that archived output is self-describing.
+//!
+//! The metadata is written as JSON when the first simulation step runs, rather than when the simulation is built,
+//! so that it includes any resources or entities added between building the simulation and running it.
+
+use std::any::type_name;
+use std::fs::File;
+use std::io::BufWriter;
+use std::marker::PhantomData;
+
+use crate::integrator::Timestep;
+use crate::laser_cooling::transition::AtomicTransition;
+use crate::magnetic::quadrupole::QuadrupoleField3D;
+use crate::query::{list_beams, BeamType};
+use crate::rng::DeterministicRng;
+use crate::simulation::Plugin;
+use serde::Serialize;
+use specs::prelude::*;
+use specs::storage::MaskedStorage;
+
+/// The parameters of a laser beam, as written to the run metadata.
+#[derive(Serialize, Clone, Debug)]
+pub struct BeamMetadata {
+    /// Either `Cooling` or `Dipole`.
+    pub beam_type: String,
+    /// The index of the beam, or `None` if not yet assigned.
+    pub index: Option<usize>,
+    /// The type of the beam profile, for example `GaussianBeam`.
+    pub profile: Option<String>,
+    /// Power of the beam, in SI units of W, if the profile states it.
+    pub power: Option<f64>,
+    /// The 1/e radius of a Gaussian beam at the focus, in SI units of m.
+    pub e_radius: Option<f64>,
+    /// Wavelength of the beam, in SI units of m.
+    pub wavelength: f64,
+    pub direction: Option<[f64; 3]>,
+    /// Detuning of a cooling beam from the transition, in units of MHz.
+    pub detuning: Option<f64>,
+}
+
+/// Describes the configuration of a run, see [crate::output::metadata].
+#[derive(Serialize, Clone, Debug)]
+pub struct RunMetadata {
+    /// Version of the AtomECS crate.
+    pub crate_version: String,
+    /// The laser cooling transition of the simulated species.
+    pub species: String,
+    /// Integration timestep, in SI units of s.
+    pub timestep: Option<f64>,
+    /// The seed of the [DeterministicRng] of the run, if it has one.
+    pub seed: Option<u64>,
+    pub beams: Vec<BeamMetadata>,
+    /// Gradients of the quadrupole fields, in units of T/m.
+    pub quadrupole_gradients: Vec<f64>,
+}
+impl RunMetadata {
+    /// Collects the metadata of a run from the world.
+    ///
+    /// # Generic Arguments
+    ///
+    /// * `T`: The laser cooling transition, used for the species name and the detuning of cooling beams.
+    pub fn from_world<T>(world: &World) -> Self
+    where
+        T: AtomicTransition,
+    {
+        let beams = list_beams::<T>(world)
+            .iter()
+            .map(|beam| BeamMetadata {
+                beam_type: match beam.beam_type {
+                    BeamType::Cooling => "Cooling".to_string(),
+                    BeamType::Dipole => "Dipole".to_string(),
+                },
+                index: beam.index,
+                profile: beam.profile.map(str::to_string),
+                power: beam.power,
+                e_radius: beam.e_radius,
+                wavelength: beam.wavelength,
+                direction: beam
+                    .direction
+                    .map(|direction| [direction[0], direction[1], direction[2]]),
+                detuning: beam.detuning,
+            })
+            .collect();
+        let quadrupole_gradients = if world.has_value::<MaskedStorage<QuadrupoleField3D>>() {
+            (&world.read_storage::<QuadrupoleField3D>())
+                .join()
+                .map(|quadrupole| quadrupole.gradient)
+                .collect()
+        } else {
+            Vec::new()
+        };
+        RunMetadata {
+            crate_version: env!("CARGO_PKG_VERSION").to_string(),
+            species: type_name::<T>().rsplit("::").next().unwrap().to_string(),
+            timestep: world.try_fetch::<Timestep>().map(|timestep| timestep.delta),
+            seed: world
+                .try_fetch::<DeterministicRng>()
+                .and_then(|rng| rng.seed()),
+            beams,
+            quadrupole_gradients,
+        }
+    }
+}
+
+/// Writes the [RunMetadata] to file when the first simulation step runs.
+///
+/// This system requires direct access to the `World`, and so must be added to the dispatcher as a thread-local system.
+pub struct WriteRunMetadataSystem<T> {
+    file_name: String,
+    written: bool,
+    phantom: PhantomData<T>,
+}
+impl<T> WriteRunMetadataSystem<T> {
+    pub fn new(file_name: String) -> Self {
+        WriteRunMetadataSystem {
+            file_name,
+            written: false,
+            phantom: PhantomData,
+        }
+    }
+}
+impl<'a, T> RunNow<'a> for WriteRunMetadataSystem<T>
+where
+    T: AtomicTransition,
+{
+    fn run_now(&mut self, world: &'a World) {
+        if self.written {
+            return;
+        }
+        let metadata = RunMetadata::from_world::<T>(world);
+        let file = match File::create(&self.file_name) {
+            Err(why) => panic!("couldn't open {}: {}", self.file_name, why),
+            Ok(file) => file,
+        };
+        serde_json::to_writer_pretty(BufWriter::new(file), &metadata)
+            .expect("Could not write run metadata.");
+        self.written = true;
+    }
+
+    fn setup(&mut self, _world: &mut World) {}
+}
+
+/// This plugin writes a [RunMetadata] file, `run_metadata.json` by default, when the simulation starts.
+///
+/// See also [crate::output::metadata].
+///
+/// # Generic Arguments
+///
+/// * `T`: The laser cooling transition of the simulated species.
+pub struct RunMetadataPlugin<T> {
+    file_name: String,
+    phantom: PhantomData<T>,
+}
+impl<T> RunMetadataPlugin<T> {
+    pub fn new(file_name: String) -> Self {
+        RunMetadataPlugin {
+            file_name,
+            phantom: PhantomData,
+        }
+    }
+}
+impl<T> Default for RunMetadataPlugin<T> {
+    fn default() -> Self {
+        RunMetadataPlugin::new("run_metadata.json".to_string())
+    }
+}
+impl<T> Plugin for RunMetadataPlugin<T>
+where
+    T: AtomicTransition + Send + Sync + 'static,
+{
+    fn build(&self, builder: &mut crate::simulation::SimulationBuilder) {
+        builder
+            .dispatcher_builder
+            .add_thread_local(WriteRunMetadataSystem::<T>::new(self.file_name.clone()));
+    }
+    fn deps(&self) -> Vec<Box<dyn Plugin>> {
+        Vec::new()
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use crate::laser::gaussian::GaussianBeam;
+    use crate::laser::LaserPlugin;
+    use crate::laser_cooling::{CoolingLight, LaserCoolingPlugin};
+    use crate::simulation::SimulationBuilder;
+    use crate::species::Rubidium87_780D2;
+    use nalgebra::Vector3;
+
+    #[test]
+    fn test_metadata_file_records_configuration() {
+        let file_name = std::env::temp_dir()
+            .join("atomecs_test_run_metadata.json")
+            .to_str()
+            .unwrap()
+            .to_string();
+        let _ = std::fs::remove_file(&file_name);
+
+        let mut sim_builder = SimulationBuilder::default();
+        sim_builder.add_plugin(LaserPlugin::<4>);
+        sim_builder.add_plugin(LaserCoolingPlugin::<Rubidium87_780D2, 4>::default());
+        sim_builder.add_plugin(RunMetadataPlugin::<Rubidium87_780D2>::new(
+            file_name.clone(),
+        ));
+        let mut sim = sim_builder.build();
+
+        // Configured after the simulation is built, but before the first step.
+        sim.world.insert(Timestep { delta: 2.5e-6 });
+        sim.world.insert(DeterministicRng::new(42));
+        for direction in [Vector3::x(), -Vector3::x(), Vector3::y()].iter() {
+            sim.world
+                .create_entity()
+                .with(GaussianBeam {
+                    intersection: Vector3::new(0.0, 0.0, 0.0),
+                    e_radius: 0.01,
+                    power: 0.01,
+                    direction: *direction,
+                    rayleigh_range: f64::INFINITY,
+                    ellipticity: 0.0,
+                    focus_offset: 0.0,
+                })
+                .with(CoolingLight::for_transition::<Rubidium87_780D2>(-12.0, 1))
+                .build();
+        }
+        sim.step();
+
+        let contents = std::fs::read_to_string(&file_name).unwrap();
+        let metadata: serde_json::Value = serde_json::from_str(&contents).unwrap();
+        assert_eq!(metadata["timestep"], 2.5e-6);
+        assert_eq!(metadata["seed"], 42);
+        assert_eq!(metadata["beams"].as_array().unwrap().len(), 3);
+        assert_eq!(metadata["species"], "Rubidium87_780D2");
+        assert_eq!(metadata["crate_version"], env!("CARGO_PKG_VERSION"));
+        std::fs::remove_file(&file_name).unwrap();
+    }
+}
@@ -1,5 +1,15 @@
 //! Create output from the simulation, such as atomic trajectories.
 
+pub mod autocorrelation;
+pub mod cloud_geometry;
 pub mod console_output;
+pub mod detection;
 pub mod file;
+pub mod fluorescence;
 pub mod memory_output;
+pub mod metadata;
+pub mod npy;
+pub mod snapshot;
+pub mod statistics;
+pub mod tracer;
+pub mod trigger;
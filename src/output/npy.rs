@@ -0,0 +1,561 @@
+//! Writes atomic trajectories in the NumPy `.npy` and `.npz` formats, which can be loaded directly using `np.load`.
+//!
+//! Two layouts are supported, which differ in how they handle the number of atoms changing during the simulation:
+//!  * [NpyOutputSystem] writes a single `.npy` array of shape `(frames, capacity, n)`, where `n` is the length of
+//!    the per-atom [BinaryConversion::data]. Frames with fewer than `capacity` atoms are padded with `NaN`.
+//!  * [NpzOutputSystem] writes a `.npz` archive containing one array of shape `(atoms, n)` per frame, named `step_<n>`.
+//!
+//! Arrays are written as little-endian `f64`. The file is updated after each frame, so it can be loaded while the
+//! simulation is still running. Atoms are written in order of entity id, so an atom keeps the same index across
+//! frames as long as no atoms are deleted.
+//!
+//! Frames are written on the steps given by an [OutputTrigger], see [crate::output::trigger].
+
+use crate::atom::Atom;
+use crate::integrator::Step;
+use crate::output::file::BinaryConversion;
+use crate::output::trigger::{OutputSchedule, OutputTrigger};
+use crate::simulation::Plugin;
+use specs::{Component, Join, ReadExpect, ReadStorage, System, World, WorldExt};
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::marker::PhantomData;
+
+extern crate byteorder;
+use byteorder::{LittleEndian, WriteBytesExt};
+
+/// Number of bytes reserved for the header of a `.npy` file written by the [NpyOutputSystem].
+const NPY_HEADER_LENGTH: usize = 128;
+
+/// Creates the header of a `.npy` file describing a little-endian `f64` array with the given shape.
+///
+/// The header is padded with spaces to a multiple of 64 bytes, and to at least `min_length` bytes.
+pub fn npy_header(shape: &[usize], min_length: usize) -> Vec<u8> {
+    let shape = match shape.len() {
+        1 => format!("({},)", shape[0]),
+        _ => format!(
+            "({})",
+            shape
+                .iter()
+                .map(|n| n.to_string())
+                .collect::<Vec<String>>()
+                .join(", ")
+        ),
+    };
+    let mut dict = format!(
+        "{{'descr': '<f8', 'fortran_order': False, 'shape': {}, }}",
+        shape
+    );
+
+    // magic string (6), version (2), header length (2), dictionary, newline (1).
+    let unpadded = 10 + dict.len() + 1;
+    let length = unpadded.max(min_length).div_ceil(64) * 64;
+    dict.push_str(&" ".repeat(length - unpadded));
+    dict.push('\n');
+
+    let mut header = b"\x93NUMPY\x01\x00".to_vec();
+    header.extend_from_slice(&(dict.len() as u16).to_le_bytes());
+    header.extend_from_slice(dict.as_bytes());
+    header
+}
+
+/// A system that writes per-atom data to a single `.npy` array of shape `(frames, capacity, n)`.
+///
+/// See [crate::output::npy].
+pub struct NpyOutputSystem<C, W, A = Atom>
+where
+    C: Component + Clone + Default + BinaryConversion,
+    W: Write + Seek,
+{
+    /// Decides on which steps a frame is written.
+    schedule: OutputSchedule,
+    /// Maximum number of atoms in each frame.
+    capacity: usize,
+    /// Number of elements of per-atom data.
+    width: usize,
+    /// Number of frames written so far.
+    frames: usize,
+    stream: W,
+    marker: PhantomData<C>,
+    atom_flag: PhantomData<A>,
+}
+impl<C, W, A> NpyOutputSystem<C, W, A>
+where
+    C: Component + Clone + Default + BinaryConversion,
+    W: Write + Seek,
+{
+    /// Creates a new [NpyOutputSystem] that writes to `stream` every `interval` steps, with space for `capacity`
+    /// atoms in each frame.
+    pub fn new(stream: W, interval: u64, capacity: usize) -> Self {
+        Self::with_schedule(stream, OutputSchedule::new(OutputTrigger::EveryNSteps(interval)), capacity)
+    }
+
+    /// Creates a new [NpyOutputSystem] that writes to `stream` on the steps given by the [OutputSchedule].
+    pub fn with_schedule(mut stream: W, schedule: OutputSchedule, capacity: usize) -> Self {
+        let width = C::default().data().len();
+        stream
+            .write_all(&npy_header(&[0, capacity, width], NPY_HEADER_LENGTH))
+            .expect("Could not write.");
+        NpyOutputSystem {
+            schedule,
+            capacity,
+            width,
+            frames: 0,
+            stream,
+            marker: PhantomData,
+            atom_flag: PhantomData,
+        }
+    }
+
+    fn write_frame(&mut self, rows: Vec<Vec<f64>>) -> Result<(), io::Error> {
+        if rows.len() > self.capacity {
+            panic!(
+                "Number of atoms {} exceeds the capacity {} of the .npy output. Increase the capacity, or use the .npz output.",
+                rows.len(),
+                self.capacity
+            );
+        }
+        for row in rows.iter() {
+            for element in row {
+                self.stream.write_f64::<LittleEndian>(*element)?;
+            }
+        }
+        for _ in 0..(self.capacity - rows.len()) * self.width {
+            self.stream.write_f64::<LittleEndian>(f64::NAN)?;
+        }
+        self.frames += 1;
+
+        // Update the shape in the header to include the new frame.
+        let header = npy_header(&[self.frames, self.capacity, self.width], NPY_HEADER_LENGTH);
+        if header.len() != NPY_HEADER_LENGTH {
+            return Err(io::Error::other(
+                "Array shape too large for the .npy header.",
+            ));
+        }
+        self.stream.seek(SeekFrom::Start(0))?;
+        self.stream.write_all(&header)?;
+        self.stream.seek(SeekFrom::End(0))?;
+        self.stream.flush()
+    }
+}
+
+impl<'a, C, W, A> System<'a> for NpyOutputSystem<C, W, A>
+where
+    C: Component + Clone + Default + BinaryConversion,
+    W: Write + Seek,
+    A: Component,
+{
+    type SystemData = (
+        ReadStorage<'a, C>,
+        ReadStorage<'a, A>,
+        ReadExpect<'a, Step>,
+    );
+
+    fn run(&mut self, (data, atom_flags, step): Self::SystemData) {
+        if self.schedule.is_due(step.n) {
+            self.write_frame(collect_rows(&data, &atom_flags))
+                .expect("Could not write.");
+        }
+    }
+
+    fn dispose(mut self, world: &mut World) {
+        if self.schedule.final_frame().is_some() {
+            let rows = collect_rows(&world.read_storage::<C>(), &world.read_storage::<A>());
+            self.write_frame(rows).expect("Could not write.");
+        }
+    }
+}
+
+/// The per-atom data of each entity associated with `A`, in order of entity id.
+fn collect_rows<C, A>(data: &ReadStorage<C>, atom_flags: &ReadStorage<A>) -> Vec<Vec<f64>>
+where
+    C: Component + BinaryConversion,
+    A: Component,
+{
+    (data, atom_flags)
+        .join()
+        .map(|(data, _)| data.data())
+        .collect()
+}
+
+/// An array stored in a `.npz` archive.
+struct NpzEntry {
+    name: String,
+    crc: u32,
+    size: u32,
+    offset: u32,
+}
+
+/// A system that writes per-atom data to a `.npz` archive, with one array of shape `(atoms, n)` per frame.
+///
+/// The arrays are named `step_<n>`, where `n` is the step number. The archive is an uncompressed zip file,
+/// and so is limited to 65535 frames and 4GB in size.
+///
+/// See [crate::output::npy].
+pub struct NpzOutputSystem<C, W, A = Atom>
+where
+    C: Component + Clone + BinaryConversion,
+    W: Write + Seek,
+{
+    /// Decides on which steps a frame is written.
+    schedule: OutputSchedule,
+    entries: Vec<NpzEntry>,
+    /// Position in the stream at which the next array is written.
+    end_of_entries: u64,
+    stream: W,
+    marker: PhantomData<C>,
+    atom_flag: PhantomData<A>,
+}
+impl<C, W, A> NpzOutputSystem<C, W, A>
+where
+    C: Component + Clone + BinaryConversion,
+    W: Write + Seek,
+{
+    /// Creates a new [NpzOutputSystem] that writes to `stream` every `interval` steps.
+    pub fn new(stream: W, interval: u64) -> Self {
+        Self::with_schedule(stream, OutputSchedule::new(OutputTrigger::EveryNSteps(interval)))
+    }
+
+    /// Creates a new [NpzOutputSystem] that writes to `stream` on the steps given by the [OutputSchedule].
+    pub fn with_schedule(stream: W, schedule: OutputSchedule) -> Self {
+        NpzOutputSystem {
+            schedule,
+            entries: Vec::new(),
+            end_of_entries: 0,
+            stream,
+            marker: PhantomData,
+            atom_flag: PhantomData,
+        }
+    }
+
+    fn write_frame(&mut self, step: u64, rows: Vec<Vec<f64>>) -> Result<(), io::Error> {
+        let width = rows.first().map_or(0, |row| row.len());
+        let mut array = npy_header(&[rows.len(), width], 0);
+        for element in rows.iter().flatten() {
+            array.write_f64::<LittleEndian>(*element)?;
+        }
+
+        if self.entries.len() >= u16::MAX as usize || self.end_of_entries > u32::MAX as u64 {
+            return Err(io::Error::other("Too much data for the .npz output."));
+        }
+        let entry = NpzEntry {
+            name: format!("step_{}.npy", step),
+            crc: crc32(&array),
+            size: array.len() as u32,
+            offset: self.end_of_entries as u32,
+        };
+
+        // Overwrite the previous central directory with the new array.
+        self.stream.seek(SeekFrom::Start(self.end_of_entries))?;
+        self.stream.write_u32::<LittleEndian>(0x04034b50)?;
+        write_zip_entry_fields(&mut self.stream, &entry)?;
+        self.stream.write_u16::<LittleEndian>(0)?; // extra field length
+        self.stream.write_all(entry.name.as_bytes())?;
+        self.stream.write_all(&array)?;
+        self.end_of_entries += 30 + entry.name.len() as u64 + array.len() as u64;
+        self.entries.push(entry);
+
+        self.write_central_directory()?;
+        self.stream.flush()
+    }
+
+    fn write_central_directory(&mut self) -> Result<(), io::Error> {
+        let mut size = 0;
+        for entry in self.entries.iter() {
+            self.stream.write_u32::<LittleEndian>(0x02014b50)?;
+            self.stream.write_u16::<LittleEndian>(20)?; // version made by
+            write_zip_entry_fields(&mut self.stream, entry)?;
+            self.stream.write_u16::<LittleEndian>(0)?; // extra field length
+            self.stream.write_u16::<LittleEndian>(0)?; // comment length
+            self.stream.write_u16::<LittleEndian>(0)?; // disk number
+            self.stream.write_u16::<LittleEndian>(0)?; // internal attributes
+            self.stream.write_u32::<LittleEndian>(0)?; // external attributes
+            self.stream.write_u32::<LittleEndian>(entry.offset)?;
+            self.stream.write_all(entry.name.as_bytes())?;
+            size += 46 + entry.name.len() as u32;
+        }
+
+        // End of central directory record.
+        self.stream.write_u32::<LittleEndian>(0x06054b50)?;
+        self.stream.write_u16::<LittleEndian>(0)?; // disk number
+        self.stream.write_u16::<LittleEndian>(0)?; // disk with central directory
+        self.stream
+            .write_u16::<LittleEndian>(self.entries.len() as u16)?;
+        self.stream
+            .write_u16::<LittleEndian>(self.entries.len() as u16)?;
+        self.stream.write_u32::<LittleEndian>(size)?;
+        self.stream
+            .write_u32::<LittleEndian>(self.end_of_entries as u32)?;
+        self.stream.write_u16::<LittleEndian>(0)?; // comment length
+        Ok(())
+    }
+}
+
+impl<'a, C, W, A> System<'a> for NpzOutputSystem<C, W, A>
+where
+    C: Component + Clone + BinaryConversion,
+    W: Write + Seek,
+    A: Component,
+{
+    type SystemData = (
+        ReadStorage<'a, C>,
+        ReadStorage<'a, A>,
+        ReadExpect<'a, Step>,
+    );
+
+    fn run(&mut self, (data, atom_flags, step): Self::SystemData) {
+        if self.schedule.is_due(step.n) {
+            self.write_frame(step.n, collect_rows(&data, &atom_flags))
+                .expect("Could not write.");
+        }
+    }
+
+    fn dispose(mut self, world: &mut World) {
+        if let Some(step) = self.schedule.final_frame() {
+            let rows = collect_rows(&world.read_storage::<C>(), &world.read_storage::<A>());
+            self.write_frame(step, rows).expect("Could not write.");
+        }
+    }
+}
+
+/// Writes the fields shared by the local file header and central directory header of an uncompressed zip entry.
+fn write_zip_entry_fields<W: Write>(writer: &mut W, entry: &NpzEntry) -> Result<(), io::Error> {
+    writer.write_u16::<LittleEndian>(20)?; // version needed to extract
+    writer.write_u16::<LittleEndian>(0)?; // flags
+    writer.write_u16::<LittleEndian>(0)?; // compression method: stored
+    writer.write_u16::<LittleEndian>(0)?; // modification time
+    writer.write_u16::<LittleEndian>(0x21)?; // modification date, 1980-01-01
+    writer.write_u32::<LittleEndian>(entry.crc)?;
+    writer.write_u32::<LittleEndian>(entry.size)?; // compressed size
+    writer.write_u32::<LittleEndian>(entry.size)?; // uncompressed size
+    writer.write_u16::<LittleEndian>(entry.name.len() as u16)?;
+    Ok(())
+}
+
+/// Calculates the CRC-32 checksum used by zip archives.
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (!(crc & 1)).wrapping_add(1);
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+fn create_file(file_name: &str) -> BufWriter<File> {
+    match File::create(file_name) {
+        Err(why) => panic!("couldn't open {}: {}", file_name, why),
+        Ok(file) => BufWriter::new(file),
+    }
+}
+
+/// This plugin writes per-atom data `C` to a `.npy` file, see [NpyOutputSystem].
+pub struct NpyOutputPlugin<C, A = Atom> {
+    file_name: String,
+    trigger: OutputTrigger,
+    capacity: usize,
+    phantom_c: PhantomData<C>,
+    phantom_a: PhantomData<A>,
+}
+impl<C, A> NpyOutputPlugin<C, A> {
+    pub fn new(file_name: String, interval: u64, capacity: usize) -> Self {
+        NpyOutputPlugin {
+            file_name,
+            trigger: OutputTrigger::EveryNSteps(interval),
+            capacity,
+            phantom_c: PhantomData,
+            phantom_a: PhantomData,
+        }
+    }
+
+    /// Writes the file on the steps given by the [OutputTrigger], instead of every `interval` steps.
+    pub fn with_trigger(mut self, trigger: OutputTrigger) -> Self {
+        self.trigger = trigger;
+        self
+    }
+}
+impl<C, A> Plugin for NpyOutputPlugin<C, A>
+where
+    C: Component + Clone + Default + BinaryConversion + Sync + Send + 'static,
+    A: Component + Sync + Send + 'static,
+{
+    fn build(&self, builder: &mut crate::simulation::SimulationBuilder) {
+        builder.dispatcher_builder.add(
+            NpyOutputSystem::<C, BufWriter<File>, A>::with_schedule(
+                create_file(&self.file_name),
+                OutputSchedule::new(self.trigger),
+                self.capacity,
+            ),
+            "",
+            &[],
+        );
+    }
+    fn deps(&self) -> Vec<Box<dyn Plugin>> {
+        Vec::new()
+    }
+}
+
+/// This plugin writes per-atom data `C` to a `.npz` file, see [NpzOutputSystem].
+pub struct NpzOutputPlugin<C, A = Atom> {
+    file_name: String,
+    trigger: OutputTrigger,
+    phantom_c: PhantomData<C>,
+    phantom_a: PhantomData<A>,
+}
+impl<C, A> NpzOutputPlugin<C, A> {
+    pub fn new(file_name: String, interval: u64) -> Self {
+        NpzOutputPlugin {
+            file_name,
+            trigger: OutputTrigger::EveryNSteps(interval),
+            phantom_c: PhantomData,
+            phantom_a: PhantomData,
+        }
+    }
+
+    /// Writes the file on the steps given by the [OutputTrigger], instead of every `interval` steps.
+    pub fn with_trigger(mut self, trigger: OutputTrigger) -> Self {
+        self.trigger = trigger;
+        self
+    }
+}
+impl<C, A> Plugin for NpzOutputPlugin<C, A>
+where
+    C: Component + Clone + BinaryConversion + Sync + Send + 'static,
+    A: Component + Sync + Send + 'static,
+{
+    fn build(&self, builder: &mut crate::simulation::SimulationBuilder) {
+        builder.dispatcher_builder.add(
+            NpzOutputSystem::<C, BufWriter<File>, A>::with_schedule(
+                create_file(&self.file_name),
+                OutputSchedule::new(self.trigger),
+            ),
+            "",
+            &[],
+        );
+    }
+    fn deps(&self) -> Vec<Box<dyn Plugin>> {
+        Vec::new()
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use crate::atom::Position;
+    use nalgebra::Vector3;
+    use specs::prelude::*;
+    use std::io::Cursor;
+
+    /// Returns the header dictionary of a `.npy` array, and the offset at which the data starts.
+    fn parse_npy_header(bytes: &[u8]) -> (String, usize) {
+        assert_eq!(&bytes[0..8], b"\x93NUMPY\x01\x00");
+        let length = u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+        assert_eq!((10 + length) % 64, 0);
+        let dict = String::from_utf8(bytes[10..10 + length].to_vec()).unwrap();
+        assert!(dict.ends_with('\n'));
+        (dict.trim().to_string(), 10 + length)
+    }
+
+    fn read_f64(bytes: &[u8], offset: usize) -> f64 {
+        let mut buffer = [0u8; 8];
+        buffer.copy_from_slice(&bytes[offset..offset + 8]);
+        f64::from_le_bytes(buffer)
+    }
+
+    fn create_world() -> World {
+        let mut world = World::new();
+        world.register::<Position>();
+        world.register::<Atom>();
+        world.insert(Step { n: 0 });
+        world
+    }
+
+    fn add_atom(world: &mut World, pos: Vector3<f64>) {
+        world
+            .create_entity()
+            .with(Position { pos: pos.cast() })
+            .with(Atom)
+            .build();
+    }
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b"123456789"), 0xCBF43926);
+    }
+
+    #[test]
+    fn test_npy_output_shape_and_dtype() {
+        let mut world = create_world();
+        add_atom(&mut world, Vector3::new(1.0, 2.0, 3.0));
+        add_atom(&mut world, Vector3::new(4.0, 5.0, 6.0));
+
+        let mut system =
+            NpyOutputSystem::<Position, Cursor<Vec<u8>>>::new(Cursor::new(Vec::new()), 1, 4);
+        for n in 1..=3 {
+            world.insert(Step { n });
+            system.run_now(&world);
+        }
+
+        let bytes = system.stream.into_inner();
+        let (dict, offset) = parse_npy_header(&bytes);
+        assert_eq!(offset, NPY_HEADER_LENGTH);
+        assert_eq!(
+            dict,
+            "{'descr': '<f8', 'fortran_order': False, 'shape': (3, 4, 3), }"
+        );
+        assert_eq!(bytes.len() - offset, 3 * 4 * 3 * 8);
+        assert_eq!(read_f64(&bytes, offset), 1.0);
+        assert_eq!(read_f64(&bytes, offset + 5 * 8), 6.0);
+        // The frame is padded to the capacity.
+        assert!(read_f64(&bytes, offset + 6 * 8).is_nan());
+    }
+
+    #[test]
+    fn test_npz_output_with_variable_atom_number() {
+        let mut world = create_world();
+        add_atom(&mut world, Vector3::new(1.0, 2.0, 3.0));
+
+        let mut system =
+            NpzOutputSystem::<Position, Cursor<Vec<u8>>>::new(Cursor::new(Vec::new()), 1);
+        world.insert(Step { n: 1 });
+        system.run_now(&world);
+        add_atom(&mut world, Vector3::new(4.0, 5.0, 6.0));
+        world.insert(Step { n: 2 });
+        system.run_now(&world);
+
+        let bytes = system.stream.into_inner();
+
+        // End of central directory record lists both arrays.
+        let eocd = bytes.len() - 22;
+        assert_eq!(&bytes[eocd..eocd + 4], &0x06054b50u32.to_le_bytes());
+        assert_eq!(u16::from_le_bytes([bytes[eocd + 10], bytes[eocd + 11]]), 2);
+
+        // The first array has shape (1, 3).
+        assert_eq!(&bytes[0..4], &0x04034b50u32.to_le_bytes());
+        let name_length = u16::from_le_bytes([bytes[26], bytes[27]]) as usize;
+        assert_eq!(&bytes[30..30 + name_length], b"step_1.npy");
+        let array = &bytes[30 + name_length..];
+        let (dict, offset) = parse_npy_header(array);
+        assert_eq!(
+            dict,
+            "{'descr': '<f8', 'fortran_order': False, 'shape': (1, 3), }"
+        );
+        assert_eq!(read_f64(array, offset + 2 * 8), 3.0);
+        let size = u32::from_le_bytes([bytes[22], bytes[23], bytes[24], bytes[25]]) as usize;
+        assert_eq!(
+            crc32(&array[0..size]),
+            u32::from_le_bytes([bytes[14], bytes[15], bytes[16], bytes[17]])
+        );
+
+        // The second array has shape (2, 3).
+        let second = &bytes[30 + name_length + size..];
+        let name_length = u16::from_le_bytes([second[26], second[27]]) as usize;
+        assert_eq!(&second[30..30 + name_length], b"step_2.npy");
+        let (dict, _) = parse_npy_header(&second[30 + name_length..]);
+        assert!(dict.contains("'shape': (2, 3)"));
+    }
+}
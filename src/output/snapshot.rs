@@ -0,0 +1,217 @@
+//! Writes the complete state of all atoms to file in a single call, for example to inspect the state of a
+//! simulation at a specific moment.
+//!
+//! Unlike the streaming output systems, a snapshot is not added to the dispatcher. [snapshot] reads the world
+//! directly, so it can be called between steps or from a [step callback](crate::callbacks).
+
+use std::any::type_name;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter};
+use std::path::Path;
+
+use crate::atom::{Atom, AtomId, Force, Mass, Position, Velocity};
+use crate::integrator::Step;
+use crate::laser_cooling::transition::TransitionComponent;
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+use specs::storage::MaskedStorage;
+
+/// The state of a single atom in a [Snapshot].
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct AtomSnapshot {
+    /// Index of the atom's [Entity](specs::Entity).
+    pub entity: u32,
+    /// The [AtomId] of the atom, if it has one.
+    pub id: Option<u64>,
+    /// Position of the atom, in SI units of m.
+    pub position: [f64; 3],
+    /// Velocity of the atom, in SI units of m/s.
+    pub velocity: Option<[f64; 3]>,
+    /// Force on the atom, in SI units of N.
+    pub force: Option<[f64; 3]>,
+    /// Mass of the atom, in atomic mass units.
+    pub mass: Option<f64>,
+    /// The laser cooling transition of the atom, or `None` if the atom does not have the transition component.
+    pub species: Option<String>,
+}
+
+/// The state of all atoms at one moment, see [crate::output::snapshot].
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Snapshot {
+    /// The simulation step at which the snapshot was taken.
+    pub step: Option<u64>,
+    pub atoms: Vec<AtomSnapshot>,
+}
+impl Snapshot {
+    /// Records the state of all atoms in the world.
+    ///
+    /// # Generic Arguments
+    ///
+    /// * `T`: The laser cooling transition used for the species of the atoms.
+    pub fn from_world<T>(world: &World) -> Self
+    where
+        T: TransitionComponent,
+    {
+        let entities = world.entities();
+        let atoms = world.read_storage::<Atom>();
+        let positions = world.read_storage::<Position>();
+        let velocities = world.read_storage::<Velocity>();
+        let forces = world.read_storage::<Force>();
+        let masses = world.read_storage::<Mass>();
+        let ids = if world.has_value::<MaskedStorage<AtomId>>() {
+            Some(world.read_storage::<AtomId>())
+        } else {
+            None
+        };
+        let transitions = if world.has_value::<MaskedStorage<T>>() {
+            Some(world.read_storage::<T>())
+        } else {
+            None
+        };
+        let species = type_name::<T>().rsplit("::").next().unwrap().to_string();
+
+        let atoms = (
+            &entities,
+            &atoms,
+            &positions,
+            velocities.maybe(),
+            forces.maybe(),
+            masses.maybe(),
+        )
+            .join()
+            .map(|(entity, _, pos, vel, force, mass)| AtomSnapshot {
+                entity: entity.id(),
+                id: ids.as_ref().and_then(|ids| ids.get(entity)).map(|id| id.id),
+                position: to_array(pos.pos.cast()),
+                velocity: vel.map(|vel| to_array(vel.vel.cast())),
+                force: force.map(|force| to_array(force.force.cast())),
+                mass: mass.map(|mass| mass.value),
+                species: match &transitions {
+                    Some(transitions) if transitions.contains(entity) => Some(species.clone()),
+                    _ => None,
+                },
+            })
+            .collect();
+        Snapshot {
+            step: world.try_fetch::<Step>().map(|step| step.n),
+            atoms,
+        }
+    }
+
+    /// Reads a snapshot written by [snapshot].
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, io::Error> {
+        let reader = BufReader::new(File::open(path)?);
+        Ok(serde_json::from_reader(reader)?)
+    }
+}
+
+fn to_array(v: nalgebra::Vector3<f64>) -> [f64; 3] {
+    [v[0], v[1], v[2]]
+}
+
+/// Writes the id, position, velocity, force, mass and species of every atom in the world to a JSON file.
+///
+/// Only storages are read, so the snapshot may be taken from a callback while the simulation is running. Atoms
+/// deleted during the current step remain in the snapshot until the world is maintained.
+///
+/// # Generic Arguments
+///
+/// * `T`: The laser cooling transition used for the species of the atoms.
+pub fn snapshot<T, P>(world: &World, path: P) -> Result<(), io::Error>
+where
+    T: TransitionComponent,
+    P: AsRef<Path>,
+{
+    let writer = BufWriter::new(File::create(path)?);
+    serde_json::to_writer_pretty(writer, &Snapshot::from_world::<T>(world))?;
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use crate::species::{Rubidium87_780D2, Strontium88_461};
+    use nalgebra::Vector3;
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        let mut world = World::new();
+        world.register::<Atom>();
+        world.register::<Position>();
+        world.register::<Velocity>();
+        world.register::<Force>();
+        world.register::<Mass>();
+        world.register::<AtomId>();
+        world.register::<Rubidium87_780D2>();
+        world.insert(Step { n: 12 });
+
+        world
+            .create_entity()
+            .with(Atom)
+            .with(Position {
+                pos: Vector3::new(1.0e-3, -2.0e-3, 0.5).cast(),
+            })
+            .with(Velocity {
+                vel: Vector3::new(0.25, 0.0, -3.0).cast(),
+            })
+            .with(Force {
+                force: Vector3::new(1.0e-22, 0.0, 2.0e-21).cast(),
+            })
+            .with(Mass { value: 87.0 })
+            .with(AtomId { id: 7 })
+            .with(Rubidium87_780D2)
+            .build();
+        world
+            .create_entity()
+            .with(Atom)
+            .with(Position {
+                pos: Vector3::new(0.0, 0.0, 0.0).cast(),
+            })
+            .with(Velocity {
+                vel: Vector3::new(0.0, 1.0, 0.0).cast(),
+            })
+            .with(Mass { value: 85.0 })
+            .build();
+        // Not an atom, so not included.
+        world.create_entity().with(Position::new()).build();
+
+        let path = std::env::temp_dir().join("atomecs_test_snapshot.json");
+        snapshot::<Rubidium87_780D2, _>(&world, &path).expect("Could not write snapshot.");
+        let read = Snapshot::from_file(&path).expect("Could not read snapshot.");
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(read, Snapshot::from_world::<Rubidium87_780D2>(&world));
+        assert_eq!(read.step, Some(12));
+        assert_eq!(read.atoms.len(), 2);
+        let first = &read.atoms[0];
+        assert_eq!(first.id, Some(7));
+        assert_eq!(
+            first.position,
+            to_array(
+                Vector3::new(1.0e-3, -2.0e-3, 0.5)
+                    .cast::<crate::atom::Scalar>()
+                    .cast()
+            )
+        );
+        assert_eq!(
+            first.force,
+            Some(to_array(
+                Vector3::new(1.0e-22, 0.0, 2.0e-21)
+                    .cast::<crate::atom::Scalar>()
+                    .cast()
+            ))
+        );
+        assert_eq!(first.mass, Some(87.0));
+        assert_eq!(first.species, Some("Rubidium87_780D2".to_string()));
+        let second = &read.atoms[1];
+        assert_eq!(second.id, None);
+        assert_eq!(second.force, None);
+        assert_eq!(second.mass, Some(85.0));
+        assert_eq!(second.species, None);
+
+        // Storages that are not registered are not required.
+        let snapshot = Snapshot::from_world::<Strontium88_461>(&world);
+        assert!(snapshot.atoms.iter().all(|atom| atom.species.is_none()));
+    }
+}
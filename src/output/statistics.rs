@@ -0,0 +1,524 @@
+//! Writes time series of aggregate statistics of the atom cloud, rather than per-atom data.
+//!
+//! For large ensembles, per-atom output is expensive to write and store. The [StatisticsOutputSystem] instead
+//! writes one comma-separated row per output step, containing:
+//!
+//! * `step` and `time`, in SI units of s.
+//! * `atom_number`, the number of real atoms represented by the simulated atoms, which is the sum of their
+//!   [StatisticalWeight] and [SuperAtomWeight]. Unweighted atoms count as one, so this is the number of simulated
+//!   atoms if no atom is weighted.
+//! * `temperature_x`, `temperature_y`, `temperature_z`: the temperature along each axis in the centre-of-mass
+//!   frame, in SI units of K.
+//! * `center_x`, `center_y`, `center_z`: the center of mass of the cloud, in SI units of m.
+//! * `rms_x`, `rms_y`, `rms_z`: the root-mean-square size of the cloud along each axis, in SI units of m.
+//! * `kinetic_energy`: the total kinetic energy of the atoms, in SI units of J.
+//! * `potential_energy`: the total potential energy of the atoms, in SI units of J, summed from their
+//!   [EnergyComponents]. It is only calculated if the [EnergyComponentsPlugin](crate::energy::EnergyComponentsPlugin)
+//!   is added, and is zero otherwise.
+//! * `total_energy`: the sum of the kinetic and potential energies, in SI units of J.
+//!
+//! Optionally, see [StatisticsOutputPlugin::with_recoil_ratio], the rows also contain:
+//!
+//! * `recoil_ratio_x`, `recoil_ratio_y`, `recoil_ratio_z`: the temperature along each axis divided by the recoil
+//!   temperature of the cooling transition, for atoms of the mean mass of the cloud. Ratios close to one show that
+//!   the cloud has reached the recoil limit, below which Doppler and Sisyphus cooling cannot cool it further.
+//!
+//! The temperature, center and size are NaN when there are no atoms.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use crate::atom::{
+    total_weight, Atom, Mass, Position, StatisticalWeight, SuperAtomWeight, Velocity,
+};
+use crate::constant::{AMU, BOLTZCONST};
+use crate::energy::{EnergyComponents, CALCULATE_ENERGY_COMPONENTS_SYSTEM_NAME};
+use crate::integrator::{SimulationTime, Step, Timestep, INTEGRATE_POSITION_SYSTEM_NAME};
+use crate::laser_cooling::transition::AtomicTransition;
+use crate::simulation::Plugin;
+use nalgebra::Vector3;
+use specs::prelude::*;
+
+const HEADER: &str = "step,time,atom_number,temperature_x,temperature_y,temperature_z,center_x,center_y,center_z,rms_x,rms_y,rms_z,kinetic_energy,potential_energy,total_energy";
+const RECOIL_HEADER: &str = "recoil_ratio_x,recoil_ratio_y,recoil_ratio_z";
+
+/// The aggregate statistics of the atoms at one step, see [crate::output::statistics].
+#[derive(Clone, Copy, Debug)]
+pub struct CloudStatistics {
+    /// Number of simulated atoms.
+    pub atom_number: usize,
+    /// Number of real atoms represented by the simulated atoms, the sum of their weights. Equal to `atom_number`
+    /// unless set with [CloudStatistics::with_real_atom_number].
+    pub real_atom_number: f64,
+    /// Temperature along each axis in the centre-of-mass frame, in SI units of K.
+    pub temperature: Vector3<f64>,
+    /// Center of mass of the cloud, in SI units of m.
+    pub center: Vector3<f64>,
+    /// Root-mean-square displacement of the atoms from the center of mass along each axis, in SI units of m.
+    pub rms_size: Vector3<f64>,
+    /// Total kinetic energy of the atoms, in SI units of J.
+    pub kinetic_energy: f64,
+    /// Total potential energy of the atoms, in SI units of J. Zero unless set with
+    /// [CloudStatistics::with_potential_energy].
+    pub potential_energy: f64,
+    /// Mean mass of the atoms, in atomic mass units.
+    pub mean_mass: f64,
+}
+impl CloudStatistics {
+    /// Calculates the statistics of a collection of atoms.
+    ///
+    /// # Arguments
+    ///
+    /// `atoms`: positions (in m), velocities (in m/s) and masses (in atomic mass units) of the atoms.
+    pub fn calculate(atoms: &[(Vector3<f64>, Vector3<f64>, f64)]) -> Self {
+        let total_mass: f64 = atoms.iter().map(|(_, _, mass)| mass).sum();
+        let center = atoms
+            .iter()
+            .fold(Vector3::new(0.0, 0.0, 0.0), |sum, (pos, _, mass)| {
+                sum + pos * *mass
+            })
+            / total_mass;
+        let mean_velocity = atoms
+            .iter()
+            .fold(Vector3::new(0.0, 0.0, 0.0), |sum, (_, vel, mass)| {
+                sum + vel * *mass
+            })
+            / total_mass;
+        let mut spread = Vector3::new(0.0, 0.0, 0.0);
+        let mut thermal = Vector3::new(0.0, 0.0, 0.0);
+        let mut kinetic_energy = 0.0;
+        for (pos, vel, mass) in atoms.iter() {
+            let displacement = pos - center;
+            let thermal_velocity = vel - mean_velocity;
+            spread += displacement.component_mul(&displacement) * *mass;
+            thermal += thermal_velocity.component_mul(&thermal_velocity) * *mass * AMU;
+            kinetic_energy += 0.5 * mass * AMU * vel.norm_squared();
+        }
+        let number = atoms.len();
+        CloudStatistics {
+            atom_number: number,
+            real_atom_number: number as f64,
+            temperature: thermal / (number as f64 * BOLTZCONST),
+            center,
+            rms_size: (spread / total_mass).map(f64::sqrt),
+            kinetic_energy,
+            potential_energy: 0.0,
+            mean_mass: total_mass / number as f64,
+        }
+    }
+
+    /// Sets the number of real atoms, for simulated atoms that have a [StatisticalWeight] or [SuperAtomWeight].
+    pub fn with_real_atom_number(mut self, real_atom_number: f64) -> Self {
+        self.real_atom_number = real_atom_number;
+        self
+    }
+
+    /// Sets the total potential energy of the atoms, for example from their [EnergyComponents].
+    pub fn with_potential_energy(mut self, potential_energy: f64) -> Self {
+        self.potential_energy = potential_energy;
+        self
+    }
+
+    /// The total energy of the atoms, in SI units of J.
+    pub fn total_energy(&self) -> f64 {
+        self.kinetic_energy + self.potential_energy
+    }
+
+    /// The temperature along each axis divided by `recoil_temperature`, in units of K.
+    pub fn recoil_ratio(&self, recoil_temperature: f64) -> Vector3<f64> {
+        self.temperature / recoil_temperature
+    }
+}
+
+/// A system that writes the [CloudStatistics] of the atoms to a comma-separated file at a defined interval.
+///
+/// Only one row of aggregate statistics is written per output step, so this is the cheapest form of output.
+pub struct StatisticsOutputSystem<W: Write> {
+    /// Number of integration steps between each row of output.
+    interval: u64,
+    /// The [Write](std::io::Write)able output stream.
+    stream: W,
+    header_written: bool,
+    /// Recoil temperature as a function of mass, used to write the recoil ratio columns if set.
+    recoil_temperature: Option<fn(f64) -> f64>,
+}
+impl<W: Write> StatisticsOutputSystem<W> {
+    pub fn new(stream: W, interval: u64) -> Self {
+        StatisticsOutputSystem {
+            interval,
+            stream,
+            header_written: false,
+            recoil_temperature: None,
+        }
+    }
+
+    /// Also writes the temperature relative to the recoil temperature of the transition `T`.
+    pub fn with_recoil_ratio<T: AtomicTransition>(mut self) -> Self {
+        self.recoil_temperature = Some(T::recoil_temperature);
+        self
+    }
+}
+
+impl<'a, W: Write> System<'a> for StatisticsOutputSystem<W> {
+    type SystemData = (
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Velocity>,
+        ReadStorage<'a, Mass>,
+        ReadStorage<'a, Atom>,
+        ReadStorage<'a, StatisticalWeight>,
+        ReadStorage<'a, SuperAtomWeight>,
+        ReadStorage<'a, EnergyComponents>,
+        ReadExpect<'a, Step>,
+        ReadExpect<'a, Timestep>,
+    );
+
+    fn run(
+        &mut self,
+        (positions, velocities, masses, atoms, weights, super_atoms, energies, step, timestep): Self::SystemData,
+    ) {
+        if step.n % self.interval != 0 {
+            return;
+        }
+        if !self.header_written {
+            match self.recoil_temperature {
+                Some(_) => writeln!(self.stream, "{},{}", HEADER, RECOIL_HEADER),
+                None => writeln!(self.stream, "{}", HEADER),
+            }
+            .expect("Could not write.");
+            self.header_written = true;
+        }
+        let samples: Vec<(Vector3<f64>, Vector3<f64>, f64)> =
+            (&positions, &velocities, &masses, &atoms)
+                .join()
+                .map(|(pos, vel, mass, _)| (pos.pos.cast(), vel.vel.cast(), mass.value))
+                .collect();
+        let real_atom_number = (&atoms, weights.maybe(), super_atoms.maybe())
+            .join()
+            .map(|(_, weight, super_atom)| total_weight(weight, super_atom))
+            .sum();
+        let potential_energy = (&atoms, &energies)
+            .join()
+            .map(|(_, energy)| energy.potential())
+            .sum();
+        let statistics = CloudStatistics::calculate(&samples)
+            .with_real_atom_number(real_atom_number)
+            .with_potential_energy(potential_energy);
+        let t = statistics.temperature;
+        let c = statistics.center;
+        let r = statistics.rms_size;
+        write!(
+            self.stream,
+            "{},{:e},{},{:e},{:e},{:e},{:e},{:e},{:e},{:e},{:e},{:e},{:e},{:e},{:e}",
+            step.n,
+            SimulationTime::new(&step, &timestep).time,
+            statistics.real_atom_number,
+            t[0],
+            t[1],
+            t[2],
+            c[0],
+            c[1],
+            c[2],
+            r[0],
+            r[1],
+            r[2],
+            statistics.kinetic_energy,
+            statistics.potential_energy,
+            statistics.total_energy()
+        )
+        .expect("Could not write.");
+        if let Some(recoil_temperature) = self.recoil_temperature {
+            let ratio = statistics.recoil_ratio(recoil_temperature(statistics.mean_mass));
+            write!(self.stream, ",{:e},{:e},{:e}", ratio[0], ratio[1], ratio[2])
+                .expect("Could not write.");
+        }
+        writeln!(self.stream).expect("Could not write.");
+    }
+}
+
+/// This plugin writes the [CloudStatistics] of the atoms to a comma-separated file.
+///
+/// Add the plugin after the [EnergyComponentsPlugin](crate::energy::EnergyComponentsPlugin) to write the potential
+/// energy of the atoms in the current step. See also [crate::output::statistics].
+pub struct StatisticsOutputPlugin {
+    file_name: String,
+    interval: u64,
+    recoil_temperature: Option<fn(f64) -> f64>,
+}
+impl StatisticsOutputPlugin {
+    /// Writes the statistics to `file_name` every `interval` integration steps.
+    pub fn new(file_name: String, interval: u64) -> Self {
+        StatisticsOutputPlugin {
+            file_name,
+            interval,
+            recoil_temperature: None,
+        }
+    }
+
+    /// Also writes the temperature relative to the [recoil temperature](AtomicTransition::recoil_temperature) of
+    /// the transition `T`, calculated for the mean mass of the atoms.
+    pub fn with_recoil_ratio<T: AtomicTransition>(mut self) -> Self {
+        self.recoil_temperature = Some(T::recoil_temperature);
+        self
+    }
+}
+impl Plugin for StatisticsOutputPlugin {
+    fn build(&self, builder: &mut crate::simulation::SimulationBuilder) {
+        let file = match File::create(&self.file_name) {
+            Err(why) => panic!("couldn't open {}: {}", self.file_name, why),
+            Ok(file) => file,
+        };
+        let mut system = StatisticsOutputSystem::new(BufWriter::new(file), self.interval);
+        system.recoil_temperature = self.recoil_temperature;
+        let mut deps = vec![INTEGRATE_POSITION_SYSTEM_NAME];
+        if builder
+            .dispatcher_builder
+            .has_system(CALCULATE_ENERGY_COMPONENTS_SYSTEM_NAME)
+        {
+            deps.push(CALCULATE_ENERGY_COMPONENTS_SYSTEM_NAME);
+        }
+        builder
+            .dispatcher_builder
+            .add(system, "statistics_output", &deps);
+    }
+    fn deps(&self) -> Vec<Box<dyn Plugin>> {
+        Vec::new()
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use crate::atom::Force;
+    use crate::simulation::{Simulation, SimulationBuilder};
+    use assert_approx_eq::assert_approx_eq;
+
+    fn add_atom(sim: &mut Simulation, pos: Vector3<f64>, vel: Vector3<f64>) {
+        sim.world
+            .create_entity()
+            .with(Position { pos: pos.cast() })
+            .with(Velocity { vel: vel.cast() })
+            .with(Force::new())
+            .with(Mass { value: 87.0 })
+            .with(Atom)
+            .build();
+    }
+
+    #[test]
+    fn test_cloud_statistics() {
+        let atoms = [
+            (
+                Vector3::new(1.0, 0.0, 0.0),
+                Vector3::new(1.0, 0.0, 2.0),
+                87.0,
+            ),
+            (
+                Vector3::new(-1.0, 0.0, 2.0),
+                Vector3::new(-1.0, 0.0, 2.0),
+                87.0,
+            ),
+        ];
+        let statistics = CloudStatistics::calculate(&atoms);
+        assert_eq!(statistics.atom_number, 2);
+        assert_eq!(statistics.center, Vector3::new(0.0, 0.0, 1.0));
+        assert_eq!(statistics.rms_size, Vector3::new(1.0, 0.0, 1.0));
+        assert_approx_eq!(statistics.temperature[0], 87.0 * AMU / BOLTZCONST, 1e-9);
+        assert_eq!(statistics.temperature[1], 0.0);
+        assert_eq!(statistics.temperature[2], 0.0);
+        assert_approx_eq!(statistics.kinetic_energy, 5.0 * 87.0 * AMU, 1e-35);
+        assert_eq!(statistics.mean_mass, 87.0);
+    }
+
+    #[test]
+    fn test_recoil_ratio_output() {
+        use crate::species::Rubidium87_780D2;
+
+        let path = std::env::temp_dir().join("atomecs_test_statistics_recoil.csv");
+        let mut sim_builder = SimulationBuilder::default();
+        sim_builder.add_plugin(
+            StatisticsOutputPlugin::new(path.to_str().unwrap().to_string(), 1)
+                .with_recoil_ratio::<Rubidium87_780D2>(),
+        );
+        let mut sim = sim_builder.build();
+        sim.world.insert(Timestep { delta: 1.0e-9 });
+
+        // A pair of counter-propagating atoms has `T = m v^2 / k_B` along their axis.
+        let recoil_temperature = Rubidium87_780D2::recoil_temperature(87.0);
+        let multiple = Vector3::new(5.0, 2.0, 0.5);
+        let speed = (multiple * recoil_temperature * BOLTZCONST / (87.0 * AMU)).map(f64::sqrt);
+        add_atom(&mut sim, Vector3::new(0.0, 0.0, 0.0), speed);
+        add_atom(&mut sim, Vector3::new(0.0, 0.0, 0.0), -speed);
+
+        let atoms = [
+            (Vector3::new(0.0, 0.0, 0.0), speed, 87.0),
+            (Vector3::new(0.0, 0.0, 0.0), -speed, 87.0),
+        ];
+        let ratio = CloudStatistics::calculate(&atoms).recoil_ratio(recoil_temperature);
+        for i in 0..3 {
+            assert_approx_eq!(ratio[i], multiple[i], 1e-9);
+        }
+
+        sim.step();
+        drop(sim);
+
+        let contents = std::fs::read_to_string(&path).expect("Could not read statistics file.");
+        std::fs::remove_file(&path).ok();
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines[0], format!("{},{}", HEADER, RECOIL_HEADER));
+        let row: Vec<f64> = lines[1].split(',').map(|x| x.parse().unwrap()).collect();
+        assert_eq!(row.len(), lines[0].split(',').count());
+        for i in 0..3 {
+            assert_approx_eq!(row[15 + i], multiple[i], 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_statistics_output_rows() {
+        let path = std::env::temp_dir().join("atomecs_test_statistics.csv");
+        let mut sim_builder = SimulationBuilder::default();
+        sim_builder.add_plugin(StatisticsOutputPlugin::new(
+            path.to_str().unwrap().to_string(),
+            2,
+        ));
+        let mut sim = sim_builder.build();
+        sim.world.insert(Timestep { delta: 1.0e-6 });
+
+        for i in 0..3 {
+            add_atom(
+                &mut sim,
+                Vector3::new(i as f64 * 1.0e-4, 0.0, 0.0),
+                Vector3::new(0.0, 0.1, 0.0),
+            );
+        }
+        for _ in 0..4 {
+            sim.step();
+        }
+        for _ in 0..2 {
+            add_atom(
+                &mut sim,
+                Vector3::new(0.0, 0.0, 0.0),
+                Vector3::new(0.0, 0.0, 0.0),
+            );
+        }
+        for _ in 0..6 {
+            sim.step();
+        }
+        // Dropping the simulation flushes the output.
+        drop(sim);
+
+        let contents = std::fs::read_to_string(&path).expect("Could not read statistics file.");
+        std::fs::remove_file(&path).ok();
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines[0], HEADER);
+        let rows: Vec<Vec<&str>> = lines[1..]
+            .iter()
+            .map(|line| line.split(',').collect())
+            .collect();
+        let steps: Vec<&str> = rows.iter().map(|row| row[0]).collect();
+        let numbers: Vec<&str> = rows.iter().map(|row| row[2]).collect();
+        assert_eq!(steps, vec!["2", "4", "6", "8", "10"]);
+        assert_eq!(numbers, vec!["3", "3", "5", "5", "5"]);
+        assert!(rows
+            .iter()
+            .all(|row| row.len() == HEADER.split(',').count()));
+    }
+
+    #[test]
+    fn test_atom_number_sums_weights() {
+        let path = std::env::temp_dir().join("atomecs_test_statistics_weighted.csv");
+        let mut sim_builder = SimulationBuilder::default();
+        sim_builder.add_plugin(StatisticsOutputPlugin::new(
+            path.to_str().unwrap().to_string(),
+            1,
+        ));
+        let mut sim = sim_builder.build();
+        sim.world.insert(Timestep { delta: 1.0e-6 });
+
+        // An unweighted atom, an importance-sampled atom and a super-atom.
+        let weights = [None, Some(StatisticalWeight { value: 0.25 }), None];
+        let super_atoms = [None, None, Some(SuperAtomWeight { n_real: 1.0e4 })];
+        for (weight, super_atom) in weights.iter().zip(super_atoms.iter()) {
+            let mut builder = sim
+                .world
+                .create_entity()
+                .with(Position::new())
+                .with(Velocity {
+                    vel: Vector3::new(0.0, 0.0, 0.0).cast(),
+                })
+                .with(Force::new())
+                .with(Mass { value: 87.0 })
+                .with(Atom);
+            if let Some(weight) = weight {
+                builder = builder.with(*weight);
+            }
+            if let Some(super_atom) = super_atom {
+                builder = builder.with(*super_atom);
+            }
+            builder.build();
+        }
+        sim.step();
+        drop(sim);
+
+        let contents = std::fs::read_to_string(&path).expect("Could not read statistics file.");
+        std::fs::remove_file(&path).ok();
+        let row: Vec<&str> = contents.lines().nth(1).unwrap().split(',').collect();
+        let atom_number: f64 = row[2].parse().unwrap();
+        assert_eq!(atom_number, 1.0 + 0.25 + 1.0e4);
+    }
+
+    #[test]
+    fn test_energy_columns_include_potential_energy() {
+        use crate::energy::EnergyComponentsPlugin;
+        use crate::gravity::ApplyGravityOption;
+        use crate::integrator::Pinned;
+
+        let path = std::env::temp_dir().join("atomecs_test_statistics_energy.csv");
+        let mut sim_builder = SimulationBuilder::default();
+        sim_builder.add_plugin(EnergyComponentsPlugin::<1>);
+        sim_builder.add_plugin(StatisticsOutputPlugin::new(
+            path.to_str().unwrap().to_string(),
+            1,
+        ));
+        let mut sim = sim_builder.build();
+        sim.world.insert(Timestep { delta: 1.0e-6 });
+        sim.world.insert(ApplyGravityOption);
+
+        // The atoms are pinned, so that they stay at the heights for which the energies are calculated.
+        let heights = [1.0e-3, -3.0e-3];
+        let vel = Vector3::new(0.0, 0.1, 0.0);
+        for height in heights.iter() {
+            sim.world
+                .create_entity()
+                .with(Position {
+                    pos: Vector3::new(0.0, 0.0, *height).cast(),
+                })
+                .with(Velocity { vel: vel.cast() })
+                .with(Force::new())
+                .with(Mass { value: 87.0 })
+                .with(Atom)
+                .with(Pinned)
+                .build();
+        }
+        sim.step();
+        drop(sim);
+
+        let contents = std::fs::read_to_string(&path).expect("Could not read statistics file.");
+        std::fs::remove_file(&path).ok();
+        let row: Vec<f64> = contents
+            .lines()
+            .nth(1)
+            .unwrap()
+            .split(',')
+            .map(|x| x.parse().unwrap())
+            .collect();
+        let kinetic = 2.0 * 0.5 * 87.0 * AMU * vel.norm_squared();
+        let potential: f64 = heights
+            .iter()
+            .map(|height| 87.0 * AMU * crate::constant::GC * height)
+            .sum();
+        assert!(potential < 0.0);
+        assert_approx_eq!(row[12], kinetic, kinetic * 1e-6);
+        assert_approx_eq!(row[13], potential, potential.abs() * 1e-6);
+        assert_approx_eq!(row[14], kinetic + potential, potential.abs() * 1e-6);
+    }
+}
@@ -0,0 +1,191 @@
+//! Writes complete trajectories of a few tagged tracer atoms.
+//!
+//! Writing the trajectory of every atom at high time resolution produces too much data, so the [TracerOutputSystem]
+//! instead writes every step of the trajectories of atoms marked with the [Tracer] component, ignoring the
+//! interval of the other outputs. Each tracer must have an [AtomId], and the trajectory of each tracer is written to
+//! its own comma-separated file, `tracer_<id>.csv`, with columns:
+//!
+//! * `step` and `time`, in SI units of s.
+//! * `x`, `y`, `z`: the position of the atom, in SI units of m.
+//! * `vx`, `vy`, `vz`: the velocity of the atom, in SI units of m/s.
+//!
+//! The file of a tracer is flushed and closed when the atom is deleted, or at the end of the simulation.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+
+use crate::atom::{AtomId, Position, Velocity};
+use crate::integrator::{SimulationTime, Step, Timestep, INTEGRATE_POSITION_SYSTEM_NAME};
+use crate::simulation::Plugin;
+use hashbrown::HashMap;
+use specs::prelude::*;
+
+const HEADER: &str = "step,time,x,y,z,vx,vy,vz";
+
+/// A component that marks an atom for which the full trajectory is written, see [crate::output::tracer].
+#[derive(Default)]
+pub struct Tracer;
+
+impl Component for Tracer {
+    type Storage = NullStorage<Self>;
+}
+
+/// A system that writes the trajectory of each [Tracer] atom to its own file, every step.
+///
+/// Tracers without an [AtomId] are ignored. The [AtomId]s of the tracers must be unique.
+pub struct TracerOutputSystem {
+    /// The directory in which the files are created.
+    directory: PathBuf,
+    /// The open file of each tracer, keyed by the [AtomId].
+    files: HashMap<u64, BufWriter<File>>,
+}
+impl TracerOutputSystem {
+    pub fn new(directory: PathBuf) -> Self {
+        TracerOutputSystem {
+            directory,
+            files: HashMap::new(),
+        }
+    }
+
+    /// The path of the file holding the trajectory of the tracer with the given `id`.
+    pub fn file_name(directory: &std::path::Path, id: u64) -> PathBuf {
+        directory.join(format!("tracer_{}.csv", id))
+    }
+}
+
+impl<'a> System<'a> for TracerOutputSystem {
+    type SystemData = (
+        ReadStorage<'a, Tracer>,
+        ReadStorage<'a, AtomId>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Velocity>,
+        ReadExpect<'a, Step>,
+        ReadExpect<'a, Timestep>,
+    );
+
+    fn run(&mut self, (tracers, ids, positions, velocities, step, timestep): Self::SystemData) {
+        let time = SimulationTime::new(&step, &timestep).time;
+        let mut present = Vec::new();
+        for (_, id, pos, vel) in (&tracers, &ids, &positions, &velocities).join() {
+            present.push(id.id);
+            let directory = &self.directory;
+            let stream = self.files.entry(id.id).or_insert_with(|| {
+                let path = TracerOutputSystem::file_name(directory, id.id);
+                let file = match File::create(&path) {
+                    Err(why) => panic!("couldn't open {}: {}", path.display(), why),
+                    Ok(file) => file,
+                };
+                let mut stream = BufWriter::new(file);
+                writeln!(stream, "{}", HEADER).expect("Could not write.");
+                stream
+            });
+            let (pos, vel) = (pos.pos.cast::<f64>(), vel.vel.cast::<f64>());
+            writeln!(
+                stream,
+                "{},{:e},{:e},{:e},{:e},{:e},{:e},{:e}",
+                step.n, time, pos[0], pos[1], pos[2], vel[0], vel[1], vel[2]
+            )
+            .expect("Could not write.");
+        }
+
+        // Finalize the files of tracers that have been deleted.
+        self.files.retain(|id, stream| {
+            if present.contains(id) {
+                return true;
+            }
+            stream.flush().expect("Could not write.");
+            false
+        });
+    }
+}
+
+/// This plugin writes the trajectories of [Tracer] atoms to files in a directory.
+///
+/// See also [crate::output::tracer].
+pub struct TracerOutputPlugin {
+    directory: String,
+}
+impl TracerOutputPlugin {
+    /// Writes the trajectories to files in `directory`, which must exist.
+    pub fn new(directory: String) -> Self {
+        TracerOutputPlugin { directory }
+    }
+}
+impl Plugin for TracerOutputPlugin {
+    fn build(&self, builder: &mut crate::simulation::SimulationBuilder) {
+        builder.world.register::<Tracer>();
+        builder.dispatcher_builder.add(
+            TracerOutputSystem::new(PathBuf::from(&self.directory)),
+            "tracer_output",
+            &[INTEGRATE_POSITION_SYSTEM_NAME],
+        );
+    }
+    fn deps(&self) -> Vec<Box<dyn Plugin>> {
+        Vec::new()
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use crate::atom::{Atom, Force, Mass};
+    use crate::simulation::SimulationBuilder;
+    use nalgebra::Vector3;
+
+    #[test]
+    fn test_tracer_trajectory_has_one_row_per_step() {
+        let directory = std::env::temp_dir();
+        let mut sim_builder = SimulationBuilder::default();
+        sim_builder.add_plugin(TracerOutputPlugin::new(
+            directory.to_str().unwrap().to_string(),
+        ));
+        let mut sim = sim_builder.build();
+        sim.world.insert(Timestep { delta: 1.0e-6 });
+
+        let (traced, untraced) = (934_001, 934_002);
+        let mut atoms = Vec::new();
+        for id in [traced, untraced].iter() {
+            let mut builder = sim
+                .world
+                .create_entity()
+                .with(Position {
+                    pos: Vector3::new(0.0, 0.0, 0.0).cast(),
+                })
+                .with(Velocity {
+                    vel: Vector3::new(1.0, 0.0, 0.0).cast(),
+                })
+                .with(Force::new())
+                .with(Mass { value: 87.0 })
+                .with(Atom)
+                .with(AtomId { id: *id });
+            if *id == traced {
+                builder = builder.with(Tracer);
+            }
+            atoms.push(builder.build());
+        }
+
+        let steps = 7;
+        for _ in 0..steps {
+            sim.step();
+        }
+        // Deleting the tracer finalizes its file, while the simulation continues.
+        sim.world.delete_entity(atoms[0]).expect("Could not delete tracer.");
+        sim.step();
+        sim.step();
+
+        let path = TracerOutputSystem::file_name(&directory, traced);
+        let contents = std::fs::read_to_string(&path).expect("Could not read tracer file.");
+        std::fs::remove_file(&path).ok();
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines[0], HEADER);
+        assert_eq!(lines.len(), steps + 1);
+        for (i, line) in lines[1..].iter().enumerate() {
+            let row: Vec<&str> = line.split(',').collect();
+            assert_eq!(row.len(), HEADER.split(',').count());
+            assert_eq!(row[0], (i + 1).to_string());
+        }
+        assert!(!TracerOutputSystem::file_name(&directory, untraced).exists());
+    }
+}
@@ -0,0 +1,172 @@
+//! Decides on which steps an output is written.
+//!
+//! Outputs are usually written every fixed number of integration steps. For live monitoring of a slow simulation,
+//! or of one whose step rate varies, it is often more useful to write at a fixed cadence in wall-clock time, for
+//! example every few seconds, regardless of how many steps have been run. An [OutputTrigger] selects between the
+//! two, and an [OutputSchedule] applies it.
+//!
+//! The file, `.npy`, `.npz`, memory and console outputs all accept an [OutputTrigger]. The file, `.npy`, `.npz`
+//! and console outputs with a wall-clock trigger also write the state at the end of the run, when their systems
+//! are disposed, see [Simulation::finish](crate::simulation::Simulation::finish). No data is copied on the steps in
+//! between.
+
+use std::time::Instant;
+
+/// When an output is written.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OutputTrigger {
+    /// On each step whose number is a multiple of the given interval.
+    EveryNSteps(u64),
+    /// On the first step, and then on the first step after at least the given number of wall-clock seconds have
+    /// elapsed since the last output.
+    ///
+    /// The last step is written when the output systems are disposed, by
+    /// [Simulation::finish](crate::simulation::Simulation::finish), [run_simulation](crate::simulation::run_simulation)
+    /// or [run_until](crate::simulation::run_until). A [Simulation](crate::simulation::Simulation) that is advanced
+    /// with `step` and then dropped does not dispose its systems, so the last step is not written unless it was due.
+    EveryWallClockSeconds(f64),
+}
+
+/// A source of wall-clock time, which can be replaced in tests.
+pub trait WallClock: Send {
+    /// The time elapsed since an arbitrary origin, in seconds.
+    fn seconds(&self) -> f64;
+}
+
+/// The [WallClock] of the system, measured from its creation.
+pub struct SystemClock {
+    start: Instant,
+}
+impl Default for SystemClock {
+    fn default() -> Self {
+        SystemClock {
+            start: Instant::now(),
+        }
+    }
+}
+impl WallClock for SystemClock {
+    fn seconds(&self) -> f64 {
+        self.start.elapsed().as_secs_f64()
+    }
+}
+
+/// Applies an [OutputTrigger], keeping track of the time of the last output.
+pub struct OutputSchedule {
+    trigger: OutputTrigger,
+    clock: Box<dyn WallClock>,
+    last_output: Option<f64>,
+    /// The last step passed to [OutputSchedule::is_due], and whether it was due.
+    last_step: Option<(u64, bool)>,
+}
+impl OutputSchedule {
+    /// Creates a schedule that measures wall-clock time with the [SystemClock].
+    pub fn new(trigger: OutputTrigger) -> Self {
+        Self::with_clock(trigger, Box::new(SystemClock::default()))
+    }
+
+    /// Creates a schedule that measures wall-clock time with the given clock.
+    pub fn with_clock(trigger: OutputTrigger, clock: Box<dyn WallClock>) -> Self {
+        OutputSchedule {
+            trigger,
+            clock,
+            last_output: None,
+            last_step: None,
+        }
+    }
+
+    /// Returns true if the output should be written on the given step. Call once per step.
+    pub fn is_due(&mut self, step: u64) -> bool {
+        let due = match self.trigger {
+            OutputTrigger::EveryNSteps(interval) => step % interval == 0,
+            OutputTrigger::EveryWallClockSeconds(seconds) => {
+                let now = self.clock.seconds();
+                let due = self.last_output.map_or(true, |last| now - last >= seconds);
+                if due {
+                    self.last_output = Some(now);
+                }
+                due
+            }
+        };
+        self.last_step = Some((step, due));
+        due
+    }
+
+    /// True if the last step must be written at the end of the simulation, even if it was not due.
+    pub fn writes_final_frame(&self) -> bool {
+        matches!(self.trigger, OutputTrigger::EveryWallClockSeconds(_))
+    }
+
+    /// The last step, if it must still be written at the end of the simulation.
+    ///
+    /// This is the case if the schedule writes a final frame, and the last step passed to
+    /// [OutputSchedule::is_due] was not due.
+    pub fn final_frame(&self) -> Option<u64> {
+        match self.last_step {
+            Some((step, false)) if self.writes_final_frame() => Some(step),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use std::sync::{Arc, Mutex};
+
+    /// A [WallClock] that only advances when told to.
+    #[derive(Clone, Default)]
+    pub struct MockClock {
+        pub time: Arc<Mutex<f64>>,
+    }
+    impl MockClock {
+        pub fn advance(&self, seconds: f64) {
+            *self.time.lock().unwrap() += seconds;
+        }
+    }
+    impl WallClock for MockClock {
+        fn seconds(&self) -> f64 {
+            *self.time.lock().unwrap()
+        }
+    }
+
+    #[test]
+    fn test_wall_clock_cadence() {
+        let clock = MockClock::default();
+        let mut schedule = OutputSchedule::with_clock(
+            OutputTrigger::EveryWallClockSeconds(2.0),
+            Box::new(clock.clone()),
+        );
+        // The steps run at 0.7 s each, so an output is due every third step.
+        let due: Vec<u64> = (1..=10)
+            .filter(|&step| {
+                let due = schedule.is_due(step);
+                clock.advance(0.7);
+                due
+            })
+            .collect();
+        assert_eq!(due, vec![1, 4, 7, 10]);
+        assert!(schedule.writes_final_frame());
+        // The last step was written, so no final frame is needed.
+        assert_eq!(schedule.final_frame(), None);
+        schedule.is_due(11);
+        assert_eq!(schedule.final_frame(), Some(11));
+
+        // The cadence does not depend on the step rate.
+        let mut schedule = OutputSchedule::with_clock(
+            OutputTrigger::EveryWallClockSeconds(2.0),
+            Box::new(clock.clone()),
+        );
+        let due = (1..=100).filter(|&step| schedule.is_due(step)).count();
+        assert_eq!(due, 1);
+    }
+
+    #[test]
+    fn test_every_n_steps() {
+        let mut schedule = OutputSchedule::new(OutputTrigger::EveryNSteps(3));
+        let due: Vec<u64> = (0..10).filter(|&step| schedule.is_due(step)).collect();
+        assert_eq!(due, vec![0, 3, 6, 9]);
+        assert!(!schedule.writes_final_frame());
+        assert_eq!(schedule.final_frame(), None);
+    }
+}
@@ -0,0 +1,179 @@
+//! Control over the parallel iteration of systems.
+//!
+//! Systems iterate over atoms in parallel using `par_join`. When the [ForceSerial] resource is present,
+//! these systems instead iterate over atoms serially using `join`. This is slower, but guarantees that the
+//! order of operations does not depend on the number of threads, for example to produce reproducible
+//! outputs in regression tests.
+//!
+//! Serial iteration alone does not make the stochastic options reproducible, such as the
+//! [EmissionForceOption](crate::laser_cooling::force::EmissionForceOption), the
+//! [ScatteringFluctuationsOption](crate::laser_cooling::photons_scattered::ScatteringFluctuationsOption) or
+//! collisions. For these, also insert a [DeterministicRng](crate::rng::DeterministicRng) with a fixed seed.
+
+use specs::prelude::*;
+
+/// A resource that indicates that systems should iterate over atoms serially, rather than in parallel.
+///
+/// See [crate::parallel].
+#[derive(Clone, Copy, Default)]
+pub struct ForceSerial;
+
+/// Iterates over a join, in parallel unless serial iteration is requested.
+pub trait MaybeParJoin: ParJoin + Sized {
+    /// Calls `op` for each item of the join. The iteration is serial if `serial` is true, and parallel otherwise.
+    fn maybe_par_for_each<F>(self, serial: bool, op: F)
+    where
+        F: Fn(Self::Type) + Send + Sync;
+}
+impl<J> MaybeParJoin for J
+where
+    J: ParJoin + Send,
+    J::Mask: Send + Sync,
+    J::Type: Send,
+    J::Value: Send,
+{
+    fn maybe_par_for_each<F>(self, serial: bool, op: F)
+    where
+        F: Fn(Self::Type) + Send + Sync,
+    {
+        use rayon::prelude::*;
+
+        if serial {
+            self.join().for_each(op);
+        } else {
+            self.par_join().for_each(op);
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use crate::atom::{Atom, Force, Mass, Position, Velocity};
+    use crate::initiate::NewlyCreated;
+    use crate::integrator::Timestep;
+    use crate::laser::gaussian::GaussianBeam;
+    use crate::laser::LaserPlugin;
+    use crate::laser_cooling::{CoolingLight, LaserCoolingPlugin};
+    use crate::laser_cooling::force::EmissionForceOption;
+    use crate::laser_cooling::photons_scattered::ScatteringFluctuationsOption;
+    use crate::magnetic::quadrupole::QuadrupoleField3D;
+    use crate::rng::DeterministicRng;
+    use crate::simulation::SimulationBuilder;
+    use crate::species::Rubidium87_780D2;
+    use nalgebra::Vector3;
+
+    /// Simulates a 3D MOT, returning the forces on the atoms after a number of steps.
+    ///
+    /// If a `seed` is given, the random recoil of spontaneous emission and the fluctuations of the number of
+    /// scattered photons are enabled, and drawn from a [DeterministicRng] with that seed.
+    fn simulate_mot(serial: bool, seed: Option<u64>) -> Vec<Force> {
+        let mut sim_builder = SimulationBuilder::default();
+        sim_builder.add_plugin(LaserPlugin::<6>);
+        sim_builder.add_plugin(LaserCoolingPlugin::<Rubidium87_780D2, 6>::default());
+        let mut sim = sim_builder.build();
+        sim.world.insert(Timestep { delta: 1.0e-6 });
+        if serial {
+            sim.world.insert(ForceSerial);
+        }
+        if let Some(seed) = seed {
+            sim.world.insert(EmissionForceOption::default());
+            sim.world.insert(ScatteringFluctuationsOption::On);
+            sim.world.insert(DeterministicRng::new(seed));
+        }
+
+        sim.world
+            .create_entity()
+            .with(QuadrupoleField3D::gauss_per_cm(15.0, Vector3::z()))
+            .with(Position::new())
+            .build();
+        let directions = [
+            (Vector3::x(), 1),
+            (-Vector3::x(), 1),
+            (Vector3::y(), 1),
+            (-Vector3::y(), 1),
+            (Vector3::z(), -1),
+            (-Vector3::z(), -1),
+        ];
+        for (direction, polarization) in directions.iter() {
+            sim.world
+                .create_entity()
+                .with(GaussianBeam {
+                    intersection: Vector3::new(0.0, 0.0, 0.0),
+                    e_radius: 0.01,
+                    power: 0.01,
+                    direction: *direction,
+                    rayleigh_range: f64::INFINITY,
+                    ellipticity: 0.0,
+                    focus_offset: 0.0,
+                })
+                .with(CoolingLight::for_transition::<Rubidium87_780D2>(
+                    -12.0,
+                    *polarization,
+                ))
+                .build();
+        }
+
+        for i in 0..200 {
+            let x = i as f64 * 1.0e-5;
+            sim.world
+                .create_entity()
+                .with(Position {
+                    pos: Vector3::new(x, -0.5 * x, 0.2 * x).cast(),
+                })
+                .with(Velocity {
+                    vel: Vector3::new((i % 7) as f64, -((i % 5) as f64), 0.5).cast(),
+                })
+                .with(Force::new())
+                .with(Mass { value: 87.0 })
+                .with(Atom)
+                .with(Rubidium87_780D2)
+                .with(NewlyCreated)
+                .build();
+        }
+
+        for _ in 0..20 {
+            sim.step();
+        }
+
+        let forces = sim.world.read_storage::<Force>();
+        let atoms = sim.world.read_storage::<Atom>();
+        (&forces, &atoms).join().map(|(force, _)| *force).collect()
+    }
+
+    #[test]
+    fn test_serial_and_parallel_forces_are_identical() {
+        let parallel = simulate_mot(false, None);
+        let serial = simulate_mot(true, None);
+        assert_eq!(serial.len(), 200);
+        for (s, p) in serial.iter().zip(parallel.iter()) {
+            assert_eq!(s.force, p.force);
+        }
+        assert!(serial.iter().any(|f| f.force.norm() > 0.0));
+    }
+
+    #[test]
+    fn test_seeded_stochastic_forces_are_reproducible() {
+        let serial = simulate_mot(true, Some(11));
+        let repeat = simulate_mot(true, Some(11));
+        let parallel = simulate_mot(false, Some(11));
+        assert_eq!(serial.len(), 200);
+        for ((s, r), p) in serial.iter().zip(repeat.iter()).zip(parallel.iter()) {
+            assert_eq!(s.force, r.force);
+            assert_eq!(s.force, p.force);
+        }
+
+        // The stochastic options change the forces, so they are really being tested.
+        let deterministic = simulate_mot(true, None);
+        assert!(serial
+            .iter()
+            .zip(deterministic.iter())
+            .any(|(s, d)| s.force != d.force));
+        let other_seed = simulate_mot(true, Some(12));
+        assert!(serial
+            .iter()
+            .zip(other_seed.iter())
+            .any(|(s, o)| s.force != o.force));
+    }
+}
@@ -0,0 +1,199 @@
+//! Implements periodic boundary conditions.
+//!
+//! When the [PeriodicBounds] resource is present, atoms that leave the primary simulation cell
+//! are wrapped back into it through the opposite face. The primary cell is centred on the origin,
+//! and spans `-box_size/2 <= x < box_size/2` along each axis. Only entities with an [Atom] component
+//! are wrapped, so sources, lasers and fields stay where they were placed.
+
+use crate::atom::{Atom, Position};
+use crate::integrator::INTEGRATE_POSITION_SYSTEM_NAME;
+use crate::parallel::{ForceSerial, MaybeParJoin};
+use crate::simulation::Plugin;
+use nalgebra::Vector3;
+use specs::prelude::*;
+
+/// A resource that indicates that the simulation should use periodic boundary conditions.
+#[derive(Clone, Copy)]
+pub struct PeriodicBounds {
+    /// Dimensions of the primary cell along the `x,y,z` axes, in SI units of m.
+    pub box_size: Vector3<f64>,
+}
+impl PeriodicBounds {
+    /// Wraps a position into the primary cell.
+    pub fn wrap(&self, pos: Vector3<f64>) -> Vector3<f64> {
+        let images = pos.component_div(&self.box_size).map(|x| (x + 0.5).floor());
+        pos - self.box_size.component_mul(&images)
+    }
+
+    /// Returns the shortest separation between two points, considering all periodic images.
+    ///
+    /// The argument is the separation `a - b` of the two points, not a position.
+    pub fn minimum_image(&self, separation: Vector3<f64>) -> Vector3<f64> {
+        self.wrap(separation)
+    }
+
+    /// Checks that an interaction of the given range cannot reach the same atom through two different images.
+    ///
+    /// Panics if the range is not less than half the box size along every axis.
+    pub fn check_interaction_range(&self, range: f64) {
+        if range >= self.box_size.min() / 2.0 {
+            panic!(
+                "Interaction range {} must be less than half the periodic box size {:?}.",
+                range, self.box_size
+            );
+        }
+    }
+
+    /// Returns the number of grid cells of the given width along each axis of the primary cell.
+    ///
+    /// Panics if the box size is not a whole number of cells along every axis, because the cells on the faces
+    /// would then overlap their periodic images.
+    pub fn cell_number(&self, width: f64) -> Vector3<i64> {
+        self.box_size.map(|size| {
+            let cells = (size / width).round();
+            if cells < 1.0 || ((size / width) - cells).abs() > 1e-6 {
+                panic!(
+                    "Periodic box size {:?} must be a whole number of cells of width {}.",
+                    self.box_size, width
+                );
+            }
+            cells as i64
+        })
+    }
+}
+
+pub const WRAP_PERIODIC_BOUNDS_SYSTEM_NAME: &str = "wrap_periodic_bounds";
+
+/// Wraps the positions of all atoms into the primary cell, if [PeriodicBounds] are present.
+///
+/// Velocities are unaffected.
+pub struct WrapPeriodicBoundsSystem;
+impl<'a> System<'a> for WrapPeriodicBoundsSystem {
+    type SystemData = (
+        WriteStorage<'a, Position>,
+        ReadStorage<'a, Atom>,
+        Option<Read<'a, PeriodicBounds>>,
+        Option<Read<'a, ForceSerial>>,
+    );
+
+    fn run(&mut self, (mut positions, atoms, bounds, force_serial): Self::SystemData) {
+        match bounds {
+            None => (),
+            Some(bounds) => {
+                (&mut positions, &atoms).maybe_par_for_each(force_serial.is_some(), |(pos, _)| {
+                    pos.pos = bounds.wrap(pos.pos.cast()).cast();
+                });
+            }
+        }
+    }
+}
+
+/// This plugin implements periodic boundary conditions.
+///
+/// See also [crate::periodic].
+pub struct PeriodicBoundsPlugin;
+impl Plugin for PeriodicBoundsPlugin {
+    fn build(&self, builder: &mut crate::simulation::SimulationBuilder) {
+        builder.dispatcher_builder.add(
+            WrapPeriodicBoundsSystem,
+            WRAP_PERIODIC_BOUNDS_SYSTEM_NAME,
+            &[INTEGRATE_POSITION_SYSTEM_NAME],
+        );
+    }
+    fn deps(&self) -> Vec<Box<dyn Plugin>> {
+        Vec::new()
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::*;
+
+    use crate::atom::{Force, Mass, Velocity};
+    use crate::initiate::NewlyCreated;
+    use crate::integrator::Timestep;
+    use crate::simulation::SimulationBuilder;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_wrap() {
+        let bounds = PeriodicBounds {
+            box_size: Vector3::new(2.0, 4.0, 6.0),
+        };
+        let wrapped = bounds.wrap(Vector3::new(1.5, -2.5, 2.0));
+        assert_approx_eq!(wrapped[0], -0.5, 1e-12);
+        assert_approx_eq!(wrapped[1], 1.5, 1e-12);
+        assert_approx_eq!(wrapped[2], 2.0, 1e-12);
+
+        let separation = bounds.minimum_image(Vector3::new(1.9, 0.0, 0.0));
+        assert_approx_eq!(separation[0], -0.1, 1e-12);
+
+        assert_eq!(bounds.cell_number(0.5), Vector3::new(4, 8, 12));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_cell_number_must_tile_box() {
+        let bounds = PeriodicBounds {
+            box_size: Vector3::new(1.0, 1.0, 1.0),
+        };
+        bounds.cell_number(0.3);
+    }
+
+    #[test]
+    fn test_only_atoms_are_wrapped() {
+        let mut world = World::new();
+        world.register::<Position>();
+        world.register::<Atom>();
+        world.insert(PeriodicBounds {
+            box_size: Vector3::new(1.0, 1.0, 1.0),
+        });
+        let outside = Vector3::new(0.7, 0.0, 0.0);
+        let atom = world
+            .create_entity()
+            .with(Position { pos: outside })
+            .with(Atom)
+            .build();
+        let source = world.create_entity().with(Position { pos: outside }).build();
+
+        WrapPeriodicBoundsSystem.run_now(&world);
+
+        let positions = world.read_storage::<Position>();
+        assert_approx_eq!(positions.get(atom).unwrap().pos[0], -0.3, 1e-9);
+        assert_eq!(positions.get(source).unwrap().pos, outside);
+    }
+
+    #[test]
+    fn test_atom_leaving_face_reappears_on_opposite_face() {
+        let mut sim = SimulationBuilder::default().build();
+        sim.world.insert(PeriodicBounds {
+            box_size: Vector3::new(1.0, 1.0, 1.0),
+        });
+        sim.world.insert(Timestep { delta: 1.0e-3 });
+
+        let velocity = Vector3::new(10.0, 0.0, 0.0);
+        let atom = sim
+            .world
+            .create_entity()
+            .with(Position {
+                pos: Vector3::new(0.495, 0.1, 0.0),
+            })
+            .with(Velocity { vel: velocity })
+            .with(Force::new())
+            .with(Mass { value: 87.0 })
+            .with(Atom)
+            .with(NewlyCreated)
+            .build();
+
+        // The first step attaches integrator components to the new atom.
+        sim.step();
+        sim.step();
+
+        let pos = sim.world.read_storage::<Position>().get(atom).unwrap().pos;
+        let vel = sim.world.read_storage::<Velocity>().get(atom).unwrap().vel;
+        assert_approx_eq!(pos[0], -0.495, 1e-9);
+        assert_approx_eq!(pos[1], 0.1, 1e-9);
+        assert_eq!(vel, velocity);
+    }
+}
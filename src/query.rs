@@ -0,0 +1,278 @@
+//! Introspection of the laser beams in a simulation, for example for logging.
+
+use crate::dipole::DipoleLight;
+use crate::laser::beam_source::{beam_profile, BeamSourceRegistry};
+use crate::laser::gaussian::GaussianBeam;
+use crate::laser::index::LaserIndex;
+use crate::laser_cooling::transition::AtomicTransition;
+use crate::laser_cooling::CoolingLight;
+use nalgebra::Vector3;
+use specs::prelude::*;
+use specs::storage::MaskedStorage;
+
+/// The purpose of a laser beam.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BeamType {
+    /// A beam with a `CoolingLight` component.
+    Cooling,
+    /// A beam with a `DipoleLight` component.
+    Dipole,
+}
+
+/// Key parameters of a laser beam, see [list_beams].
+#[derive(Clone, Copy, Debug)]
+pub struct BeamInfo {
+    /// The entity of the laser beam.
+    pub entity: Entity,
+    pub beam_type: BeamType,
+    /// The index of the beam in the per-atom sampler arrays.
+    ///
+    /// `None` if the beam is still being initialised and has not yet been assigned an index.
+    pub index: Option<usize>,
+    /// Name of the [BeamSource](crate::laser::beam_source::BeamSource) type of the beam, for example
+    /// `GaussianBeam`.
+    ///
+    /// `None` if the beam has no [GaussianBeam], and no component of a type registered with a
+    /// [BeamSourcePlugin](crate::laser::beam_source::BeamSourcePlugin).
+    pub profile: Option<&'static str>,
+    /// Power of the beam, in SI units of W.
+    ///
+    /// `None` if the profile has no stated power, see
+    /// [BeamSource::transverse_plane](crate::laser::beam_source::BeamSource::transverse_plane).
+    pub power: Option<f64>,
+    /// The 1/e radius of the beam at the focus, in SI units of m.
+    ///
+    /// `None` for beams that are not a [GaussianBeam].
+    pub e_radius: Option<f64>,
+    /// Wavelength of the beam, in SI units of m.
+    pub wavelength: f64,
+    /// Direction of propagation of the beam.
+    ///
+    /// `None` if the beam has no profile, or its profile has no direction, see
+    /// [BeamSource::direction](crate::laser::beam_source::BeamSource::direction).
+    pub direction: Option<Vector3<f64>>,
+    /// Detuning of the beam from the transition, in units of MHz.
+    ///
+    /// `None` for dipole beams.
+    pub detuning: Option<f64>,
+}
+impl BeamInfo {
+    /// Returns true if the beam has been assigned an index.
+    pub fn is_initiated(&self) -> bool {
+        self.index.is_some()
+    }
+}
+
+/// Lists the cooling and dipole beams in the world, in order of entity id.
+///
+/// Every entity with a `CoolingLight` or `DipoleLight` is listed, whatever the type of its profile: the
+/// [GaussianBeam], or any [BeamSource](crate::laser::beam_source::BeamSource) type registered with a
+/// [BeamSourcePlugin](crate::laser::beam_source::BeamSourcePlugin), such as a tabulated or hollow beam. These are
+/// the beams that are given a [LaserIndex].
+///
+/// The detuning of cooling beams is given relative to the transition `T`.
+/// Storages which have not been registered, for example if the dipole plugin is not used, are skipped.
+pub fn list_beams<T>(world: &World) -> Vec<BeamInfo>
+where
+    T: AtomicTransition,
+{
+    let entities = world.entities();
+    let gaussian = read_if_registered::<GaussianBeam>(world);
+    let indices = read_if_registered::<LaserIndex>(world);
+    let cooling = read_if_registered::<CoolingLight>(world);
+    let dipole = read_if_registered::<DipoleLight>(world);
+    let registry = world.try_fetch::<BeamSourceRegistry>();
+
+    let mut beams = Vec::new();
+    for entity in (&entities).join() {
+        let (beam_type, wavelength, detuning) =
+            if let Some(light) = cooling.as_ref().and_then(|s| s.get(entity)) {
+                let detuning = (light.frequency() - T::frequency()) / 1.0e6;
+                (BeamType::Cooling, light.wavelength, Some(detuning))
+            } else if let Some(light) = dipole.as_ref().and_then(|s| s.get(entity)) {
+                (BeamType::Dipole, light.wavelength, None)
+            } else {
+                continue;
+            };
+        let index = indices
+            .as_ref()
+            .and_then(|s| s.get(entity))
+            .filter(|index| index.initiated)
+            .map(|index| index.index);
+        let profile = beam_profile::<GaussianBeam>(world, entity).or_else(|| {
+            registry
+                .as_ref()
+                .and_then(|registry| registry.beam_profile(world, entity))
+        });
+        let plane = profile.and_then(|profile| profile.plane);
+        beams.push(BeamInfo {
+            entity,
+            beam_type,
+            index,
+            profile: profile.map(|profile| profile.name),
+            power: plane.map(|plane| plane.power),
+            e_radius: gaussian
+                .as_ref()
+                .and_then(|s| s.get(entity))
+                .map(|gaussian| gaussian.e_radius),
+            wavelength,
+            direction: profile.and_then(|profile| profile.direction),
+            detuning,
+        });
+    }
+    beams
+}
+
+fn read_if_registered<C: Component>(world: &World) -> Option<ReadStorage<'_, C>> {
+    if world.has_value::<MaskedStorage<C>>() {
+        Some(world.read_storage::<C>())
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use crate::laser::axicon::HollowConicalBeam;
+    use crate::laser::beam_source::BeamSourcePlugin;
+    use crate::laser::gaussian::calculate_rayleigh_range;
+    use crate::laser::tabulated::{IntensityTable, TabulatedBeam};
+    use crate::laser::LaserPlugin;
+    use crate::simulation::SimulationBuilder;
+    use crate::species::Rubidium87_780D2;
+    use assert_approx_eq::assert_approx_eq;
+
+    fn beam(direction: Vector3<f64>, power: f64, e_radius: f64) -> GaussianBeam {
+        GaussianBeam {
+            intersection: Vector3::new(0.0, 0.0, 0.0),
+            direction,
+            e_radius,
+            power,
+            rayleigh_range: calculate_rayleigh_range(&780.0e-9, &e_radius),
+            ellipticity: 0.0,
+            focus_offset: 0.0,
+        }
+    }
+
+    #[test]
+    fn test_list_beams() {
+        let mut test_world = World::new();
+        test_world.register::<GaussianBeam>();
+        test_world.register::<LaserIndex>();
+        test_world.register::<CoolingLight>();
+        test_world.register::<DipoleLight>();
+
+        let cooling_a = test_world
+            .create_entity()
+            .with(beam(Vector3::x(), 0.01, 0.005))
+            .with(CoolingLight::for_transition::<Rubidium87_780D2>(-12.0, 1))
+            .with(LaserIndex {
+                index: 0,
+                initiated: true,
+            })
+            .build();
+        let cooling_b = test_world
+            .create_entity()
+            .with(beam(-Vector3::x(), 0.02, 0.005))
+            .with(CoolingLight::for_transition::<Rubidium87_780D2>(-6.0, -1))
+            .with(LaserIndex::default())
+            .build();
+        let dipole = test_world
+            .create_entity()
+            .with(beam(Vector3::y(), 5.0, 50.0e-6))
+            .with(DipoleLight {
+                wavelength: 1064.0e-9,
+            })
+            .with(LaserIndex {
+                index: 1,
+                initiated: true,
+            })
+            .build();
+        test_world
+            .create_entity()
+            .with(beam(Vector3::z(), 1.0, 1.0e-3))
+            .build();
+
+        let beams = list_beams::<Rubidium87_780D2>(&test_world);
+        assert_eq!(beams.len(), 3);
+
+        assert_eq!(beams[0].entity, cooling_a);
+        assert_eq!(beams[0].beam_type, BeamType::Cooling);
+        assert_eq!(beams[0].index, Some(0));
+        assert_eq!(beams[0].profile, Some("GaussianBeam"));
+        assert_eq!(beams[0].power, Some(0.01));
+        assert_eq!(beams[0].direction, Some(Vector3::x()));
+        assert_approx_eq!(beams[0].detuning.unwrap(), -12.0, 1e-6);
+
+        assert_eq!(beams[1].entity, cooling_b);
+        assert!(!beams[1].is_initiated());
+        assert_eq!(beams[1].power, Some(0.02));
+        assert_approx_eq!(beams[1].detuning.unwrap(), -6.0, 1e-6);
+
+        assert_eq!(beams[2].entity, dipole);
+        assert_eq!(beams[2].beam_type, BeamType::Dipole);
+        assert_eq!(beams[2].index, Some(1));
+        assert_eq!(beams[2].e_radius, Some(50.0e-6));
+        assert_eq!(beams[2].wavelength, 1064.0e-9);
+        assert_eq!(beams[2].detuning, None);
+    }
+
+    #[test]
+    fn test_list_beams_of_registered_sources() {
+        let mut sim_builder = SimulationBuilder::default();
+        sim_builder.add_plugin(BeamSourcePlugin::<HollowConicalBeam, 4>::default());
+        sim_builder.add_plugin(BeamSourcePlugin::<TabulatedBeam, 4>::default());
+        sim_builder.add_plugin(LaserPlugin::<4>);
+        let mut sim = sim_builder.build();
+        sim.world.register::<CoolingLight>();
+        sim.world.register::<DipoleLight>();
+
+        let hollow = sim
+            .world
+            .create_entity()
+            .with(HollowConicalBeam {
+                intersection: Vector3::new(0.0, 0.0, 0.0),
+                direction: Vector3::z(),
+                radius: 1.0e-3,
+                cone_angle: 0.01,
+                ring_width: 1.0e-4,
+                power: 0.5,
+            })
+            .with(DipoleLight {
+                wavelength: 532.0e-9,
+            })
+            .build();
+        let table = IntensityTable::new((-1.0e-3, -1.0e-3), (1.0e-3, 1.0e-3), 3, 3, vec![1.0; 9]);
+        let tabulated = sim
+            .world
+            .create_entity()
+            .with(TabulatedBeam::new(
+                Vector3::new(0.0, 0.0, 0.0),
+                Vector3::x(),
+                Vector3::y(),
+                table,
+            ))
+            .with(CoolingLight::for_transition::<Rubidium87_780D2>(-12.0, 1))
+            .build();
+
+        let beams = list_beams::<Rubidium87_780D2>(&sim.world);
+        assert_eq!(beams.len(), 2);
+
+        assert_eq!(beams[0].entity, hollow);
+        assert_eq!(beams[0].beam_type, BeamType::Dipole);
+        assert_eq!(beams[0].profile, Some("HollowConicalBeam"));
+        assert_eq!(beams[0].power, Some(0.5));
+        assert_eq!(beams[0].direction, Some(Vector3::z()));
+        assert_eq!(beams[0].e_radius, None);
+        assert!(!beams[0].is_initiated());
+
+        // A tabulated beam has no stated power.
+        assert_eq!(beams[1].entity, tabulated);
+        assert_eq!(beams[1].beam_type, BeamType::Cooling);
+        assert_eq!(beams[1].profile, Some("TabulatedBeam"));
+        assert_eq!(beams[1].power, None);
+        assert_eq!(beams[1].direction, Some(Vector3::x()));
+    }
+}
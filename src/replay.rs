@@ -0,0 +1,292 @@
+//! Records the forces on atoms to file, and replays them in a later run.
+//!
+//! Replaying a recorded force history reproduces the trajectories of the atoms without recomputing the physics,
+//! which isolates the behaviour of the integrator from the calculation of the forces. Atoms are matched between
+//! the runs by their [AtomId].
+//!
+//! The recording is a text file with one line per atom per step, `step id fx fy fz`, where `step` is the
+//! [Step] number, `id` the [AtomId] and `fx fy fz` the force on the atom in SI units of N.
+
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+use crate::atom::{Atom, AtomId, Force};
+use crate::integrator::{Step, INTEGRATE_POSITION_SYSTEM_NAME};
+use crate::simulation::Plugin;
+use nalgebra::Vector3;
+use specs::prelude::*;
+
+/// Writes the force on each atom with an [AtomId] to the recording at the end of each step.
+///
+/// The force is recorded after all forces have been calculated and the atoms integrated. This system requires
+/// direct access to the `World`, and so must be added to the dispatcher as a thread-local system.
+pub struct RecordForcesSystem<W: Write> {
+    writer: W,
+}
+impl<W: Write> RecordForcesSystem<W> {
+    pub fn new(writer: W) -> Self {
+        RecordForcesSystem { writer }
+    }
+}
+impl<'a, W: Write> RunNow<'a> for RecordForcesSystem<W> {
+    fn run_now(&mut self, world: &'a World) {
+        let step = world.fetch::<Step>().n;
+        let forces = world.read_storage::<Force>();
+        let ids = world.read_storage::<AtomId>();
+        let atoms = world.read_storage::<Atom>();
+        for (force, id, _) in (&forces, &ids, &atoms).join() {
+            let force = force.force.cast::<f64>();
+            writeln!(
+                self.writer,
+                "{} {} {:e} {:e} {:e}",
+                step, id.id, force[0], force[1], force[2]
+            )
+            .expect("Could not write recorded forces.");
+        }
+        self.writer
+            .flush()
+            .expect("Could not write recorded forces.");
+    }
+
+    fn setup(&mut self, _world: &mut World) {}
+}
+
+/// This plugin records the forces on all atoms with an [AtomId] to file, see [crate::replay].
+pub struct RecordForcesPlugin {
+    file_name: String,
+}
+impl RecordForcesPlugin {
+    pub fn new(file_name: String) -> Self {
+        RecordForcesPlugin { file_name }
+    }
+}
+impl Plugin for RecordForcesPlugin {
+    fn build(&self, builder: &mut crate::simulation::SimulationBuilder) {
+        let file = match File::create(&self.file_name) {
+            Err(why) => panic!("couldn't open {}: {}", self.file_name, why),
+            Ok(file) => file,
+        };
+        builder
+            .dispatcher_builder
+            .add_thread_local(RecordForcesSystem::new(BufWriter::new(file)));
+    }
+    fn deps(&self) -> Vec<Box<dyn Plugin>> {
+        Vec::new()
+    }
+}
+
+/// A resource holding a recorded force history, indexed by [Step] number and [AtomId].
+#[derive(Default)]
+pub struct RecordedForceSource {
+    forces: HashMap<u64, HashMap<u64, Vector3<f64>>>,
+}
+impl RecordedForceSource {
+    /// Reads a recording written by [RecordForcesSystem].
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, io::Error> {
+        Self::from_reader(BufReader::new(File::open(path)?))
+    }
+
+    /// Reads a recording in the format described in [crate::replay].
+    pub fn from_reader<R: BufRead>(reader: R) -> Result<Self, io::Error> {
+        let mut forces: HashMap<u64, HashMap<u64, Vector3<f64>>> = HashMap::new();
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let invalid = || {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid line in force recording: {}", line),
+                )
+            };
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() != 5 {
+                return Err(invalid());
+            }
+            let step: u64 = fields[0].parse().map_err(|_| invalid())?;
+            let id: u64 = fields[1].parse().map_err(|_| invalid())?;
+            let mut force = Vector3::new(0.0, 0.0, 0.0);
+            for axis in 0..3 {
+                force[axis] = fields[axis + 2].parse().map_err(|_| invalid())?;
+            }
+            forces.entry(step).or_default().insert(id, force);
+        }
+        Ok(RecordedForceSource { forces })
+    }
+
+    /// The recorded force on the atom with the given id at the given step, in SI units of N.
+    pub fn get(&self, step: u64, id: &AtomId) -> Option<Vector3<f64>> {
+        self.forces.get(&step)?.get(&id.id).copied()
+    }
+}
+
+/// Sets the force on each atom to the value in the [RecordedForceSource].
+///
+/// The recorded force replaces any force calculated by other systems. Atoms that are missing from the recording,
+/// or that have no [AtomId], experience no force, and a warning is printed the first time each is encountered.
+#[derive(Default)]
+pub struct ApplyRecordedForceSystem {
+    warned: HashSet<Entity>,
+}
+impl<'a> System<'a> for ApplyRecordedForceSystem {
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, Force>,
+        ReadStorage<'a, AtomId>,
+        ReadStorage<'a, Atom>,
+        ReadExpect<'a, Step>,
+        ReadExpect<'a, RecordedForceSource>,
+    );
+
+    fn run(&mut self, (entities, mut forces, ids, atoms, step, source): Self::SystemData) {
+        for (entity, force, id, _) in (&entities, &mut forces, ids.maybe(), &atoms).join() {
+            match id.and_then(|id| source.get(step.n, id)) {
+                Some(recorded) => force.force = recorded.cast(),
+                None => {
+                    force.force = Vector3::new(0.0, 0.0, 0.0).cast();
+                    if self.warned.insert(entity) {
+                        eprintln!(
+                            "Warning: no recorded force for atom {:?} at step {}, applying zero force.",
+                            id, step.n
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// This plugin replays the forces of a [RecordedForceSource], see [crate::replay].
+///
+/// The recorded forces replace the forces calculated by other systems, so this plugin should be added after all
+/// plugins that apply forces. Typically, the plugins that apply forces are left out of the replay entirely.
+pub struct RecordedForcePlugin {
+    file_name: String,
+}
+impl RecordedForcePlugin {
+    pub fn new(file_name: String) -> Self {
+        RecordedForcePlugin { file_name }
+    }
+}
+impl Plugin for RecordedForcePlugin {
+    fn build(&self, builder: &mut crate::simulation::SimulationBuilder) {
+        let source = match RecordedForceSource::from_file(&self.file_name) {
+            Err(why) => panic!("couldn't read {}: {}", self.file_name, why),
+            Ok(source) => source,
+        };
+        builder.world.insert(source);
+        // Ensure the recorded forces are applied after all other forces have been calculated.
+        builder.dispatcher_builder.add_barrier();
+        builder.dispatcher_builder.add(
+            ApplyRecordedForceSystem::default(),
+            "apply_recorded_force",
+            &["clear", INTEGRATE_POSITION_SYSTEM_NAME],
+        );
+    }
+    fn deps(&self) -> Vec<Box<dyn Plugin>> {
+        Vec::new()
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use crate::atom::{Mass, Position, Velocity};
+    use crate::constant::{AMU, PI};
+    use crate::custom_force::{CustomForceField, CustomForcePlugin};
+    use crate::initiate::NewlyCreated;
+    use crate::integrator::Timestep;
+    use crate::simulation::{Simulation, SimulationBuilder};
+
+    fn add_atom(sim: &mut Simulation, id: Option<u64>, pos: Vector3<f64>) -> Entity {
+        let builder = sim
+            .world
+            .create_entity()
+            .with(Position { pos: pos.cast() })
+            .with(Velocity {
+                vel: Vector3::new(0.0, 0.1, 0.0).cast(),
+            })
+            .with(Force::new())
+            .with(Mass { value: 87.0 })
+            .with(Atom)
+            .with(NewlyCreated);
+        match id {
+            Some(id) => builder.with(AtomId { id }).build(),
+            None => builder.build(),
+        }
+    }
+
+    fn positions(sim: &Simulation, atoms: &[Entity]) -> Vec<Vector3<f64>> {
+        let storage = sim.world.read_storage::<Position>();
+        atoms
+            .iter()
+            .map(|atom| storage.get(*atom).unwrap().pos.cast())
+            .collect()
+    }
+
+    #[test]
+    fn test_replayed_forces_reproduce_trajectory() {
+        let file_name = std::env::temp_dir()
+            .join("atomecs_test_recorded_forces.txt")
+            .to_str()
+            .unwrap()
+            .to_string();
+        let starts = [
+            Vector3::new(1.0e-3, 0.0, 0.0),
+            Vector3::new(0.0, -2.0e-3, 1.0e-3),
+        ];
+        let dt = 1.0e-6;
+        let steps = 1000;
+
+        // Record the forces of atoms in a harmonic trap.
+        let recorded = {
+            let mut sim_builder = SimulationBuilder::default();
+            sim_builder.add_plugin(CustomForcePlugin);
+            sim_builder.add_plugin(RecordForcesPlugin::new(file_name.clone()));
+            let mut sim = sim_builder.build();
+            let spring_constant = 87.0 * AMU * (2.0 * PI * 100.0).powi(2);
+            sim.world.insert(CustomForceField::new(move |pos, _vel| {
+                -spring_constant * pos
+            }));
+            sim.world.insert(Timestep { delta: dt });
+            let atoms: Vec<Entity> = starts
+                .iter()
+                .enumerate()
+                .map(|(i, start)| add_atom(&mut sim, Some(i as u64), *start))
+                .collect();
+            for _ in 0..steps {
+                sim.step();
+            }
+            positions(&sim, &atoms)
+        };
+
+        // Replay the forces without the harmonic trap. The final atom is missing from the recording.
+        let mut sim_builder = SimulationBuilder::default();
+        sim_builder.add_plugin(RecordedForcePlugin::new(file_name.clone()));
+        let mut sim = sim_builder.build();
+        sim.world.insert(Timestep { delta: dt });
+        let mut atoms: Vec<Entity> = starts
+            .iter()
+            .enumerate()
+            .map(|(i, start)| add_atom(&mut sim, Some(i as u64), *start))
+            .collect();
+        atoms.push(add_atom(&mut sim, Some(99), starts[0]));
+        for _ in 0..steps {
+            sim.step();
+        }
+        let replayed = positions(&sim, &atoms);
+
+        assert_eq!(replayed[0], recorded[0]);
+        assert_eq!(replayed[1], recorded[1]);
+        assert!(recorded[0][0] < starts[0][0]);
+        // The missing atom moves without any force, along its initial velocity.
+        assert_eq!(replayed[2][0], starts[0][0]);
+        assert!(replayed[2][1] > 0.0);
+        assert_eq!(replayed[2][2], starts[0][2]);
+        std::fs::remove_file(&file_name).unwrap();
+    }
+}
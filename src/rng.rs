@@ -0,0 +1,209 @@
+//! A seeded source of randomness whose state can be saved and restored.
+//!
+//! By default the stochastic systems draw from the thread-local generator of [rand], so every run is different.
+//! When the [DeterministicRng] resource is present, the following systems draw from it instead, which makes these
+//! parts of the run reproducible from its seed:
+//!
+//! * background loss, see [crate::destructor::ApplyBackgroundLossSystem],
+//! * two-body loss and collisions, see [crate::collisions],
+//! * fluctuations of the number of scattered photons, see
+//!   [crate::laser_cooling::photons_scattered::CalculateActualPhotonsScatteredSystem],
+//! * the recoil of spontaneous emission, see [crate::laser_cooling::force::ApplyEmissionForceSystem].
+//!
+//! Atom sources still use the thread-local generator.
+//!
+//! Systems that iterate over atoms in parallel use [RngStreams], which derive an independent generator for each atom
+//! from a single draw of the [DeterministicRng], so that the draws do not depend on the number of threads.
+//!
+//! The state of a [DeterministicRng] can be saved with [DeterministicRng::snapshot] and restored with
+//! [DeterministicRng::restore]. After a restore, the generator produces exactly the draws that followed the
+//! snapshot. A controller can use this to pin the randomness of a critical window, such as a measurement pulse,
+//! so that the window is identical between runs while the rest of the run uses live randomness, or to replay a
+//! particular event while debugging.
+
+use rand::rngs::{StdRng, ThreadRng};
+use rand::{Error, RngCore, SeedableRng};
+use specs::Entity;
+
+/// A resource that provides seeded randomness to the systems that support it, see [crate::rng].
+#[derive(Clone, Debug)]
+pub struct DeterministicRng {
+    rng: StdRng,
+    seed: Option<u64>,
+}
+impl DeterministicRng {
+    /// Creates a generator from a seed. Generators with the same seed produce the same draws.
+    pub fn new(seed: u64) -> Self {
+        DeterministicRng {
+            rng: StdRng::seed_from_u64(seed),
+            seed: Some(seed),
+        }
+    }
+
+    /// Creates a generator seeded from the thread-local generator, for live randomness that can still be
+    /// snapshotted.
+    pub fn from_entropy() -> Self {
+        DeterministicRng {
+            rng: StdRng::from_rng(rand::thread_rng()).expect("Could not seed the generator."),
+            seed: None,
+        }
+    }
+
+    /// The seed the generator was created from, or `None` if it was seeded from entropy.
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    /// Saves the current state of the generator.
+    pub fn snapshot(&self) -> RngSnapshot {
+        RngSnapshot {
+            rng: self.rng.clone(),
+        }
+    }
+
+    /// Restores a state saved by [DeterministicRng::snapshot], so that the draws that followed the snapshot are
+    /// repeated. A snapshot can be restored any number of times.
+    pub fn restore(&mut self, snapshot: &RngSnapshot) {
+        self.rng = snapshot.rng.clone();
+    }
+}
+impl RngCore for DeterministicRng {
+    fn next_u32(&mut self) -> u32 {
+        self.rng.next_u32()
+    }
+    fn next_u64(&mut self) -> u64 {
+        self.rng.next_u64()
+    }
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.rng.fill_bytes(dest)
+    }
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.rng.try_fill_bytes(dest)
+    }
+}
+
+/// The saved state of a [DeterministicRng].
+#[derive(Clone, Debug)]
+pub struct RngSnapshot {
+    rng: StdRng,
+}
+
+/// Independent generators for the items visited by one run of a stochastic system, such as atoms or collision cells.
+///
+/// When created from a [DeterministicRng], a single seed is drawn from it, and the generator of each item is seeded
+/// from this draw and a key that identifies the item. The draws then depend neither on the order in which the items
+/// are visited nor on the number of threads. Otherwise, each item uses the thread-local generator of [rand].
+#[derive(Clone, Copy, Debug)]
+pub struct RngStreams {
+    seed: Option<u64>,
+}
+impl RngStreams {
+    /// Creates the generators for one run of a system, drawing a seed from `deterministic` if it is present.
+    pub fn new(deterministic: Option<&mut DeterministicRng>) -> Self {
+        RngStreams {
+            seed: deterministic.map(|rng| rng.next_u64()),
+        }
+    }
+
+    /// The generator of the item identified by `key`.
+    pub fn stream(&self, key: u64) -> RngStream {
+        match self.seed {
+            Some(seed) => RngStream::Seeded(StdRng::seed_from_u64(
+                seed ^ key.wrapping_mul(0x9E37_79B9_7F4A_7C15),
+            )),
+            None => RngStream::Thread(rand::thread_rng()),
+        }
+    }
+
+    /// The generator of an entity, keyed by its id and generation.
+    pub fn entity_stream(&self, entity: Entity) -> RngStream {
+        self.stream(((entity.gen().id() as u32 as u64) << 32) | entity.id() as u64)
+    }
+}
+
+/// A generator returned by [RngStreams].
+pub enum RngStream {
+    Seeded(StdRng),
+    Thread(ThreadRng),
+}
+impl RngCore for RngStream {
+    fn next_u32(&mut self) -> u32 {
+        match self {
+            RngStream::Seeded(rng) => rng.next_u32(),
+            RngStream::Thread(rng) => rng.next_u32(),
+        }
+    }
+    fn next_u64(&mut self) -> u64 {
+        match self {
+            RngStream::Seeded(rng) => rng.next_u64(),
+            RngStream::Thread(rng) => rng.next_u64(),
+        }
+    }
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        match self {
+            RngStream::Seeded(rng) => rng.fill_bytes(dest),
+            RngStream::Thread(rng) => rng.fill_bytes(dest),
+        }
+    }
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        match self {
+            RngStream::Seeded(rng) => rng.try_fill_bytes(dest),
+            RngStream::Thread(rng) => rng.try_fill_bytes(dest),
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use rand::Rng;
+
+    #[test]
+    fn test_restore_reproduces_draws() {
+        let mut rng = DeterministicRng::from_entropy();
+        let _: Vec<f64> = (0..17).map(|_| rng.gen()).collect();
+
+        let snapshot = rng.snapshot();
+        let first: Vec<f64> = (0..100).map(|_| rng.gen_range(0.0..1.0)).collect();
+        let after_first: u64 = rng.gen();
+
+        rng.restore(&snapshot);
+        let second: Vec<f64> = (0..100).map(|_| rng.gen_range(0.0..1.0)).collect();
+        assert_eq!(first, second);
+        assert_eq!(rng.gen::<u64>(), after_first);
+
+        // The snapshot can be restored again, after further draws.
+        let _: Vec<u32> = (0..5).map(|_| rng.gen()).collect();
+        rng.restore(&snapshot);
+        let third: Vec<f64> = (0..100).map(|_| rng.gen_range(0.0..1.0)).collect();
+        assert_eq!(first, third);
+
+        // Generators with the same seed agree.
+        let mut a = DeterministicRng::new(7);
+        let mut b = DeterministicRng::new(7);
+        assert_eq!(a.gen::<[u64; 4]>(), b.gen::<[u64; 4]>());
+    }
+
+    #[test]
+    fn test_streams_are_reproducible() {
+        let draws = |seed: u64, keys: &[u64]| -> Vec<f64> {
+            let mut rng = DeterministicRng::new(seed);
+            let streams = RngStreams::new(Some(&mut rng));
+            keys.iter().map(|key| streams.stream(*key).gen()).collect()
+        };
+        // The draw of each key does not depend on the order in which the keys are visited.
+        let forward = draws(3, &[0, 1, 2, 3]);
+        let mut backward = draws(3, &[3, 2, 1, 0]);
+        backward.reverse();
+        assert_eq!(forward, backward);
+        // Different keys give different draws.
+        assert!(forward.windows(2).all(|pair| pair[0] != pair[1]));
+
+        // Each run of a system draws a new seed.
+        let mut rng = DeterministicRng::new(3);
+        let first = RngStreams::new(Some(&mut rng)).stream(0).gen::<u64>();
+        let second = RngStreams::new(Some(&mut rng)).stream(0).gen::<u64>();
+        assert_ne!(first, second);
+    }
+}
@@ -8,6 +8,7 @@
 // This pattern is also used elsewhere, eg `MagneticFieldSampler`.
 
 use crate::atom::Position;
+use crate::dispatch::SystemDispatcherBuilder;
 use crate::initiate::NewlyCreated;
 use crate::shapes::{Cuboid, Cylinder, Sphere, Volume};
 use crate::simulation::Plugin;
@@ -76,7 +77,7 @@ where
                 match result.result {
                     Result::Reject => (),
                     _ => {
-                        let contained = volume.contains(&vol_pos.pos, &pos.pos);
+                        let contained = volume.contains(&vol_pos.pos.cast(), &pos.pos.cast());
                         match sim_volume.volume_type {
                             VolumeType::Inclusive => {
                                 if contained {
@@ -173,7 +174,7 @@ impl Plugin for SimulationRegionPlugin {
 ///
 /// `deps`: any dependencies that must be completed before the `sim_region` systems run.
 fn add_systems_to_dispatch(
-    builder: &mut DispatcherBuilder<'static, 'static>,
+    builder: &mut impl SystemDispatcherBuilder,
     deps: &[&str],
 ) {
     builder.add(ClearRegionTestSystem, "clear_region_test", deps);
@@ -260,11 +261,13 @@ pub mod tests {
         register_components(&mut test_world);
         test_world.register::<Position>();
 
-        let sphere_pos = Vector3::new(1.0, 1.0, 1.0);
+        let sphere_pos: Vector3<f64> = Vector3::new(1.0, 1.0, 1.0);
         let sphere_radius = 1.0;
         test_world
             .create_entity()
-            .with(Position { pos: sphere_pos })
+            .with(Position {
+                pos: sphere_pos.cast(),
+            })
             .with(Sphere {
                 radius: sphere_radius,
             })
@@ -286,7 +289,7 @@ pub mod tests {
                 .with(RegionTest {
                     result: Result::Untested,
                 })
-                .with(Position { pos })
+                .with(Position { pos: pos.cast() })
                 .build();
 
             let delta = pos - sphere_pos;
@@ -320,11 +323,13 @@ pub mod tests {
         register_components(&mut test_world);
         test_world.register::<Position>();
 
-        let cuboid_pos = Vector3::new(1.0, 1.0, 1.0);
+        let cuboid_pos: Vector3<f64> = Vector3::new(1.0, 1.0, 1.0);
         let half_width = Vector3::new(0.2, 0.3, 0.1);
         test_world
             .create_entity()
-            .with(Position { pos: cuboid_pos })
+            .with(Position {
+                pos: cuboid_pos.cast(),
+            })
             .with(Cuboid {
                 half_width,
             })
@@ -346,7 +351,7 @@ pub mod tests {
                 .with(RegionTest {
                     result: Result::Untested,
                 })
-                .with(Position { pos })
+                .with(Position { pos: pos.cast() })
                 .build();
 
             let delta = pos - cuboid_pos;
@@ -3,9 +3,11 @@
 //! Allows a simulation to be created in a flexible manner by combining different plugins.
 
 use std::{any::{Any, type_name}};
+use nalgebra::Vector3;
 use specs::prelude::*;
 
-use crate::{magnetic::MagneticsPlugin, atom::{AtomPlugin, ClearForceSystem}, sim_region::SimulationRegionPlugin, integrator::{VelocityVerletIntegratePositionSystem, INTEGRATE_POSITION_SYSTEM_NAME, INTEGRATE_VELOCITY_SYSTEM_NAME, VelocityVerletIntegrateVelocitySystem, Step}, gravity::GravityPlugin, destructor::DestroyAtomsPlugin, output::console_output::ConsoleOutputSystem};
+use crate::dispatch::{validate_dispatch_order, RecordingDispatcherBuilder, CLEAR_FORCE_SYSTEM_NAME};
+use crate::{magnetic::MagneticsPlugin, atom::{AtomPlugin, ClearForceSystem, Atom, Position, Velocity, weighted_atom_number}, sim_region::SimulationRegionPlugin, integrator::{VelocityVerletIntegratePositionSystem, INTEGRATE_POSITION_SYSTEM_NAME, INTEGRATE_VELOCITY_SYSTEM_NAME, VelocityVerletIntegrateVelocitySystem, ClampStabilitySystem, CLAMP_STABILITY_SYSTEM_NAME, Step, SimulationTime, Timestep}, gravity::GravityPlugin, periodic::PeriodicBoundsPlugin, destructor::DestroyAtomsPlugin, initiate::{ValidateNewAtomsSystem, VALIDATE_NEW_ATOMS_SYSTEM_NAME}, output::{console_output::ConsoleOutputSystem, trigger::OutputTrigger}};
 
 /// A simulation in AtomECS.
 pub struct Simulation {
@@ -13,35 +15,194 @@ pub struct Simulation {
     pub dispatcher: Dispatcher<'static, 'static>
 }
 impl Simulation {
+    /// Advances the simulation by one step.
+    ///
+    /// Call [Simulation::finish] once done, rather than dropping the simulation, so that outputs with a wall-clock
+    /// [OutputTrigger] write their final frame.
     pub fn step(&mut self) {
         self.dispatcher.dispatch(&self.world);
         self.world.maintain();
     }
+
+    /// Ends the simulation and returns its world.
+    ///
+    /// The systems are disposed, which writes the final frame of outputs with a wall-clock
+    /// [OutputTrigger] and flushes the outputs.
+    pub fn finish(self) -> World {
+        let Simulation { mut world, dispatcher } = self;
+        dispatcher.dispose(&mut world);
+        world
+    }
+}
+
+/// The outcome of a simulation run with [run_simulation] or [run_until].
+pub struct SimulationResult {
+    /// The world at the end of the run, for any further analysis.
+    pub world: World,
+    /// The elapsed time of the simulation at the end of the run.
+    pub time: SimulationTime,
+    /// Number of steps performed by this run.
+    pub steps_run: u64,
+    /// The final position of each atom, in SI units of m.
+    pub final_positions: Vec<(Entity, Vector3<f64>)>,
+    /// The final velocity of each atom, in SI units of m/s.
+    pub final_velocities: Vec<(Entity, Vector3<f64>)>,
+    /// Warnings raised during the run.
+    pub warnings: Vec<String>,
+}
+impl SimulationResult {
+    /// Number of atoms remaining at the end of the run.
+    pub fn atom_number(&self) -> usize {
+        self.final_positions.len()
+    }
+
+    /// Number of real atoms represented by the atoms remaining at the end of the run, the sum of their
+    /// [StatisticalWeight](crate::atom::StatisticalWeight) and [SuperAtomWeight](crate::atom::SuperAtomWeight).
+    /// Unweighted atoms count as one.
+    pub fn real_atom_number(&self) -> f64 {
+        weighted_atom_number(&self.world)
+    }
+}
+
+/// A condition that ends a simulation run with [run_until].
+#[derive(Clone, Debug)]
+pub enum StopCondition {
+    /// Stop after the given number of steps have been run.
+    Steps(u64),
+    /// Stop once the elapsed [SimulationTime] reaches the given time, in SI units of s.
+    ///
+    /// The run ends on the first step at which the elapsed time is at least the given time, so a time that is not a
+    /// multiple of the [Timestep] is rounded up to the next step.
+    Time(f64),
+    /// Stop once fewer than the given number of atoms remain.
+    AtomCountBelow(usize),
+    /// Stop once any of the conditions is met.
+    Any(Vec<StopCondition>),
+}
+impl StopCondition {
+    /// Combines two conditions, so that the run stops when either is met.
+    pub fn or(self, other: StopCondition) -> StopCondition {
+        match self {
+            StopCondition::Any(mut conditions) => {
+                conditions.push(other);
+                StopCondition::Any(conditions)
+            }
+            condition => StopCondition::Any(vec![condition, other]),
+        }
+    }
+
+    /// Returns true if the condition is met, given the world and the number of steps run so far.
+    pub fn is_met(&self, world: &World, steps_run: u64) -> bool {
+        match self {
+            StopCondition::Steps(steps) => steps_run >= *steps,
+            StopCondition::Time(time) => {
+                let timestep = world.read_resource::<Timestep>();
+                let elapsed = SimulationTime::new(&world.read_resource::<Step>(), &timestep).time;
+                // Tolerate rounding in the accumulated time, so that a multiple of the timestep stops exactly.
+                elapsed >= time - 1e-6 * timestep.delta
+            }
+            StopCondition::AtomCountBelow(number) => world.read_storage::<Atom>().join().count() < *number,
+            StopCondition::Any(conditions) => conditions.iter().any(|condition| condition.is_met(world, steps_run)),
+        }
+    }
+}
+
+/// Runs a [Simulation] for a fixed number of steps, and summarises the final state.
+///
+/// Each step dispatches the systems and then maintains the world, so that entities created or deleted during the step
+/// are updated. The run stops early, with a warning, if all atoms are lost. Warnings are also printed as they occur.
+///
+/// The simulation is consumed so that its systems are disposed at the end of the run, which flushes any file outputs.
+/// The world is returned in the [SimulationResult].
+pub fn run_simulation(simulation: Simulation, steps: u64) -> SimulationResult {
+    run_until(simulation, StopCondition::Steps(steps))
+}
+
+/// Runs a [Simulation] until the [StopCondition] is met, and summarises the final state.
+///
+/// The condition is checked before each step, so a condition that is already met runs no steps. As for
+/// [run_simulation], the run also stops early, with a warning, if all atoms are lost.
+pub fn run_until(simulation: Simulation, condition: StopCondition) -> SimulationResult {
+    let Simulation { mut world, mut dispatcher } = simulation;
+    let mut warnings = Vec::new();
+    let mut had_atoms = world.read_storage::<Atom>().join().next().is_some();
+    let mut steps_run = 0;
+    while !condition.is_met(&world, steps_run) {
+        dispatcher.dispatch(&world);
+        world.maintain();
+        steps_run += 1;
+
+        let has_atoms = world.read_storage::<Atom>().join().next().is_some();
+        if had_atoms && !has_atoms {
+            warnings.push(format!("All atoms were lost after {} steps, stopping the simulation.", steps_run));
+            break;
+        }
+        had_atoms |= has_atoms;
+    }
+    if !had_atoms {
+        warnings.push("No atoms were simulated.".to_string());
+    }
+    for warning in warnings.iter() {
+        eprintln!("Warning: {}", warning);
+    }
+    // Dispose the systems to write final frames and flush their outputs.
+    dispatcher.dispose(&mut world);
+
+    let time = SimulationTime::new(&world.read_resource::<Step>(), &world.read_resource::<Timestep>());
+    let (final_positions, final_velocities) = {
+        let entities = world.entities();
+        let positions = world.read_storage::<Position>();
+        let velocities = world.read_storage::<Velocity>();
+        let atoms = world.read_storage::<Atom>();
+        (&entities, &positions, velocities.maybe(), &atoms)
+            .join()
+            .map(|(entity, pos, vel, _)| {
+                let vel = vel.map_or(Vector3::new(0.0, 0.0, 0.0), |vel| vel.vel.cast());
+                ((entity, pos.pos.cast()), (entity, vel))
+            })
+            .unzip()
+    };
+    SimulationResult {
+        world,
+        time,
+        steps_run,
+        final_positions,
+        final_velocities,
+        warnings,
+    }
 }
 
 /// Used to construct a simulation in AtomECS.
 pub struct SimulationBuilder {
     pub world: World,
-    pub dispatcher_builder: DispatcherBuilder<'static, 'static>,
+    /// Builds the dispatcher of the simulation, and records the order of its systems, see [crate::dispatch].
+    ///
+    /// This was a plain [DispatcherBuilder](specs::DispatcherBuilder) before; it still dereferences to one.
+    pub dispatcher_builder: RecordingDispatcherBuilder,
     end_frame_systems_added: bool,
+    validate_dispatch_order: bool,
+    console_output: OutputTrigger,
     plugins: Vec<Box<dyn Plugin>>
 }
 impl SimulationBuilder {
     pub fn new() -> Self {
-        let mut dispatcher_builder = DispatcherBuilder::default();
+        let mut dispatcher_builder = RecordingDispatcherBuilder::default();
 
+        dispatcher_builder.add(ValidateNewAtomsSystem, VALIDATE_NEW_ATOMS_SYSTEM_NAME, &[]);
         dispatcher_builder.add(
             VelocityVerletIntegratePositionSystem,
             INTEGRATE_POSITION_SYSTEM_NAME,
-            &[],
+            &[VALIDATE_NEW_ATOMS_SYSTEM_NAME],
         );
         dispatcher_builder
-            .add(ClearForceSystem, "clear", &[INTEGRATE_POSITION_SYSTEM_NAME]);
+            .add(ClearForceSystem, CLEAR_FORCE_SYSTEM_NAME, &[INTEGRATE_POSITION_SYSTEM_NAME]);
 
         SimulationBuilder {
             world: World::new(),
             dispatcher_builder,
             end_frame_systems_added: false,
+            validate_dispatch_order: false,
+            console_output: OutputTrigger::EveryNSteps(100),
             plugins: Vec::new()
         }
     }
@@ -61,6 +222,19 @@ impl SimulationBuilder {
         }
     }
 
+    /// Checks the order of the systems with [validate_dispatch_order] when the simulation is built, and panics with
+    /// a description of the first problem found.
+    pub fn set_dispatch_order_validation(&mut self, enabled: bool) {
+        self.validate_dispatch_order = enabled;
+    }
+
+    /// Sets the steps on which the number of atoms is written to the console, every 100 steps by default.
+    ///
+    /// Has no effect once [SimulationBuilder::add_end_frame_systems] has been called.
+    pub fn set_console_output(&mut self, trigger: OutputTrigger) {
+        self.console_output = trigger;
+    }
+
     /// Builds a [Simulation] from the [SimulationBuilder].
     pub fn build(mut self) -> Simulation {
 
@@ -68,6 +242,12 @@ impl SimulationBuilder {
             self.add_end_frame_systems();
         }
 
+        if self.validate_dispatch_order {
+            if let Err(error) = validate_dispatch_order(&self.dispatcher_builder) {
+                panic!("Invalid system order: {}", error);
+            }
+        }
+
         let mut dispatcher = self.dispatcher_builder.build();
         dispatcher.setup(&mut self.world);
 
@@ -88,7 +268,16 @@ impl SimulationBuilder {
                 // No deps specified now - implicit in the barrier.
             ],
         );
-        self.dispatcher_builder.add(ConsoleOutputSystem, "", &[INTEGRATE_VELOCITY_SYSTEM_NAME]);
+        self.dispatcher_builder.add(
+            ClampStabilitySystem,
+            CLAMP_STABILITY_SYSTEM_NAME,
+            &[INTEGRATE_VELOCITY_SYSTEM_NAME],
+        );
+        self.dispatcher_builder.add(
+            ConsoleOutputSystem::new(self.console_output),
+            "",
+            &[CLAMP_STABILITY_SYSTEM_NAME],
+        );
         self.end_frame_systems_added = true;
     }
 }
@@ -100,6 +289,7 @@ impl Default for SimulationBuilder {
         builder.add_plugin(MagneticsPlugin);
         builder.add_plugin(SimulationRegionPlugin);
         builder.add_plugin(GravityPlugin);
+        builder.add_plugin(PeriodicBoundsPlugin);
         builder.add_plugin(DestroyAtomsPlugin);
         builder
     }
@@ -111,4 +301,99 @@ pub trait Plugin : Any + Send + Sync {
     fn build(&self, builder: &mut SimulationBuilder);
     fn name(&self) -> &str { type_name::<Self>() }
     fn deps(&self) -> Vec::<Box<dyn Plugin>>;
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use crate::atom::{Force, Mass};
+    use crate::destructor::ToBeDestroyed;
+    use crate::integrator::OldForce;
+    use assert_approx_eq::assert_approx_eq;
+
+    fn create_atom(world: &mut World, pos: Vector3<f64>, vel: Vector3<f64>) -> Entity {
+        world
+            .create_entity()
+            .with(Position { pos: pos.cast() })
+            .with(Velocity { vel: vel.cast() })
+            .with(Force::new())
+            .with(OldForce::default())
+            .with(Mass { value: 87.0 })
+            .with(Atom)
+            .build()
+    }
+
+    #[test]
+    fn test_run_simulation_free_expansion() {
+        let mut sim = SimulationBuilder::default().build();
+        let dt = 1.0e-6;
+        sim.world.insert(Timestep { delta: dt });
+        let starts = [
+            (Vector3::new(0.0, 0.0, 0.0), Vector3::new(1.0, 0.0, 0.0)),
+            (Vector3::new(1.0e-3, 0.0, 0.0), Vector3::new(0.0, -2.0, 0.5)),
+        ];
+        let atoms: Vec<Entity> = starts.iter().map(|(pos, vel)| create_atom(&mut sim.world, *pos, *vel)).collect();
+
+        let steps = 1000;
+        let result = run_simulation(sim, steps);
+        assert_eq!(result.steps_run, steps);
+        assert_eq!(result.time.step, steps);
+        assert_approx_eq!(result.time.time, steps as f64 * dt, 1e-15);
+        assert_eq!(result.atom_number(), 2);
+        assert_eq!(result.real_atom_number(), 2.0);
+        assert!(result.warnings.is_empty());
+        for (atom, (start, vel)) in atoms.iter().zip(starts.iter()) {
+            let (_, pos) = result.final_positions.iter().find(|(entity, _)| entity == atom).unwrap();
+            let expected = start + vel * steps as f64 * dt;
+            assert!((pos - expected).norm() < 1e-12);
+            let (_, final_vel) = result.final_velocities.iter().find(|(entity, _)| entity == atom).unwrap();
+            assert_eq!(final_vel, vel);
+        }
+    }
+
+    #[test]
+    fn test_run_simulation_stops_when_atoms_are_lost() {
+        let mut sim = SimulationBuilder::default().build();
+        sim.world.insert(Timestep { delta: 1.0e-6 });
+        let atom = create_atom(&mut sim.world, Vector3::new(0.0, 0.0, 0.0), Vector3::new(0.0, 0.0, 0.0));
+        sim.world.write_storage::<ToBeDestroyed>().insert(atom, ToBeDestroyed).unwrap();
+
+        let result = run_simulation(sim, 100);
+        assert_eq!(result.steps_run, 1);
+        assert_eq!(result.atom_number(), 0);
+        assert_eq!(result.warnings.len(), 1);
+    }
+
+    #[test]
+    fn test_run_until_time_stops_at_correct_step() {
+        let dt = 1.0e-6;
+        for (time, expected_steps) in [(2.5e-4, 250), (2.505e-4, 251)].iter() {
+            let mut sim = SimulationBuilder::default().build();
+            sim.world.insert(Timestep { delta: dt });
+            create_atom(&mut sim.world, Vector3::new(0.0, 0.0, 0.0), Vector3::new(1.0, 0.0, 0.0));
+            let result = run_until(sim, StopCondition::Time(*time));
+            assert_eq!(result.steps_run, *expected_steps);
+            assert_eq!(result.time.step, *expected_steps);
+            assert!(result.warnings.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_run_until_combined_conditions_stop_at_first() {
+        let mut sim = SimulationBuilder::default().build();
+        sim.world.insert(Timestep { delta: 1.0e-6 });
+        create_atom(&mut sim.world, Vector3::new(0.0, 0.0, 0.0), Vector3::new(0.0, 0.0, 0.0));
+        let condition = StopCondition::Time(1.0e-3)
+            .or(StopCondition::Steps(40))
+            .or(StopCondition::AtomCountBelow(1));
+        let result = run_until(sim, condition);
+        assert_eq!(result.steps_run, 40);
+
+        let mut sim = SimulationBuilder::default().build();
+        sim.world.insert(Timestep { delta: 1.0e-6 });
+        create_atom(&mut sim.world, Vector3::new(0.0, 0.0, 0.0), Vector3::new(0.0, 0.0, 0.0));
+        let result = run_until(sim, StopCondition::AtomCountBelow(2).or(StopCondition::Steps(40)));
+        assert_eq!(result.steps_run, 0);
+    }
+}
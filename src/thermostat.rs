@@ -0,0 +1,197 @@
+//! A velocity-rescaling thermostat, for preparing a cloud at a chosen temperature.
+//!
+//! The thermostat is a preparation tool, and does not model any physical process: it rescales the velocities of
+//! the atoms directly, without any force or exchange of momentum with a bath. Use it to bring a cloud to a target
+//! temperature quickly before the experiment of interest, then remove the [Thermostat] resource.
+//!
+//! The [Thermostat] follows Berendsen et al., J. Chem. Phys. 81, 3684 (1984). Each step, the velocities relative to
+//! the centre of mass are scaled by `sqrt(1 + dt / tau (T_0 / T - 1))`, where `T` is the measured temperature, `T_0`
+//! the target temperature and `tau` the coupling time. In the absence of other forces, the temperature relaxes
+//! exponentially to the target, `T(t) = T_0 + (T(0) - T_0) exp(-t / tau)`. A long coupling time gives a weak
+//! coupling, which disturbs the dynamics less. The velocity distribution is not made thermal: its shape is
+//! unchanged, and only its width is rescaled.
+
+use crate::atom::{total_weight, Atom, Mass, StatisticalWeight, SuperAtomWeight, Velocity};
+use crate::equilibrium::measure_weighted_temperature;
+use crate::integrator::{Timestep, INTEGRATE_VELOCITY_SYSTEM_NAME};
+use crate::simulation::Plugin;
+use nalgebra::Vector3;
+use specs::prelude::*;
+
+/// A resource that rescales the velocities of the atoms towards a target temperature, see [crate::thermostat].
+#[derive(Clone, Copy, Debug)]
+pub struct Thermostat {
+    /// The temperature towards which the atoms are driven, in SI units of K.
+    pub target_temperature: f64,
+    /// The time constant of the relaxation to the target temperature, in SI units of s.
+    ///
+    /// Must be at least the timestep of the simulation.
+    pub coupling_time: f64,
+}
+impl Thermostat {
+    /// The factor by which velocities are scaled in a step of duration `dt`, for a cloud at `temperature`.
+    ///
+    /// The factor is one if the temperature is zero, since a cloud at rest cannot be heated by rescaling.
+    pub fn scale_factor(&self, temperature: f64, dt: f64) -> f64 {
+        if temperature <= 0.0 {
+            return 1.0;
+        }
+        let ratio = dt / self.coupling_time;
+        (1.0 + ratio * (self.target_temperature / temperature - 1.0))
+            .max(0.0)
+            .sqrt()
+    }
+}
+
+/// Rescales the velocities of the atoms each step according to the [Thermostat] resource.
+///
+/// The temperature accounts for the [StatisticalWeight] and [SuperAtomWeight] of each atom, and the velocity of the
+/// centre of mass is unchanged. Does nothing if the [Thermostat] resource is not present.
+pub struct ApplyThermostatSystem;
+impl<'a> System<'a> for ApplyThermostatSystem {
+    type SystemData = (
+        WriteStorage<'a, Velocity>,
+        ReadStorage<'a, Mass>,
+        ReadStorage<'a, StatisticalWeight>,
+        ReadStorage<'a, SuperAtomWeight>,
+        ReadStorage<'a, Atom>,
+        ReadExpect<'a, Timestep>,
+        Option<Read<'a, Thermostat>>,
+    );
+
+    fn run(
+        &mut self,
+        (mut velocities, masses, weights, super_atoms, atoms, timestep, thermostat): Self::SystemData,
+    ) {
+        let thermostat = match thermostat {
+            Some(thermostat) => *thermostat,
+            None => return,
+        };
+        let samples: Vec<(Vector3<f64>, f64, f64)> =
+            (&velocities, &masses, weights.maybe(), super_atoms.maybe(), &atoms)
+                .join()
+                .map(|(vel, mass, weight, super_atom, _)| {
+                    (vel.vel.cast::<f64>(), mass.value, total_weight(weight, super_atom))
+                })
+                .collect();
+        if samples.is_empty() {
+            return;
+        }
+        let total_mass: f64 = samples.iter().map(|(_, mass, weight)| mass * weight).sum();
+        let centre_of_mass = samples
+            .iter()
+            .fold(Vector3::new(0.0, 0.0, 0.0), |sum, (vel, mass, weight)| {
+                sum + vel * *mass * *weight
+            })
+            / total_mass;
+        let scale = thermostat.scale_factor(measure_weighted_temperature(&samples), timestep.delta);
+        for (vel, _, _) in (&mut velocities, &masses, &atoms).join() {
+            vel.vel = (centre_of_mass + (vel.vel.cast::<f64>() - centre_of_mass) * scale).cast();
+        }
+    }
+}
+
+/// This plugin applies the [Thermostat] resource, if present, each step.
+///
+/// The velocities are rescaled after the velocity integrator, so the end frame systems must be added to the
+/// [SimulationBuilder](crate::simulation::SimulationBuilder) before this plugin.
+///
+/// See also [crate::thermostat].
+pub struct ThermostatPlugin;
+impl Plugin for ThermostatPlugin {
+    fn build(&self, builder: &mut crate::simulation::SimulationBuilder) {
+        builder.dispatcher_builder.add(
+            ApplyThermostatSystem,
+            "apply_thermostat",
+            &[INTEGRATE_VELOCITY_SYSTEM_NAME],
+        );
+    }
+    fn deps(&self) -> Vec<Box<dyn Plugin>> {
+        Vec::new()
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use crate::atom::{Force, Position};
+    use crate::atom_sources::thermal::maxwell_boltzmann_velocity;
+    use crate::simulation::{Simulation, SimulationBuilder};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn temperature(sim: &Simulation) -> f64 {
+        let velocities = sim.world.read_storage::<Velocity>();
+        let masses = sim.world.read_storage::<Mass>();
+        let samples: Vec<(Vector3<f64>, f64, f64)> = (&velocities, &masses)
+            .join()
+            .map(|(vel, mass)| (vel.vel.cast::<f64>(), mass.value, 1.0))
+            .collect();
+        measure_weighted_temperature(&samples)
+    }
+
+    #[test]
+    fn test_hot_cloud_relaxes_to_target_temperature() {
+        let mut sim_builder = SimulationBuilder::default();
+        sim_builder.add_end_frame_systems();
+        sim_builder.add_plugin(ThermostatPlugin);
+        let mut sim = sim_builder.build();
+        let dt = 1.0e-6;
+        sim.world.insert(Timestep { delta: dt });
+
+        let mut rng = StdRng::seed_from_u64(3);
+        let drift = Vector3::new(0.1, 0.0, 0.0);
+        for _ in 0..1000 {
+            let vel = maxwell_boltzmann_velocity(&mut rng, 1.0e-3, 87.0) + drift;
+            sim.world
+                .create_entity()
+                .with(Position::new())
+                .with(Velocity { vel: vel.cast() })
+                .with(Force::new())
+                .with(Mass { value: 87.0 })
+                .with(Atom)
+                .build();
+        }
+        let initial = temperature(&sim);
+
+        let thermostat = Thermostat {
+            target_temperature: 10.0e-6,
+            coupling_time: 100.0e-6,
+        };
+        sim.world.insert(thermostat);
+        let steps = 100;
+        for _ in 0..steps {
+            sim.step();
+        }
+        let expected = thermostat.target_temperature
+            + (initial - thermostat.target_temperature) * (-(steps as f64) * dt / thermostat.coupling_time).exp();
+        let relative = (temperature(&sim) - expected) / expected;
+        assert!(relative.abs() < 0.01, "relative error {}", relative);
+
+        for _ in 0..2000 {
+            sim.step();
+        }
+        let relative = (temperature(&sim) - thermostat.target_temperature) / thermostat.target_temperature;
+        assert!(relative.abs() < 1e-6, "relative error {}", relative);
+
+        // The drift of the cloud is unchanged.
+        let velocities = sim.world.read_storage::<Velocity>();
+        let mean = velocities
+            .join()
+            .fold(Vector3::new(0.0, 0.0, 0.0), |sum, vel| sum + vel.vel.cast::<f64>())
+            / 1000.0;
+        assert!((mean - drift).norm() < 0.05);
+    }
+
+    #[test]
+    fn test_long_coupling_time_is_weak() {
+        let thermostat = Thermostat {
+            target_temperature: 1.0e-6,
+            coupling_time: 1.0,
+        };
+        let scale = thermostat.scale_factor(1.0e-3, 1.0e-6);
+        assert!(scale < 1.0 && 1.0 - scale < 1e-6);
+        assert_eq!(thermostat.scale_factor(0.0, 1.0e-6), 1.0);
+    }
+}
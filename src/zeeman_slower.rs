@@ -0,0 +1,307 @@
+//! A complete one-dimensional Zeeman slower.
+//!
+//! [make_zeeman_slower] composes an [Oven], the tapered [ZeemanSlowerField], a counter-propagating slowing beam and
+//! a [SlowerDetector] at the exit of the slower. The detector records the axial velocity of each atom leaving the
+//! slower in the [SlowerOutput] resource, and then removes the atom, so that the output velocity distribution can be
+//! compared against the design final velocity. Add the [ZeemanSlowerPlugin] to the simulation to run the detector.
+
+use nalgebra::Vector3;
+use specs::prelude::*;
+
+use crate::atom::{Atom, Position, Velocity};
+use crate::atom_sources::emit::AtomNumberToEmit;
+use crate::atom_sources::mass::{MassDistribution, MassRatio};
+use crate::atom_sources::oven::{Oven, OvenAperture, OvenBuilder};
+use crate::atom_sources::species::AtomCreator;
+use crate::destructor::ToBeDestroyed;
+use crate::integrator::INTEGRATE_POSITION_SYSTEM_NAME;
+use crate::laser::gaussian::GaussianBeam;
+use crate::laser_cooling::transition::AtomicTransition;
+use crate::laser_cooling::CoolingLight;
+use crate::magnetic::slower::ZeemanSlowerField;
+use crate::simulation::Plugin;
+
+/// Configures the Zeeman slower created by [make_zeeman_slower].
+pub struct ZeemanSlowerConfig {
+    /// Position of the entrance of the slower, in SI units of m.
+    pub entrance: Vector3<f64>,
+    /// Axis of the slower, from its entrance to its exit.
+    pub direction: Vector3<f64>,
+    /// Length of the slower, in SI units of m.
+    pub length: f64,
+    /// Velocity of the atoms resonant at the entrance, in SI units of m/s.
+    pub capture_velocity: f64,
+    /// Design velocity of the atoms leaving the slower, in SI units of m/s.
+    pub final_velocity: f64,
+    /// Detuning of the slowing beam from the transition, in MHz.
+    pub detuning: f64,
+    /// Power of the slowing beam, in SI units of W.
+    pub beam_power: f64,
+    /// Radius of the slowing beam at which the intensity is 1/e of the peak value, in SI units of m.
+    pub beam_e_radius: f64,
+    /// Temperature of the oven, in SI units of K.
+    pub oven_temperature: f64,
+    /// Distance of the oven before the entrance of the slower, in SI units of m.
+    pub oven_distance: f64,
+    /// Largest angle from the axis at which atoms leave the oven, in radians.
+    pub oven_max_theta: f64,
+    /// Number of atoms emitted by the oven, on the first step of the simulation.
+    pub atom_number: i32,
+    /// Mass of the atoms, in atomic mass units.
+    pub mass: f64,
+}
+
+/// The entities created by [make_zeeman_slower].
+pub struct ZeemanSlower {
+    /// Entity holding the [ZeemanSlowerField].
+    pub field: Entity,
+    /// Entity holding the slowing beam.
+    pub beam: Entity,
+    /// Entity holding the [Oven]. The oven is deleted once it has emitted its atoms.
+    pub oven: Entity,
+    /// Entity holding the [SlowerDetector].
+    pub detector: Entity,
+}
+
+/// A component that records and removes atoms crossing a plane at the exit of a Zeeman slower.
+///
+/// The plane passes through the entity's [Position], perpendicular to `direction`. See [crate::zeeman_slower].
+#[derive(Clone, Copy)]
+pub struct SlowerDetector {
+    /// A unit vector along the axis of the slower, pointing out of the slower.
+    pub direction: Vector3<f64>,
+}
+impl Component for SlowerDetector {
+    type Storage = HashMapStorage<Self>;
+}
+
+/// A resource holding the axial velocities of the atoms detected by the [SlowerDetector]s, in SI units of m/s.
+#[derive(Default)]
+pub struct SlowerOutput {
+    pub velocities: Vec<f64>,
+}
+
+/// Creates the oven, magnetic field, slowing beam and detector of a Zeeman slower.
+///
+/// The slowing beam propagates against the axis of the slower, and drives the sigma+ transition of `T` relative to
+/// the field. The detector is placed at the exit of the slower.
+///
+/// # Generic Arguments
+///
+/// * `S`: The atom species emitted by the oven.
+///
+/// * `T`: The laser cooling transition of the species.
+pub fn make_zeeman_slower<S, T>(world: &mut World, config: &ZeemanSlowerConfig) -> ZeemanSlower
+where
+    S: AtomCreator + 'static,
+    T: AtomicTransition,
+{
+    let direction = config.direction.normalize();
+    let field = world
+        .create_entity()
+        .with(ZeemanSlowerField::design::<T>(
+            direction,
+            config.length,
+            config.capture_velocity,
+            config.final_velocity,
+            config.detuning,
+        ))
+        .with(Position {
+            pos: config.entrance.cast(),
+        })
+        .build();
+
+    // The beam propagates against the field, so the polarization is reversed to drive the sigma+ transition.
+    let beam = world
+        .create_entity()
+        .with(GaussianBeam {
+            intersection: config.entrance,
+            e_radius: config.beam_e_radius,
+            power: config.beam_power,
+            direction: -direction,
+            rayleigh_range: f64::INFINITY,
+            ellipticity: 0.0,
+            focus_offset: 0.0,
+        })
+        .with(CoolingLight::for_transition::<T>(config.detuning, -1))
+        .build();
+
+    let oven: Oven<S> = OvenBuilder::<S>::new(config.oven_temperature, direction)
+        .with_aperture(OvenAperture::Circular {
+            radius: 0.5e-3,
+            thickness: 1.0e-4,
+        })
+        .with_lip(1.0, config.oven_max_theta.tan())
+        .build();
+    let oven = world
+        .create_entity()
+        .with(oven)
+        .with(Position {
+            pos: (config.entrance - config.oven_distance * direction).cast(),
+        })
+        .with(MassDistribution::new(vec![MassRatio {
+            mass: config.mass,
+            ratio: 1.0,
+        }]))
+        .with(AtomNumberToEmit {
+            number: config.atom_number,
+        })
+        .with(ToBeDestroyed)
+        .build();
+
+    let detector = world
+        .create_entity()
+        .with(SlowerDetector { direction })
+        .with(Position {
+            pos: (config.entrance + config.length * direction).cast(),
+        })
+        .build();
+
+    ZeemanSlower {
+        field,
+        beam,
+        oven,
+        detector,
+    }
+}
+
+/// Records the axial velocity of atoms that have crossed a [SlowerDetector] in the [SlowerOutput], and marks them
+/// for destruction.
+pub struct DetectSlowerOutputSystem;
+impl<'a> System<'a> for DetectSlowerOutputSystem {
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, SlowerDetector>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Velocity>,
+        ReadStorage<'a, Atom>,
+        ReadStorage<'a, ToBeDestroyed>,
+        Write<'a, SlowerOutput>,
+        Read<'a, LazyUpdate>,
+    );
+
+    fn run(
+        &mut self,
+        (entities, detectors, positions, velocities, atoms, destroyed, mut output, updater): Self::SystemData,
+    ) {
+        for (detector, plane) in (&detectors, &positions).join() {
+            let direction = detector.direction.normalize();
+            let plane = plane.pos.cast::<f64>();
+            for (atom, pos, vel, _, _) in
+                (&entities, &positions, &velocities, &atoms, !&destroyed).join()
+            {
+                if (pos.pos.cast::<f64>() - plane).dot(&direction) >= 0.0 {
+                    output.velocities.push(vel.vel.cast::<f64>().dot(&direction));
+                    updater.insert(atom, ToBeDestroyed);
+                }
+            }
+        }
+    }
+}
+
+/// This plugin runs the [SlowerDetector]s of Zeeman slowers created by [make_zeeman_slower].
+///
+/// See also [crate::zeeman_slower].
+pub struct ZeemanSlowerPlugin;
+impl Plugin for ZeemanSlowerPlugin {
+    fn build(&self, builder: &mut crate::simulation::SimulationBuilder) {
+        builder.world.register::<SlowerDetector>();
+        builder.world.insert(SlowerOutput::default());
+        builder.dispatcher_builder.add(
+            DetectSlowerOutputSystem,
+            "detect_slower_output",
+            &[INTEGRATE_POSITION_SYSTEM_NAME],
+        );
+    }
+    fn deps(&self) -> Vec<Box<dyn Plugin>> {
+        Vec::new()
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    use crate::atom_sources::{AtomSourcePlugin, VelocityCap};
+    use crate::integrator::Timestep;
+    use crate::laser::LaserPlugin;
+    use crate::laser_cooling::LaserCoolingPlugin;
+    use crate::simulation::SimulationBuilder;
+    use crate::species::{Rubidium87, Rubidium87_780D2};
+
+    fn mean_and_deviation(values: &[f64]) -> (f64, f64) {
+        let mean = values.iter().sum::<f64>() / values.len() as f64;
+        let variance =
+            values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / values.len() as f64;
+        (mean, variance.sqrt())
+    }
+
+    #[test]
+    fn test_slower_narrows_velocity_distribution_about_final_velocity() {
+        let mut sim_builder = SimulationBuilder::default();
+        sim_builder.add_plugin(LaserPlugin::<4>);
+        sim_builder.add_plugin(LaserCoolingPlugin::<Rubidium87_780D2, 4>::default());
+        sim_builder.add_plugin(AtomSourcePlugin::<Rubidium87>::default());
+        sim_builder.add_plugin(ZeemanSlowerPlugin);
+        let mut sim = sim_builder.build();
+        sim.world.insert(Timestep { delta: 2.0e-6 });
+
+        let config = ZeemanSlowerConfig {
+            entrance: Vector3::new(0.0, 0.0, 0.0),
+            direction: Vector3::x(),
+            length: 0.15,
+            capture_velocity: 120.0,
+            final_velocity: 30.0,
+            detuning: -30.0,
+            beam_power: 0.05,
+            beam_e_radius: 0.01,
+            oven_temperature: 400.0,
+            oven_distance: 0.01,
+            oven_max_theta: 0.01,
+            atom_number: 20_000,
+            mass: 87.0,
+        };
+        make_zeeman_slower::<Rubidium87, Rubidium87_780D2>(&mut sim.world, &config);
+        // Only atoms below the capture velocity are simulated.
+        sim.world.insert(VelocityCap {
+            value: config.capture_velocity,
+        });
+
+        sim.step();
+        sim.step();
+        let initial: Vec<f64> = {
+            let velocities = sim.world.read_storage::<Velocity>();
+            let atoms = sim.world.read_storage::<Atom>();
+            (&velocities, &atoms)
+                .join()
+                .map(|(vel, _)| vel.vel[0] as f64)
+                .collect()
+        };
+        assert!(initial.len() > 50, "{} atoms emitted", initial.len());
+        for _ in 0..3000 {
+            sim.step();
+        }
+
+        let output = sim.world.read_resource::<SlowerOutput>();
+        assert!(
+            output.velocities.len() > initial.len() * 9 / 10,
+            "{} of {} atoms detected",
+            output.velocities.len(),
+            initial.len()
+        );
+        let (initial_mean, initial_deviation) = mean_and_deviation(&initial);
+        let (final_mean, final_deviation) = mean_and_deviation(&output.velocities);
+        assert!(final_mean < initial_mean);
+        assert!(
+            (final_mean - config.final_velocity).abs() < 10.0,
+            "mean output velocity {} m/s",
+            final_mean
+        );
+        assert!(
+            final_deviation < 0.5 * initial_deviation,
+            "output velocity spread {} m/s, input {} m/s",
+            final_deviation,
+            initial_deviation
+        );
+    }
+}